@@ -0,0 +1,55 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use pyxel::{write_rgb24, DisplayEffect, Image, Recti, RgbTables, DEFAULT_COLORS};
+
+const SCREEN_WIDTH: u32 = 256;
+const SCREEN_HEIGHT: u32 = 224;
+
+fn dirty_region(c: &mut Criterion) {
+    let image = Image::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let mut image = image.lock();
+    image.set_dirty_tracking(true);
+    image.take_dirty_rects();
+    let pitch = SCREEN_WIDTH as usize * 3;
+    let mut buffer = vec![0; pitch * SCREEN_HEIGHT as usize];
+    let full_rect = Recti::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+    let rgb_tables = RgbTables::new(&DEFAULT_COLORS, None);
+    let mut color = 0;
+
+    // A 16x16 sprite moving by one pixel each frame, the common case dirty tracking targets
+    c.bench_function("full frame", |b| {
+        b.iter(|| {
+            color = (color + 1) % 16;
+            image.rect(100.0 + color as f64, 100.0, 16.0, 16.0, color);
+            image.take_dirty_rects();
+            write_rgb24(
+                &mut buffer,
+                pitch,
+                image.data(),
+                &rgb_tables,
+                DisplayEffect::None,
+                full_rect,
+                None,
+            );
+        })
+    });
+    c.bench_function("16x16 dirty region", |b| {
+        b.iter(|| {
+            color = (color + 1) % 16;
+            image.rect(100.0 + color as f64, 100.0, 16.0, 16.0, color);
+            for rect in image.take_dirty_rects().unwrap() {
+                write_rgb24(
+                    &mut buffer,
+                    pitch,
+                    image.data(),
+                    &rgb_tables,
+                    DisplayEffect::None,
+                    rect,
+                    None,
+                );
+            }
+        })
+    });
+}
+
+criterion_group!(benches, dirty_region);
+criterion_main!(benches);
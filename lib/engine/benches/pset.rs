@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pyxel::{Color, Image};
+
+const NUM_POINTS: usize = 10000;
+
+fn points() -> Vec<(i32, i32)> {
+    // Spread over and slightly past the image so that clipping is exercised too
+    (0..NUM_POINTS as i32)
+        .map(|i| ((i * 37) % 280 - 12, (i * 53) % 248 - 12))
+        .collect()
+}
+
+fn pset(c: &mut Criterion) {
+    let image = Image::new(256, 224);
+    let points = points();
+    let colored: Vec<(i32, i32, Color)> = points
+        .iter()
+        .enumerate()
+        .map(|(i, &(x, y))| (x, y, (i % 16) as Color))
+        .collect();
+    let mut image = image.lock();
+
+    c.bench_function("pset loop", |b| {
+        b.iter(|| {
+            for &(x, y) in &points {
+                image.pset(x as f64, y as f64, 7);
+            }
+        })
+    });
+    c.bench_function("pset_slice", |b| {
+        b.iter(|| image.pset_slice(black_box(&points), 7))
+    });
+    c.bench_function("pset colored loop", |b| {
+        b.iter(|| {
+            for &(x, y, color) in &colored {
+                image.pset(x as f64, y as f64, color);
+            }
+        })
+    });
+    c.bench_function("pset_colored", |b| {
+        b.iter(|| image.pset_colored(black_box(&colored)))
+    });
+}
+
+criterion_group!(benches, pset);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use pyxel::{write_rgb24, Color, DisplayEffect, Recti, Rgb8, RgbTables, DEFAULT_COLORS};
+
+const SCREEN_WIDTH: u32 = 256;
+const SCREEN_HEIGHT: u32 = 224;
+
+// The conversion loop write_rgb24 replaced, which looks up and shades every pixel
+fn write_rgb24_per_pixel(
+    buffer: &mut [u8],
+    pitch: usize,
+    image: &[Vec<Color>],
+    colors: &[Rgb8],
+    effect: DisplayEffect,
+) {
+    let effect_scale = effect.scale() as usize;
+    for i in 0..image.len() * effect_scale {
+        for j in 0..image[0].len() * effect_scale {
+            let color = colors[image[i / effect_scale][j / effect_scale] as usize];
+            let color = effect.shade(color, (j % effect_scale) as u32, (i % effect_scale) as u32);
+            let offset = i * pitch + j * 3;
+            buffer[offset] = ((color >> 16) & 0xff) as u8;
+            buffer[offset + 1] = ((color >> 8) & 0xff) as u8;
+            buffer[offset + 2] = (color & 0xff) as u8;
+        }
+    }
+}
+
+fn write_rgb24_(c: &mut Criterion) {
+    let image: Vec<Vec<Color>> = (0..SCREEN_HEIGHT)
+        .map(|y| {
+            (0..SCREEN_WIDTH)
+                .map(|x| ((x * 7 + y * 13) % 16) as Color)
+                .collect()
+        })
+        .collect();
+    let rect = Recti::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+    for effect in [
+        DisplayEffect::None,
+        DisplayEffect::Scanlines { darkness: 96 },
+    ] {
+        let scale = effect.scale() as usize;
+        let pitch = SCREEN_WIDTH as usize * scale * 3;
+        let mut buffer = vec![0; pitch * SCREEN_HEIGHT as usize * scale];
+        c.bench_function(&format!("per-pixel loop ({:?})", effect), |b| {
+            b.iter(|| write_rgb24_per_pixel(&mut buffer, pitch, &image, &DEFAULT_COLORS, effect))
+        });
+        c.bench_function(&format!("write_rgb24 ({:?})", effect), |b| {
+            b.iter(|| {
+                let rgb_tables = RgbTables::new(&DEFAULT_COLORS, None);
+                write_rgb24(&mut buffer, pitch, &image, &rgb_tables, effect, rect, None);
+            })
+        });
+    }
+}
+
+criterion_group!(benches, write_rgb24_);
+criterion_main!(benches);
@@ -0,0 +1,68 @@
+use pyxel::{Context, Game, Pyxel, System};
+
+struct Ball {
+    x: f64,
+    y: f64,
+    vx: f64,
+    vy: f64,
+    color: u8,
+}
+
+pub struct App {
+    balls: Vec<Ball>,
+}
+
+impl App {
+    fn add_ball(&mut self, ctx: &mut Context) {
+        let ball = Ball {
+            x: ctx.rndf(8.0, ctx.width() as f64 - 8.0),
+            y: ctx.rndf(8.0, ctx.height() as f64 - 8.0),
+            vx: ctx.rndf(-2.0, 2.0),
+            vy: ctx.rndf(-2.0, 2.0),
+            color: ctx.rndi(1, 15) as u8,
+        };
+        self.balls.push(ball);
+    }
+}
+
+impl Game for App {
+    fn update(&mut self, ctx: &mut Context) {
+        // Runs once per fixed step, so the speed stays the same when drawing falls behind
+        if ctx.btnp(pyxel::KEY_SPACE, None, None) {
+            self.add_ball(ctx);
+        }
+        let width = ctx.width() as f64;
+        let height = ctx.height() as f64;
+        for ball in &mut self.balls {
+            ball.x += ball.vx;
+            ball.y += ball.vy;
+            if ball.x < 4.0 || ball.x > width - 4.0 {
+                ball.vx = -ball.vx;
+            }
+            if ball.y < 4.0 || ball.y > height - 4.0 {
+                ball.vy = -ball.vy;
+            }
+        }
+        if ctx.btnp(pyxel::KEY_Q, None, None) {
+            ctx.quit();
+        }
+    }
+
+    fn draw(&mut self, ctx: &mut Context) {
+        ctx.cls(0);
+        for ball in &self.balls {
+            ctx.circ(ball.x, ball.y, 4.0, ball.color);
+        }
+        ctx.text(4.0, 4.0, "SPACE: ADD BALL  Q: QUIT", 7);
+        ctx.text(4.0, 12.0, &format!("BALLS: {}", self.balls.len()), 7);
+    }
+}
+
+pub fn main() {
+    let mut ctx = Pyxel::new(160, 120, Some("Bouncing Balls"), Some(60), None, None, None);
+    let mut app = App { balls: Vec::new() };
+    for _ in 0..3 {
+        app.add_ball(&mut ctx);
+    }
+    System::run(ctx, app);
+}
@@ -0,0 +1,60 @@
+use pyxel::{Pyxel, PyxelCallback, VirtualKeyboard, VirtualKeyboardEvent, KEY_Q};
+
+const MAX_NAME_LENGTH: usize = 8;
+
+pub struct App {
+    keyboard: VirtualKeyboard,
+    name: String,
+    entered_name: Option<String>,
+}
+
+impl PyxelCallback for App {
+    fn update(&mut self, pyxel: &mut Pyxel) {
+        // Arrow keys or the first gamepad's dpad move, RETURN or A types, BACKSPACE or B erases
+        match self.keyboard.update(pyxel) {
+            Some(VirtualKeyboardEvent::TextInput { text }) => {
+                for c in text.chars() {
+                    if self.name.chars().count() < MAX_NAME_LENGTH {
+                        self.name.push(c);
+                    }
+                }
+            }
+            Some(VirtualKeyboardEvent::Backspace) => {
+                self.name.pop();
+            }
+            Some(VirtualKeyboardEvent::Done) => {
+                self.entered_name = Some(self.name.trim().to_string());
+            }
+            None => {}
+        }
+        if pyxel.btnp(KEY_Q, None, None) {
+            pyxel.quit();
+        }
+    }
+
+    fn draw(&mut self, pyxel: &mut Pyxel) {
+        pyxel.cls(0);
+        pyxel.text(29.0, 8.0, "ENTER YOUR NAME", 7);
+        pyxel.rectb(29.0, 18.0, 4.0 * MAX_NAME_LENGTH as f64 + 4.0, 10.0, 13);
+        pyxel.text(31.0, 20.0, &self.name, 10);
+        if pyxel.frame_count() % 30 < 15 && self.name.chars().count() < MAX_NAME_LENGTH {
+            let x = 31.0 + 4.0 * self.name.chars().count() as f64;
+            pyxel.rect(x, 25.0, 3.0, 1.0, 10);
+        }
+        pyxel.draw_virtual_keyboard(&self.keyboard);
+        if let Some(name) = &self.entered_name {
+            pyxel.text(29.0, 112.0, &format!("HELLO, {}!", name), 11);
+        }
+    }
+}
+
+pub fn main() {
+    let mut pyxel = Pyxel::new(160, 120, Some("Name Entry"), Some(60), None, None, None);
+    let mut app = App {
+        keyboard: VirtualKeyboard::new(29.0, 40.0),
+        name: String::new(),
+        entered_name: None,
+    };
+    app.keyboard.cursor_color = 10;
+    pyxel.run(&mut app);
+}
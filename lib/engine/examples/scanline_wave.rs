@@ -0,0 +1,98 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use pyxel::{
+    Palette, Pyxel, PyxelCallback, ScanlineEdge, ScanlineParams, DEFAULT_COLORS, KEY_Q, KEY_SPACE,
+};
+
+const WATER_LINE: u32 = 80;
+
+pub struct App {
+    time: Rc<Cell<f64>>,
+    is_wavy: bool,
+}
+
+impl PyxelCallback for App {
+    fn update(&mut self, pyxel: &mut Pyxel) {
+        self.time.set(self.time.get() + 1.0);
+        if pyxel.btnp(KEY_SPACE, None, None) {
+            self.is_wavy = !self.is_wavy;
+            if self.is_wavy {
+                pyxel.set_scanline_effect(Some(wave_effect(self.time.clone())));
+            } else {
+                pyxel.set_scanline_effect(None);
+            }
+        }
+        if pyxel.btnp(KEY_Q, None, None) {
+            pyxel.quit();
+        }
+    }
+
+    fn draw(&mut self, pyxel: &mut Pyxel) {
+        pyxel.cls(0);
+        let scroll = self.time.get() % 8.0;
+        pyxel.bltm(-scroll, 0.0, 0, 0.0, 0.0, 176.0, 128.0, None);
+        pyxel.text(4.0, 4.0, "SPACE: TOGGLE WAVE  Q: QUIT", 7);
+    }
+}
+
+fn wave_effect(time: Rc<Cell<f64>>) -> pyxel::ScanlineEffect {
+    // Heat shimmer above the water line, a slower darker ripple below it
+    Box::new(move |row| {
+        let time = time.get();
+        if row < WATER_LINE {
+            ScanlineParams {
+                x_offset: ((row as f64 * 0.3 + time * 0.15).sin() * 2.0).round() as i32,
+                edge: ScanlineEdge::Clamp,
+                palette: None,
+            }
+        } else {
+            ScanlineParams {
+                x_offset: ((row as f64 * 0.5 - time * 0.08).sin() * 5.0).round() as i32,
+                edge: ScanlineEdge::Wrap,
+                palette: Some(0),
+            }
+        }
+    })
+}
+
+pub fn main() {
+    let mut pyxel = Pyxel::new(160, 120, Some("Scanline Wave"), Some(60), None, None, None);
+    pyxel.image(0).lock().set(
+        0,
+        0,
+        &[
+            "99999994", "94444444", "94444444", "44444444", //
+            "99949999", "44449444", "44449444", "44444444", //
+        ],
+    );
+    pyxel.image(0).lock().set(
+        8,
+        0,
+        &[
+            "cccccccc", "c111c111", "cccccccc", "11c111c1", //
+            "cccccccc", "c111c111", "cccccccc", "11c111c1",
+        ],
+    );
+    for y in 0..16 {
+        for x in 0..22 {
+            let tile = if y * 8 < WATER_LINE as i32 {
+                (0, 0)
+            } else {
+                (1, 0)
+            };
+            pyxel.tilemap(0).lock().pset(x as f64, y as f64, tile);
+        }
+    }
+    let mut water = Palette::new(&DEFAULT_COLORS);
+    water.set_display_tint(0x1d2b53, 0.5);
+    pyxel.set_scanline_palettes(&[water]);
+
+    let time = Rc::new(Cell::new(0.0));
+    pyxel.set_scanline_effect(Some(wave_effect(time.clone())));
+    let mut app = App {
+        time,
+        is_wavy: true,
+    };
+    pyxel.run(&mut app);
+}
@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::event::{ControllerAxis, ControllerButton, Event};
+use crate::input::Input;
+use crate::input_record::{CONTROLLER_AXES, CONTROLLER_BUTTONS};
+use crate::key::{
+    key_from_name, key_name, GAMEPAD1_AXIS_LEFTX, GAMEPAD1_BUTTON_A, MOUSE_BUTTON_LEFT,
+};
+use crate::settings::{ACTION_AXIS_THRESHOLD, MAX_AXIS_VALUE, VIRTUAL_GAMEPAD};
+use crate::types::Key;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(Key),
+    Button(ControllerButton),
+    Axis(ControllerAxis),
+    AxisPair(Key, Key),
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Buttons and axes are named like the first gamepad's keys without the gamepad prefix
+        let name = |key: Key| key_name(key).ok_or(fmt::Error);
+        match *self {
+            Self::Key(key) => write!(f, "{}", name(key)?),
+            Self::Button(button) => {
+                let name = name(GAMEPAD1_BUTTON_A + button as Key)?;
+                write!(f, "{}", name.trim_start_matches("GAMEPAD1_"))
+            }
+            Self::Axis(axis) => {
+                let name = name(GAMEPAD1_AXIS_LEFTX + axis as Key)?;
+                write!(f, "{}", name.trim_start_matches("GAMEPAD1_"))
+            }
+            Self::AxisPair(negative_key, positive_key) => {
+                write!(f, "{},{}", name(negative_key)?, name(positive_key)?)
+            }
+        }
+    }
+}
+
+impl Binding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some((negative_name, positive_name)) = name.split_once(',') {
+            return Some(Self::AxisPair(
+                key_from_name(negative_name.trim())?,
+                key_from_name(positive_name.trim())?,
+            ));
+        }
+        let name = name.trim().to_ascii_uppercase();
+        if name.starts_with("BUTTON_") {
+            let key = key_from_name(&format!("GAMEPAD1_{}", name))?;
+            CONTROLLER_BUTTONS
+                .get(key.checked_sub(GAMEPAD1_BUTTON_A)? as usize)
+                .copied()
+                .map(Self::Button)
+        } else if name.starts_with("AXIS_") {
+            let key = key_from_name(&format!("GAMEPAD1_{}", name))?;
+            CONTROLLER_AXES
+                .get(key.checked_sub(GAMEPAD1_AXIS_LEFTX)? as usize)
+                .copied()
+                .map(Self::Axis)
+        } else {
+            key_from_name(&name).map(Self::Key)
+        }
+    }
+
+    fn value(&self, input: &Input, frame_count: u32) -> f32 {
+        let key_value = |key: Key| input.btn(key, frame_count) as i32 as f32;
+        match *self {
+            Self::Key(key) => key_value(key),
+            Self::Button(button) => key_value(GAMEPAD1_BUTTON_A + button as Key),
+            Self::Axis(axis) => input.axis(0, axis),
+            Self::AxisPair(negative_key, positive_key) => {
+                key_value(positive_key) - key_value(negative_key)
+            }
+        }
+    }
+
+    fn keys(&self) -> Vec<Key> {
+        match *self {
+            Self::Key(key) => vec![key],
+            Self::Button(button) => vec![GAMEPAD1_BUTTON_A + button as Key],
+            Self::Axis(_) => Vec::new(),
+            Self::AxisPair(negative_key, positive_key) => vec![negative_key, positive_key],
+        }
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct ActionMap {
+    pub(crate) bindings: BTreeMap<String, Vec<Binding>>,
+    is_capturing: bool,
+    captured_binding: Option<Binding>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.bindings.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.remove(action);
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(String::as_str)
+    }
+
+    pub fn capture_next_binding(&mut self) -> Option<Binding> {
+        // The first call starts listening, and the key, button, or axis that moves next
+        // is returned by a later call
+        if let Some(binding) = self.captured_binding.take() {
+            self.is_capturing = false;
+            return Some(binding);
+        }
+        self.is_capturing = true;
+        None
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.is_capturing = false;
+        self.captured_binding = None;
+    }
+
+    pub(crate) fn capture_event(&mut self, event: &Event) {
+        if !self.is_capturing || self.captured_binding.is_some() {
+            return;
+        }
+        self.captured_binding = match *event {
+            Event::KeyDown { keycode } => Some(Binding::Key(keycode)),
+            Event::MouseButtonDown { button, .. } => {
+                Some(Binding::Key(MOUSE_BUTTON_LEFT + button as Key))
+            }
+            Event::ControllerButtonDown { which, button } if which != VIRTUAL_GAMEPAD => {
+                Some(Binding::Button(button))
+            }
+            Event::ControllerAxisMotion { which, axis, value }
+                if which != VIRTUAL_GAMEPAD
+                    && value.unsigned_abs() as f32 >= ACTION_AXIS_THRESHOLD * MAX_AXIS_VALUE =>
+            {
+                Some(Binding::Axis(axis))
+            }
+            _ => None,
+        };
+    }
+
+    pub(crate) fn is_down(&self, action: &str, input: &Input, frame_count: u32) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.value(input, frame_count).abs() >= ACTION_AXIS_THRESHOLD)
+    }
+
+    pub(crate) fn is_pressed(&self, action: &str, input: &Input, frame_count: u32) -> bool {
+        // Axes have no press edge, so only key and button bindings are checked
+        self.bindings(action).iter().any(|binding| {
+            binding
+                .keys()
+                .iter()
+                .any(|key| input.btnp(*key, None, None, frame_count))
+        })
+    }
+
+    pub(crate) fn is_released(&self, action: &str, input: &Input, frame_count: u32) -> bool {
+        // Letting go of one binding while another still holds the action is not a release
+        !self.is_down(action, input, frame_count)
+            && self.bindings(action).iter().any(|binding| {
+                binding
+                    .keys()
+                    .iter()
+                    .any(|key| input.btnr(*key, frame_count))
+            })
+    }
+
+    pub(crate) fn value(&self, action: &str, input: &Input, frame_count: u32) -> f32 {
+        self.bindings(action)
+            .iter()
+            .map(|binding| binding.value(input, frame_count))
+            .fold(0.0, |value: f32, binding_value| {
+                if binding_value.abs() > value.abs() {
+                    binding_value
+                } else {
+                    value
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::*;
+
+    #[test]
+    fn binding_name() {
+        let bindings = [
+            (Binding::Key(KEY_Z), "Z"),
+            (Binding::Key(GAMEPAD2_BUTTON_B), "GAMEPAD2_BUTTON_B"),
+            (
+                Binding::Button(ControllerButton::DPadLeft),
+                "BUTTON_DPAD_LEFT",
+            ),
+            (
+                Binding::Axis(ControllerAxis::TriggerRight),
+                "AXIS_TRIGGERRIGHT",
+            ),
+            (Binding::AxisPair(KEY_LEFT, KEY_RIGHT), "LEFT,RIGHT"),
+        ];
+        for (binding, name) in bindings {
+            assert_eq!(binding.to_string(), name);
+            assert_eq!(Binding::from_name(name), Some(binding));
+        }
+        assert_eq!(Binding::from_name("key_z"), Some(Binding::Key(KEY_Z)));
+        assert_eq!(Binding::from_name("BUTTON_Q"), None);
+        assert_eq!(Binding::from_name("LEFT,"), None);
+    }
+
+    #[test]
+    fn actions() {
+        let mut input = Input::new();
+        let mut action_map = ActionMap::new();
+        action_map.bind("jump", Binding::Key(KEY_Z));
+        action_map.bind("jump", Binding::Button(ControllerButton::A));
+        action_map.bind("jump", Binding::Key(KEY_Z));
+        action_map.bind("move_x", Binding::AxisPair(KEY_LEFT, KEY_RIGHT));
+        action_map.bind("move_x", Binding::Axis(ControllerAxis::LeftX));
+        assert_eq!(action_map.bindings("jump").len(), 2);
+        assert_eq!(action_map.actions().collect::<Vec<_>>(), ["jump", "move_x"]);
+
+        input.process_input_event(Event::KeyDown { keycode: KEY_Z }, 1);
+        assert!(action_map.is_down("jump", &input, 1));
+        assert!(action_map.is_pressed("jump", &input, 1));
+        input.process_input_event(
+            Event::ControllerButtonDown {
+                which: 0,
+                button: ControllerButton::A,
+            },
+            2,
+        );
+        input.process_input_event(Event::KeyUp { keycode: KEY_Z }, 3);
+        assert!(action_map.is_down("jump", &input, 3));
+        assert!(!action_map.is_released("jump", &input, 3));
+        input.process_input_event(
+            Event::ControllerButtonUp {
+                which: 0,
+                button: ControllerButton::A,
+            },
+            4,
+        );
+        assert!(!action_map.is_down("jump", &input, 4));
+        assert!(action_map.is_released("jump", &input, 4));
+        assert!(!action_map.is_down("fly", &input, 4));
+
+        input.process_input_event(Event::KeyDown { keycode: KEY_LEFT }, 5);
+        assert_eq!(action_map.value("move_x", &input, 5), -1.0);
+        input.process_input_event(
+            Event::ControllerAxisMotion {
+                which: 0,
+                axis: ControllerAxis::LeftX,
+                value: 16384,
+            },
+            5,
+        );
+        assert_eq!(action_map.value("move_x", &input, 5), -1.0);
+        input.process_input_event(Event::KeyUp { keycode: KEY_LEFT }, 6);
+        let value = action_map.value("move_x", &input, 6);
+        assert!(value > 0.0 && value < 1.0);
+        action_map.unbind("move_x");
+        assert_eq!(action_map.value("move_x", &input, 6), 0.0);
+    }
+
+    #[test]
+    fn capture_next_binding() {
+        let mut action_map = ActionMap::new();
+        action_map.capture_event(&Event::KeyDown { keycode: KEY_X });
+        assert_eq!(action_map.capture_next_binding(), None);
+        action_map.capture_event(&Event::ControllerAxisMotion {
+            which: 0,
+            axis: ControllerAxis::LeftY,
+            value: 2000,
+        });
+        assert_eq!(action_map.capture_next_binding(), None);
+        action_map.capture_event(&Event::ControllerAxisMotion {
+            which: 0,
+            axis: ControllerAxis::LeftY,
+            value: -30000,
+        });
+        action_map.capture_event(&Event::KeyDown { keycode: KEY_X });
+        assert_eq!(
+            action_map.capture_next_binding(),
+            Some(Binding::Axis(ControllerAxis::LeftY))
+        );
+        action_map.capture_event(&Event::KeyDown { keycode: KEY_X });
+        assert_eq!(action_map.capture_next_binding(), None);
+
+        action_map.cancel_capture();
+        action_map.capture_event(&Event::KeyDown { keycode: KEY_X });
+        assert_eq!(action_map.capture_next_binding(), None);
+        action_map.capture_event(&Event::ControllerButtonDown {
+            which: VIRTUAL_GAMEPAD,
+            button: ControllerButton::B,
+        });
+        action_map.capture_event(&Event::KeyDown { keycode: KEY_X });
+        assert_eq!(action_map.capture_next_binding(), Some(Binding::Key(KEY_X)));
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use log::warn;
+
+use crate::image::{Image, SharedImage};
+use crate::types::Color;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AnimationFrame {
+    pub u: f64,
+    pub v: f64,
+    pub width: f64,
+    pub height: f64,
+    pub duration: u32,
+}
+
+#[derive(Clone, Debug)]
+struct Clip {
+    frames: Vec<AnimationFrame>,
+    is_looping: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub image: u32,
+    pub colkey: Option<Color>,
+    clips: BTreeMap<String, Clip>,
+    clip: Option<String>,
+    frame_index: usize,
+    frame_count: u32,
+    is_finished: bool,
+}
+
+impl Animation {
+    pub fn new(image: u32) -> Self {
+        Self {
+            image,
+            colkey: None,
+            clips: BTreeMap::new(),
+            clip: None,
+            frame_index: 0,
+            frame_count: 0,
+            is_finished: false,
+        }
+    }
+
+    pub fn add_clip(&mut self, name: &str, frames: &[(f64, f64, f64, f64, u32)], is_looping: bool) {
+        let frames = frames
+            .iter()
+            .map(|&(u, v, width, height, duration)| AnimationFrame {
+                u,
+                v,
+                width,
+                height,
+                duration,
+            })
+            .collect();
+        self.clips
+            .insert(name.to_string(), Clip { frames, is_looping });
+
+        // The first clip plays right away so a single-clip animation needs no play call
+        if self.clip.is_none() {
+            self.play(name);
+        } else if self.clip.as_deref() == Some(name) {
+            self.restart();
+        }
+    }
+
+    pub fn add_grid_clip(
+        &mut self,
+        name: &str,
+        u: f64,
+        v: f64,
+        width: f64,
+        height: f64,
+        num_frames: u32,
+        duration: u32,
+        is_looping: bool,
+    ) {
+        let frames: Vec<(f64, f64, f64, f64, u32)> = (0..num_frames)
+            .map(|i| (u + width * i as f64, v, width, height, duration))
+            .collect();
+        self.add_clip(name, &frames, is_looping);
+    }
+
+    pub fn play(&mut self, name: &str) {
+        if !self.clips.contains_key(name) {
+            warn!("Animation clip '{}' not found", name);
+            return;
+        }
+        if self.clip.as_deref() != Some(name) {
+            self.clip = Some(name.to_string());
+            self.restart();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.frame_index = 0;
+        self.frame_count = 0;
+        self.is_finished = false;
+    }
+
+    pub fn clip(&self) -> Option<&str> {
+        self.clip.as_deref()
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.frame_index
+    }
+
+    pub fn frame(&self) -> Option<&AnimationFrame> {
+        self.current_clip()?.frames.get(self.frame_index)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    pub fn update(&mut self) {
+        if self.is_finished {
+            return;
+        }
+        let (num_frames, duration, is_looping) = match self.current_clip() {
+            Some(clip) if !clip.frames.is_empty() => (
+                clip.frames.len(),
+                clip.frames[self.frame_index].duration.max(1),
+                clip.is_looping,
+            ),
+            _ => return,
+        };
+        self.frame_count += 1;
+        if self.frame_count < duration {
+            return;
+        }
+        self.frame_count = 0;
+        if self.frame_index + 1 < num_frames {
+            self.frame_index += 1;
+        } else if is_looping {
+            self.frame_index = 0;
+        } else {
+            // One-shot clips hold their last frame
+            self.is_finished = true;
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Image, image: SharedImage, x: f64, y: f64, flip_h: bool) {
+        if let Some(frame) = self.frame() {
+            let width = if flip_h { -frame.width } else { frame.width };
+            canvas.blt(
+                x,
+                y,
+                image,
+                frame.u,
+                frame.v,
+                width,
+                frame.height,
+                self.colkey,
+                None,
+                None,
+            );
+        }
+    }
+
+    fn current_clip(&self) -> Option<&Clip> {
+        self.clips.get(self.clip.as_deref()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(animation: &mut Animation, num_frames: u32) -> usize {
+        for _ in 0..num_frames {
+            animation.update();
+        }
+        animation.frame_index()
+    }
+
+    #[test]
+    fn looping() {
+        let mut animation = Animation::new(0);
+        animation.add_clip(
+            "idle",
+            &[(0.0, 0.0, 8.0, 8.0, 2), (8.0, 0.0, 8.0, 8.0, 3)],
+            true,
+        );
+        assert_eq!(animation.clip(), Some("idle"));
+        assert_eq!(step(&mut animation, 1), 0);
+        assert_eq!(step(&mut animation, 1), 1);
+        assert_eq!(step(&mut animation, 2), 1);
+        assert_eq!(step(&mut animation, 1), 0);
+        assert_eq!(step(&mut animation, 10), 0);
+        assert_eq!(step(&mut animation, 2), 1);
+        assert!(!animation.is_finished());
+
+        animation.add_grid_clip("run", 0.0, 8.0, 16.0, 8.0, 3, 1, true);
+        animation.play("run");
+        assert_eq!(animation.frame_index(), 0);
+        assert_eq!(step(&mut animation, 2), 2);
+        assert_eq!(animation.frame().unwrap().u, 32.0);
+        animation.play("run");
+        assert_eq!(animation.frame_index(), 2);
+        assert_eq!(step(&mut animation, 1), 0);
+        animation.play("fly");
+        assert_eq!(animation.clip(), Some("run"));
+    }
+
+    #[test]
+    fn one_shot() {
+        let mut animation = Animation::new(0);
+        animation.add_grid_clip("jump", 0.0, 0.0, 8.0, 8.0, 3, 2, false);
+        assert_eq!(step(&mut animation, 3), 1);
+        assert_eq!(step(&mut animation, 2), 2);
+        assert!(!animation.is_finished());
+        assert_eq!(step(&mut animation, 1), 2);
+        assert!(animation.is_finished());
+        assert_eq!(step(&mut animation, 5), 2);
+        animation.play("jump");
+        assert!(animation.is_finished());
+        animation.restart();
+        assert!(!animation.is_finished());
+        assert_eq!(animation.frame_index(), 0);
+    }
+
+    #[test]
+    fn draw() {
+        let sprite = Image::new(4, 1);
+        sprite.lock().canvas.data[0] = vec![1, 2, 3, 4];
+        let mut animation = Animation::new(0);
+        animation.colkey = Some(2);
+        animation.add_grid_clip("walk", 0.0, 0.0, 2.0, 1.0, 2, 1, true);
+        let canvas = Image::new(2, 1);
+        let mut canvas = canvas.lock();
+        animation.draw(&mut canvas, sprite.clone(), 0.0, 0.0, false);
+        assert_eq!(canvas.canvas.data[0], [1, 0]);
+        animation.update();
+        animation.draw(&mut canvas, sprite, 0.0, 0.0, true);
+        assert_eq!(canvas.canvas.data[0], [4, 3]);
+        drop(canvas);
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs;
+use std::time::SystemTime;
+
+use log::warn;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Image(u32),
+    Tilemap(u32),
+    Resource,
+}
+
+struct WatchedAsset {
+    path: String,
+    kind: AssetKind,
+    modified: Option<SystemTime>,
+    is_pending: bool,
+    last_error: Option<String>,
+}
+
+pub struct AssetWatcher {
+    assets: Vec<WatchedAsset>,
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl AssetWatcher {
+    pub fn new() -> Self {
+        Self { assets: Vec::new() }
+    }
+
+    pub fn watch(&mut self, path: &str, kind: AssetKind) {
+        // The current file is taken as already loaded, so only later changes trigger a reload
+        self.unwatch(path);
+        self.assets.push(WatchedAsset {
+            path: path.to_string(),
+            kind,
+            modified: modified_time(path),
+            is_pending: false,
+            last_error: None,
+        });
+    }
+
+    pub fn unwatch(&mut self, path: &str) {
+        self.assets.retain(|asset| asset.path != path);
+    }
+
+    pub fn changed_assets(&mut self) -> Vec<(String, AssetKind)> {
+        let mut changed_assets = Vec::new();
+        for asset in &mut self.assets {
+            let modified = modified_time(&asset.path);
+            if modified.is_some() && (modified != asset.modified || asset.is_pending) {
+                asset.modified = modified;
+                changed_assets.push((asset.path.clone(), asset.kind));
+            }
+        }
+        changed_assets
+    }
+
+    pub fn finish_reload(&mut self, path: &str, result: Result<(), String>) {
+        // A failed reload is usually a file caught mid-save, so it is retried every frame
+        // and each distinct error is reported once instead of on every attempt
+        if let Some(asset) = self.assets.iter_mut().find(|asset| asset.path == path) {
+            match result {
+                Ok(()) => {
+                    asset.is_pending = false;
+                    asset.last_error = None;
+                }
+                Err(err) => {
+                    if asset.last_error.as_ref() != Some(&err) {
+                        warn!("Unable to reload '{}': {}", path, err);
+                        asset.last_error = Some(err);
+                    }
+                    asset.is_pending = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn changed_assets() {
+        let path = std::env::temp_dir().join("pyxel_asset_watcher.txt");
+        let path = path.to_str().unwrap();
+        let _ = fs::remove_file(path);
+        let mut watcher = AssetWatcher::new();
+        watcher.watch(path, AssetKind::Image(1));
+        assert!(watcher.changed_assets().is_empty());
+
+        fs::write(path, "0").unwrap();
+        assert_eq!(
+            watcher.changed_assets(),
+            vec![(path.to_string(), AssetKind::Image(1))]
+        );
+        assert!(watcher.changed_assets().is_empty());
+
+        watcher.finish_reload(path, Err("truncated".to_string()));
+        assert_eq!(watcher.changed_assets().len(), 1);
+        watcher.finish_reload(path, Ok(()));
+        assert!(watcher.changed_assets().is_empty());
+
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(watcher.changed_assets().len(), 1);
+        watcher.unwatch(path);
+        fs::write(path, "1").unwrap();
+        assert!(watcher.changed_assets().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,67 +1,557 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+
 use array_macro::array;
 use blip_buf::BlipBuf;
 
-use crate::channel::{Channel, SharedChannel};
+use crate::audio_capture::{AudioCapture, CaptureBuffer, CaptureStats};
+use crate::audio_monitor::{AudioMonitor, AudioStats};
+use crate::channel::{Channel, ChannelParams, PlayState};
+use crate::command_queue::{command_queue, CommandReceiver, CommandSender};
 use crate::music::{Music, SharedMusic};
-use crate::platform::{AudioCallback, Platform};
+use crate::platform::{AudioCallback, AudioError, Platform};
+use crate::sample::Sample;
 use crate::settings::{
-    CLOCK_RATE, NUM_CHANNELS, NUM_CLOCKS_PER_TICK, NUM_MUSICS, NUM_SAMPLES, NUM_SOUNDS, SAMPLE_RATE,
+    AUDIO_COMMAND_QUEUE_SIZE, CLOCK_RATE, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE, NUM_CLOCKS_PER_TICK,
+    NUM_MUSICS, NUM_OUTPUT_CHANNELS, NUM_SAMPLES, NUM_SOUNDS, SAMPLE_RATE, SOFT_CLIP_THRESHOLD,
+    VOLUME_RAMP_SAMPLES,
 };
 use crate::sound::{SharedSound, Sound};
 use crate::Pyxel;
 
+struct SequencePlay {
+    channel_no: u32,
+    sounds: Vec<Sound>,
+    loop_range: Option<(u32, u32)>,
+}
+
+// Everything the game thread asks of the synth, applied by the callback between
+// buffers. Sound data is copied out beforehand so the callback never waits on it.
+// The callback owns the channels, so commands are the only way to change them.
+enum AudioCommand {
+    Play {
+        plays: Vec<SequencePlay>,
+        start_tick: Option<u32>,
+        should_loop: bool,
+    },
+    PlaySample {
+        channel_no: u32,
+        data: Arc<[f32]>,
+        should_loop: bool,
+    },
+    Stop {
+        channel_no: Option<u32>,
+    },
+    SetVolume {
+        channel_no: u32,
+        volume: f64,
+    },
+    SetPan {
+        channel_no: u32,
+        pan: f64,
+    },
+    SetGain {
+        channel_no: u32,
+        gain: u8,
+    },
+    SetSpeed {
+        channel_no: u32,
+        speed: f64,
+    },
+    SetPitch {
+        channel_no: u32,
+        pitch: f64,
+    },
+    // New channels are built on the game thread along with their play states
+    SetNumChannels {
+        num_channels: u32,
+        new_channels: Vec<Channel>,
+    },
+    SetCapture {
+        capture: Option<Arc<CaptureBuffer>>,
+    },
+}
+
+// The output format the device settled on, published for the game thread
+struct DeviceFormat {
+    sample_rate: AtomicU32,
+    num_channels: AtomicU32,
+}
+
 struct AudioCore {
-    blip_buf: BlipBuf,
-    channels: [SharedChannel; NUM_CHANNELS as usize],
+    sample_rate: u32,
+    blip_bufs: Vec<BlipBuf>,
+    buffer: Vec<i16>,
+    mix_buffer: Vec<f32>,
+    num_channels: u32,
+    channels: Vec<Channel>,
+    master_volume: Arc<AtomicU64>,
+    master_gain: f64,
+    capture: Option<Arc<CaptureBuffer>>,
+    commands: CommandReceiver<AudioCommand>,
+    format: Arc<DeviceFormat>,
 }
 
+type SharedAudioCore = shared_type!(AudioCore);
+
 pub struct Audio {
-    channels: [SharedChannel; NUM_CHANNELS as usize],
+    core: SharedAudioCore,
+    commands: CommandSender<AudioCommand>,
+    pending_commands: VecDeque<AudioCommand>,
+    format: Arc<DeviceFormat>,
+    master_volume: Arc<AtomicU64>,
+    pub(crate) is_paused: bool,
+    channels: Vec<ChannelParams>,
+    play_states: Vec<Arc<PlayState>>,
+    capture: Option<AudioCapture>,
+    monitor: Arc<AudioMonitor>,
     sounds: [SharedSound; NUM_SOUNDS as usize],
     musics: [SharedMusic; NUM_MUSICS as usize],
 }
 
+pub fn soft_clip(value: f32) -> f32 {
+    // Linear up to the threshold, then a tanh knee that never reaches full scale
+    let magnitude = value.abs();
+    if magnitude <= SOFT_CLIP_THRESHOLD {
+        return value;
+    }
+    let headroom = 1.0 - SOFT_CLIP_THRESHOLD;
+    let clipped =
+        SOFT_CLIP_THRESHOLD + headroom * ((magnitude - SOFT_CLIP_THRESHOLD) / headroom).tanh();
+    clipped.copysign(value)
+}
+
 impl Audio {
-    pub fn new<T: Platform>(platform: &mut T) -> Self {
-        let mut blip_buf = BlipBuf::new(NUM_SAMPLES);
-        blip_buf.set_rates(CLOCK_RATE as f64, SAMPLE_RATE as f64);
-        let channels = array![_ => Channel::new(); NUM_CHANNELS as usize];
+    pub fn new<T: Platform>(platform: &mut T, num_channels: u32) -> Self {
+        let audio = Self::without_device(num_channels, platform.audio_monitor());
+        // Audio is optional, so keep running silently without a device
+        platform
+            .start_audio(
+                SAMPLE_RATE,
+                NUM_OUTPUT_CHANNELS,
+                NUM_SAMPLES,
+                None,
+                audio.core.clone(),
+            )
+            .ok();
+        audio
+    }
+
+    fn without_device(num_channels: u32, monitor: Arc<AudioMonitor>) -> Self {
+        let channels: Vec<Channel> = (0..num_channels).map(|_| Channel::new()).collect();
         let sounds = array![_ => Sound::new(); NUM_SOUNDS as usize];
         let musics = array![_ => Music::new(); NUM_MUSICS as usize];
-
-        platform.start_audio(
-            SAMPLE_RATE,
-            NUM_SAMPLES,
-            new_shared_type!(AudioCore {
-                blip_buf,
-                channels: channels.clone(),
-            }),
-        );
+        let play_states = channels.iter().map(Channel::play_state).collect();
+        let master_volume = Arc::new(AtomicU64::new(1.0f64.to_bits()));
+        let (core, commands) = AudioCore::new(channels, master_volume.clone(), SAMPLE_RATE);
+        let format = core.format.clone();
 
         Self {
-            channels,
+            core: new_shared_type!(core),
+            commands,
+            pending_commands: VecDeque::new(),
+            format,
+            master_volume,
+            is_paused: false,
+            channels: vec![ChannelParams::default(); num_channels as usize],
+            play_states,
+            capture: None,
+            monitor,
             sounds,
             musics,
         }
     }
+
+    pub fn num_channels(&self) -> u32 {
+        self.channels.len() as u32
+    }
+
+    pub fn set_num_channels(&mut self, num_channels: u32) {
+        // Queued commands keep their order, so the ones sent before still
+        // reach the old channels and the ones sent after reach the new ones
+        let new_channels: Vec<Channel> = (self.channels.len() as u32..num_channels)
+            .map(|_| Channel::new())
+            .collect();
+        self.play_states.truncate(num_channels as usize);
+        self.play_states
+            .extend(new_channels.iter().map(Channel::play_state));
+        self.channels
+            .resize(num_channels as usize, ChannelParams::default());
+        self.send_command(AudioCommand::SetNumChannels {
+            num_channels,
+            new_channels,
+        });
+    }
+
+    fn channel(&mut self, channel_no: u32) -> Result<&mut ChannelParams, AudioError> {
+        self.channels
+            .get_mut(channel_no as usize)
+            .ok_or(AudioError::InvalidChannel(channel_no))
+    }
+
+    pub fn channel_params(&self, channel_no: u32) -> Result<ChannelParams, AudioError> {
+        self.channels
+            .get(channel_no as usize)
+            .copied()
+            .ok_or(AudioError::InvalidChannel(channel_no))
+    }
+
+    fn send_command(&mut self, command: AudioCommand) {
+        self.pending_commands.push_back(command);
+        self.flush_commands();
+    }
+
+    pub fn flush_commands(&mut self) {
+        // Commands that don't fit wait here for the next frame instead of
+        // blocking the game or being dropped
+        loop {
+            while let Some(command) = self.pending_commands.pop_front() {
+                if let Err(command) = self.commands.push(command) {
+                    self.pending_commands.push_front(command);
+                    break;
+                }
+            }
+            // Without a device nothing else holds the core, so drain the queue here
+            match Arc::get_mut(&mut self.core) {
+                Some(core) => core.get_mut().apply_commands(),
+                None => return,
+            }
+            if self.pending_commands.is_empty() {
+                return;
+            }
+        }
+    }
+
+    pub fn master_volume(&self) -> f64 {
+        f64::from_bits(self.master_volume.load(Ordering::Relaxed))
+    }
+
+    pub fn set_master_volume(&self, volume: f64) {
+        // Stored as bits so the audio callback can read it without locking
+        self.master_volume
+            .store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    // Positions follow the samples handed to the device, which still has its
+    // own output latency on top before they are actually heard
+    pub fn play_pos(&self, channel_no: u32) -> Result<Option<(u32, u32)>, AudioError> {
+        Ok(self.play_state(channel_no)?.play_pos())
+    }
+
+    pub fn elapsed_samples(&self, channel_no: u32) -> Result<u64, AudioError> {
+        Ok(self.play_state(channel_no)?.elapsed_samples())
+    }
+
+    fn play_state(&self, channel_no: u32) -> Result<&PlayState, AudioError> {
+        self.play_states
+            .get(channel_no as usize)
+            .map(AsRef::as_ref)
+            .ok_or(AudioError::InvalidChannel(channel_no))
+    }
+
+    pub fn stats(&self) -> AudioStats {
+        self.monitor.take_stats()
+    }
+
+    pub fn start_capture(&mut self, path: &Path) -> Result<(), AudioError> {
+        if self.capture.is_some() {
+            self.stop_capture()?;
+        }
+        let capture = AudioCapture::new(
+            path,
+            self.format.sample_rate.load(Ordering::Relaxed),
+            self.format.num_channels.load(Ordering::Relaxed),
+        )?;
+        self.send_command(AudioCommand::SetCapture {
+            capture: Some(capture.buffer()),
+        });
+        self.capture = Some(capture);
+        Ok(())
+    }
+
+    pub fn capture_path(&self) -> Option<&Path> {
+        self.capture.as_ref().map(|capture| capture.path())
+    }
+
+    pub fn update_capture(&mut self) {
+        // File writes stay on the game thread, the callback only fills the buffer
+        if let Some(capture) = &mut self.capture {
+            capture.drain();
+        }
+    }
+
+    pub fn stop_capture(&mut self) -> Result<CaptureStats, AudioError> {
+        let capture = self.capture.take().ok_or(AudioError::NotCapturing)?;
+        self.send_command(AudioCommand::SetCapture { capture: None });
+        capture.finish()
+    }
+}
+
+impl AudioCore {
+    fn new(
+        channels: Vec<Channel>,
+        master_volume: Arc<AtomicU64>,
+        sample_rate: u32,
+    ) -> (Self, CommandSender<AudioCommand>) {
+        let (sender, commands) = command_queue(AUDIO_COMMAND_QUEUE_SIZE as usize);
+        let num_samples = sample_rate * NUM_SAMPLES / SAMPLE_RATE;
+        let master_gain = f64::from_bits(master_volume.load(Ordering::Relaxed));
+        let mut core = Self {
+            sample_rate,
+            blip_bufs: Vec::new(),
+            buffer: vec![0; num_samples as usize],
+            mix_buffer: vec![0.0; num_samples as usize],
+            num_channels: 1,
+            channels,
+            master_volume,
+            master_gain,
+            capture: None,
+            commands,
+            format: Arc::new(DeviceFormat {
+                sample_rate: AtomicU32::new(sample_rate),
+                num_channels: AtomicU32::new(1),
+            }),
+        };
+        core.reset_blip_bufs();
+        (core, sender)
+    }
+
+    fn apply_commands(&mut self) {
+        while let Some(command) = self.commands.pop() {
+            self.apply_command(command);
+        }
+    }
+
+    fn apply_command(&mut self, command: AudioCommand) {
+        match command {
+            AudioCommand::Play {
+                plays,
+                start_tick,
+                should_loop,
+            } => {
+                // One command per music keeps every channel starting on the same tick
+                for play in plays {
+                    if let Some(channel) = self.channels.get_mut(play.channel_no as usize) {
+                        channel.play_sounds(play.sounds, start_tick, should_loop);
+                        if should_loop {
+                            channel.set_loop_range(play.loop_range);
+                        }
+                    }
+                }
+            }
+            AudioCommand::PlaySample {
+                channel_no,
+                data,
+                should_loop,
+            } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.play_sample_data(data, should_loop);
+                }
+            }
+            AudioCommand::Stop {
+                channel_no: Some(channel_no),
+            } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.stop();
+                }
+            }
+            AudioCommand::Stop { channel_no: None } => {
+                for channel in &mut self.channels {
+                    channel.stop();
+                }
+            }
+            AudioCommand::SetVolume { channel_no, volume } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.set_volume(volume);
+                }
+            }
+            AudioCommand::SetPan { channel_no, pan } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.set_pan(pan);
+                }
+            }
+            AudioCommand::SetGain { channel_no, gain } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.set_gain(gain);
+                }
+            }
+            AudioCommand::SetSpeed { channel_no, speed } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.set_speed(speed);
+                }
+            }
+            AudioCommand::SetPitch { channel_no, pitch } => {
+                if let Some(channel) = self.channels.get_mut(channel_no as usize) {
+                    channel.set_pitch(pitch);
+                }
+            }
+            AudioCommand::SetNumChannels {
+                num_channels,
+                new_channels,
+            } => {
+                self.channels.truncate(num_channels as usize);
+                self.channels.extend(new_channels);
+                self.reset_blip_bufs();
+            }
+            AudioCommand::SetCapture { capture } => {
+                self.capture = capture;
+            }
+        }
+    }
+
+    fn reset_blip_bufs(&mut self) {
+        // Each channel renders into its own buffer so it can be panned, and
+        // all of them restart together to stay aligned on the same clock
+        let num_samples = self.buffer.len() as u32;
+        self.blip_bufs = self
+            .channels
+            .iter()
+            .map(|_| {
+                let mut blip_buf = BlipBuf::new(num_samples);
+                blip_buf.set_rates(CLOCK_RATE as f64, self.sample_rate as f64);
+                blip_buf
+            })
+            .collect();
+    }
+
+    fn render_tick(&mut self) {
+        for (channel, blip_buf) in self.channels.iter_mut().zip(&mut self.blip_bufs) {
+            channel.update(blip_buf);
+            blip_buf.end_frame(NUM_CLOCKS_PER_TICK);
+        }
+    }
+
+    fn read_frames(&mut self, start: usize, num_frames: usize) -> usize {
+        let num_channels = self.num_channels as usize;
+        let frames = self.blip_bufs.first().map_or(num_frames, |blip_buf| {
+            (blip_buf.samples_avail() as usize).min(num_frames)
+        });
+        let out = &mut self.mix_buffer[start * num_channels..(start + frames) * num_channels];
+        for (channel, blip_buf) in self.channels.iter_mut().zip(&mut self.blip_bufs) {
+            let samples = &mut self.buffer[..frames];
+            blip_buf.read_samples(samples, false);
+            let gains = channel.output_gains(num_channels);
+            for (frame, sample) in out.chunks_exact_mut(num_channels).zip(samples.iter()) {
+                for (value, gain) in frame.iter_mut().zip(&gains) {
+                    *value += *sample as f32 * gain;
+                }
+            }
+            channel.advance_play_state(frames);
+        }
+        frames
+    }
+
+    fn mix(&mut self, num_frames: usize) {
+        let num_channels = self.num_channels as usize;
+        let len = num_frames * num_channels;
+        if self.mix_buffer.len() < len {
+            self.mix_buffer.resize(len, 0.0);
+        }
+        self.mix_buffer[..len].fill(0.0);
+        let mut frames = self.read_frames(0, num_frames);
+        while frames < num_frames {
+            self.render_tick();
+            frames += self.read_frames(frames, num_frames - frames);
+        }
+        for channel in &mut self.channels {
+            channel.mix_sample(&mut self.mix_buffer[..len], num_channels, self.sample_rate);
+        }
+    }
+
+    fn output(&mut self, out: &mut [i16]) {
+        let master_volume = f64::from_bits(self.master_volume.load(Ordering::Relaxed));
+        let gain_step = SAMPLE_RATE as f64 / (VOLUME_RAMP_SAMPLES as f64 * self.sample_rate as f64);
+        let num_channels = self.num_channels as usize;
+        for (frame, mixed) in out
+            .chunks_exact_mut(num_channels)
+            .zip(self.mix_buffer.chunks_exact(num_channels))
+        {
+            self.master_gain += (master_volume - self.master_gain).clamp(-gain_step, gain_step);
+            for (sample, value) in frame.iter_mut().zip(mixed) {
+                let value = *value * self.master_gain as f32 / i16::MAX as f32;
+                *sample = (soft_clip(value) * i16::MAX as f32) as i16;
+            }
+        }
+    }
+}
+
+pub fn render_sounds(
+    sequences: Vec<Vec<SharedSound>>,
+    sample_rate: u32,
+    should_loop: bool,
+    num_ticks: u32,
+) -> Result<Vec<i16>, AudioError> {
+    if !(MIN_SAMPLE_RATE..=MAX_SAMPLE_RATE).contains(&sample_rate) {
+        return Err(AudioError::InvalidSampleRate(sample_rate));
+    }
+    let mut channels: Vec<Channel> = (0..sequences.len().max(1))
+        .map(|_| Channel::new())
+        .collect();
+    for (channel, sounds) in channels.iter_mut().zip(sequences) {
+        channel.play(sounds, None, should_loop);
+    }
+    let (mut core, _) = AudioCore::new(
+        channels,
+        Arc::new(AtomicU64::new(1.0f64.to_bits())),
+        sample_rate,
+    );
+    let mut output = Vec::new();
+    let mut buffer = vec![0; core.buffer.len()];
+    // One extra tick after stopping lets the channels ramp out to silence
+    for tick in 0..=num_ticks {
+        if tick == num_ticks {
+            for channel in &mut core.channels {
+                channel.stop();
+            }
+        }
+        core.render_tick();
+        let num_frames = core.blip_bufs[0].samples_avail() as usize;
+        core.mix(num_frames);
+        core.output(&mut buffer[..num_frames]);
+        output.extend_from_slice(&buffer[..num_frames]);
+    }
+    Ok(output)
 }
 
 impl AudioCallback for AudioCore {
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        // Rebuilding the buffers at the device rate keeps every pitch exact
+        self.sample_rate = sample_rate;
+        self.format
+            .sample_rate
+            .store(sample_rate, Ordering::Relaxed);
+        self.buffer = vec![0; (sample_rate * NUM_SAMPLES / SAMPLE_RATE) as usize];
+        self.reset_blip_bufs();
+        self.mix_buffer = vec![0.0; self.buffer.len() * self.num_channels as usize];
+    }
+
+    fn set_num_channels(&mut self, num_channels: u32) {
+        self.num_channels = num_channels.max(1);
+        self.format
+            .num_channels
+            .store(self.num_channels, Ordering::Relaxed);
+        self.mix_buffer = vec![0.0; self.buffer.len() * self.num_channels as usize];
+    }
+
     fn update(&mut self, out: &mut [i16]) {
-        let mut samples = self.blip_buf.read_samples(out, false);
-        while samples < out.len() {
-            for channel in &mut self.channels {
-                channel.lock().update(&mut self.blip_buf);
-            }
-            self.blip_buf.end_frame(NUM_CLOCKS_PER_TICK);
-            samples += self.blip_buf.read_samples(&mut out[samples..], false);
+        self.apply_commands();
+        self.mix(out.len() / self.num_channels as usize);
+        self.output(out);
+        if let Some(capture) = &self.capture {
+            capture.push_frames(out, self.num_channels);
         }
     }
 }
 
 impl Pyxel {
-    pub fn channel(&self, channel_no: u32) -> SharedChannel {
-        self.audio.channels[channel_no as usize].clone()
+    pub fn num_channels(&self) -> u32 {
+        self.audio.num_channels()
+    }
+
+    pub fn set_num_channels(&mut self, num_channels: u32) {
+        self.audio.set_num_channels(num_channels);
     }
 
     pub fn sound(&self, sound_no: u32) -> SharedSound {
@@ -72,8 +562,19 @@ impl Pyxel {
         self.audio.musics[music_no as usize].clone()
     }
 
-    pub fn play_pos(&mut self, channel_no: u32) -> Option<(u32, u32)> {
-        self.audio.channels[channel_no as usize].lock().play_pos()
+    pub fn play_pos(&self, channel_no: u32) -> Result<Option<(u32, u32)>, AudioError> {
+        self.audio.play_pos(channel_no)
+    }
+
+    pub fn elapsed_samples(&self, channel_no: u32) -> Result<u64, AudioError> {
+        self.audio.elapsed_samples(channel_no)
+    }
+
+    fn sequence_sounds(&self, sequence: &[u32]) -> Vec<Sound> {
+        sequence
+            .iter()
+            .map(|sound_no| self.audio.sounds[*sound_no as usize].lock().clone())
+            .collect()
     }
 
     pub fn play(
@@ -82,17 +583,32 @@ impl Pyxel {
         sequence: &[u32],
         start_tick: Option<u32>,
         should_loop: bool,
-    ) {
-        if sequence.is_empty() {
-            return;
+    ) -> Result<(), AudioError> {
+        let sounds = self.sequence_sounds(sequence);
+        self.play_sounds(channel_no, sounds, start_tick, should_loop)
+    }
+
+    pub fn play_sounds(
+        &mut self,
+        channel_no: u32,
+        sounds: Vec<Sound>,
+        start_tick: Option<u32>,
+        should_loop: bool,
+    ) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?;
+        if sounds.is_empty() {
+            return Ok(());
         }
-        let sounds = sequence
-            .iter()
-            .map(|sound_no| self.audio.sounds[*sound_no as usize].clone())
-            .collect();
-        self.audio.channels[channel_no as usize]
-            .lock()
-            .play(sounds, start_tick, should_loop);
+        self.audio.send_command(AudioCommand::Play {
+            plays: vec![SequencePlay {
+                channel_no,
+                sounds,
+                loop_range: None,
+            }],
+            start_tick,
+            should_loop,
+        });
+        Ok(())
     }
 
     pub fn play1(
@@ -101,33 +617,769 @@ impl Pyxel {
         sound_no: u32,
         start_tick: Option<u32>,
         should_loop: bool,
-    ) {
-        self.audio.channels[channel_no as usize].lock().play1(
-            self.audio.sounds[sound_no as usize].clone(),
+    ) -> Result<(), AudioError> {
+        self.play(channel_no, &[sound_no], start_tick, should_loop)
+    }
+
+    pub fn play_sample(
+        &mut self,
+        channel_no: u32,
+        sample: &Sample,
+        should_loop: bool,
+    ) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?;
+        self.audio.send_command(AudioCommand::PlaySample {
+            channel_no,
+            data: sample.data.clone(),
+            should_loop,
+        });
+        Ok(())
+    }
+
+    pub fn playm(
+        &mut self,
+        music_no: u32,
+        start_tick: Option<u32>,
+        should_loop: bool,
+    ) -> Result<(), AudioError> {
+        let music = self.audio.musics[music_no as usize].lock().clone();
+        if let Some(channel_no) = (0..music.sequences.len() as u32)
+            .find(|&i| !music.sequences[i as usize].is_empty() && i >= self.num_channels())
+        {
+            return Err(AudioError::InvalidChannel(channel_no));
+        }
+        let plays = music
+            .sequences
+            .iter()
+            .enumerate()
+            .filter(|(_, sequence)| !sequence.is_empty())
+            .map(|(i, sequence)| SequencePlay {
+                channel_no: i as u32,
+                sounds: self.sequence_sounds(sequence),
+                loop_range: music.loop_ranges[i],
+            })
+            .collect();
+        self.audio.send_command(AudioCommand::Play {
+            plays,
             start_tick,
             should_loop,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&mut self, channel_no: u32) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?;
+        self.audio.send_command(AudioCommand::Stop {
+            channel_no: Some(channel_no),
+        });
+        Ok(())
+    }
+
+    pub fn channel_volume(&self, channel_no: u32) -> Result<f64, AudioError> {
+        Ok(self.audio.channel_params(channel_no)?.volume)
+    }
+
+    pub fn set_channel_volume(&mut self, channel_no: u32, volume: f64) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?.set_volume(volume);
+        self.audio
+            .send_command(AudioCommand::SetVolume { channel_no, volume });
+        Ok(())
+    }
+
+    pub fn channel_pan(&self, channel_no: u32) -> Result<f64, AudioError> {
+        Ok(self.audio.channel_params(channel_no)?.pan)
+    }
+
+    pub fn set_channel_pan(&mut self, channel_no: u32, pan: f64) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?.set_pan(pan);
+        self.audio
+            .send_command(AudioCommand::SetPan { channel_no, pan });
+        Ok(())
+    }
+
+    pub fn channel_gain(&self, channel_no: u32) -> Result<u8, AudioError> {
+        Ok(self.audio.channel_params(channel_no)?.gain)
+    }
+
+    pub fn set_channel_gain(&mut self, channel_no: u32, gain: u8) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?.gain = gain;
+        self.audio
+            .send_command(AudioCommand::SetGain { channel_no, gain });
+        Ok(())
+    }
+
+    pub fn channel_speed(&self, channel_no: u32) -> Result<f64, AudioError> {
+        Ok(self.audio.channel_params(channel_no)?.speed)
+    }
+
+    pub fn set_channel_speed(&mut self, channel_no: u32, speed: f64) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?.set_speed(speed);
+        self.audio
+            .send_command(AudioCommand::SetSpeed { channel_no, speed });
+        Ok(())
+    }
+
+    pub fn channel_pitch(&self, channel_no: u32) -> Result<f64, AudioError> {
+        Ok(self.audio.channel_params(channel_no)?.pitch)
+    }
+
+    pub fn set_channel_pitch(&mut self, channel_no: u32, pitch: f64) -> Result<(), AudioError> {
+        self.audio.channel(channel_no)?.set_pitch(pitch);
+        self.audio
+            .send_command(AudioCommand::SetPitch { channel_no, pitch });
+        Ok(())
+    }
+
+    pub fn master_volume(&self) -> f64 {
+        self.audio.master_volume()
+    }
+
+    pub fn set_master_volume(&mut self, volume: f64) {
+        self.audio.set_master_volume(volume);
+    }
+
+    pub fn audio_stats(&self) -> AudioStats {
+        self.audio.stats()
+    }
+
+    pub fn audio_devices(&self) -> Vec<String> {
+        self.platform.audio_devices()
+    }
+
+    pub fn set_audio_device(&mut self, device_name: Option<&str>) -> Result<(), AudioError> {
+        self.platform.reopen_audio(device_name)
+    }
+
+    pub fn pause_audio(&mut self) {
+        self.audio.is_paused = true;
+        self.platform.pause_audio();
+    }
+
+    pub fn resume_audio(&mut self) {
+        self.audio.is_paused = false;
+        self.platform.resume_audio();
+    }
+
+    pub fn is_audio_paused(&self) -> bool {
+        self.platform.is_audio_paused()
+    }
+
+    pub fn stop0(&mut self) {
+        self.audio
+            .send_command(AudioCommand::Stop { channel_no: None });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::settings::NUM_CHANNELS;
+
+    fn new_channels() -> Vec<Channel> {
+        (0..NUM_CHANNELS).map(|_| Channel::new()).collect()
+    }
+
+    fn new_core(channels: Vec<Channel>) -> AudioCore {
+        let master_volume = Arc::new(AtomicU64::new(1.0f64.to_bits()));
+        AudioCore::new(channels, master_volume, SAMPLE_RATE).0
+    }
+
+    fn period<T: PartialEq>(values: &[T]) -> usize {
+        (1..values.len() / 2)
+            .find(|&p| (0..values.len() - p).all(|i| values[i] == values[i + p]))
+            .unwrap()
+    }
+
+    #[test]
+    fn music_loop() {
+        let sound0 = Sound::new();
+        sound0.lock().set("c2e2g2", "s", "7", "n", 2);
+        let sound1 = Sound::new();
+        sound1.lock().set("c3r", "s", "7", "n", 3);
+        let sound2 = Sound::new();
+        sound2.lock().set("g2", "s", "7", "n", 4);
+        let mut channels = new_channels();
+        channels[0].play(vec![sound0], None, true);
+        channels[1].play(vec![sound1, sound2], None, true);
+        let mut core = new_core(channels);
+
+        // Channel 0 loops every 6 ticks and channel 1 every 10, so both
+        // return to their first note together every 30 ticks
+        let samples_per_tick = SAMPLE_RATE as f64 / (CLOCK_RATE / NUM_CLOCKS_PER_TICK) as f64;
+        let mut out = vec![0; NUM_SAMPLES as usize];
+        let mut num_samples = 0;
+        let mut positions = Vec::new();
+        for tick in 1..=90 {
+            let target = ((tick as f64 - 0.5) * samples_per_tick) as usize;
+            core.update(&mut out[..target - num_samples]);
+            num_samples = target;
+            positions.push((
+                core.channels[0].play_pos().unwrap(),
+                core.channels[1].play_pos().unwrap(),
+            ));
+        }
+        let channel0: Vec<(u32, u32)> = positions.iter().map(|pos| pos.0).collect();
+        let channel1: Vec<(u32, u32)> = positions.iter().map(|pos| pos.1).collect();
+        assert_eq!(period(&channel0), 6);
+        assert_eq!(period(&channel1), 10);
+        assert_eq!(period(&positions), 30);
+        assert_eq!(
+            channel1[..10],
+            [
+                (0, 0),
+                (0, 0),
+                (0, 0),
+                (0, 1),
+                (0, 1),
+                (0, 1),
+                (1, 0),
+                (1, 0),
+                (1, 0),
+                (1, 0)
+            ]
         );
     }
 
-    pub fn playm(&mut self, music_no: u32, start_tick: Option<u32>, should_loop: bool) {
-        let music = self.audio.musics[music_no as usize].clone();
-        for i in 0..NUM_CHANNELS {
-            self.play(
-                i,
-                &music.lock().sequences[i as usize],
-                start_tick,
-                should_loop,
+    #[test]
+    fn music_loop_range() {
+        let sounds: Vec<SharedSound> = ["c2e2", "g2", "c3", "e3"]
+            .iter()
+            .map(|notes| {
+                let sound = Sound::new();
+                sound.lock().set(notes, "s", "7", "n", 1);
+                sound
+            })
+            .collect();
+        let mut channels = new_channels();
+        channels[0].play(sounds.clone(), None, true);
+        channels[0].set_loop_range(Some((1, 2)));
+        let mut core = new_core(channels);
+
+        let samples_per_tick = SAMPLE_RATE as f64 / (CLOCK_RATE / NUM_CLOCKS_PER_TICK) as f64;
+        let mut out = vec![0; NUM_SAMPLES as usize];
+        let mut num_ticks = 0;
+        let mut num_samples = 0;
+        let mut record = |core: &mut AudioCore, ticks: u32| {
+            let mut positions = Vec::new();
+            for _ in 0..ticks {
+                num_ticks += 1;
+                let target = ((num_ticks as f64 - 0.5) * samples_per_tick) as usize;
+                core.update(&mut out[..target - num_samples]);
+                num_samples = target;
+                positions.push(core.channels[0].play_pos().unwrap());
+            }
+            positions
+        };
+        assert_eq!(
+            record(&mut core, 8),
+            [
+                (0, 0),
+                (0, 1),
+                (1, 0),
+                (2, 0),
+                (1, 0),
+                (2, 0),
+                (1, 0),
+                (2, 0)
+            ]
+        );
+
+        // Restarting goes back to the intro and a range past the end stops at the last sound
+        core.channels[0].play(sounds.clone(), None, true);
+        assert_eq!(core.channels[0].play_pos(), Some((0, 0)));
+        core.channels[0].set_loop_range(Some((2, 10)));
+        assert_eq!(
+            record(&mut core, 6),
+            [(0, 0), (0, 1), (1, 0), (2, 0), (3, 0), (2, 0)]
+        );
+
+        let silent = Sound::new();
+        core.channels[0].play(vec![sounds[0].clone(), silent], None, true);
+        core.channels[0].set_loop_range(Some((1, 1)));
+        assert_eq!(record(&mut core, 3), [(0, 0), (0, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn stop_ramp() {
+        let sound = Sound::new();
+        sound.lock().set("c1", "s", "7", "n", 120);
+        let mut channels = new_channels();
+        channels[0].set_gain(u8::MAX);
+        channels[0].play(vec![sound], None, true);
+        let mut core = new_core(channels);
+        let mut out = vec![0; NUM_SAMPLES as usize];
+        core.update(&mut out);
+        let peak = out
+            .iter()
+            .map(|sample| (*sample as i32).abs())
+            .max()
+            .unwrap();
+
+        core.channels[0].stop();
+        core.update(&mut out);
+        let max_step = out[NUM_SAMPLES as usize / 2..]
+            .windows(2)
+            .map(|pair| (pair[1] as i32 - pair[0] as i32).abs())
+            .max()
+            .unwrap();
+        assert!(max_step < peak / 4, "step {} peak {}", max_step, peak);
+        assert!(out[out.len() - 100..]
+            .iter()
+            .all(|sample| (*sample as i32).abs() < peak / 20));
+    }
+
+    #[test]
+    fn volume() {
+        let sound = Sound::new();
+        sound.lock().set("a2", "s", "7", "n", 255);
+        let mut channels = new_channels();
+        channels[0].play(vec![sound], None, true);
+        let master_volume = Arc::new(AtomicU64::new(1.0f64.to_bits()));
+        let mut core = AudioCore::new(channels, master_volume.clone(), SAMPLE_RATE).0;
+        let peak = |core: &mut AudioCore| {
+            let mut out = vec![0; NUM_SAMPLES as usize * 2];
+            core.update(&mut out);
+            out[NUM_SAMPLES as usize..]
+                .iter()
+                .map(|sample| (*sample as i32).abs())
+                .max()
+                .unwrap() as f64
+        };
+        let full = peak(&mut core);
+
+        master_volume.store(0.5f64.to_bits(), Ordering::Relaxed);
+        let half = peak(&mut core);
+        assert!((half / full - 0.5).abs() < 0.02, "{} {}", half, full);
+
+        master_volume.store(1.0f64.to_bits(), Ordering::Relaxed);
+        core.channels[0].set_volume(0.25);
+        let quarter = peak(&mut core);
+        assert!((quarter / full - 0.25).abs() < 0.02, "{} {}", quarter, full);
+
+        core.channels[0].set_volume(1.0);
+        master_volume.store(0.0f64.to_bits(), Ordering::Relaxed);
+        let mut out = [0; 8];
+        core.update(&mut out);
+        assert!(out.iter().any(|sample| *sample != 0));
+        assert_eq!(peak(&mut core), 0.0);
+    }
+
+    #[test]
+    fn play_sample() {
+        let mut channels = new_channels();
+        channels[1].set_gain(u8::MAX);
+        let mut core = new_core(channels);
+        let sample = Sample::new(&[0.5, -0.5, 1.0]);
+        core.channels[1].play_sample_data(sample.data.clone(), false);
+        let mut out = [0; 5];
+        core.update(&mut out);
+        assert_eq!(out, [16383, -16383, 31204, 0, 0]);
+
+        core.channels[1].play_sample_data(sample.data.clone(), true);
+        core.update(&mut out);
+        assert_eq!(out, [16383, -16383, 31204, 16383, -16383]);
+        core.channels[1].stop();
+        core.update(&mut out);
+        assert_eq!(out, [0; 5]);
+    }
+
+    #[test]
+    fn render_sounds_() {
+        let sound0 = Sound::new();
+        sound0.lock().set("c2e2g2", "tspn", "7531", "nsvf", 5);
+        let sound1 = Sound::new();
+        sound1.lock().set("a1r", "s", "6", "n", 7);
+        let rendered = render_sounds(
+            vec![vec![sound0.clone()], vec![sound1.clone()]],
+            SAMPLE_RATE,
+            false,
+            15,
+        )
+        .unwrap();
+        let num_samples = |ticks: u64, sample_rate: u32| {
+            (ticks * NUM_CLOCKS_PER_TICK as u64 * sample_rate as u64 / CLOCK_RATE as u64) as usize
+        };
+        assert_eq!(rendered.len(), num_samples(16, SAMPLE_RATE));
+
+        let mut channels = new_channels();
+        channels[0].play(vec![sound0], None, false);
+        channels[1].play(vec![sound1], None, false);
+        let mut core = new_core(channels);
+        let mut live = vec![0; rendered.len()];
+        for chunk in live.chunks_mut(100) {
+            core.update(chunk);
+        }
+        assert_eq!(rendered, live);
+        assert!(rendered.iter().any(|sample| *sample != 0));
+
+        let rendered = render_sounds(Vec::new(), SAMPLE_RATE / 2, false, 3).unwrap();
+        assert_eq!(rendered, vec![0; num_samples(4, SAMPLE_RATE / 2)]);
+        for sample_rate in [0, MIN_SAMPLE_RATE - 1, MAX_SAMPLE_RATE + 1] {
+            assert!(matches!(
+                render_sounds(Vec::new(), sample_rate, false, 3),
+                Err(AudioError::InvalidSampleRate(rate)) if rate == sample_rate
+            ));
+        }
+    }
+
+    #[test]
+    fn play_state() {
+        let sound = Sound::new();
+        sound.lock().set("c2e2g2", "s", "7", "n", 3);
+        let channels = new_channels();
+        let play_state = channels[0].play_state();
+        let mut core = new_core(channels);
+        assert_eq!(play_state.play_pos(), None);
+
+        core.channels[0].play(vec![sound.clone()], None, true);
+        assert_eq!(play_state.play_pos(), Some((0, 0)));
+        assert_eq!(play_state.elapsed_samples(), 0);
+        let tick_samples = |ticks: u64| {
+            ticks * NUM_CLOCKS_PER_TICK as u64 * SAMPLE_RATE as u64 / CLOCK_RATE as u64
+        };
+        let mut out = vec![0; NUM_SAMPLES as usize];
+        let mut elapsed_samples = 0;
+        for len in [1, 100, 367, 368, 735, 1000, 1, 1470, 50, 200] {
+            core.update(&mut out[..len]);
+            elapsed_samples += len as u64;
+            assert_eq!(play_state.elapsed_samples(), elapsed_samples);
+            let tick = (1..)
+                .find(|&tick| tick_samples(tick) >= elapsed_samples)
+                .unwrap()
+                - 1;
+            assert_eq!(
+                play_state.play_pos(),
+                Some((0, (tick / 3 % 3) as u32)),
+                "{}",
+                elapsed_samples
             );
         }
+
+        core.update(&mut out[..10]);
+        core.channels[0].play(vec![sound], Some(3), false);
+        assert_eq!(play_state.play_pos(), Some((0, 1)));
+        assert_eq!(play_state.elapsed_samples(), 0);
+        core.update(&mut out);
+        assert!(play_state.elapsed_samples() < NUM_SAMPLES as u64);
+        core.channels[0].stop();
+        assert_eq!(play_state.play_pos(), None);
+        let elapsed_samples = play_state.elapsed_samples();
+        core.update(&mut out);
+        assert_eq!(play_state.play_pos(), None);
+        assert_eq!(play_state.elapsed_samples(), elapsed_samples);
     }
 
-    pub fn stop(&mut self, channel_no: u32) {
-        self.audio.channels[channel_no as usize].lock().stop();
+    #[test]
+    fn slide_first_note() {
+        let render = |effects: &str| {
+            let sound = Sound::new();
+            sound.lock().set("a2c3", "s", "7", effects, 10);
+            render_sounds(vec![vec![sound]], SAMPLE_RATE, false, 20).unwrap()
+        };
+        let first = render("nn");
+        assert_eq!(render("sn"), first);
+        assert_ne!(render("ns"), first);
     }
 
-    pub fn stop0(&mut self) {
-        for i in 0..NUM_CHANNELS {
-            self.stop(i);
+    #[test]
+    fn speed_and_pitch() {
+        let render = |speed: f64, pitch: f64| {
+            let sound = Sound::new();
+            sound.lock().set("a2a2", "s", "7", "n", 15);
+            let mut channels = new_channels();
+            channels[0].play(vec![sound], None, false);
+            channels[0].set_speed(speed);
+            channels[0].set_pitch(pitch);
+            let mut core = new_core(channels);
+            let mut out = vec![0; SAMPLE_RATE as usize / 2];
+            core.update(&mut out);
+            out
+        };
+        let duration = |out: &[i16]| out.iter().rposition(|sample| *sample != 0).unwrap();
+        let crossings = |out: &[i16]| {
+            out[..2000]
+                .windows(2)
+                .filter(|pair| pair[0] < 0 && pair[1] >= 0)
+                .count()
+        };
+        let tick_samples =
+            SAMPLE_RATE as usize * NUM_CLOCKS_PER_TICK as usize / CLOCK_RATE as usize;
+
+        let normal = render(1.0, 0.0);
+        let fast = render(2.0, 0.0);
+        // Both renders share the same release tail after the 30 ticks of notes
+        let tail = duration(&normal) - 30 * tick_samples;
+        assert!((duration(&fast) - tail).abs_diff(15 * tick_samples) <= tick_samples * 2);
+        assert_eq!(crossings(&fast), crossings(&normal));
+
+        let high = render(1.0, 12.0);
+        assert!(duration(&high).abs_diff(duration(&normal)) <= tick_samples * 2);
+        let ratio = crossings(&high) as f64 / crossings(&normal) as f64;
+        assert!((ratio - 2.0).abs() < 0.1, "{}", ratio);
+
+        let mut channel = Channel::new();
+        channel.set_speed(10.0);
+        channel.set_pitch(-30.0);
+        assert_eq!(channel.params.speed, 4.0);
+        assert_eq!(channel.params.pitch, -24.0);
+    }
+
+    #[test]
+    fn stereo_pan() {
+        let sound = Sound::new();
+        sound.lock().set("a2", "s", "7", "n", 255);
+        let mut channels = new_channels();
+        channels[0].play(vec![sound], None, true);
+        channels[0].set_pan(-1.0);
+        let mut core = new_core(channels);
+        core.set_num_channels(2);
+        let peaks = |core: &mut AudioCore| {
+            let mut out = vec![0; NUM_SAMPLES as usize * 2];
+            core.update(&mut out);
+            let peak = |side: usize| {
+                out[NUM_SAMPLES as usize..]
+                    .iter()
+                    .skip(side)
+                    .step_by(2)
+                    .map(|sample| (*sample as i32).abs())
+                    .max()
+                    .unwrap()
+            };
+            (peak(0), peak(1))
+        };
+        let (left, right) = peaks(&mut core);
+        assert!(left > 1000);
+        assert!(right < 2, "{}", right);
+
+        core.channels[0].set_pan(0.0);
+        peaks(&mut core);
+        let (center_left, center_right) = peaks(&mut core);
+        assert_eq!(center_left, center_right);
+        assert!((center_left as f64 / left as f64 - 0.5f64.sqrt()).abs() < 0.02);
+
+        let mut core = new_core(new_channels());
+        core.set_num_channels(2);
+        core.channels[1].set_gain(u8::MAX);
+        core.channels[1].set_pan(1.0);
+        core.channels[1].play_sample_data(Sample::new(&[0.5, -0.5]).data, true);
+        let mut out = [0; 6];
+        core.update(&mut out);
+        assert_eq!(out, [0, 23169, 0, -23169, 0, 23169]);
+    }
+
+    #[test]
+    fn soft_clip_() {
+        assert_eq!(soft_clip(0.0), 0.0);
+        assert_eq!(soft_clip(0.5), 0.5);
+        assert_eq!(soft_clip(-SOFT_CLIP_THRESHOLD), -SOFT_CLIP_THRESHOLD);
+        for value in [0.9, 1.0, 2.0, 8.0, 1000.0] {
+            let clipped = soft_clip(value);
+            assert!(
+                clipped > SOFT_CLIP_THRESHOLD && clipped <= 1.0,
+                "{}",
+                clipped
+            );
+            assert_eq!(soft_clip(-value), -clipped);
+        }
+        assert!(soft_clip(0.9) < soft_clip(1.0));
+    }
+
+    #[test]
+    fn limit_channels() {
+        let sound = Sound::new();
+        sound.lock().set("a2", "s", "7", "n", 255);
+        let mut channels: Vec<Channel> = (0..8).map(|_| Channel::new()).collect();
+        for channel in &mut channels {
+            channel.set_gain(u8::MAX);
+            channel.play(vec![sound.clone()], None, true);
+            channel.play_sample_data(Sample::new(&[1.0, -1.0]).data, true);
+        }
+        let mut core = new_core(channels);
+        let mut out = vec![0; NUM_SAMPLES as usize];
+        core.update(&mut out);
+        let peak = out
+            .iter()
+            .map(|sample| (*sample as i32).abs())
+            .max()
+            .unwrap();
+        assert!(peak > i16::MAX as i32 * 9 / 10, "{}", peak);
+        assert!(out.chunks(2).all(|pair| pair[0] > 0 && pair[1] < 0));
+    }
+
+    #[test]
+    fn device_sample_rate() {
+        let count_cycles = |sample_rate: u32| {
+            let sound = Sound::new();
+            sound.lock().set("a2", "s", "7", "n", 255);
+            let mut channels = new_channels();
+            channels[0].play(vec![sound], None, true);
+            let mut core = new_core(channels);
+            core.set_sample_rate(sample_rate);
+            let mut out = vec![0; sample_rate as usize];
+            for chunk in out.chunks_mut(1000) {
+                core.update(chunk);
+            }
+            let mut is_high = false;
+            let mut num_cycles = 0;
+            for sample in &out {
+                if !is_high && *sample > 1000 {
+                    is_high = true;
+                    num_cycles += 1;
+                } else if is_high && *sample < -1000 {
+                    is_high = false;
+                }
+            }
+            num_cycles
+        };
+        let expected = count_cycles(SAMPLE_RATE);
+        assert!((expected - 440i32).abs() <= 2, "{}", expected);
+        assert!((count_cycles(48000) - expected).abs() <= 1);
+        assert!((count_cycles(22050) - expected).abs() <= 1);
+
+        let mut core = new_core(new_channels());
+        core.set_sample_rate(SAMPLE_RATE * 2);
+        core.channels[0].play_sample_data(Sample::new(&[0.5; 100]).data, false);
+        let mut out = vec![0; 300];
+        core.update(&mut out);
+        assert_eq!(out.iter().filter(|sample| **sample != 0).count(), 200);
+    }
+
+    #[test]
+    fn command_stress() {
+        let sound = Sound::new();
+        sound.lock().set("c2e2g2", "s", "7", "n", 1);
+        let sound = sound.lock().clone();
+        let channels = new_channels();
+        let play_states: Vec<_> = channels.iter().map(Channel::play_state).collect();
+        let (mut core, mut commands) = AudioCore::new(
+            channels,
+            Arc::new(AtomicU64::new(1.0f64.to_bits())),
+            SAMPLE_RATE,
+        );
+        let is_running = Arc::new(AtomicBool::new(true));
+        // The callback thread owns the core outright, just as the device does
+        let callback = {
+            let is_running = is_running.clone();
+            thread::spawn(move || {
+                let mut out = vec![0; NUM_SAMPLES as usize];
+                while is_running.load(Ordering::Relaxed) {
+                    core.update(&mut out);
+                }
+                core
+            })
+        };
+        let mut push = |command: AudioCommand| {
+            let start = Instant::now();
+            let mut command = command;
+            while let Err(rejected) = commands.push(command) {
+                assert!(
+                    start.elapsed() < Duration::from_secs(1),
+                    "queue never drained"
+                );
+                command = rejected;
+                thread::yield_now();
+            }
+        };
+        let mut max_latency = Duration::ZERO;
+        for i in 0..500 {
+            let channel_no = i % NUM_CHANNELS;
+            push(AudioCommand::Stop { channel_no: None });
+            push(AudioCommand::SetVolume {
+                channel_no,
+                volume: 0.5,
+            });
+            push(AudioCommand::SetPan {
+                channel_no,
+                pan: -0.5,
+            });
+            push(AudioCommand::SetSpeed {
+                channel_no,
+                speed: 2.0,
+            });
+            let start = Instant::now();
+            push(AudioCommand::Play {
+                plays: vec![SequencePlay {
+                    channel_no,
+                    sounds: vec![sound.clone()],
+                    loop_range: None,
+                }],
+                start_tick: None,
+                should_loop: true,
+            });
+            while play_states[channel_no as usize].play_pos().is_none() {
+                assert!(
+                    start.elapsed() < Duration::from_secs(1),
+                    "command never applied"
+                );
+                thread::yield_now();
+            }
+            max_latency = max_latency.max(start.elapsed());
+        }
+        is_running.store(false, Ordering::Relaxed);
+        let core = callback.join().unwrap();
+        assert!(max_latency < Duration::from_millis(500));
+        let params = core.channels[1].params;
+        assert_eq!((params.volume, params.pan, params.speed), (0.5, -0.5, 2.0));
+    }
+
+    #[test]
+    fn play_pos_invalid_channel() {
+        let audio = Audio::without_device(2, Arc::new(AudioMonitor::new()));
+        assert_eq!(audio.play_pos(1).unwrap(), None);
+        assert_eq!(audio.elapsed_samples(1).unwrap(), 0);
+        assert!(matches!(
+            audio.play_pos(2),
+            Err(AudioError::InvalidChannel(2))
+        ));
+        assert!(matches!(
+            audio.elapsed_samples(7),
+            Err(AudioError::InvalidChannel(7))
+        ));
+    }
+
+    #[test]
+    fn pending_commands() {
+        let mut audio = Audio::without_device(NUM_CHANNELS, Arc::new(AudioMonitor::new()));
+        let num_commands = AUDIO_COMMAND_QUEUE_SIZE * 3;
+        for i in 0..num_commands {
+            audio.send_command(AudioCommand::SetGain {
+                channel_no: 0,
+                gain: i as u8,
+            });
+        }
+        // Without a device the game thread drains the queue itself
+        assert!(audio.pending_commands.is_empty());
+        assert_eq!(
+            audio.core.lock().channels[0].params.gain,
+            (num_commands - 1) as u8
+        );
+
+        // A device holding the core leaves the overflow waiting in order
+        let device = audio.core.clone();
+        for i in 0..num_commands {
+            audio.send_command(AudioCommand::SetGain {
+                channel_no: 1,
+                gain: i as u8,
+            });
+        }
+        audio.set_num_channels(NUM_CHANNELS + 2);
+        assert_eq!(
+            audio.pending_commands.len(),
+            (num_commands + 1 - AUDIO_COMMAND_QUEUE_SIZE) as usize
+        );
+        let mut out = [0; 4];
+        while !audio.pending_commands.is_empty() {
+            device.lock().update(&mut out);
+            audio.flush_commands();
         }
+        device.lock().update(&mut out);
+        let core = device.lock();
+        assert_eq!(core.channels.len(), NUM_CHANNELS as usize + 2);
+        assert_eq!(core.channels[1].params.gain, (num_commands - 1) as u8);
+        assert_eq!(audio.num_channels(), NUM_CHANNELS + 2);
     }
 }
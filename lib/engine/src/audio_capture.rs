@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::platform::AudioError;
+use crate::ring_buffer::RingBuffer;
+use crate::sample::wav_header;
+use crate::settings::{AUDIO_CAPTURE_BUFFER_SEC, AUDIO_CAPTURE_CHUNK_SIZE};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureStats {
+    pub num_frames: u64,
+    pub num_dropped_frames: u64,
+}
+
+pub struct CaptureBuffer {
+    samples: RingBuffer,
+    num_channels: u32,
+    num_dropped_frames: AtomicU64,
+}
+
+impl CaptureBuffer {
+    pub fn push_frames(&self, out: &[i16], num_channels: u32) {
+        // Only whole frames are queued, and a full buffer drops them instead of waiting
+        let num_frames = out.len() / num_channels as usize;
+        let num_pushed_frames = if num_channels == self.num_channels {
+            let num_free_frames =
+                (self.samples.capacity() - self.samples.len()) / num_channels as usize;
+            let len = num_frames.min(num_free_frames) * num_channels as usize;
+            self.samples.push(&out[..len]) / num_channels as usize
+        } else {
+            0
+        };
+        self.num_dropped_frames
+            .fetch_add((num_frames - num_pushed_frames) as u64, Ordering::Relaxed);
+    }
+}
+
+pub struct AudioCapture {
+    path: PathBuf,
+    buffer: Arc<CaptureBuffer>,
+    writer: BufWriter<File>,
+    sample_rate: u32,
+    chunk: Vec<i16>,
+    num_samples: u64,
+    error: Option<AudioError>,
+}
+
+impl AudioCapture {
+    pub fn new(path: &Path, sample_rate: u32, num_channels: u32) -> Result<Self, AudioError> {
+        let write_error = || AudioError::WriteFile(path.display().to_string());
+        let mut writer = BufWriter::new(File::create(path).map_err(|_| write_error())?);
+        writer
+            .write_all(&wav_header(sample_rate, num_channels, 0))
+            .map_err(|_| write_error())?;
+        let capacity = (sample_rate * num_channels * AUDIO_CAPTURE_BUFFER_SEC) as usize;
+        Ok(Self {
+            path: path.to_path_buf(),
+            buffer: Arc::new(CaptureBuffer {
+                samples: RingBuffer::new(capacity),
+                num_channels,
+                num_dropped_frames: AtomicU64::new(0),
+            }),
+            writer,
+            sample_rate,
+            chunk: vec![0; AUDIO_CAPTURE_CHUNK_SIZE as usize],
+            num_samples: 0,
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn buffer(&self) -> Arc<CaptureBuffer> {
+        self.buffer.clone()
+    }
+
+    pub fn drain(&mut self) {
+        loop {
+            let len = self.buffer.samples.pop(&mut self.chunk);
+            if len == 0 {
+                break;
+            }
+            self.num_samples += len as u64;
+            if self.error.is_some() {
+                continue;
+            }
+            for sample in &self.chunk[..len] {
+                if self.writer.write_all(&sample.to_le_bytes()).is_err() {
+                    self.error = Some(AudioError::WriteFile(self.path.display().to_string()));
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Result<CaptureStats, AudioError> {
+        self.drain();
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let num_channels = self.buffer.num_channels;
+        let data_size = (self.num_samples * 2) as u32;
+        self.writer
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| {
+                self.writer
+                    .write_all(&wav_header(self.sample_rate, num_channels, data_size))
+            })
+            .and_then(|_| self.writer.flush())
+            .map_err(|_| AudioError::WriteFile(self.path.display().to_string()))?;
+        Ok(CaptureStats {
+            num_frames: self.num_samples / num_channels as u64,
+            num_dropped_frames: self.buffer.num_dropped_frames.load(Ordering::Relaxed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::Sample;
+    use crate::settings::SAMPLE_RATE;
+
+    #[test]
+    fn capture() {
+        let path = std::env::temp_dir().join("pyxel_audio_capture.wav");
+        let mut capture = AudioCapture::new(&path, SAMPLE_RATE, 2).unwrap();
+        let buffer = capture.buffer();
+        buffer.push_frames(&[16384, -16384, 8192, 0], 2);
+        capture.drain();
+        buffer.push_frames(&[0, 16384], 2);
+        buffer.push_frames(&[1, 2, 3], 3);
+        let stats = capture.finish().unwrap();
+        assert_eq!(
+            stats,
+            CaptureStats {
+                num_frames: 3,
+                num_dropped_frames: 1,
+            }
+        );
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(&data[..44], &wav_header(SAMPLE_RATE, 2, 12)[..]);
+        let sample = Sample::from_wav(&path).unwrap();
+        assert_eq!(sample.len(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn drop_frames() {
+        let path = std::env::temp_dir().join("pyxel_audio_capture_drop.wav");
+        let capture = AudioCapture::new(&path, 2, 1).unwrap();
+        let buffer = capture.buffer();
+        buffer.push_frames(&[1, 2, 3, 4, 5], 1);
+        let stats = capture.finish().unwrap();
+        assert_eq!(stats.num_frames, 2);
+        assert_eq!(stats.num_dropped_frames, 3);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
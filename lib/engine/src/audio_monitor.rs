@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::platform::AudioCallback;
+use crate::settings::{AUDIO_LOCK_TIMEOUT_US, AUDIO_LOCK_WAIT_THRESHOLD_US};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AudioStats {
+    pub num_callbacks: u64,
+    pub num_lock_waits: u64,
+    pub num_lock_timeouts: u64,
+    pub max_fill_us: u64,
+    pub mean_fill_us: f64,
+}
+
+#[derive(Default)]
+pub struct AudioMonitor {
+    num_callbacks: AtomicU64,
+    num_lock_waits: AtomicU64,
+    num_lock_timeouts: AtomicU64,
+    total_fill_us: AtomicU64,
+    max_fill_us: AtomicU64,
+}
+
+impl AudioMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn take_stats(&self) -> AudioStats {
+        let num_callbacks = self.num_callbacks.swap(0, Ordering::Relaxed);
+        let total_fill_us = self.total_fill_us.swap(0, Ordering::Relaxed);
+        AudioStats {
+            num_callbacks,
+            num_lock_waits: self.num_lock_waits.swap(0, Ordering::Relaxed),
+            num_lock_timeouts: self.num_lock_timeouts.swap(0, Ordering::Relaxed),
+            max_fill_us: self.max_fill_us.swap(0, Ordering::Relaxed),
+            mean_fill_us: if num_callbacks > 0 {
+                total_fill_us as f64 / num_callbacks as f64
+            } else {
+                0.0
+            },
+        }
+    }
+
+    pub fn fill(
+        &self,
+        audio: &shared_type!(dyn AudioCallback + Send),
+        history: &mut Vec<i16>,
+        out: &mut [i16],
+    ) {
+        let start = Instant::now();
+        self.num_callbacks.fetch_add(1, Ordering::Relaxed);
+        if let Some(mut audio) = audio.try_lock().or_else(|| {
+            let audio = audio.try_lock_for(Duration::from_micros(AUDIO_LOCK_TIMEOUT_US));
+            if start.elapsed() > Duration::from_micros(AUDIO_LOCK_WAIT_THRESHOLD_US) {
+                self.num_lock_waits.fetch_add(1, Ordering::Relaxed);
+            }
+            audio
+        }) {
+            audio.update(out);
+            drop(audio);
+            // Capacity is reserved when the device opens, so this never allocates
+            history.clear();
+            history.extend_from_slice(out);
+        } else {
+            // Repeating the last output is less jarring than a gap of silence
+            self.num_lock_timeouts.fetch_add(1, Ordering::Relaxed);
+            if history.is_empty() {
+                out.fill(0);
+            } else {
+                let tail = &history[history.len().saturating_sub(out.len())..];
+                for (value, sample) in out.iter_mut().zip(tail.iter().cycle()) {
+                    *value = *sample;
+                }
+            }
+        }
+        let fill_us = start.elapsed().as_micros() as u64;
+        self.total_fill_us.fetch_add(fill_us, Ordering::Relaxed);
+        self.max_fill_us.fetch_max(fill_us, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Counter {
+        value: i16,
+    }
+
+    impl AudioCallback for Counter {
+        fn set_sample_rate(&mut self, _sample_rate: u32) {}
+
+        fn set_num_channels(&mut self, _num_channels: u32) {}
+
+        fn update(&mut self, out: &mut [i16]) {
+            for value in out {
+                self.value += 1;
+                *value = self.value;
+            }
+        }
+    }
+
+    #[test]
+    fn fill() {
+        let monitor = AudioMonitor::new();
+        let audio: shared_type!(dyn AudioCallback + Send) = new_shared_type!(Counter { value: 0 });
+        let mut history = Vec::with_capacity(4);
+        let mut out = [0; 4];
+
+        let guard = audio.lock();
+        monitor.fill(&audio, &mut history, &mut out);
+        assert_eq!(out, [0; 4]);
+        drop(guard);
+
+        monitor.fill(&audio, &mut history, &mut out);
+        assert_eq!(out, [1, 2, 3, 4]);
+        let guard = audio.lock();
+        monitor.fill(&audio, &mut history, &mut out[..3]);
+        assert_eq!(out[..3], [2, 3, 4]);
+        drop(guard);
+
+        let stats = monitor.take_stats();
+        assert_eq!(stats.num_callbacks, 3);
+        assert_eq!(stats.num_lock_waits, 2);
+        assert_eq!(stats.num_lock_timeouts, 2);
+        assert!(stats.max_fill_us >= AUDIO_LOCK_TIMEOUT_US);
+        assert!(stats.mean_fill_us > 0.0);
+        assert_eq!(monitor.take_stats(), AudioStats::default());
+    }
+}
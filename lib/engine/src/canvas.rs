@@ -1,7 +1,9 @@
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::mem::swap;
 
-use crate::rectarea::RectArea;
+use crate::dirty_region::DirtyRegion;
+use crate::geometry::Recti;
+use crate::settings::BEZIER_SEGMENT_LENGTH;
 use crate::utils::{as_i32, as_u32};
 
 pub trait ToIndex {
@@ -9,21 +11,31 @@ pub trait ToIndex {
 }
 
 pub struct Canvas<T: Copy + PartialEq + Default + ToIndex> {
-    pub self_rect: RectArea,
-    pub clip_rect: RectArea,
+    pub self_rect: Recti,
+    pub clip_rect: Recti,
     pub camera_x: i32,
     pub camera_y: i32,
+    pub fill_pattern: u16,
+    pub fill_pattern_values: Option<(T, T)>,
     pub data: Vec<Vec<T>>,
+    pub dirty_region: Option<DirtyRegion>,
+    fill_marks: Vec<u32>,
+    fill_generation: u32,
 }
 
 impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
     pub fn new(width: u32, height: u32) -> Self {
         Self {
-            self_rect: RectArea::new(0, 0, width, height),
-            clip_rect: RectArea::new(0, 0, width, height),
+            self_rect: Recti::new(0, 0, width, height),
+            clip_rect: Recti::new(0, 0, width, height),
             camera_x: 0,
             camera_y: 0,
+            fill_pattern: 0xffff,
+            fill_pattern_values: None,
             data: vec![vec![T::default(); width as usize]; height as usize],
+            dirty_region: None,
+            fill_marks: Vec::new(),
+            fill_generation: 0,
         }
     }
 
@@ -40,9 +52,7 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         let y = as_i32(y);
         let width = as_u32(width);
         let height = as_u32(height);
-        self.clip_rect = self
-            .self_rect
-            .intersects(RectArea::new(x, y, width, height));
+        self.clip_rect = self.self_rect.intersection(Recti::new(x, y, width, height));
     }
 
     pub fn clip0(&mut self) {
@@ -59,12 +69,25 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         self.camera_y = 0;
     }
 
+    pub fn fillp(&mut self, pattern: u16) {
+        self.fill_pattern = pattern;
+    }
+
+    pub fn fillp_colors(&mut self, value1: T, value2: T) {
+        self.fill_pattern_values = Some((value1, value2));
+    }
+
+    pub fn fillp0(&mut self) {
+        self.fill_pattern = 0xffff;
+        self.fill_pattern_values = None;
+    }
+
     pub fn cls(&mut self, value: T) {
         let width = self.width();
         let height = self.height();
         for y in 0..height {
             for x in 0..width {
-                self.write_data(x as i32, y as i32, value);
+                self.write_pattern_data(x as i32, y as i32, value);
             }
         }
     }
@@ -72,58 +95,141 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
     pub fn pget(&mut self, x: f64, y: f64) -> T {
         let x = as_i32(x);
         let y = as_i32(y);
-        if self.self_rect.contains(x, y) {
+        if self.self_rect.contains_point(x, y) {
             self.read_data(x, y)
         } else {
             T::default()
         }
     }
 
+    pub fn pick(&self, x: f64, y: f64) -> Option<T> {
+        let x = as_i32(x);
+        let y = as_i32(y);
+        self.self_rect
+            .contains_point(x, y)
+            .then(|| self.read_data(x, y))
+    }
+
+    pub fn find_bounds(&self, value: T) -> Option<Recti> {
+        let mut bounds: Option<(usize, usize, usize, usize)> = None;
+        for (y, row) in self.data.iter().enumerate() {
+            if let Some(left) = row.iter().position(|&data| data == value) {
+                let right = row.iter().rposition(|&data| data == value).unwrap();
+                bounds = Some(
+                    bounds.map_or((left, y, right, y), |(min_x, min_y, max_x, _)| {
+                        (min_x.min(left), min_y, max_x.max(right), y)
+                    }),
+                );
+            }
+        }
+        bounds.map(|(min_x, min_y, max_x, max_y)| {
+            Recti::new(
+                min_x as i32,
+                min_y as i32,
+                (max_x - min_x + 1) as u32,
+                (max_y - min_y + 1) as u32,
+            )
+        })
+    }
+
     pub fn pset(&mut self, x: f64, y: f64, value: T) {
         let x = as_i32(x) - self.camera_x;
         let y = as_i32(y) - self.camera_y;
         self.write_clipped_data(x, y, value);
     }
 
+    pub fn pset_slice(&mut self, points: &[(i32, i32)], value: T) {
+        self.pset_points(points.iter().map(|&(x, y)| (x, y, value)));
+    }
+
+    pub(crate) fn pset_points(&mut self, points: impl Iterator<Item = (i32, i32, T)>) {
+        // Points are moved into clip space once, so that one unsigned comparison per axis
+        // rejects both sides of the clip rect
+        let clip_left = self.clip_rect.left();
+        let clip_top = self.clip_rect.top();
+        let clip_width = self.clip_rect.width();
+        let clip_height = self.clip_rect.height();
+        let offset_x = self.camera_x.wrapping_add(clip_left);
+        let offset_y = self.camera_y.wrapping_add(clip_top);
+        for (x, y, value) in points {
+            let clip_x = x.wrapping_sub(offset_x) as u32;
+            let clip_y = y.wrapping_sub(offset_y) as u32;
+            if clip_x >= clip_width || clip_y >= clip_height {
+                continue;
+            }
+            let x = clip_x as i32 + clip_left;
+            let y = clip_y as i32 + clip_top;
+            self.data[y as usize][x as usize] = value;
+            if let Some(dirty_region) = &mut self.dirty_region {
+                dirty_region.mark(x, y);
+            }
+        }
+    }
+
     pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, value: T) {
         let x1 = as_i32(x1) - self.camera_x;
         let y1 = as_i32(y1) - self.camera_y;
         let x2 = as_i32(x2) - self.camera_x;
         let y2 = as_i32(y2) - self.camera_y;
+        self.draw_line(x1, y1, x2, y2, value);
+    }
 
-        if x1 == x2 && y1 == y2 {
-            self.write_clipped_data(x1, y1, value);
-        } else if (x1 - x2).abs() > (y1 - y2).abs() {
-            let (start_x, start_y, end_x, end_y) = if x1 < x2 {
-                (x1, y1, x2, y2)
-            } else {
-                (x2, y2, x1, y1)
-            };
-            let length = end_x - start_x + 1;
-            let alpha = (end_y - start_y) as f64 / (end_x - start_x) as f64;
-            for xi in 0..length {
-                self.write_clipped_data(start_x + xi, start_y + as_i32(alpha * xi as f64), value);
-            }
-        } else {
-            let (start_x, start_y, end_x, end_y) = if y1 < y2 {
-                (x1, y1, x2, y2)
-            } else {
-                (x2, y2, x1, y1)
-            };
-            let length = end_y - start_y + 1;
-            let alpha = (end_x - start_x) as f64 / (end_y - start_y) as f64;
-            for yi in 0..length {
-                self.write_clipped_data(start_x + as_i32(alpha * yi as f64), start_y + yi, value);
+    pub fn line_w(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, value: T) {
+        let thickness = as_i32(thickness);
+        if thickness <= 1 {
+            self.line(x1, y1, x2, y2, value);
+            return;
+        }
+        let x1 = as_i32(x1) - self.camera_x;
+        let y1 = as_i32(y1) - self.camera_y;
+        let x2 = as_i32(x2) - self.camera_x;
+        let y2 = as_i32(y2) - self.camera_y;
+        let offset = (thickness - 1) / 2;
+        for (x, y) in Self::line_points(x1, y1, x2, y2) {
+            for yi in (y - offset)..(y - offset + thickness) {
+                for xi in (x - offset)..(x - offset + thickness) {
+                    self.write_clipped_data(xi, yi, value);
+                }
             }
         }
     }
 
+    pub fn polyline(&mut self, points: &[(f64, f64)], value: T) {
+        let points: Vec<(i32, i32)> = points
+            .iter()
+            .map(|(x, y)| (as_i32(*x) - self.camera_x, as_i32(*y) - self.camera_y))
+            .collect();
+        self.draw_polyline(&points, value);
+    }
+
+    pub fn bezier(&mut self, x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, value: T) {
+        let x1 = x1 - self.camera_x as f64;
+        let y1 = y1 - self.camera_y as f64;
+        let cx = cx - self.camera_x as f64;
+        let cy = cy - self.camera_y as f64;
+        let x2 = x2 - self.camera_x as f64;
+        let y2 = y2 - self.camera_y as f64;
+        let length = (cx - x1).hypot(cy - y1) + (x2 - cx).hypot(y2 - cy);
+        let num_segments = max((length / BEZIER_SEGMENT_LENGTH).ceil() as u32, 1);
+        let points: Vec<(i32, i32)> = (0..=num_segments)
+            .map(|i| {
+                let t = i as f64 / num_segments as f64;
+                let s = 1.0 - t;
+                (
+                    as_i32(s * s * x1 + 2.0 * s * t * cx + t * t * x2),
+                    as_i32(s * s * y1 + 2.0 * s * t * cy + t * t * y2),
+                )
+            })
+            .collect();
+        self.draw_polyline(&points, value);
+    }
+
     pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, value: T) {
         let x = as_i32(x) - self.camera_x;
         let y = as_i32(y) - self.camera_y;
         let width = as_u32(width);
         let height = as_u32(height);
-        let rect = RectArea::new(x, y, width, height).intersects(self.clip_rect);
+        let rect = Recti::new(x, y, width, height).intersection(self.clip_rect);
         if rect.is_empty() {
             return;
         }
@@ -133,7 +239,7 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         let bottom = rect.bottom();
         for y in top..=bottom {
             for x in left..=right {
-                self.write_data(x, y, value);
+                self.write_pattern_data(x, y, value);
             }
         }
     }
@@ -143,8 +249,8 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         let y = as_i32(y) - self.camera_y;
         let width = as_u32(width);
         let height = as_u32(height);
-        let rect = RectArea::new(x, y, width, height);
-        if rect.intersects(self.clip_rect).is_empty() {
+        let rect = Recti::new(x, y, width, height);
+        if rect.intersection(self.clip_rect).is_empty() {
             return;
         }
         let left = rect.left();
@@ -161,6 +267,22 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         }
     }
 
+    pub fn rectb_w(&mut self, x: f64, y: f64, width: f64, height: f64, thickness: f64, value: T) {
+        let width = as_u32(width);
+        let height = as_u32(height);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let left = as_i32(x) as f64;
+        let top = as_i32(y) as f64;
+        let right = left + width as f64 - 1.0;
+        let bottom = top + height as f64 - 1.0;
+        self.line_w(left, top, right, top, thickness, value);
+        self.line_w(left, bottom, right, bottom, thickness, value);
+        self.line_w(left, top, left, bottom, thickness, value);
+        self.line_w(right, top, right, bottom, thickness, value);
+    }
+
     pub fn circ(&mut self, x: f64, y: f64, radius: f64, value: T) {
         let x = as_i32(x) - self.camera_x;
         let y = as_i32(y) - self.camera_y;
@@ -168,10 +290,10 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         for xi in 0..=radius as i32 {
             let (x1, y1, x2, y2) = Self::ellipse_area(0.0, 0.0, radius as f64, radius as f64, xi);
             for yi in y1..=y2 {
-                self.write_clipped_data(x + x1, y + yi, value);
-                self.write_clipped_data(x + x2, y + yi, value);
-                self.write_clipped_data(x + yi, y + x1, value);
-                self.write_clipped_data(x + yi, y + x2, value);
+                self.write_clipped_pattern_data(x + x1, y + yi, value);
+                self.write_clipped_pattern_data(x + x2, y + yi, value);
+                self.write_clipped_pattern_data(x + yi, y + x1, value);
+                self.write_clipped_pattern_data(x + yi, y + x2, value);
             }
         }
     }
@@ -197,21 +319,21 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
     pub fn elli(&mut self, x: f64, y: f64, width: f64, height: f64, value: T) {
         let x = as_i32(x) - self.camera_x;
         let y = as_i32(y) - self.camera_y;
-        let width = as_u32(width);
-        let height = as_u32(height);
+        let width = max(as_u32(width), 1);
+        let height = max(as_u32(height), 1);
         let (ra, rb, cx, cy) = Self::ellipse_params(x, y, width, height);
         for xi in x..(x + width as i32 / 2) + 1 {
             let (x1, y1, x2, y2) = Self::ellipse_area(cx, cy, ra, rb, xi);
             for yi in y1..=y2 {
-                self.write_clipped_data(x1, yi, value);
-                self.write_clipped_data(x2, yi, value);
+                self.write_clipped_pattern_data(x1, yi, value);
+                self.write_clipped_pattern_data(x2, yi, value);
             }
         }
         for yi in y..(y + height as i32 / 2) + 1 {
             let (y1, x1, y2, x2) = Self::ellipse_area(cy, cx, rb, ra, yi);
             for xi in x1..=x2 {
-                self.write_clipped_data(xi, y1, value);
-                self.write_clipped_data(xi, y2, value);
+                self.write_clipped_pattern_data(xi, y1, value);
+                self.write_clipped_pattern_data(xi, y2, value);
             }
         }
     }
@@ -219,8 +341,8 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
     pub fn ellib(&mut self, x: f64, y: f64, width: f64, height: f64, value: T) {
         let x = as_i32(x) - self.camera_x;
         let y = as_i32(y) - self.camera_y;
-        let width = as_u32(width);
-        let height = as_u32(height);
+        let width = max(as_u32(width), 1);
+        let height = max(as_u32(height), 1);
         let (ra, rb, cx, cy) = Self::ellipse_params(x, y, width, height);
         for xi in x..(x + width as i32 / 2) + 1 {
             let (x1, y1, x2, y2) = Self::ellipse_area(cx, cy, ra, rb, xi);
@@ -239,72 +361,51 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
     }
 
     pub fn tri(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64, value: T) {
-        let mut x1 = as_i32(x1) - self.camera_x;
-        let mut y1 = as_i32(y1) - self.camera_y;
+        let x1 = as_i32(x1) - self.camera_x;
+        let y1 = as_i32(y1) - self.camera_y;
         let mut x2 = as_i32(x2) - self.camera_x;
         let mut y2 = as_i32(y2) - self.camera_y;
         let mut x3 = as_i32(x3) - self.camera_x;
         let mut y3 = as_i32(y3) - self.camera_y;
-        if y1 > y2 {
-            swap(&mut y1, &mut y2);
-            swap(&mut x1, &mut x2);
-        }
-        if y1 > y3 {
-            swap(&mut y1, &mut y3);
-            swap(&mut x1, &mut x3);
+        let area = Self::edge_function(x1, y1, x2, y2, x3, y3);
+        if area == 0 {
+            let (x1, y1) = ((x1 + self.camera_x) as f64, (y1 + self.camera_y) as f64);
+            let (x2, y2) = ((x2 + self.camera_x) as f64, (y2 + self.camera_y) as f64);
+            let (x3, y3) = ((x3 + self.camera_x) as f64, (y3 + self.camera_y) as f64);
+            self.trib(x1, y1, x2, y2, x3, y3, value);
+            return;
         }
-        if y2 > y3 {
-            swap(&mut y2, &mut y3);
+        if area < 0 {
             swap(&mut x2, &mut x3);
+            swap(&mut y2, &mut y3);
         }
 
-        let alpha12 = if y2 == y1 {
-            0.0
-        } else {
-            (x2 - x1) as f64 / (y2 - y1) as f64
-        };
-        let alpha13 = if y3 == y1 {
-            0.0
-        } else {
-            (x3 - x1) as f64 / (y3 - y1) as f64
-        };
-        let alpha23 = if y3 == y2 {
-            0.0
-        } else {
-            (x3 - x2) as f64 / (y3 - y2) as f64
-        };
-        let x_inter = as_i32(x1 as f64 + alpha13 * (y2 - y1) as f64);
-
-        for y in y1..=y2 {
-            let (x_slider, x_end) = if x_inter < x2 {
-                (
-                    as_i32(x_inter as f64 + alpha13 * (y - y2) as f64),
-                    as_i32(x2 as f64 + alpha12 * (y - y2) as f64),
-                )
-            } else {
-                (
-                    as_i32(x2 as f64 + alpha12 * (y - y2) as f64),
-                    as_i32(x_inter as f64 + alpha13 * (y - y2) as f64),
-                )
-            };
-            for x in x_slider..=x_end {
-                self.write_clipped_data(x, y, value);
-            }
+        let left = min(min(x1, x2), x3);
+        let top = min(min(y1, y2), y3);
+        let right = max(max(x1, x2), x3);
+        let bottom = max(max(y1, y2), y3);
+        let rect = Recti::new(
+            left,
+            top,
+            (right - left + 1) as u32,
+            (bottom - top + 1) as u32,
+        )
+        .intersection(self.clip_rect);
+        if rect.is_empty() {
+            return;
         }
-        for y in (y2 + 1)..=y3 {
-            let (x_slider, x_end) = if x_inter < x2 {
-                (
-                    as_i32(x_inter as f64 + alpha13 * (y - y2) as f64),
-                    as_i32(x2 as f64 + alpha23 * (y - y2) as f64),
-                )
-            } else {
-                (
-                    as_i32(x2 as f64 + alpha23 * (y - y2) as f64),
-                    as_i32(x_inter as f64 + alpha13 * (y - y2) as f64),
-                )
-            };
-            for x in x_slider..=x_end {
-                self.write_clipped_data(x, y, value);
+
+        let bias1 = Self::top_left_bias(x2, y2, x3, y3);
+        let bias2 = Self::top_left_bias(x3, y3, x1, y1);
+        let bias3 = Self::top_left_bias(x1, y1, x2, y2);
+        for y in rect.top()..=rect.bottom() {
+            for x in rect.left()..=rect.right() {
+                if Self::edge_function(x2, y2, x3, y3, x, y) + bias1 > 0
+                    && Self::edge_function(x3, y3, x1, y1, x, y) + bias2 > 0
+                    && Self::edge_function(x1, y1, x2, y2, x, y) + bias3 > 0
+                {
+                    self.write_pattern_data(x, y, value);
+                }
             }
         }
     }
@@ -318,12 +419,12 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
     pub fn fill(&mut self, x: f64, y: f64, value: T) {
         let x = as_i32(x) - self.camera_x;
         let y = as_i32(y) - self.camera_y;
-        if !self.clip_rect.contains(x, y) {
+        if !self.clip_rect.contains_point(x, y) {
             return;
         }
         let dst_value = self.read_data(x, y);
-        if value != dst_value {
-            self.fill_rec(x, y, value, dst_value);
+        if value != dst_value || self.fill_pattern_values.is_some() {
+            self.fill_span(x, y, value, dst_value);
         }
     }
 
@@ -371,6 +472,19 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
             return;
         }
 
+        if transparent.is_none() && palette.is_none() && sign_x == 1 {
+            let src_left = (src_x + offset_x) as usize;
+            let dst_left = dst_x as usize;
+            for yi in 0..height {
+                let value_y = src_y + sign_y * yi + offset_y;
+                let src_row = &canvas.data[value_y as usize][src_left..src_left + width as usize];
+                self.data[(dst_y + yi) as usize][dst_left..dst_left + width as usize]
+                    .copy_from_slice(src_row);
+            }
+            self.mark_dirty(Recti::new(dst_x, dst_y, width as u32, height as u32));
+            return;
+        }
+
         for yi in 0..height {
             for xi in 0..width {
                 let value_x = src_x + sign_x * xi + offset_x;
@@ -387,17 +501,352 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         }
     }
 
+    pub fn copy(
+        &mut self,
+        x: f64,
+        y: f64,
+        canvas: &Self,
+        canvas_x: f64,
+        canvas_y: f64,
+        width: f64,
+        height: f64,
+        transparent: Option<T>,
+    ) {
+        let CopyArea {
+            dst_x,
+            dst_y,
+            src_x,
+            src_y,
+            sign_x,
+            sign_y,
+            offset_x,
+            offset_y,
+            width,
+            height,
+        } = CopyArea::new(
+            as_i32(x),
+            as_i32(y),
+            self.self_rect,
+            as_i32(canvas_x),
+            as_i32(canvas_y),
+            canvas.self_rect,
+            as_i32(width),
+            as_i32(height),
+        );
+        for yi in 0..height {
+            for xi in 0..width {
+                let value_x = src_x + sign_x * xi + offset_x;
+                let value_y = src_y + sign_y * yi + offset_y;
+                let value = canvas.read_data(value_x, value_y);
+                if transparent == Some(value) {
+                    continue;
+                }
+                self.write_data(dst_x + xi, dst_y + yi, value);
+            }
+        }
+    }
+
+    pub fn copy_within(
+        &mut self,
+        x: f64,
+        y: f64,
+        canvas_x: f64,
+        canvas_y: f64,
+        width: f64,
+        height: f64,
+        transparent: Option<T>,
+    ) {
+        let mut canvas = Self::new(self.width(), self.height());
+        canvas.data.clone_from(&self.data);
+        self.copy(
+            x,
+            y,
+            &canvas,
+            canvas_x,
+            canvas_y,
+            width,
+            height,
+            transparent,
+        );
+    }
+
+    pub fn blt_transform(
+        &mut self,
+        x: f64,
+        y: f64,
+        canvas: &Self,
+        canvas_x: f64,
+        canvas_y: f64,
+        width: f64,
+        height: f64,
+        transparent: Option<T>,
+        palette: Option<&[T]>,
+        rotate: f64,
+        scale: f64,
+    ) {
+        if rotate == 0.0 && scale == 1.0 {
+            self.blt(
+                x,
+                y,
+                canvas,
+                canvas_x,
+                canvas_y,
+                width,
+                height,
+                transparent,
+                palette,
+            );
+            return;
+        }
+        if scale <= 0.0 {
+            return;
+        }
+        let x = as_i32(x) - self.camera_x;
+        let y = as_i32(y) - self.camera_y;
+        let canvas_x = as_i32(canvas_x);
+        let canvas_y = as_i32(canvas_y);
+        let flip_x = width < 0.0;
+        let flip_y = height < 0.0;
+        let width = as_i32(width.abs());
+        let height = as_i32(height.abs());
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let (sin, cos) = Self::snapped_sin_cos(rotate.to_radians());
+        let half_width = width as f64 / 2.0;
+        let half_height = height as f64 / 2.0;
+        let center_x = x as f64 + half_width;
+        let center_y = y as f64 + half_height;
+        let extent_x = (half_width * cos).abs() + (half_height * sin).abs();
+        let extent_y = (half_width * sin).abs() + (half_height * cos).abs();
+        let rect = Recti::new(
+            (center_x - extent_x * scale).floor() as i32,
+            (center_y - extent_y * scale).floor() as i32,
+            (extent_x * scale * 2.0).ceil() as u32 + 1,
+            (extent_y * scale * 2.0).ceil() as u32 + 1,
+        )
+        .intersection(self.clip_rect);
+        if rect.is_empty() {
+            return;
+        }
+
+        for yi in rect.top()..=rect.bottom() {
+            for xi in rect.left()..=rect.right() {
+                let dx = xi as f64 + 0.5 - center_x;
+                let dy = yi as f64 + 0.5 - center_y;
+                let u = (dx * cos + dy * sin) / scale + half_width;
+                let v = (dy * cos - dx * sin) / scale + half_height;
+                if u < 0.0 || v < 0.0 || u >= width as f64 || v >= height as f64 {
+                    continue;
+                }
+                let mut u = u as i32;
+                let mut v = v as i32;
+                if flip_x {
+                    u = width - 1 - u;
+                }
+                if flip_y {
+                    v = height - 1 - v;
+                }
+                let value_x = canvas_x + u;
+                let value_y = canvas_y + v;
+                if !canvas.self_rect.contains_point(value_x, value_y) {
+                    continue;
+                }
+                let value = canvas.read_data(value_x, value_y);
+                if let Some(transparent) = transparent {
+                    if value == transparent {
+                        continue;
+                    }
+                }
+                let value = palette.map_or(value, |palette| palette[value.to_index()]);
+                self.write_data(xi, yi, value);
+            }
+        }
+    }
+
+    fn draw_line(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, value: T) {
+        for (x, y) in Self::line_points(x1, y1, x2, y2) {
+            self.write_clipped_data(x, y, value);
+        }
+    }
+
+    fn line_points(x1: i32, y1: i32, x2: i32, y2: i32) -> Vec<(i32, i32)> {
+        if x1 == x2 && y1 == y2 {
+            vec![(x1, y1)]
+        } else if (x1 - x2).abs() > (y1 - y2).abs() {
+            let (start_x, start_y, end_x, end_y) = if x1 < x2 {
+                (x1, y1, x2, y2)
+            } else {
+                (x2, y2, x1, y1)
+            };
+            let length = end_x - start_x + 1;
+            let alpha = (end_y - start_y) as f64 / (end_x - start_x) as f64;
+            (0..length)
+                .map(|xi| (start_x + xi, start_y + as_i32(alpha * xi as f64)))
+                .collect()
+        } else {
+            let (start_x, start_y, end_x, end_y) = if y1 < y2 {
+                (x1, y1, x2, y2)
+            } else {
+                (x2, y2, x1, y1)
+            };
+            let length = end_y - start_y + 1;
+            let alpha = (end_x - start_x) as f64 / (end_y - start_y) as f64;
+            (0..length)
+                .map(|yi| (start_x + as_i32(alpha * yi as f64), start_y + yi))
+                .collect()
+        }
+    }
+
+    fn polyline_points(points: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        // Each segment skips its start, which the previous segment already wrote, and a
+        // closing segment also skips its end so that the first vertex is written once
+        let mut polyline_points: Vec<(i32, i32)> = points.first().copied().into_iter().collect();
+        let num_segments = points.len().saturating_sub(1);
+        for (i, segment) in points.windows(2).enumerate() {
+            let (start, end) = (segment[0], segment[1]);
+            if start == end {
+                continue;
+            }
+            let is_closing = i + 1 == num_segments && end == points[0];
+            polyline_points.extend(
+                Self::line_points(start.0, start.1, end.0, end.1)
+                    .into_iter()
+                    .filter(|point| *point != start && !(is_closing && *point == end)),
+            );
+        }
+        polyline_points
+    }
+
+    fn draw_polyline(&mut self, points: &[(i32, i32)], value: T) {
+        for (x, y) in Self::polyline_points(points) {
+            self.write_clipped_data(x, y, value);
+        }
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        for row in &mut self.data {
+            row.reverse();
+        }
+        self.mark_dirty(self.self_rect);
+    }
+
+    pub fn flip_vertical(&mut self) {
+        self.data.reverse();
+        self.mark_dirty(self.self_rect);
+    }
+
+    pub fn rotate90(&mut self, times: u32) {
+        // Rotates clockwise, and quarter turns swap the width and height
+        match times % 4 {
+            0 => return,
+            2 => {
+                self.flip_horizontal();
+                self.flip_vertical();
+                return;
+            }
+            _ => {}
+        }
+        let is_clockwise = times % 4 == 1;
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+        let mut rotated = vec![Vec::with_capacity(height); width];
+        for (x, rotated_row) in rotated.iter_mut().enumerate() {
+            for y in 0..height {
+                rotated_row.push(if is_clockwise {
+                    self.data[height - 1 - y][x]
+                } else {
+                    self.data[y][width - 1 - x]
+                });
+            }
+        }
+        self.data = rotated;
+        self.self_rect = Recti::new(0, 0, height as u32, width as u32);
+        self.clip_rect = self.self_rect;
+        if self.dirty_region.is_some() {
+            self.set_dirty_tracking(true);
+            self.mark_dirty(self.self_rect);
+        }
+    }
+
+    pub fn set_dirty_tracking(&mut self, is_enabled: bool) {
+        self.dirty_region = is_enabled.then(|| DirtyRegion::new(self.width(), self.height()));
+    }
+
+    pub fn mark_dirty(&mut self, rect: Recti) {
+        if let Some(dirty_region) = &mut self.dirty_region {
+            dirty_region.mark_rect(rect);
+        }
+    }
+
+    pub fn take_dirty_rects(&mut self) -> Option<Vec<Recti>> {
+        self.dirty_region.as_mut().map(DirtyRegion::take_rects)
+    }
+
     fn read_data(&self, x: i32, y: i32) -> T {
         self.data[y as usize][x as usize]
     }
 
     fn write_data(&mut self, x: i32, y: i32, value: T) {
         self.data[y as usize][x as usize] = value;
+        if let Some(dirty_region) = &mut self.dirty_region {
+            dirty_region.mark(x, y);
+        }
     }
 
     fn write_clipped_data(&mut self, x: i32, y: i32, value: T) {
-        if self.clip_rect.contains(x, y) {
-            self.data[y as usize][x as usize] = value;
+        if self.clip_rect.contains_point(x, y) {
+            self.write_data(x, y, value);
+        }
+    }
+
+    fn write_pattern_data(&mut self, x: i32, y: i32, value: T) {
+        let bit = 15 - ((y & 3) * 4 + (x & 3));
+        let is_set = self.fill_pattern & (1 << bit) != 0;
+        let value = match self.fill_pattern_values {
+            Some((value1, value2)) => {
+                if is_set {
+                    value1
+                } else {
+                    value2
+                }
+            }
+            None if is_set => value,
+            None => return,
+        };
+        self.write_data(x, y, value);
+    }
+
+    fn write_clipped_pattern_data(&mut self, x: i32, y: i32, value: T) {
+        if self.clip_rect.contains_point(x, y) {
+            self.write_pattern_data(x, y, value);
+        }
+    }
+
+    fn snapped_sin_cos(angle: f64) -> (f64, f64) {
+        let snap = |value: f64| {
+            if (value - value.round()).abs() < 1e-10 {
+                value.round()
+            } else {
+                value
+            }
+        };
+        let (sin, cos) = angle.sin_cos();
+        (snap(sin), snap(cos))
+    }
+
+    fn edge_function(x1: i32, y1: i32, x2: i32, y2: i32, x: i32, y: i32) -> i64 {
+        (x2 - x1) as i64 * (y - y1) as i64 - (y2 - y1) as i64 * (x - x1) as i64
+    }
+
+    fn top_left_bias(x1: i32, y1: i32, x2: i32, y2: i32) -> i64 {
+        let dx = x2 - x1;
+        let dy = y2 - y1;
+        if dy < 0 || dy == 0 && dx > 0 {
+            1
+        } else {
+            0
         }
     }
 
@@ -423,37 +872,62 @@ impl<T: Copy + PartialEq + Default + ToIndex> Canvas<T> {
         (x1, y1, x2, y2)
     }
 
-    fn fill_rec(&mut self, x: i32, y: i32, value: T, dst_value: T) {
-        if self.read_data(x, y) != dst_value {
-            return;
+    fn fill_span(&mut self, x: i32, y: i32, value: T, dst_value: T) {
+        let left_bound = self.clip_rect.left();
+        let right_bound = self.clip_rect.right();
+        let top_bound = self.clip_rect.top();
+        let bottom_bound = self.clip_rect.bottom();
+        // Visited pixels carry the current generation, so the marks are reused
+        // across fills without clearing them or allocating a grid each time
+        let width = self.width() as usize;
+        let num_pixels = width * self.height() as usize;
+        if self.fill_marks.len() != num_pixels {
+            self.fill_marks = vec![0; num_pixels];
+            self.fill_generation = 0;
         }
-        let mut xi = x;
-        while xi >= self.clip_rect.left() {
-            if self.read_data(xi, y) != dst_value {
-                break;
-            }
-            self.write_data(xi, y, value);
-            if y > self.clip_rect.top() {
-                self.fill_rec(xi, y - 1, value, dst_value)
+        self.fill_generation = self.fill_generation.wrapping_add(1);
+        if self.fill_generation == 0 {
+            self.fill_marks.fill(0);
+            self.fill_generation = 1;
+        }
+        let generation = self.fill_generation;
+        let is_target = |canvas: &Self, x: i32, y: i32| {
+            canvas.fill_marks[y as usize * width + x as usize] != generation
+                && canvas.read_data(x, y) == dst_value
+        };
+        let mut stack = vec![(x, y)];
+        while let Some((x, y)) = stack.pop() {
+            if !is_target(self, x, y) {
+                continue;
             }
-            if y < self.clip_rect.bottom() {
-                self.fill_rec(xi, y + 1, value, dst_value)
+            let mut left = x;
+            while left > left_bound && is_target(self, left - 1, y) {
+                left -= 1;
             }
-            xi -= 1;
-        }
-        let mut xi = x + 1;
-        while xi <= self.clip_rect.right() {
-            if self.read_data(xi, y) != dst_value {
-                break;
+            let mut right = x;
+            while right < right_bound && is_target(self, right + 1, y) {
+                right += 1;
             }
-            self.write_data(xi, y, value);
-            if y > self.clip_rect.top() {
-                self.fill_rec(xi, y - 1, value, dst_value)
+            for xi in left..=right {
+                self.fill_marks[y as usize * width + xi as usize] = generation;
+                self.write_pattern_data(xi, y, value);
             }
-            if y < self.clip_rect.bottom() {
-                self.fill_rec(xi, y + 1, value, dst_value)
+            for yi in [y - 1, y + 1] {
+                if yi < top_bound || yi > bottom_bound {
+                    continue;
+                }
+                let mut is_in_span = false;
+                for xi in left..=right {
+                    if is_target(self, xi, yi) {
+                        if !is_in_span {
+                            stack.push((xi, yi));
+                            is_in_span = true;
+                        }
+                    } else {
+                        is_in_span = false;
+                    }
+                }
             }
-            xi += 1;
         }
     }
 }
@@ -475,10 +949,10 @@ impl CopyArea {
     pub fn new(
         dst_x: i32,
         dst_y: i32,
-        dst_rect: RectArea,
+        dst_rect: Recti,
         src_x: i32,
         src_y: i32,
-        src_rect: RectArea,
+        src_rect: Recti,
         width: i32,
         height: i32,
     ) -> Self {
@@ -487,22 +961,37 @@ impl CopyArea {
         let width = width.abs();
         let height = height.abs();
 
-        let left_cut = max(max(src_rect.left() - src_x, dst_rect.left() - dst_x), 0);
-        let top_cut = max(max(src_rect.top() - src_y, dst_rect.top() - dst_y), 0);
-        let right_cut = max(
-            max(
-                src_x + width - 1 - src_rect.right(),
-                dst_x + width - 1 - dst_rect.right(),
-            ),
-            0,
-        );
-        let bottom_cut = max(
-            max(
-                src_y + height - 1 - src_rect.bottom(),
-                dst_y + height - 1 - dst_rect.bottom(),
-            ),
-            0,
-        );
+        let src_left_cut = max(src_rect.left() - src_x, 0);
+        let src_top_cut = max(src_rect.top() - src_y, 0);
+        let src_right_cut = max(src_x + width - 1 - src_rect.right(), 0);
+        let src_bottom_cut = max(src_y + height - 1 - src_rect.bottom(), 0);
+        let dst_left_cut = max(dst_rect.left() - dst_x, 0);
+        let dst_top_cut = max(dst_rect.top() - dst_y, 0);
+        let dst_right_cut = max(dst_x + width - 1 - dst_rect.right(), 0);
+        let dst_bottom_cut = max(dst_y + height - 1 - dst_rect.bottom(), 0);
+
+        let (left_cut, right_cut) = if flip_x {
+            (
+                max(dst_left_cut, src_right_cut),
+                max(dst_right_cut, src_left_cut),
+            )
+        } else {
+            (
+                max(dst_left_cut, src_left_cut),
+                max(dst_right_cut, src_right_cut),
+            )
+        };
+        let (top_cut, bottom_cut) = if flip_y {
+            (
+                max(dst_top_cut, src_bottom_cut),
+                max(dst_bottom_cut, src_top_cut),
+            )
+        } else {
+            (
+                max(dst_top_cut, src_top_cut),
+                max(dst_bottom_cut, src_bottom_cut),
+            )
+        };
 
         let width = max(width - left_cut - right_cut, 0);
         let height = max(height - top_cut - bottom_cut, 0);
@@ -523,3 +1012,689 @@ impl CopyArea {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::types::Color;
+
+    fn canvas_from(rows: &[&[Color]]) -> Canvas<Color> {
+        let mut canvas = Canvas::new(rows[0].len() as u32, rows.len() as u32);
+        for (y, row) in rows.iter().enumerate() {
+            canvas.data[y] = row.to_vec();
+        }
+        canvas
+    }
+
+    fn rotated(rotate: f64) -> Vec<Vec<Color>> {
+        let src = canvas_from(&[&[1, 2, 3], &[4, 5, 6], &[7, 8, 9]]);
+        let mut dst = Canvas::new(3, 3);
+        dst.blt_transform(0.0, 0.0, &src, 0.0, 0.0, 3.0, 3.0, None, None, rotate, 1.0);
+        dst.data
+    }
+
+    fn flipped(x: f64, u: f64, width: f64, height: f64) -> Vec<Vec<Color>> {
+        let src = canvas_from(&[&[1, 2, 3], &[4, 5, 6]]);
+        let mut dst = Canvas::new(4, 3);
+        dst.blt(x, 0.0, &src, u, 0.0, width, height, Some(5), None);
+        dst.data
+    }
+
+    #[test]
+    fn blt_opaque() {
+        let src = canvas_from(&[&[1, 2, 3], &[4, 5, 6]]);
+        let palette: Vec<Color> = (0..8).collect();
+        for (x, y, u, width, height) in [
+            (0.0, 0.0, 0.0, 3.0, 2.0),
+            (-1.0, 1.0, 0.0, 3.0, 2.0),
+            (2.0, -1.0, 1.0, 2.0, 2.0),
+            (1.0, 0.0, 0.0, 3.0, -2.0),
+            (1.0, 0.0, 0.0, -3.0, 2.0),
+        ] {
+            let mut fast = Canvas::new(4, 3);
+            fast.blt(x, y, &src, u, 0.0, width, height, None, None);
+            let mut slow = Canvas::new(4, 3);
+            slow.blt(x, y, &src, u, 0.0, width, height, None, Some(&palette));
+            assert_eq!(fast.data, slow.data);
+        }
+        let mut dst = Canvas::new(4, 3);
+        dst.blt(-1.0, 1.0, &src, 0.0, 0.0, 3.0, 2.0, None, None);
+        assert_eq!(to_pattern(&dst), ["0000", "2300", "5600"]);
+    }
+
+    #[test]
+    fn blt_flip() {
+        let empty = vec![0; 4];
+        assert_eq!(
+            flipped(0.0, 0.0, 3.0, 2.0),
+            vec![vec![1, 2, 3, 0], vec![4, 0, 6, 0], empty.clone()]
+        );
+        assert_eq!(
+            flipped(0.0, 0.0, -3.0, 2.0),
+            vec![vec![3, 2, 1, 0], vec![6, 0, 4, 0], empty.clone()]
+        );
+        assert_eq!(
+            flipped(0.0, 0.0, 3.0, -2.0),
+            vec![vec![4, 0, 6, 0], vec![1, 2, 3, 0], empty.clone()]
+        );
+        assert_eq!(
+            flipped(0.0, 0.0, -3.0, -2.0),
+            vec![vec![6, 0, 4, 0], vec![3, 2, 1, 0], empty]
+        );
+    }
+
+    #[test]
+    fn blt_flip_clipping() {
+        let empty = vec![0; 4];
+        assert_eq!(
+            flipped(-1.0, 0.0, -3.0, 2.0),
+            vec![vec![2, 1, 0, 0], vec![0, 4, 0, 0], empty.clone()]
+        );
+        assert_eq!(
+            flipped(2.0, 0.0, -3.0, 2.0),
+            vec![vec![0, 0, 3, 2], vec![0, 0, 6, 0], empty.clone()]
+        );
+        assert_eq!(
+            flipped(0.0, -1.0, -3.0, 2.0),
+            vec![vec![2, 1, 0, 0], vec![0, 4, 0, 0], empty.clone()]
+        );
+        assert_eq!(
+            flipped(0.0, 1.0, -3.0, 2.0),
+            vec![vec![0, 3, 2, 0], vec![0, 6, 0, 0], empty]
+        );
+    }
+
+    #[test]
+    fn pset_slice() {
+        let points: Vec<(i32, i32)> = (0..200)
+            .map(|i| ((i * 37) % 23 - 5, (i * 11) % 19 - 4))
+            .collect();
+        let mut canvas1 = Canvas::new(12, 10);
+        let mut canvas2 = Canvas::new(12, 10);
+        for canvas in [&mut canvas1, &mut canvas2] {
+            canvas.camera(-2.0, 1.0);
+            canvas.clip(1.0, 2.0, 9.0, 6.0);
+        }
+        for &(x, y) in &points {
+            canvas1.pset(x as f64, y as f64, 3);
+        }
+        canvas2.pset_slice(&points, 3);
+        assert_eq!(canvas1.data, canvas2.data);
+
+        let colored: Vec<(i32, i32, Color)> = points
+            .iter()
+            .enumerate()
+            .map(|(i, &(x, y))| (x, y, (i % 16) as Color))
+            .collect();
+        for &(x, y, value) in &colored {
+            canvas1.pset(x as f64, y as f64, value);
+        }
+        canvas2.pset_points(colored.iter().copied());
+        assert_eq!(canvas1.data, canvas2.data);
+
+        canvas2.pset_slice(&[(i32::MIN, i32::MAX), (i32::MAX, i32::MIN)], 15);
+        assert_eq!(canvas1.data, canvas2.data);
+    }
+
+    #[test]
+    fn clip() {
+        let mut canvas = Canvas::new(6, 4);
+        canvas.clip(-2.0, -2.0, 20.0, 20.0);
+        assert_eq!(canvas.clip_rect, canvas.self_rect);
+        canvas.clip(4.0, 0.0, 2.0, 2.0);
+        canvas.clip(1.0, 1.0, 3.0, 2.0);
+        canvas.rect(-1.0, -1.0, 10.0, 10.0, 1);
+        canvas.line(0.0, 3.0, 5.0, 0.0, 2);
+        canvas.circ(5.0, 1.0, 1.0, 3);
+        canvas.pset(0.0, 0.0, 4);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["000000", "011200", "022100", "000000"]
+        );
+        canvas.clip0();
+        canvas.pset(0.0, 0.0, 4);
+        assert_eq!(canvas.data[0][0], 4);
+    }
+
+    #[test]
+    fn clip_blt() {
+        let src = canvas_from(&[&[1, 2, 3], &[4, 5, 6]]);
+        let mut dst = Canvas::new(4, 3);
+        dst.clip(1.0, 0.0, 2.0, 2.0);
+        dst.blt(-1.0, 0.0, &src, 0.0, 0.0, 3.0, 2.0, None, None);
+        dst.blt(2.0, 1.0, &src, 0.0, 0.0, 3.0, 2.0, None, None);
+        assert_eq!(to_pattern(&dst), ["0300", "0610", "0000"]);
+    }
+
+    fn draw_scene(canvas: &mut Canvas<Color>, offset_x: f64, offset_y: f64) {
+        let src = canvas_from(&[&[1, 2], &[3, 4]]);
+        canvas.rect(offset_x - 2.0, offset_y + 1.0, 4.0, 3.0, 5);
+        canvas.line(offset_x, offset_y, offset_x + 7.0, offset_y + 5.0, 6);
+        canvas.circb(offset_x + 4.0, offset_y + 4.0, 2.0, 7);
+        canvas.tri(
+            offset_x + 1.0,
+            offset_y + 6.0,
+            offset_x + 5.0,
+            offset_y + 7.0,
+            offset_x + 2.0,
+            offset_y + 3.0,
+            8,
+        );
+        canvas.blt(
+            offset_x + 6.0,
+            offset_y - 1.0,
+            &src,
+            0.0,
+            0.0,
+            2.0,
+            2.0,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn camera() {
+        let mut shifted = Canvas::new(8, 8);
+        draw_scene(&mut shifted, -3.0, 2.0);
+        let mut canvas = Canvas::new(8, 8);
+        canvas.camera(3.0, -2.0);
+        draw_scene(&mut canvas, 0.0, 0.0);
+        assert_eq!(canvas.data, shifted.data);
+
+        canvas.clip(0.0, 0.0, 2.0, 2.0);
+        canvas.cls(0);
+        canvas.rect(2.0, -3.0, 4.0, 4.0, 1);
+        assert_eq!(
+            to_pattern(&canvas)[..3],
+            ["11000000", "11000000", "00000000"]
+        );
+        canvas.camera0();
+        canvas.clip0();
+        canvas.rect(-2.0, -2.0, 3.0, 3.0, 2);
+        assert_eq!(to_pattern(&canvas)[..2], ["21000000", "11000000"]);
+        assert_eq!(canvas.data[7][7], 0);
+    }
+
+    fn to_pattern(canvas: &Canvas<Color>) -> Vec<String> {
+        canvas
+            .data
+            .iter()
+            .map(|row| row.iter().map(|value| (b'0' + *value) as char).collect())
+            .collect()
+    }
+
+    #[test]
+    fn elli() {
+        let mut canvas = Canvas::new(5, 7);
+        canvas.elli(1.0, 1.0, 3.0, 5.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["00000", "00100", "01110", "01110", "01110", "00100", "00000"]
+        );
+        let mut canvas = Canvas::new(6, 4);
+        canvas.elli(1.0, 1.0, 4.0, 2.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["000000", "011110", "011110", "000000"]
+        );
+    }
+
+    #[test]
+    fn ellib() {
+        let mut canvas = Canvas::new(5, 7);
+        canvas.ellib(1.0, 1.0, 3.0, 5.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["00000", "00100", "01010", "01010", "01010", "00100", "00000"]
+        );
+        let mut canvas = Canvas::new(9, 5);
+        canvas.ellib(1.0, 1.0, 7.0, 3.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            [
+                "000000000",
+                "001111100",
+                "010000010",
+                "001111100",
+                "000000000"
+            ]
+        );
+    }
+
+    #[test]
+    fn elli_degenerate() {
+        let mut canvas = Canvas::new(3, 6);
+        canvas.elli(1.0, 1.0, 1.0, 4.0, 1);
+        canvas.ellib(0.0, 5.0, 3.0, 0.0, 2);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["000", "010", "010", "010", "010", "222"]
+        );
+        let mut canvas = Canvas::new(3, 3);
+        canvas.elli(1.0, 1.0, 0.0, 0.0, 1);
+        assert_eq!(to_pattern(&canvas), ["000", "010", "000"]);
+    }
+
+    #[test]
+    fn elli_clipping() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.clip(0.0, 0.0, 4.0, 3.0);
+        canvas.elli(-2.0, 1.0, 5.0, 3.0, 1);
+        canvas.ellib(2.0, -3.0, 7.0, 7.0, 2);
+        assert_eq!(to_pattern(&canvas), ["0020", "1120", "1112", "0000"]);
+    }
+
+    #[test]
+    fn tri() {
+        let mut canvas = Canvas::new(6, 6);
+        canvas.tri(1.0, 1.0, 5.0, 1.0, 1.0, 5.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["000000", "011110", "011100", "011000", "010000", "000000"]
+        );
+    }
+
+    #[test]
+    fn tri_shared_edge() {
+        for (x1, y1, x2, y2) in [(0.0, 0.0, 5.0, 4.0), (5.0, 0.0, 0.0, 4.0)] {
+            let (x3, y3, x4, y4) = if x1 == 0.0 {
+                (5.0, 0.0, 0.0, 4.0)
+            } else {
+                (0.0, 0.0, 5.0, 4.0)
+            };
+            let mut canvas1 = Canvas::new(8, 8);
+            let mut canvas2 = Canvas::new(8, 8);
+            canvas1.tri(x1, y1, x2, y2, x3, y3, 1);
+            canvas2.tri(x2, y2, x1, y1, x4, y4, 1);
+            for y in 0..8 {
+                for x in 0..8 {
+                    let count = canvas1.data[y][x] + canvas2.data[y][x];
+                    let expected = if x < 5 && y < 4 { 1 } else { 0 };
+                    assert_eq!(count, expected, "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tri_degenerate() {
+        let mut canvas = Canvas::new(5, 3);
+        canvas.tri(0.0, 1.0, 4.0, 1.0, 2.0, 1.0, 1);
+        canvas.tri(4.0, 2.0, 4.0, 2.0, 4.0, 2.0, 2);
+        assert_eq!(to_pattern(&canvas), ["00000", "11111", "00002"]);
+    }
+
+    #[test]
+    fn tri_clipping() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.clip(1.0, 1.0, 3.0, 3.0);
+        canvas.tri(-4.0, -4.0, 12.0, -4.0, -4.0, 12.0, 1);
+        assert_eq!(to_pattern(&canvas), ["0000", "0111", "0111", "0111"]);
+    }
+
+    #[test]
+    fn fill() {
+        let mut canvas = Canvas::new(6, 5);
+        canvas.rectb(0.0, 0.0, 6.0, 5.0, 1);
+        canvas.line(3.0, 0.0, 3.0, 3.0, 1);
+        canvas.pset(1.0, 2.0, 1);
+        canvas.fill(1.0, 1.0, 2);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["111111", "122101", "112101", "122101", "111111"]
+        );
+        canvas.fill(4.0, 2.0, 0);
+        canvas.fill(3.0, 2.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["111111", "122101", "112101", "122101", "111111"]
+        );
+    }
+
+    #[test]
+    fn fill_clipping() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.clip(1.0, 0.0, 2.0, 3.0);
+        canvas.fill(1.0, 1.0, 3);
+        assert_eq!(to_pattern(&canvas), ["0330", "0330", "0330", "0000"]);
+        canvas.fill(0.0, 0.0, 4);
+        assert_eq!(canvas.data[0][0], 0);
+    }
+
+    #[test]
+    fn fill_marks() {
+        let mut canvas = Canvas::new(4, 3);
+        canvas.fill(0.0, 0.0, 1);
+        canvas.fill(0.0, 0.0, 2);
+        assert_eq!(to_pattern(&canvas), ["2222", "2222", "2222"]);
+        assert_eq!(canvas.fill_generation, 2);
+
+        // The marks are cleared once the generation wraps around
+        canvas.fill_generation = u32::MAX;
+        canvas.fill(0.0, 0.0, 3);
+        assert_eq!(to_pattern(&canvas), ["3333", "3333", "3333"]);
+        assert_eq!(canvas.fill_generation, 1);
+
+        canvas.rotate90(1);
+        canvas.fill(0.0, 0.0, 4);
+        assert_eq!(to_pattern(&canvas), ["444", "444", "444", "444"]);
+    }
+
+    #[test]
+    fn fill_large() {
+        let mut canvas = Canvas::new(256, 256);
+        for i in (0..256).step_by(4) {
+            canvas.line(i as f64, 1.0, i as f64, 255.0, 1);
+            canvas.line(i as f64 + 2.0, 0.0, i as f64 + 2.0, 254.0, 1);
+        }
+        canvas.fill(1.0, 0.0, 2);
+        assert!(canvas
+            .data
+            .iter()
+            .all(|row| row.iter().all(|value| *value != 0)));
+    }
+
+    #[test]
+    fn polyline() {
+        let mut canvas = Canvas::new(5, 4);
+        canvas.polyline(
+            &[(0.0, 0.0), (4.0, 0.0), (4.0, 3.0), (4.0, 3.0), (0.0, 3.0)],
+            1,
+        );
+        assert_eq!(to_pattern(&canvas), ["11111", "00001", "00001", "11111"]);
+        let mut canvas = Canvas::new(3, 3);
+        canvas.polyline(&[(-2.0, 1.0), (1.0, 1.0), (1.0, 5.0)], 1);
+        assert_eq!(to_pattern(&canvas), ["000", "110", "010"]);
+    }
+
+    #[test]
+    fn polyline_matches_lines() {
+        let points = [(1.0, 1.0), (6.0, 2.0), (3.0, 6.0), (1.0, 1.0)];
+        let mut canvas1 = Canvas::new(8, 8);
+        let mut canvas2 = Canvas::new(8, 8);
+        canvas1.polyline(&points, 1);
+        for segment in points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (segment[0], segment[1]);
+            canvas2.line(x1, y1, x2, y2, 1);
+        }
+        assert_eq!(canvas1.data, canvas2.data);
+    }
+
+    #[test]
+    fn closed_polyline() {
+        let points = [(1, 1), (6, 2), (3, 6), (1, 1)];
+        let polyline_points = Canvas::<Color>::polyline_points(&points);
+        let unique_points: HashSet<(i32, i32)> = polyline_points.iter().copied().collect();
+        assert_eq!(unique_points.len(), polyline_points.len());
+        assert!(unique_points.contains(&(1, 1)));
+        assert!(unique_points.contains(&(3, 6)));
+    }
+
+    #[test]
+    fn bezier() {
+        let mut canvas = Canvas::new(9, 5);
+        canvas.bezier(0.0, 4.0, 4.0, -4.0, 8.0, 4.0, 1);
+        assert_eq!(canvas.data[4][0], 1);
+        assert_eq!(canvas.data[0][4], 1);
+        assert_eq!(canvas.data[4][8], 1);
+        for y in 0..5 {
+            for x in 0..4 {
+                assert_eq!(canvas.data[y][x], canvas.data[y][8 - x]);
+            }
+        }
+        let mut canvas = Canvas::new(3, 3);
+        canvas.bezier(1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1);
+        assert_eq!(to_pattern(&canvas), ["000", "010", "000"]);
+    }
+
+    #[test]
+    fn line_w() {
+        let mut canvas1 = Canvas::new(8, 8);
+        let mut canvas2 = Canvas::new(8, 8);
+        canvas1.line_w(1.0, 2.0, 6.0, 5.0, 1.0, 1);
+        canvas2.line(1.0, 2.0, 6.0, 5.0, 1);
+        assert_eq!(canvas1.data, canvas2.data);
+
+        let mut canvas = Canvas::new(6, 5);
+        canvas.line_w(1.0, 2.0, 4.0, 2.0, 3.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["000000", "111111", "111111", "111111", "000000"]
+        );
+        let mut canvas = Canvas::new(4, 4);
+        canvas.line_w(1.0, 1.0, 1.0, 1.0, 2.0, 1);
+        assert_eq!(to_pattern(&canvas), ["0000", "0110", "0110", "0000"]);
+    }
+
+    #[test]
+    fn rectb_w() {
+        let mut canvas1 = Canvas::new(8, 8);
+        let mut canvas2 = Canvas::new(8, 8);
+        canvas1.rectb_w(1.0, 2.0, 6.0, 5.0, 1.0, 1);
+        canvas2.rectb(1.0, 2.0, 6.0, 5.0, 1);
+        assert_eq!(canvas1.data, canvas2.data);
+
+        let mut canvas = Canvas::new(9, 8);
+        canvas.rectb_w(1.0, 1.0, 7.0, 6.0, 3.0, 1);
+        assert_eq!(
+            to_pattern(&canvas),
+            [
+                "111111111",
+                "111111111",
+                "111111111",
+                "111000111",
+                "111000111",
+                "111111111",
+                "111111111",
+                "111111111",
+            ]
+        );
+    }
+
+    #[test]
+    fn fillp() {
+        let mut canvas1 = Canvas::new(8, 4);
+        let mut canvas2 = Canvas::new(8, 4);
+        canvas1.fillp(0xffff);
+        canvas1.rect(1.0, 1.0, 6.0, 2.0, 1);
+        canvas2.rect(1.0, 1.0, 6.0, 2.0, 1);
+        assert_eq!(canvas1.data, canvas2.data);
+
+        let mut canvas = Canvas::new(8, 4);
+        canvas.fillp(0b1010_0101_1010_0101);
+        canvas.rect(0.0, 0.0, 3.0, 4.0, 1);
+        canvas.rect(3.0, 0.0, 5.0, 4.0, 2);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["10102020", "01020202", "10102020", "01020202"]
+        );
+        canvas.fillp_colors(3, 4);
+        canvas.cls(0);
+        assert_eq!(
+            to_pattern(&canvas),
+            ["34343434", "43434343", "34343434", "43434343"]
+        );
+        canvas.fillp0();
+        canvas.cls(5);
+        assert_eq!(to_pattern(&canvas)[0], "55555555");
+    }
+
+    #[test]
+    fn fillp_fill() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.fillp(0xf0f0);
+        canvas.fill(0.0, 0.0, 1);
+        assert_eq!(to_pattern(&canvas), ["1111", "0000", "1111", "0000"]);
+        canvas.fillp(0xffff);
+        canvas.fill(0.0, 0.0, 2);
+        assert_eq!(to_pattern(&canvas), ["2222", "0000", "1111", "0000"]);
+    }
+
+    #[test]
+    fn blt_transform_right_angles() {
+        assert_eq!(
+            rotated(90.0),
+            vec![vec![7, 4, 1], vec![8, 5, 2], vec![9, 6, 3]]
+        );
+        assert_eq!(
+            rotated(180.0),
+            vec![vec![9, 8, 7], vec![6, 5, 4], vec![3, 2, 1]]
+        );
+        assert_eq!(
+            rotated(270.0),
+            vec![vec![3, 6, 9], vec![2, 5, 8], vec![1, 4, 7]]
+        );
+        assert_eq!(rotated(-90.0), rotated(270.0));
+    }
+
+    #[test]
+    fn blt_transform_bounding_box() {
+        let mut src = Canvas::new(8, 8);
+        src.cls(1);
+        let mut dst = Canvas::new(32, 32);
+        dst.blt_transform(12.0, 12.0, &src, 0.0, 0.0, 8.0, 8.0, None, None, 30.0, 1.5);
+        let extent = (4.0 * 30f64.to_radians().cos() + 4.0 * 30f64.to_radians().sin()) * 1.5;
+        for (y, row) in dst.data.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                if *value != 0 {
+                    assert!((x as f64 + 0.5 - 16.0).abs() <= extent);
+                    assert!((y as f64 + 0.5 - 16.0).abs() <= extent);
+                }
+            }
+        }
+        assert_eq!(dst.data[16][16], 1);
+        assert_eq!(dst.data[16][22], 1);
+        assert_eq!(dst.data[16][23], 0);
+    }
+
+    #[test]
+    fn blt_transform_scale() {
+        let src = canvas_from(&[&[1, 0], &[2, 3]]);
+        let mut dst = Canvas::new(4, 4);
+        dst.cls(5);
+        dst.blt_transform(1.0, 1.0, &src, 0.0, 0.0, 2.0, 2.0, Some(0), None, 0.0, 2.0);
+        assert_eq!(
+            dst.data,
+            vec![
+                vec![1, 1, 5, 5],
+                vec![1, 1, 5, 5],
+                vec![2, 2, 3, 3],
+                vec![2, 2, 3, 3],
+            ]
+        );
+    }
+
+    #[test]
+    fn blt_transform_clipping() {
+        let src = canvas_from(&[&[1, 0], &[2, 3]]);
+        let mut dst = Canvas::new(4, 4);
+        dst.cls(5);
+        dst.blt_transform(-1.0, -1.0, &src, 0.0, 0.0, 2.0, 2.0, None, None, 0.0, 2.0);
+        assert_eq!(dst.data[0][0], 3);
+        assert_eq!(dst.data[1][1], 3);
+        assert_eq!(dst.data[0][2], 5);
+        assert_eq!(dst.data[2][0], 5);
+        dst.blt_transform(2.0, 2.0, &src, 0.0, 0.0, 2.0, 2.0, None, None, 45.0, 4.0);
+        dst.blt_transform(0.0, 0.0, &src, 1.0, 1.0, 2.0, 2.0, None, None, 90.0, 1.0);
+    }
+
+    #[test]
+    fn flip() {
+        let mut canvas = canvas_from(&[&[1, 2, 3], &[4, 5, 6]]);
+        canvas.flip_horizontal();
+        assert_eq!(canvas.data, vec![vec![3, 2, 1], vec![6, 5, 4]]);
+        canvas.flip_vertical();
+        assert_eq!(canvas.data, vec![vec![6, 5, 4], vec![3, 2, 1]]);
+
+        let mut canvas = canvas_from(&[&[1], &[2], &[3]]);
+        canvas.flip_horizontal();
+        assert_eq!(canvas.data, vec![vec![1], vec![2], vec![3]]);
+        canvas.flip_vertical();
+        assert_eq!(canvas.data, vec![vec![3], vec![2], vec![1]]);
+    }
+
+    #[test]
+    fn rotate90() {
+        let mut canvas = canvas_from(&[&[1, 2, 3], &[4, 5, 6]]);
+        canvas.rotate90(1);
+        assert_eq!(canvas.data, vec![vec![4, 1], vec![5, 2], vec![6, 3]]);
+        assert_eq!((canvas.width(), canvas.height()), (2, 3));
+        assert_eq!(canvas.clip_rect, Recti::new(0, 0, 2, 3));
+        canvas.rotate90(1);
+        assert_eq!(canvas.data, vec![vec![6, 5, 4], vec![3, 2, 1]]);
+        canvas.rotate90(1);
+        assert_eq!(canvas.data, vec![vec![3, 6], vec![2, 5], vec![1, 4]]);
+        canvas.rotate90(5);
+        assert_eq!(canvas.data, vec![vec![1, 2, 3], vec![4, 5, 6]]);
+
+        for (width, height) in [(1, 1), (1, 4), (4, 1), (2, 3), (3, 5), (5, 5)] {
+            let mut original = Canvas::new(width, height);
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    original.data[y][x] = (y * width as usize + x) as Color;
+                }
+            }
+            let copy = || {
+                let rows: Vec<&[Color]> = original.data.iter().map(Vec::as_slice).collect();
+                canvas_from(&rows)
+            };
+            for times in 0..8 {
+                let mut canvas = copy();
+                canvas.rotate90(times);
+                let size = if times % 2 == 0 {
+                    (width, height)
+                } else {
+                    (height, width)
+                };
+                assert_eq!((canvas.width(), canvas.height()), size);
+                assert!(canvas.data.iter().all(|row| row.len() == size.0 as usize));
+                canvas.rotate90(4 - times % 4);
+                assert_eq!(canvas.data, original.data);
+            }
+            let mut flipped = copy();
+            flipped.flip_horizontal();
+            flipped.flip_vertical();
+            let mut rotated = copy();
+            rotated.rotate90(1);
+            rotated.rotate90(1);
+            assert_eq!(rotated.data, flipped.data);
+        }
+
+        let mut canvas = canvas_from(&[&[1, 2, 3], &[4, 5, 6]]);
+        canvas.set_dirty_tracking(true);
+        canvas.take_dirty_rects();
+        canvas.rotate90(3);
+        assert_eq!(
+            canvas.take_dirty_rects(),
+            Some(vec![Recti::new(0, 0, 2, 3)])
+        );
+    }
+
+    #[test]
+    fn pick() {
+        let mut canvas = Canvas::<u8>::new(4, 3);
+        canvas.data[2][3] = 5;
+        canvas.camera(1.0, 1.0);
+        assert_eq!(canvas.pick(3.0, 2.0), Some(5));
+        assert_eq!(canvas.pick(0.0, 0.0), Some(0));
+        assert_eq!(canvas.pick(3.4, 2.4), Some(5));
+        for (x, y) in [(-1.0, 0.0), (4.0, 0.0), (0.0, 3.0), (1e20, 1e20)] {
+            assert_eq!(canvas.pick(x, y), None);
+        }
+    }
+
+    #[test]
+    fn find_bounds() {
+        let mut canvas = Canvas::<u8>::new(6, 5);
+        assert_eq!(canvas.find_bounds(3), None);
+        assert_eq!(canvas.find_bounds(0), Some(Recti::new(0, 0, 6, 5)));
+        canvas.data[1][4] = 3;
+        assert_eq!(canvas.find_bounds(3), Some(Recti::new(4, 1, 1, 1)));
+        canvas.data[3][2] = 3;
+        canvas.data[2][5] = 3;
+        assert_eq!(canvas.find_bounds(3), Some(Recti::new(2, 1, 4, 3)));
+    }
+}
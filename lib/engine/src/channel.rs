@@ -1,42 +1,141 @@
 use std::cmp::max;
+use std::f64::consts::{FRAC_PI_4, SQRT_2};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use blip_buf::BlipBuf;
 
 use crate::oscillator::Oscillator;
 use crate::settings::{
-    EFFECT_NONE, MAX_EFFECT, MAX_NOTE, MAX_TONE, MAX_VOLUME, NUM_CHANNELS, TONE_TRIANGLE,
+    EFFECT_NONE, EFFECT_SLIDE, MAX_CHANNEL_PITCH, MAX_CHANNEL_SPEED, MAX_EFFECT, MAX_NOTE,
+    MAX_TONE, MAX_VOLUME, MIN_CHANNEL_SPEED, NUM_CHANNELS, SAMPLE_RATE, TONE_TRIANGLE,
 };
 use crate::sound::{SharedSound, Sound};
 use crate::types::{Effect, Note, Tone, Volume};
 
+const NO_PLAY_POS: u64 = u64::MAX;
+
+pub struct PlayState {
+    play_pos: AtomicU64,
+    elapsed_samples: AtomicU64,
+}
+
+impl PlayState {
+    fn new() -> Self {
+        Self {
+            play_pos: AtomicU64::new(NO_PLAY_POS),
+            elapsed_samples: AtomicU64::new(0),
+        }
+    }
+
+    pub fn play_pos(&self) -> Option<(u32, u32)> {
+        match self.play_pos.load(Ordering::Relaxed) {
+            NO_PLAY_POS => None,
+            pos => Some(((pos >> 32) as u32, pos as u32)),
+        }
+    }
+
+    pub fn elapsed_samples(&self) -> u64 {
+        self.elapsed_samples.load(Ordering::Relaxed)
+    }
+
+    fn store(&self, play_pos: Option<(u32, u32)>, elapsed_samples: u64) {
+        let play_pos = play_pos.map_or(NO_PLAY_POS, |(sound_index, note_index)| {
+            ((sound_index as u64) << 32) | note_index as u64
+        });
+        self.play_pos.store(play_pos, Ordering::Relaxed);
+        self.elapsed_samples
+            .store(elapsed_samples, Ordering::Relaxed);
+    }
+}
+
+// Settings the game thread changes. It keeps its own copy to read back, since
+// the copy in the callback only ever changes through its commands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelParams {
+    pub volume: f64,
+    pub pan: f64,
+    pub speed: f64,
+    pub pitch: f64,
+    pub gain: u8,
+}
+
+impl Default for ChannelParams {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            pan: 0.0,
+            speed: 1.0,
+            pitch: 0.0,
+            gain: u8::MAX / NUM_CHANNELS as u8,
+        }
+    }
+}
+
+impl ChannelParams {
+    pub fn set_volume(&mut self, volume: f64) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn set_pan(&mut self, pan: f64) {
+        self.pan = pan.clamp(-1.0, 1.0);
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.clamp(MIN_CHANNEL_SPEED, MAX_CHANNEL_SPEED);
+    }
+
+    pub fn set_pitch(&mut self, semitones: f64) {
+        self.pitch = semitones.clamp(-MAX_CHANNEL_PITCH, MAX_CHANNEL_PITCH);
+    }
+}
+
 pub struct Channel {
     oscillator: Oscillator,
     sounds: Vec<Sound>,
     is_playing: bool,
     should_loop: bool,
+    loop_range: Option<(u32, u32)>,
     sound_index: u32,
     note_index: u32,
     tick_count: u32,
-    pub gain: u8,
+    tick_fraction: f64,
+    pub(crate) params: ChannelParams,
+    sample: Option<Arc<[f32]>>,
+    sample_pos: f64,
+    should_loop_sample: bool,
+    play_state: Arc<PlayState>,
+    play_count: u64,
+    rendered_pos: Option<(u32, u32)>,
+    rendered_play_count: u64,
+    elapsed_samples: u64,
 }
 
-pub type SharedChannel = shared_type!(Channel);
-
 impl Channel {
-    pub fn new() -> SharedChannel {
-        new_shared_type!(Self {
+    pub fn new() -> Self {
+        Self {
             oscillator: Oscillator::new(),
             sounds: Vec::new(),
             is_playing: false,
             should_loop: false,
+            loop_range: None,
             sound_index: 0,
             note_index: 0,
             tick_count: 0,
-            gain: u8::MAX / NUM_CHANNELS as u8,
-        })
+            tick_fraction: 1.0,
+            params: ChannelParams::default(),
+            sample: None,
+            sample_pos: 0.0,
+            should_loop_sample: false,
+            play_state: Arc::new(PlayState::new()),
+            play_count: 0,
+            rendered_pos: None,
+            rendered_play_count: 0,
+            elapsed_samples: 0,
+        }
     }
 
-    pub fn play_pos(&mut self) -> Option<(u32, u32)> {
+    pub fn play_pos(&self) -> Option<(u32, u32)> {
         if self.is_playing {
             Some((self.sound_index, self.note_index))
         } else {
@@ -44,16 +143,49 @@ impl Channel {
         }
     }
 
+    pub(crate) fn play_state(&self) -> Arc<PlayState> {
+        self.play_state.clone()
+    }
+
+    pub(crate) fn advance_play_state(&mut self, num_samples: usize) {
+        // Samples still buffered from before the last play or stop belong to
+        // the previous playback, so they don't move the reported position
+        if self.rendered_play_count != self.play_count || self.rendered_pos.is_none() {
+            return;
+        }
+        self.elapsed_samples += num_samples as u64;
+        self.play_state
+            .store(self.rendered_pos, self.elapsed_samples);
+    }
+
+    fn restart_play_state(&mut self) {
+        self.play_count += 1;
+        self.elapsed_samples = 0;
+        self.play_state.store(self.play_pos(), 0);
+    }
+
     pub fn play(&mut self, sounds: Vec<SharedSound>, start_tick: Option<u32>, should_loop: bool) {
-        let sounds: Vec<Sound> = sounds.iter().map(|sound| sound.lock().clone()).collect();
+        let sounds = sounds.iter().map(|sound| sound.lock().clone()).collect();
+        self.play_sounds(sounds, start_tick, should_loop);
+    }
+
+    pub(crate) fn play_sounds(
+        &mut self,
+        sounds: Vec<Sound>,
+        start_tick: Option<u32>,
+        should_loop: bool,
+    ) {
         if sounds.is_empty() || sounds.iter().all(|sound| sound.notes.is_empty()) {
             return;
         }
+        self.sample = None;
         self.sounds = sounds;
         self.should_loop = should_loop;
+        self.loop_range = None;
         self.sound_index = 0;
         self.note_index = 0;
         self.tick_count = start_tick.unwrap_or(0);
+        self.tick_fraction = 1.0;
         loop {
             let sound = &self.sounds[self.sound_index as usize];
             let sound_ticks = sound.notes.len() as u32 * sound.speed;
@@ -73,33 +205,138 @@ impl Channel {
             }
         }
         self.is_playing = true;
+        self.restart_play_state();
+    }
+
+    pub fn set_loop_range(&mut self, loop_range: Option<(u32, u32)>) {
+        // Ranges past the end of the sequence loop back from its last sound, and
+        // ranges of silent sounds are ignored so the sequencer can't spin on them
+        let num_sounds = self.sounds.len() as u32;
+        self.loop_range = loop_range
+            .filter(|(start_index, _)| *start_index < num_sounds)
+            .map(|(start_index, end_index)| (start_index, end_index.min(num_sounds - 1)))
+            .filter(|(start_index, end_index)| {
+                self.sounds[*start_index as usize..=*end_index as usize]
+                    .iter()
+                    .any(|sound| !sound.notes.is_empty())
+            });
+    }
+
+    pub fn set_volume(&mut self, volume: f64) {
+        self.params.set_volume(volume);
+    }
+
+    pub fn set_pan(&mut self, pan: f64) {
+        self.params.set_pan(pan);
+    }
+
+    pub fn set_gain(&mut self, gain: u8) {
+        self.params.gain = gain;
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        let prev_speed = self.params.speed;
+        self.params.set_speed(speed);
+        if self.is_playing {
+            // Stretch the sounding note so it still lasts until the next one starts
+            self.oscillator
+                .scale_duration(prev_speed / self.params.speed);
+        }
+    }
+
+    pub fn set_pitch(&mut self, semitones: f64) {
+        self.params.set_pitch(semitones);
+        self.oscillator.set_pitch_scale(self.pitch_scale());
+    }
+
+    fn pitch_scale(&self) -> f64 {
+        (self.params.pitch / 12.0).exp2()
     }
 
-    pub fn play1(&mut self, sound: SharedSound, start_tick: Option<u32>, should_loop: bool) {
-        self.play(vec![sound], start_tick, should_loop);
+    pub(crate) fn output_gains(&self, num_channels: usize) -> [f32; 2] {
+        if num_channels < 2 {
+            return [1.0, 0.0];
+        }
+        // Constant-power panning, scaled so the center matches mono output
+        let angle = (self.params.pan + 1.0) * FRAC_PI_4;
+        [(angle.cos() * SQRT_2) as f32, (angle.sin() * SQRT_2) as f32]
+    }
+
+    pub(crate) fn play_sample_data(&mut self, data: Arc<[f32]>, should_loop: bool) {
+        self.stop();
+        self.sample = Some(data);
+        self.sample_pos = 0.0;
+        self.should_loop_sample = should_loop;
     }
 
     pub fn stop(&mut self) {
         self.is_playing = false;
         self.oscillator.stop();
+        self.sample = None;
+        self.play_count += 1;
+        self.play_state.store(None, self.elapsed_samples);
+    }
+
+    pub(crate) fn mix_sample(&mut self, out: &mut [f32], num_channels: usize, sample_rate: u32) {
+        // The sample data is only released on the game thread, never here
+        let data = match &self.sample {
+            Some(data) if (self.sample_pos as usize) < data.len() => data,
+            _ => return,
+        };
+        // Samples are stored at the synth rate, so step through them to match the device
+        // Without time stretching, both speed and pitch change the playback rate
+        let step = SAMPLE_RATE as f64 / sample_rate as f64 * self.params.speed * self.pitch_scale();
+        let gain =
+            self.params.gain as f32 / u8::MAX as f32 * self.params.volume as f32 * i16::MAX as f32;
+        let gains = self.output_gains(num_channels);
+        for frame in out.chunks_exact_mut(num_channels) {
+            if self.sample_pos as usize >= data.len() {
+                if !self.should_loop_sample {
+                    break;
+                }
+                self.sample_pos %= data.len() as f64;
+            }
+            for (value, channel_gain) in frame.iter_mut().zip(&gains) {
+                *value += data[self.sample_pos as usize] * gain * channel_gain;
+            }
+            self.sample_pos += step;
+        }
     }
 
     pub(crate) fn update(&mut self, blip_buf: &mut BlipBuf) {
-        if !self.is_playing {
-            return;
+        self.update_sound(blip_buf);
+        self.rendered_pos = self.play_pos();
+        self.rendered_play_count = self.play_count;
+    }
+
+    fn update_sound(&mut self, blip_buf: &mut BlipBuf) {
+        // The sequence moves by more or less than one tick per rendered tick
+        // while the oscillator keeps running at the real clock rate
+        while self.is_playing && self.tick_fraction >= 1.0 {
+            self.tick_fraction -= 1.0;
+            self.advance_tick();
+        }
+        if self.is_playing {
+            self.tick_fraction += self.params.speed;
         }
+        self.oscillator.update(blip_buf, self.params.volume);
+    }
+
+    fn advance_tick(&mut self) {
         let mut sound = &self.sounds[self.sound_index as usize];
-        let speed = max(sound.speed, 1);
-        if self.tick_count % speed == 0 {
-            if self.tick_count > 0 {
-                self.note_index += 1;
-            }
+        if self.tick_count >= max(sound.speed, 1) {
+            self.note_index += 1;
+            self.tick_count = 0;
+        }
+        if self.tick_count == 0 {
             while self.note_index >= sound.notes.len() as u32 {
                 self.sound_index += 1;
                 self.note_index = 0;
-                if self.sound_index >= self.sounds.len() as u32 {
+                let (loop_start, loop_end) =
+                    self.loop_range.unwrap_or((0, self.sounds.len() as u32 - 1));
+                if self.sound_index > loop_end {
                     if self.should_loop {
-                        self.sound_index = 0;
+                        self.sound_index = loop_start;
                     } else {
                         self.stop();
                         return;
@@ -114,21 +351,31 @@ impl Channel {
             assert!(volume <= MAX_VOLUME, "invalid sound volume {}", volume);
             let tone = Self::circular_tone(&sound.tones, self.note_index);
             assert!(tone <= MAX_TONE, "invalid sound tone {}", tone);
-            let effect = Self::circular_effect(&sound.effects, self.note_index);
+            let mut effect = Self::circular_effect(&sound.effects, self.note_index);
             assert!(effect <= MAX_EFFECT, "invalid sound effect {}", effect);
-            let speed = max(sound.speed, 1);
+            // Notes are held for their real length so they join up at any speed
+            let duration = max(
+                (max(sound.speed, 1) as f64 / self.params.speed).round() as u32,
+                1,
+            );
+            // The first note of a sound has no previous note to slide from
+            if effect == EFFECT_SLIDE && self.note_index == 0 {
+                effect = EFFECT_NONE;
+            }
 
             if note >= 0 && volume > 0 {
+                self.oscillator.set_vibrato_depth(sound.vibrato_depth);
                 self.oscillator.play(
                     note as f64,
                     tone,
-                    (self.gain as f64 * volume as f64) / (u8::MAX as f64 * MAX_VOLUME as f64),
+                    (self.params.gain as f64 * volume as f64)
+                        / (u8::MAX as f64 * MAX_VOLUME as f64),
                     effect,
-                    speed,
+                    duration,
+                    sound.envelope,
                 );
             }
         }
-        self.oscillator.update(blip_buf);
         self.tick_count += 1;
     }
 
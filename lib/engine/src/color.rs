@@ -0,0 +1,109 @@
+use crate::types::Rgb8;
+
+pub fn rgb(r: u8, g: u8, b: u8) -> Rgb8 {
+    ((r as Rgb8) << 16) | ((g as Rgb8) << 8) | b as Rgb8
+}
+
+pub fn red(color: Rgb8) -> u8 {
+    ((color >> 16) & 0xff) as u8
+}
+
+pub fn green(color: Rgb8) -> u8 {
+    ((color >> 8) & 0xff) as u8
+}
+
+pub fn blue(color: Rgb8) -> u8 {
+    (color & 0xff) as u8
+}
+
+pub fn rgb_components(color: Rgb8) -> (u8, u8, u8) {
+    (red(color), green(color), blue(color))
+}
+
+fn to_component(value: f64) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+pub fn hsv(h: f64, s: f64, v: f64) -> Rgb8 {
+    let h = h.rem_euclid(360.0) / 60.0;
+    let s = s.clamp(0.0, 1.0);
+    let v = v.clamp(0.0, 1.0);
+    let c = v * s;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    rgb(
+        to_component(r + m),
+        to_component(g + m),
+        to_component(b + m),
+    )
+}
+
+pub fn lerp(a: Rgb8, b: Rgb8, t: f64) -> Rgb8 {
+    let t = t.clamp(0.0, 1.0);
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    rgb(
+        mix(red(a), red(b)),
+        mix(green(a), green(b)),
+        mix(blue(a), blue(b)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb_() {
+        for value in 0..=255 {
+            assert_eq!(rgb_components(rgb(value, 0, 0)), (value, 0, 0));
+            assert_eq!(rgb_components(rgb(0, value, 0)), (0, value, 0));
+            assert_eq!(rgb_components(rgb(0, 0, value)), (0, 0, value));
+            assert_eq!(rgb(value, value, value), value as Rgb8 * 0x010101);
+        }
+        for color in (0..=0xffffff).step_by(0x010203) {
+            let (r, g, b) = rgb_components(color);
+            assert_eq!(rgb(r, g, b), color);
+        }
+        assert_eq!(red(0xff123456), 0x12);
+    }
+
+    #[test]
+    fn hsv_() {
+        assert_eq!(hsv(0.0, 1.0, 1.0), 0xff0000);
+        assert_eq!(hsv(360.0, 1.0, 1.0), 0xff0000);
+        assert_eq!(hsv(-360.0, 1.0, 1.0), 0xff0000);
+        assert_eq!(hsv(60.0, 1.0, 1.0), 0xffff00);
+        assert_eq!(hsv(120.0, 1.0, 1.0), 0x00ff00);
+        assert_eq!(hsv(180.0, 1.0, 1.0), 0x00ffff);
+        assert_eq!(hsv(240.0, 1.0, 1.0), 0x0000ff);
+        assert_eq!(hsv(300.0, 1.0, 1.0), 0xff00ff);
+        assert_eq!(hsv(359.999, 1.0, 1.0), 0xff0000);
+        assert_eq!(hsv(30.0, 1.0, 1.0), 0xff8000);
+        for h in [0.0, 90.0, 200.0, 360.0] {
+            assert_eq!(hsv(h, 0.0, 1.0), 0xffffff);
+            assert_eq!(hsv(h, 0.0, 0.5), 0x808080);
+            assert_eq!(hsv(h, 1.0, 0.0), 0x000000);
+        }
+        assert_eq!(hsv(0.0, 2.0, 2.0), 0xff0000);
+        assert_eq!(hsv(0.0, -1.0, 1.0), 0xffffff);
+    }
+
+    #[test]
+    fn lerp_() {
+        assert_eq!(lerp(0x000000, 0xffffff, 0.0), 0x000000);
+        assert_eq!(lerp(0x000000, 0xffffff, 1.0), 0xffffff);
+        assert_eq!(lerp(0x000000, 0xffffff, 0.5), 0x808080);
+        assert_eq!(lerp(0x102030, 0x302010, 0.5), 0x202020);
+        assert_eq!(lerp(0xff0000, 0x0000ff, 0.25), 0xbf0040);
+        assert_eq!(lerp(0x123456, 0xabcdef, -1.0), 0x123456);
+        assert_eq!(lerp(0x123456, 0xabcdef, 2.0), 0xabcdef);
+    }
+}
@@ -0,0 +1,160 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// A bounded single-producer single-consumer ring buffer. Each end is owned by one
+// thread, so sending and receiving never block and never take a lock.
+struct CommandQueue<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    read_pos: AtomicUsize,
+    write_pos: AtomicUsize,
+}
+
+unsafe impl<T: Send> Sync for CommandQueue<T> {}
+
+impl<T> Drop for CommandQueue<T> {
+    fn drop(&mut self) {
+        let write_pos = *self.write_pos.get_mut();
+        let mut read_pos = *self.read_pos.get_mut();
+        while read_pos != write_pos {
+            unsafe {
+                (*self.slots[read_pos % self.slots.len()].get()).assume_init_drop();
+            }
+            read_pos = read_pos.wrapping_add(1);
+        }
+    }
+}
+
+// Each end caches the other end's last seen position and only reloads it when
+// the queue looks full or empty, which keeps the shared cache line quiet
+pub struct CommandSender<T> {
+    queue: Arc<CommandQueue<T>>,
+    read_pos: usize,
+}
+
+pub struct CommandReceiver<T> {
+    queue: Arc<CommandQueue<T>>,
+    write_pos: usize,
+}
+
+pub fn command_queue<T>(capacity: usize) -> (CommandSender<T>, CommandReceiver<T>) {
+    assert!(capacity > 0);
+    let queue = Arc::new(CommandQueue {
+        slots: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+        read_pos: AtomicUsize::new(0),
+        write_pos: AtomicUsize::new(0),
+    });
+    (
+        CommandSender {
+            queue: queue.clone(),
+            read_pos: 0,
+        },
+        CommandReceiver {
+            queue,
+            write_pos: 0,
+        },
+    )
+}
+
+impl<T> CommandSender<T> {
+    pub fn push(&mut self, command: T) -> Result<(), T> {
+        let queue = &self.queue;
+        let write_pos = queue.write_pos.load(Ordering::Relaxed);
+        if write_pos.wrapping_sub(self.read_pos) == queue.slots.len() {
+            self.read_pos = queue.read_pos.load(Ordering::Acquire);
+            if write_pos.wrapping_sub(self.read_pos) == queue.slots.len() {
+                return Err(command);
+            }
+        }
+        unsafe {
+            (*queue.slots[write_pos % queue.slots.len()].get()).write(command);
+        }
+        // Publishing the write position hands the slot over to the receiver
+        queue
+            .write_pos
+            .store(write_pos.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T> CommandReceiver<T> {
+    pub fn pop(&mut self) -> Option<T> {
+        let queue = &self.queue;
+        let read_pos = queue.read_pos.load(Ordering::Relaxed);
+        if read_pos == self.write_pos {
+            self.write_pos = queue.write_pos.load(Ordering::Acquire);
+            if read_pos == self.write_pos {
+                return None;
+            }
+        }
+        let command =
+            unsafe { (*queue.slots[read_pos % queue.slots.len()].get()).assume_init_read() };
+        queue
+            .read_pos
+            .store(read_pos.wrapping_add(1), Ordering::Release);
+        Some(command)
+    }
+}
+
+unsafe impl<T: Send> Send for CommandSender<T> {}
+unsafe impl<T: Send> Send for CommandReceiver<T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn push_pop() {
+        let (mut sender, mut receiver) = command_queue(3);
+        assert_eq!(receiver.pop(), None);
+        for i in 0..3 {
+            assert_eq!(sender.push(i), Ok(()));
+        }
+        assert_eq!(sender.push(3), Err(3));
+        assert_eq!(receiver.pop(), Some(0));
+        assert_eq!(sender.push(4), Ok(()));
+        assert_eq!(
+            (0..4).map(|_| receiver.pop()).collect::<Vec<_>>(),
+            [Some(1), Some(2), Some(4), None]
+        );
+    }
+
+    #[test]
+    fn drop_pending() {
+        let command = Arc::new(());
+        let (mut sender, receiver) = command_queue(4);
+        sender.push(command.clone()).unwrap();
+        sender.push(command.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&command), 3);
+        drop(sender);
+        drop(receiver);
+        assert_eq!(Arc::strong_count(&command), 1);
+    }
+
+    #[test]
+    fn threads() {
+        let (mut sender, mut receiver) = command_queue(16);
+        let consumer = thread::spawn(move || {
+            let mut expected = 0;
+            while expected < 100_000 {
+                if let Some(value) = receiver.pop() {
+                    assert_eq!(value, expected);
+                    expected += 1;
+                }
+            }
+        });
+        for i in 0..100_000 {
+            let mut value = i;
+            while let Err(rejected) = sender.push(value) {
+                value = rejected;
+                thread::yield_now();
+            }
+        }
+        consumer.join().unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::settings::{DEFAULT_AXIS_DEADZONE_INNER, DEFAULT_AXIS_DEADZONE_OUTER, MAX_AXIS_VALUE};
+use crate::types::KeyValue;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deadzone {
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl Default for Deadzone {
+    fn default() -> Self {
+        Self {
+            inner: DEFAULT_AXIS_DEADZONE_INNER,
+            outer: DEFAULT_AXIS_DEADZONE_OUTER,
+        }
+    }
+}
+
+impl Deadzone {
+    pub fn new(inner: f32, outer: f32) -> Option<Self> {
+        (0.0 <= inner && inner < outer && outer <= 1.0).then_some(Self { inner, outer })
+    }
+
+    pub fn rescale(&self, magnitude: f32) -> f32 {
+        // The output starts from 0 at the inner edge so that leaving the deadzone doesn't jump
+        ((magnitude - self.inner) / (self.outer - self.inner)).clamp(0.0, 1.0)
+    }
+
+    pub fn axial(&self, value: KeyValue) -> f32 {
+        let value = normalize(value);
+        value.signum() * self.rescale(value.abs())
+    }
+
+    pub fn radial(&self, x: KeyValue, y: KeyValue) -> (f32, f32) {
+        let (x, y) = (normalize(x), normalize(y));
+        let magnitude = x.hypot(y);
+        if magnitude == 0.0 {
+            return (0.0, 0.0);
+        }
+        let scale = self.rescale(magnitude.min(1.0)) / magnitude;
+        ((x * scale).clamp(-1.0, 1.0), (y * scale).clamp(-1.0, 1.0))
+    }
+}
+
+fn normalize(value: KeyValue) -> f32 {
+    (value as f32 / MAX_AXIS_VALUE).clamp(-1.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axial() {
+        let deadzone = Deadzone::default();
+        let inner = (DEFAULT_AXIS_DEADZONE_INNER * MAX_AXIS_VALUE) as KeyValue;
+        let mut last = -1.0;
+        for value in (-32768..=32767).step_by(97) {
+            let output = deadzone.axial(value);
+            assert!(output >= last, "{} at {}", output, value);
+            assert!((-1.0..=1.0).contains(&output));
+            if value.abs() <= inner {
+                assert_eq!(output, 0.0);
+            }
+            last = output;
+        }
+        assert_eq!(deadzone.axial(-32768), -1.0);
+        assert_eq!(deadzone.axial(32767), 1.0);
+        assert!(deadzone.axial(inner + 100) < 0.01);
+        assert_eq!(deadzone.axial(31200), 1.0);
+
+        let deadzone = Deadzone::new(0.0, 1.0).unwrap();
+        assert_eq!(deadzone.axial(16384), 16384.0 / MAX_AXIS_VALUE);
+        assert!(Deadzone::new(0.5, 0.5).is_none());
+        assert!(Deadzone::new(-0.1, 0.9).is_none());
+        assert!(Deadzone::new(0.2, 1.1).is_none());
+    }
+
+    #[test]
+    fn radial() {
+        let deadzone = Deadzone::default();
+        for y in (-32768..=32767).step_by(4093) {
+            let mut last = -1.0;
+            for x in (-32768..=32767).step_by(397) {
+                let (output_x, output_y) = deadzone.radial(x, y);
+                // Saturated directions are unit vectors, which only differ by rounding
+                assert!(output_x >= last - 1e-6, "{} at {}, {}", output_x, x, y);
+                assert!(output_x.hypot(output_y) <= 1.0 + f32::EPSILON);
+                if normalize(x).hypot(normalize(y)) <= DEFAULT_AXIS_DEADZONE_INNER {
+                    assert_eq!((output_x, output_y), (0.0, 0.0));
+                }
+                last = output_x;
+            }
+        }
+
+        // Small offsets from a cardinal direction are kept instead of snapping to the axis
+        let (x, y) = (
+            (0.6 * MAX_AXIS_VALUE) as KeyValue,
+            (0.1 * MAX_AXIS_VALUE) as KeyValue,
+        );
+        assert_eq!(deadzone.axial(y), 0.0);
+        assert!(deadzone.radial(x, y).1 > 0.0);
+        let (x, y) = deadzone.radial(32767, 32767);
+        assert!((x - y).abs() < f32::EPSILON);
+        assert!((x.hypot(y) - 1.0).abs() < 1e-5);
+    }
+}
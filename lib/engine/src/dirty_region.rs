@@ -0,0 +1,106 @@
+use std::cmp::min;
+
+use crate::geometry::Recti;
+use crate::settings::DIRTY_BLOCK_SIZE;
+
+pub struct DirtyRegion {
+    width: u32,
+    height: u32,
+    num_columns: u32,
+    num_rows: u32,
+    blocks: Vec<bool>,
+}
+
+impl DirtyRegion {
+    pub fn new(width: u32, height: u32) -> Self {
+        let num_columns = width.div_ceil(DIRTY_BLOCK_SIZE);
+        let num_rows = height.div_ceil(DIRTY_BLOCK_SIZE);
+        Self {
+            width,
+            height,
+            num_columns,
+            num_rows,
+            blocks: vec![true; (num_columns * num_rows) as usize],
+        }
+    }
+
+    pub fn mark(&mut self, x: i32, y: i32) {
+        let column = x as u32 / DIRTY_BLOCK_SIZE;
+        let row = y as u32 / DIRTY_BLOCK_SIZE;
+        self.blocks[(row * self.num_columns + column) as usize] = true;
+    }
+
+    pub fn mark_rect(&mut self, rect: Recti) {
+        let rect = rect.intersection(Recti::new(0, 0, self.width, self.height));
+        if rect.is_empty() {
+            return;
+        }
+        for row in rect.top() as u32 / DIRTY_BLOCK_SIZE..=rect.bottom() as u32 / DIRTY_BLOCK_SIZE {
+            for column in
+                rect.left() as u32 / DIRTY_BLOCK_SIZE..=rect.right() as u32 / DIRTY_BLOCK_SIZE
+            {
+                self.blocks[(row * self.num_columns + column) as usize] = true;
+            }
+        }
+    }
+
+    pub fn take_rects(&mut self) -> Vec<Recti> {
+        let mut rects = Vec::new();
+        for row in 0..self.num_rows {
+            let mut column = 0;
+            while column < self.num_columns {
+                if !self.blocks[(row * self.num_columns + column) as usize] {
+                    column += 1;
+                    continue;
+                }
+                let start = column;
+                while column < self.num_columns
+                    && self.blocks[(row * self.num_columns + column) as usize]
+                {
+                    self.blocks[(row * self.num_columns + column) as usize] = false;
+                    column += 1;
+                }
+                let left = start * DIRTY_BLOCK_SIZE;
+                let top = row * DIRTY_BLOCK_SIZE;
+                rects.push(Recti::new(
+                    left as i32,
+                    top as i32,
+                    min(column * DIRTY_BLOCK_SIZE, self.width) - left,
+                    min(top + DIRTY_BLOCK_SIZE, self.height) - top,
+                ));
+            }
+        }
+        rects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_rects() {
+        let mut dirty_region = DirtyRegion::new(40, 20);
+        assert_eq!(
+            dirty_region.take_rects(),
+            vec![Recti::new(0, 0, 40, 16), Recti::new(0, 16, 40, 4)]
+        );
+        assert!(dirty_region.take_rects().is_empty());
+
+        dirty_region.mark(33, 2);
+        dirty_region.mark(5, 18);
+        dirty_region.mark(20, 19);
+        assert_eq!(
+            dirty_region.take_rects(),
+            vec![Recti::new(32, 0, 8, 16), Recti::new(0, 16, 32, 4)]
+        );
+
+        dirty_region.mark_rect(Recti::new(-10, 10, 30, 100));
+        assert_eq!(
+            dirty_region.take_rects(),
+            vec![Recti::new(0, 0, 32, 16), Recti::new(0, 16, 32, 4)]
+        );
+        dirty_region.mark_rect(Recti::new(50, 0, 10, 10));
+        assert!(dirty_region.take_rects().is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::settings::CLOCK_RATE;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: u32,
+    pub decay: u32,
+    pub sustain: f64,
+    pub release: u32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            attack: 0,
+            decay: 0,
+            sustain: 1.0,
+            release: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Off,
+}
+
+pub struct EnvelopeGenerator {
+    envelope: Envelope,
+    stage: Stage,
+    level: f64,
+    release_step: f64,
+}
+
+fn ms_to_clocks(ms: u32) -> f64 {
+    ms as f64 * CLOCK_RATE as f64 / 1000.0
+}
+
+impl EnvelopeGenerator {
+    pub fn new() -> Self {
+        Self {
+            envelope: Envelope::default(),
+            stage: Stage::Off,
+            level: 0.0,
+            release_step: 0.0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.stage != Stage::Off
+    }
+
+    pub fn note_on(&mut self, envelope: Envelope) {
+        // The attack starts from the current level, so a retriggered or
+        // still releasing note crossfades into the new one without a click
+        self.envelope = envelope;
+        self.envelope.sustain = envelope.sustain.clamp(0.0, 1.0);
+        self.stage = Stage::Attack;
+    }
+
+    pub fn note_off(&mut self) {
+        if matches!(self.stage, Stage::Release | Stage::Off) {
+            return;
+        }
+        if self.envelope.release == 0 {
+            self.level = 0.0;
+            self.stage = Stage::Off;
+        } else {
+            self.stage = Stage::Release;
+            self.release_step = self.level / ms_to_clocks(self.envelope.release);
+        }
+    }
+
+    pub fn advance(&mut self, clocks: u32) -> f64 {
+        // Clocks left over after a stage ends carry into the next stage
+        let mut clocks = clocks as f64;
+        loop {
+            match self.stage {
+                Stage::Attack => {
+                    let attack = ms_to_clocks(self.envelope.attack);
+                    let remaining = (1.0 - self.level) * attack;
+                    if clocks < remaining {
+                        self.level += clocks / attack;
+                        break;
+                    }
+                    clocks -= remaining;
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+                Stage::Decay => {
+                    let decay = ms_to_clocks(self.envelope.decay);
+                    let step = (1.0 - self.envelope.sustain) / decay;
+                    if decay > 0.0 && clocks * step < self.level - self.envelope.sustain {
+                        self.level -= clocks * step;
+                        break;
+                    }
+                    self.level = self.envelope.sustain;
+                    self.stage = Stage::Sustain;
+                }
+                Stage::Sustain => break,
+                Stage::Release => {
+                    if clocks * self.release_step < self.level {
+                        self.level -= clocks * self.release_step;
+                    } else {
+                        self.level = 0.0;
+                        self.stage = Stage::Off;
+                    }
+                    break;
+                }
+                Stage::Off => {
+                    self.level = 0.0;
+                    break;
+                }
+            }
+        }
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_envelope() {
+        let mut generator = EnvelopeGenerator::new();
+        assert!(!generator.is_active());
+        generator.note_on(Envelope::default());
+        assert_eq!(generator.advance(100), 1.0);
+        assert_eq!(generator.advance(100000), 1.0);
+        generator.note_off();
+        assert_eq!(generator.advance(100), 0.0);
+        assert!(!generator.is_active());
+    }
+
+    #[test]
+    fn stages() {
+        let mut generator = EnvelopeGenerator::new();
+        generator.note_on(Envelope {
+            attack: 10,
+            decay: 20,
+            sustain: 0.5,
+            release: 40,
+        });
+        let ms = CLOCK_RATE / 1000;
+        let level_at = |generator: &mut EnvelopeGenerator, num_ms: u32| {
+            let mut level = 0.0;
+            for _ in 0..num_ms {
+                level = generator.advance(ms);
+            }
+            level
+        };
+        assert!((level_at(&mut generator, 5) - 0.5).abs() < 0.01);
+        assert!((level_at(&mut generator, 15) - 0.75).abs() < 0.01);
+        assert_eq!(level_at(&mut generator, 20), 0.5);
+        assert_eq!(level_at(&mut generator, 100), 0.5);
+        generator.note_off();
+        assert!((level_at(&mut generator, 20) - 0.25).abs() < 0.01);
+        assert!(generator.is_active());
+        level_at(&mut generator, 21);
+        assert!(!generator.is_active());
+        assert_eq!(generator.advance(ms), 0.0);
+    }
+}
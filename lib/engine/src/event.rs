@@ -1,3 +1,6 @@
+use crate::platform::{AuxWindowEvent, AuxWindowId};
+
+#[derive(Clone, Copy)]
 pub enum MouseButton {
     Left,
     Middle,
@@ -7,6 +10,7 @@ pub enum MouseButton {
     Unknown,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ControllerAxis {
     LeftX,
     LeftY,
@@ -16,6 +20,7 @@ pub enum ControllerAxis {
     TriggerRight,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ControllerButton {
     A,
     B,
@@ -40,16 +45,43 @@ pub enum ControllerButton {
     Touchpad,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HatState {
+    Centered,
+    Up,
+    Right,
+    Down,
+    Left,
+    RightUp,
+    RightDown,
+    LeftUp,
+    LeftDown,
+}
+
 pub enum Event {
     // System events
     Quit,
     DropFile {
         filename: String,
+        x: i32,
+        y: i32,
+    },
+    DropText {
+        text: String,
+        x: i32,
+        y: i32,
     },
 
     // Window events
     Shown,
     Hidden,
+    AuxWindow {
+        window: AuxWindowId,
+        event: AuxWindowEvent,
+    },
+
+    // Audio events
+    AudioDeviceRemoved,
 
     // Key events
     KeyDown {
@@ -61,21 +93,37 @@ pub enum Event {
     TextInput {
         text: String,
     },
+    TextEditing {
+        text: String,
+        start: i32,
+        length: i32,
+    },
 
     // Mouse events
     MouseMotion {
         x: i32,
         y: i32,
+        window_x: i32,
+        window_y: i32,
+        is_on_screen: bool,
     },
     MouseButtonDown {
         button: MouseButton,
+        x: i32,
+        y: i32,
+        is_on_screen: bool,
     },
     MouseButtonUp {
         button: MouseButton,
+        x: i32,
+        y: i32,
+        is_on_screen: bool,
     },
     MouseWheel {
         x: i32,
         y: i32,
+        precise_x: f32,
+        precise_y: f32,
     },
 
     // Controller events
@@ -92,4 +140,207 @@ pub enum Event {
         which: u32,
         button: ControllerButton,
     },
+
+    // Joystick events
+    JoystickAxisMotion {
+        which: u32,
+        axis: u32,
+        value: i32,
+    },
+    JoystickButtonDown {
+        which: u32,
+        button: u32,
+    },
+    JoystickButtonUp {
+        which: u32,
+        button: u32,
+    },
+    JoystickHatMotion {
+        which: u32,
+        hat: u32,
+        state: HatState,
+    },
+}
+
+impl Event {
+    pub fn is_press(&self) -> bool {
+        matches!(
+            self,
+            Self::KeyDown { .. }
+                | Self::TextInput { .. }
+                | Self::TextEditing { .. }
+                | Self::MouseButtonDown { .. }
+                | Self::MouseWheel { .. }
+                | Self::ControllerButtonDown { .. }
+                | Self::JoystickButtonDown { .. }
+                | Self::DropFile { .. }
+                | Self::DropText { .. }
+        )
+    }
+
+    pub const fn is_button_press(&self) -> bool {
+        matches!(
+            self,
+            Self::KeyDown { .. }
+                | Self::MouseButtonDown { .. }
+                | Self::ControllerButtonDown { .. }
+                | Self::JoystickButtonDown { .. }
+        )
+    }
+}
+
+// Timestamps are microseconds on the platform's high-resolution clock
+pub struct TimedEvent {
+    pub timestamp_us: u64,
+    pub event: Event,
+}
+
+pub fn event_timestamp_us(now_us: u64, now_ms: u32, event_ms: u32, last_us: u64) -> u64 {
+    // Event times come in whole milliseconds on a separate tick counter, so only
+    // their age is taken from it and measured back from the current time
+    let age_us = now_ms.saturating_sub(event_ms) as u64 * 1000;
+    now_us
+        .saturating_sub(age_us)
+        .clamp(last_us.min(now_us), now_us)
+}
+
+pub fn drain_events(
+    out: &mut Vec<TimedEvent>,
+    should_coalesce_mouse_motion: bool,
+    mut poll_event: impl FnMut() -> Option<TimedEvent>,
+) {
+    let start = out.len();
+    while let Some(event) = poll_event() {
+        // Only a motion right after another one from this pass replaces it, so
+        // motions on either side of a click stay separate
+        if should_coalesce_mouse_motion
+            && matches!(event.event, Event::MouseMotion { .. })
+            && out.len() > start
+            && matches!(
+                out.last(),
+                Some(TimedEvent {
+                    event: Event::MouseMotion { .. },
+                    ..
+                })
+            )
+        {
+            *out.last_mut().unwrap() = event;
+        } else {
+            out.push(event);
+        }
+    }
+}
+
+pub fn flush_events(
+    pending_events: &mut Vec<TimedEvent>,
+    poll_event: impl FnMut() -> Option<TimedEvent>,
+) {
+    // Releases, movements, and window events still apply so held buttons and the
+    // game's view of the window don't go stale
+    drain_events(pending_events, true, poll_event);
+    pending_events.retain(|event| !event.event.is_press());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    fn motion(x: i32) -> Event {
+        Event::MouseMotion {
+            x,
+            y: 0,
+            window_x: x,
+            window_y: 0,
+            is_on_screen: true,
+        }
+    }
+
+    fn names(events: &[TimedEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match &event.event {
+                Event::MouseMotion { x, .. } => format!("motion{}", x),
+                Event::KeyDown { keycode } => format!("down{}", keycode),
+                Event::KeyUp { keycode } => format!("up{}", keycode),
+                Event::MouseButtonDown { .. } => "click".to_string(),
+                Event::Quit => "quit".to_string(),
+                _ => "other".to_string(),
+            })
+            .collect()
+    }
+
+    fn timed(timestamp_us: u64, event: Event) -> TimedEvent {
+        TimedEvent {
+            timestamp_us,
+            event,
+        }
+    }
+
+    fn script() -> VecDeque<TimedEvent> {
+        let events = vec![
+            motion(1),
+            motion(2),
+            motion(3),
+            Event::KeyDown { keycode: 4 },
+            motion(5),
+            Event::MouseButtonDown {
+                button: MouseButton::Left,
+                x: 5,
+                y: 0,
+                is_on_screen: true,
+            },
+            motion(6),
+            motion(7),
+            Event::KeyUp { keycode: 4 },
+            Event::Quit,
+        ];
+        events
+            .into_iter()
+            .enumerate()
+            .map(|(i, event)| timed(i as u64 + 1, event))
+            .collect()
+    }
+
+    #[test]
+    fn drain_events() {
+        let mut source = script();
+        let mut events = vec![timed(0, motion(0))];
+        super::drain_events(&mut events, true, || source.pop_front());
+        assert_eq!(
+            names(&events),
+            ["motion0", "motion3", "down4", "motion5", "click", "motion7", "up4", "quit"]
+        );
+        assert_eq!(events[1].timestamp_us, 3);
+        assert_eq!(events[5].timestamp_us, 8);
+
+        let mut source = script();
+        let mut events = Vec::new();
+        super::drain_events(&mut events, false, || source.pop_front());
+        assert_eq!(events.len(), 10);
+        assert_eq!(names(&events[..3]), ["motion1", "motion2", "motion3"]);
+    }
+
+    #[test]
+    fn flush_events() {
+        let mut source = script();
+        let mut pending_events = Vec::new();
+        super::flush_events(&mut pending_events, || source.pop_front());
+        assert_eq!(
+            names(&pending_events),
+            ["motion3", "motion5", "motion7", "up4", "quit"]
+        );
+        assert!(source.is_empty());
+    }
+
+    #[test]
+    fn event_timestamp_us() {
+        assert_eq!(super::event_timestamp_us(50_000, 40, 40, 0), 50_000);
+        assert_eq!(super::event_timestamp_us(50_000, 40, 32, 0), 42_000);
+        assert_eq!(super::event_timestamp_us(50_000, 40, 32, 45_000), 45_000);
+        assert_eq!(super::event_timestamp_us(50_000, 40, 41, 0), 50_000);
+        assert_eq!(super::event_timestamp_us(5_000, 1000, 0, 0), 0);
+        assert_eq!(super::event_timestamp_us(5_000, 40, 40, 9_000), 5_000);
+    }
 }
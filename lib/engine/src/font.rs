@@ -0,0 +1,153 @@
+use std::cmp::{max, min};
+
+use crate::canvas::Canvas;
+use crate::image::SharedImage;
+use crate::types::Color;
+
+pub struct Font {
+    pub image: SharedImage,
+    pub glyph_width: u32,
+    pub glyph_height: u32,
+    pub first_char: char,
+    pub widths: Vec<u32>,
+    pub colkey: Color,
+    num_columns: u32,
+}
+
+pub type SharedFont = shared_type!(Font);
+
+impl Font {
+    pub fn from_image(
+        image: SharedImage,
+        glyph_width: u32,
+        glyph_height: u32,
+        first_char: char,
+    ) -> SharedFont {
+        assert!(glyph_width > 0 && glyph_height > 0);
+        let (width, height) = {
+            let image = image.lock();
+            (image.width(), image.height())
+        };
+        let num_glyphs = (width / glyph_width) * (height / glyph_height);
+        Self::with_widths(
+            image,
+            glyph_width,
+            glyph_height,
+            first_char,
+            &vec![glyph_width; num_glyphs as usize],
+        )
+    }
+
+    pub fn with_widths(
+        image: SharedImage,
+        glyph_width: u32,
+        glyph_height: u32,
+        first_char: char,
+        widths: &[u32],
+    ) -> SharedFont {
+        assert!(glyph_width > 0 && glyph_height > 0);
+        let num_columns = max(image.lock().width() / glyph_width, 1);
+        new_shared_type!(Self {
+            image,
+            glyph_width,
+            glyph_height,
+            first_char,
+            widths: widths.to_vec(),
+            colkey: 0,
+            num_columns,
+        })
+    }
+
+    pub fn glyph_index(&self, c: char) -> Option<usize> {
+        let index = (c as u32).checked_sub(self.first_char as u32)? as usize;
+        (index < self.widths.len()).then_some(index)
+    }
+
+    pub fn glyph_position(&self, index: usize) -> (i32, i32) {
+        let index = index as u32;
+        (
+            ((index % self.num_columns) * self.glyph_width) as i32,
+            ((index / self.num_columns) * self.glyph_height) as i32,
+        )
+    }
+
+    pub(crate) fn glyph_points(&self, canvas: &Canvas<Color>, string: &str) -> Vec<(i32, i32)> {
+        let mut points = Vec::new();
+        let mut glyph_x = 0;
+        let mut glyph_y = 0;
+        for c in string.chars() {
+            if c == '\n' {
+                glyph_x = 0;
+                glyph_y += self.glyph_height as i32;
+                continue;
+            }
+            let index = match self.glyph_index(c) {
+                Some(index) => index,
+                None => continue,
+            };
+            let (u, v) = self.glyph_position(index);
+            let width = self.widths[index];
+            for yi in 0..self.glyph_height as i32 {
+                for xi in 0..min(width, self.glyph_width) as i32 {
+                    if canvas.self_rect.contains_point(u + xi, v + yi)
+                        && canvas.data[(v + yi) as usize][(u + xi) as usize] != self.colkey
+                    {
+                        points.push((glyph_x + xi, glyph_y + yi));
+                    }
+                }
+            }
+            glyph_x += width as i32;
+        }
+        points
+    }
+
+    pub fn text_width(&self, string: &str) -> u32 {
+        string
+            .split('\n')
+            .map(|line| {
+                line.chars()
+                    .filter_map(|c| self.glyph_index(c))
+                    .map(|index| self.widths[index])
+                    .sum()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    #[test]
+    fn glyph_index() {
+        let font = Font::from_image(Image::new(40, 24), 10, 12, 'A');
+        assert_eq!(font.lock().widths.len(), 8);
+        assert_eq!(font.lock().glyph_index('A'), Some(0));
+        assert_eq!(font.lock().glyph_index('H'), Some(7));
+        assert_eq!(font.lock().glyph_index('I'), None);
+        assert_eq!(font.lock().glyph_index(' '), None);
+        assert_eq!(font.lock().glyph_position(5), (10, 12));
+    }
+
+    #[test]
+    fn text_width() {
+        let font = Font::from_image(Image::new(40, 24), 10, 12, 'A');
+        assert_eq!(font.lock().text_width(""), 0);
+        assert_eq!(font.lock().text_width("ABC"), 30);
+        assert_eq!(font.lock().text_width("A\nBCD\nE"), 30);
+        assert_eq!(font.lock().text_width("A?B"), 20);
+
+        let font = Font::with_widths(Image::new(40, 24), 10, 12, 'a', &[3, 10, 5]);
+        assert_eq!(font.lock().text_width("abc"), 18);
+        assert_eq!(font.lock().text_width("cc\nb"), 10);
+        assert_eq!(font.lock().text_width("d"), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_glyph_width() {
+        Font::from_image(Image::new(40, 24), 0, 12, 'A');
+    }
+}
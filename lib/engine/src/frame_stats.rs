@@ -0,0 +1,203 @@
+use std::time::{Duration, Instant};
+
+use crate::settings::FRAME_STATS_WINDOW_MS;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub target_fps: u32,
+    pub fps: f64,
+    pub last_frame_ms: f64,
+    pub mean_frame_ms: f64,
+    pub max_frame_ms: f64,
+    pub events_ms: f64,
+    pub update_ms: f64,
+    pub draw_ms: f64,
+    pub render_ms: f64,
+    pub mean_input_latency_ms: f64,
+    pub max_input_latency_ms: f64,
+}
+
+#[derive(Clone, Copy)]
+pub enum FramePhase {
+    Events,
+    Update,
+    Draw,
+    Render,
+}
+
+pub struct FrameStatsCollector {
+    stats: FrameStats,
+    window_start: Option<Instant>,
+    frame_start: Option<Instant>,
+    num_frames: u32,
+    total_frame_time: Duration,
+    max_frame_time: Duration,
+    phase_times: [Duration; 4],
+    num_input_latencies: u32,
+    total_input_latency_us: u64,
+    max_input_latency_us: u64,
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl FrameStatsCollector {
+    pub fn new() -> Self {
+        Self {
+            stats: FrameStats::default(),
+            window_start: None,
+            frame_start: None,
+            num_frames: 0,
+            total_frame_time: Duration::ZERO,
+            max_frame_time: Duration::ZERO,
+            phase_times: [Duration::ZERO; 4],
+            num_input_latencies: 0,
+            total_input_latency_us: 0,
+            max_input_latency_us: 0,
+        }
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        // Frames measured at the previous rate would skew the stats of the new one
+        self.stats.target_fps = target_fps;
+        self.window_start = self.frame_start;
+        self.reset_window();
+    }
+
+    fn reset_window(&mut self) {
+        self.num_frames = 0;
+        self.total_frame_time = Duration::ZERO;
+        self.max_frame_time = Duration::ZERO;
+        self.phase_times = [Duration::ZERO; 4];
+        self.num_input_latencies = 0;
+        self.total_input_latency_us = 0;
+        self.max_input_latency_us = 0;
+    }
+
+    pub fn start_frame(&mut self, now: Instant) {
+        // Frame time runs from one frame start to the next, so it includes the wait for the next frame
+        if let Some(frame_start) = self.frame_start {
+            let frame_time = now - frame_start;
+            self.stats.last_frame_ms = as_ms(frame_time);
+            self.num_frames += 1;
+            self.total_frame_time += frame_time;
+            self.max_frame_time = self.max_frame_time.max(frame_time);
+        }
+        self.frame_start = Some(now);
+        let window_start = *self.window_start.get_or_insert(now);
+        let window_time = now - window_start;
+        if window_time >= Duration::from_millis(FRAME_STATS_WINDOW_MS) && self.num_frames > 0 {
+            let num_frames = self.num_frames as f64;
+            let phase_ms = |phase: FramePhase| as_ms(self.phase_times[phase as usize]) / num_frames;
+            self.stats = FrameStats {
+                target_fps: self.stats.target_fps,
+                fps: num_frames / window_time.as_secs_f64(),
+                last_frame_ms: self.stats.last_frame_ms,
+                mean_frame_ms: as_ms(self.total_frame_time) / num_frames,
+                max_frame_ms: as_ms(self.max_frame_time),
+                events_ms: phase_ms(FramePhase::Events),
+                update_ms: phase_ms(FramePhase::Update),
+                draw_ms: phase_ms(FramePhase::Draw),
+                render_ms: phase_ms(FramePhase::Render),
+                mean_input_latency_ms: if self.num_input_latencies == 0 {
+                    0.0
+                } else {
+                    self.total_input_latency_us as f64 / self.num_input_latencies as f64 / 1000.0
+                },
+                max_input_latency_ms: self.max_input_latency_us as f64 / 1000.0,
+            };
+            self.window_start = Some(now);
+            self.reset_window();
+        }
+    }
+
+    pub fn add_phase_time(&mut self, phase: FramePhase, start: Instant, end: Instant) {
+        self.phase_times[phase as usize] += end - start;
+    }
+
+    pub fn add_input_latency(&mut self, latency_us: u64) {
+        self.num_input_latencies += 1;
+        self.total_input_latency_us += latency_us;
+        self.max_input_latency_us = self.max_input_latency_us.max(latency_us);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start_frame() {
+        let mut collector = FrameStatsCollector::new();
+        let start = Instant::now();
+        let ms = |value: u64| start + Duration::from_millis(value);
+        collector.start_frame(start);
+        assert_eq!(collector.stats(), FrameStats::default());
+
+        for i in 1..=50 {
+            let frame_start = ms((i - 1) * 20);
+            collector.add_phase_time(
+                FramePhase::Events,
+                frame_start,
+                frame_start + Duration::from_millis(1),
+            );
+            collector.add_phase_time(
+                FramePhase::Update,
+                frame_start,
+                frame_start + Duration::from_millis(2),
+            );
+            collector.add_phase_time(
+                FramePhase::Update,
+                frame_start,
+                frame_start + Duration::from_millis(2),
+            );
+            collector.add_phase_time(
+                FramePhase::Draw,
+                frame_start,
+                frame_start + Duration::from_millis(5),
+            );
+            collector.add_phase_time(
+                FramePhase::Render,
+                frame_start,
+                frame_start + Duration::from_millis(3),
+            );
+            if i % 10 == 0 {
+                collector.add_input_latency(i * 100);
+            }
+            collector.start_frame(ms(if i == 50 { 1000 } else { i * 20 }));
+            if i == 49 {
+                assert_eq!(collector.stats().fps, 0.0);
+                assert_eq!(collector.stats().last_frame_ms, 20.0);
+            }
+        }
+        let stats = collector.stats();
+        assert!((stats.fps - 50.0).abs() < 1e-9);
+        assert!((stats.mean_frame_ms - 20.0).abs() < 1e-9);
+        assert!((stats.max_frame_ms - 20.0).abs() < 1e-9);
+        assert!((stats.events_ms - 1.0).abs() < 1e-9);
+        assert!((stats.update_ms - 4.0).abs() < 1e-9);
+        assert!((stats.draw_ms - 5.0).abs() < 1e-9);
+        assert!((stats.render_ms - 3.0).abs() < 1e-9);
+        assert!((stats.mean_input_latency_ms - 3.0).abs() < 1e-9);
+        assert!((stats.max_input_latency_ms - 5.0).abs() < 1e-9);
+
+        collector.start_frame(ms(1100));
+        assert!((collector.stats().last_frame_ms - 100.0).abs() < 1e-9);
+        assert!((collector.stats().fps - 50.0).abs() < 1e-9);
+
+        collector.set_target_fps(30);
+        for i in 1..=25 {
+            collector.start_frame(ms(1100 + i * 40));
+        }
+        let stats = collector.stats();
+        assert_eq!(stats.target_fps, 30);
+        assert!((stats.fps - 25.0).abs() < 1e-9);
+        assert!((stats.max_frame_ms - 40.0).abs() < 1e-9);
+        assert_eq!(stats.max_input_latency_ms, 0.0);
+    }
+}
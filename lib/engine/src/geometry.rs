@@ -0,0 +1,427 @@
+use std::cmp::{max, min};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl Vec2 {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Self) -> f64 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        if length == 0.0 {
+            self
+        } else {
+            self * (1.0 / length)
+        }
+    }
+
+    pub fn rotate(self, deg: f64) -> Self {
+        let (sin, cos) = deg.to_radians().sin_cos();
+        Self::new(self.x * cos - self.y * sin, self.x * sin + self.y * cos)
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<f64> for Vec2 {
+    type Output = Self;
+
+    fn mul(self, scale: f64) -> Self {
+        Self::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.x, -self.y)
+    }
+}
+
+impl From<Vec2> for (f64, f64) {
+    fn from(vec: Vec2) -> Self {
+        (vec.x, vec.y)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Recti {
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+    width: u32,
+    height: u32,
+}
+
+impl Recti {
+    pub fn new(left: i32, top: i32, width: u32, height: u32) -> Self {
+        Self {
+            left,
+            top,
+            right: left + width as i32 - 1,
+            bottom: top + height as i32 - 1,
+            width,
+            height,
+        }
+    }
+
+    pub fn left(&self) -> i32 {
+        self.left
+    }
+
+    pub fn top(&self) -> i32 {
+        self.top
+    }
+
+    pub fn right(&self) -> i32 {
+        self.right
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.bottom
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains_point(&self, x: i32, y: i32) -> bool {
+        x >= self.left
+            && x < self.left + self.width as i32
+            && y >= self.top
+            && y < self.top + self.height as i32
+    }
+
+    pub fn intersects(&self, rect: Self) -> bool {
+        !self.intersection(rect).is_empty()
+    }
+
+    pub fn clamp_to(&self, bounds: Self) -> Self {
+        // A rect larger than the bounds is aligned to their top-left corner
+        let clamp = |start: i32, size: u32, bounds_start: i32, bounds_size: u32| {
+            let bounds_end = bounds_start + bounds_size as i32 - size as i32;
+            max(min(start, bounds_end), bounds_start)
+        };
+        Self::new(
+            clamp(self.left, self.width, bounds.left, bounds.width),
+            clamp(self.top, self.height, bounds.top, bounds.height),
+            self.width,
+            self.height,
+        )
+    }
+
+    pub fn intersection(&self, rect: Self) -> Self {
+        let left = max(self.left, rect.left);
+        let top = max(self.top, rect.top);
+        let right = min(self.right, rect.right);
+        let bottom = min(self.bottom, rect.bottom);
+        let width = right - left + 1;
+        let height = bottom - top + 1;
+        if width > 0 && height > 0 {
+            Self::new(left, top, width as u32, height as u32)
+        } else {
+            Self::new(0, 0, 0, 0)
+        }
+    }
+}
+
+impl From<Recti> for (f64, f64, f64, f64) {
+    fn from(rect: Recti) -> Self {
+        (
+            rect.left as f64,
+            rect.top as f64,
+            rect.width as f64,
+            rect.height as f64,
+        )
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Rectf {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rectf {
+    pub const fn new(x: f64, y: f64, width: f64, height: f64) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    pub fn contains_point(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn intersects(&self, rect: Self) -> bool {
+        !self.intersection(rect).is_empty()
+    }
+
+    pub fn intersection(&self, rect: Self) -> Self {
+        let x = self.x.max(rect.x);
+        let y = self.y.max(rect.y);
+        let width = self.right().min(rect.right()) - x;
+        let height = self.bottom().min(rect.bottom()) - y;
+        if width > 0.0 && height > 0.0 {
+            Self::new(x, y, width, height)
+        } else {
+            Self::default()
+        }
+    }
+
+    pub fn clamp_to(&self, bounds: Self) -> Self {
+        let clamp = |start: f64, size: f64, bounds_start: f64, bounds_size: f64| {
+            start
+                .min(bounds_start + bounds_size - size)
+                .max(bounds_start)
+        };
+        Self::new(
+            clamp(self.x, self.width, bounds.x, bounds.width),
+            clamp(self.y, self.height, bounds.y, bounds.height),
+            self.width,
+            self.height,
+        )
+    }
+}
+
+impl From<Rectf> for (f64, f64, f64, f64) {
+    fn from(rect: Rectf) -> Self {
+        (rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
+impl From<Recti> for Rectf {
+    fn from(rect: Recti) -> Self {
+        let (x, y, width, height) = rect.into();
+        Self::new(x, y, width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let rect1 = Recti::new(1, 2, 3, 4);
+        assert_eq!(rect1.left(), 1);
+        assert_eq!(rect1.top(), 2);
+        assert_eq!(rect1.right(), 3);
+        assert_eq!(rect1.bottom(), 5);
+        assert_eq!(rect1.width(), 3);
+        assert_eq!(rect1.height(), 4);
+
+        let rect2 = Recti::new(10, 20, 0, 40);
+        assert_eq!(rect2.left(), 10);
+        assert_eq!(rect2.top(), 20);
+        assert_eq!(rect2.right(), 9);
+        assert_eq!(rect2.bottom(), 59);
+        assert_eq!(rect2.width(), 0);
+        assert_eq!(rect2.height(), 40);
+
+        let rect3 = Recti::new(100, 200, 300, 0);
+        assert_eq!(rect3.left(), 100);
+        assert_eq!(rect3.top(), 200);
+        assert_eq!(rect3.right(), 399);
+        assert_eq!(rect3.bottom(), 199);
+        assert_eq!(rect3.width(), 300);
+        assert_eq!(rect3.height(), 0);
+    }
+
+    #[test]
+    fn is_empty() {
+        let rect1 = Recti::new(1, 2, 3, 4);
+        assert!(!rect1.is_empty());
+
+        let rect2 = Recti::new(1, 2, 0, 4);
+        assert!(rect2.is_empty());
+
+        let rect3 = Recti::new(1, 2, 3, 0);
+        assert!(rect3.is_empty());
+    }
+
+    #[test]
+    fn contains() {
+        let rect1 = Recti::new(1, 2, 3, 3);
+        assert!(rect1.contains_point(1, 2));
+        assert!(rect1.contains_point(3, 4));
+        assert!(!rect1.contains_point(0, 2));
+        assert!(!rect1.contains_point(1, 1));
+        assert!(!rect1.contains_point(4, 4));
+        assert!(!rect1.contains_point(3, 5));
+
+        let rect2 = Recti::new(1, 2, 0, 4);
+        assert!(!rect2.contains_point(1, 2));
+        assert!(!rect2.contains_point(1, 4));
+
+        let rect3 = Recti::new(1, 2, 3, 0);
+        assert!(!rect3.contains_point(1, 2));
+        assert!(!rect3.contains_point(3, 2));
+    }
+
+    #[test]
+    fn vec2() {
+        let a = Vec2::new(3.0, 4.0);
+        let b = Vec2::new(-1.0, 2.0);
+        assert_eq!(a + b, Vec2::new(2.0, 6.0));
+        assert_eq!(a - b, Vec2::new(4.0, 2.0));
+        assert_eq!(a * 2.0, Vec2::new(6.0, 8.0));
+        assert_eq!(-b, Vec2::new(1.0, -2.0));
+        assert_eq!(a.dot(b), 5.0);
+        assert_eq!(a.length(), 5.0);
+        assert!((a.normalize() - Vec2::new(0.6, 0.8)).length() < 1e-12);
+        assert_eq!(Vec2::default().normalize(), Vec2::default());
+        let rotated = Vec2::new(1.0, 0.0).rotate(90.0);
+        assert!(rotated.x.abs() < 1e-12 && (rotated.y - 1.0).abs() < 1e-12);
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+        assert_eq!(<(f64, f64)>::from(a), (3.0, 4.0));
+    }
+
+    #[test]
+    fn intersection_properties() {
+        let mut rng = crate::math::Rng::new(1);
+        let mut random_rect = || {
+            Recti::new(
+                rng.rndi(-8, 8),
+                rng.rndi(-8, 8),
+                rng.rndi(0, 8) as u32,
+                rng.rndi(0, 8) as u32,
+            )
+        };
+        for _ in 0..1000 {
+            let (a, b) = (random_rect(), random_rect());
+            let intersection = a.intersection(b);
+            assert_eq!(intersection, b.intersection(a));
+            assert_eq!(a.intersects(b), !intersection.is_empty());
+            assert_eq!(a.intersects(b), b.intersects(a));
+            if a.is_empty() || b.is_empty() {
+                assert!(!a.intersects(b));
+            }
+            if !intersection.is_empty() {
+                assert_eq!(intersection.intersection(a), intersection);
+                assert_eq!(intersection.intersection(b), intersection);
+            }
+            for (x, y) in [(a.left(), a.top()), (b.right(), b.bottom())] {
+                assert_eq!(
+                    intersection.contains_point(x, y),
+                    a.contains_point(x, y) && b.contains_point(x, y)
+                );
+            }
+
+            let (af, bf) = (Rectf::from(a), Rectf::from(b));
+            assert_eq!(af.intersection(bf), bf.intersection(af));
+            assert_eq!(af.intersects(bf), a.intersects(b));
+            assert_eq!(af.intersection(bf), Rectf::from(intersection));
+        }
+    }
+
+    #[test]
+    fn clamp_to() {
+        let bounds = Recti::new(0, 0, 100, 50);
+        assert_eq!(
+            Recti::new(-5, 45, 10, 10).clamp_to(bounds),
+            Recti::new(0, 40, 10, 10)
+        );
+        assert_eq!(
+            Recti::new(20, 10, 10, 10).clamp_to(bounds),
+            Recti::new(20, 10, 10, 10)
+        );
+        assert_eq!(
+            Recti::new(20, 10, 200, 10).clamp_to(bounds),
+            Recti::new(0, 10, 200, 10)
+        );
+        assert_eq!(
+            Rectf::new(95.5, -1.0, 10.0, 5.0).clamp_to(Rectf::new(0.0, 0.0, 100.0, 50.0)),
+            Rectf::new(90.0, 0.0, 10.0, 5.0)
+        );
+        assert_eq!(
+            <(f64, f64, f64, f64)>::from(Recti::new(1, 2, 3, 4)),
+            (1.0, 2.0, 3.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn intersects() {
+        let rect1 = Recti::new(10, 20, 30, 40);
+        let rect2 = Recti::new(11, 22, 300, 400);
+        let rect3 = Recti::new(5, 6, 10, 20);
+        let rect4 = Recti::new(1, 2, 3, 4);
+        let rect5 = Recti::new(0, 0, 0, 0);
+        assert_eq!(rect1.intersection(rect2), Recti::new(11, 22, 29, 38));
+        assert_eq!(rect1.intersection(rect3), Recti::new(10, 20, 5, 6));
+        assert!(rect1.intersection(rect4).is_empty());
+        assert!(rect1.intersection(rect5).is_empty());
+        assert!(rect1.intersects(rect2));
+        assert!(!rect1.intersects(rect4));
+    }
+}
@@ -0,0 +1,210 @@
+use std::fs::File;
+
+use gif::{ColorOutput, DecodeOptions, DisposalMethod};
+
+use crate::image::{Image, ImageError, SharedImage};
+use crate::palette::Palette;
+use crate::types::Color;
+
+impl Image {
+    pub fn from_gif(filename: &str, palette: &Palette) -> Result<SharedImage, ImageError> {
+        Self::read_gif(filename, palette, false).map(|mut frames| frames.remove(0))
+    }
+
+    pub fn frames_from_gif(
+        filename: &str,
+        palette: &Palette,
+    ) -> Result<Vec<SharedImage>, ImageError> {
+        Self::read_gif(filename, palette, true)
+    }
+
+    fn read_gif(
+        filename: &str,
+        palette: &Palette,
+        is_all_frames: bool,
+    ) -> Result<Vec<SharedImage>, ImageError> {
+        let open_error = || ImageError::Open(filename.to_string());
+        let file = File::open(filename).map_err(|_| open_error())?;
+        let mut options = DecodeOptions::new();
+        options.set_color_output(ColorOutput::Indexed);
+        let mut decoder = options.read_info(file).map_err(|_| open_error())?;
+        let width = decoder.width() as usize;
+        let height = decoder.height() as usize;
+        let global_palette = decoder.global_palette().map(<[u8]>::to_vec);
+        let colors = palette.colors();
+        let mut color_table = Self::color_table(colors);
+
+        // Frames only cover part of the logical screen and are drawn over the previous ones
+        let mut data: Vec<Vec<Color>> = vec![vec![0; width]; height];
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(|_| open_error())? {
+            let gif_palette = frame
+                .palette
+                .as_ref()
+                .or(global_palette.as_ref())
+                .ok_or_else(open_error)?;
+            let frame_colors: Vec<Color> = gif_palette
+                .chunks_exact(3)
+                .map(|rgb| Self::closest_color(&mut color_table, colors, (rgb[0], rgb[1], rgb[2])))
+                .collect();
+            let previous_data = (frame.dispose == DisposalMethod::Previous).then(|| data.clone());
+            let left = frame.left as usize;
+            let top = frame.top as usize;
+            let frame_width = frame.width as usize;
+            for (i, index) in frame.buffer.iter().enumerate() {
+                let (x, y) = (left + i % frame_width, top + i / frame_width);
+                if x >= width || y >= height || Some(*index) == frame.transparent {
+                    continue;
+                }
+                data[y][x] = frame_colors.get(*index as usize).copied().unwrap_or(0);
+            }
+
+            let image = Self::new(width as u32, height as u32);
+            image.lock().canvas.data = data.clone();
+            frames.push(image);
+            if !is_all_frames {
+                break;
+            }
+            match frame.dispose {
+                DisposalMethod::Background => {
+                    for row in data.iter_mut().skip(top).take(frame.height as usize) {
+                        for color in row.iter_mut().skip(left).take(frame_width) {
+                            *color = 0;
+                        }
+                    }
+                }
+                DisposalMethod::Previous => data = previous_data.unwrap(),
+                DisposalMethod::Any | DisposalMethod::Keep => {}
+            }
+        }
+        if frames.is_empty() {
+            return Err(open_error());
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use gif::{Encoder, Frame};
+
+    use super::*;
+    use crate::settings::DEFAULT_COLORS;
+
+    fn write_gif(name: &str, width: u16, height: u16, frames: Vec<Frame>) -> String {
+        let filename = std::env::temp_dir().join(name);
+        let filename = filename.to_str().unwrap().to_string();
+        let file = File::create(&filename).unwrap();
+        let mut encoder = Encoder::new(file, width, height, &[]).unwrap();
+        for frame in frames {
+            encoder.write_frame(&frame).unwrap();
+        }
+        filename
+    }
+
+    fn gif_palette(colors: &[u32]) -> Option<Vec<u8>> {
+        Some(
+            colors
+                .iter()
+                .flat_map(|rgb| [(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8])
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn from_gif() {
+        let palette = Palette::new(&DEFAULT_COLORS);
+        let frame = Frame {
+            width: 3,
+            height: 2,
+            palette: gif_palette(&[DEFAULT_COLORS[8], 0xfefefe, 0x123456, DEFAULT_COLORS[1]]),
+            transparent: Some(2),
+            buffer: Cow::Owned(vec![0, 1, 2, 3, 3, 0]),
+            ..Frame::default()
+        };
+        let filename = write_gif("pyxel_test_from_gif.gif", 3, 2, vec![frame]);
+        let image = Image::from_gif(&filename, &palette).unwrap();
+        assert_eq!(image.lock().canvas.data, [[8, 7, 0], [1, 1, 8]]);
+        std::fs::remove_file(&filename).unwrap();
+        assert!(matches!(
+            Image::from_gif(&filename, &palette),
+            Err(ImageError::Open(_))
+        ));
+    }
+
+    #[test]
+    fn interlaced() {
+        let palette = Palette::new(&DEFAULT_COLORS);
+        let rows: Vec<u8> = (0..9).collect();
+        let interlaced_rows: Vec<u8> = [0, 8, 4, 2, 6, 1, 3, 5, 7]
+            .iter()
+            .map(|y| rows[*y])
+            .collect();
+        let frame = Frame {
+            width: 1,
+            height: 9,
+            interlaced: true,
+            palette: gif_palette(&DEFAULT_COLORS[..9]),
+            buffer: Cow::Owned(interlaced_rows),
+            ..Frame::default()
+        };
+        let filename = write_gif("pyxel_test_interlaced.gif", 1, 9, vec![frame]);
+        let image = Image::from_gif(&filename, &palette).unwrap();
+        let column: Vec<Color> = image.lock().canvas.data.iter().map(|row| row[0]).collect();
+        assert_eq!(column, (0..9).collect::<Vec<Color>>());
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn frames_from_gif() {
+        let palette = Palette::new(&DEFAULT_COLORS);
+        let frame_palette = gif_palette(&DEFAULT_COLORS[..4]);
+        let frames = vec![
+            Frame {
+                width: 2,
+                height: 2,
+                palette: frame_palette.clone(),
+                buffer: Cow::Owned(vec![1, 1, 1, 1]),
+                ..Frame::default()
+            },
+            Frame {
+                left: 1,
+                width: 1,
+                height: 1,
+                palette: frame_palette.clone(),
+                dispose: DisposalMethod::Previous,
+                buffer: Cow::Owned(vec![2]),
+                ..Frame::default()
+            },
+            Frame {
+                top: 1,
+                width: 2,
+                height: 1,
+                palette: frame_palette,
+                transparent: Some(0),
+                dispose: DisposalMethod::Background,
+                buffer: Cow::Owned(vec![0, 3]),
+                ..Frame::default()
+            },
+        ];
+        let filename = write_gif("pyxel_test_frames_from_gif.gif", 2, 2, frames);
+        let frames = Image::frames_from_gif(&filename, &palette).unwrap();
+        let frames: Vec<Vec<Vec<Color>>> = frames
+            .iter()
+            .map(|frame| frame.lock().canvas.data.clone())
+            .collect();
+        assert_eq!(
+            frames,
+            [
+                vec![vec![1, 1], vec![1, 1]],
+                vec![vec![1, 2], vec![1, 1]],
+                vec![vec![1, 1], vec![1, 3]],
+            ]
+        );
+        let image = Image::from_gif(&filename, &palette).unwrap();
+        assert_eq!(image.lock().canvas.data, [[1, 1], [1, 1]]);
+        std::fs::remove_file(&filename).unwrap();
+    }
+}
@@ -1,24 +1,101 @@
+use std::ops::Range;
+
 use array_macro::array;
 
-use crate::image::{Image, SharedImage};
+use crate::animation::Animation;
+use crate::font::SharedFont;
+use crate::image::{Image, ImageError, SharedImage};
+use crate::layer::Layer;
+use crate::palette::Palette;
+use crate::scanline::{ScanlineEffect, Scanlines};
 use crate::settings::{
     CURSOR_DATA, CURSOR_HEIGHT, CURSOR_WIDTH, FONT_DATA, FONT_HEIGHT, FONT_WIDTH, IMAGE_SIZE,
-    NUM_FONT_ROWS, NUM_IMAGES, NUM_TILEMAPS, TILEMAP_SIZE,
+    NUM_FONT_ROWS, NUM_IMAGES, NUM_LAYERS, NUM_TILEMAPS, TILEMAP_SIZE,
 };
+use crate::text_layout::Align;
 use crate::tilemap::{SharedTilemap, Tilemap};
-use crate::types::Color;
+use crate::transition::Transition;
+use crate::types::{Color, Rgb8};
+use crate::ui::VirtualKeyboard;
+use crate::utils::as_i32;
 use crate::Pyxel;
 
 pub struct Graphics {
     images: [SharedImage; NUM_IMAGES as usize],
     tilemaps: [SharedTilemap; NUM_TILEMAPS as usize],
+    pub(crate) transition: Transition,
+    pub(crate) layers: Vec<Layer>,
+    pub(crate) screen_colkey: Color,
+    pub(crate) composited_screen: Vec<Vec<Color>>,
+    scanline_effect: Option<ScanlineEffect>,
+    pub(crate) scanlines: Scanlines,
+    draw_target: Option<u32>,
 }
 
 impl Graphics {
     pub fn new() -> Self {
         let images = array![_ => Image::new(IMAGE_SIZE, IMAGE_SIZE); NUM_IMAGES as usize];
         let tilemaps = array![_ => Tilemap::new(TILEMAP_SIZE, TILEMAP_SIZE, images[0].clone()); NUM_TILEMAPS as usize];
-        Self { images, tilemaps }
+        Self {
+            images,
+            tilemaps,
+            transition: Transition::default(),
+            layers: Vec::new(),
+            screen_colkey: 0,
+            composited_screen: Vec::new(),
+            scanline_effect: None,
+            scanlines: Scanlines::new(),
+            draw_target: None,
+        }
+    }
+
+    fn draw_target_image(&self, screen: &SharedImage) -> SharedImage {
+        self.draw_target.map_or_else(
+            || screen.clone(),
+            |image_no| self.images[image_no as usize].clone(),
+        )
+    }
+
+    // The screen texture keeps its size, so odd quarter turns need a square screen
+    fn rotate90_image(
+        screen: &SharedImage,
+        image: &SharedImage,
+        times: u32,
+    ) -> Result<(), ImageError> {
+        if image.data_ptr() == screen.data_ptr() && times % 2 == 1 {
+            let (width, height) = {
+                let image = image.lock();
+                (image.width(), image.height())
+            };
+            if width != height {
+                return Err(ImageError::ScreenRotation(width, height));
+            }
+        }
+        image.lock().rotate90(times);
+        Ok(())
+    }
+
+    fn copy_screen(&self, screen: &SharedImage, image_no: u32) {
+        let (width, height) = {
+            let screen = screen.lock();
+            (screen.width() as f64, screen.height() as f64)
+        };
+        self.images[image_no as usize].lock().copy(
+            0.0,
+            0.0,
+            screen.clone(),
+            0.0,
+            0.0,
+            width,
+            height,
+            None,
+        );
+    }
+
+    pub(crate) fn update_scanlines(&mut self, height: u32) -> Option<&Scanlines> {
+        let effect = self.scanline_effect.as_mut()?;
+        self.scanlines.update(effect, height);
+        Some(&self.scanlines)
     }
 
     pub fn new_cursor_image() -> SharedImage {
@@ -70,80 +147,221 @@ impl Pyxel {
         self.graphics.tilemaps[image_no as usize].clone()
     }
 
+    pub fn set_draw_target(&mut self, image_no: Option<u32>) {
+        self.graphics.draw_target = image_no;
+    }
+
+    pub fn draw_target(&self) -> SharedImage {
+        self.graphics.draw_target_image(&self.screen)
+    }
+
+    pub fn rotate90(&self, image: &SharedImage, times: u32) -> Result<(), ImageError> {
+        Graphics::rotate90_image(&self.screen, image, times)
+    }
+
+    pub fn screen_to_image(&mut self, image_no: u32) {
+        self.graphics.copy_screen(&self.screen, image_no);
+    }
+
+    pub fn layer(&mut self, layer_no: u32) -> SharedImage {
+        assert!(layer_no < NUM_LAYERS);
+        while self.graphics.layers.len() <= layer_no as usize {
+            let (width, height) = (self.width(), self.height());
+            self.graphics.layers.push(Layer::new(width, height));
+        }
+        self.graphics.layers[layer_no as usize].image.clone()
+    }
+
+    pub fn layer_colkey(&mut self, layer_no: u32, color: Color) {
+        self.layer(layer_no);
+        self.graphics.layers[layer_no as usize].colkey = color;
+    }
+
+    pub fn layer_scroll(&mut self, layer_no: u32, x: i32, y: i32) {
+        self.layer(layer_no);
+        let layer = &mut self.graphics.layers[layer_no as usize];
+        layer.scroll_x = x;
+        layer.scroll_y = y;
+    }
+
+    // Both take screen coordinates, as returned by mouse_x and mouse_y
+    pub fn pick(&self, x: f64, y: f64) -> Option<Color> {
+        self.screen.lock().canvas.pick(x, y)
+    }
+
+    pub fn pick_layer(&self, layer_no: u32, x: f64, y: f64) -> Option<Color> {
+        let layer = self.graphics.layers.get(layer_no as usize)?;
+        self.screen.lock().canvas.pick(x, y)?;
+        Some(layer.pick(as_i32(x), as_i32(y)))
+    }
+
+    // The effect is asked for every row of the screen right before it's presented
+    pub fn set_scanline_effect(&mut self, effect: Option<ScanlineEffect>) {
+        self.graphics.scanline_effect = effect;
+    }
+
+    pub fn set_scanline_palettes(&mut self, palettes: &[Palette]) {
+        self.graphics.scanlines.set_palettes(palettes);
+    }
+
+    pub fn screen_colkey(&mut self, color: Color) {
+        self.graphics.screen_colkey = color;
+    }
+
     pub fn clip(&mut self, x: f64, y: f64, width: f64, height: f64) {
-        self.screen.lock().clip(x, y, width, height);
+        self.draw_target().lock().clip(x, y, width, height);
     }
 
     pub fn clip0(&mut self) {
-        self.screen.lock().clip0();
+        self.draw_target().lock().clip0();
     }
 
     pub fn camera(&mut self, x: f64, y: f64) {
-        self.screen.lock().camera(x, y);
+        self.draw_target().lock().camera(x, y);
     }
 
     pub fn camera0(&mut self) {
-        self.screen.lock().camera0();
+        self.draw_target().lock().camera0();
     }
 
     pub fn pal(&mut self, src_color: Color, dst_color: Color) {
-        self.screen.lock().pal(src_color, dst_color);
+        self.draw_target().lock().pal(src_color, dst_color);
     }
 
     pub fn pal0(&mut self) {
-        self.screen.lock().pal0();
+        self.draw_target().lock().pal0();
+    }
+
+    pub fn pal_cycle(&mut self, range: Range<Color>, shift: i32) {
+        self.palette.cycle(range, shift);
+    }
+
+    pub fn pal_cycle0(&mut self) {
+        self.palette.reset_display_colors();
+    }
+
+    pub fn display_brightness(&mut self, factor: f64) {
+        self.palette.set_display_brightness(factor);
+    }
+
+    pub fn display_tint(&mut self, color: Rgb8, strength: f64) {
+        self.palette.set_display_tint(color, strength);
+    }
+
+    pub fn fade_out(&mut self, frames: u32, color: Rgb8) {
+        self.graphics.transition.fade_out(frames, color);
+    }
+
+    pub fn fade_in(&mut self, frames: u32) {
+        self.graphics.transition.fade_in(frames);
+    }
+
+    pub fn is_transition_done(&self) -> bool {
+        self.graphics.transition.is_done()
+    }
+
+    pub fn fillp(&mut self, pattern: u16) {
+        self.draw_target().lock().fillp(pattern);
+    }
+
+    pub fn fillp_colors(&mut self, color1: Color, color2: Color) {
+        self.draw_target().lock().fillp_colors(color1, color2);
+    }
+
+    pub fn fillp0(&mut self) {
+        self.draw_target().lock().fillp0();
     }
 
     pub fn cls(&mut self, color: Color) {
-        self.screen.lock().cls(color);
+        self.draw_target().lock().cls(color);
     }
 
     pub fn pget(&mut self, x: f64, y: f64) -> Color {
-        self.screen.lock().pget(x, y)
+        self.draw_target().lock().pget(x, y)
     }
 
     pub fn pset(&mut self, x: f64, y: f64, color: Color) {
-        self.screen.lock().pset(x, y, color);
+        self.draw_target().lock().pset(x, y, color);
+    }
+
+    pub fn pset_slice(&mut self, points: &[(i32, i32)], color: Color) {
+        self.draw_target().lock().pset_slice(points, color);
+    }
+
+    pub fn pset_colored(&mut self, points: &[(i32, i32, Color)]) {
+        self.draw_target().lock().pset_colored(points);
     }
 
     pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, color: Color) {
-        self.screen.lock().line(x1, y1, x2, y2, color);
+        self.draw_target().lock().line(x1, y1, x2, y2, color);
+    }
+
+    pub fn line_w(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, color: Color) {
+        self.draw_target()
+            .lock()
+            .line_w(x1, y1, x2, y2, thickness, color);
+    }
+
+    pub fn polyline(&mut self, points: &[(f64, f64)], color: Color) {
+        self.draw_target().lock().polyline(points, color);
+    }
+
+    pub fn bezier(&mut self, x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, color: Color) {
+        self.draw_target()
+            .lock()
+            .bezier(x1, y1, cx, cy, x2, y2, color);
     }
 
     pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
-        self.screen.lock().rect(x, y, width, height, color);
+        self.draw_target().lock().rect(x, y, width, height, color);
     }
 
     pub fn rectb(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
-        self.screen.lock().rectb(x, y, width, height, color);
+        self.draw_target().lock().rectb(x, y, width, height, color);
+    }
+
+    pub fn rectb_w(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        thickness: f64,
+        color: Color,
+    ) {
+        self.draw_target()
+            .lock()
+            .rectb_w(x, y, width, height, thickness, color);
     }
 
     pub fn circ(&mut self, x: f64, y: f64, radius: f64, color: Color) {
-        self.screen.lock().circ(x, y, radius, color);
+        self.draw_target().lock().circ(x, y, radius, color);
     }
 
     pub fn circb(&mut self, x: f64, y: f64, radius: f64, color: Color) {
-        self.screen.lock().circb(x, y, radius, color);
+        self.draw_target().lock().circb(x, y, radius, color);
     }
 
     pub fn elli(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
-        self.screen.lock().elli(x, y, width, height, color);
+        self.draw_target().lock().elli(x, y, width, height, color);
     }
 
     pub fn ellib(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
-        self.screen.lock().ellib(x, y, width, height, color);
+        self.draw_target().lock().ellib(x, y, width, height, color);
     }
 
     pub fn tri(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64, color: Color) {
-        self.screen.lock().tri(x1, y1, x2, y2, x3, y3, color);
+        self.draw_target().lock().tri(x1, y1, x2, y2, x3, y3, color);
     }
 
     pub fn trib(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, x3: f64, y3: f64, color: Color) {
-        self.screen.lock().trib(x1, y1, x2, y2, x3, y3, color);
+        self.draw_target()
+            .lock()
+            .trib(x1, y1, x2, y2, x3, y3, color);
     }
 
     pub fn fill(&mut self, x: f64, y: f64, color: Color) {
-        self.screen.lock().fill(x, y, color);
+        self.draw_target().lock().fill(x, y, color);
     }
 
     pub fn blt(
@@ -156,8 +374,10 @@ impl Pyxel {
         width: f64,
         height: f64,
         color_key: Option<Color>,
+        rotate: Option<f64>,
+        scale: Option<f64>,
     ) {
-        self.screen.lock().blt(
+        self.draw_target().lock().blt(
             x,
             y,
             self.graphics.images[image_no as usize].clone(),
@@ -166,6 +386,37 @@ impl Pyxel {
             width,
             height,
             color_key,
+            rotate,
+            scale,
+        );
+    }
+
+    pub fn blt9(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        image_no: u32,
+        image_x: f64,
+        image_y: f64,
+        image_width: f64,
+        image_height: f64,
+        border: u32,
+        color_key: Option<Color>,
+    ) {
+        self.draw_target().lock().blt9(
+            x,
+            y,
+            width,
+            height,
+            self.graphics.images[image_no as usize].clone(),
+            image_x,
+            image_y,
+            image_width,
+            image_height,
+            border,
+            color_key,
         );
     }
 
@@ -180,7 +431,7 @@ impl Pyxel {
         height: f64,
         color_key: Option<Color>,
     ) {
-        self.screen.lock().bltm(
+        self.draw_target().lock().bltm(
             x,
             y,
             self.graphics.tilemaps[tilemap_no as usize].clone(),
@@ -192,9 +443,177 @@ impl Pyxel {
         );
     }
 
-    pub fn text(&mut self, x: f64, y: f64, string: &str, color: Color) {
-        self.screen
+    pub fn text_with(
+        &mut self,
+        x: f64,
+        y: f64,
+        string: &str,
+        color: Color,
+        font: SharedFont,
+    ) -> u32 {
+        self.draw_target()
+            .lock()
+            .text_with(x, y, string, color, font)
+    }
+
+    pub fn text(&mut self, x: f64, y: f64, string: &str, color: Color) -> u32 {
+        self.draw_target()
+            .lock()
+            .text(x, y, string, color, self.font.clone())
+    }
+
+    pub fn draw_animation(&mut self, animation: &Animation, x: f64, y: f64, flip_h: bool) {
+        let image = self.image(animation.image);
+        animation.draw(&mut self.draw_target().lock(), image, x, y, flip_h);
+    }
+
+    pub fn draw_virtual_keyboard(&mut self, keyboard: &VirtualKeyboard) {
+        keyboard.draw(&mut self.draw_target().lock(), self.font.clone());
+    }
+
+    pub fn text_box(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: u32,
+        string: &str,
+        color: Color,
+        align: Align,
+        font: Option<SharedFont>,
+    ) -> u32 {
+        let draw_target = self.draw_target();
+        let mut draw_target = draw_target.lock();
+        match font {
+            Some(font) => draw_target.text_box_with(x, y, width, string, color, align, font),
+            None => draw_target.text_box(x, y, width, string, color, align, self.font.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw_scene(image: &SharedImage) {
+        let mut image = image.lock();
+        image.cls(1);
+        image.clip(2.0, 2.0, 60.0, 40.0);
+        image.camera(-3.0, 1.0);
+        image.pal(7, 9);
+        image.rect(4.0, 4.0, 20.0, 10.0, 7);
+        image.circ(30.0, 20.0, 8.0, 12);
+        image.line(0.0, 0.0, 63.0, 47.0, 8);
+        image.text(5.0, 30.0, "TARGET", 7, Graphics::new_font_image());
+    }
+
+    #[test]
+    fn draw_target() {
+        let screen = Image::new(64, 48);
+        let mut graphics = Graphics::new();
+        assert_eq!(
+            graphics.draw_target_image(&screen).data_ptr(),
+            screen.data_ptr()
+        );
+        graphics.draw_target = Some(1);
+        let target = graphics.draw_target_image(&screen);
+        assert_eq!(target.data_ptr(), graphics.images[1].data_ptr());
+        draw_scene(&target);
+
+        graphics.draw_target = None;
+        let target = graphics.draw_target_image(&screen);
+        assert_eq!(screen.lock().canvas.camera_x, 0);
+        let self_rect = screen.lock().canvas.self_rect;
+        assert_eq!(screen.lock().canvas.clip_rect, self_rect);
+        target.lock().blt(
+            0.0,
+            0.0,
+            graphics.images[1].clone(),
+            0.0,
+            0.0,
+            64.0,
+            48.0,
+            None,
+            None,
+            None,
+        );
+
+        let direct = Image::new(64, 48);
+        draw_scene(&direct);
+        assert_eq!(screen.lock().canvas.data, direct.lock().canvas.data);
+        assert_eq!(graphics.images[1].lock().canvas.camera_x, -3);
+    }
+
+    #[test]
+    fn rotate90_screen() {
+        let screen = Image::new(4, 2);
+        screen.lock().pset(0.0, 0.0, 3);
+        assert!(matches!(
+            Graphics::rotate90_image(&screen, &screen, 1),
+            Err(ImageError::ScreenRotation(4, 2))
+        ));
+        assert!(matches!(
+            Graphics::rotate90_image(&screen, &screen, 3),
+            Err(ImageError::ScreenRotation(4, 2))
+        ));
+        assert_eq!(screen.lock().width(), 4);
+
+        Graphics::rotate90_image(&screen, &screen, 2).unwrap();
+        assert_eq!(screen.lock().canvas.data[1][3], 3);
+
+        let image = Image::new(4, 2);
+        Graphics::rotate90_image(&screen, &image, 1).unwrap();
+        let image = image.lock();
+        assert_eq!((image.width(), image.height()), (2, 4));
+        drop(image);
+
+        let square_screen = Image::new(3, 3);
+        Graphics::rotate90_image(&square_screen, &square_screen, 1).unwrap();
+    }
+
+    #[test]
+    fn copy_screen() {
+        let screen = Image::new(300, 20);
+        draw_scene(&screen);
+        let graphics = Graphics::new();
+        graphics.images[2].lock().cls(3);
+        graphics.copy_screen(&screen, 2);
+
+        let image = graphics.images[2].lock();
+        let screen = screen.lock();
+        for y in 0..20 {
+            assert_eq!(
+                image.canvas.data[y][..IMAGE_SIZE as usize],
+                screen.canvas.data[y][..IMAGE_SIZE as usize]
+            );
+        }
+        assert_eq!(image.canvas.data[20][0], 3);
+        assert_eq!(
+            image.canvas.data[IMAGE_SIZE as usize - 1][IMAGE_SIZE as usize - 1],
+            3
+        );
+        drop(screen);
+        drop(image);
+    }
+
+    #[test]
+    fn blt_image_to_draw_target() {
+        let screen = Image::new(16, 16);
+        let mut graphics = Graphics::new();
+        graphics.draw_target = Some(0);
+        let source = Image::new(4, 4);
+        source.lock().cls(5);
+        graphics
+            .draw_target_image(&screen)
+            .lock()
+            .blt(2.0, 3.0, source, 0.0, 0.0, 4.0, 4.0, None, None, None);
+        assert_eq!(graphics.images[0].lock().canvas.data[3][2], 5);
+        assert_eq!(graphics.images[0].lock().canvas.data[6][5], 5);
+        assert!(screen
             .lock()
-            .text(x, y, string, color, self.font.clone());
+            .canvas
+            .data
+            .iter()
+            .flatten()
+            .all(|&color| color == 0));
     }
 }
@@ -1,4 +1,6 @@
+use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
 
 use array_macro::array;
@@ -6,12 +8,15 @@ use image::imageops::{self, FilterType};
 use image::{Rgb, RgbImage};
 
 use crate::canvas::{Canvas, CopyArea, ToIndex};
-use crate::rectarea::RectArea;
+use crate::color::rgb_components;
+use crate::font::SharedFont;
+use crate::geometry::Recti;
 use crate::resource::ResourceItem;
 use crate::settings::{
-    FONT_HEIGHT, FONT_WIDTH, MAX_FONT_CODE, MIN_FONT_CODE, NUM_COLORS, NUM_FONT_ROWS,
-    RESOURCE_ARCHIVE_DIRNAME, TILE_SIZE,
+    FONT_HEIGHT, FONT_WIDTH, MAX_COLORS, MAX_FONT_CODE, MAX_SAVE_SCALE, MIN_FONT_CODE,
+    NUM_FONT_ROWS, RESOURCE_ARCHIVE_DIRNAME,
 };
+use crate::text_layout::{break_lines, line_height, Align};
 use crate::tilemap::SharedTilemap;
 use crate::types::{Color, Rgb8};
 use crate::utils::{add_file_extension, as_i32, as_u32, parse_hex_string, simplify_string};
@@ -23,9 +28,41 @@ impl ToIndex for Color {
     }
 }
 
+#[derive(Debug)]
+pub enum ImageError {
+    Open(String),
+    Save(String),
+    TooLarge(u32, u32),
+    InvalidScale(u32),
+    ScreenRotation(u32, u32),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Open(filename) => write!(f, "Unable to open file '{}'", filename),
+            Self::Save(filename) => write!(f, "Unable to save file '{}'", filename),
+            Self::TooLarge(width, height) => {
+                write!(f, "Image size {}x{} exceeds image bank", width, height)
+            }
+            Self::InvalidScale(scale) => {
+                write!(f, "Scale {} is out of range 1 to {}", scale, MAX_SAVE_SCALE)
+            }
+            Self::ScreenRotation(width, height) => {
+                write!(
+                    f,
+                    "Unable to turn the {}x{} screen by 90 degrees",
+                    width, height
+                )
+            }
+        }
+    }
+}
+
 pub struct Image {
     pub(crate) canvas: Canvas<Color>,
-    pub(crate) palette: [Color; NUM_COLORS as usize],
+    pub(crate) palette: [Color; MAX_COLORS as usize],
+    pub(crate) num_remapped_colors: u32,
 }
 
 pub type SharedImage = shared_type!(Image);
@@ -34,48 +71,37 @@ impl Image {
     pub fn new(width: u32, height: u32) -> SharedImage {
         new_shared_type!(Self {
             canvas: Canvas::new(width, height),
-            palette: array![i => i as Color; NUM_COLORS as usize],
+            palette: array![i => i as Color; MAX_COLORS as usize],
+            num_remapped_colors: 0,
         })
     }
 
     pub fn from_image(filename: &str, colors: &[Rgb8]) -> SharedImage {
+        Self::try_from_image(filename, colors).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_from_image(filename: &str, colors: &[Rgb8]) -> Result<SharedImage, ImageError> {
         let image_file = image::open(&Path::new(&filename))
-            .unwrap_or_else(|_| panic!("Unable to open file '{}'", filename))
-            .to_rgb8();
+            .map_err(|_| ImageError::Open(filename.to_string()))?
+            .to_rgba8();
         let (width, height) = image_file.dimensions();
         let image = Self::new(width, height);
         {
             let mut image = image.lock();
-            let mut color_table = HashMap::<(u8, u8, u8), Color>::new();
+            let mut color_table = Self::color_table(colors);
             for y in 0..height {
                 for x in 0..width {
                     let p = image_file.get_pixel(x, y);
-                    let src_rgb = (p[0], p[1], p[2]);
-                    if let Some(color) = color_table.get(&src_rgb) {
-                        image.canvas.data[y as usize][x as usize] = *color;
-                    } else {
-                        let mut closest_color: Color = 0;
-                        let mut closest_dist: f64 = f64::MAX;
-                        for i in 0..NUM_COLORS {
-                            let pal_color = colors[i as usize];
-                            let pal_rgb = (
-                                ((pal_color >> 16) & 0xff) as u8,
-                                ((pal_color >> 8) & 0xff) as u8,
-                                (pal_color & 0xff) as u8,
-                            );
-                            let dist = Self::color_dist(src_rgb, pal_rgb);
-                            if dist < closest_dist {
-                                closest_color = i as Color;
-                                closest_dist = dist;
-                            }
-                        }
-                        color_table.insert(src_rgb, closest_color);
-                        image.canvas.data[y as usize][x as usize] = closest_color;
+                    if p[3] == 0 {
+                        image.canvas.data[y as usize][x as usize] = 0;
+                        continue;
                     }
+                    image.canvas.data[y as usize][x as usize] =
+                        Self::closest_color(&mut color_table, colors, (p[0], p[1], p[2]));
                 }
             }
         }
-        image
+        Ok(image)
     }
 
     pub fn width(&self) -> u32 {
@@ -86,36 +112,49 @@ impl Image {
         self.canvas.height()
     }
 
+    pub fn data(&self) -> &[Vec<Color>] {
+        &self.canvas.data
+    }
+
+    pub fn set_dirty_tracking(&mut self, is_enabled: bool) {
+        self.canvas.set_dirty_tracking(is_enabled);
+    }
+
+    pub fn take_dirty_rects(&mut self) -> Option<Vec<Recti>> {
+        self.canvas.take_dirty_rects()
+    }
+
     pub fn set(&mut self, x: i32, y: i32, data_str: &[&str]) {
-        let width = simplify_string(data_str[0]).len() as u32;
-        let height = data_str.len() as u32;
-        let image = Self::new(width, height);
-        {
-            let mut image = image.lock();
-            for y in 0..height {
-                let src_data = simplify_string(data_str[y as usize]);
-                for x in 0..width {
-                    let color = parse_hex_string(&src_data[x as usize..x as usize + 1]).unwrap();
-                    image.canvas.data[y as usize][x as usize] = color as Color;
+        // Characters other than hex digits are skipped and leave the pixel unchanged
+        for (yi, row) in data_str.iter().enumerate() {
+            for (xi, c) in simplify_string(row).chars().enumerate() {
+                let value_x = x + xi as i32;
+                let value_y = y + yi as i32;
+                if let Some(color) = c.to_digit(16) {
+                    if self.canvas.self_rect.contains_point(value_x, value_y) {
+                        self.canvas.data[value_y as usize][value_x as usize] = color as Color;
+                    }
                 }
             }
         }
-        self.blt(
-            x as f64,
-            y as f64,
-            image,
-            0.0,
-            0.0,
-            width as f64,
-            height as f64,
-            None,
-        );
+        let width = data_str.iter().map(|row| row.len()).max().unwrap_or(0);
+        self.canvas
+            .mark_dirty(Recti::new(x, y, width as u32, data_str.len() as u32));
     }
 
-    pub fn load(&mut self, x: i32, y: i32, filename: &str, colors: &[Rgb8]) {
-        let image = Self::from_image(filename, colors);
+    pub fn load(
+        &mut self,
+        x: i32,
+        y: i32,
+        filename: &str,
+        colors: &[Rgb8],
+    ) -> Result<(), ImageError> {
+        let image = Self::try_from_image(filename, colors)?;
         let width = image.lock().width();
         let height = image.lock().height();
+        if width > self.width() || height > self.height() {
+            return Err(ImageError::TooLarge(width, height));
+        }
         self.blt(
             x as f64,
             y as f64,
@@ -125,16 +164,23 @@ impl Image {
             width as f64,
             height as f64,
             None,
+            None,
+            None,
         );
+        Ok(())
     }
 
-    pub fn save(&self, filename: &str, colors: &[Rgb8], scale: u32) {
+    pub fn save(&self, filename: &str, colors: &[Rgb8], scale: u32) -> Result<(), ImageError> {
+        if scale == 0 || scale > MAX_SAVE_SCALE {
+            return Err(ImageError::InvalidScale(scale));
+        }
         let width = self.width();
         let height = self.height();
         let mut image = RgbImage::new(width, height);
         for y in 0..height {
             for x in 0..width {
-                let rgb = colors[self.canvas.data[y as usize][x as usize] as usize];
+                let color = self.canvas.data[y as usize][x as usize] as usize;
+                let rgb = colors[min(color, colors.len() - 1)];
                 let r = ((rgb >> 16) & 0xff) as u8;
                 let g = ((rgb >> 8) & 0xff) as u8;
                 let b = (rgb & 0xff) as u8;
@@ -145,7 +191,41 @@ impl Image {
         let filename = add_file_extension(filename, ".png");
         image
             .save(&filename)
-            .unwrap_or_else(|_| panic!("Unable to open file '{}'", filename));
+            .map_err(|_| ImageError::Save(filename))
+    }
+
+    pub fn flip_horizontal(&mut self) {
+        self.canvas.flip_horizontal();
+    }
+
+    pub fn flip_vertical(&mut self) {
+        self.canvas.flip_vertical();
+    }
+
+    pub fn rotate90(&mut self, times: u32) {
+        self.canvas.rotate90(times);
+    }
+
+    pub fn remap_colors(&mut self, map: &[Color]) {
+        // Colors past the end of the map are left as they are
+        for color in self.canvas.data.iter_mut().flatten() {
+            if let Some(mapped_color) = map.get(*color as usize) {
+                *color = *mapped_color;
+            }
+        }
+        self.canvas.mark_dirty(self.canvas.self_rect);
+    }
+
+    pub fn find_color_bounds(&self, color: Color) -> Option<Recti> {
+        self.canvas.find_bounds(color)
+    }
+
+    pub fn histogram(&self) -> [u32; MAX_COLORS as usize] {
+        let mut counts = [0; MAX_COLORS as usize];
+        for color in self.canvas.data.iter().flatten() {
+            counts[*color as usize] += 1;
+        }
+        counts
     }
 
     pub fn clip(&mut self, x: f64, y: f64, width: f64, height: f64) {
@@ -165,13 +245,38 @@ impl Image {
     }
 
     pub fn pal(&mut self, src_color: Color, dst_color: Color) {
+        let was_remapped = self.palette[src_color as usize] != src_color;
         self.palette[src_color as usize] = dst_color;
+        let is_remapped = dst_color != src_color;
+        self.num_remapped_colors =
+            self.num_remapped_colors + is_remapped as u32 - was_remapped as u32;
     }
 
     pub fn pal0(&mut self) {
-        for i in 0..NUM_COLORS {
+        for i in 0..MAX_COLORS {
             self.palette[i as usize] = i as Color;
         }
+        self.num_remapped_colors = 0;
+    }
+
+    pub(crate) fn set_palette(&mut self, palette: [Color; MAX_COLORS as usize]) {
+        self.palette = palette;
+        self.num_remapped_colors = (0..MAX_COLORS)
+            .filter(|&i| palette[i as usize] != i as Color)
+            .count() as u32;
+    }
+
+    pub fn fillp(&mut self, pattern: u16) {
+        self.canvas.fillp(pattern);
+    }
+
+    pub fn fillp_colors(&mut self, color1: Color, color2: Color) {
+        self.canvas
+            .fillp_colors(self.palette[color1 as usize], self.palette[color2 as usize]);
+    }
+
+    pub fn fillp0(&mut self) {
+        self.canvas.fillp0();
     }
 
     pub fn cls(&mut self, color: Color) {
@@ -186,11 +291,38 @@ impl Image {
         self.canvas.pset(x, y, self.palette[color as usize]);
     }
 
+    pub fn pset_slice(&mut self, points: &[(i32, i32)], color: Color) {
+        self.canvas.pset_slice(points, self.palette[color as usize]);
+    }
+
+    pub fn pset_colored(&mut self, points: &[(i32, i32, Color)]) {
+        let palette = &self.palette;
+        self.canvas.pset_points(
+            points
+                .iter()
+                .map(|&(x, y, color)| (x, y, palette[color as usize])),
+        );
+    }
+
     pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, color: Color) {
         self.canvas
             .line(x1, y1, x2, y2, self.palette[color as usize]);
     }
 
+    pub fn line_w(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, color: Color) {
+        self.canvas
+            .line_w(x1, y1, x2, y2, thickness, self.palette[color as usize]);
+    }
+
+    pub fn polyline(&mut self, points: &[(f64, f64)], color: Color) {
+        self.canvas.polyline(points, self.palette[color as usize]);
+    }
+
+    pub fn bezier(&mut self, x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, color: Color) {
+        self.canvas
+            .bezier(x1, y1, cx, cy, x2, y2, self.palette[color as usize]);
+    }
+
     pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
         self.canvas
             .rect(x, y, width, height, self.palette[color as usize]);
@@ -201,6 +333,19 @@ impl Image {
             .rectb(x, y, width, height, self.palette[color as usize]);
     }
 
+    pub fn rectb_w(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        thickness: f64,
+        color: Color,
+    ) {
+        self.canvas
+            .rectb_w(x, y, width, height, thickness, self.palette[color as usize]);
+    }
+
     pub fn circ(&mut self, x: f64, y: f64, radius: f64, color: Color) {
         self.canvas.circ(x, y, radius, self.palette[color as usize]);
     }
@@ -244,9 +389,14 @@ impl Image {
         width: f64,
         height: f64,
         transparent: Option<Color>,
+        rotate: Option<f64>,
+        scale: Option<f64>,
     ) {
+        let rotate = rotate.unwrap_or(0.0);
+        let scale = scale.unwrap_or(1.0);
+        let palette = (self.num_remapped_colors > 0).then_some(&self.palette[..]);
         if let Some(image) = image.try_lock() {
-            self.canvas.blt(
+            self.canvas.blt_transform(
                 x,
                 y,
                 &image.canvas,
@@ -255,7 +405,9 @@ impl Image {
                 width,
                 height,
                 transparent,
-                Some(&self.palette),
+                palette,
+                rotate,
+                scale,
             );
         } else {
             let copy_width = as_u32(width.abs());
@@ -272,7 +424,7 @@ impl Image {
                 None,
                 None,
             );
-            self.canvas.blt(
+            self.canvas.blt_transform(
                 x,
                 y,
                 &canvas,
@@ -281,11 +433,121 @@ impl Image {
                 width,
                 height,
                 transparent,
-                Some(&self.palette),
+                palette,
+                rotate,
+                scale,
             );
         }
     }
 
+    pub fn blt9(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        image: SharedImage,
+        image_x: f64,
+        image_y: f64,
+        image_width: f64,
+        image_height: f64,
+        border: u32,
+        transparent: Option<Color>,
+    ) {
+        let spans_x = Self::nine_slice_spans(
+            as_i32(x),
+            as_u32(width),
+            as_i32(image_x),
+            as_u32(image_width),
+            border,
+        );
+        let spans_y = Self::nine_slice_spans(
+            as_i32(y),
+            as_u32(height),
+            as_i32(image_y),
+            as_u32(image_height),
+            border,
+        );
+        for &(dst_y, src_y, span_height) in &spans_y {
+            for &(dst_x, src_x, span_width) in &spans_x {
+                self.blt(
+                    dst_x as f64,
+                    dst_y as f64,
+                    image.clone(),
+                    src_x as f64,
+                    src_y as f64,
+                    span_width as f64,
+                    span_height as f64,
+                    transparent,
+                    None,
+                    None,
+                );
+            }
+        }
+    }
+
+    fn nine_slice_spans(
+        dst: i32,
+        dst_len: u32,
+        src: i32,
+        src_len: u32,
+        border: u32,
+    ) -> Vec<(i32, i32, u32)> {
+        let border = min(border, src_len / 2);
+        let start_len = min(border, dst_len.div_ceil(2));
+        let end_len = min(border, dst_len - start_len);
+        let src_center_len = src_len - border * 2;
+        let dst_center_len = dst_len - start_len - end_len;
+        let mut spans = Vec::new();
+        if start_len > 0 {
+            spans.push((dst, src, start_len));
+        }
+        if src_center_len > 0 {
+            let mut offset = 0;
+            while offset < dst_center_len {
+                let len = min(src_center_len, dst_center_len - offset);
+                spans.push((dst + (start_len + offset) as i32, src + border as i32, len));
+                offset += len;
+            }
+        }
+        if end_len > 0 {
+            spans.push((
+                dst + (dst_len - end_len) as i32,
+                src + (src_len - end_len) as i32,
+                end_len,
+            ));
+        }
+        spans
+    }
+
+    pub fn copy(
+        &mut self,
+        x: f64,
+        y: f64,
+        image: SharedImage,
+        image_x: f64,
+        image_y: f64,
+        width: f64,
+        height: f64,
+        transparent: Option<Color>,
+    ) {
+        if let Some(image) = image.try_lock() {
+            self.canvas.copy(
+                x,
+                y,
+                &image.canvas,
+                image_x,
+                image_y,
+                width,
+                height,
+                transparent,
+            );
+        } else {
+            self.canvas
+                .copy_within(x, y, image_x, image_y, width, height, transparent);
+        }
+    }
+
     pub fn bltm(
         &mut self,
         x: f64,
@@ -305,11 +567,13 @@ impl Image {
         let height = as_i32(height);
 
         let tilemap = tilemap.lock();
-        let tilemap_rect = RectArea::new(
-            tilemap.canvas.self_rect.left() * TILE_SIZE as i32,
-            tilemap.canvas.self_rect.top() * TILE_SIZE as i32,
-            tilemap.canvas.self_rect.width() * TILE_SIZE,
-            tilemap.canvas.self_rect.height() * TILE_SIZE,
+        let transparent = transparent.or(tilemap.colkey);
+        let tile_size = tilemap.tile_size as i32;
+        let tilemap_rect = Recti::new(
+            tilemap.canvas.self_rect.left() * tile_size,
+            tilemap.canvas.self_rect.top() * tile_size,
+            tilemap.canvas.self_rect.width() * tilemap.tile_size,
+            tilemap.canvas.self_rect.height() * tilemap.tile_size,
         );
 
         let CopyArea {
@@ -343,12 +607,15 @@ impl Image {
                 let tilemap_x = src_x + sign_x * xi + offset_x;
                 let tilemap_y = src_y + sign_y * yi + offset_y;
 
-                let tile_x = tilemap_x / TILE_SIZE as i32;
-                let tile_y = tilemap_y / TILE_SIZE as i32;
+                let tile_x = tilemap_x / tile_size;
+                let tile_y = tilemap_y / tile_size;
                 let tile = tilemap.canvas.data[tile_y as usize][tile_x as usize];
 
-                let value_x = tile.0 as i32 * TILE_SIZE as i32 + tilemap_x % TILE_SIZE as i32;
-                let value_y = tile.1 as i32 * TILE_SIZE as i32 + tilemap_y % TILE_SIZE as i32;
+                let value_x = tile.0 as i32 * tile_size + tilemap_x % tile_size;
+                let value_y = tile.1 as i32 * tile_size + tilemap_y % tile_size;
+                if !image.canvas.self_rect.contains_point(value_x, value_y) {
+                    continue;
+                }
                 let value = image.canvas.data[value_y as usize][value_x as usize];
 
                 if let Some(transparent) = transparent {
@@ -360,24 +627,29 @@ impl Image {
                 self.canvas.data[(dst_y + yi) as usize][(dst_x + xi) as usize] = value;
             }
         }
+        self.canvas
+            .mark_dirty(Recti::new(dst_x, dst_y, width as u32, height as u32));
     }
 
-    pub fn text(&mut self, x: f64, y: f64, string: &str, color: Color, font: SharedImage) {
+    pub fn text(&mut self, x: f64, y: f64, string: &str, color: Color, font: SharedImage) -> u32 {
         let mut x = as_i32(x); // No need to reflect camera_x
         let mut y = as_i32(y); // No need to reflect camera_y
         let color = self.palette[color as usize];
         let palette1 = self.palette[1];
         self.pal(1, color);
         let start_x = x;
+        let mut text_width = 0;
         for c in string.chars() {
             if c == '\n' {
                 x = start_x;
                 y += FONT_HEIGHT as i32;
                 continue;
             }
-            if c < MIN_FONT_CODE || c > MAX_FONT_CODE {
-                continue;
-            }
+            let c = if (MIN_FONT_CODE..=MAX_FONT_CODE).contains(&c) {
+                c
+            } else {
+                MAX_FONT_CODE
+            };
             let code = c as i32 - MIN_FONT_CODE as i32;
             let src_x = (code % NUM_FONT_ROWS as i32) * FONT_WIDTH as i32;
             let src_y = (code / NUM_FONT_ROWS as i32) * FONT_HEIGHT as i32;
@@ -390,10 +662,118 @@ impl Image {
                 FONT_WIDTH as f64,
                 FONT_HEIGHT as f64,
                 Some(0),
+                None,
+                None,
             );
             x += FONT_WIDTH as i32;
+            text_width = max(text_width, (x - start_x) as u32);
         }
         self.pal(1, palette1);
+        text_width
+    }
+
+    pub fn text_with(
+        &mut self,
+        x: f64,
+        y: f64,
+        string: &str,
+        color: Color,
+        font: SharedFont,
+    ) -> u32 {
+        let x = as_i32(x);
+        let y = as_i32(y);
+        let color = self.palette[color as usize];
+        let font = font.lock();
+        let points = match font.image.try_lock() {
+            Some(font_image) => font.glyph_points(&font_image.canvas, string),
+            None => font.glyph_points(&self.canvas, string),
+        };
+        for (xi, yi) in points {
+            self.canvas.pset((x + xi) as f64, (y + yi) as f64, color);
+        }
+        font.text_width(string)
+    }
+
+    pub fn text_box(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: u32,
+        string: &str,
+        color: Color,
+        align: Align,
+        font: SharedImage,
+    ) -> u32 {
+        let lines = break_lines(width, string, None);
+        for (i, line) in lines.iter().enumerate() {
+            self.text(
+                x + align.offset(width, line.width) as f64,
+                y + (i as u32 * FONT_HEIGHT) as f64,
+                &line.text,
+                color,
+                font.clone(),
+            );
+        }
+        lines.len() as u32 * FONT_HEIGHT
+    }
+
+    pub fn text_box_with(
+        &mut self,
+        x: f64,
+        y: f64,
+        width: u32,
+        string: &str,
+        color: Color,
+        align: Align,
+        font: SharedFont,
+    ) -> u32 {
+        let (lines, line_height) = {
+            let font = font.lock();
+            (
+                break_lines(width, string, Some(&font)),
+                line_height(Some(&font)),
+            )
+        };
+        for (i, line) in lines.iter().enumerate() {
+            self.text_with(
+                x + align.offset(width, line.width) as f64,
+                y + (i as u32 * line_height) as f64,
+                &line.text,
+                color,
+                font.clone(),
+            );
+        }
+        lines.len() as u32 * line_height
+    }
+
+    pub(crate) fn color_table(colors: &[Rgb8]) -> HashMap<(u8, u8, u8), Color> {
+        let mut color_table = HashMap::new();
+        for (i, pal_color) in colors.iter().enumerate() {
+            color_table
+                .entry(rgb_components(*pal_color))
+                .or_insert(i as Color);
+        }
+        color_table
+    }
+
+    pub(crate) fn closest_color(
+        color_table: &mut HashMap<(u8, u8, u8), Color>,
+        colors: &[Rgb8],
+        src_rgb: (u8, u8, u8),
+    ) -> Color {
+        // Exact matches come from the table, and the nearest colors found are cached in it
+        *color_table.entry(src_rgb).or_insert_with(|| {
+            let mut closest_color: Color = 0;
+            let mut closest_dist: f64 = f64::MAX;
+            for (i, pal_color) in colors.iter().enumerate() {
+                let dist = Self::color_dist(src_rgb, rgb_components(*pal_color));
+                if dist < closest_dist {
+                    closest_color = i as Color;
+                    closest_dist = dist;
+                }
+            }
+            closest_color
+        })
     }
 
     fn color_dist(rgb1: (u8, u8, u8), rgb2: (u8, u8, u8)) -> f64 {
@@ -437,11 +817,502 @@ impl ResourceItem for Image {
         output
     }
 
-    fn deserialize(&mut self, _pyxel: &Pyxel, _version: u32, input: &str) {
-        for (i, line) in input.lines().enumerate() {
+    fn deserialize(
+        &mut self,
+        _images: &[SharedImage],
+        _version: u32,
+        input: &str,
+    ) -> Result<(), String> {
+        let width = input.lines().map(str::len).max().unwrap_or(0) as u32;
+        let height = input.lines().count() as u32;
+        if width > self.width() || height > self.height() {
+            return Err(format!(
+                "image data is {}x{} but the image is {}x{}",
+                width,
+                height,
+                self.width(),
+                self.height()
+            ));
+        }
+        let mut rows = Vec::with_capacity(height as usize);
+        for line in input.lines() {
+            let mut row = Vec::with_capacity(line.len());
             string_loop!(j, color, line, 1, {
-                self.canvas.data[i][j] = parse_hex_string(&color).unwrap() as Color;
+                let color =
+                    parse_hex_string(&color).map_err(|_| format!("invalid color '{}'", color))?;
+                row.push(color as Color);
             });
+            rows.push(row);
+        }
+        for (i, row) in rows.into_iter().enumerate() {
+            self.canvas.data[i][..row.len()].copy_from_slice(&row);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::Font;
+    use crate::graphics::Graphics;
+    use crate::settings::DEFAULT_COLORS;
+    use crate::tilemap::Tilemap;
+
+    #[test]
+    fn from_image() {
+        let filename = std::env::temp_dir().join("pyxel_test_from_image.png");
+        let filename = filename.to_str().unwrap();
+        let mut png = image::RgbaImage::new(4, 1);
+        png.put_pixel(0, 0, image::Rgba([0x2b, 0x33, 0x5f, 0xff]));
+        png.put_pixel(1, 0, image::Rgba([0xf0, 0xf0, 0xf0, 0xff]));
+        png.put_pixel(2, 0, image::Rgba([0x29, 0x36, 0x6f, 0x00]));
+        png.put_pixel(3, 0, image::Rgba([0xe0, 0x10, 0x50, 0x80]));
+        png.save(filename).unwrap();
+
+        let image = Image::try_from_image(filename, &DEFAULT_COLORS).unwrap();
+        assert_eq!(image.lock().canvas.data[0], [1, 7, 0, 8]);
+
+        let bank = Image::new(4, 4);
+        bank.lock().load(0, 2, filename, &DEFAULT_COLORS).unwrap();
+        assert_eq!(bank.lock().canvas.data[2], [1, 7, 0, 8]);
+        let bank = Image::new(3, 3);
+        assert!(matches!(
+            bank.lock().load(0, 0, filename, &DEFAULT_COLORS),
+            Err(ImageError::TooLarge(4, 1))
+        ));
+        std::fs::remove_file(filename).unwrap();
+        assert!(matches!(
+            Image::try_from_image(filename, &DEFAULT_COLORS),
+            Err(ImageError::Open(_))
+        ));
+    }
+
+    #[test]
+    fn save() {
+        let filename = std::env::temp_dir().join("pyxel_test_save");
+        let filename = filename.to_str().unwrap();
+        let image = Image::new(3, 2);
+        image.lock().set(0, 0, &["123", "f07"]);
+        image.lock().save(filename, &DEFAULT_COLORS, 2).unwrap();
+
+        let filename = format!("{}.png", filename);
+        let saved = Image::try_from_image(&filename, &DEFAULT_COLORS).unwrap();
+        assert_eq!(saved.lock().width(), 6);
+        assert_eq!(saved.lock().height(), 4);
+        assert_eq!(saved.lock().canvas.data[0], [1, 1, 2, 2, 3, 3]);
+        assert_eq!(saved.lock().canvas.data[3], [15, 15, 0, 0, 7, 7]);
+        std::fs::remove_file(&filename).unwrap();
+
+        for scale in [0, MAX_SAVE_SCALE + 1] {
+            assert!(matches!(
+                image.lock().save(&filename, &DEFAULT_COLORS, scale),
+                Err(ImageError::InvalidScale(_))
+            ));
         }
+        assert!(matches!(
+            image
+                .lock()
+                .save("/nonexistent/dir/image", &DEFAULT_COLORS, 1),
+            Err(ImageError::Save(_))
+        ));
+    }
+
+    #[test]
+    fn transform() {
+        let image = Image::new(3, 2);
+        image.lock().set(0, 0, &["012", "345"]);
+        image.lock().clip(0.0, 0.0, 1.0, 1.0);
+        let mut image = image.lock();
+        image.rotate90(1);
+        assert_eq!((image.width(), image.height()), (2, 3));
+        assert_eq!(image.canvas.data, [[3, 0], [4, 1], [5, 2]]);
+        image.pset(1.0, 2.0, 7);
+        assert_eq!(image.canvas.data[2], [5, 7]);
+        image.flip_horizontal();
+        image.flip_vertical();
+        assert_eq!(image.canvas.data, [[7, 5], [1, 4], [0, 3]]);
+
+        image.remap_colors(&[9, 8, 7, 6, 5]);
+        assert_eq!(image.canvas.data, [[7, 5], [8, 5], [9, 6]]);
+        image.remap_colors(&[]);
+        assert_eq!(image.canvas.data, [[7, 5], [8, 5], [9, 6]]);
+        drop(image);
+    }
+
+    #[test]
+    fn set() {
+        let image = Image::new(4, 3);
+        image.lock().camera(1.0, 1.0);
+        image.lock().pal(1, 2);
+        image.lock().set(-1, 1, &["01 23", "4x6789", "ab"]);
+        assert_eq!(
+            image.lock().canvas.data,
+            [[0, 0, 0, 0], [1, 2, 3, 0], [0, 6, 7, 8]]
+        );
+        image.lock().set(2, 0, &["fF"]);
+        assert_eq!(image.lock().canvas.data[0], [0, 0, 15, 15]);
+    }
+
+    #[test]
+    fn blt9() {
+        let src = Image::new(4, 4);
+        src.lock().set(0, 0, &["1223", "4556", "4786", "9aab"]);
+        let dst = Image::new(7, 5);
+        dst.lock()
+            .blt9(0.0, 0.0, 7.0, 5.0, src.clone(), 0.0, 0.0, 4.0, 4.0, 1, None);
+        assert_eq!(
+            dst.lock().canvas.data,
+            [
+                [1, 2, 2, 2, 2, 2, 3],
+                [4, 5, 5, 5, 5, 5, 6],
+                [4, 7, 8, 7, 8, 7, 6],
+                [4, 5, 5, 5, 5, 5, 6],
+                [9, 10, 10, 10, 10, 10, 11]
+            ]
+        );
+
+        let dst = Image::new(4, 3);
+        dst.lock().cls(15);
+        dst.lock().camera(-1.0, 0.0);
+        dst.lock().pal(1, 12);
+        dst.lock().blt9(
+            0.0,
+            0.0,
+            3.0,
+            3.0,
+            src.clone(),
+            0.0,
+            0.0,
+            4.0,
+            4.0,
+            1,
+            Some(5),
+        );
+        assert_eq!(
+            dst.lock().canvas.data,
+            [[15, 12, 2, 3], [15, 4, 15, 6], [15, 9, 10, 11]]
+        );
+
+        let dst = Image::new(3, 3);
+        dst.lock()
+            .blt9(0.0, 0.0, 3.0, 1.0, src, 0.0, 0.0, 4.0, 4.0, 8, None);
+        assert_eq!(dst.lock().canvas.data[0], [1, 2, 3]);
+    }
+
+    #[test]
+    fn copy() {
+        let src = Image::new(4, 2);
+        src.lock().set(0, 0, &["1234", "5678"]);
+        let dst = Image::new(4, 3);
+        dst.lock().camera(1.0, 1.0);
+        dst.lock().pal(1, 9);
+        dst.lock()
+            .copy(-1.0, 2.0, src.clone(), 0.0, 0.0, 4.0, 2.0, Some(3));
+        dst.lock().copy(2.0, -1.0, src, -1.0, 0.0, 3.0, 2.0, None);
+        assert_eq!(
+            dst.lock().canvas.data,
+            [[0, 0, 0, 5], [0, 0, 0, 0], [2, 0, 4, 0]]
+        );
+    }
+
+    #[test]
+    fn copy_overlap() {
+        let rows = ["12345", "6789a", "bcdef", "01234", "56789"];
+        let copied = |x: f64, y: f64| {
+            let image = Image::new(5, 5);
+            image.lock().set(0, 0, &rows);
+            image
+                .lock()
+                .copy(x, y, image.clone(), 1.0, 1.0, 3.0, 3.0, None);
+            let data = image.lock().canvas.data.clone();
+            data
+        };
+        let expected = |x: usize, y: usize| {
+            let image = Image::new(5, 5);
+            image.lock().set(0, 0, &rows);
+            let mut data = image.lock().canvas.data.clone();
+            for yi in 0..3 {
+                for xi in 0..3 {
+                    data[y + yi][x + xi] = image.lock().canvas.data[1 + yi][1 + xi];
+                }
+            }
+            data
+        };
+        assert_eq!(copied(0.0, 1.0), expected(0, 1));
+        assert_eq!(copied(2.0, 1.0), expected(2, 1));
+        assert_eq!(copied(1.0, 0.0), expected(1, 0));
+        assert_eq!(copied(1.0, 2.0), expected(1, 2));
+        assert_eq!(copied(0.0, 0.0), expected(0, 0));
+        assert_eq!(copied(2.0, 2.0), expected(2, 2));
+    }
+
+    #[test]
+    fn pal() {
+        let image = Image::new(4, 1);
+        image.lock().pal(1, 2);
+        image.lock().pal(1, 3);
+        image.lock().pal(2, 4);
+        assert_eq!(image.lock().num_remapped_colors, 2);
+        image.lock().pset(0.0, 0.0, 1);
+        image.lock().pset(1.0, 0.0, 2);
+        image.lock().pal0();
+        assert_eq!(image.lock().num_remapped_colors, 0);
+        image.lock().pset(2.0, 0.0, 1);
+        assert_eq!(image.lock().canvas.data[0], [3, 4, 1, 0]);
+
+        image.lock().pal(5, 6);
+        image.lock().pal(5, 5);
+        assert_eq!(image.lock().num_remapped_colors, 0);
+        let mut palette = image.lock().palette;
+        palette[7] = 8;
+        image.lock().set_palette(palette);
+        assert_eq!(image.lock().num_remapped_colors, 1);
+    }
+
+    #[test]
+    fn pal_blt() {
+        let src = Image::new(3, 1);
+        src.lock().canvas.data[0] = vec![1, 2, 3];
+        let dst = Image::new(3, 1);
+        dst.lock().cls(5);
+        dst.lock().pal(1, 2);
+        dst.lock().pal(3, 1);
+        dst.lock()
+            .blt(0.0, 0.0, src, 0.0, 0.0, 3.0, 1.0, Some(1), None, None);
+        assert_eq!(dst.lock().canvas.data[0], [5, 2, 1]);
+    }
+
+    fn to_pattern(image: &SharedImage) -> Vec<String> {
+        image
+            .lock()
+            .canvas
+            .data
+            .iter()
+            .map(|row| row.iter().map(|color| format!("{:x}", color)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn text() {
+        let font = Graphics::new_font_image();
+        let image = Image::new(12, 12);
+        let width = image.lock().text(0.0, 0.0, "Hi!", 8, font.clone());
+        assert_eq!(width, 12);
+        assert_eq!(
+            to_pattern(&image)[..6],
+            [
+                "808008000800",
+                "808000000800",
+                "888008000800",
+                "808008000000",
+                "808008000800",
+                "000000000000",
+            ]
+        );
+
+        let image = Image::new(12, 12);
+        image.lock().camera(-1.0, 0.0);
+        image.lock().pal(2, 14);
+        let width = image.lock().text(0.0, 1.0, "\u{e9}\nab\t", 2, font);
+        assert_eq!(width, 12);
+        assert_eq!(
+            to_pattern(&image)[1..12],
+            [
+                "0eee00000000",
+                "0eee00000000",
+                "0eee00000000",
+                "0eee00000000",
+                "0eee00000000",
+                "000000000000",
+                "00000e000eee",
+                "00ee0ee00eee",
+                "0e0e0e0e0eee",
+                "0e0e0e0e0eee",
+                "00ee0ee00eee",
+            ]
+        );
+    }
+
+    #[test]
+    fn text_box() {
+        let font = Graphics::new_font_image();
+        let image = Image::new(24, 20);
+        let mut image = image.lock();
+        let height = image.text_box(0.0, 0.0, 24, "ab c\n!", 8, Align::Right, font.clone());
+        assert_eq!(height, 12);
+        let (width, _) = crate::measure_text_box(24, "ab c\n!", None);
+        assert_eq!(width, 16);
+
+        let expected = Image::new(24, 20);
+        let mut expected = expected.lock();
+        expected.text(8.0, 0.0, "ab c", 8, font.clone());
+        expected.text(20.0, 6.0, "!", 8, font.clone());
+        assert_eq!(image.canvas.data, expected.canvas.data);
+
+        expected.cls(0);
+        image.cls(0);
+        let height = image.text_box(0.0, 2.0, 12, "ab cd", 3, Align::Center, font.clone());
+        assert_eq!(height, 12);
+        expected.text(2.0, 2.0, "ab", 3, font.clone());
+        expected.text(2.0, 8.0, "cd", 3, font);
+        assert_eq!(image.canvas.data, expected.canvas.data);
+        drop(expected);
+        drop(image);
+    }
+
+    #[test]
+    fn text_with() {
+        let font_image = Image::new(24, 8);
+        font_image.lock().cls(3);
+        font_image.lock().pset(0.0, 0.0, 1);
+        font_image.lock().pset(11.0, 7.0, 2);
+        font_image.lock().pset(14.0, 1.0, 0);
+        let font = Font::from_image(font_image.clone(), 12, 8, 'A');
+        font.lock().colkey = 3;
+
+        let image = Image::new(24, 16);
+        let width = image.lock().text_with(0.0, 0.0, "AB\nB", 7, font);
+        assert_eq!(width, 24);
+        let data = image.lock().canvas.data.clone();
+        assert_eq!(data[0][0], 7);
+        assert_eq!(data[7][11], 7);
+        assert_eq!(data[1][14], 7);
+        assert_eq!(data[9][2], 7);
+        assert_eq!(
+            data.iter().flatten().filter(|&&color| color == 7).count(),
+            4
+        );
+
+        let font = Font::with_widths(font_image.clone(), 12, 8, 'A', &[2, 12]);
+        font.lock().colkey = 3;
+        image.lock().cls(0);
+        image.lock().camera(1.0, 0.0);
+        let width = image.lock().text_with(0.0, 0.0, "AAB", 5, font.clone());
+        assert_eq!(width, 16);
+        let data = image.lock().canvas.data.clone();
+        assert_eq!(data[0][..4], [0, 5, 0, 0]);
+        assert_eq!(data[1][5], 5);
+        assert_eq!(data[7][10], 0);
+
+        let width = font_image.lock().text_with(0.0, 0.0, "B", 4, font);
+        assert_eq!(width, 12);
+        assert_eq!(font_image.lock().canvas.data[1][2], 4);
+    }
+
+    #[test]
+    fn bltm() {
+        for tile_size in [8, 5] {
+            let image = Image::new(32, 32);
+            for y in 0..32 {
+                for x in 0..32 {
+                    image.lock().canvas.data[y][x] = ((x * 7 + y * 3) % 16) as Color;
+                }
+            }
+            let tilemap = Tilemap::new(6, 6, image.clone());
+            tilemap.lock().set_tile_size(tile_size).unwrap();
+            for y in 0..6 {
+                for x in 0..6 {
+                    tilemap.lock().canvas.data[y][x] = ((x + y) as u8 % 3, (x * y) as u8 % 4);
+                }
+            }
+            let size = tile_size as f64;
+
+            let dst = Image::new(40, 40);
+            dst.lock().camera(-3.0, 2.0);
+            dst.lock().bltm(
+                0.0,
+                0.0,
+                tilemap.clone(),
+                size,
+                2.0 * size,
+                4.0 * size,
+                4.0 * size,
+                Some(5),
+            );
+
+            let expected = Image::new(40, 40);
+            expected.lock().camera(-3.0, 2.0);
+            for ty in 0..4 {
+                for tx in 0..4 {
+                    let tile = tilemap.lock().canvas.data[ty + 2][tx + 1];
+                    expected.lock().blt(
+                        tx as f64 * size,
+                        ty as f64 * size,
+                        image.clone(),
+                        tile.0 as f64 * size,
+                        tile.1 as f64 * size,
+                        size,
+                        size,
+                        Some(5),
+                        None,
+                        None,
+                    );
+                }
+            }
+            assert_eq!(dst.lock().canvas.data, expected.lock().canvas.data);
+        }
+    }
+
+    #[test]
+    fn bltm_colkey() {
+        let image = Image::new(16, 8);
+        image.lock().rect(0.0, 0.0, 8.0, 8.0, 11);
+        image.lock().pset(3.0, 2.0, 4);
+        let tilemap = Tilemap::new(1, 1, image);
+        let dst = Image::new(8, 8);
+        dst.lock().cls(7);
+        dst.lock()
+            .bltm(0.0, 0.0, tilemap.clone(), 0.0, 0.0, 8.0, 8.0, None);
+        assert_eq!(dst.lock().canvas.data[0][0], 11);
+
+        tilemap.lock().colkey = Some(11);
+        dst.lock().cls(7);
+        dst.lock()
+            .bltm(0.0, 0.0, tilemap.clone(), 0.0, 0.0, 8.0, 8.0, None);
+        assert_eq!(dst.lock().canvas.data[0][0], 7);
+        assert_eq!(dst.lock().canvas.data[2][3], 4);
+        dst.lock()
+            .bltm(0.0, 0.0, tilemap, 0.0, 0.0, 8.0, 8.0, Some(4));
+        assert_eq!(dst.lock().canvas.data[0][0], 11);
+        assert_eq!(dst.lock().canvas.data[2][3], 4);
+    }
+
+    #[test]
+    fn bltm_camera() {
+        let image = Image::new(16, 8);
+        image.lock().rect(8.0, 0.0, 8.0, 8.0, 3);
+        let tilemap = Tilemap::new(4, 4, image);
+        tilemap.lock().cls((1, 0));
+
+        let shifted = Image::new(16, 16);
+        shifted
+            .lock()
+            .bltm(-5.0, -3.0, tilemap.clone(), 0.0, 0.0, 32.0, 32.0, None);
+        let dst = Image::new(16, 16);
+        dst.lock().camera(5.0, 3.0);
+        dst.lock()
+            .bltm(0.0, 0.0, tilemap, 0.0, 0.0, 32.0, 32.0, None);
+        assert_eq!(dst.lock().canvas.data, shifted.lock().canvas.data);
+        assert_eq!(dst.lock().canvas.data[15][15], 3);
+    }
+
+    #[test]
+    fn histogram() {
+        let image = Image::new(4, 2);
+        image.lock().set(0, 0, &["0123", "3f3f"]);
+        let counts = image.lock().histogram();
+        assert_eq!(counts[..4], [1, 1, 1, 3]);
+        assert_eq!(counts[15], 2);
+        assert_eq!(counts.iter().sum::<u32>(), 8);
+        assert_eq!(
+            image.lock().find_color_bounds(3),
+            Some(Recti::new(0, 0, 4, 2))
+        );
+        assert_eq!(
+            image.lock().find_color_bounds(15),
+            Some(Recti::new(1, 1, 3, 1))
+        );
+        assert_eq!(image.lock().find_color_bounds(7), None);
     }
 }
@@ -1,10 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::event::Event;
+use log::warn;
+
+use crate::action_map::{ActionMap, Binding};
+use crate::deadzone::Deadzone;
+use crate::event::{ControllerAxis, ControllerButton, Event, TimedEvent};
 use crate::key::*;
 use crate::platform::Platform;
+use crate::settings::{VIRTUAL_GAMEPAD, VIRTUAL_GAMEPAD_AXIS_VALUE};
 use crate::types::{Key, KeyValue};
-use crate::utils::as_i32;
+use crate::utils::{as_i32, as_u32};
 use crate::Pyxel;
 
 #[derive(PartialEq)]
@@ -17,22 +22,52 @@ enum KeyState {
 
 pub struct Input {
     is_mouse_visible: bool,
+    is_mouse_on_screen: bool,
+    mouse_window_pos: (i32, i32),
     key_states: HashMap<Key, (u32, KeyState)>,
     key_values: HashMap<Key, KeyValue>,
+    gamepad_button_sources: HashMap<Key, HashSet<u32>>,
+    gamepad_axis_sources: HashMap<Key, HashMap<u32, KeyValue>>,
+    virtual_gamepad: HashMap<Key, ControllerButton>,
+    axis_deadzones: [Deadzone; 6],
+    is_radial_deadzone: bool,
+    action_map: ActionMap,
+    wheel_remainder_x: f32,
+    wheel_remainder_y: f32,
     input_keys: Vec<Key>,
     input_text: String,
+    editing_text: String,
+    editing_start: i32,
+    editing_length: i32,
     drop_files: Vec<String>,
+    pending_press_times: Vec<u64>,
+    last_press_latency_us: Option<u64>,
 }
 
 impl Input {
     pub fn new() -> Self {
         Self {
             is_mouse_visible: false,
+            is_mouse_on_screen: false,
+            mouse_window_pos: (0, 0),
             key_states: HashMap::new(),
             key_values: HashMap::new(),
+            gamepad_button_sources: HashMap::new(),
+            gamepad_axis_sources: HashMap::new(),
+            virtual_gamepad: HashMap::new(),
+            axis_deadzones: [Deadzone::default(); 6],
+            is_radial_deadzone: false,
+            action_map: ActionMap::new(),
+            wheel_remainder_x: 0.0,
+            wheel_remainder_y: 0.0,
             input_keys: Vec::new(),
             input_text: "".to_string(),
+            editing_text: "".to_string(),
+            editing_start: 0,
+            editing_length: 0,
             drop_files: Vec::new(),
+            pending_press_times: Vec::new(),
+            last_press_latency_us: None,
         }
     }
 
@@ -40,6 +75,14 @@ impl Input {
         self.is_mouse_visible
     }
 
+    pub fn mouse_on_screen(&self) -> bool {
+        self.is_mouse_on_screen
+    }
+
+    pub fn mouse_window_pos(&self) -> (i32, i32) {
+        self.mouse_window_pos
+    }
+
     pub fn reset_input_states(&mut self) {
         self.key_values.insert(MOUSE_WHEEL_X, 0);
         self.key_values.insert(MOUSE_WHEEL_Y, 0);
@@ -48,78 +91,353 @@ impl Input {
         self.drop_files.clear();
     }
 
+    pub fn skip_frames(&mut self, num_frames: u32, current_frame_count: u32) {
+        // Frames the game never saw are dropped so held keys keep their hold time,
+        // and anything that changed during them happens on the current frame
+        for (frame_count, _) in self.key_states.values_mut() {
+            *frame_count = (*frame_count + num_frames).min(current_frame_count);
+        }
+    }
+
+    pub fn last_press_latency_us(&self) -> Option<u64> {
+        self.last_press_latency_us
+    }
+
+    pub fn process_timed_event(&mut self, event: TimedEvent, frame_count: u32) {
+        if event.event.is_button_press() {
+            self.pending_press_times.push(event.timestamp_us);
+        }
+        self.process_input_event(event.event, frame_count);
+    }
+
+    pub fn take_press_latencies(&mut self, update_start_us: u64) -> impl Iterator<Item = u64> + '_ {
+        // Presses are measured against the update that first sees them
+        if let Some(&timestamp_us) = self.pending_press_times.last() {
+            self.last_press_latency_us = Some(update_start_us.saturating_sub(timestamp_us));
+        }
+        self.pending_press_times
+            .drain(..)
+            .map(move |timestamp_us| update_start_us.saturating_sub(timestamp_us))
+    }
+
     pub fn process_input_event(&mut self, event: Event, frame_count: u32) {
+        self.action_map.capture_event(&event);
         match event {
             // System events
             Event::Quit => {}
-            Event::DropFile { filename } => {
+            Event::DropFile { filename, .. } => {
                 self.drop_files.push(filename);
             }
+            Event::DropText { .. } => {}
 
             // Window events
             Event::Shown => {}
             Event::Hidden => {}
+            Event::AuxWindow { .. } => {}
+
+            // Audio events
+            Event::AudioDeviceRemoved => {}
 
             // Key events
             Event::KeyDown { keycode } => {
                 self.press_key(keycode, frame_count);
+                if let Some(button) = self.virtual_gamepad.get(&keycode).copied() {
+                    self.process_virtual_gamepad_event(button, true, frame_count);
+                }
             }
             Event::KeyUp { keycode } => {
                 self.release_key(keycode, frame_count);
+                if let Some(button) = self.virtual_gamepad.get(&keycode).copied() {
+                    self.process_virtual_gamepad_event(button, false, frame_count);
+                }
             }
             Event::TextInput { text } => {
                 self.input_text += &text;
+                self.editing_text.clear();
+                self.editing_start = 0;
+                self.editing_length = 0;
+            }
+            Event::TextEditing {
+                text,
+                start,
+                length,
+            } => {
+                self.editing_text = text;
+                self.editing_start = start;
+                self.editing_length = length;
             }
 
             // Mouse events
-            Event::MouseMotion { x, y } => {
+            Event::MouseMotion {
+                x,
+                y,
+                window_x,
+                window_y,
+                is_on_screen,
+            } => {
                 self.key_values.insert(MOUSE_POS_X, x);
                 self.key_values.insert(MOUSE_POS_Y, y);
+                self.mouse_window_pos = (window_x, window_y);
+                self.is_mouse_on_screen = is_on_screen;
             }
-            Event::MouseButtonDown { button } => {
+            // Clicks over the letterbox are still delivered, clamped to the screen edge
+            Event::MouseButtonDown {
+                button,
+                x,
+                y,
+                is_on_screen,
+            } => {
+                self.key_values.insert(MOUSE_POS_X, x);
+                self.key_values.insert(MOUSE_POS_Y, y);
+                self.is_mouse_on_screen = is_on_screen;
                 self.press_key(MOUSE_BUTTON_LEFT + button as Key, frame_count);
             }
-            Event::MouseButtonUp { button } => {
+            Event::MouseButtonUp {
+                button,
+                x,
+                y,
+                is_on_screen,
+            } => {
+                self.key_values.insert(MOUSE_POS_X, x);
+                self.key_values.insert(MOUSE_POS_Y, y);
+                self.is_mouse_on_screen = is_on_screen;
                 self.release_key(MOUSE_BUTTON_LEFT + button as Key, frame_count);
             }
-            Event::MouseWheel { x, y } => {
-                *self.key_values.entry(MOUSE_WHEEL_X).or_insert(0) += x;
-                *self.key_values.entry(MOUSE_WHEEL_Y).or_insert(0) += y;
+            Event::MouseWheel {
+                precise_x,
+                precise_y,
+                ..
+            } => {
+                self.wheel_remainder_x += precise_x;
+                self.wheel_remainder_y += precise_y;
+                let steps_x = self.wheel_remainder_x.trunc();
+                let steps_y = self.wheel_remainder_y.trunc();
+                self.wheel_remainder_x -= steps_x;
+                self.wheel_remainder_y -= steps_y;
+                *self.key_values.entry(MOUSE_WHEEL_X).or_insert(0) += steps_x as KeyValue;
+                *self.key_values.entry(MOUSE_WHEEL_Y).or_insert(0) += steps_y as KeyValue;
             }
 
             // Controller events
             Event::ControllerAxisMotion { which, axis, value } => {
-                let offset = if which == 0 {
-                    0
-                } else if which == 1 {
-                    GAMEPAD2_AXIS_LEFTX - GAMEPAD1_AXIS_LEFTX
+                let offset = if let Some(offset) = Self::gamepad_offset(which) {
+                    offset
                 } else {
                     return;
                 };
-                self.key_values
-                    .insert(GAMEPAD1_AXIS_LEFTX + axis as Key + offset, value);
+                let key = GAMEPAD1_AXIS_LEFTX + axis as Key + offset;
+                let sources = self.gamepad_axis_sources.entry(key).or_default();
+                sources.insert(which, value);
+                let value = sources
+                    .values()
+                    .copied()
+                    .max_by_key(|value| value.abs())
+                    .unwrap_or(0);
+                self.key_values.insert(key, value);
             }
             Event::ControllerButtonDown { which, button } => {
-                let offset = if which == 0 {
-                    0
-                } else if which == 1 {
-                    GAMEPAD2_BUTTON_A - GAMEPAD1_BUTTON_A
+                let offset = if let Some(offset) = Self::gamepad_offset(which) {
+                    offset
                 } else {
                     return;
                 };
-                self.press_key(GAMEPAD1_BUTTON_A + button as Key + offset, frame_count);
+                let key = GAMEPAD1_BUTTON_A + button as Key + offset;
+                let sources = self.gamepad_button_sources.entry(key).or_default();
+                if sources.insert(which) && sources.len() == 1 {
+                    self.press_key(key, frame_count);
+                }
             }
             Event::ControllerButtonUp { which, button } => {
-                let offset = if which == 0 {
-                    0
-                } else if which == 1 {
-                    GAMEPAD2_BUTTON_A - GAMEPAD1_BUTTON_A
+                let offset = if let Some(offset) = Self::gamepad_offset(which) {
+                    offset
                 } else {
                     return;
                 };
-                self.release_key(GAMEPAD1_BUTTON_A + button as Key + offset, frame_count);
+                let key = GAMEPAD1_BUTTON_A + button as Key + offset;
+                let sources = self.gamepad_button_sources.entry(key).or_default();
+                if sources.remove(&which) && sources.is_empty() {
+                    self.release_key(key, frame_count);
+                }
             }
+
+            // Joystick events
+            Event::JoystickAxisMotion { .. } => {}
+            Event::JoystickButtonDown { .. } => {}
+            Event::JoystickButtonUp { .. } => {}
+            Event::JoystickHatMotion { .. } => {}
+        }
+    }
+
+    pub fn set_virtual_gamepad(&mut self, mapping: &[(Key, ControllerButton)], frame_count: u32) {
+        let held_buttons: Vec<ControllerButton> = self
+            .virtual_gamepad
+            .iter()
+            .filter(|(keycode, _)| self.btn(**keycode, frame_count))
+            .map(|(_, button)| *button)
+            .collect();
+        for button in held_buttons {
+            self.process_virtual_gamepad_event(button, false, frame_count);
         }
+        self.virtual_gamepad = mapping.iter().copied().collect();
+    }
+
+    pub fn set_axis_deadzone(&mut self, axis: ControllerAxis, inner: f32, outer: f32) {
+        match Deadzone::new(inner, outer) {
+            Some(deadzone) => self.axis_deadzones[axis as usize] = deadzone,
+            None => warn!("Ignoring invalid deadzone {}..{}", inner, outer),
+        }
+    }
+
+    pub fn set_radial_deadzone(&mut self, is_radial: bool) {
+        self.is_radial_deadzone = is_radial;
+    }
+
+    pub fn axis(&self, slot: u32, axis: ControllerAxis) -> f32 {
+        let offset = if let Some(offset) = Self::gamepad_offset(slot) {
+            offset
+        } else {
+            return 0.0;
+        };
+        let value = |axis: ControllerAxis| self.btnv(GAMEPAD1_AXIS_LEFTX + axis as Key + offset);
+        let stick = match axis {
+            ControllerAxis::LeftX | ControllerAxis::LeftY => {
+                Some((ControllerAxis::LeftX, ControllerAxis::LeftY))
+            }
+            ControllerAxis::RightX | ControllerAxis::RightY => {
+                Some((ControllerAxis::RightX, ControllerAxis::RightY))
+            }
+            ControllerAxis::TriggerLeft | ControllerAxis::TriggerRight => None,
+        };
+        match stick {
+            Some((axis_x, axis_y)) if self.is_radial_deadzone => {
+                // Both axes of a stick share the deadzone set for its X axis
+                let (x, y) =
+                    self.axis_deadzones[axis_x as usize].radial(value(axis_x), value(axis_y));
+                if axis as usize == axis_x as usize {
+                    x
+                } else {
+                    y
+                }
+            }
+            _ => self.axis_deadzones[axis as usize].axial(value(axis)),
+        }
+    }
+
+    fn gamepad_offset(which: u32) -> Option<Key> {
+        match which {
+            0 | VIRTUAL_GAMEPAD => Some(0),
+            1 => Some(GAMEPAD2_BUTTON_A - GAMEPAD1_BUTTON_A),
+            2 => Some(GAMEPAD3_BUTTON_A - GAMEPAD1_BUTTON_A),
+            3 => Some(GAMEPAD4_BUTTON_A - GAMEPAD1_BUTTON_A),
+            _ => None,
+        }
+    }
+
+    fn process_virtual_gamepad_event(
+        &mut self,
+        button: ControllerButton,
+        is_pressed: bool,
+        frame_count: u32,
+    ) {
+        let event = if is_pressed {
+            Event::ControllerButtonDown {
+                which: VIRTUAL_GAMEPAD,
+                button,
+            }
+        } else {
+            Event::ControllerButtonUp {
+                which: VIRTUAL_GAMEPAD,
+                button,
+            }
+        };
+        self.process_input_event(event, frame_count);
+        let (axis, negative_key, positive_key) = match button {
+            ControllerButton::DPadLeft | ControllerButton::DPadRight => (
+                ControllerAxis::LeftX,
+                GAMEPAD1_BUTTON_DPAD_LEFT,
+                GAMEPAD1_BUTTON_DPAD_RIGHT,
+            ),
+            ControllerButton::DPadUp | ControllerButton::DPadDown => (
+                ControllerAxis::LeftY,
+                GAMEPAD1_BUTTON_DPAD_UP,
+                GAMEPAD1_BUTTON_DPAD_DOWN,
+            ),
+            _ => return,
+        };
+        let is_virtual_held = |key: Key| {
+            self.gamepad_button_sources
+                .get(&key)
+                .is_some_and(|sources| sources.contains(&VIRTUAL_GAMEPAD))
+        };
+        let value = (is_virtual_held(positive_key) as i32 - is_virtual_held(negative_key) as i32)
+            * VIRTUAL_GAMEPAD_AXIS_VALUE;
+        self.process_input_event(
+            Event::ControllerAxisMotion {
+                which: VIRTUAL_GAMEPAD,
+                axis,
+                value,
+            },
+            frame_count,
+        );
+    }
+
+    pub fn btn(&self, key: Key, current_frame_count: u32) -> bool {
+        if let Some((frame_count, key_state)) = self.key_states.get(&key) {
+            if *key_state == KeyState::Pressed
+                || *key_state == KeyState::ReleasedAndPressed
+                || *frame_count == current_frame_count && *key_state == KeyState::PressedAndReleased
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn btnp(
+        &self,
+        key: Key,
+        hold_frame_count: Option<u32>,
+        period_frame_count: Option<u32>,
+        current_frame_count: u32,
+    ) -> bool {
+        if let Some((frame_count, key_state)) = self.key_states.get(&key) {
+            if *key_state == KeyState::Released {
+                return false;
+            }
+            if *frame_count == current_frame_count {
+                return true;
+            }
+            if *key_state == KeyState::PressedAndReleased {
+                return false;
+            }
+            let hold_frame_count = hold_frame_count.unwrap_or(0);
+            let period_frame_count = period_frame_count.unwrap_or(0);
+            if period_frame_count == 0 {
+                return false;
+            }
+            let elapsed_frames =
+                current_frame_count as i32 - (*frame_count + hold_frame_count) as i32;
+            if elapsed_frames >= 0 && elapsed_frames % period_frame_count as i32 == 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn btnr(&self, key: Key, current_frame_count: u32) -> bool {
+        if let Some((frame_count, key_state)) = self.key_states.get(&key) {
+            if *key_state == KeyState::Pressed {
+                return false;
+            }
+            if *frame_count == current_frame_count {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn btnv(&self, key: Key) -> KeyValue {
+        self.key_values.get(&key).copied().unwrap_or(0)
     }
 
     fn press_key(&mut self, key: Key, frame_count: u32) {
@@ -161,6 +479,66 @@ impl Pyxel {
         *self.input.key_values.get(&MOUSE_POS_Y).unwrap_or(&0)
     }
 
+    pub fn mouse_on_screen(&self) -> bool {
+        self.input.mouse_on_screen()
+    }
+
+    pub fn mouse_window_pos(&self) -> (i32, i32) {
+        self.input.mouse_window_pos()
+    }
+
+    pub fn axis(&self, slot: u32, axis: ControllerAxis) -> f32 {
+        self.input.axis(slot, axis)
+    }
+
+    pub fn set_axis_deadzone(&mut self, axis: ControllerAxis, inner: f32, outer: f32) {
+        self.input.set_axis_deadzone(axis, inner, outer);
+    }
+
+    pub fn set_radial_deadzone(&mut self, is_radial: bool) {
+        self.input.set_radial_deadzone(is_radial);
+    }
+
+    pub fn action_map(&self) -> &ActionMap {
+        &self.input.action_map
+    }
+
+    pub fn action_map_mut(&mut self) -> &mut ActionMap {
+        &mut self.input.action_map
+    }
+
+    pub fn set_action_map(&mut self, action_map: ActionMap) {
+        self.input.action_map = action_map;
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        self.input.action_map.bind(action, binding);
+    }
+
+    pub fn action_down(&self, action: &str) -> bool {
+        self.input
+            .action_map
+            .is_down(action, &self.input, self.frame_count())
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.input
+            .action_map
+            .is_pressed(action, &self.input, self.frame_count())
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.input
+            .action_map
+            .is_released(action, &self.input, self.frame_count())
+    }
+
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.input
+            .action_map
+            .value(action, &self.input, self.frame_count())
+    }
+
     pub fn mouse_wheel(&self) -> i32 {
         *self.input.key_values.get(&MOUSE_WHEEL_Y).unwrap_or(&0)
     }
@@ -173,20 +551,20 @@ impl Pyxel {
         &self.input.input_text
     }
 
+    pub fn editing_text(&self) -> &str {
+        &self.input.editing_text
+    }
+
+    pub fn editing_range(&self) -> (i32, i32) {
+        (self.input.editing_start, self.input.editing_length)
+    }
+
     pub fn drop_files(&self) -> &Vec<String> {
         &self.input.drop_files
     }
 
     pub fn btn(&self, key: Key) -> bool {
-        if let Some((frame_count, key_state)) = self.input.key_states.get(&key) {
-            if *key_state == KeyState::Pressed
-                || *key_state == KeyState::ReleasedAndPressed
-                || *frame_count == self.frame_count() && *key_state == KeyState::PressedAndReleased
-            {
-                return true;
-            }
-        }
-        false
+        self.input.btn(key, self.frame_count())
     }
 
     pub fn btnp(
@@ -195,44 +573,20 @@ impl Pyxel {
         hold_frame_count: Option<u32>,
         period_frame_count: Option<u32>,
     ) -> bool {
-        if let Some((frame_count, key_state)) = self.input.key_states.get(&key) {
-            if *key_state == KeyState::Released {
-                return false;
-            }
-            if *frame_count == self.frame_count() {
-                return true;
-            }
-            if *key_state == KeyState::PressedAndReleased {
-                return false;
-            }
-            let hold_frame_count = hold_frame_count.unwrap_or(0);
-            let period_frame_count = period_frame_count.unwrap_or(0);
-            if period_frame_count == 0 {
-                return false;
-            }
-            let elapsed_frames =
-                self.frame_count() as i32 - (*frame_count + hold_frame_count) as i32;
-            if elapsed_frames >= 0 && elapsed_frames % period_frame_count as i32 == 0 {
-                return true;
-            }
-        }
-        false
+        self.input.btnp(
+            key,
+            hold_frame_count,
+            period_frame_count,
+            self.frame_count(),
+        )
     }
 
     pub fn btnr(&self, key: Key) -> bool {
-        if let Some((frame_count, key_state)) = self.input.key_states.get(&key) {
-            if *key_state == KeyState::Pressed {
-                return false;
-            }
-            if *frame_count == self.frame_count() {
-                return true;
-            }
-        }
-        false
+        self.input.btnr(key, self.frame_count())
     }
 
     pub fn btnv(&self, key: Key) -> KeyValue {
-        self.input.key_values.get(&key).copied().unwrap_or(0)
+        self.input.btnv(key)
     }
 
     pub fn mouse(&mut self, is_visible: bool) {
@@ -247,16 +601,326 @@ impl Pyxel {
         }
     }
 
+    pub fn set_virtual_gamepad(&mut self, mapping: &[(Key, ControllerButton)]) {
+        let frame_count = self.frame_count();
+        self.input.set_virtual_gamepad(mapping, frame_count);
+    }
+
     pub fn set_btnv(&mut self, key: Key, key_value: f64) {
         let key_value = as_i32(key_value);
         self.input.key_values.insert(key, key_value);
     }
 
+    pub fn start_text_input(&mut self) {
+        self.platform.start_text_input();
+    }
+
+    pub fn stop_text_input(&mut self) {
+        self.input.editing_text.clear();
+        self.input.editing_start = 0;
+        self.input.editing_length = 0;
+        self.platform.stop_text_input();
+    }
+
+    pub fn set_text_input_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.platform
+            .set_text_input_rect(as_i32(x), as_i32(y), as_u32(width), as_u32(height));
+    }
+
     pub fn set_mouse_pos(&mut self, x: f64, y: f64) {
         let x = as_i32(x);
         let y = as_i32(y);
         self.input.key_values.insert(MOUSE_POS_X, x);
         self.input.key_values.insert(MOUSE_POS_Y, y);
-        self.platform.move_cursor(x, y);
+        self.platform.warp_mouse(x, y);
+    }
+
+    pub fn last_press_latency_us(&self) -> Option<u64> {
+        self.input.last_press_latency_us()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::MouseButton;
+
+    #[test]
+    fn btn() {
+        let mut input = Input::new();
+        assert!(!input.btn(KEY_A, 1));
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 1);
+        assert!(input.btn(KEY_A, 1));
+        assert!(input.btn(KEY_A, 2));
+        input.process_input_event(Event::KeyUp { keycode: KEY_A }, 3);
+        assert!(!input.btn(KEY_A, 3));
+    }
+
+    #[test]
+    fn btn_within_frame() {
+        let mut input = Input::new();
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 1);
+        input.process_input_event(Event::KeyUp { keycode: KEY_A }, 1);
+        assert!(input.btn(KEY_A, 1));
+        assert!(input.btnp(KEY_A, None, None, 1));
+        assert!(input.btnr(KEY_A, 1));
+        assert!(!input.btn(KEY_A, 2));
+        assert!(!input.btnp(KEY_A, None, None, 2));
+        assert!(!input.btnr(KEY_A, 2));
+
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 3);
+        input.process_input_event(Event::KeyUp { keycode: KEY_A }, 4);
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 4);
+        assert!(input.btn(KEY_A, 4));
+        assert!(input.btnp(KEY_A, None, None, 4));
+        assert!(input.btnr(KEY_A, 4));
+        assert!(input.btn(KEY_A, 5));
+    }
+
+    #[test]
+    fn btnp() {
+        let mut input = Input::new();
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 10);
+        assert!(input.btnp(KEY_A, None, None, 10));
+        assert!(!input.btnp(KEY_A, None, None, 11));
+        assert!(!input.btnp(KEY_A, Some(5), Some(2), 14));
+        assert!(input.btnp(KEY_A, Some(5), Some(2), 15));
+        assert!(!input.btnp(KEY_A, Some(5), Some(2), 16));
+        assert!(input.btnp(KEY_A, Some(5), Some(2), 17));
+        input.process_input_event(Event::KeyUp { keycode: KEY_A }, 18);
+        assert!(!input.btnp(KEY_A, Some(5), Some(2), 19));
+    }
+
+    #[test]
+    fn press_latency() {
+        let mut input = Input::new();
+        assert_eq!(input.last_press_latency_us(), None);
+        let timed = |timestamp_us, event| TimedEvent {
+            timestamp_us,
+            event,
+        };
+        input.process_timed_event(timed(1000, Event::KeyDown { keycode: KEY_A }), 1);
+        input.process_timed_event(timed(1500, Event::KeyUp { keycode: KEY_A }), 1);
+        input.process_timed_event(timed(2500, Event::KeyDown { keycode: KEY_B }), 1);
+        assert!(input.btn(KEY_B, 1));
+        assert_eq!(
+            input.take_press_latencies(4000).collect::<Vec<_>>(),
+            [3000, 1500]
+        );
+        assert_eq!(input.last_press_latency_us(), Some(1500));
+        assert_eq!(input.take_press_latencies(5000).count(), 0);
+        assert_eq!(input.last_press_latency_us(), Some(1500));
+    }
+
+    #[test]
+    fn mouse_wheel() {
+        let mut input = Input::new();
+        let wheel = |precise_y| Event::MouseWheel {
+            x: 0,
+            y: 0,
+            precise_x: 0.0,
+            precise_y,
+        };
+        input.process_input_event(wheel(0.4), 1);
+        input.process_input_event(wheel(0.4), 1);
+        assert_eq!(input.btnv(MOUSE_WHEEL_Y), 0);
+        input.process_input_event(wheel(0.4), 1);
+        assert_eq!(input.btnv(MOUSE_WHEEL_Y), 1);
+        input.process_input_event(wheel(-1.5), 1);
+        assert_eq!(input.btnv(MOUSE_WHEEL_Y), 0);
+    }
+
+    #[test]
+    fn btnr() {
+        let mut input = Input::new();
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 1);
+        assert!(!input.btnr(KEY_A, 1));
+        input.process_input_event(Event::KeyUp { keycode: KEY_A }, 2);
+        assert!(input.btnr(KEY_A, 2));
+        assert!(!input.btnr(KEY_A, 3));
+    }
+
+    #[test]
+    fn btnv() {
+        let mut input = Input::new();
+        input.process_input_event(
+            Event::ControllerAxisMotion {
+                which: 1,
+                axis: ControllerAxis::LeftY,
+                value: -100,
+            },
+            1,
+        );
+        assert_eq!(input.btnv(GAMEPAD2_AXIS_LEFTY), -100);
+        assert_eq!(input.btnv(GAMEPAD1_AXIS_LEFTY), 0);
+    }
+
+    #[test]
+    fn virtual_gamepad() {
+        let mut input = Input::new();
+        input.set_virtual_gamepad(
+            &[
+                (KEY_LEFT, ControllerButton::DPadLeft),
+                (KEY_RIGHT, ControllerButton::DPadRight),
+                (KEY_Z, ControllerButton::A),
+            ],
+            1,
+        );
+        input.process_input_event(Event::KeyDown { keycode: KEY_Z }, 1);
+        input.process_input_event(Event::KeyDown { keycode: KEY_LEFT }, 1);
+        assert!(input.btn(GAMEPAD1_BUTTON_A, 1));
+        assert!(input.btn(GAMEPAD1_BUTTON_DPAD_LEFT, 1));
+        assert_eq!(input.btnv(GAMEPAD1_AXIS_LEFTX), -VIRTUAL_GAMEPAD_AXIS_VALUE);
+        input.process_input_event(Event::KeyDown { keycode: KEY_RIGHT }, 2);
+        assert_eq!(input.btnv(GAMEPAD1_AXIS_LEFTX), 0);
+        input.process_input_event(Event::KeyUp { keycode: KEY_LEFT }, 3);
+        assert_eq!(input.btnv(GAMEPAD1_AXIS_LEFTX), VIRTUAL_GAMEPAD_AXIS_VALUE);
+        input.set_virtual_gamepad(&[], 4);
+        assert!(!input.btn(GAMEPAD1_BUTTON_A, 4));
+        assert!(!input.btn(GAMEPAD1_BUTTON_DPAD_RIGHT, 4));
+        assert_eq!(input.btnv(GAMEPAD1_AXIS_LEFTX), 0);
+    }
+
+    #[test]
+    fn virtual_gamepad_with_controller() {
+        let mut input = Input::new();
+        input.set_virtual_gamepad(&[(KEY_Z, ControllerButton::A)], 1);
+        input.process_input_event(Event::KeyDown { keycode: KEY_Z }, 1);
+        input.process_input_event(
+            Event::ControllerButtonDown {
+                which: 0,
+                button: ControllerButton::A,
+            },
+            2,
+        );
+        input.process_input_event(
+            Event::ControllerButtonUp {
+                which: 0,
+                button: ControllerButton::A,
+            },
+            3,
+        );
+        assert!(input.btn(GAMEPAD1_BUTTON_A, 3));
+        assert!(!input.btnp(GAMEPAD1_BUTTON_A, None, None, 2));
+        input.process_input_event(Event::KeyUp { keycode: KEY_Z }, 4);
+        assert!(!input.btn(GAMEPAD1_BUTTON_A, 4));
+    }
+
+    #[test]
+    fn keyspace() {
+        let mut input = Input::new();
+        input.process_input_event(
+            Event::MouseButtonDown {
+                button: MouseButton::Right,
+                x: 3,
+                y: 4,
+                is_on_screen: true,
+            },
+            1,
+        );
+        input.process_input_event(
+            Event::ControllerButtonDown {
+                which: 1,
+                button: ControllerButton::A,
+            },
+            1,
+        );
+        input.process_input_event(
+            Event::ControllerButtonDown {
+                which: 3,
+                button: ControllerButton::Start,
+            },
+            1,
+        );
+        assert!(input.btn(MOUSE_BUTTON_RIGHT, 1));
+        assert!(input.btn(GAMEPAD2_BUTTON_A, 1));
+        assert!(!input.btn(GAMEPAD1_BUTTON_A, 1));
+        assert!(input.btn(GAMEPAD4_BUTTON_START, 1));
+        assert!(!input.btn(GAMEPAD3_BUTTON_START, 1));
+        assert_eq!(input.btnv(MOUSE_POS_X), 3);
+        assert_eq!(input.btnv(MOUSE_POS_Y), 4);
+    }
+
+    #[test]
+    fn skip_frames() {
+        let mut input = Input::new();
+        input.process_input_event(Event::KeyDown { keycode: KEY_A }, 10);
+        input.process_input_event(Event::KeyDown { keycode: KEY_B }, 20);
+        input.process_input_event(Event::KeyDown { keycode: KEY_C }, 610);
+        input.skip_frames(600, 620);
+        assert!(!input.btnp(KEY_A, Some(15), Some(10), 620));
+        assert!(input.btnp(KEY_A, Some(15), Some(10), 625));
+        assert!(input.btn(KEY_B, 620));
+        assert!(input.btnp(KEY_B, None, None, 620));
+        assert!(input.btnp(KEY_C, None, None, 620));
+    }
+
+    #[test]
+    fn mouse_on_screen() {
+        let mut input = Input::new();
+        input.process_input_event(
+            Event::MouseMotion {
+                x: 0,
+                y: 50,
+                window_x: -12,
+                window_y: 200,
+                is_on_screen: false,
+            },
+            1,
+        );
+        assert!(!input.mouse_on_screen());
+        assert_eq!(input.mouse_window_pos(), (-12, 200));
+        assert_eq!(input.btnv(MOUSE_POS_X), 0);
+
+        input.process_input_event(
+            Event::MouseButtonDown {
+                button: MouseButton::Left,
+                x: 0,
+                y: 50,
+                is_on_screen: false,
+            },
+            1,
+        );
+        assert!(input.btn(MOUSE_BUTTON_LEFT, 1));
+        assert!(!input.mouse_on_screen());
+
+        input.process_input_event(
+            Event::MouseMotion {
+                x: 8,
+                y: 50,
+                window_x: 32,
+                window_y: 200,
+                is_on_screen: true,
+            },
+            2,
+        );
+        assert!(input.mouse_on_screen());
+        assert_eq!(input.mouse_window_pos(), (32, 200));
+    }
+
+    #[test]
+    fn axis() {
+        let mut input = Input::new();
+        let move_axis = |input: &mut Input, which, axis, value| {
+            input.process_input_event(Event::ControllerAxisMotion { which, axis, value }, 1)
+        };
+        move_axis(&mut input, 1, ControllerAxis::LeftX, 3000);
+        move_axis(&mut input, 1, ControllerAxis::LeftY, -32768);
+        move_axis(&mut input, 0, ControllerAxis::TriggerRight, 32767);
+        assert_eq!(input.axis(1, ControllerAxis::LeftX), 0.0);
+        assert_eq!(input.axis(1, ControllerAxis::LeftY), -1.0);
+        assert_eq!(input.axis(0, ControllerAxis::LeftY), 0.0);
+        assert_eq!(input.axis(0, ControllerAxis::TriggerRight), 1.0);
+        assert_eq!(input.axis(2, ControllerAxis::TriggerRight), 0.0);
+
+        input.set_radial_deadzone(true);
+        assert!(input.axis(1, ControllerAxis::LeftX) > 0.0);
+        assert_eq!(input.axis(0, ControllerAxis::TriggerRight), 1.0);
+
+        input.set_axis_deadzone(ControllerAxis::TriggerRight, 0.0, 0.5);
+        move_axis(&mut input, 0, ControllerAxis::TriggerRight, 8192);
+        assert!((input.axis(0, ControllerAxis::TriggerRight) - 0.5).abs() < 0.001);
+        input.set_axis_deadzone(ControllerAxis::TriggerRight, 0.6, 0.5);
+        assert!((input.axis(0, ControllerAxis::TriggerRight) - 0.5).abs() < 0.001);
     }
 }
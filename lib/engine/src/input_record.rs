@@ -0,0 +1,562 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::str::{FromStr, SplitWhitespace};
+
+use crate::event::{ControllerAxis, ControllerButton, Event, HatState, MouseButton};
+use crate::settings::PYXEL_VERSION;
+
+const RECORD_SIGNATURE: &str = "pyxel-input-record";
+
+const MOUSE_BUTTONS: [MouseButton; 6] = [
+    MouseButton::Left,
+    MouseButton::Middle,
+    MouseButton::Right,
+    MouseButton::X1,
+    MouseButton::X2,
+    MouseButton::Unknown,
+];
+
+pub const CONTROLLER_AXES: [ControllerAxis; 6] = [
+    ControllerAxis::LeftX,
+    ControllerAxis::LeftY,
+    ControllerAxis::RightX,
+    ControllerAxis::RightY,
+    ControllerAxis::TriggerLeft,
+    ControllerAxis::TriggerRight,
+];
+
+pub const CONTROLLER_BUTTONS: [ControllerButton; 21] = [
+    ControllerButton::A,
+    ControllerButton::B,
+    ControllerButton::X,
+    ControllerButton::Y,
+    ControllerButton::Back,
+    ControllerButton::Guide,
+    ControllerButton::Start,
+    ControllerButton::LeftStick,
+    ControllerButton::RightStick,
+    ControllerButton::LeftShoulder,
+    ControllerButton::RightShoulder,
+    ControllerButton::DPadUp,
+    ControllerButton::DPadDown,
+    ControllerButton::DPadLeft,
+    ControllerButton::DPadRight,
+    ControllerButton::Misc1,
+    ControllerButton::Paddle1,
+    ControllerButton::Paddle2,
+    ControllerButton::Paddle3,
+    ControllerButton::Paddle4,
+    ControllerButton::Touchpad,
+];
+
+const HAT_STATES: [HatState; 9] = [
+    HatState::Centered,
+    HatState::Up,
+    HatState::Right,
+    HatState::Down,
+    HatState::Left,
+    HatState::RightUp,
+    HatState::RightDown,
+    HatState::LeftUp,
+    HatState::LeftDown,
+];
+
+#[derive(Debug)]
+pub enum InputRecordError {
+    OpenFile(String),
+    WriteFile(String),
+    InvalidHeader(String),
+    VersionMismatch(String),
+    FpsMismatch(u32),
+    InvalidEvent(usize, String),
+}
+
+impl fmt::Display for InputRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OpenFile(filename) => write!(f, "Unable to open file '{}'", filename),
+            Self::WriteFile(filename) => write!(f, "Unable to write file '{}'", filename),
+            Self::InvalidHeader(filename) => {
+                write!(f, "Invalid input record header in '{}'", filename)
+            }
+            Self::VersionMismatch(version) => write!(
+                f,
+                "Input record version '{}' does not match '{}'",
+                version, PYXEL_VERSION
+            ),
+            Self::FpsMismatch(fps) => write!(
+                f,
+                "Input record made at {} fps does not match the current fps",
+                fps
+            ),
+            Self::InvalidEvent(line, text) => {
+                write!(f, "Invalid input event '{}' on line {}", text, line)
+            }
+        }
+    }
+}
+
+fn encode_text(text: &str) -> String {
+    // Text is stored as hex so that spaces and newlines survive the line format
+    let mut encoded = "x".to_string();
+    for byte in text.bytes() {
+        encoded += &format!("{:02x}", byte);
+    }
+    encoded
+}
+
+fn decode_text(encoded: &str) -> Option<String> {
+    let hex = encoded.strip_prefix('x')?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+pub fn format_event(event: &Event) -> Option<String> {
+    let text = match event {
+        Event::Quit => "Quit".to_string(),
+        Event::DropFile { filename, x, y } => {
+            format!("DropFile {} {} {}", encode_text(filename), x, y)
+        }
+        Event::DropText { text, x, y } => format!("DropText {} {} {}", encode_text(text), x, y),
+        Event::Shown | Event::Hidden | Event::AuxWindow { .. } | Event::AudioDeviceRemoved => {
+            return None
+        }
+        Event::KeyDown { keycode } => format!("KeyDown {}", keycode),
+        Event::KeyUp { keycode } => format!("KeyUp {}", keycode),
+        Event::TextInput { text } => format!("TextInput {}", encode_text(text)),
+        Event::TextEditing {
+            text,
+            start,
+            length,
+        } => format!("TextEditing {} {} {}", encode_text(text), start, length),
+        Event::MouseMotion {
+            x,
+            y,
+            window_x,
+            window_y,
+            is_on_screen,
+        } => format!(
+            "MouseMotion {} {} {} {} {}",
+            x, y, window_x, window_y, is_on_screen
+        ),
+        Event::MouseButtonDown {
+            button,
+            x,
+            y,
+            is_on_screen,
+        } => format!(
+            "MouseButtonDown {} {} {} {}",
+            *button as u32, x, y, is_on_screen
+        ),
+        Event::MouseButtonUp {
+            button,
+            x,
+            y,
+            is_on_screen,
+        } => format!(
+            "MouseButtonUp {} {} {} {}",
+            *button as u32, x, y, is_on_screen
+        ),
+        Event::MouseWheel {
+            x,
+            y,
+            precise_x,
+            precise_y,
+        } => format!("MouseWheel {} {} {} {}", x, y, precise_x, precise_y),
+        Event::ControllerAxisMotion { which, axis, value } => {
+            format!("ControllerAxisMotion {} {} {}", which, *axis as u32, value)
+        }
+        Event::ControllerButtonDown { which, button } => {
+            format!("ControllerButtonDown {} {}", which, *button as u32)
+        }
+        Event::ControllerButtonUp { which, button } => {
+            format!("ControllerButtonUp {} {}", which, *button as u32)
+        }
+        Event::JoystickAxisMotion { which, axis, value } => {
+            format!("JoystickAxisMotion {} {} {}", which, axis, value)
+        }
+        Event::JoystickButtonDown { which, button } => {
+            format!("JoystickButtonDown {} {}", which, button)
+        }
+        Event::JoystickButtonUp { which, button } => {
+            format!("JoystickButtonUp {} {}", which, button)
+        }
+        Event::JoystickHatMotion { which, hat, state } => {
+            format!("JoystickHatMotion {} {} {}", which, hat, *state as u32)
+        }
+    };
+    Some(text)
+}
+
+fn field<T: FromStr>(tokens: &mut SplitWhitespace) -> Option<T> {
+    tokens.next()?.parse().ok()
+}
+
+fn variant<T: Copy>(tokens: &mut SplitWhitespace, variants: &[T]) -> Option<T> {
+    variants.get(field::<usize>(tokens)?).copied()
+}
+
+pub fn parse_event(text: &str) -> Option<Event> {
+    let mut tokens = text.split_whitespace();
+    let tokens = &mut tokens;
+    let event = match tokens.next()? {
+        "Quit" => Event::Quit,
+        "DropFile" => Event::DropFile {
+            filename: decode_text(tokens.next()?)?,
+            x: field(tokens)?,
+            y: field(tokens)?,
+        },
+        "DropText" => Event::DropText {
+            text: decode_text(tokens.next()?)?,
+            x: field(tokens)?,
+            y: field(tokens)?,
+        },
+        "KeyDown" => Event::KeyDown {
+            keycode: field(tokens)?,
+        },
+        "KeyUp" => Event::KeyUp {
+            keycode: field(tokens)?,
+        },
+        "TextInput" => Event::TextInput {
+            text: decode_text(tokens.next()?)?,
+        },
+        "TextEditing" => Event::TextEditing {
+            text: decode_text(tokens.next()?)?,
+            start: field(tokens)?,
+            length: field(tokens)?,
+        },
+        "MouseMotion" => Event::MouseMotion {
+            x: field(tokens)?,
+            y: field(tokens)?,
+            window_x: field(tokens)?,
+            window_y: field(tokens)?,
+            is_on_screen: field(tokens)?,
+        },
+        "MouseButtonDown" => Event::MouseButtonDown {
+            button: variant(tokens, &MOUSE_BUTTONS)?,
+            x: field(tokens)?,
+            y: field(tokens)?,
+            is_on_screen: field(tokens)?,
+        },
+        "MouseButtonUp" => Event::MouseButtonUp {
+            button: variant(tokens, &MOUSE_BUTTONS)?,
+            x: field(tokens)?,
+            y: field(tokens)?,
+            is_on_screen: field(tokens)?,
+        },
+        "MouseWheel" => Event::MouseWheel {
+            x: field(tokens)?,
+            y: field(tokens)?,
+            precise_x: field(tokens)?,
+            precise_y: field(tokens)?,
+        },
+        "ControllerAxisMotion" => Event::ControllerAxisMotion {
+            which: field(tokens)?,
+            axis: variant(tokens, &CONTROLLER_AXES)?,
+            value: field(tokens)?,
+        },
+        "ControllerButtonDown" => Event::ControllerButtonDown {
+            which: field(tokens)?,
+            button: variant(tokens, &CONTROLLER_BUTTONS)?,
+        },
+        "ControllerButtonUp" => Event::ControllerButtonUp {
+            which: field(tokens)?,
+            button: variant(tokens, &CONTROLLER_BUTTONS)?,
+        },
+        "JoystickAxisMotion" => Event::JoystickAxisMotion {
+            which: field(tokens)?,
+            axis: field(tokens)?,
+            value: field(tokens)?,
+        },
+        "JoystickButtonDown" => Event::JoystickButtonDown {
+            which: field(tokens)?,
+            button: field(tokens)?,
+        },
+        "JoystickButtonUp" => Event::JoystickButtonUp {
+            which: field(tokens)?,
+            button: field(tokens)?,
+        },
+        "JoystickHatMotion" => Event::JoystickHatMotion {
+            which: field(tokens)?,
+            hat: field(tokens)?,
+            state: variant(tokens, &HAT_STATES)?,
+        },
+        _ => return None,
+    };
+    if tokens.next().is_some() {
+        return None;
+    }
+    Some(event)
+}
+
+pub struct InputRecorder {
+    filename: String,
+    writer: BufWriter<File>,
+    start_frame: u32,
+}
+
+impl InputRecorder {
+    pub fn create(
+        filename: &str,
+        fps: u32,
+        seed: u32,
+        start_frame: u32,
+    ) -> Result<Self, InputRecordError> {
+        let file =
+            File::create(filename).map_err(|_| InputRecordError::OpenFile(filename.to_string()))?;
+        let mut recorder = Self {
+            filename: filename.to_string(),
+            writer: BufWriter::new(file),
+            start_frame,
+        };
+        recorder.write_line(&format!(
+            "{}\nversion {}\nfps {}\nseed {}",
+            RECORD_SIGNATURE, PYXEL_VERSION, fps, seed
+        ))?;
+        Ok(recorder)
+    }
+
+    pub fn record(&mut self, frame_count: u32, event: &Event) -> Result<(), InputRecordError> {
+        if let Some(text) = format_event(event) {
+            self.write_line(&format!("{} {}", frame_count - self.start_frame, text))?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), InputRecordError> {
+        self.writer
+            .flush()
+            .map_err(|_| InputRecordError::WriteFile(self.filename.clone()))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), InputRecordError> {
+        writeln!(self.writer, "{}", line)
+            .map_err(|_| InputRecordError::WriteFile(self.filename.clone()))
+    }
+}
+
+pub struct InputReplay {
+    events: VecDeque<(u32, Event)>,
+    start_frame: u32,
+}
+
+impl InputReplay {
+    pub fn open(
+        filename: &str,
+        fps: u32,
+        start_frame: u32,
+    ) -> Result<(Self, u32), InputRecordError> {
+        let text = fs::read_to_string(filename)
+            .map_err(|_| InputRecordError::OpenFile(filename.to_string()))?;
+        Self::parse(&text, filename, fps, start_frame)
+    }
+
+    fn parse(
+        text: &str,
+        filename: &str,
+        fps: u32,
+        start_frame: u32,
+    ) -> Result<(Self, u32), InputRecordError> {
+        let invalid_header = || InputRecordError::InvalidHeader(filename.to_string());
+        let mut lines = text.lines();
+        if lines.next() != Some(RECORD_SIGNATURE) {
+            return Err(invalid_header());
+        }
+        let mut header = |name: &str| {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|value| value.strip_prefix(' '))
+                .map(ToString::to_string)
+                .ok_or_else(invalid_header)
+        };
+        let version = header("version")?;
+        if version != PYXEL_VERSION {
+            return Err(InputRecordError::VersionMismatch(version));
+        }
+        let record_fps: u32 = header("fps")?.parse().map_err(|_| invalid_header())?;
+        if record_fps != fps {
+            return Err(InputRecordError::FpsMismatch(record_fps));
+        }
+        let seed = header("seed")?.parse().map_err(|_| invalid_header())?;
+
+        let mut events = VecDeque::new();
+        for (i, line) in text.lines().enumerate().skip(4) {
+            let event = line
+                .split_once(' ')
+                .and_then(|(frame, event)| Some((frame.parse::<u32>().ok()?, parse_event(event)?)));
+            match event {
+                Some(event)
+                    if events
+                        .back()
+                        .is_none_or(|last: &(u32, Event)| last.0 <= event.0) =>
+                {
+                    events.push_back(event);
+                }
+                _ => return Err(InputRecordError::InvalidEvent(i + 1, line.to_string())),
+            }
+        }
+        Ok((
+            Self {
+                events,
+                start_frame,
+            },
+            seed,
+        ))
+    }
+
+    pub fn next_event(&mut self, frame_count: u32) -> Option<Event> {
+        if self.events.front()?.0 <= frame_count - self.start_frame {
+            self.events.pop_front().map(|(_, event)| event)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_event_() {
+        let events = [
+            Event::Quit,
+            Event::DropFile {
+                filename: "my game/level 1.tmx".to_string(),
+                x: 4,
+                y: -2,
+            },
+            Event::KeyDown { keycode: 97 },
+            Event::TextInput {
+                text: "é 漢".to_string(),
+            },
+            Event::TextInput {
+                text: String::new(),
+            },
+            Event::MouseButtonUp {
+                button: MouseButton::X2,
+                x: 10,
+                y: 20,
+                is_on_screen: false,
+            },
+            Event::MouseMotion {
+                x: 0,
+                y: 119,
+                window_x: -3,
+                window_y: 480,
+                is_on_screen: false,
+            },
+            Event::MouseWheel {
+                x: 0,
+                y: -1,
+                precise_x: 0.1,
+                precise_y: -1.25,
+            },
+            Event::ControllerAxisMotion {
+                which: 1,
+                axis: ControllerAxis::TriggerRight,
+                value: -32768,
+            },
+            Event::ControllerButtonDown {
+                which: 0,
+                button: ControllerButton::Touchpad,
+            },
+            Event::JoystickHatMotion {
+                which: 2,
+                hat: 0,
+                state: HatState::LeftDown,
+            },
+        ];
+        for event in &events {
+            let text = format_event(event).unwrap();
+            assert_eq!(format_event(&parse_event(&text).unwrap()).unwrap(), text);
+        }
+        assert_eq!(
+            format_event(&events[1]).unwrap(),
+            "DropFile x6d792067616d652f6c6576656c20312e746d78 4 -2"
+        );
+        assert!(format_event(&Event::Shown).is_none());
+        assert!(parse_event("KeyDown").is_none());
+        assert!(parse_event("KeyDown 97 98").is_none());
+        assert!(parse_event("MouseButtonDown 6 0 0 true").is_none());
+        assert!(parse_event("MouseButtonDown 0 0 0 1").is_none());
+        assert!(parse_event("TextInput x4").is_none());
+        assert!(parse_event("Unknown 1").is_none());
+    }
+
+    #[test]
+    fn parse() {
+        let header = format!(
+            "{}\nversion {}\nfps 30\nseed 42\n",
+            RECORD_SIGNATURE, PYXEL_VERSION
+        );
+        let text = header.clone() + "0 KeyDown 97\n2 MouseMotion 3 4 12 16 true\n2 KeyUp 97\n";
+        let (mut replay, seed) = InputReplay::parse(&text, "test", 30, 10).unwrap();
+        assert_eq!(seed, 42);
+        assert!(matches!(
+            replay.next_event(10),
+            Some(Event::KeyDown { keycode: 97 })
+        ));
+        assert!(replay.next_event(10).is_none());
+        assert!(replay.next_event(11).is_none());
+        assert!(matches!(
+            replay.next_event(12),
+            Some(Event::MouseMotion {
+                x: 3,
+                y: 4,
+                window_x: 12,
+                window_y: 16,
+                is_on_screen: true
+            })
+        ));
+        assert!(!replay.is_finished());
+        assert!(matches!(
+            replay.next_event(12),
+            Some(Event::KeyUp { keycode: 97 })
+        ));
+        assert!(replay.is_finished());
+
+        let error = |text: &str, fps: u32| {
+            InputReplay::parse(text, "test", fps, 0)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            error(&text, 60),
+            "Input record made at 30 fps does not match the current fps"
+        );
+        assert_eq!(
+            error("pyxel\n", 30),
+            "Invalid input record header in 'test'"
+        );
+        assert_eq!(
+            error(&text.replace(PYXEL_VERSION, "1.0.0"), 30),
+            format!(
+                "Input record version '1.0.0' does not match '{}'",
+                PYXEL_VERSION
+            )
+        );
+        assert_eq!(
+            error(&(header.clone() + "3 KeyDown 97\n1 KeyUp 97\n"), 30),
+            "Invalid input event '1 KeyUp 97' on line 6"
+        );
+        assert_eq!(
+            error(&(header + "KeyDown 97\n"), 30),
+            "Invalid input event 'KeyDown 97' on line 5"
+        );
+    }
+}
@@ -302,8 +302,404 @@ pub const GAMEPAD2_BUTTON_DPAD_DOWN: Key = 41012;
 pub const GAMEPAD2_BUTTON_DPAD_LEFT: Key = 41013;
 pub const GAMEPAD2_BUTTON_DPAD_RIGHT: Key = 41014;
 
+// Gamepad3
+pub const GAMEPAD3_AXIS_LEFTX: Key = 50000;
+pub const GAMEPAD3_AXIS_LEFTY: Key = 50001;
+pub const GAMEPAD3_AXIS_RIGHTX: Key = 50002;
+pub const GAMEPAD3_AXIS_RIGHTY: Key = 50003;
+pub const GAMEPAD3_AXIS_TRIGGERLEFT: Key = 50004;
+pub const GAMEPAD3_AXIS_TRIGGERRIGHT: Key = 50005;
+pub const GAMEPAD3_BUTTON_A: Key = 51000;
+pub const GAMEPAD3_BUTTON_B: Key = 51001;
+pub const GAMEPAD3_BUTTON_X: Key = 51002;
+pub const GAMEPAD3_BUTTON_Y: Key = 51003;
+pub const GAMEPAD3_BUTTON_BACK: Key = 51004;
+pub const GAMEPAD3_BUTTON_GUIDE: Key = 51005;
+pub const GAMEPAD3_BUTTON_START: Key = 51006;
+pub const GAMEPAD3_BUTTON_LEFTSTICK: Key = 51007;
+pub const GAMEPAD3_BUTTON_RIGHTSTICK: Key = 51008;
+pub const GAMEPAD3_BUTTON_LEFTSHOULDER: Key = 51009;
+pub const GAMEPAD3_BUTTON_RIGHTSHOULDER: Key = 51010;
+pub const GAMEPAD3_BUTTON_DPAD_UP: Key = 51011;
+pub const GAMEPAD3_BUTTON_DPAD_DOWN: Key = 51012;
+pub const GAMEPAD3_BUTTON_DPAD_LEFT: Key = 51013;
+pub const GAMEPAD3_BUTTON_DPAD_RIGHT: Key = 51014;
+
+// Gamepad4
+pub const GAMEPAD4_AXIS_LEFTX: Key = 60000;
+pub const GAMEPAD4_AXIS_LEFTY: Key = 60001;
+pub const GAMEPAD4_AXIS_RIGHTX: Key = 60002;
+pub const GAMEPAD4_AXIS_RIGHTY: Key = 60003;
+pub const GAMEPAD4_AXIS_TRIGGERLEFT: Key = 60004;
+pub const GAMEPAD4_AXIS_TRIGGERRIGHT: Key = 60005;
+pub const GAMEPAD4_BUTTON_A: Key = 61000;
+pub const GAMEPAD4_BUTTON_B: Key = 61001;
+pub const GAMEPAD4_BUTTON_X: Key = 61002;
+pub const GAMEPAD4_BUTTON_Y: Key = 61003;
+pub const GAMEPAD4_BUTTON_BACK: Key = 61004;
+pub const GAMEPAD4_BUTTON_GUIDE: Key = 61005;
+pub const GAMEPAD4_BUTTON_START: Key = 61006;
+pub const GAMEPAD4_BUTTON_LEFTSTICK: Key = 61007;
+pub const GAMEPAD4_BUTTON_RIGHTSTICK: Key = 61008;
+pub const GAMEPAD4_BUTTON_LEFTSHOULDER: Key = 61009;
+pub const GAMEPAD4_BUTTON_RIGHTSHOULDER: Key = 61010;
+pub const GAMEPAD4_BUTTON_DPAD_UP: Key = 61011;
+pub const GAMEPAD4_BUTTON_DPAD_DOWN: Key = 61012;
+pub const GAMEPAD4_BUTTON_DPAD_LEFT: Key = 61013;
+pub const GAMEPAD4_BUTTON_DPAD_RIGHT: Key = 61014;
+
+// Key names
+pub const KEY_NAMES: [(Key, &str); 334] = [
+    (KEY_BACKSPACE, "BACKSPACE"),
+    (KEY_TAB, "TAB"),
+    (KEY_RETURN, "RETURN"),
+    (KEY_ESCAPE, "ESCAPE"),
+    (KEY_SPACE, "SPACE"),
+    (KEY_EXCLAIM, "EXCLAIM"),
+    (KEY_QUOTEDBL, "QUOTEDBL"),
+    (KEY_HASH, "HASH"),
+    (KEY_DOLLAR, "DOLLAR"),
+    (KEY_PERCENT, "PERCENT"),
+    (KEY_AMPERSAND, "AMPERSAND"),
+    (KEY_QUOTE, "QUOTE"),
+    (KEY_LEFTPAREN, "LEFTPAREN"),
+    (KEY_RIGHTPAREN, "RIGHTPAREN"),
+    (KEY_ASTERISK, "ASTERISK"),
+    (KEY_PLUS, "PLUS"),
+    (KEY_COMMA, "COMMA"),
+    (KEY_MINUS, "MINUS"),
+    (KEY_PERIOD, "PERIOD"),
+    (KEY_SLASH, "SLASH"),
+    (KEY_0, "0"),
+    (KEY_1, "1"),
+    (KEY_2, "2"),
+    (KEY_3, "3"),
+    (KEY_4, "4"),
+    (KEY_5, "5"),
+    (KEY_6, "6"),
+    (KEY_7, "7"),
+    (KEY_8, "8"),
+    (KEY_9, "9"),
+    (KEY_COLON, "COLON"),
+    (KEY_SEMICOLON, "SEMICOLON"),
+    (KEY_LESS, "LESS"),
+    (KEY_EQUALS, "EQUALS"),
+    (KEY_GREATER, "GREATER"),
+    (KEY_QUESTION, "QUESTION"),
+    (KEY_AT, "AT"),
+    (KEY_LEFTBRACKET, "LEFTBRACKET"),
+    (KEY_BACKSLASH, "BACKSLASH"),
+    (KEY_RIGHTBRACKET, "RIGHTBRACKET"),
+    (KEY_CARET, "CARET"),
+    (KEY_UNDERSCORE, "UNDERSCORE"),
+    (KEY_BACKQUOTE, "BACKQUOTE"),
+    (KEY_A, "A"),
+    (KEY_B, "B"),
+    (KEY_C, "C"),
+    (KEY_D, "D"),
+    (KEY_E, "E"),
+    (KEY_F, "F"),
+    (KEY_G, "G"),
+    (KEY_H, "H"),
+    (KEY_I, "I"),
+    (KEY_J, "J"),
+    (KEY_K, "K"),
+    (KEY_L, "L"),
+    (KEY_M, "M"),
+    (KEY_N, "N"),
+    (KEY_O, "O"),
+    (KEY_P, "P"),
+    (KEY_Q, "Q"),
+    (KEY_R, "R"),
+    (KEY_S, "S"),
+    (KEY_T, "T"),
+    (KEY_U, "U"),
+    (KEY_V, "V"),
+    (KEY_W, "W"),
+    (KEY_X, "X"),
+    (KEY_Y, "Y"),
+    (KEY_Z, "Z"),
+    (KEY_DELETE, "DELETE"),
+    (KEY_CAPSLOCK, "CAPSLOCK"),
+    (KEY_F1, "F1"),
+    (KEY_F2, "F2"),
+    (KEY_F3, "F3"),
+    (KEY_F4, "F4"),
+    (KEY_F5, "F5"),
+    (KEY_F6, "F6"),
+    (KEY_F7, "F7"),
+    (KEY_F8, "F8"),
+    (KEY_F9, "F9"),
+    (KEY_F10, "F10"),
+    (KEY_F11, "F11"),
+    (KEY_F12, "F12"),
+    (KEY_PRINTSCREEN, "PRINTSCREEN"),
+    (KEY_SCROLLLOCK, "SCROLLLOCK"),
+    (KEY_PAUSE, "PAUSE"),
+    (KEY_INSERT, "INSERT"),
+    (KEY_HOME, "HOME"),
+    (KEY_PAGEUP, "PAGEUP"),
+    (KEY_END, "END"),
+    (KEY_PAGEDOWN, "PAGEDOWN"),
+    (KEY_RIGHT, "RIGHT"),
+    (KEY_LEFT, "LEFT"),
+    (KEY_DOWN, "DOWN"),
+    (KEY_UP, "UP"),
+    (KEY_NUMLOCKCLEAR, "NUMLOCKCLEAR"),
+    (KEY_KP_DIVIDE, "KP_DIVIDE"),
+    (KEY_KP_MULTIPLY, "KP_MULTIPLY"),
+    (KEY_KP_MINUS, "KP_MINUS"),
+    (KEY_KP_PLUS, "KP_PLUS"),
+    (KEY_KP_ENTER, "KP_ENTER"),
+    (KEY_KP_1, "KP_1"),
+    (KEY_KP_2, "KP_2"),
+    (KEY_KP_3, "KP_3"),
+    (KEY_KP_4, "KP_4"),
+    (KEY_KP_5, "KP_5"),
+    (KEY_KP_6, "KP_6"),
+    (KEY_KP_7, "KP_7"),
+    (KEY_KP_8, "KP_8"),
+    (KEY_KP_9, "KP_9"),
+    (KEY_KP_0, "KP_0"),
+    (KEY_KP_PERIOD, "KP_PERIOD"),
+    (KEY_APPLICATION, "APPLICATION"),
+    (KEY_POWER, "POWER"),
+    (KEY_KP_EQUALS, "KP_EQUALS"),
+    (KEY_F13, "F13"),
+    (KEY_F14, "F14"),
+    (KEY_F15, "F15"),
+    (KEY_F16, "F16"),
+    (KEY_F17, "F17"),
+    (KEY_F18, "F18"),
+    (KEY_F19, "F19"),
+    (KEY_F20, "F20"),
+    (KEY_F21, "F21"),
+    (KEY_F22, "F22"),
+    (KEY_F23, "F23"),
+    (KEY_F24, "F24"),
+    (KEY_EXECUTE, "EXECUTE"),
+    (KEY_HELP, "HELP"),
+    (KEY_MENU, "MENU"),
+    (KEY_SELECT, "SELECT"),
+    (KEY_STOP, "STOP"),
+    (KEY_AGAIN, "AGAIN"),
+    (KEY_UNDO, "UNDO"),
+    (KEY_CUT, "CUT"),
+    (KEY_COPY, "COPY"),
+    (KEY_PASTE, "PASTE"),
+    (KEY_FIND, "FIND"),
+    (KEY_MUTE, "MUTE"),
+    (KEY_VOLUMEUP, "VOLUMEUP"),
+    (KEY_VOLUMEDOWN, "VOLUMEDOWN"),
+    (KEY_KP_COMMA, "KP_COMMA"),
+    (KEY_KP_EQUALSAS400, "KP_EQUALSAS400"),
+    (KEY_ALTERASE, "ALTERASE"),
+    (KEY_SYSREQ, "SYSREQ"),
+    (KEY_CANCEL, "CANCEL"),
+    (KEY_CLEAR, "CLEAR"),
+    (KEY_PRIOR, "PRIOR"),
+    (KEY_RETURN2, "RETURN2"),
+    (KEY_SEPARATOR, "SEPARATOR"),
+    (KEY_OUT, "OUT"),
+    (KEY_OPER, "OPER"),
+    (KEY_CLEARAGAIN, "CLEARAGAIN"),
+    (KEY_CRSEL, "CRSEL"),
+    (KEY_EXSEL, "EXSEL"),
+    (KEY_KP_00, "KP_00"),
+    (KEY_KP_000, "KP_000"),
+    (KEY_THOUSANDSSEPARATOR, "THOUSANDSSEPARATOR"),
+    (KEY_DECIMALSEPARATOR, "DECIMALSEPARATOR"),
+    (KEY_CURRENCYUNIT, "CURRENCYUNIT"),
+    (KEY_CURRENCYSUBUNIT, "CURRENCYSUBUNIT"),
+    (KEY_KP_LEFTPAREN, "KP_LEFTPAREN"),
+    (KEY_KP_RIGHTPAREN, "KP_RIGHTPAREN"),
+    (KEY_KP_LEFTBRACE, "KP_LEFTBRACE"),
+    (KEY_KP_RIGHTBRACE, "KP_RIGHTBRACE"),
+    (KEY_KP_TAB, "KP_TAB"),
+    (KEY_KP_BACKSPACE, "KP_BACKSPACE"),
+    (KEY_KP_A, "KP_A"),
+    (KEY_KP_B, "KP_B"),
+    (KEY_KP_C, "KP_C"),
+    (KEY_KP_D, "KP_D"),
+    (KEY_KP_E, "KP_E"),
+    (KEY_KP_F, "KP_F"),
+    (KEY_KP_XOR, "KP_XOR"),
+    (KEY_KP_POWER, "KP_POWER"),
+    (KEY_KP_PERCENT, "KP_PERCENT"),
+    (KEY_KP_LESS, "KP_LESS"),
+    (KEY_KP_GREATER, "KP_GREATER"),
+    (KEY_KP_AMPERSAND, "KP_AMPERSAND"),
+    (KEY_KP_DBLAMPERSAND, "KP_DBLAMPERSAND"),
+    (KEY_KP_VERTICALBAR, "KP_VERTICALBAR"),
+    (KEY_KP_DBLVERTICALBAR, "KP_DBLVERTICALBAR"),
+    (KEY_KP_COLON, "KP_COLON"),
+    (KEY_KP_HASH, "KP_HASH"),
+    (KEY_KP_SPACE, "KP_SPACE"),
+    (KEY_KP_AT, "KP_AT"),
+    (KEY_KP_EXCLAM, "KP_EXCLAM"),
+    (KEY_KP_MEMSTORE, "KP_MEMSTORE"),
+    (KEY_KP_MEMRECALL, "KP_MEMRECALL"),
+    (KEY_KP_MEMCLEAR, "KP_MEMCLEAR"),
+    (KEY_KP_MEMADD, "KP_MEMADD"),
+    (KEY_KP_MEMSUBTRACT, "KP_MEMSUBTRACT"),
+    (KEY_KP_MEMMULTIPLY, "KP_MEMMULTIPLY"),
+    (KEY_KP_MEMDIVIDE, "KP_MEMDIVIDE"),
+    (KEY_KP_PLUSMINUS, "KP_PLUSMINUS"),
+    (KEY_KP_CLEAR, "KP_CLEAR"),
+    (KEY_KP_CLEARENTRY, "KP_CLEARENTRY"),
+    (KEY_KP_BINARY, "KP_BINARY"),
+    (KEY_KP_OCTAL, "KP_OCTAL"),
+    (KEY_KP_DECIMAL, "KP_DECIMAL"),
+    (KEY_KP_HEXADECIMAL, "KP_HEXADECIMAL"),
+    (KEY_LCTRL, "LCTRL"),
+    (KEY_LSHIFT, "LSHIFT"),
+    (KEY_LALT, "LALT"),
+    (KEY_LGUI, "LGUI"),
+    (KEY_RCTRL, "RCTRL"),
+    (KEY_RSHIFT, "RSHIFT"),
+    (KEY_RALT, "RALT"),
+    (KEY_RGUI, "RGUI"),
+    (KEY_MODE, "MODE"),
+    (KEY_AUDIONEXT, "AUDIONEXT"),
+    (KEY_AUDIOPREV, "AUDIOPREV"),
+    (KEY_AUDIOSTOP, "AUDIOSTOP"),
+    (KEY_AUDIOPLAY, "AUDIOPLAY"),
+    (KEY_AUDIOMUTE, "AUDIOMUTE"),
+    (KEY_MEDIASELECT, "MEDIASELECT"),
+    (KEY_WWW, "WWW"),
+    (KEY_MAIL, "MAIL"),
+    (KEY_CALCULATOR, "CALCULATOR"),
+    (KEY_COMPUTER, "COMPUTER"),
+    (KEY_AC_SEARCH, "AC_SEARCH"),
+    (KEY_AC_HOME, "AC_HOME"),
+    (KEY_AC_BACK, "AC_BACK"),
+    (KEY_AC_FORWARD, "AC_FORWARD"),
+    (KEY_AC_STOP, "AC_STOP"),
+    (KEY_AC_REFRESH, "AC_REFRESH"),
+    (KEY_AC_BOOKMARKS, "AC_BOOKMARKS"),
+    (KEY_BRIGHTNESSDOWN, "BRIGHTNESSDOWN"),
+    (KEY_BRIGHTNESSUP, "BRIGHTNESSUP"),
+    (KEY_DISPLAYSWITCH, "DISPLAYSWITCH"),
+    (KEY_KBDILLUMTOGGLE, "KBDILLUMTOGGLE"),
+    (KEY_KBDILLUMDOWN, "KBDILLUMDOWN"),
+    (KEY_KBDILLUMUP, "KBDILLUMUP"),
+    (KEY_EJECT, "EJECT"),
+    (KEY_SLEEP, "SLEEP"),
+    (KEY_NONE, "NONE"),
+    (KEY_SHIFT, "SHIFT"),
+    (KEY_CTRL, "CTRL"),
+    (KEY_ALT, "ALT"),
+    (KEY_GUI, "GUI"),
+    (MOUSE_POS_X, "MOUSE_POS_X"),
+    (MOUSE_POS_Y, "MOUSE_POS_Y"),
+    (MOUSE_WHEEL_X, "MOUSE_WHEEL_X"),
+    (MOUSE_WHEEL_Y, "MOUSE_WHEEL_Y"),
+    (MOUSE_BUTTON_LEFT, "MOUSE_BUTTON_LEFT"),
+    (MOUSE_BUTTON_MIDDLE, "MOUSE_BUTTON_MIDDLE"),
+    (MOUSE_BUTTON_RIGHT, "MOUSE_BUTTON_RIGHT"),
+    (MOUSE_BUTTON_X1, "MOUSE_BUTTON_X1"),
+    (MOUSE_BUTTON_X2, "MOUSE_BUTTON_X2"),
+    (MOUSE_BUTTON_UNKNOWN, "MOUSE_BUTTON_UNKNOWN"),
+    (GAMEPAD1_AXIS_LEFTX, "GAMEPAD1_AXIS_LEFTX"),
+    (GAMEPAD1_AXIS_LEFTY, "GAMEPAD1_AXIS_LEFTY"),
+    (GAMEPAD1_AXIS_RIGHTX, "GAMEPAD1_AXIS_RIGHTX"),
+    (GAMEPAD1_AXIS_RIGHTY, "GAMEPAD1_AXIS_RIGHTY"),
+    (GAMEPAD1_AXIS_TRIGGERLEFT, "GAMEPAD1_AXIS_TRIGGERLEFT"),
+    (GAMEPAD1_AXIS_TRIGGERRIGHT, "GAMEPAD1_AXIS_TRIGGERRIGHT"),
+    (GAMEPAD1_BUTTON_A, "GAMEPAD1_BUTTON_A"),
+    (GAMEPAD1_BUTTON_B, "GAMEPAD1_BUTTON_B"),
+    (GAMEPAD1_BUTTON_X, "GAMEPAD1_BUTTON_X"),
+    (GAMEPAD1_BUTTON_Y, "GAMEPAD1_BUTTON_Y"),
+    (GAMEPAD1_BUTTON_BACK, "GAMEPAD1_BUTTON_BACK"),
+    (GAMEPAD1_BUTTON_GUIDE, "GAMEPAD1_BUTTON_GUIDE"),
+    (GAMEPAD1_BUTTON_START, "GAMEPAD1_BUTTON_START"),
+    (GAMEPAD1_BUTTON_LEFTSTICK, "GAMEPAD1_BUTTON_LEFTSTICK"),
+    (GAMEPAD1_BUTTON_RIGHTSTICK, "GAMEPAD1_BUTTON_RIGHTSTICK"),
+    (GAMEPAD1_BUTTON_LEFTSHOULDER, "GAMEPAD1_BUTTON_LEFTSHOULDER"),
+    (
+        GAMEPAD1_BUTTON_RIGHTSHOULDER,
+        "GAMEPAD1_BUTTON_RIGHTSHOULDER",
+    ),
+    (GAMEPAD1_BUTTON_DPAD_UP, "GAMEPAD1_BUTTON_DPAD_UP"),
+    (GAMEPAD1_BUTTON_DPAD_DOWN, "GAMEPAD1_BUTTON_DPAD_DOWN"),
+    (GAMEPAD1_BUTTON_DPAD_LEFT, "GAMEPAD1_BUTTON_DPAD_LEFT"),
+    (GAMEPAD1_BUTTON_DPAD_RIGHT, "GAMEPAD1_BUTTON_DPAD_RIGHT"),
+    (GAMEPAD2_AXIS_LEFTX, "GAMEPAD2_AXIS_LEFTX"),
+    (GAMEPAD2_AXIS_LEFTY, "GAMEPAD2_AXIS_LEFTY"),
+    (GAMEPAD2_AXIS_RIGHTX, "GAMEPAD2_AXIS_RIGHTX"),
+    (GAMEPAD2_AXIS_RIGHTY, "GAMEPAD2_AXIS_RIGHTY"),
+    (GAMEPAD2_AXIS_TRIGGERLEFT, "GAMEPAD2_AXIS_TRIGGERLEFT"),
+    (GAMEPAD2_AXIS_TRIGGERRIGHT, "GAMEPAD2_AXIS_TRIGGERRIGHT"),
+    (GAMEPAD2_BUTTON_A, "GAMEPAD2_BUTTON_A"),
+    (GAMEPAD2_BUTTON_B, "GAMEPAD2_BUTTON_B"),
+    (GAMEPAD2_BUTTON_X, "GAMEPAD2_BUTTON_X"),
+    (GAMEPAD2_BUTTON_Y, "GAMEPAD2_BUTTON_Y"),
+    (GAMEPAD2_BUTTON_BACK, "GAMEPAD2_BUTTON_BACK"),
+    (GAMEPAD2_BUTTON_GUIDE, "GAMEPAD2_BUTTON_GUIDE"),
+    (GAMEPAD2_BUTTON_START, "GAMEPAD2_BUTTON_START"),
+    (GAMEPAD2_BUTTON_LEFTSTICK, "GAMEPAD2_BUTTON_LEFTSTICK"),
+    (GAMEPAD2_BUTTON_RIGHTSTICK, "GAMEPAD2_BUTTON_RIGHTSTICK"),
+    (GAMEPAD2_BUTTON_LEFTSHOULDER, "GAMEPAD2_BUTTON_LEFTSHOULDER"),
+    (
+        GAMEPAD2_BUTTON_RIGHTSHOULDER,
+        "GAMEPAD2_BUTTON_RIGHTSHOULDER",
+    ),
+    (GAMEPAD2_BUTTON_DPAD_UP, "GAMEPAD2_BUTTON_DPAD_UP"),
+    (GAMEPAD2_BUTTON_DPAD_DOWN, "GAMEPAD2_BUTTON_DPAD_DOWN"),
+    (GAMEPAD2_BUTTON_DPAD_LEFT, "GAMEPAD2_BUTTON_DPAD_LEFT"),
+    (GAMEPAD2_BUTTON_DPAD_RIGHT, "GAMEPAD2_BUTTON_DPAD_RIGHT"),
+    (GAMEPAD3_AXIS_LEFTX, "GAMEPAD3_AXIS_LEFTX"),
+    (GAMEPAD3_AXIS_LEFTY, "GAMEPAD3_AXIS_LEFTY"),
+    (GAMEPAD3_AXIS_RIGHTX, "GAMEPAD3_AXIS_RIGHTX"),
+    (GAMEPAD3_AXIS_RIGHTY, "GAMEPAD3_AXIS_RIGHTY"),
+    (GAMEPAD3_AXIS_TRIGGERLEFT, "GAMEPAD3_AXIS_TRIGGERLEFT"),
+    (GAMEPAD3_AXIS_TRIGGERRIGHT, "GAMEPAD3_AXIS_TRIGGERRIGHT"),
+    (GAMEPAD3_BUTTON_A, "GAMEPAD3_BUTTON_A"),
+    (GAMEPAD3_BUTTON_B, "GAMEPAD3_BUTTON_B"),
+    (GAMEPAD3_BUTTON_X, "GAMEPAD3_BUTTON_X"),
+    (GAMEPAD3_BUTTON_Y, "GAMEPAD3_BUTTON_Y"),
+    (GAMEPAD3_BUTTON_BACK, "GAMEPAD3_BUTTON_BACK"),
+    (GAMEPAD3_BUTTON_GUIDE, "GAMEPAD3_BUTTON_GUIDE"),
+    (GAMEPAD3_BUTTON_START, "GAMEPAD3_BUTTON_START"),
+    (GAMEPAD3_BUTTON_LEFTSTICK, "GAMEPAD3_BUTTON_LEFTSTICK"),
+    (GAMEPAD3_BUTTON_RIGHTSTICK, "GAMEPAD3_BUTTON_RIGHTSTICK"),
+    (GAMEPAD3_BUTTON_LEFTSHOULDER, "GAMEPAD3_BUTTON_LEFTSHOULDER"),
+    (
+        GAMEPAD3_BUTTON_RIGHTSHOULDER,
+        "GAMEPAD3_BUTTON_RIGHTSHOULDER",
+    ),
+    (GAMEPAD3_BUTTON_DPAD_UP, "GAMEPAD3_BUTTON_DPAD_UP"),
+    (GAMEPAD3_BUTTON_DPAD_DOWN, "GAMEPAD3_BUTTON_DPAD_DOWN"),
+    (GAMEPAD3_BUTTON_DPAD_LEFT, "GAMEPAD3_BUTTON_DPAD_LEFT"),
+    (GAMEPAD3_BUTTON_DPAD_RIGHT, "GAMEPAD3_BUTTON_DPAD_RIGHT"),
+    (GAMEPAD4_AXIS_LEFTX, "GAMEPAD4_AXIS_LEFTX"),
+    (GAMEPAD4_AXIS_LEFTY, "GAMEPAD4_AXIS_LEFTY"),
+    (GAMEPAD4_AXIS_RIGHTX, "GAMEPAD4_AXIS_RIGHTX"),
+    (GAMEPAD4_AXIS_RIGHTY, "GAMEPAD4_AXIS_RIGHTY"),
+    (GAMEPAD4_AXIS_TRIGGERLEFT, "GAMEPAD4_AXIS_TRIGGERLEFT"),
+    (GAMEPAD4_AXIS_TRIGGERRIGHT, "GAMEPAD4_AXIS_TRIGGERRIGHT"),
+    (GAMEPAD4_BUTTON_A, "GAMEPAD4_BUTTON_A"),
+    (GAMEPAD4_BUTTON_B, "GAMEPAD4_BUTTON_B"),
+    (GAMEPAD4_BUTTON_X, "GAMEPAD4_BUTTON_X"),
+    (GAMEPAD4_BUTTON_Y, "GAMEPAD4_BUTTON_Y"),
+    (GAMEPAD4_BUTTON_BACK, "GAMEPAD4_BUTTON_BACK"),
+    (GAMEPAD4_BUTTON_GUIDE, "GAMEPAD4_BUTTON_GUIDE"),
+    (GAMEPAD4_BUTTON_START, "GAMEPAD4_BUTTON_START"),
+    (GAMEPAD4_BUTTON_LEFTSTICK, "GAMEPAD4_BUTTON_LEFTSTICK"),
+    (GAMEPAD4_BUTTON_RIGHTSTICK, "GAMEPAD4_BUTTON_RIGHTSTICK"),
+    (GAMEPAD4_BUTTON_LEFTSHOULDER, "GAMEPAD4_BUTTON_LEFTSHOULDER"),
+    (
+        GAMEPAD4_BUTTON_RIGHTSHOULDER,
+        "GAMEPAD4_BUTTON_RIGHTSHOULDER",
+    ),
+    (GAMEPAD4_BUTTON_DPAD_UP, "GAMEPAD4_BUTTON_DPAD_UP"),
+    (GAMEPAD4_BUTTON_DPAD_DOWN, "GAMEPAD4_BUTTON_DPAD_DOWN"),
+    (GAMEPAD4_BUTTON_DPAD_LEFT, "GAMEPAD4_BUTTON_DPAD_LEFT"),
+    (GAMEPAD4_BUTTON_DPAD_RIGHT, "GAMEPAD4_BUTTON_DPAD_RIGHT"),
+];
+
 pub fn is_keyboard_key(key: Key) -> bool {
-    !(MOUSE_POS_X..=GAMEPAD2_BUTTON_DPAD_RIGHT).contains(&key)
+    !(MOUSE_POS_X..=GAMEPAD4_BUTTON_DPAD_RIGHT).contains(&key)
 }
 
 pub fn to_integrated_key(key: Key) -> Option<Key> {
@@ -315,3 +711,48 @@ pub fn to_integrated_key(key: Key) -> Option<Key> {
         _ => None,
     }
 }
+
+pub fn key_name(key: Key) -> Option<&'static str> {
+    KEY_NAMES
+        .iter()
+        .find(|(named_key, _)| *named_key == key)
+        .map(|(_, name)| *name)
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    let name = name.to_ascii_uppercase();
+    let name = name.strip_prefix("KEY_").unwrap_or(&name);
+    KEY_NAMES
+        .iter()
+        .find(|(_, key_name)| *key_name == name)
+        .map(|(key, _)| *key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_name_() {
+        assert_eq!(key_name(KEY_RETURN), Some("RETURN"));
+        assert_eq!(key_name(KEY_KP_1), Some("KP_1"));
+        assert_eq!(key_name(GAMEPAD1_BUTTON_A), Some("GAMEPAD1_BUTTON_A"));
+        assert_eq!(key_name(0xffff_ffff), None);
+    }
+
+    #[test]
+    fn key_from_name_() {
+        assert_eq!(key_from_name("SPACE"), Some(KEY_SPACE));
+        assert_eq!(key_from_name("key_space"), Some(KEY_SPACE));
+        assert_eq!(key_from_name("F12"), Some(KEY_F12));
+        assert_eq!(key_from_name("mouse_button_left"), Some(MOUSE_BUTTON_LEFT));
+        assert_eq!(key_from_name("UNKNOWN_KEY"), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        for (key, _) in KEY_NAMES {
+            assert_eq!(key_from_name(key_name(key).unwrap()), Some(key));
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::canvas::Canvas;
+use crate::image::{Image, SharedImage};
+use crate::types::Color;
+
+pub struct Layer {
+    pub image: SharedImage,
+    pub colkey: Color,
+    pub scroll_x: i32,
+    pub scroll_y: i32,
+}
+
+impl Layer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            image: Image::new(width, height),
+            colkey: 0,
+            scroll_x: 0,
+            scroll_y: 0,
+        }
+    }
+
+    // Reads the layer pixel shown at a screen position, following the wrapping scroll
+    pub fn pick(&self, x: i32, y: i32) -> Color {
+        let image = self.image.lock();
+        let src_x = (x as i64 + self.scroll_x as i64).rem_euclid(image.width() as i64);
+        let src_y = (y as i64 + self.scroll_y as i64).rem_euclid(image.height() as i64);
+        image.canvas.data[src_y as usize][src_x as usize]
+    }
+}
+
+pub fn composite_layers(
+    output: &mut Vec<Vec<Color>>,
+    layers: &[Layer],
+    screen: &Canvas<Color>,
+    screen_colkey: Color,
+) {
+    let width = screen.width() as usize;
+    let height = screen.height() as usize;
+    output.resize(height, Vec::new());
+    for row in output.iter_mut() {
+        row.clear();
+        row.resize(width, 0);
+    }
+    for layer in layers {
+        let image = layer.image.lock();
+        let layer_width = image.width() as i32;
+        let layer_height = image.height() as i32;
+        for (y, row) in output.iter_mut().enumerate() {
+            let src_y = (y as i32 + layer.scroll_y).rem_euclid(layer_height);
+            let src_row = &image.canvas.data[src_y as usize];
+            for (x, value) in row.iter_mut().enumerate() {
+                let src_x = (x as i32 + layer.scroll_x).rem_euclid(layer_width);
+                let src_value = src_row[src_x as usize];
+                if src_value != layer.colkey {
+                    *value = src_value;
+                }
+            }
+        }
+        drop(image);
+    }
+    for (row, src_row) in output.iter_mut().zip(&screen.data) {
+        for (value, &src_value) in row.iter_mut().zip(src_row) {
+            if src_value != screen_colkey {
+                *value = src_value;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composite_layers_() {
+        let back = Layer::new(4, 2);
+        back.image.lock().set(0, 0, &["1234", "5678"]);
+        let mut front = Layer::new(4, 2);
+        front.image.lock().set(0, 0, &["0900", "0009"]);
+        front.scroll_x = 1;
+        front.scroll_y = -1;
+        let mut screen = Canvas::<Color>::new(4, 2);
+        screen.data[1][3] = 15;
+
+        let mut output = Vec::new();
+        let layers = [back, front];
+        composite_layers(&mut output, &layers, &screen, 0);
+        assert_eq!(output, [[1, 2, 9, 4], [9, 6, 7, 15]]);
+        assert_eq!(layers[1].pick(2, 0), 9);
+        assert_eq!(layers[1].pick(0, 1), 9);
+        assert_eq!(layers[1].pick(-2, 3), 0);
+
+        let mut front = Layer::new(4, 2);
+        front.colkey = 1;
+        composite_layers(&mut output, &[front], &screen, 15);
+        assert_eq!(output, [[0, 0, 0, 0], [0, 0, 0, 0]]);
+    }
+}
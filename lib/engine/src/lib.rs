@@ -25,45 +25,112 @@
 
 #[macro_use]
 mod utils;
+mod action_map;
+mod animation;
+mod asset_watcher;
 mod audio;
+mod audio_capture;
+mod audio_monitor;
 mod canvas;
 mod channel;
+mod color;
+mod command_queue;
+mod deadzone;
+mod dirty_region;
+mod envelope;
 mod event;
+mod font;
+mod frame_stats;
+mod geometry;
+#[cfg(feature = "gif-import")]
+mod gif_import;
 mod graphics;
 mod image;
 mod input;
+mod input_record;
 mod key;
+mod layer;
+#[cfg(feature = "logger")]
+mod logger;
 mod math;
+mod mml;
 mod music;
+mod options;
 mod oscillator;
+mod palette;
 mod platform;
+mod platform_handle;
+mod player_slots;
 mod profiler;
-mod rectarea;
 mod resource;
+mod ring_buffer;
+mod sample;
+mod scanline;
 mod screencast;
 mod sdl2;
+#[cfg(feature = "serde")]
+mod serialization;
 mod settings;
 mod sound;
+mod storage;
 mod system;
+mod text_layout;
 mod tilemap;
+mod tmx;
+mod transition;
 mod types;
+mod ui;
 
+pub use ::sdl2::render::WindowCanvas;
+use log::warn;
+
+pub use crate::action_map::{ActionMap, Binding};
+pub use crate::animation::{Animation, AnimationFrame};
+pub use crate::asset_watcher::AssetKind;
 use crate::audio::Audio;
-pub use crate::channel::{Channel, SharedChannel};
+pub use crate::audio_capture::CaptureStats;
+pub use crate::audio_monitor::AudioStats;
+pub use crate::color::{blue, green, hsv, lerp, red, rgb, rgb_components};
+pub use crate::envelope::Envelope;
+pub use crate::event::{ControllerAxis, ControllerButton};
+pub use crate::font::{Font, SharedFont};
+pub use crate::frame_stats::FrameStats;
+pub use crate::geometry::{Rectf, Recti, Vec2};
 use crate::graphics::Graphics;
-pub use crate::image::{Image, SharedImage};
+pub use crate::image::{Image, ImageError, SharedImage};
 use crate::input::Input;
+pub use crate::input_record::InputRecordError;
 pub use crate::key::*;
-pub use crate::math::Math;
+#[cfg(feature = "logger")]
+pub use crate::logger::init_logger;
+pub use crate::math::{Math, Rng};
+pub use crate::mml::MmlError;
 pub use crate::music::{Music, SharedMusic};
+pub use crate::options::SystemOptions;
+pub use crate::palette::Palette;
 use crate::platform::Platform;
+pub use crate::platform::{
+    write_rgb24, AudioError, AuxWindowEvent, AuxWindowId, DisplayEffect, FullscreenMode,
+    PlatformBuilder, PlatformError, PostRenderHook, RgbTables, ScreenFit, ScreenLayout, ScreenMode,
+};
+pub use crate::platform_handle::{PlatformCommand, PlatformHandle};
+pub use crate::profiler::{ProfileScope, ScopeStats};
 use crate::resource::Resource;
+pub use crate::resource::ResourceError;
+pub use crate::sample::Sample;
+pub use crate::scanline::{ScanlineEdge, ScanlineEffect, ScanlineParams};
 use crate::sdl2::Sdl2;
+#[cfg(feature = "serde")]
+pub use crate::serialization::rgb as serde_rgb;
 pub use crate::settings::*;
-pub use crate::sound::{SharedSound, Sound};
-use crate::system::System;
-pub use crate::tilemap::{SharedTilemap, Tilemap};
+pub use crate::sound::{SharedSound, Sound, SoundError};
+pub use crate::storage::{Storage, StorageError};
+pub use crate::system::{CaptureAction, System};
+pub use crate::text_layout::{measure_text_box, Align};
+pub use crate::tilemap::{SharedTilemap, SweepResult, Tilemap, TilemapError};
+pub use crate::transition::Transition;
 pub use crate::types::*;
+pub use crate::ui::{CharSet, VirtualKey, VirtualKeyboard, VirtualKeyboardEvent};
 
 type TargetPlatform = Sdl2;
 
@@ -75,7 +142,7 @@ pub struct Pyxel {
     graphics: Graphics,
     audio: Audio,
     math: Math,
-    pub colors: [Rgb8; NUM_COLORS as usize],
+    pub palette: Palette,
     pub screen: SharedImage,
     pub cursor: SharedImage,
     pub font: SharedImage,
@@ -84,6 +151,21 @@ pub struct Pyxel {
 pub trait PyxelCallback {
     fn update(&mut self, pyxel: &mut Pyxel);
     fn draw(&mut self, pyxel: &mut Pyxel);
+
+    fn on_quit(&mut self, _pyxel: &mut Pyxel) -> bool {
+        true
+    }
+}
+
+pub type Context = Pyxel;
+
+pub trait Game {
+    fn update(&mut self, ctx: &mut Context);
+    fn draw(&mut self, ctx: &mut Context);
+
+    fn on_quit(&mut self, _ctx: &mut Context) -> bool {
+        true
+    }
 }
 
 impl Pyxel {
@@ -96,21 +178,54 @@ impl Pyxel {
         capture_scale: Option<u32>,
         capture_sec: Option<u32>,
     ) -> Self {
-        let title = title.unwrap_or(DEFAULT_TITLE);
+        let builder = PlatformBuilder::new()
+            .title(title.unwrap_or(DEFAULT_TITLE))
+            .screen_size(width, height)
+            .display_ratio(DISPLAY_RATIO);
+        Self::with_builder(builder, fps, quit_key, capture_scale, capture_sec)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    // The builder sets up the window, and the default icon is used unless it sets one
+    pub fn with_builder(
+        builder: PlatformBuilder,
+        fps: Option<u32>,
+        quit_key: Option<Key>,
+        capture_scale: Option<u32>,
+        capture_sec: Option<u32>,
+    ) -> Result<Self, PlatformError> {
         let fps = fps.unwrap_or(DEFAULT_FPS);
         let quit_key = quit_key.unwrap_or(DEFAULT_QUIT_KEY);
         let capture_scale = capture_scale.unwrap_or(DEFAULT_CAPTURE_SCALE);
         let capture_sec = capture_sec.unwrap_or(DEFAULT_CAPTURE_SEC);
+        let (width, height) = (builder.width, builder.height);
+
+        let palette = Palette::default();
+        let builder = if builder.icon.is_none() {
+            let icon = Self::icon_image(&ICON_DATA);
+            let icon = icon.lock();
+            builder.icon(&icon, palette.display_colors(), ICON_SCALE)
+        } else {
+            builder
+        };
 
-        let mut platform = TargetPlatform::new(title, width, height, DISPLAY_RATIO);
+        // The options only set the initial window, so the game can still change it afterwards,
+        // and options the platform rejects fall back to the window the game asked for
+        let options = SystemOptions::from_env_and_args();
+        let mut platform = options
+            .configure(builder.clone())
+            .build::<TargetPlatform>()
+            .or_else(|err| {
+                warn!("Ignoring window options: {}", err);
+                builder.build()
+            })?;
         let system = System::new(fps, quit_key);
-        let resource = Resource::new(fps, capture_scale, capture_sec);
+        let resource = Resource::new(&builder.title, fps, capture_scale, capture_sec);
         let input = Input::new();
         let graphics = Graphics::new();
-        let audio = Audio::new(&mut platform);
+        let audio = Audio::new(&mut platform, NUM_CHANNELS);
         let math = Math::new(&mut platform);
 
-        let colors = DEFAULT_COLORS;
         let screen = Image::new(width, height);
         let cursor = Graphics::new_cursor_image();
         let font = Graphics::new_font_image();
@@ -123,12 +238,12 @@ impl Pyxel {
             graphics,
             audio,
             math,
-            colors,
+            palette,
             screen,
             cursor,
             font,
         };
-        pyxel.icon(&ICON_DATA, ICON_SCALE);
-        pyxel
+        pyxel.apply_options(&options);
+        Ok(pyxel)
     }
 }
@@ -0,0 +1,61 @@
+use std::env;
+use std::io::{stderr, Write};
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::settings::{DEFAULT_LOG_LEVEL, LOG_LEVEL_ENV};
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let _ = writeln!(
+                stderr(),
+                "[{} {}] {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {
+        let _ = stderr().flush();
+    }
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn parse_level(value: &str) -> Option<LevelFilter> {
+    value.trim().parse().ok()
+}
+
+pub fn init_logger() -> Result<(), SetLoggerError> {
+    // A logger installed by the application takes precedence over this one
+    let level = env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|value| parse_level(&value))
+        .unwrap_or(DEFAULT_LOG_LEVEL);
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_level() {
+        assert_eq!(super::parse_level("off"), Some(LevelFilter::Off));
+        assert_eq!(super::parse_level(" Debug "), Some(LevelFilter::Debug));
+        assert_eq!(super::parse_level("WARN"), Some(LevelFilter::Warn));
+        assert_eq!(super::parse_level("loud"), None);
+        assert_eq!(super::parse_level(""), None);
+    }
+}
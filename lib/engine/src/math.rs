@@ -1,21 +1,88 @@
 use std::f64::consts;
 
 use noise::{NoiseFn, Perlin, Seedable};
-use rand::{Rng, SeedableRng};
-use rand_xoshiro::Xoshiro256StarStar;
 
 use crate::Platform;
 use crate::Pyxel;
 
+#[derive(Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: [0; 4] };
+        rng.seed(seed);
+        rng
+    }
+
+    pub fn seed(&mut self, seed: u64) {
+        // The state is expanded with SplitMix64, which never yields the all-zero state
+        let mut value = seed;
+        for state in &mut self.state {
+            value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = value;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            *state = z ^ (z >> 31);
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // xoshiro256**
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+
+    fn below(&mut self, range: u64) -> u64 {
+        // Lemire's multiply-and-reject keeps every value equally likely
+        let threshold = range.wrapping_neg() % range;
+        loop {
+            let product = self.next_u64() as u128 * range as u128;
+            if product as u64 >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+
+    pub fn rndi(&mut self, a: i32, b: i32) -> i32 {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        let range = (b as i64 - a as i64 + 1) as u64;
+        (a as i64 + self.below(range) as i64) as i32
+    }
+
+    pub fn rndf(&mut self, a: f64, b: f64) -> f64 {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        let unit = (self.next_u64() >> 11) as f64 / ((1_u64 << 53) - 1) as f64;
+        a + (b - a) * unit
+    }
+
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            values.swap(i, j);
+        }
+    }
+}
+
 pub struct Math {
-    rng: Xoshiro256StarStar,
+    rng: Rng,
     perlin: Perlin,
 }
 
 impl Math {
     pub fn new<T: Platform>(platform: &mut T) -> Self {
         let seed = platform.tick_count();
-        let rng = Xoshiro256StarStar::seed_from_u64(seed as u64);
+        let rng = Rng::new(seed as u64);
         let perlin = Perlin::new();
         perlin.set_seed(seed);
         Self { rng, perlin }
@@ -62,17 +129,23 @@ impl Pyxel {
     }
 
     pub fn rseed(&mut self, seed: u32) {
-        self.math.rng = Xoshiro256StarStar::seed_from_u64(seed as u64);
+        self.math.rng.seed(seed as u64);
     }
 
     pub fn rndi(&mut self, a: i32, b: i32) -> i32 {
-        let (a, b) = if a < b { (a, b) } else { (b, a) };
-        self.math.rng.gen_range(a..=b)
+        self.math.rng.rndi(a, b)
     }
 
     pub fn rndf(&mut self, a: f64, b: f64) -> f64 {
-        let (a, b) = if a < b { (a, b) } else { (b, a) };
-        self.math.rng.gen_range(a..=b)
+        self.math.rng.rndf(a, b)
+    }
+
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        self.math.rng.shuffle(values);
+    }
+
+    pub fn rng_mut(&mut self) -> &mut Rng {
+        &mut self.math.rng
     }
 
     pub fn nseed(&mut self, seed: u32) {
@@ -83,3 +156,38 @@ impl Pyxel {
         self.math.perlin.get([x, y, z])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rng() {
+        // These sequences are part of the API, since replays and generated levels depend on them
+        let mut rng = Rng::new(0);
+        let values: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        assert_eq!(
+            values,
+            [
+                11091344671253066420,
+                13793997310169335082,
+                1900383378846508768
+            ]
+        );
+        rng.seed(12345);
+        let values: Vec<i32> = (0..8).map(|_| rng.rndi(1, 6)).collect();
+        assert_eq!(values, [5, 1, 6, 1, 4, 1, 1, 2]);
+        rng.seed(12345);
+        assert_eq!(rng.rndf(-1.0, 1.0), 0.48761632631317897);
+        let mut values = [1, 2, 3, 4, 5, 6, 7, 8];
+        rng.shuffle(&mut values);
+        assert_eq!(values, [8, 5, 4, 6, 3, 1, 7, 2]);
+
+        assert_eq!(rng.rndi(3, 3), 3);
+        assert_eq!(rng.rndf(2.0, 2.0), 2.0);
+        for _ in 0..100 {
+            assert!((-2..=1).contains(&rng.rndi(1, -2)));
+        }
+        rng.rndi(i32::MIN, i32::MAX);
+    }
+}
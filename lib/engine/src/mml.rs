@@ -0,0 +1,325 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::settings::{
+    CLOCK_RATE, EFFECT_NONE, INITIAL_SPEED, MAX_NOTE, MAX_TONE, MAX_VOLUME, MML_DEFAULT_LENGTH,
+    MML_DEFAULT_OCTAVE, MML_DEFAULT_TEMPO, MML_MAX_LENGTH, MML_MAX_OCTAVE, MML_MAX_TEMPO,
+    MML_MIN_OCTAVE, NUM_CLOCKS_PER_TICK, TONE_TRIANGLE,
+};
+use crate::types::{Effect, Note, Speed, Tone, Volume};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MmlError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for MmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MmlSound {
+    pub notes: Vec<Note>,
+    pub tones: Vec<Tone>,
+    pub volumes: Vec<Volume>,
+    pub effects: Vec<Effect>,
+    pub speed: Speed,
+}
+
+struct MmlEvent {
+    position: usize,
+    note: Note,
+    tone: Tone,
+    volume: Volume,
+    end_tick: f64,
+}
+
+struct MmlParser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    len: usize,
+}
+
+fn error<T>(position: usize, message: String) -> Result<T, MmlError> {
+    Err(MmlError { position, message })
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl<'a> MmlParser<'a> {
+    fn new(mml: &'a str) -> Self {
+        Self {
+            chars: mml.char_indices().peekable(),
+            len: mml.len(),
+        }
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map_or(self.len, |(position, _)| *position)
+    }
+
+    fn next_command(&mut self) -> Option<(usize, char)> {
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+        self.chars.next()
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let mut value: Option<u32> = None;
+        while let Some(digit) = self.chars.peek().and_then(|(_, c)| c.to_digit(10)) {
+            value = Some(value.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            self.chars.next();
+        }
+        value
+    }
+
+    fn value(
+        &mut self,
+        position: usize,
+        command: char,
+        max: u32,
+        name: &str,
+    ) -> Result<u32, MmlError> {
+        match self.number() {
+            Some(value) if value <= max => Ok(value),
+            Some(value) => error(position, format!("invalid {} {}", name, value)),
+            None => error(position, format!("missing value after '{}'", command)),
+        }
+    }
+
+    fn length(
+        &mut self,
+        position: usize,
+        command: char,
+        default_length: Option<f64>,
+    ) -> Result<f64, MmlError> {
+        let value_position = self.position();
+        let mut length = match (self.number(), default_length) {
+            (Some(value @ 1..=MML_MAX_LENGTH), _) => 1.0 / value as f64,
+            (Some(value), _) => return error(value_position, format!("invalid length {}", value)),
+            (None, Some(default_length)) => default_length,
+            (None, None) => return error(position, format!("missing value after '{}'", command)),
+        };
+        let mut dot_length = length;
+        while matches!(self.chars.peek(), Some((_, '.'))) {
+            self.chars.next();
+            dot_length /= 2.0;
+            length += dot_length;
+        }
+        Ok(length)
+    }
+}
+
+pub fn parse_mml(mml: &str) -> Result<MmlSound, MmlError> {
+    let mut parser = MmlParser::new(mml);
+    let mut tempo = MML_DEFAULT_TEMPO;
+    let mut octave = MML_DEFAULT_OCTAVE as i32;
+    let mut default_length = 1.0 / MML_DEFAULT_LENGTH as f64;
+    let mut tone = TONE_TRIANGLE;
+    let mut volume = MAX_VOLUME;
+    let mut tick = 0.0;
+    let mut events = Vec::new();
+    let ticks_per_second = CLOCK_RATE as f64 / NUM_CLOCKS_PER_TICK as f64;
+    while let Some((position, command)) = parser.next_command() {
+        let semitone = match command.to_ascii_lowercase() {
+            't' => {
+                tempo = parser.value(position, command, MML_MAX_TEMPO, "tempo")?;
+                if tempo == 0 {
+                    return error(position, "invalid tempo 0".to_string());
+                }
+                continue;
+            }
+            'o' => {
+                let value = parser.value(position, command, MML_MAX_OCTAVE, "octave")?;
+                if value < MML_MIN_OCTAVE {
+                    return error(position, format!("invalid octave {}", value));
+                }
+                octave = value as i32;
+                continue;
+            }
+            '<' => {
+                octave -= 1;
+                continue;
+            }
+            '>' => {
+                octave += 1;
+                continue;
+            }
+            'l' => {
+                default_length = parser.length(position, command, None)?;
+                continue;
+            }
+            'v' => {
+                volume = parser.value(position, command, MAX_VOLUME as u32, "volume")? as Volume;
+                continue;
+            }
+            '@' => {
+                tone = parser.value(position, command, MAX_TONE as u32, "tone")? as Tone;
+                continue;
+            }
+            'r' => None,
+            'c' => Some(0),
+            'd' => Some(2),
+            'e' => Some(4),
+            'f' => Some(5),
+            'g' => Some(7),
+            'a' => Some(9),
+            'b' => Some(11),
+            _ => return error(position, format!("invalid note '{}'", command)),
+        };
+        let note = match semitone {
+            Some(mut semitone) => {
+                if let Some((_, accidental @ ('+' | '#' | '-'))) = parser.chars.peek().copied() {
+                    semitone += if accidental == '-' { -1 } else { 1 };
+                    parser.chars.next();
+                }
+                let note = (octave - MML_MIN_OCTAVE as i32) * 12 + semitone;
+                if !(0..=MAX_NOTE as i32).contains(&note) {
+                    return error(position, format!("note '{}' out of range", command));
+                }
+                note as Note
+            }
+            None => -1,
+        };
+        let length = parser.length(position, command, Some(default_length))?;
+        tick += length * 4.0 * 60.0 / tempo as f64 * ticks_per_second;
+        events.push(MmlEvent {
+            position,
+            note,
+            tone,
+            volume,
+            end_tick: tick,
+        });
+    }
+    compile_events(&events)
+}
+
+fn compile_events(events: &[MmlEvent]) -> Result<MmlSound, MmlError> {
+    // Notes start on whole ticks, and the longest step that fits every note becomes the speed
+    let mut durations = Vec::with_capacity(events.len());
+    let mut start_tick = 0;
+    for event in events {
+        let end_tick = event.end_tick.round() as u32;
+        if end_tick <= start_tick {
+            return error(event.position, "note too short for the tempo".to_string());
+        }
+        durations.push(end_tick - start_tick);
+        start_tick = end_tick;
+    }
+    let speed = durations
+        .iter()
+        .copied()
+        .reduce(gcd)
+        .unwrap_or(INITIAL_SPEED);
+    let mut sound = MmlSound {
+        notes: Vec::new(),
+        tones: Vec::new(),
+        volumes: Vec::new(),
+        effects: Vec::new(),
+        speed,
+    };
+    for (event, duration) in events.iter().zip(durations) {
+        for _ in 0..duration / speed {
+            sound.notes.push(event.note);
+            sound.tones.push(event.tone);
+            sound.volumes.push(event.volume);
+            sound.effects.push(EFFECT_NONE);
+        }
+    }
+    Ok(sound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notes(mml: &str) -> (Vec<Note>, Speed) {
+        let sound = parse_mml(mml).unwrap();
+        assert_eq!(sound.tones.len(), sound.notes.len());
+        assert_eq!(sound.volumes.len(), sound.notes.len());
+        assert_eq!(sound.effects, vec![EFFECT_NONE; sound.notes.len()]);
+        (sound.notes, sound.speed)
+    }
+
+    #[test]
+    fn parse_mml_() {
+        assert_eq!(
+            notes("t120 o4 l8 cdefgab>c"),
+            (vec![24, 26, 28, 29, 31, 33, 35, 36], 30)
+        );
+        assert_eq!(
+            notes("c4 r8 c8."),
+            (vec![24, 24, 24, 24, -1, -1, 24, 24, 24], 15)
+        );
+        assert_eq!(notes("o2c o6b"), (vec![0, 59], 60));
+        assert_eq!(notes("<<c >>>>b"), (vec![0, 59], 60));
+        assert_eq!(notes("C D  E\n"), (vec![24, 26, 28], 60));
+        assert_eq!(notes("c+ d- e# b-"), (vec![25, 25, 29, 34], 60));
+        assert_eq!(notes("t60 l2 c"), (vec![24], 240));
+        assert_eq!(notes("t60 l2. c"), (vec![24], 360));
+        assert_eq!(notes("c.. c8"), (vec![24; 9], 15));
+        assert_eq!(notes("t240 l16 c"), (vec![24], 8));
+        assert_eq!(notes("t120 l32 cc"), (vec![24; 15], 1));
+        assert_eq!(notes(""), (Vec::new(), INITIAL_SPEED));
+
+        let sound = parse_mml("v3 @1 c v7 @6 d").unwrap();
+        assert_eq!(sound.tones, vec![1, 6]);
+        assert_eq!(sound.volumes, vec![3, 7]);
+    }
+
+    #[test]
+    fn parse_mml_invalid() {
+        let cases = [
+            ("cdh", 2, "invalid note 'h'"),
+            ("c\u{3000}h", 4, "invalid note 'h'"),
+            ("cé", 1, "invalid note 'é'"),
+            ("t0 c", 0, "invalid tempo 0"),
+            ("t1000", 0, "invalid tempo 1000"),
+            ("c t", 2, "missing value after 't'"),
+            ("o7", 0, "invalid octave 7"),
+            ("o1", 0, "invalid octave 1"),
+            ("O", 0, "missing value after 'O'"),
+            ("c65", 1, "invalid length 65"),
+            ("c r0", 3, "invalid length 0"),
+            ("l", 0, "missing value after 'l'"),
+            ("l99", 1, "invalid length 99"),
+            ("v8", 0, "invalid volume 8"),
+            ("@7", 0, "invalid tone 7"),
+            ("@", 0, "missing value after '@'"),
+            ("o2 c-", 3, "note 'c' out of range"),
+            ("o6 b+", 3, "note 'b' out of range"),
+            ("o6 >c", 4, "note 'c' out of range"),
+            ("t960 l64 c", 9, "note too short for the tempo"),
+        ];
+        for (mml, position, message) in cases {
+            assert_eq!(
+                parse_mml(mml),
+                Err(MmlError {
+                    position,
+                    message: message.to_string()
+                }),
+                "{}",
+                mml
+            );
+        }
+        assert_eq!(
+            parse_mml("cdh").unwrap_err().to_string(),
+            "invalid note 'h' at position 2"
+        );
+    }
+}
@@ -1,11 +1,24 @@
+use std::cmp::max;
+use std::path::Path;
+
+use log::warn;
+
+use crate::audio::render_sounds;
+use crate::image::SharedImage;
+use crate::platform::AudioError;
 use crate::resource::ResourceItem;
+use crate::sample::write_wav;
 use crate::settings::{NUM_CHANNELS, RESOURCE_ARCHIVE_DIRNAME};
+use crate::sound::SharedSound;
 use crate::utils::parse_hex_string;
 use crate::Pyxel;
 
+// Music always holds NUM_CHANNELS sequences, and playm rejects music whose
+// non-empty sequences fall outside the configured channel count
 #[derive(Clone)]
 pub struct Music {
     pub sequences: [Vec<u32>; NUM_CHANNELS as usize],
+    pub loop_ranges: [Option<(u32, u32)>; NUM_CHANNELS as usize],
 }
 
 pub type SharedMusic = shared_type!(Music);
@@ -14,9 +27,31 @@ impl Music {
     pub fn new() -> SharedMusic {
         new_shared_type!(Self {
             sequences: Default::default(),
+            loop_ranges: Default::default(),
         })
     }
 
+    pub fn set_loop_range(&mut self, start_index: u32, end_index: u32) {
+        for channel_no in 0..NUM_CHANNELS {
+            self.set_channel_loop_range(channel_no, start_index, end_index);
+        }
+    }
+
+    pub fn set_channel_loop_range(&mut self, channel_no: u32, start_index: u32, end_index: u32) {
+        if channel_no >= NUM_CHANNELS || start_index > end_index {
+            warn!(
+                "Invalid loop range {}-{} for channel {}",
+                start_index, end_index, channel_no
+            );
+            return;
+        }
+        self.loop_ranges[channel_no as usize] = Some((start_index, end_index));
+    }
+
+    pub fn clear_loop_range(&mut self) {
+        self.loop_ranges = Default::default();
+    }
+
     pub fn set(
         &mut self,
         sequence0: &[u32],
@@ -29,6 +64,33 @@ impl Music {
         self.sequences[2] = sequence2.to_vec();
         self.sequences[3] = sequence3.to_vec();
     }
+
+    pub fn to_wav(
+        &self,
+        pyxel: &Pyxel,
+        path: &Path,
+        sample_rate: u32,
+        loops: u32,
+    ) -> Result<(), AudioError> {
+        let sequences: Vec<Vec<SharedSound>> = self
+            .sequences
+            .iter()
+            .map(|sequence| {
+                sequence
+                    .iter()
+                    .map(|sound_no| pyxel.sound(*sound_no))
+                    .collect()
+            })
+            .collect();
+        let num_ticks = sequences
+            .iter()
+            .map(|sounds| sounds.iter().map(|sound| sound.lock().num_ticks()).sum())
+            .max()
+            .unwrap_or(0)
+            * max(loops, 1);
+        let samples = render_sounds(sequences, sample_rate, loops > 1, num_ticks)?;
+        write_wav(path, sample_rate, &samples)
+    }
 }
 
 impl ResourceItem for Music {
@@ -47,6 +109,7 @@ impl ResourceItem for Music {
 
     fn clear(&mut self) {
         self.sequences = Default::default();
+        self.loop_ranges = Default::default();
     }
 
     fn serialize(&self, _pyxel: &Pyxel) -> String {
@@ -64,16 +127,28 @@ impl ResourceItem for Music {
         output
     }
 
-    fn deserialize(&mut self, _pyxel: &Pyxel, _version: u32, input: &str) {
-        self.clear();
+    fn deserialize(
+        &mut self,
+        _images: &[SharedImage],
+        _version: u32,
+        input: &str,
+    ) -> Result<(), String> {
+        let mut sequences: [Vec<u32>; NUM_CHANNELS as usize] = Default::default();
         for (i, line) in input.lines().enumerate() {
             if line == "none" {
                 continue;
             }
+            let sequence = sequences
+                .get_mut(i)
+                .ok_or_else(|| format!("unexpected line {}", i + 1))?;
             string_loop!(j, value, line, 2, {
-                self.sequences[i].push(parse_hex_string(&value).unwrap());
+                let sound_no =
+                    parse_hex_string(&value).map_err(|_| format!("invalid value '{}'", value))?;
+                sequence.push(sound_no);
             });
         }
+        self.sequences = sequences;
+        Ok(())
     }
 }
 
@@ -99,4 +174,19 @@ mod tests {
             assert_eq!(&music.lock().sequences[i as usize], &vec![i, i + 1, i + 2]);
         }
     }
+
+    #[test]
+    fn set_loop_range() {
+        let music = Music::new();
+        music.lock().set_loop_range(1, 3);
+        music.lock().set_channel_loop_range(2, 0, 0);
+        music.lock().set_channel_loop_range(1, 2, 1);
+        music.lock().set_channel_loop_range(NUM_CHANNELS, 0, 1);
+        assert_eq!(
+            music.lock().loop_ranges,
+            [Some((1, 3)), Some((1, 3)), Some((0, 0)), Some((1, 3))]
+        );
+        music.lock().clear_loop_range();
+        assert_eq!(music.lock().loop_ranges, [None; NUM_CHANNELS as usize]);
+    }
 }
@@ -0,0 +1,304 @@
+use std::env;
+
+use log::warn;
+
+use crate::platform::{FullscreenMode, PlatformBuilder};
+
+const OPTION_HELP: [(&str, &str, &str); 10] = [
+    (
+        "--scale=N",
+        "PYXEL_SCALE=N",
+        "Scale the window to N times the screen size",
+    ),
+    (
+        "--fullscreen",
+        "PYXEL_FULLSCREEN=1",
+        "Start in fullscreen mode",
+    ),
+    (
+        "--fullscreen=exclusive",
+        "PYXEL_FULLSCREEN=exclusive",
+        "Start in exclusive fullscreen mode",
+    ),
+    ("--windowed", "PYXEL_FULLSCREEN=0", "Start in a window"),
+    (
+        "--borderless",
+        "PYXEL_BORDERLESS=1",
+        "Open the window without a border",
+    ),
+    (
+        "--display=N",
+        "PYXEL_DISPLAY=N",
+        "Open the window on display N",
+    ),
+    (
+        "--position=X,Y",
+        "PYXEL_POSITION=X,Y",
+        "Open the window at (X, Y) on the display",
+    ),
+    (
+        "--no-vsync",
+        "PYXEL_VSYNC=0",
+        "Present frames without waiting for vsync",
+    ),
+    (
+        "--vsync",
+        "PYXEL_VSYNC=1",
+        "Wait for vsync when presenting frames",
+    ),
+    (
+        "--record-input=FILE",
+        "PYXEL_RECORD_INPUT=FILE",
+        "Record the input of the session to FILE",
+    ),
+];
+
+const VALUE_OPTIONS: [&str; 7] = [
+    "scale",
+    "fullscreen",
+    "borderless",
+    "display",
+    "position",
+    "vsync",
+    "record-input",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemOptions {
+    pub scale: Option<u32>,
+    pub fullscreen: Option<FullscreenMode>,
+    pub borderless: Option<bool>,
+    pub display: Option<u32>,
+    pub position: Option<(i32, i32)>,
+    pub vsync: Option<bool>,
+    pub record_input: Option<String>,
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn warn_invalid(value: &str, source: &str) {
+    warn!("Ignoring invalid value '{}' for '{}'", value, source);
+}
+
+impl SystemOptions {
+    pub fn from_env_and_args() -> Self {
+        Self::parse(env::args().skip(1), |name| env::var(name).ok())
+    }
+
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        env_var: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        // Command line flags take precedence over environment variables, and arguments
+        // that are not engine flags are left for the game
+        let mut options = Self::default();
+        for name in VALUE_OPTIONS {
+            let env_name = format!("PYXEL_{}", name.to_uppercase().replace('-', "_"));
+            if let Some(value) = env_var(&env_name) {
+                options.set(name, &value, &env_name);
+            }
+        }
+        for arg in args {
+            let flag = match arg.strip_prefix("--") {
+                Some(flag) => flag,
+                None => continue,
+            };
+            match flag.split_once('=') {
+                Some((name, value)) if VALUE_OPTIONS.contains(&name) => {
+                    options.set(name, value, &arg);
+                }
+                Some(_) => {}
+                None => match flag {
+                    "fullscreen" => options.fullscreen = Some(FullscreenMode::Desktop),
+                    "windowed" => options.fullscreen = Some(FullscreenMode::Off),
+                    "borderless" => options.borderless = Some(true),
+                    "vsync" => options.vsync = Some(true),
+                    "no-vsync" => options.vsync = Some(false),
+                    "scale" | "display" | "position" | "record-input" => {
+                        warn!("Ignoring option '{}' without a value", arg);
+                    }
+                    _ => {}
+                },
+            }
+        }
+        options
+    }
+
+    fn set(&mut self, name: &str, value: &str, source: &str) {
+        match name {
+            "scale" => match value.parse() {
+                Ok(scale) if scale > 0 => self.scale = Some(scale),
+                _ => warn_invalid(value, source),
+            },
+            "display" => match value.parse() {
+                Ok(display) => self.display = Some(display),
+                Err(_) => warn_invalid(value, source),
+            },
+            "fullscreen" => match (value, parse_bool(value)) {
+                ("exclusive", _) => self.fullscreen = Some(FullscreenMode::Exclusive),
+                (_, Some(true)) => self.fullscreen = Some(FullscreenMode::Desktop),
+                (_, Some(false)) => self.fullscreen = Some(FullscreenMode::Off),
+                (_, None) => warn_invalid(value, source),
+            },
+            "borderless" => match parse_bool(value) {
+                Some(borderless) => self.borderless = Some(borderless),
+                None => warn_invalid(value, source),
+            },
+            "position" => match value
+                .split_once(',')
+                .and_then(|(x, y)| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+            {
+                Some(position) => self.position = Some(position),
+                None => warn_invalid(value, source),
+            },
+            "vsync" => match parse_bool(value) {
+                Some(vsync) => self.vsync = Some(vsync),
+                None => warn_invalid(value, source),
+            },
+            "record-input" if !value.is_empty() => self.record_input = Some(value.to_string()),
+            _ => warn_invalid(value, source),
+        }
+    }
+
+    pub fn configure(&self, mut builder: PlatformBuilder) -> PlatformBuilder {
+        if self.scale.is_some() {
+            builder = builder.scale(self.scale);
+        }
+        if let Some(mode) = self.fullscreen {
+            builder = builder.fullscreen(mode);
+        }
+        if let Some(is_borderless) = self.borderless {
+            builder = builder.borderless(is_borderless);
+        }
+        if self.display.is_some() {
+            builder = builder.display(self.display);
+        }
+        if self.position.is_some() {
+            builder = builder.position(self.position);
+        }
+        if let Some(is_vsync) = self.vsync {
+            builder = builder.vsync(is_vsync);
+        }
+        builder
+    }
+
+    pub fn help() -> String {
+        let flag_width = OPTION_HELP
+            .iter()
+            .map(|(flag, _, _)| flag.len())
+            .max()
+            .unwrap();
+        let env_width = OPTION_HELP
+            .iter()
+            .map(|(_, env, _)| env.len())
+            .max()
+            .unwrap();
+        OPTION_HELP
+            .iter()
+            .map(|(flag, env, description)| {
+                format!(
+                    "  {:flag_width$}  {:env_width$}  {}\n",
+                    flag,
+                    env,
+                    description,
+                    flag_width = flag_width,
+                    env_width = env_width
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str], env: &[(&str, &str)]) -> SystemOptions {
+        SystemOptions::parse(args.iter().map(ToString::to_string), |name| {
+            env.iter()
+                .find(|(env_name, _)| *env_name == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn parse_args() {
+        assert_eq!(parse(&[], &[]), SystemOptions::default());
+        assert_eq!(
+            parse(
+                &[
+                    "level2",
+                    "--scale=3",
+                    "--fullscreen",
+                    "--borderless",
+                    "--display=1",
+                    "--position=-10,20",
+                    "--no-vsync",
+                    "--record-input=play.pyxrec",
+                    "--god-mode",
+                    "--lives=9",
+                    "-v",
+                ],
+                &[]
+            ),
+            SystemOptions {
+                scale: Some(3),
+                fullscreen: Some(FullscreenMode::Desktop),
+                borderless: Some(true),
+                display: Some(1),
+                position: Some((-10, 20)),
+                vsync: Some(false),
+                record_input: Some("play.pyxrec".to_string()),
+            }
+        );
+        assert_eq!(
+            parse(&["--scale=0", "--scale=x", "--display", "--scale=2"], &[]).scale,
+            Some(2)
+        );
+        assert_eq!(
+            parse(&["--fullscreen=exclusive", "--position=1"], &[]),
+            SystemOptions {
+                fullscreen: Some(FullscreenMode::Exclusive),
+                ..SystemOptions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_env() {
+        let env = [
+            ("PYXEL_SCALE", "4"),
+            ("PYXEL_FULLSCREEN", "yes"),
+            ("PYXEL_VSYNC", "maybe"),
+            ("PYXEL_RECORD_INPUT", "env.pyxrec"),
+        ];
+        let options = parse(&[], &env);
+        assert_eq!(options.scale, Some(4));
+        assert_eq!(options.fullscreen, Some(FullscreenMode::Desktop));
+        assert_eq!(options.vsync, None);
+        assert_eq!(options.record_input.as_deref(), Some("env.pyxrec"));
+
+        let options = parse(&["--windowed", "--scale=2"], &env);
+        assert_eq!(options.scale, Some(2));
+        assert_eq!(options.fullscreen, Some(FullscreenMode::Off));
+
+        let builder = options.configure(PlatformBuilder::new().vsync(false));
+        assert_eq!(builder.scale, Some(2));
+        assert_eq!(builder.fullscreen, FullscreenMode::Off);
+        assert!(!builder.is_vsync);
+    }
+
+    #[test]
+    fn help() {
+        let help = SystemOptions::help();
+        assert_eq!(help.lines().count(), OPTION_HELP.len());
+        assert!(help.contains("  --fullscreen=exclusive  PYXEL_FULLSCREEN=exclusive  "));
+        assert!(help.contains("  --scale=N               PYXEL_SCALE=N               "));
+    }
+}
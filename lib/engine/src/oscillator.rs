@@ -1,131 +1,179 @@
+use std::f64::consts::TAU;
+
 use blip_buf::BlipBuf;
 
+use crate::envelope::{Envelope, EnvelopeGenerator};
 use crate::settings::{
-    CLOCK_RATE, EFFECT_FADEOUT, EFFECT_NONE, EFFECT_SLIDE, EFFECT_VIBRATO, NOISE_VOLUME_FACTOR,
-    NUM_CLOCKS_PER_TICK, OSCILLATOR_RESOLUTION, PULSE_VOLUME_FACTOR, SQUARE_VOLUME_FACTOR,
-    TONE_NOISE, TONE_PULSE, TONE_SQUARE, TONE_TRIANGLE, TRIANGLE_VOLUME_FACTOR, VIBRATO_DEPTH,
-    VIBRATO_FREQUENCY,
+    CLOCK_RATE, EFFECT_FADEOUT, EFFECT_NONE, EFFECT_SLIDE, EFFECT_VIBRATO,
+    NARROW_PULSE_VOLUME_FACTOR, NOISE_VOLUME_FACTOR, NUM_CLOCKS_PER_TICK, NUM_STOP_RAMP_STEPS,
+    OSCILLATOR_RESOLUTION, PULSE_VOLUME_FACTOR, SAWTOOTH_VOLUME_FACTOR, SQUARE_VOLUME_FACTOR,
+    STOP_RAMP_CLOCKS, TONE_NARROW_PULSE, TONE_NOISE, TONE_PULSE, TONE_SAWTOOTH, TONE_SHORT_NOISE,
+    TONE_SQUARE, TONE_TRIANGLE, TRIANGLE_VOLUME_FACTOR, VIBRATO_DEPTH, VIBRATO_FREQUENCY,
+    VOLUME_RAMP_CLOCKS,
 };
 use crate::types::{Effect, Tone};
 
-const VIBRATO_PERIOD: u32 =
-    (CLOCK_RATE as f64 / VIBRATO_FREQUENCY / OSCILLATOR_RESOLUTION as f64) as u32;
-
-struct Slide {
-    pitch: f64,
-}
-
-struct Vibrato {
-    time: u32,
-    phase: u32,
-}
-
-struct FadeOut {
-    volume: f64,
-}
+const LONG_NOISE_TAP: u32 = 1;
+const SHORT_NOISE_TAP: u32 = 6;
 
 pub struct Oscillator {
     pitch: f64,
+    start_pitch: f64,
+    current_pitch: f64,
     tone: Tone,
     volume: f64,
+    gain: f64,
     effect: Effect,
+    vibrato_depth: f64,
+    pitch_scale: f64,
     duration: u32,
+    num_note_clocks: u32,
+    note_time: u32,
     time: u32,
     phase: u32,
     amplitude: i16,
-    noise: u32,
-    slide: Slide,
-    vibrato: Vibrato,
-    fadeout: FadeOut,
+    noise: u16,
+    envelope: EnvelopeGenerator,
 }
 
 impl Oscillator {
     pub fn new() -> Self {
+        let pitch = Self::note_to_pitch(0.0);
         Self {
-            pitch: Self::note_to_pitch(0.0),
+            pitch,
+            start_pitch: pitch,
+            current_pitch: pitch,
             tone: TONE_TRIANGLE,
             volume: 0.0,
+            gain: 1.0,
             effect: EFFECT_NONE,
+            vibrato_depth: VIBRATO_DEPTH,
+            pitch_scale: 1.0,
             duration: 0,
+            num_note_clocks: 0,
+            note_time: 0,
             time: 0,
             phase: 0,
             amplitude: 0,
-            noise: 0x8000,
-            slide: Slide { pitch: 0.0 },
-            vibrato: Vibrato { time: 0, phase: 0 },
-            fadeout: FadeOut { volume: 0.0 },
+            noise: 1,
+            envelope: EnvelopeGenerator::new(),
+        }
+    }
+
+    pub fn set_vibrato_depth(&mut self, depth: f64) {
+        self.vibrato_depth = depth;
+    }
+
+    pub fn set_pitch_scale(&mut self, scale: f64) {
+        self.pitch_scale = scale;
+    }
+
+    pub fn scale_duration(&mut self, scale: f64) {
+        if self.duration == 0 {
+            return;
         }
+        // Scaling the elapsed time too keeps effects at the same progress through the note
+        self.duration = ((self.duration as f64 * scale).round() as u32).max(1);
+        self.note_time = (self.note_time as f64 * scale) as u32;
+        self.num_note_clocks = ((self.num_note_clocks as f64 * scale) as u32).max(1);
     }
 
-    pub fn play(&mut self, note: f64, tone: Tone, volume: f64, effect: Effect, duration: u32) {
-        let last_pitch = self.pitch;
+    pub fn play(
+        &mut self,
+        note: f64,
+        tone: Tone,
+        volume: f64,
+        effect: Effect,
+        duration: u32,
+        envelope: Envelope,
+    ) {
+        self.envelope.note_on(envelope);
+        // A slide starts from wherever the previous note's pitch ended up
+        self.start_pitch = self.current_pitch;
         self.pitch = Self::note_to_pitch(note);
         self.tone = tone;
         self.volume = volume;
         self.effect = effect;
         self.duration = duration;
-        if effect == EFFECT_SLIDE {
-            self.slide.pitch = (self.pitch - last_pitch) / self.duration as f64;
-            self.pitch = last_pitch;
-        } else if effect == EFFECT_FADEOUT {
-            self.fadeout.volume = -self.volume / self.duration as f64;
-        }
+        self.num_note_clocks = duration * NUM_CLOCKS_PER_TICK;
+        self.note_time = 0;
     }
 
     pub fn stop(&mut self) {
         self.duration = 0;
     }
 
-    pub fn update(&mut self, blip_buf: &mut BlipBuf) {
+    pub fn update(&mut self, blip_buf: &mut BlipBuf, gain: f64) {
         if self.duration == 0 {
+            self.envelope.note_off();
+        }
+        if self.duration == 0 && !self.envelope.is_active() {
+            self.gain = gain;
             if self.amplitude != 0 {
-                blip_buf.add_delta(0, -(self.amplitude as i32));
+                // Ramp down to silence instead of stepping to avoid a click
+                let amplitude = self.amplitude as i32;
+                let steps = NUM_STOP_RAMP_STEPS as i32;
+                for i in 0..steps {
+                    let delta =
+                        amplitude * (steps - i - 1) / steps - amplitude * (steps - i) / steps;
+                    blip_buf.add_delta(i as u32 * STOP_RAMP_CLOCKS / NUM_STOP_RAMP_STEPS, delta);
+                }
             }
             self.time = 0;
             self.amplitude = 0;
             return;
         }
-        let pitch = self.pitch
-            + if self.effect == EFFECT_VIBRATO {
-                self.pitch * Self::triangle(self.vibrato.phase) * VIBRATO_DEPTH
-            } else {
-                0.0
-            };
-        let period = (CLOCK_RATE as f64 / pitch / OSCILLATOR_RESOLUTION as f64) as u32;
 
         while self.time < NUM_CLOCKS_PER_TICK {
+            // Effects follow the oscillator clock rather than the tick rate
+            let note_time = self.note_time + self.time;
+            let progress = (note_time as f64 / self.num_note_clocks.max(1) as f64).min(1.0);
+            let (pitch, volume) = match self.effect {
+                EFFECT_NONE => (self.pitch, self.volume),
+                EFFECT_SLIDE => (
+                    self.start_pitch + (self.pitch - self.start_pitch) * progress,
+                    self.volume,
+                ),
+                EFFECT_VIBRATO => {
+                    let angle = note_time as f64 * VIBRATO_FREQUENCY / CLOCK_RATE as f64 * TAU;
+                    (
+                        self.pitch * (1.0 + angle.sin() * self.vibrato_depth),
+                        self.volume,
+                    )
+                }
+                EFFECT_FADEOUT => (self.pitch, self.volume * (1.0 - progress)),
+                _ => panic!("Invalid effect '{}'", self.effect),
+            };
+            self.current_pitch = pitch;
+            // The phase is kept across pitch changes, so bending the pitch never jumps
+            let pitch = pitch * self.pitch_scale;
+            let period = ((CLOCK_RATE as f64 / pitch / OSCILLATOR_RESOLUTION as f64) as u32).max(1);
+            let gain_step = period as f64 / VOLUME_RAMP_CLOCKS as f64;
+
             let last_amplitude = self.amplitude;
             self.phase = (self.phase + 1) % OSCILLATOR_RESOLUTION;
+            self.gain += (gain - self.gain).clamp(-gain_step, gain_step);
+            let level = self.envelope.advance(period);
             self.amplitude = (match self.tone {
                 TONE_TRIANGLE => Self::triangle(self.phase) * TRIANGLE_VOLUME_FACTOR,
                 TONE_SQUARE => Self::square(self.phase) * SQUARE_VOLUME_FACTOR,
-                TONE_PULSE => Self::pulse(self.phase) * PULSE_VOLUME_FACTOR,
-                TONE_NOISE => self.noise(self.phase) * NOISE_VOLUME_FACTOR,
+                TONE_PULSE => Self::pulse(self.phase, 4) * PULSE_VOLUME_FACTOR,
+                TONE_NOISE => self.noise(self.phase, LONG_NOISE_TAP) * NOISE_VOLUME_FACTOR,
+                TONE_SAWTOOTH => Self::sawtooth(self.phase) * SAWTOOTH_VOLUME_FACTOR,
+                TONE_NARROW_PULSE => Self::pulse(self.phase, 8) * NARROW_PULSE_VOLUME_FACTOR,
+                TONE_SHORT_NOISE => self.noise(self.phase, SHORT_NOISE_TAP) * NOISE_VOLUME_FACTOR,
                 _ => panic!("Invalid tone '{}'", self.tone),
-            } * self.volume
+            } * volume
+                * self.gain
+                * level
                 * i16::MAX as f64) as i16;
             blip_buf.add_delta(self.time, self.amplitude as i32 - last_amplitude as i32);
             self.time += period;
         }
 
-        match self.effect {
-            EFFECT_NONE => {}
-            EFFECT_SLIDE => {
-                self.pitch += self.slide.pitch;
-            }
-            EFFECT_VIBRATO => {
-                self.vibrato.time += NUM_CLOCKS_PER_TICK;
-                self.vibrato.phase = (self.vibrato.phase + self.vibrato.time / VIBRATO_PERIOD)
-                    % OSCILLATOR_RESOLUTION;
-                self.vibrato.time %= VIBRATO_PERIOD;
-            }
-            EFFECT_FADEOUT => {
-                self.volume += self.fadeout.volume;
-            }
-            _ => panic!("Invalid effect '{}'", self.effect),
-        }
-
-        self.duration -= 1;
+        // The release tail keeps running the effects past the note end
+        self.note_time = self.note_time.saturating_add(NUM_CLOCKS_PER_TICK);
+        self.duration = self.duration.saturating_sub(1);
         self.time -= NUM_CLOCKS_PER_TICK;
     }
 
@@ -149,19 +197,267 @@ impl Oscillator {
         }
     }
 
-    fn pulse(phase: u32) -> f64 {
-        if phase < OSCILLATOR_RESOLUTION / 4 {
+    fn sawtooth(phase: u32) -> f64 {
+        phase as f64 / (OSCILLATOR_RESOLUTION / 2) as f64 - 1.0
+    }
+
+    fn pulse(phase: u32, duty_divisor: u32) -> f64 {
+        if phase < OSCILLATOR_RESOLUTION / duty_divisor {
             1.0
         } else {
             -1.0
         }
     }
 
-    fn noise(&mut self, phase: u32) -> f64 {
+    fn noise(&mut self, phase: u32, tap: u32) -> f64 {
+        // 15-bit LFSR as in the NES APU, clocked at a rate set by the pitch
         if phase % 8 == 0 {
-            self.noise >>= 1;
-            self.noise |= ((self.noise ^ (self.noise >> 1)) & 1) << 15;
+            let feedback = (self.noise ^ (self.noise >> tap)) & 1;
+            self.noise = (self.noise >> 1) | (feedback << 14);
         }
-        (self.noise & 1) as f64 * 2.0 - 1.0
+        if self.noise & 1 == 0 {
+            1.0
+        } else {
+            -1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::SAMPLE_RATE;
+
+    fn render(tone: Tone, note: f64) -> Vec<i16> {
+        let num_ticks = CLOCK_RATE / NUM_CLOCKS_PER_TICK;
+        render_note(tone, note, Envelope::default(), num_ticks, num_ticks)
+    }
+
+    fn render_note(
+        tone: Tone,
+        note: f64,
+        envelope: Envelope,
+        duration: u32,
+        num_ticks: u32,
+    ) -> Vec<i16> {
+        let mut blip_buf = new_blip_buf();
+        let mut oscillator = Oscillator::new();
+        oscillator.play(note, tone, 1.0, EFFECT_NONE, duration, envelope);
+        render_ticks(&mut oscillator, &mut blip_buf, num_ticks)
+    }
+
+    fn new_blip_buf() -> BlipBuf {
+        let mut blip_buf = BlipBuf::new(SAMPLE_RATE);
+        blip_buf.set_rates(CLOCK_RATE as f64, SAMPLE_RATE as f64);
+        blip_buf
+    }
+
+    fn render_ticks(
+        oscillator: &mut Oscillator,
+        blip_buf: &mut BlipBuf,
+        num_ticks: u32,
+    ) -> Vec<i16> {
+        let mut samples = vec![0; SAMPLE_RATE as usize];
+        let mut num_samples = 0;
+        for _ in 0..num_ticks {
+            oscillator.update(blip_buf, 1.0);
+            blip_buf.end_frame(NUM_CLOCKS_PER_TICK);
+            num_samples += blip_buf.read_samples(&mut samples[num_samples..], false);
+        }
+        samples.truncate(num_samples);
+        samples
+    }
+
+    fn frequency_at(samples: &[i16], ms: usize) -> f64 {
+        let center = ms * SAMPLE_RATE as usize / 1000;
+        let window = SAMPLE_RATE as usize / 40;
+        num_crossings(&samples[center - window / 2..center + window / 2]) as f64 * 20.0
+    }
+
+    fn num_crossings(samples: &[i16]) -> usize {
+        // Hysteresis keeps ringing around zero from counting as extra crossings
+        let mut is_positive = samples[0] > 0;
+        let mut crossings = 0;
+        for &sample in samples {
+            if is_positive && sample < -1000 || !is_positive && sample > 1000 {
+                is_positive = !is_positive;
+                crossings += 1;
+            }
+        }
+        crossings
+    }
+
+    fn positive_ratio(samples: &[i16]) -> f64 {
+        samples.iter().filter(|sample| **sample > 0).count() as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn waveforms() {
+        let period = CLOCK_RATE / 440 / OSCILLATOR_RESOLUTION;
+        let expected = 2.0 * CLOCK_RATE as f64 / (period * OSCILLATOR_RESOLUTION) as f64;
+        for tone in [
+            TONE_TRIANGLE,
+            TONE_SQUARE,
+            TONE_PULSE,
+            TONE_SAWTOOTH,
+            TONE_NARROW_PULSE,
+        ] {
+            let samples = render(tone, 33.0);
+            let crossings = num_crossings(&samples) as f64;
+            assert!(
+                (crossings - expected).abs() <= 3.0,
+                "{} {}",
+                tone,
+                crossings
+            );
+        }
+        for (tone, duty) in [
+            (TONE_SQUARE, 0.5),
+            (TONE_PULSE, 0.25),
+            (TONE_NARROW_PULSE, 0.125),
+        ] {
+            let ratio = positive_ratio(&render(tone, 33.0));
+            assert!((ratio - duty).abs() < 0.02, "{} {}", tone, ratio);
+        }
+    }
+
+    #[test]
+    fn noise() {
+        let noise_period = |tap| {
+            let mut oscillator = Oscillator::new();
+            (1..)
+                .find(|_| {
+                    oscillator.noise(0, tap);
+                    oscillator.noise == 1
+                })
+                .unwrap()
+        };
+        assert_eq!(noise_period(LONG_NOISE_TAP), 32767);
+        assert_eq!(noise_period(SHORT_NOISE_TAP), 93);
+
+        // One octave up doubles the LFSR clock and so the rate of transitions
+        for tone in [TONE_NOISE, TONE_SHORT_NOISE] {
+            let low = num_crossings(&render(tone, 33.0)) as f64;
+            let high = num_crossings(&render(tone, 45.0)) as f64;
+            assert!((high / low - 2.0).abs() < 0.25, "{} {} {}", tone, low, high);
+        }
+    }
+
+    #[test]
+    fn envelope() {
+        let envelope = Envelope {
+            attack: 20,
+            decay: 20,
+            sustain: 0.5,
+            release: 40,
+        };
+        let samples = render_note(TONE_TRIANGLE, 33.0, envelope, 12, 24);
+        let level_at = |ms: usize| {
+            let center = ms * SAMPLE_RATE as usize / 1000;
+            let peak = samples[center - 50..center + 50]
+                .iter()
+                .map(|sample| (*sample as i32).abs())
+                .max()
+                .unwrap();
+            peak as f64 / i16::MAX as f64
+        };
+
+        // The note lasts 12 ticks (100 ms) and then releases over 40 ms
+        for (ms, level) in [
+            (10, 0.5),
+            (30, 0.75),
+            (60, 0.5),
+            (90, 0.5),
+            (110, 0.375),
+            (120, 0.25),
+            (130, 0.125),
+            (150, 0.0),
+        ] {
+            assert!(
+                (level_at(ms) - level).abs() < 0.05,
+                "{} ms {}",
+                ms,
+                level_at(ms)
+            );
+        }
+
+        let samples = render_note(TONE_TRIANGLE, 33.0, Envelope::default(), 12, 24);
+        let offset = SAMPLE_RATE as usize / 10;
+        assert!(samples[..offset].iter().any(|sample| *sample > 32000));
+        assert!(samples[offset + 100..]
+            .iter()
+            .all(|sample| (*sample as i32).abs() < 1500));
+    }
+
+    #[test]
+    fn slide() {
+        let mut blip_buf = new_blip_buf();
+        let mut oscillator = Oscillator::new();
+        oscillator.play(33.0, TONE_SQUARE, 1.0, EFFECT_NONE, 12, Envelope::default());
+        let samples = render_ticks(&mut oscillator, &mut blip_buf, 12);
+        assert!((frequency_at(&samples, 50) - 440.0).abs() < 30.0);
+
+        // Sliding from A2 to A3 over half a second rises linearly in frequency
+        oscillator.play(
+            45.0,
+            TONE_SQUARE,
+            1.0,
+            EFFECT_SLIDE,
+            60,
+            Envelope::default(),
+        );
+        let samples = render_ticks(&mut oscillator, &mut blip_buf, 72);
+        for (ms, frequency) in [(25, 462.0), (250, 660.0), (475, 858.0)] {
+            let measured = frequency_at(&samples, ms);
+            assert!(
+                (measured - frequency).abs() < 30.0,
+                "{} ms {}",
+                ms,
+                measured
+            );
+        }
+    }
+
+    #[test]
+    fn vibrato_and_fadeout() {
+        let mut blip_buf = new_blip_buf();
+        let mut oscillator = Oscillator::new();
+        oscillator.set_vibrato_depth(0.1);
+        oscillator.play(
+            45.0,
+            TONE_SQUARE,
+            1.0,
+            EFFECT_VIBRATO,
+            120,
+            Envelope::default(),
+        );
+        let samples = render_ticks(&mut oscillator, &mut blip_buf, 120);
+        // A 6 Hz vibrato peaks a quarter of a cycle in and bottoms out at three quarters
+        assert!((frequency_at(&samples, 42) - 968.0).abs() < 30.0);
+        assert!((frequency_at(&samples, 125) - 792.0).abs() < 30.0);
+        assert!((frequency_at(&samples, 500) - 880.0).abs() < 60.0);
+
+        let mut oscillator = Oscillator::new();
+        oscillator.play(
+            33.0,
+            TONE_TRIANGLE,
+            1.0,
+            EFFECT_FADEOUT,
+            60,
+            Envelope::default(),
+        );
+        let samples = render_ticks(&mut oscillator, &mut blip_buf, 60);
+        let peak_at = |ms: usize| {
+            let center = ms * SAMPLE_RATE as usize / 1000;
+            samples[center - 50..center + 50]
+                .iter()
+                .map(|sample| (*sample as i32).abs())
+                .max()
+                .unwrap() as f64
+                / i16::MAX as f64
+        };
+        assert!((peak_at(10) - 0.98).abs() < 0.05);
+        assert!((peak_at(250) - 0.5).abs() < 0.05);
+        assert!(peak_at(490) < 0.05);
     }
 }
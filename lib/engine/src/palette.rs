@@ -0,0 +1,260 @@
+use std::cmp::min;
+use std::ops::Range;
+
+use array_macro::array;
+
+use crate::color::{blue, green, lerp, red, rgb};
+use crate::settings::{DEFAULT_COLORS, MAX_COLORS};
+use crate::types::{Color, Rgb8};
+
+#[derive(Clone)]
+pub struct Palette {
+    colors: Vec<Rgb8>,
+    display_indices: [Color; MAX_COLORS as usize],
+    display_colors: [Rgb8; MAX_COLORS as usize],
+    brightness: f64,
+    tint: Rgb8,
+    tint_strength: f64,
+}
+
+fn adjust_color(color: Rgb8, brightness: f64, tint: Rgb8, tint_strength: f64) -> Rgb8 {
+    let scale = |value: u8| (value as f64 * brightness).round().min(255.0) as u8;
+    let color = rgb(scale(red(color)), scale(green(color)), scale(blue(color)));
+    lerp(color, tint, tint_strength)
+}
+
+impl Palette {
+    pub fn new(colors: &[Rgb8]) -> Self {
+        let mut palette = Self {
+            colors: Vec::new(),
+            display_indices: array![i => i as Color; MAX_COLORS as usize],
+            display_colors: [0; MAX_COLORS as usize],
+            brightness: 1.0,
+            tint: 0,
+            tint_strength: 0.0,
+        };
+        palette.set_colors(colors);
+        palette
+    }
+
+    pub fn num_colors(&self) -> u32 {
+        self.colors.len() as u32
+    }
+
+    pub fn colors(&self) -> &[Rgb8] {
+        &self.colors
+    }
+
+    pub fn set_colors(&mut self, colors: &[Rgb8]) {
+        assert!(!colors.is_empty() && colors.len() <= MAX_COLORS as usize);
+        self.colors = colors.to_vec();
+        self.update_display_colors();
+    }
+
+    pub fn color(&self, color: Color) -> Rgb8 {
+        self.colors[min(color as usize, self.colors.len() - 1)]
+    }
+
+    pub fn set_color(&mut self, color: Color, rgb: Rgb8) {
+        assert!((color as usize) < self.colors.len());
+        self.colors[color as usize] = rgb;
+        self.update_display_colors();
+    }
+
+    pub fn gradient(&mut self, start: Color, from: Rgb8, to: Rgb8, steps: usize) {
+        // Steps past the end of the palette are dropped, so the range never grows it
+        let start = start as usize;
+        let end = min(start.saturating_add(steps), self.colors.len());
+        if start >= end {
+            return;
+        }
+        for (i, color) in self.colors[start..end].iter_mut().enumerate() {
+            *color = lerp(from, to, i as f64 / (steps.max(2) - 1) as f64);
+        }
+        self.update_display_colors();
+    }
+
+    pub fn display_color(&self, color: Color) -> Rgb8 {
+        self.display_colors[color as usize]
+    }
+
+    pub fn display_colors(&self) -> &[Rgb8; MAX_COLORS as usize] {
+        &self.display_colors
+    }
+
+    pub fn cycle(&mut self, range: Range<Color>, shift: i32) {
+        let start = range.start as usize;
+        let end = range.end as usize;
+        if start >= end {
+            return;
+        }
+        let shift = shift.rem_euclid((end - start) as i32) as usize;
+        self.display_indices[start..end].rotate_right(shift);
+        self.update_display_colors();
+    }
+
+    pub fn set_display_brightness(&mut self, factor: f64) {
+        self.brightness = factor.max(0.0);
+        self.update_display_colors();
+    }
+
+    pub fn set_display_tint(&mut self, color: Rgb8, strength: f64) {
+        self.tint = color;
+        self.tint_strength = strength.clamp(0.0, 1.0);
+        self.update_display_colors();
+    }
+
+    pub fn display_tint(&self) -> (Rgb8, f64) {
+        (self.tint, self.tint_strength)
+    }
+
+    pub fn reset_display_colors(&mut self) {
+        for i in 0..MAX_COLORS {
+            self.display_indices[i as usize] = i as Color;
+        }
+        self.update_display_colors();
+    }
+
+    fn update_display_colors(&mut self) {
+        // Image indices are not checked against the palette on write, so indices
+        // past the end of the palette are clamped to its last color on display
+        let is_adjusted = self.brightness != 1.0 || self.tint_strength != 0.0;
+        for i in 0..MAX_COLORS as usize {
+            let color = self.color(self.display_indices[i]);
+            self.display_colors[i] = if is_adjusted {
+                adjust_color(color, self.brightness, self.tint, self.tint_strength)
+            } else {
+                color
+            };
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new(&DEFAULT_COLORS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        let palette = Palette::default();
+        assert_eq!(palette.num_colors(), 16);
+        assert_eq!(palette.colors(), &DEFAULT_COLORS);
+        assert_eq!(palette.display_color(7), DEFAULT_COLORS[7]);
+        assert_eq!(palette.display_color(16), DEFAULT_COLORS[15]);
+        assert_eq!(palette.display_color(255), DEFAULT_COLORS[15]);
+
+        let colors: Vec<Rgb8> = (0..64).map(|i| i * 0x040404).collect();
+        let palette = Palette::new(&colors);
+        assert_eq!(palette.num_colors(), 64);
+        assert_eq!(palette.display_color(63), 0xfcfcfc);
+        assert_eq!(palette.display_color(64), 0xfcfcfc);
+    }
+
+    #[test]
+    fn set_color() {
+        let mut palette = Palette::new(&[0x000000, 0x111111, 0x222222]);
+        palette.set_color(1, 0xabcdef);
+        assert_eq!(palette.color(1), 0xabcdef);
+        assert_eq!(palette.display_color(1), 0xabcdef);
+        palette.set_color(2, 0x123456);
+        assert_eq!(palette.display_color(200), 0x123456);
+
+        palette.set_colors(&[0xffffff; 256]);
+        assert_eq!(palette.num_colors(), 256);
+        assert_eq!(palette.display_color(255), 0xffffff);
+    }
+
+    #[test]
+    fn gradient() {
+        let mut palette = Palette::new(&[0x111111; 7]);
+        palette.gradient(1, 0x000000, 0xff8040, 5);
+        assert_eq!(
+            palette.colors(),
+            &[0x111111, 0x000000, 0x402010, 0x804020, 0xbf6030, 0xff8040, 0x111111]
+        );
+        assert_eq!(palette.display_color(5), 0xff8040);
+
+        palette.gradient(6, 0x123456, 0xffffff, 1);
+        assert_eq!(palette.color(6), 0x123456);
+        palette.gradient(0, 0xffffff, 0xffffff, 0);
+        assert_eq!(palette.color(0), 0x111111);
+        palette.gradient(5, 0x000000, 0xffffff, 3);
+        assert_eq!(&palette.colors()[5..], &[0x000000, 0x808080]);
+
+        let mut palette = Palette::new(&[0; 256]);
+        palette.gradient(0, 0, 0xffffff, 256);
+        assert_eq!(palette.color(255), 0xffffff);
+    }
+
+    #[test]
+    fn cycle() {
+        let mut palette = Palette::new(&[0, 1, 2, 3, 4, 5]);
+        let display_colors =
+            |palette: &Palette| -> Vec<Rgb8> { (0..6).map(|i| palette.display_color(i)).collect() };
+        palette.cycle(1..5, 1);
+        assert_eq!(display_colors(&palette), vec![0, 4, 1, 2, 3, 5]);
+        palette.cycle(1..5, 1);
+        palette.cycle(1..5, 1);
+        assert_eq!(display_colors(&palette), vec![0, 2, 3, 4, 1, 5]);
+        assert_eq!(palette.colors(), &[0, 1, 2, 3, 4, 5]);
+
+        palette.cycle(1..5, 1);
+        assert_eq!(display_colors(&palette), vec![0, 1, 2, 3, 4, 5]);
+        palette.cycle(1..5, 4);
+        assert_eq!(display_colors(&palette), vec![0, 1, 2, 3, 4, 5]);
+        palette.cycle(1..5, -1);
+        assert_eq!(display_colors(&palette), vec![0, 2, 3, 4, 1, 5]);
+
+        palette.set_color(4, 0x40);
+        assert_eq!(display_colors(&palette), vec![0, 2, 3, 0x40, 1, 5]);
+        palette.reset_display_colors();
+        assert_eq!(display_colors(&palette), vec![0, 1, 2, 3, 0x40, 5]);
+    }
+
+    #[test]
+    fn adjust_color_() {
+        for color in [0x000000, 0x123456, 0x808080, 0xffffff] {
+            assert_eq!(adjust_color(color, 1.0, 0xff0000, 0.0), color);
+        }
+        assert_eq!(adjust_color(0x123456, 0.0, 0, 0.0), 0x000000);
+        assert_eq!(adjust_color(0x204080, 0.5, 0, 0.0), 0x102040);
+        assert_eq!(adjust_color(0x204080, 2.0, 0, 0.0), 0x4080ff);
+        assert_eq!(adjust_color(0x204080, 100.0, 0, 0.0), 0xffffff);
+        assert_eq!(adjust_color(0x123456, 1.0, 0xff0000, 1.0), 0xff0000);
+        assert_eq!(adjust_color(0x000000, 1.0, 0xff8000, 0.5), 0x804000);
+        assert_eq!(adjust_color(0xffffff, 0.0, 0x0000ff, 1.0), 0x0000ff);
+    }
+
+    #[test]
+    fn display_brightness() {
+        let mut palette = Palette::default();
+        palette.set_display_brightness(0.0);
+        assert_eq!(palette.display_color(7), 0x000000);
+        assert_eq!(palette.colors(), &DEFAULT_COLORS);
+        palette.set_display_brightness(-1.0);
+        assert_eq!(palette.display_color(7), 0x000000);
+        palette.set_display_brightness(10.0);
+        assert_eq!(palette.display_color(1), 0xffffff);
+        palette.set_display_tint(0x00ff00, 2.0);
+        assert_eq!(palette.display_color(1), 0x00ff00);
+
+        palette.set_display_brightness(1.0);
+        palette.set_display_tint(0x00ff00, 0.0);
+        assert_eq!(palette.display_colors()[..16], DEFAULT_COLORS);
+        palette.cycle(0..2, 1);
+        palette.set_display_brightness(0.5);
+        assert_eq!(palette.display_color(0), 0x161a30);
+    }
+
+    #[test]
+    #[should_panic]
+    fn set_color_out_of_range() {
+        Palette::default().set_color(16, 0xffffff);
+    }
+}
@@ -1,28 +1,982 @@
-use crate::event::Event;
+use std::cmp::{max, min};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::audio_monitor::AudioMonitor;
+use crate::event::{Event, TimedEvent};
+use crate::geometry::Recti;
+use crate::image::Image;
+use crate::scanline::Scanlines;
+use crate::settings::{GRID_DARKNESS, MAX_COLORS, MAX_SAMPLE_RATE, MIN_SAMPLE_RATE};
 use crate::types::{Color, Rgb8};
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayEffect {
+    None,
+    Scanlines { darkness: u8 },
+    Grid,
+}
+
+impl DisplayEffect {
+    pub const fn scale(self) -> u32 {
+        match self {
+            Self::None => 1,
+            Self::Scanlines { .. } => 2,
+            Self::Grid => 3,
+        }
+    }
+
+    pub fn shade(self, color: Rgb8, sub_x: u32, sub_y: u32) -> Rgb8 {
+        let darkness = match self {
+            Self::None => 0,
+            Self::Scanlines { darkness } => {
+                if sub_y == 1 {
+                    darkness
+                } else {
+                    0
+                }
+            }
+            Self::Grid => {
+                if sub_x == 2 || sub_y == 2 {
+                    GRID_DARKNESS
+                } else {
+                    0
+                }
+            }
+        };
+        if darkness == 0 {
+            return color;
+        }
+        let brightness = 255 - darkness as u32;
+        let r = ((color >> 16) & 0xff) * brightness / 255;
+        let g = ((color >> 8) & 0xff) * brightness / 255;
+        let b = (color & 0xff) * brightness / 255;
+        (r << 16) | (g << 8) | b
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AuxWindowId(pub(crate) u32);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuxWindowEvent {
+    Moved { x: i32, y: i32 },
+    Resized { width: u32, height: u32 },
+    Focused(bool),
+    Closed,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScreenFit {
+    Integer,
+    Aspect,
+    FillIgnoreAspect,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScreenMode {
+    Nearest,
+    SharpBilinear,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScreenLayout {
+    pub rect: Recti,
+    screen_width: u32,
+    screen_height: u32,
+}
+
+impl ScreenLayout {
+    pub fn new(
+        fit: ScreenFit,
+        pixel_aspect: f64,
+        screen_width: u32,
+        screen_height: u32,
+        window_width: u32,
+        window_height: u32,
+        effect_scale: u32,
+    ) -> Self {
+        let pixel_width = screen_width as f64 * pixel_aspect;
+        let (width, height) = match fit {
+            ScreenFit::Integer => {
+                let mut scale = max(
+                    min(
+                        (window_width as f64 / pixel_width) as u32,
+                        window_height / screen_height,
+                    ),
+                    1,
+                );
+                if scale >= effect_scale {
+                    // Keep every effect row and column the same number of window pixels
+                    scale -= scale % effect_scale;
+                }
+                (
+                    max((pixel_width * scale as f64).round() as u32, 1),
+                    screen_height * scale,
+                )
+            }
+            ScreenFit::Aspect => {
+                // The limiting side takes the window size exactly, so no gap is left at its edges
+                if window_width as f64 * screen_height as f64 <= window_height as f64 * pixel_width
+                {
+                    let height = window_width as f64 * screen_height as f64 / pixel_width;
+                    (window_width, (height.round() as u32).min(window_height))
+                } else {
+                    let width = window_height as f64 * pixel_width / screen_height as f64;
+                    ((width.round() as u32).min(window_width), window_height)
+                }
+            }
+            ScreenFit::FillIgnoreAspect => (window_width, window_height),
+        };
+        let (width, height) = (max(width, 1), max(height, 1));
+        Self {
+            rect: Recti::new(
+                (window_width.saturating_sub(width) / 2) as i32,
+                (window_height.saturating_sub(height) / 2) as i32,
+                width,
+                height,
+            ),
+            screen_width,
+            screen_height,
+        }
+    }
+
+    pub fn window_to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        let x = ((x - self.rect.left()) as i64 * self.screen_width as i64)
+            .div_euclid(self.rect.width() as i64) as i32;
+        let y = ((y - self.rect.top()) as i64 * self.screen_height as i64)
+            .div_euclid(self.rect.height() as i64) as i32;
+        (
+            x.clamp(0, self.screen_width as i32 - 1),
+            y.clamp(0, self.screen_height as i32 - 1),
+        )
+    }
+
+    pub fn screen_to_window(&self, x: i32, y: i32) -> (i32, i32) {
+        // The first window pixel that maps back to the screen pixel
+        let x = -(-x as i64 * self.rect.width() as i64).div_euclid(self.screen_width as i64);
+        let y = -(-y as i64 * self.rect.height() as i64).div_euclid(self.screen_height as i64);
+        (self.rect.left() + x as i32, self.rect.top() + y as i32)
+    }
+
+    pub fn prescale(&self, texture_width: u32, texture_height: u32) -> Option<u32> {
+        // Integer multiples are already sharp with nearest filtering, so they skip the second pass
+        let (width, height) = (self.rect.width(), self.rect.height());
+        if width % texture_width == 0 && height % texture_height == 0 {
+            return None;
+        }
+        Some(max(
+            width.div_ceil(texture_width),
+            height.div_ceil(texture_height),
+        ))
+    }
+
+    pub fn screen_to_window_size(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            (width as u64 * self.rect.width() as u64 / self.screen_width as u64) as u32,
+            (height as u64 * self.rect.height() as u64 / self.screen_height as u64) as u32,
+        )
+    }
+}
+
+fn to_rgb_table(colors: &[Rgb8]) -> [[u8; 3]; MAX_COLORS as usize] {
+    let mut rgb_table = [[0; 3]; MAX_COLORS as usize];
+    for (rgb, color) in rgb_table.iter_mut().zip(colors) {
+        *rgb = [(color >> 16) as u8, (color >> 8) as u8, *color as u8];
+    }
+    rgb_table
+}
+
+pub struct RgbTables {
+    colors: [[u8; 3]; MAX_COLORS as usize],
+    palettes: Vec<[[u8; 3]; MAX_COLORS as usize]>,
+}
+
+impl RgbTables {
+    // Built once per frame and shared by every write_rgb24 call of that frame
+    pub fn new(colors: &[Rgb8], scanlines: Option<&Scanlines>) -> Self {
+        Self {
+            colors: to_rgb_table(colors),
+            palettes: scanlines.map_or_else(Vec::new, |scanlines| {
+                scanlines
+                    .palettes
+                    .iter()
+                    .map(|colors| to_rgb_table(colors))
+                    .collect()
+            }),
+        }
+    }
+}
+
+pub fn write_rgb24(
+    buffer: &mut [u8],
+    pitch: usize,
+    image: &[Vec<Color>],
+    rgb_tables: &RgbTables,
+    effect: DisplayEffect,
+    rect: Recti,
+    scanlines: Option<&Scanlines>,
+) {
+    let mut shifted_row = Vec::new();
+    let left = rect.left() as usize;
+    let top = rect.top() as usize;
+    let width = rect.width() as usize;
+    let height = rect.height() as usize;
+    let effect_scale = effect.scale() as usize;
+    for i in 0..height {
+        let (src_row, rgb_table) = scanlines.map_or_else(
+            || (&image[top + i][left..left + width], &rgb_tables.colors),
+            |scanlines| {
+                let params = scanlines.params(top + i);
+                params.shift_row(&mut shifted_row, &image[top + i], left, width);
+                let palette_table = params
+                    .palette
+                    .and_then(|palette| rgb_tables.palettes.get(palette as usize));
+                (
+                    &shifted_row[..],
+                    palette_table.unwrap_or(&rgb_tables.colors),
+                )
+            },
+        );
+        if effect == DisplayEffect::None {
+            let dst_row = &mut buffer[i * pitch..i * pitch + width * 3];
+            for (dst, &color) in dst_row.chunks_exact_mut(3).zip(src_row) {
+                dst.copy_from_slice(&rgb_table[color as usize]);
+            }
+            continue;
+        }
+        for sub_y in 0..effect_scale {
+            let offset = (i * effect_scale + sub_y) * pitch;
+            let dst_row = &mut buffer[offset..offset + width * effect_scale * 3];
+            for (j, dst) in dst_row.chunks_exact_mut(3).enumerate() {
+                let [r, g, b] = rgb_table[src_row[j / effect_scale] as usize];
+                let color = effect.shade(
+                    ((r as Rgb8) << 16) | ((g as Rgb8) << 8) | b as Rgb8,
+                    (j % effect_scale) as u32,
+                    sub_y as u32,
+                );
+                dst.copy_from_slice(&[(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+            }
+        }
+    }
+}
+
+pub fn blend_overlay(
+    buffer: &mut [u8],
+    pitch: usize,
+    overlay: &[u8],
+    width: u32,
+    height: u32,
+    effect_scale: u32,
+) {
+    let width = width as usize;
+    let effect_scale = effect_scale as usize;
+    for i in 0..height as usize * effect_scale {
+        let src_row = &overlay[i / effect_scale * width * 4..(i / effect_scale + 1) * width * 4];
+        let dst_row = &mut buffer[i * pitch..i * pitch + width * effect_scale * 3];
+        for (j, dst) in dst_row.chunks_exact_mut(3).enumerate() {
+            let src = &src_row[j / effect_scale * 4..j / effect_scale * 4 + 4];
+            let alpha = src[3] as u32;
+            if alpha == 0 {
+                continue;
+            }
+            for k in 0..3 {
+                dst[k] =
+                    ((src[k] as u32 * alpha + dst[k] as u32 * (255 - alpha) + 127) / 255) as u8;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+    AlreadyStarted,
+    NotStarted,
+    NotCapturing,
+    Open(String),
+    ReadFile(String),
+    WriteFile(String),
+    InvalidWav(String),
+    InvalidChannel(u32),
+    InvalidSampleRate(u32),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::AlreadyStarted => write!(f, "Audio device is already started"),
+            Self::NotStarted => write!(f, "Audio device is not started"),
+            Self::NotCapturing => write!(f, "Audio capture is not started"),
+            Self::Open(err) => write!(f, "Unable to open audio device: {}", err),
+            Self::ReadFile(filename) => write!(f, "Unable to open file '{}'", filename),
+            Self::WriteFile(filename) => write!(f, "Unable to write file '{}'", filename),
+            Self::InvalidWav(err) => write!(f, "Invalid WAV file: {}", err),
+            Self::InvalidChannel(channel_no) => write!(f, "Invalid audio channel {}", channel_no),
+            Self::InvalidSampleRate(sample_rate) => write!(
+                f,
+                "Sample rate {} is outside {}..={}",
+                sample_rate, MIN_SAMPLE_RATE, MAX_SAMPLE_RATE
+            ),
+        }
+    }
+}
+
 pub trait AudioCallback {
+    fn set_sample_rate(&mut self, sample_rate: u32);
+    fn set_num_channels(&mut self, num_channels: u32);
     fn update(&mut self, out: &mut [i16]);
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FullscreenMode {
+    Off,
+    Desktop,
+    Exclusive,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlatformError {
+    InvalidScreenSize(u32, u32),
+    InvalidScale(u32),
+    InvalidDisplayRatio(f64),
+    InvalidDisplay(u32, u32),
+    ConflictingOptions(String),
+    Init(String),
+}
+
+impl fmt::Display for PlatformError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidScreenSize(width, height) => {
+                write!(f, "Invalid screen size {}x{}", width, height)
+            }
+            Self::InvalidScale(scale) => write!(f, "Invalid window scale {}", scale),
+            Self::InvalidDisplayRatio(ratio) => {
+                write!(f, "Display ratio {} is not in the range (0, 1]", ratio)
+            }
+            Self::InvalidDisplay(display, num_displays) => write!(
+                f,
+                "Display {} is not available, the number of displays is {}",
+                display, num_displays
+            ),
+            Self::ConflictingOptions(message) => {
+                write!(f, "Conflicting window options: {}", message)
+            }
+            Self::Init(err) => write!(f, "Unable to create window: {}", err),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PlatformBuilder {
+    pub(crate) title: String,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) scale: Option<u32>,
+    pub(crate) display_ratio: f64,
+    pub(crate) is_resizable: bool,
+    pub(crate) is_vsync: bool,
+    pub(crate) fullscreen: FullscreenMode,
+    pub(crate) position: Option<(i32, i32)>,
+    pub(crate) display: Option<u32>,
+    pub(crate) icon: Option<(Vec<Vec<Color>>, Vec<Rgb8>, u32)>,
+    pub(crate) is_borderless: bool,
+}
+
+impl PlatformBuilder {
+    pub fn new() -> Self {
+        Self {
+            title: String::new(),
+            width: 0,
+            height: 0,
+            scale: None,
+            display_ratio: 1.0,
+            is_resizable: true,
+            is_vsync: true,
+            fullscreen: FullscreenMode::Off,
+            position: None,
+            display: None,
+            icon: None,
+            is_borderless: false,
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn screen_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn scale(mut self, scale: Option<u32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn display_ratio(mut self, display_ratio: f64) -> Self {
+        self.display_ratio = display_ratio;
+        self
+    }
+
+    pub fn resizable(mut self, is_resizable: bool) -> Self {
+        self.is_resizable = is_resizable;
+        self
+    }
+
+    pub fn vsync(mut self, is_vsync: bool) -> Self {
+        self.is_vsync = is_vsync;
+        self
+    }
+
+    pub fn fullscreen(mut self, mode: FullscreenMode) -> Self {
+        self.fullscreen = mode;
+        self
+    }
+
+    pub fn position(mut self, position: Option<(i32, i32)>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn display(mut self, display: Option<u32>) -> Self {
+        self.display = display;
+        self
+    }
+
+    pub fn icon(mut self, image: &Image, colors: &[Rgb8], scale: u32) -> Self {
+        self.icon = Some((image.canvas.data.clone(), colors.to_vec(), scale));
+        self
+    }
+
+    pub fn borderless(mut self, is_borderless: bool) -> Self {
+        self.is_borderless = is_borderless;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), PlatformError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(PlatformError::InvalidScreenSize(self.width, self.height));
+        }
+        if let Some(scale) = self.scale {
+            let window_size = self
+                .width
+                .checked_mul(scale)
+                .zip(self.height.checked_mul(scale));
+            if scale == 0
+                || window_size.is_none_or(|(width, height)| {
+                    width > i32::MAX as u32 || height > i32::MAX as u32
+                })
+            {
+                return Err(PlatformError::InvalidScale(scale));
+            }
+        }
+        if !(self.display_ratio > 0.0 && self.display_ratio <= 1.0) {
+            return Err(PlatformError::InvalidDisplayRatio(self.display_ratio));
+        }
+        if self.position.is_some() && self.fullscreen != FullscreenMode::Off {
+            return Err(PlatformError::ConflictingOptions(
+                "a window position cannot be used in fullscreen mode".to_string(),
+            ));
+        }
+        if self.is_borderless && self.fullscreen == FullscreenMode::Exclusive {
+            return Err(PlatformError::ConflictingOptions(
+                "an exclusive fullscreen window is always borderless".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn build<P: Platform>(&self) -> Result<P, PlatformError> {
+        self.validate()?;
+        P::from_builder(self)
+    }
+}
+
+impl Default for PlatformBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type PostRenderHook<C> = Box<dyn FnMut(&mut C)>;
+
 pub trait Platform {
-    fn new(title: &str, width: u32, height: u32, display_ratio: f64) -> Self;
+    type Canvas;
+
+    fn from_builder(builder: &PlatformBuilder) -> Result<Self, PlatformError>
+    where
+        Self: Sized;
+
+    fn new(title: &str, width: u32, height: u32, display_ratio: f64) -> Self
+    where
+        Self: Sized,
+    {
+        PlatformBuilder::new()
+            .title(title)
+            .screen_size(width, height)
+            .display_ratio(display_ratio)
+            .build()
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
     fn set_title(&mut self, title: &str);
     fn set_icon(&mut self, image: &[Vec<Color>], colors: &[Rgb8], scale: u32);
     fn show_cursor(&self, show: bool);
-    fn move_cursor(&self, x: i32, y: i32);
+    fn warp_mouse(&self, screen_x: i32, screen_y: i32);
     fn is_fullscreen(&self) -> bool;
     fn set_fullscreen(&mut self, is_fullscreen: bool);
+    fn window_to_screen(&self, x: i32, y: i32) -> (i32, i32);
+    fn set_raw_mouse(&mut self, is_raw_mouse: bool);
+    fn is_mouse_on_screen(&self) -> bool;
+    fn start_text_input(&mut self);
+    fn stop_text_input(&mut self);
+    fn set_text_input_rect(&mut self, x: i32, y: i32, width: u32, height: u32);
+    fn controller_name(&self, slot: u32) -> Option<String>;
+    fn controller_connected(&self, slot: u32) -> bool;
     fn tick_count(&self) -> u32;
+    // Monotonic microseconds on the same clock as the event timestamps
+    fn elapsed_us(&self) -> u64;
     fn sleep(&mut self, ms: u32);
     fn poll_event(&mut self) -> Option<Event>;
-    fn render_screen(&mut self, image: &[Vec<Color>], colors: &[Rgb8], bg_color: Rgb8);
+    fn poll_event_timed(&mut self) -> Option<TimedEvent>;
+    fn poll_events(&mut self, out: &mut Vec<TimedEvent>);
+    fn flush_events(&mut self);
+    fn set_coalesce_mouse_motion(&mut self, is_enabled: bool);
+    fn set_display_effect(&mut self, effect: DisplayEffect);
+    fn set_screen_fit(&mut self, fit: ScreenFit, pixel_aspect: f64);
+    fn set_screen_mode(&mut self, mode: ScreenMode);
+    fn set_overlay_enabled(&mut self, is_enabled: bool);
+    fn overlay_mut(&mut self) -> Option<&mut [u8]>;
+    fn render_screen(
+        &mut self,
+        image: &[Vec<Color>],
+        colors: &[Rgb8],
+        bg_color: Rgb8,
+        dirty_rects: Option<&[Recti]>,
+        scanlines: Option<&Scanlines>,
+    );
+    // The hook draws on the canvas between the copy of the screen and the present. The canvas is
+    // only lent for the call, so the hook must not present it or leave another render target set,
+    // and the textures it creates must be dropped before the platform
+    fn set_post_render_hook(&mut self, hook: Option<PostRenderHook<Self::Canvas>>);
+    fn create_aux_window(
+        &mut self,
+        title: &str,
+        width: u32,
+        height: u32,
+        scale: u32,
+    ) -> Result<AuxWindowId, PlatformError>;
+    fn close_aux_window(&mut self, window: AuxWindowId);
+    fn is_aux_window_open(&self, window: AuxWindowId) -> bool;
+    fn render_aux_window(
+        &mut self,
+        window: AuxWindowId,
+        image: &[Vec<Color>],
+        colors: &[Rgb8],
+        bg_color: Rgb8,
+    );
     fn start_audio(
         &mut self,
         sample_rate: u32,
+        num_channels: u32,
         num_samples: u32,
+        device_name: Option<&str>,
         audio_callback: shared_type!(dyn AudioCallback + Send),
-    );
+    ) -> Result<(), AudioError>;
+    fn actual_sample_rate(&self) -> u32;
+    fn audio_monitor(&self) -> Arc<AudioMonitor>;
+    fn audio_devices(&self) -> Vec<String>;
+    fn reopen_audio(&mut self, device_name: Option<&str>) -> Result<(), AudioError>;
     fn pause_audio(&mut self);
     fn resume_audio(&mut self);
+    fn is_audio_paused(&self) -> bool;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+    use crate::scanline::{ScanlineEdge, ScanlineParams};
+    use crate::settings::DEFAULT_COLORS;
+
+    #[test]
+    fn validate_builder() {
+        let builder = PlatformBuilder::new().title("test").screen_size(160, 120);
+        assert_eq!(builder.validate(), Ok(()));
+        assert_eq!(
+            builder.clone().screen_size(0, 120).validate(),
+            Err(PlatformError::InvalidScreenSize(0, 120))
+        );
+        assert_eq!(
+            builder.clone().scale(Some(0)).validate(),
+            Err(PlatformError::InvalidScale(0))
+        );
+        assert_eq!(
+            builder.clone().scale(Some(u32::MAX / 100)).validate(),
+            Err(PlatformError::InvalidScale(u32::MAX / 100))
+        );
+        assert_eq!(builder.clone().scale(Some(4)).validate(), Ok(()));
+        assert!(matches!(
+            builder.clone().display_ratio(0.0).validate(),
+            Err(PlatformError::InvalidDisplayRatio(_))
+        ));
+        assert!(matches!(
+            builder
+                .clone()
+                .position(Some((10, 10)))
+                .fullscreen(FullscreenMode::Desktop)
+                .validate(),
+            Err(PlatformError::ConflictingOptions(_))
+        ));
+        assert!(matches!(
+            builder
+                .clone()
+                .borderless(true)
+                .fullscreen(FullscreenMode::Exclusive)
+                .validate(),
+            Err(PlatformError::ConflictingOptions(_))
+        ));
+        assert_eq!(
+            builder
+                .borderless(true)
+                .fullscreen(FullscreenMode::Desktop)
+                .validate(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn screen_layout() {
+        let layout = |fit, pixel_aspect, window_width, window_height, effect_scale| {
+            let layout = ScreenLayout::new(
+                fit,
+                pixel_aspect,
+                160,
+                120,
+                window_width,
+                window_height,
+                effect_scale,
+            );
+            let rect = layout.rect;
+            (rect.left(), rect.top(), rect.width(), rect.height())
+        };
+        assert_eq!(
+            layout(ScreenFit::Integer, 1.0, 500, 400, 1),
+            (10, 20, 480, 360)
+        );
+        assert_eq!(
+            layout(ScreenFit::Integer, 1.0, 500, 400, 2),
+            (90, 80, 320, 240)
+        );
+        assert_eq!(
+            layout(ScreenFit::Integer, 1.0, 100, 100, 1),
+            (0, 0, 160, 120)
+        );
+        assert_eq!(
+            layout(ScreenFit::Integer, 1.2, 600, 400, 1),
+            (12, 20, 576, 360)
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 1.0, 1001, 700, 1),
+            (34, 0, 933, 700)
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 1.0, 333, 1000, 1),
+            (0, 375, 333, 250)
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 1.2, 1000, 1000, 1),
+            (0, 187, 1000, 625)
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 0.7, 1000, 1000, 3),
+            (33, 0, 933, 1000)
+        );
+        assert_eq!(
+            layout(ScreenFit::FillIgnoreAspect, 1.2, 999, 333, 1),
+            (0, 0, 999, 333)
+        );
+    }
+
+    #[test]
+    fn screen_layout_mouse() {
+        let layout = ScreenLayout::new(ScreenFit::FillIgnoreAspect, 1.0, 160, 120, 1000, 333, 1);
+        assert_eq!(layout.window_to_screen(0, 0), (0, 0));
+        assert_eq!(layout.window_to_screen(999, 332), (159, 119));
+        assert_eq!(layout.window_to_screen(7, 3), (1, 1));
+        assert_eq!(layout.window_to_screen(-5, 1000), (0, 119));
+        assert_eq!(layout.screen_to_window(80, 60), (500, 167));
+        assert_eq!(layout.screen_to_window_size(16, 12), (100, 33));
+
+        // Every screen pixel maps to the window pixels it covers and back again
+        for fit in [
+            ScreenFit::Integer,
+            ScreenFit::Aspect,
+            ScreenFit::FillIgnoreAspect,
+        ] {
+            let layout = ScreenLayout::new(fit, 1.2, 160, 120, 777, 555, 1);
+            for x in 0..160 {
+                let (window_x, _) = layout.screen_to_window(x, 0);
+                let (next_x, _) = layout.screen_to_window(x + 1, 0);
+                assert_eq!(layout.window_to_screen(window_x, 0).0, x);
+                assert_eq!(layout.window_to_screen(next_x - 1, 0).0, x);
+            }
+            for y in 0..120 {
+                let (_, window_y) = layout.screen_to_window(0, y);
+                assert_eq!(layout.window_to_screen(0, window_y).1, y);
+            }
+            let rect = layout.rect;
+            assert_eq!(
+                layout.screen_to_window(160, 120),
+                (rect.right() + 1, rect.bottom() + 1)
+            );
+        }
+    }
+
+    #[test]
+    fn prescale() {
+        let layout = |fit, window_width, window_height| {
+            ScreenLayout::new(fit, 1.0, 160, 120, window_width, window_height, 1)
+        };
+        assert_eq!(
+            layout(ScreenFit::Integer, 500, 400).prescale(160, 120),
+            None
+        );
+        assert_eq!(layout(ScreenFit::Aspect, 480, 360).prescale(160, 120), None);
+        assert_eq!(
+            layout(ScreenFit::FillIgnoreAspect, 320, 360).prescale(160, 120),
+            None
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 1920, 1080).prescale(160, 120),
+            None
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 1366, 768).prescale(160, 120),
+            Some(7)
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 1366, 768).prescale(320, 240),
+            Some(4)
+        );
+        assert_eq!(
+            layout(ScreenFit::Aspect, 379, 400).prescale(160, 120),
+            Some(3)
+        );
+        let layout = ScreenLayout::new(ScreenFit::Integer, 1.25, 160, 120, 1000, 1000, 1);
+        assert_eq!(layout.prescale(160, 120), Some(7));
+    }
+
+    fn render(canvas: &Canvas<Color>, buffer: &mut [u8], rects: Option<&[Recti]>) {
+        let pitch = canvas.width() as usize * 3;
+        let full_rect = [canvas.self_rect];
+        for rect in rects.unwrap_or(&full_rect) {
+            let offset = rect.top() as usize * pitch + rect.left() as usize * 3;
+            write_rgb24(
+                &mut buffer[offset..],
+                pitch,
+                &canvas.data,
+                &RgbTables::new(&DEFAULT_COLORS, None),
+                DisplayEffect::None,
+                *rect,
+                None,
+            );
+        }
+    }
+
+    fn write_rgb24_reference(
+        buffer: &mut [u8],
+        pitch: usize,
+        image: &[Vec<Color>],
+        colors: &[Rgb8],
+        effect: DisplayEffect,
+        rect: Recti,
+    ) {
+        let effect_scale = effect.scale() as usize;
+        let left = rect.left() as usize * effect_scale;
+        let top = rect.top() as usize * effect_scale;
+        for i in 0..rect.height() as usize * effect_scale {
+            for j in 0..rect.width() as usize * effect_scale {
+                let screen_x = left + j;
+                let screen_y = top + i;
+                let color =
+                    colors[image[screen_y / effect_scale][screen_x / effect_scale] as usize];
+                let color = effect.shade(
+                    color,
+                    (screen_x % effect_scale) as u32,
+                    (screen_y % effect_scale) as u32,
+                );
+                let offset = i * pitch + j * 3;
+                buffer[offset] = ((color >> 16) & 0xff) as u8;
+                buffer[offset + 1] = ((color >> 8) & 0xff) as u8;
+                buffer[offset + 2] = (color & 0xff) as u8;
+            }
+        }
+    }
+
+    #[test]
+    fn write_rgb24_golden() {
+        let mut canvas = Canvas::<Color>::new(256, 224);
+        for y in 0..224 {
+            for x in 0..256 {
+                canvas.data[y][x] = ((x * 7 + y * 13 + x * y) % 16) as Color;
+            }
+        }
+        for effect in [
+            DisplayEffect::None,
+            DisplayEffect::Scanlines { darkness: 96 },
+            DisplayEffect::Grid,
+        ] {
+            let scale = effect.scale() as usize;
+            let pitch = 256 * scale * 3 + 5;
+            for rect in [Recti::new(0, 0, 256, 224), Recti::new(17, 30, 40, 9)] {
+                let offset = rect.top() as usize * scale * pitch + rect.left() as usize * scale * 3;
+                let mut expected = vec![0; pitch * 224 * scale];
+                let mut actual = vec![0; pitch * 224 * scale];
+                write_rgb24_reference(
+                    &mut expected[offset..],
+                    pitch,
+                    &canvas.data,
+                    &DEFAULT_COLORS,
+                    effect,
+                    rect,
+                );
+                write_rgb24(
+                    &mut actual[offset..],
+                    pitch,
+                    &canvas.data,
+                    &RgbTables::new(&DEFAULT_COLORS, None),
+                    effect,
+                    rect,
+                    None,
+                );
+                assert!(actual == expected);
+            }
+        }
+    }
+
+    #[test]
+    fn write_rgb24_scanlines() {
+        let image = vec![vec![0, 1, 2, 3], vec![0, 1, 2, 3]];
+        let colors = [0x000000, 0x111111, 0x222222, 0x333333];
+        let mut palette_colors = [0; MAX_COLORS as usize];
+        palette_colors[..4].copy_from_slice(&[0x000001, 0x000002, 0x000003, 0x000004]);
+        let scanlines = Scanlines {
+            params: vec![
+                ScanlineParams {
+                    x_offset: 1,
+                    edge: ScanlineEdge::Wrap,
+                    palette: Some(5),
+                },
+                ScanlineParams {
+                    x_offset: i32::MIN,
+                    edge: ScanlineEdge::Clamp,
+                    palette: Some(0),
+                },
+            ],
+            palettes: vec![palette_colors],
+        };
+        let rgb_tables = RgbTables::new(&colors, Some(&scanlines));
+        let rect = Recti::new(1, 0, 3, 2);
+        let mut buffer = vec![0; 3 * 3 * 2];
+        write_rgb24(
+            &mut buffer,
+            9,
+            &image,
+            &rgb_tables,
+            DisplayEffect::None,
+            rect,
+            Some(&scanlines),
+        );
+        assert_eq!(
+            buffer,
+            [
+                0x00, 0x00, 0x00, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x00, 0x00, 0x04, 0x00, 0x00,
+                0x04, 0x00, 0x00, 0x04
+            ]
+        );
+
+        let mut buffer = vec![0; 6 * 3 * 4];
+        write_rgb24(
+            &mut buffer,
+            18,
+            &image,
+            &rgb_tables,
+            DisplayEffect::Scanlines { darkness: 0 },
+            rect,
+            Some(&scanlines),
+        );
+        let first_row: Vec<u8> = [0x00, 0x11, 0x22]
+            .iter()
+            .flat_map(|&value| [value; 6])
+            .collect();
+        assert_eq!(buffer[..18], first_row);
+        assert!(buffer[36..]
+            .chunks_exact(3)
+            .all(|rgb| rgb == [0x00, 0x00, 0x04]));
+    }
+
+    #[test]
+    fn blend_overlay_() {
+        let mut buffer = vec![0x40; 2 * 3 * 2];
+        let overlay = [
+            0xff, 0x00, 0x00, 0xff, 0x00, 0xff, 0x00, 0x00, //
+            0xff, 0xff, 0xff, 0x80, 0x00, 0x00, 0x00, 0x40,
+        ];
+        blend_overlay(&mut buffer, 6, &overlay, 2, 2, 1);
+        assert_eq!(
+            buffer,
+            [0xff, 0x00, 0x00, 0x40, 0x40, 0x40, 0xa0, 0xa0, 0xa0, 0x30, 0x30, 0x30]
+        );
+
+        let mut buffer = vec![0x00; 2 * 3 * 2];
+        blend_overlay(&mut buffer, 6, &[0x10, 0x20, 0x30, 0xff], 1, 1, 2);
+        assert!(buffer.chunks_exact(3).all(|rgb| rgb == [0x10, 0x20, 0x30]));
+    }
+
+    #[test]
+    fn write_rgb24_dirty_rects() {
+        let mut canvas = Canvas::<Color>::new(256, 224);
+        canvas.set_dirty_tracking(true);
+        let mut tracked = vec![0; 256 * 224 * 3];
+        let rects = canvas.take_dirty_rects().unwrap();
+        render(&canvas, &mut tracked, Some(&rects));
+
+        for frame in 0..3 {
+            canvas.rect(100.0 + frame as f64, 50.0, 16.0, 16.0, 8 + frame);
+            canvas.pset(3.0, 220.0, 7);
+            canvas.line(250.0, 0.0, 255.0, 10.0, 12);
+            let rects = canvas.take_dirty_rects().unwrap();
+            assert!(rects.len() <= 5);
+            render(&canvas, &mut tracked, Some(&rects));
+
+            let mut untracked = vec![0; 256 * 224 * 3];
+            render(&canvas, &mut untracked, None);
+            assert!(tracked == untracked);
+        }
+    }
+
+    #[test]
+    fn shade() {
+        let effect = DisplayEffect::Scanlines { darkness: 255 };
+        assert_eq!(effect.scale(), 2);
+        assert_eq!(effect.shade(0x80ff40, 1, 0), 0x80ff40);
+        assert_eq!(effect.shade(0x80ff40, 0, 1), 0x000000);
+
+        let effect = DisplayEffect::Scanlines { darkness: 128 };
+        assert_eq!(effect.shade(0xfefefe, 0, 1), 0x7e7e7e);
+
+        let effect = DisplayEffect::Grid;
+        assert_eq!(effect.scale(), 3);
+        assert_eq!(effect.shade(0xffffff, 1, 1), 0xffffff);
+        assert_ne!(effect.shade(0xffffff, 2, 0), 0xffffff);
+        assert_eq!(effect.shade(0xffffff, 0, 2), effect.shade(0xffffff, 2, 2));
+
+        assert_eq!(DisplayEffect::None.scale(), 1);
+        assert_eq!(DisplayEffect::None.shade(0x123456, 1, 1), 0x123456);
+    }
 }
@@ -0,0 +1,117 @@
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlatformCommand {
+    SetWindowTitle(String),
+    SetFullscreen(bool),
+    RequestQuit,
+    PauseAudio,
+    ResumeAudio,
+}
+
+#[derive(Clone)]
+pub struct PlatformHandle {
+    sender: Sender<PlatformCommand>,
+}
+
+impl PlatformHandle {
+    fn send(&self, command: PlatformCommand) {
+        // The queue is closed at shutdown, after which commands have nowhere to go
+        let _ = self.sender.send(command);
+    }
+
+    pub fn set_window_title(&self, title: &str) {
+        self.send(PlatformCommand::SetWindowTitle(title.to_string()));
+    }
+
+    pub fn set_fullscreen(&self, is_fullscreen: bool) {
+        self.send(PlatformCommand::SetFullscreen(is_fullscreen));
+    }
+
+    pub fn request_quit(&self) {
+        self.send(PlatformCommand::RequestQuit);
+    }
+
+    pub fn pause_audio(&self) {
+        self.send(PlatformCommand::PauseAudio);
+    }
+
+    pub fn resume_audio(&self) {
+        self.send(PlatformCommand::ResumeAudio);
+    }
+}
+
+pub struct PlatformCommandQueue {
+    sender: Sender<PlatformCommand>,
+    receiver: Option<Receiver<PlatformCommand>>,
+}
+
+impl PlatformCommandQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = channel();
+        Self {
+            sender,
+            receiver: Some(receiver),
+        }
+    }
+
+    pub fn handle(&self) -> PlatformHandle {
+        PlatformHandle {
+            sender: self.sender.clone(),
+        }
+    }
+
+    pub fn drain(&self) -> Vec<PlatformCommand> {
+        self.receiver
+            .as_ref()
+            .map_or_else(Vec::new, |receiver| receiver.try_iter().collect())
+    }
+
+    pub fn close(&mut self) {
+        self.receiver = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn worker_thread() {
+        let mut queue = PlatformCommandQueue::new();
+        let handle = queue.handle();
+        let worker = thread::spawn(move || {
+            for progress in [0, 50, 100] {
+                handle.set_window_title(&format!("Loading {}%", progress));
+            }
+            handle.pause_audio();
+            handle.set_fullscreen(true);
+            handle.request_quit();
+            handle
+        });
+        let handle = worker.join().unwrap();
+        assert_eq!(
+            queue.drain(),
+            [
+                PlatformCommand::SetWindowTitle("Loading 0%".to_string()),
+                PlatformCommand::SetWindowTitle("Loading 50%".to_string()),
+                PlatformCommand::SetWindowTitle("Loading 100%".to_string()),
+                PlatformCommand::PauseAudio,
+                PlatformCommand::SetFullscreen(true),
+                PlatformCommand::RequestQuit,
+            ]
+        );
+        assert!(queue.drain().is_empty());
+
+        queue.close();
+        let worker = thread::spawn(move || {
+            handle.resume_audio();
+            handle.request_quit();
+        });
+        worker.join().unwrap();
+        queue.handle().set_window_title("Done");
+        assert!(queue.drain().is_empty());
+    }
+}
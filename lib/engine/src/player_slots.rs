@@ -0,0 +1,73 @@
+use crate::settings::NUM_PLAYER_SLOTS;
+
+pub struct PlayerSlots {
+    instance_ids: [Option<u32>; NUM_PLAYER_SLOTS as usize],
+}
+
+impl PlayerSlots {
+    pub fn new() -> Self {
+        Self {
+            instance_ids: [None; NUM_PLAYER_SLOTS as usize],
+        }
+    }
+
+    pub fn add(&mut self, instance_id: u32) -> Option<u32> {
+        if let Some(slot) = self.slot(instance_id) {
+            return Some(slot);
+        }
+        let slot = self.instance_ids.iter().position(Option::is_none)?;
+        self.instance_ids[slot] = Some(instance_id);
+        Some(slot as u32)
+    }
+
+    pub fn remove(&mut self, instance_id: u32) -> Option<u32> {
+        let slot = self.slot(instance_id)?;
+        self.instance_ids[slot as usize] = None;
+        Some(slot)
+    }
+
+    pub fn slot(&self, instance_id: u32) -> Option<u32> {
+        self.instance_ids
+            .iter()
+            .position(|id| *id == Some(instance_id))
+            .map(|slot| slot as u32)
+    }
+
+    pub fn instance_id(&self, slot: u32) -> Option<u32> {
+        self.instance_ids.get(slot as usize).copied().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add() {
+        let mut player_slots = PlayerSlots::new();
+        assert_eq!(player_slots.add(3), Some(0));
+        assert_eq!(player_slots.add(7), Some(1));
+        assert_eq!(player_slots.add(3), Some(0));
+        assert_eq!(player_slots.add(8), Some(2));
+        assert_eq!(player_slots.add(9), Some(3));
+        assert_eq!(player_slots.add(10), None);
+        assert_eq!(player_slots.instance_id(1), Some(7));
+        assert_eq!(player_slots.instance_id(NUM_PLAYER_SLOTS), None);
+    }
+
+    #[test]
+    fn reconnect() {
+        let mut player_slots = PlayerSlots::new();
+        player_slots.add(0);
+        player_slots.add(1);
+        assert_eq!(player_slots.remove(0), Some(0));
+        assert_eq!(player_slots.remove(0), None);
+        assert_eq!(player_slots.slot(1), Some(1));
+        assert_eq!(player_slots.instance_id(0), None);
+        assert_eq!(player_slots.add(5), Some(0));
+        assert_eq!(player_slots.slot(5), Some(0));
+        assert_eq!(player_slots.slot(1), Some(1));
+        assert_eq!(player_slots.remove(1), Some(1));
+        assert_eq!(player_slots.add(6), Some(1));
+    }
+}
@@ -1,47 +1,286 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::settings::PROFILER_NUM_FRAMES;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeStats {
+    pub name: String,
+    pub depth: u32,
+    pub num_calls: u32,
+    pub total_ms: f64,
+    pub self_ms: f64,
+    pub max_ms: f64,
+}
+
+struct ScopeNode {
+    name: String,
+    parent: Option<usize>,
+    num_calls: u32,
+    total_time: Duration,
+    child_time: Duration,
+}
+
+struct OpenScope {
+    node: usize,
+    start: Instant,
+}
+
+pub struct ProfileScope {
+    scope: Option<(shared_type!(Profiler), u64)>,
+}
+
+impl ProfileScope {
+    pub(crate) fn new(profiler: Option<&shared_type!(Profiler)>, name: &str) -> Self {
+        Self {
+            scope: profiler.map(|profiler| {
+                let frame_count = profiler.lock().begin_scope(name, Instant::now());
+                (profiler.clone(), frame_count)
+            }),
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if let Some((profiler, frame_count)) = self.scope.take() {
+            let now = Instant::now();
+            profiler.lock().end_scope(frame_count, now);
+        }
+    }
+}
+
 pub struct Profiler {
-    num_measure_frames: u32,
-    num_measured_frames: u32,
-    start_time: u32,
-    total_time: u32,
-    average_time: f64,
-    average_fps: f64,
+    frame_count: u64,
+    nodes: Vec<ScopeNode>,
+    open_scopes: Vec<OpenScope>,
+    history: VecDeque<Vec<ScopeNode>>,
+    top_level_names: Vec<String>,
+}
+
+fn as_ms(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
 }
 
 impl Profiler {
-    pub fn new(num_measure_frames: u32) -> Self {
-        assert!(num_measure_frames >= 1, "invalid measure frame count");
+    pub fn new() -> Self {
         Self {
-            num_measure_frames,
-            num_measured_frames: 0,
-            start_time: 0,
-            total_time: 0,
-            average_time: 0.0,
-            average_fps: 0.0,
+            frame_count: 0,
+            nodes: Vec::new(),
+            open_scopes: Vec::new(),
+            history: VecDeque::with_capacity(PROFILER_NUM_FRAMES),
+            top_level_names: Vec::new(),
         }
     }
 
-    #[allow(dead_code)]
-    pub fn average_time(&self) -> f64 {
-        self.average_time
+    pub fn begin_scope(&mut self, name: &str, now: Instant) -> u64 {
+        let parent = self.open_scopes.last().map(|scope| scope.node);
+        let node = if let Some(node) = self
+            .nodes
+            .iter()
+            .position(|node| node.parent == parent && node.name == name)
+        {
+            node
+        } else {
+            self.nodes.push(ScopeNode {
+                name: name.to_string(),
+                parent,
+                num_calls: 0,
+                total_time: Duration::ZERO,
+                child_time: Duration::ZERO,
+            });
+            self.nodes.len() - 1
+        };
+        self.open_scopes.push(OpenScope { node, start: now });
+        self.frame_count
     }
 
-    #[allow(dead_code)]
-    pub fn average_fps(&self) -> f64 {
-        self.average_fps
+    pub fn end_scope(&mut self, frame_count: u64, now: Instant) {
+        // Scopes still open when their frame ended were discarded with it
+        if frame_count != self.frame_count {
+            return;
+        }
+        if let Some(scope) = self.open_scopes.pop() {
+            let elapsed = now - scope.start;
+            let node = &mut self.nodes[scope.node];
+            node.num_calls += 1;
+            node.total_time += elapsed;
+            if let Some(parent) = node.parent {
+                self.nodes[parent].child_time += elapsed;
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        for node in &self.nodes {
+            if node.parent.is_none() && !self.top_level_names.contains(&node.name) {
+                self.top_level_names.push(node.name.clone());
+            }
+        }
+        if self.history.len() == PROFILER_NUM_FRAMES {
+            self.history.pop_front();
+        }
+        self.history.push_back(std::mem::take(&mut self.nodes));
+        self.open_scopes.clear();
+        self.frame_count += 1;
     }
 
-    pub fn start(&mut self, tick_count: u32) {
-        self.start_time = tick_count;
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.open_scopes.clear();
+        self.history.clear();
+        self.top_level_names.clear();
+        self.frame_count += 1;
     }
 
-    pub fn end(&mut self, tick_count: u32) {
-        self.total_time += tick_count - self.start_time;
-        self.num_measured_frames += 1;
-        if self.num_measured_frames >= self.num_measure_frames {
-            self.average_time = self.total_time as f64 / self.num_measured_frames as f64;
-            self.average_fps = 1000.0 / self.average_time;
-            self.num_measured_frames = 0;
-            self.total_time = 0;
+    pub fn frame_bars(&self) -> Vec<Vec<(usize, f64)>> {
+        // Top-level scopes keep the index of their first appearance so their colors stay stable
+        self.history
+            .iter()
+            .map(|nodes| {
+                nodes
+                    .iter()
+                    .filter(|node| node.parent.is_none())
+                    .map(|node| {
+                        let index = self
+                            .top_level_names
+                            .iter()
+                            .position(|name| *name == node.name)
+                            .unwrap();
+                        (index, as_ms(node.total_time))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn report(&self) -> Vec<ScopeStats> {
+        let mut parents: Vec<Option<usize>> = Vec::new();
+        let mut stats: Vec<ScopeStats> = Vec::new();
+        for nodes in &self.history {
+            let mut indices = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                // Parents are always created before their children, so they are already mapped
+                let parent = node.parent.map(|parent| indices[parent]);
+                let index = (0..stats.len())
+                    .find(|&index| parents[index] == parent && stats[index].name == node.name)
+                    .unwrap_or_else(|| {
+                        parents.push(parent);
+                        stats.push(ScopeStats {
+                            name: node.name.clone(),
+                            depth: parent.map_or(0, |parent: usize| stats[parent].depth + 1),
+                            num_calls: 0,
+                            total_ms: 0.0,
+                            self_ms: 0.0,
+                            max_ms: 0.0,
+                        });
+                        stats.len() - 1
+                    });
+                let total_ms = as_ms(node.total_time);
+                let scope_stats = &mut stats[index];
+                scope_stats.num_calls += node.num_calls;
+                scope_stats.total_ms += total_ms;
+                scope_stats.self_ms += as_ms(node.total_time.saturating_sub(node.child_time));
+                scope_stats.max_ms = scope_stats.max_ms.max(total_ms);
+                indices.push(index);
+            }
+        }
+        let num_frames = self.history.len().max(1) as f64;
+        for scope_stats in &mut stats {
+            scope_stats.total_ms /= num_frames;
+            scope_stats.self_ms /= num_frames;
+        }
+
+        let mut report = Vec::with_capacity(stats.len());
+        let mut pending: Vec<usize> = (0..stats.len())
+            .rev()
+            .filter(|&index| parents[index].is_none())
+            .collect();
+        while let Some(index) = pending.pop() {
+            report.push(stats[index].clone());
+            pending.extend(
+                (0..stats.len())
+                    .rev()
+                    .filter(|&child| parents[child] == Some(index)),
+            );
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let mut profiler = Profiler::new();
+        let start = Instant::now();
+        let ms = |value: u64| start + Duration::from_millis(value);
+        for i in 0..2 {
+            let offset = i * 20;
+            let frame = profiler.begin_scope("update", ms(offset));
+            profiler.begin_scope("physics", ms(offset + 1));
+            profiler.end_scope(frame, ms(offset + 4));
+            profiler.begin_scope("ai", ms(offset + 4));
+            profiler.begin_scope("physics", ms(offset + 5));
+            profiler.end_scope(frame, ms(offset + 6));
+            profiler.end_scope(frame, ms(offset + 8));
+            profiler.begin_scope("physics", ms(offset + 8));
+            profiler.end_scope(frame, ms(offset + 9));
+            profiler.end_scope(frame, ms(offset + 10));
+            if i == 1 {
+                profiler.begin_scope("draw", ms(offset + 10));
+                profiler.end_scope(frame, ms(offset + 16));
+            }
+            profiler.end_frame();
+        }
+
+        let report = profiler.report();
+        let names: Vec<(&str, u32)> = report
+            .iter()
+            .map(|stats| (stats.name.as_str(), stats.depth))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("update", 0),
+                ("physics", 1),
+                ("ai", 1),
+                ("physics", 2),
+                ("draw", 0)
+            ]
+        );
+        let approx = |value: f64, expected: f64| (value - expected).abs() < 1e-9;
+        assert!(approx(report[0].total_ms, 10.0) && approx(report[0].self_ms, 2.0));
+        assert_eq!(report[1].num_calls, 4);
+        assert!(approx(report[1].total_ms, 4.0) && approx(report[1].self_ms, 4.0));
+        assert!(approx(report[2].total_ms, 4.0) && approx(report[2].self_ms, 3.0));
+        assert!(approx(report[3].total_ms, 1.0));
+        assert!(approx(report[4].total_ms, 3.0) && approx(report[4].max_ms, 6.0));
+
+        let bars = profiler.frame_bars();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[1].len(), 2);
+        assert!(bars[1][0].0 == 0 && approx(bars[1][0].1, 10.0));
+        assert!(bars[1][1].0 == 1 && approx(bars[1][1].1, 6.0));
+    }
+
+    #[test]
+    fn end_frame() {
+        let mut profiler = Profiler::new();
+        let start = Instant::now();
+        let frame = profiler.begin_scope("loading", start);
+        profiler.end_frame();
+        profiler.end_scope(frame, start + Duration::from_millis(1));
+        profiler.end_frame();
+        let report = profiler.report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].num_calls, 0);
+
+        for _ in 0..PROFILER_NUM_FRAMES + 10 {
+            profiler.end_frame();
         }
+        assert_eq!(profiler.frame_bars().len(), PROFILER_NUM_FRAMES);
     }
 }
@@ -1,22 +1,28 @@
-use std::fs::File;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use chrono::Local;
+use log::warn;
 use platform_dirs::UserDirs;
 use zip::{ZipArchive, ZipWriter};
 
-use crate::image::Image;
-use crate::music::Music;
+use crate::asset_watcher::{AssetKind, AssetWatcher};
+use crate::audio_capture::CaptureStats;
+use crate::image::{Image, ImageError, SharedImage};
+use crate::music::{Music, SharedMusic};
+use crate::platform::AudioError;
 use crate::screencast::Screencast;
 use crate::settings::{
-    NUM_COLORS, NUM_IMAGES, NUM_MUSICS, NUM_SOUNDS, NUM_TILEMAPS, PYXEL_VERSION,
+    MAX_COLORS, MAX_SAVE_SCALE, NUM_IMAGES, NUM_MUSICS, NUM_SOUNDS, NUM_TILEMAPS, PYXEL_VERSION,
     RESOURCE_ARCHIVE_DIRNAME,
 };
-use crate::sound::Sound;
-use crate::tilemap::Tilemap;
+use crate::sound::{SharedSound, Sound};
+use crate::storage::{Storage, StorageError};
+use crate::tilemap::{SharedTilemap, Tilemap};
 use crate::types::{Color, Rgb8};
-use crate::utils::parse_version_string;
+use crate::utils::{add_file_extension, parse_version_string, simplify_string};
 use crate::Pyxel;
 
 pub trait ResourceItem {
@@ -24,108 +30,291 @@ pub trait ResourceItem {
     fn is_modified(&self) -> bool;
     fn clear(&mut self);
     fn serialize(&self, pyxel: &Pyxel) -> String;
-    fn deserialize(&mut self, pyxel: &Pyxel, version: u32, input: &str);
+    fn deserialize(
+        &mut self,
+        images: &[SharedImage],
+        version: u32,
+        input: &str,
+    ) -> Result<(), String>;
+}
+
+#[derive(Debug)]
+pub enum ResourceError {
+    OpenFile(String),
+    WriteFile(String),
+    InvalidArchive(String),
+    UnsupportedVersion(String),
+    InvalidItem(String, String),
+}
+
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::OpenFile(filename) => write!(f, "Unable to open file '{}'", filename),
+            Self::WriteFile(filename) => write!(f, "Unable to write file '{}'", filename),
+            Self::InvalidArchive(filename) => {
+                write!(f, "Unable to parse zip archive '{}'", filename)
+            }
+            Self::UnsupportedVersion(version) => {
+                write!(f, "Unsupported resource file version '{}'", version)
+            }
+            Self::InvalidItem(name, reason) => write!(f, "Invalid resource '{}': {}", name, reason),
+        }
+    }
 }
 
 pub struct Resource {
     capture_scale: u32,
     screencast: Screencast,
+    asset_watcher: AssetWatcher,
+    reloaded_assets: Vec<String>,
+    capture_dir: Option<String>,
+    app_id: String,
 }
 
 impl Resource {
-    pub fn new(fps: u32, capture_scale: u32, capture_sec: u32) -> Self {
+    pub fn new(title: &str, fps: u32, capture_scale: u32, capture_sec: u32) -> Self {
         Self {
             capture_scale: u32::max(capture_scale, 1),
             screencast: Screencast::new(fps, capture_sec),
+            asset_watcher: AssetWatcher::new(),
+            reloaded_assets: Vec::new(),
+            capture_dir: None,
+            app_id: title.to_string(),
         }
     }
 
+    pub fn set_capture_fps(&mut self, fps: u32) {
+        self.screencast.set_fps(fps);
+    }
+
     pub fn capture_screen(
         &mut self,
         image: &[Vec<Color>],
-        colors: &[Rgb8; NUM_COLORS as usize],
+        colors: &[Rgb8; MAX_COLORS as usize],
         frame_count: u32,
     ) {
         self.screencast.capture(image, colors, frame_count);
     }
 
-    fn export_path() -> String {
-        UserDirs::new()
-            .unwrap()
-            .desktop_dir
-            .join(Local::now().format("pyxel-%Y%m%d-%H%M%S").to_string())
-            .to_str()
-            .unwrap()
-            .to_string()
+    fn export_path(&self) -> String {
+        let dir = self.capture_dir.as_ref().map_or_else(
+            || {
+                // The working directory is used when there is no home or pictures directory
+                UserDirs::new()
+                    .map(|dirs| dirs.picture_dir)
+                    .filter(|dir| dir.is_dir())
+                    .unwrap_or_default()
+            },
+            |dir| {
+                fs::create_dir_all(dir).ok();
+                PathBuf::from(dir)
+            },
+        );
+        dir.join(Local::now().format("pyxel-%Y%m%d-%H%M%S").to_string())
+            .to_string_lossy()
+            .into_owned()
     }
 }
 
-impl Pyxel {
-    pub fn load(&mut self, filename: &str, image: bool, tilemap: bool, sound: bool, music: bool) {
-        let mut archive = ZipArchive::new(
-            File::open(&Path::new(&filename))
-                .unwrap_or_else(|_| panic!("Unable to open file '{}'", filename)),
-        )
-        .unwrap_or_else(|_| panic!("Unable to parse zip archive '{}'", filename));
+struct ResourceArchive {
+    version: u32,
+    images: Vec<Option<String>>,
+    tilemaps: Vec<Option<String>>,
+    sounds: Vec<Option<String>>,
+    musics: Vec<Option<String>>,
+    warnings: Vec<String>,
+}
+
+impl ResourceArchive {
+    fn open(
+        filename: &str,
+        image: bool,
+        tilemap: bool,
+        sound: bool,
+        music: bool,
+    ) -> Result<Self, ResourceError> {
+        let invalid_archive = || ResourceError::InvalidArchive(filename.to_string());
+        let file = File::open(Path::new(&filename))
+            .map_err(|_| ResourceError::OpenFile(filename.to_string()))?;
+        let mut archive = ZipArchive::new(file).map_err(|_| invalid_archive())?;
         let version_name = RESOURCE_ARCHIVE_DIRNAME.to_string() + "version";
-        let contents = {
-            let mut file = archive.by_name(&version_name).unwrap();
+        let mut known_names = vec![RESOURCE_ARCHIVE_DIRNAME.to_string(), version_name.clone()];
+        known_names.extend((0..NUM_IMAGES).map(Image::resource_name));
+        known_names.extend((0..NUM_TILEMAPS).map(Tilemap::resource_name));
+        known_names.extend((0..NUM_SOUNDS).map(Sound::resource_name));
+        known_names.extend((0..NUM_MUSICS).map(Music::resource_name));
+        let mut warnings: Vec<String> = archive
+            .file_names()
+            .filter(|name| !known_names.iter().any(|known_name| known_name == name))
+            .map(|name| format!("skipped unknown resource '{}'", name))
+            .collect();
+        warnings.sort();
+
+        let mut read_entry = |name: &str| -> Result<Option<String>, ResourceError> {
+            let mut file = match archive.by_name(name) {
+                Ok(file) => file,
+                Err(_) => return Ok(None),
+            };
             let mut contents = String::new();
-            file.read_to_string(&mut contents).unwrap();
-            contents
+            file.read_to_string(&mut contents)
+                .map_err(|_| invalid_archive())?;
+            if contents.is_ascii() {
+                Ok(Some(contents))
+            } else {
+                Err(ResourceError::InvalidItem(
+                    name.to_string(),
+                    "non-ASCII data".to_string(),
+                ))
+            }
         };
-        let version = parse_version_string(&contents).unwrap();
+        let contents = read_entry(&version_name)?.ok_or_else(invalid_archive)?;
+        let version = parse_version_string(&contents)
+            .map_err(|_| ResourceError::UnsupportedVersion(contents.clone()))?;
         if version > parse_version_string(PYXEL_VERSION).unwrap() {
-            panic!("Unsupported resource file version '{}'", contents);
+            // Newer files have kept the same layout so far, so they are read as the current version
+            warnings.push(format!(
+                "resource file version '{}' is newer than '{}'",
+                simplify_string(&contents),
+                PYXEL_VERSION
+            ));
+        }
+
+        // Entries are all read up front, so an unreadable archive leaves the resources untouched
+        macro_rules! read_entries {
+            ($type: ty, $enabled: ident, $count: expr) => {{
+                let mut items = Vec::new();
+                if $enabled {
+                    for i in 0..$count {
+                        items.push(read_entry(&<$type>::resource_name(i))?);
+                    }
+                }
+                items
+            }};
         }
+        Ok(Self {
+            version: version.min(parse_version_string(PYXEL_VERSION).unwrap()),
+            images: read_entries!(Image, image, NUM_IMAGES),
+            tilemaps: read_entries!(Tilemap, tilemap, NUM_TILEMAPS),
+            sounds: read_entries!(Sound, sound, NUM_SOUNDS),
+            musics: read_entries!(Music, music, NUM_MUSICS),
+            warnings,
+        })
+    }
 
+    fn deserialize(
+        &self,
+        images: &[SharedImage],
+        tilemaps: &[SharedTilemap],
+        sounds: &[SharedSound],
+        musics: &[SharedMusic],
+    ) -> Result<(), ResourceError> {
+        // Every entry is parsed into a scratch item first, so an invalid entry
+        // leaves all the resources untouched
+        macro_rules! check {
+            ($type: ty, $entries: ident, $items: ident, $new_scratch: expr) => {
+                for (i, (input, item)) in self.$entries.iter().zip($items).enumerate() {
+                    if let Some(input) = input {
+                        let scratch = $new_scratch(&*item.lock());
+                        let result = scratch.lock().deserialize(images, self.version, input);
+                        result.map_err(|reason| {
+                            ResourceError::InvalidItem(<$type>::resource_name(i as u32), reason)
+                        })?;
+                    }
+                }
+            };
+        }
         macro_rules! deserialize {
-            ($type: ty, $getter: ident, $count: expr) => {
-                for i in 0..$count {
-                    if let Ok(mut file) = archive.by_name(&<$type>::resource_name(i)) {
-                        let mut input = String::new();
-                        file.read_to_string(&mut input).unwrap();
-                        self.$getter(i).lock().deserialize(self, version, &input);
+            ($type: ty, $entries: ident, $items: ident) => {
+                for (i, (input, item)) in self.$entries.iter().zip($items).enumerate() {
+                    let mut item = item.lock();
+                    if let Some(input) = input {
+                        item.deserialize(images, self.version, input)
+                            .map_err(|reason| {
+                                ResourceError::InvalidItem(<$type>::resource_name(i as u32), reason)
+                            })?;
                     } else {
-                        self.$getter(i).lock().clear();
+                        item.clear();
                     }
                 }
             };
         }
 
-        if image {
-            deserialize!(Image, image, NUM_IMAGES);
-        }
-        if tilemap {
-            deserialize!(Tilemap, tilemap, NUM_TILEMAPS);
-        }
-        if sound {
-            deserialize!(Sound, sound, NUM_SOUNDS);
-        }
-        if music {
-            deserialize!(Music, music, NUM_MUSICS);
+        check!(Image, images, images, |image: &Image| Image::new(
+            image.width(),
+            image.height()
+        ));
+        check!(Tilemap, tilemaps, tilemaps, |tilemap: &Tilemap| {
+            Tilemap::new(tilemap.width(), tilemap.height(), tilemap.image.clone())
+        });
+        check!(Sound, sounds, sounds, |_: &Sound| Sound::new());
+        check!(Music, musics, musics, |_: &Music| Music::new());
+        deserialize!(Image, images, images);
+        deserialize!(Tilemap, tilemaps, tilemaps);
+        deserialize!(Sound, sounds, sounds);
+        deserialize!(Music, musics, musics);
+        Ok(())
+    }
+}
+
+impl Pyxel {
+    pub fn load(&mut self, filename: &str, image: bool, tilemap: bool, sound: bool, music: bool) {
+        self.try_load(filename, image, tilemap, sound, music)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_load(
+        &mut self,
+        filename: &str,
+        image: bool,
+        tilemap: bool,
+        sound: bool,
+        music: bool,
+    ) -> Result<(), ResourceError> {
+        let archive = ResourceArchive::open(filename, image, tilemap, sound, music)?;
+        for warning in &archive.warnings {
+            warn!("{}", warning);
         }
+        let images: Vec<SharedImage> = (0..NUM_IMAGES).map(|i| self.image(i)).collect();
+        let tilemaps: Vec<SharedTilemap> = (0..NUM_TILEMAPS).map(|i| self.tilemap(i)).collect();
+        let sounds: Vec<SharedSound> = (0..NUM_SOUNDS).map(|i| self.sound(i)).collect();
+        let musics: Vec<SharedMusic> = (0..NUM_MUSICS).map(|i| self.music(i)).collect();
+        archive.deserialize(&images, &tilemaps, &sounds, &musics)
     }
 
     pub fn save(&mut self, filename: &str, image: bool, tilemap: bool, sound: bool, music: bool) {
-        let path = std::path::Path::new(&filename);
-        let file = std::fs::File::create(&path)
-            .unwrap_or_else(|_| panic!("Unable to open file '{}'", filename));
+        self.try_save(filename, image, tilemap, sound, music)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_save(
+        &mut self,
+        filename: &str,
+        image: bool,
+        tilemap: bool,
+        sound: bool,
+        music: bool,
+    ) -> Result<(), ResourceError> {
+        let write_error = || ResourceError::WriteFile(filename.to_string());
+        let file = File::create(Path::new(&filename))
+            .map_err(|_| ResourceError::OpenFile(filename.to_string()))?;
         let mut zip = ZipWriter::new(file);
         zip.add_directory(RESOURCE_ARCHIVE_DIRNAME, Default::default())
-            .unwrap();
+            .map_err(|_| write_error())?;
         let version_name = RESOURCE_ARCHIVE_DIRNAME.to_string() + "version";
-        zip.start_file(version_name, Default::default()).unwrap();
-        zip.write_all(PYXEL_VERSION.as_bytes()).unwrap();
+        zip.start_file(version_name, Default::default())
+            .map_err(|_| write_error())?;
+        zip.write_all(PYXEL_VERSION.as_bytes())
+            .map_err(|_| write_error())?;
 
         macro_rules! serialize {
             ($type: ty, $getter: ident, $count: expr) => {
                 for i in 0..$count {
                     if self.$getter(i).lock().is_modified() {
                         zip.start_file(<$type>::resource_name(i), Default::default())
-                            .unwrap();
+                            .map_err(|_| write_error())?;
                         zip.write_all(self.$getter(i).lock().serialize(self).as_bytes())
-                            .unwrap();
+                            .map_err(|_| write_error())?;
                     }
                 }
             };
@@ -143,24 +332,316 @@ impl Pyxel {
         if music {
             serialize!(Music, music, NUM_MUSICS);
         }
-        zip.finish().unwrap();
+        zip.finish().map_err(|_| write_error())?;
+        Ok(())
+    }
+
+    pub fn watch(&mut self, filename: &str, kind: AssetKind) {
+        match kind {
+            AssetKind::Image(image_no) if image_no >= NUM_IMAGES => {
+                panic!("Invalid image number {}", image_no)
+            }
+            AssetKind::Tilemap(tilemap_no) if tilemap_no >= NUM_TILEMAPS => {
+                panic!("Invalid tilemap number {}", tilemap_no)
+            }
+            _ => {}
+        }
+        self.resource.asset_watcher.watch(filename, kind);
+    }
+
+    pub fn unwatch(&mut self, filename: &str) {
+        self.resource.asset_watcher.unwatch(filename);
+    }
+
+    pub fn reloaded_assets(&self) -> &Vec<String> {
+        &self.resource.reloaded_assets
+    }
+
+    pub(crate) fn reload_changed_assets(&mut self) {
+        self.resource.reloaded_assets.clear();
+        for (filename, kind) in self.resource.asset_watcher.changed_assets() {
+            let result = match kind {
+                AssetKind::Image(image_no) => self
+                    .image(image_no)
+                    .lock()
+                    .load(0, 0, &filename, self.palette.colors())
+                    .map_err(|err| err.to_string()),
+                AssetKind::Tilemap(tilemap_no) => self
+                    .tilemap(tilemap_no)
+                    .lock()
+                    .load_tmx(&filename)
+                    .map_err(|err| err.to_string()),
+                AssetKind::Resource => self
+                    .try_load(&filename, true, true, true, true)
+                    .map_err(|err| err.to_string()),
+            };
+            if result.is_ok() {
+                self.resource.reloaded_assets.push(filename.clone());
+            }
+            self.resource.asset_watcher.finish_reload(&filename, result);
+        }
     }
 
     pub fn screenshot(&mut self, scale: Option<u32>) {
-        let filename = Resource::export_path();
-        let scale = u32::max(scale.unwrap_or(self.resource.capture_scale), 1);
-        self.screen.lock().save(&filename, &self.colors, scale);
+        if let Err(err) = self.try_screenshot(scale) {
+            warn!("Unable to save the screenshot: {}", err);
+        }
+    }
+
+    pub fn try_screenshot(&mut self, scale: Option<u32>) -> Result<(), ImageError> {
+        let filename = self.resource.export_path();
+        let scale = scale
+            .unwrap_or(self.resource.capture_scale)
+            .clamp(1, MAX_SAVE_SCALE);
+        let result = self
+            .screen
+            .lock()
+            .save(&filename, self.palette.display_colors(), scale);
         self.system.disable_next_frame_skip();
+        result
+    }
+
+    pub fn set_capture_dir(&mut self, dir: Option<&str>) {
+        self.resource.capture_dir = dir.map(ToString::to_string);
+    }
+
+    pub fn set_app_id(&mut self, app_id: &str) {
+        self.resource.app_id = app_id.to_string();
+    }
+
+    pub fn storage(&self) -> Result<Storage, StorageError> {
+        // The save directory follows the initial title, so later title changes keep the saves
+        Storage::new(&self.resource.app_id)
     }
 
     pub fn reset_capture(&mut self) {
         self.resource.screencast.reset();
     }
 
+    pub fn start_capture(&mut self, filename: &str) -> Result<(), AudioError> {
+        // The screencast restarts too, so the GIF and WAV begin on the same frame
+        self.resource.screencast.reset();
+        let filename = add_file_extension(filename, ".wav");
+        self.audio.start_capture(Path::new(&filename))
+    }
+
+    pub fn stop_capture(&mut self) -> Result<CaptureStats, AudioError> {
+        let filename = self
+            .audio
+            .capture_path()
+            .map(|path| path.with_extension("gif").display().to_string());
+        let stats = self.audio.stop_capture()?;
+        if let Some(filename) = filename {
+            let scale = self.resource.capture_scale;
+            if let Err(err) = self.resource.screencast.save(&filename, scale) {
+                warn!("Unable to save the screencast: {}", err);
+            }
+            self.system.disable_next_frame_skip();
+        }
+        Ok(stats)
+    }
+
     pub fn screencast(&mut self, scale: Option<u32>) {
-        let filename = Resource::export_path();
-        let scale = u32::max(scale.unwrap_or(self.resource.capture_scale), 1);
-        self.resource.screencast.save(&filename, scale);
+        if let Err(err) = self.try_screencast(scale) {
+            warn!("Unable to save the screencast: {}", err);
+        }
+    }
+
+    pub fn try_screencast(&mut self, scale: Option<u32>) -> Result<(), ImageError> {
+        let filename = self.resource.export_path();
+        let scale = scale
+            .unwrap_or(self.resource.capture_scale)
+            .clamp(1, MAX_SAVE_SCALE);
+        let result = self.resource.screencast.save(&filename, scale);
         self.system.disable_next_frame_skip();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{IMAGE_SIZE, TILEMAP_SIZE};
+
+    fn load_archive(
+        filename: &str,
+    ) -> (
+        Vec<SharedImage>,
+        Vec<SharedTilemap>,
+        Vec<SharedSound>,
+        Vec<SharedMusic>,
+    ) {
+        let images: Vec<SharedImage> = (0..NUM_IMAGES)
+            .map(|_| Image::new(IMAGE_SIZE, IMAGE_SIZE))
+            .collect();
+        let tilemaps: Vec<SharedTilemap> = (0..NUM_TILEMAPS)
+            .map(|_| Tilemap::new(TILEMAP_SIZE, TILEMAP_SIZE, images[0].clone()))
+            .collect();
+        let sounds: Vec<SharedSound> = (0..NUM_SOUNDS).map(|_| Sound::new()).collect();
+        let musics: Vec<SharedMusic> = (0..NUM_MUSICS).map(|_| Music::new()).collect();
+        let archive = ResourceArchive::open(filename, true, true, true, true).unwrap();
+        assert!(archive.warnings.is_empty());
+        archive
+            .deserialize(&images, &tilemaps, &sounds, &musics)
+            .unwrap();
+        (images, tilemaps, sounds, musics)
+    }
+
+    fn example_asset(name: &str) -> String {
+        format!(
+            "{}/../../pyxel/examples/assets/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    #[test]
+    fn load_pyxres_1_5() {
+        let (images, tilemaps, sounds, musics) = load_archive(&example_asset("sample.pyxres"));
+        let image = images[0].lock();
+        assert_eq!(image.canvas.data[0][8..12], [0, 10, 0, 12]);
+        assert_eq!(image.canvas.data[16][..2], [8, 2]);
+        drop(image);
+        assert_eq!(images[1].lock().canvas.data[0][0], 7);
+
+        assert_eq!(tilemaps[0].lock().canvas.data[0][..2], [(10, 6), (11, 6)]);
+        let tilemap = tilemaps[1].lock();
+        assert_eq!(tilemap.canvas.data[0][..3], [(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(tilemap.image.data_ptr(), images[1].data_ptr());
+        drop(tilemap);
+
+        let sound = sounds[1].lock();
+        assert_eq!(sound.notes[..2], [-1, 21]);
+        assert_eq!(sound.tones, [1]);
+        assert_eq!(sound.volumes, [6]);
+        assert_eq!(sound.effects[..3], [0, 0, 3]);
+        assert_eq!(sound.speed, 25);
+        drop(sound);
+        assert_eq!(
+            musics[0].lock().sequences,
+            [vec![0, 1], vec![2, 3], vec![4], vec![]]
+        );
+    }
+
+    #[test]
+    fn load_pyxres_1_1() {
+        let (images, tilemaps, sounds, musics) = load_archive(&example_asset("jump_game.pyxres"));
+        assert_eq!(images[0].lock().canvas.data[0][0], 12);
+        assert_eq!(images[0].lock().canvas.data[16][7], 9);
+        assert_eq!(tilemaps[0].lock().canvas.data[0][0], (0, 0));
+
+        let sound = sounds[0].lock();
+        assert_eq!(sound.notes[..3], [36, 28, 31]);
+        assert_eq!(sound.tones, [0]);
+        assert_eq!(sound.volumes, [2]);
+        assert_eq!(sound.effects, [3]);
+        assert_eq!(sound.speed, 30);
+        drop(sound);
+        assert_eq!(
+            musics[0].lock().sequences,
+            [vec![0], vec![1], vec![2], vec![]]
+        );
+
+        let tilemap = Tilemap::new(2, 2, images[0].clone());
+        tilemap
+            .lock()
+            .deserialize(&images, 11000, "001021\n0")
+            .unwrap();
+        assert_eq!(tilemap.lock().canvas.data[0], [(1, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn load_newer_version() {
+        let filename = std::env::temp_dir().join("pyxel_test_newer_version.pyxres");
+        let filename = filename.to_str().unwrap();
+        let mut zip = ZipWriter::new(File::create(filename).unwrap());
+        for (name, contents) in [
+            ("version", "9.0.0"),
+            ("palette", "000000"),
+            ("sound00", "1c\n0\n7\n0\n10"),
+        ] {
+            zip.start_file(
+                RESOURCE_ARCHIVE_DIRNAME.to_string() + name,
+                Default::default(),
+            )
+            .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let archive = ResourceArchive::open(filename, true, true, true, true).unwrap();
+        assert_eq!(
+            archive.warnings,
+            [
+                "skipped unknown resource 'pyxel_resource/palette'".to_string(),
+                format!(
+                    "resource file version '9.0.0' is newer than '{}'",
+                    PYXEL_VERSION
+                ),
+            ]
+        );
+        let sounds: Vec<SharedSound> = (0..NUM_SOUNDS).map(|_| Sound::new()).collect();
+        archive.deserialize(&[], &[], &sounds, &[]).unwrap();
+        assert_eq!(sounds[0].lock().notes, [28]);
+        assert_eq!(sounds[0].lock().speed, 10);
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn load_invalid_size() {
+        let images = [Image::new(2, 2)];
+        let err = images[0]
+            .lock()
+            .deserialize(&images, 15000, "000\n000")
+            .unwrap_err();
+        assert_eq!(err, "image data is 3x2 but the image is 2x2");
+    }
+
+    #[test]
+    fn load_invalid_entry() {
+        let filename = std::env::temp_dir().join("pyxel_test_invalid_entry.pyxres");
+        let filename = filename.to_str().unwrap();
+        let mut zip = ZipWriter::new(File::create(filename).unwrap());
+        for (name, contents) in [
+            ("version", PYXEL_VERSION),
+            ("image0", "12\n34"),
+            ("sound00", "1c\n0\n7\n0\n10"),
+            ("sound01", "zz\n0\n7\n0\n10"),
+        ] {
+            zip.start_file(
+                RESOURCE_ARCHIVE_DIRNAME.to_string() + name,
+                Default::default(),
+            )
+            .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let archive = ResourceArchive::open(filename, true, false, true, false).unwrap();
+        let images = [Image::new(2, 2)];
+        images[0].lock().cls(7);
+        let sounds: Vec<SharedSound> = (0..NUM_SOUNDS).map(|_| Sound::new()).collect();
+        sounds[0].lock().set("c2", "t", "5", "n", 30);
+        let err = archive.deserialize(&images, &[], &sounds, &[]).unwrap_err();
+        assert!(
+            matches!(err, ResourceError::InvalidItem(name, _) if name == Sound::resource_name(1))
+        );
+        assert_eq!(images[0].lock().canvas.data, [[7, 7], [7, 7]]);
+        assert_eq!(sounds[0].lock().notes, [24]);
+        assert_eq!(sounds[0].lock().speed, 30);
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn export_path() {
+        let resource = Resource::new("test", 30, 1, 10);
+        let path = PathBuf::from(resource.export_path());
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("pyxel-"));
+        let dir = path.parent().unwrap();
+        assert!(dir.as_os_str().is_empty() || dir.is_dir());
     }
 }
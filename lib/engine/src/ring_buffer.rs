@@ -0,0 +1,76 @@
+use std::sync::atomic::{AtomicI16, AtomicUsize, Ordering};
+
+// Safe to share between exactly one pushing and one popping thread
+pub struct RingBuffer {
+    data: Box<[AtomicI16]>,
+    read_pos: AtomicUsize,
+    write_pos: AtomicUsize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: (0..capacity.max(1)).map(|_| AtomicI16::new(0)).collect(),
+            read_pos: AtomicUsize::new(0),
+            write_pos: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn len(&self) -> usize {
+        let write_pos = self.write_pos.load(Ordering::Acquire);
+        write_pos.wrapping_sub(self.read_pos.load(Ordering::Acquire))
+    }
+
+    pub fn push(&self, values: &[i16]) -> usize {
+        let write_pos = self.write_pos.load(Ordering::Relaxed);
+        let read_pos = self.read_pos.load(Ordering::Acquire);
+        let count = values
+            .len()
+            .min(self.capacity() - write_pos.wrapping_sub(read_pos));
+        for (i, value) in values[..count].iter().enumerate() {
+            self.data[write_pos.wrapping_add(i) % self.capacity()].store(*value, Ordering::Relaxed);
+        }
+        self.write_pos
+            .store(write_pos.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    pub fn pop(&self, out: &mut [i16]) -> usize {
+        let read_pos = self.read_pos.load(Ordering::Relaxed);
+        let write_pos = self.write_pos.load(Ordering::Acquire);
+        let count = out.len().min(write_pos.wrapping_sub(read_pos));
+        for (i, value) in out[..count].iter_mut().enumerate() {
+            *value = self.data[read_pos.wrapping_add(i) % self.capacity()].load(Ordering::Relaxed);
+        }
+        self.read_pos
+            .store(read_pos.wrapping_add(count), Ordering::Release);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_pop() {
+        let ring_buffer = RingBuffer::new(4);
+        assert_eq!(ring_buffer.len(), 0);
+        assert_eq!(ring_buffer.push(&[1, 2, 3]), 3);
+        assert_eq!(ring_buffer.push(&[4, 5]), 1);
+        assert_eq!(ring_buffer.len(), 4);
+
+        let mut out = [0; 3];
+        assert_eq!(ring_buffer.pop(&mut out), 3);
+        assert_eq!(out, [1, 2, 3]);
+        assert_eq!(ring_buffer.push(&[6, 7, 8]), 3);
+        let mut out = [0; 8];
+        assert_eq!(ring_buffer.pop(&mut out), 4);
+        assert_eq!(out[..4], [4, 6, 7, 8]);
+        assert_eq!(ring_buffer.pop(&mut out), 0);
+    }
+}
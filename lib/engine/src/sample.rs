@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::platform::AudioError;
+use crate::settings::SAMPLE_RATE;
+
+#[derive(Clone)]
+pub struct Sample {
+    pub(crate) data: Arc<[f32]>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn resample(data: &[f32], src_rate: u32, dst_rate: u32) -> Vec<f32> {
+    if src_rate == dst_rate || data.len() < 2 {
+        return data.to_vec();
+    }
+    let len = (data.len() as u64 * dst_rate as u64 / src_rate as u64) as usize;
+    let step = src_rate as f64 / dst_rate as f64;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let next = data[(index + 1).min(data.len() - 1)];
+            data[index] + (next - data[index]) * frac
+        })
+        .collect()
+}
+
+pub fn wav_header(sample_rate: u32, num_channels: u32, data_size: u32) -> Vec<u8> {
+    let block_size = num_channels * 2;
+    let mut data = Vec::with_capacity(44);
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + data_size).to_le_bytes());
+    data.extend_from_slice(b"WAVEfmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&(num_channels as u16).to_le_bytes());
+    data.extend_from_slice(&sample_rate.to_le_bytes());
+    data.extend_from_slice(&(sample_rate * block_size).to_le_bytes());
+    data.extend_from_slice(&(block_size as u16).to_le_bytes());
+    data.extend_from_slice(&16u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&data_size.to_le_bytes());
+    data
+}
+
+pub fn write_wav(path: &Path, sample_rate: u32, samples: &[i16]) -> Result<(), AudioError> {
+    let data_size = samples.len() as u32 * 2;
+    let mut data = wav_header(sample_rate, 1, data_size);
+    data.reserve(data_size as usize);
+    for sample in samples {
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+    fs::write(path, data).map_err(|_| AudioError::WriteFile(path.display().to_string()))
+}
+
+impl Sample {
+    pub fn new(data: &[f32]) -> Self {
+        Self { data: data.into() }
+    }
+
+    pub fn from_wav(path: &Path) -> Result<Self, AudioError> {
+        let data = fs::read(path).map_err(|_| AudioError::ReadFile(path.display().to_string()))?;
+        Self::from_wav_data(&data)
+    }
+
+    pub fn from_wav_data(data: &[u8]) -> Result<Self, AudioError> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(AudioError::InvalidWav("missing RIFF header".to_string()));
+        }
+        let mut format = None;
+        let mut samples = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let chunk_size = read_u32(data, offset + 4) as usize;
+            let start = offset + 8;
+            let end = start.saturating_add(chunk_size).min(data.len());
+            match &data[offset..offset + 4] {
+                b"fmt " if end - start >= 16 => {
+                    format = Some((
+                        read_u16(data, start),
+                        read_u16(data, start + 2),
+                        read_u32(data, start + 4),
+                        read_u16(data, start + 14),
+                    ));
+                }
+                b"data" => samples = Some(&data[start..end]),
+                _ => {}
+            }
+            offset = start.saturating_add(chunk_size + chunk_size % 2);
+        }
+        let (audio_format, num_channels, sample_rate, bits_per_sample) =
+            format.ok_or_else(|| AudioError::InvalidWav("missing fmt chunk".to_string()))?;
+        let samples =
+            samples.ok_or_else(|| AudioError::InvalidWav("missing data chunk".to_string()))?;
+        if audio_format != 1 || bits_per_sample != 16 {
+            return Err(AudioError::InvalidWav(
+                "only 16-bit PCM is supported".to_string(),
+            ));
+        }
+        if !(1..=2).contains(&num_channels) || sample_rate == 0 {
+            return Err(AudioError::InvalidWav(format!(
+                "unsupported format {} channels at {} Hz",
+                num_channels, sample_rate
+            )));
+        }
+        let frame_size = num_channels as usize * 2;
+        let frames: Vec<f32> = samples
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                let sum: f32 = frame
+                    .chunks_exact(2)
+                    .map(|value| i16::from_le_bytes([value[0], value[1]]) as f32 / 32768.0)
+                    .sum();
+                sum / num_channels as f32
+            })
+            .collect();
+        Ok(Self::new(&resample(&frames, sample_rate, SAMPLE_RATE)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_data(num_channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples.len() as u32 * 2).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&num_channels.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&(sample_rate * num_channels as u32 * 2).to_le_bytes());
+        data.extend_from_slice(&(num_channels * 2).to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+        for sample in samples {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn from_wav_data() {
+        let sample = Sample::from_wav_data(&wav_data(1, SAMPLE_RATE, &[0, 16384, -32768])).unwrap();
+        assert_eq!(&*sample.data, &[0.0, 0.5, -1.0]);
+
+        let sample =
+            Sample::from_wav_data(&wav_data(2, SAMPLE_RATE, &[16384, 0, -16384, -16384])).unwrap();
+        assert_eq!(&*sample.data, &[0.25, -0.5]);
+
+        let sample = Sample::from_wav_data(&wav_data(1, SAMPLE_RATE / 2, &[0, 16384])).unwrap();
+        assert_eq!(&*sample.data, &[0.0, 0.25, 0.5, 0.5]);
+
+        let sample = Sample::from_wav_data(&wav_data(1, SAMPLE_RATE * 2, &[0; 100])).unwrap();
+        assert_eq!(sample.len(), 50);
+    }
+
+    #[test]
+    fn from_wav_data_invalid() {
+        assert!(matches!(
+            Sample::from_wav_data(b"RIFF"),
+            Err(AudioError::InvalidWav(_))
+        ));
+        let mut data = wav_data(1, SAMPLE_RATE, &[0]);
+        data[34] = 8;
+        assert!(matches!(
+            Sample::from_wav_data(&data),
+            Err(AudioError::InvalidWav(_))
+        ));
+        let data = wav_data(3, SAMPLE_RATE, &[0, 0, 0]);
+        assert!(matches!(
+            Sample::from_wav_data(&data),
+            Err(AudioError::InvalidWav(_))
+        ));
+        let data = wav_data(1, SAMPLE_RATE, &[0]);
+        assert!(matches!(
+            Sample::from_wav_data(&data[..36]),
+            Err(AudioError::InvalidWav(_))
+        ));
+        assert!(matches!(
+            Sample::from_wav(Path::new("missing.wav")),
+            Err(AudioError::ReadFile(_))
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::palette::Palette;
+use crate::settings::MAX_COLORS;
+use crate::types::{Color, Rgb8};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScanlineEdge {
+    Wrap,
+    Clamp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ScanlineParams {
+    pub x_offset: i32,
+    pub edge: ScanlineEdge,
+    pub palette: Option<u32>,
+}
+
+impl Default for ScanlineParams {
+    fn default() -> Self {
+        Self {
+            x_offset: 0,
+            edge: ScanlineEdge::Wrap,
+            palette: None,
+        }
+    }
+}
+
+impl ScanlineParams {
+    pub fn shift_row(&self, out: &mut Vec<Color>, row: &[Color], left: usize, width: usize) {
+        // Offsets are resolved in 64 bits so no value can land outside the row
+        let row_width = row.len() as i64;
+        out.clear();
+        out.extend((left..left + width).map(|x| {
+            let x = x as i64 - self.x_offset as i64;
+            let x = match self.edge {
+                ScanlineEdge::Wrap => x.rem_euclid(row_width),
+                ScanlineEdge::Clamp => x.clamp(0, row_width - 1),
+            };
+            row[x as usize]
+        }));
+    }
+}
+
+pub type ScanlineEffect = Box<dyn FnMut(u32) -> ScanlineParams>;
+
+pub struct Scanlines {
+    pub params: Vec<ScanlineParams>,
+    pub palettes: Vec<[Rgb8; MAX_COLORS as usize]>,
+}
+
+impl Scanlines {
+    pub const fn new() -> Self {
+        Self {
+            params: Vec::new(),
+            palettes: Vec::new(),
+        }
+    }
+
+    pub fn set_palettes(&mut self, palettes: &[Palette]) {
+        self.palettes = palettes
+            .iter()
+            .map(|palette| *palette.display_colors())
+            .collect();
+    }
+
+    pub fn update(&mut self, effect: &mut ScanlineEffect, height: u32) {
+        self.params.clear();
+        self.params.extend((0..height).map(effect));
+    }
+
+    pub fn params(&self, row: usize) -> ScanlineParams {
+        self.params.get(row).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shift_row() {
+        let row = [0, 1, 2, 3, 4];
+        let mut out = Vec::new();
+        let shift = |x_offset, edge, out: &mut Vec<Color>| {
+            let params = ScanlineParams {
+                x_offset,
+                edge,
+                palette: None,
+            };
+            params.shift_row(out, &row, 1, 3);
+            out.clone()
+        };
+        assert_eq!(shift(0, ScanlineEdge::Wrap, &mut out), [1, 2, 3]);
+        assert_eq!(shift(2, ScanlineEdge::Wrap, &mut out), [4, 0, 1]);
+        assert_eq!(shift(-3, ScanlineEdge::Wrap, &mut out), [4, 0, 1]);
+        assert_eq!(shift(2, ScanlineEdge::Clamp, &mut out), [0, 0, 1]);
+        assert_eq!(shift(-2, ScanlineEdge::Clamp, &mut out), [3, 4, 4]);
+        for x_offset in [i32::MIN, i32::MIN + 1, i32::MAX] {
+            assert_eq!(shift(x_offset, ScanlineEdge::Wrap, &mut out).len(), 3);
+            assert_eq!(shift(x_offset, ScanlineEdge::Clamp, &mut out).len(), 3);
+        }
+    }
+}
@@ -4,20 +4,33 @@ use std::fs::File;
 use gif::{DisposalMethod, Encoder, Frame, Repeat};
 use indexmap::IndexMap;
 
-use crate::rectarea::RectArea;
-use crate::settings::NUM_COLORS;
+use crate::geometry::Recti;
+use crate::image::ImageError;
+use crate::settings::MAX_COLORS;
 use crate::types::{Color, Rgb8};
 use crate::utils::add_file_extension;
 
 const TRANSPARENT: Rgb8 = 0xffffffff;
+const GIF_MAX_FPS: u32 = 50;
+const GIF_MAX_COLORS: usize = 256;
 
 struct Screen {
     image: Vec<Vec<Color>>,
-    colors: [Rgb8; NUM_COLORS as usize],
+    colors: [Rgb8; MAX_COLORS as usize],
     frame_count: u32,
+    fps: u32,
 }
 
 impl Screen {
+    fn new(fps: u32) -> Self {
+        Self {
+            image: Vec::new(),
+            colors: [0; MAX_COLORS as usize],
+            frame_count: 0,
+            fps,
+        }
+    }
+
     fn width(&self) -> u32 {
         self.image[0].len() as u32
     }
@@ -42,8 +55,18 @@ impl Screen {
     }
 }
 
+fn capture_fps(fps: u32) -> u32 {
+    // Uncapped frames are shown at the shortest delay that viewers reliably honor
+    if fps == 0 {
+        GIF_MAX_FPS
+    } else {
+        fps
+    }
+}
+
 pub struct Screencast {
     fps: u32,
+    capture_sec: u32,
     max_screens: u32,
     screens: Vec<Screen>,
     capture_start_index: u32,
@@ -52,16 +75,11 @@ pub struct Screencast {
 
 impl Screencast {
     pub fn new(fps: u32, capture_sec: u32) -> Self {
-        let max_screens = fps * capture_sec;
-        let screens = (0..max_screens)
-            .map(|_| Screen {
-                image: Vec::new(),
-                colors: [0; NUM_COLORS as usize],
-                frame_count: 0,
-            })
-            .collect();
+        let max_screens = capture_fps(fps) * capture_sec;
+        let screens = (0..max_screens).map(|_| Screen::new(fps)).collect();
         Self {
             fps,
+            capture_sec,
             max_screens,
             screens,
             capture_start_index: 0,
@@ -74,14 +92,33 @@ impl Screencast {
     }
 
     fn screen_delay(&self, index: u32) -> u16 {
-        let frame_count = self.screen(index).frame_count;
+        let screen = self.screen(index);
         let next_frame_count = self.screen(index + 1).frame_count;
-        let num_elapsed_frames = if frame_count > next_frame_count {
+        let num_elapsed_frames = if screen.frame_count > next_frame_count {
             1
         } else {
-            next_frame_count - frame_count
+            next_frame_count - screen.frame_count
         };
-        (100.0 / self.fps as f64 * num_elapsed_frames as f64 + 0.5) as u16
+        (100.0 / capture_fps(screen.fps) as f64 * num_elapsed_frames as f64 + 0.5) as u16
+    }
+
+    pub fn set_fps(&mut self, fps: u32) {
+        self.fps = fps;
+        let max_screens = capture_fps(fps) * self.capture_sec;
+        if max_screens == self.max_screens {
+            return;
+        }
+        // The buffer keeps covering capture_sec seconds, and the newest screens survive a shrink
+        self.screens.rotate_left(self.capture_start_index as usize);
+        if self.num_captured_screens > max_screens {
+            self.screens
+                .drain(..(self.num_captured_screens - max_screens) as usize);
+            self.num_captured_screens = max_screens;
+        }
+        self.screens
+            .resize_with(max_screens as usize, || Screen::new(fps));
+        self.max_screens = max_screens;
+        self.capture_start_index = 0;
     }
 
     pub fn reset(&mut self) {
@@ -92,7 +129,7 @@ impl Screencast {
     pub fn capture(
         &mut self,
         image: &[Vec<Color>],
-        colors: &[Rgb8; NUM_COLORS as usize],
+        colors: &[Rgb8; MAX_COLORS as usize],
         frame_count: u32,
     ) {
         if self.screens.is_empty() {
@@ -107,16 +144,17 @@ impl Screencast {
         screen.colors = *colors;
         screen.image = image.to_vec();
         screen.frame_count = frame_count;
+        screen.fps = self.fps;
         self.num_captured_screens += 1;
     }
 
-    pub fn save(&mut self, filename: &str, scale: u32) {
+    pub fn save(&mut self, filename: &str, scale: u32) -> Result<(), ImageError> {
         if self.num_captured_screens == 0 {
-            return;
+            return Ok(());
         }
         let filename = add_file_extension(filename, ".gif");
-        let mut file = File::create(&filename)
-            .unwrap_or_else(|_| panic!("Unable to open file '{}'", filename));
+        let save_error = || ImageError::Save(filename.clone());
+        let mut file = File::create(&filename).map_err(|_| save_error())?;
         let screen = self.screen(0);
         let width = screen.width();
         let height = screen.height();
@@ -126,13 +164,15 @@ impl Screencast {
             (height * scale) as u16,
             &[],
         )
-        .unwrap();
-        encoder.set_repeat(Repeat::Infinite).unwrap();
+        .map_err(|_| save_error())?;
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|_| save_error())?;
 
         // Write first frame
         let mut base_image = screen.to_rgb_image();
         let (rect, palette, buffer) =
-            Self::make_gif_buffer(RectArea::new(0, 0, width, height), &base_image, scale);
+            Self::make_gif_buffer(Recti::new(0, 0, width, height), &base_image, scale);
         encoder
             .write_frame(&Frame {
                 delay: self.screen_delay(0),
@@ -147,7 +187,7 @@ impl Screencast {
                 palette: Some(palette),
                 buffer: Cow::Borrowed(&buffer),
             })
-            .unwrap();
+            .map_err(|_| save_error())?;
 
         // Write subsequent frames
         for i in 1..self.num_captured_screens {
@@ -169,15 +209,16 @@ impl Screencast {
                     palette: Some(palette),
                     buffer: Cow::Borrowed(&buffer),
                 })
-                .unwrap();
+                .map_err(|_| save_error())?;
         }
         self.reset();
+        Ok(())
     }
 
     fn make_diff_image(
         base_image: &mut Vec<Vec<Rgb8>>,
         new_image: &[Vec<Rgb8>],
-    ) -> (RectArea, Vec<Vec<Rgb8>>) {
+    ) -> (Recti, Vec<Vec<Rgb8>>) {
         let mut min_x = i16::MAX;
         let mut min_y = i16::MAX;
         let mut max_x = i16::MIN;
@@ -203,14 +244,14 @@ impl Screencast {
             diff_image.push(diff_line);
         }
         if min_x > max_x || min_y > max_y {
-            return (RectArea::new(0, 0, 0, 0), Vec::new());
+            return (Recti::new(0, 0, 0, 0), Vec::new());
         }
         diff_image = diff_image[min_y as usize..=max_y as usize].to_vec();
         for diff_line in &mut diff_image {
             *diff_line = diff_line[min_x as usize..=max_x as usize].to_vec();
         }
         (
-            RectArea::new(
+            Recti::new(
                 min_x as i32,
                 min_y as i32,
                 diff_image[0].len() as u32,
@@ -220,30 +261,29 @@ impl Screencast {
         )
     }
 
-    fn make_gif_buffer(
-        rect: RectArea,
-        image: &[Vec<Rgb8>],
-        scale: u32,
-    ) -> (RectArea, Vec<u8>, Vec<u8>) {
+    fn make_gif_buffer(rect: Recti, image: &[Vec<Rgb8>], scale: u32) -> (Recti, Vec<u8>, Vec<u8>) {
         let mut color_table = IndexMap::<Rgb8, u8>::new();
         color_table.insert(TRANSPARENT, 0);
-        let mut num_colors = 1;
         let mut buffer = Vec::new();
         for line in image {
             for rgb in line {
-                if let Some(index) = color_table.get(rgb) {
-                    buffer.push(*index);
-                } else {
-                    color_table.insert(*rgb, num_colors);
-                    buffer.push(num_colors);
-                    num_colors += 1;
-                }
+                let index = match color_table.get(rgb) {
+                    Some(index) => *index,
+                    None if color_table.len() < GIF_MAX_COLORS => {
+                        let index = color_table.len() as u8;
+                        color_table.insert(*rgb, index);
+                        index
+                    }
+                    // The table is full, so the color falls back to the closest entry
+                    None => Self::nearest_color_index(&color_table, *rgb),
+                };
+                buffer.push(index);
             }
         }
 
         let rect = if buffer.is_empty() {
             buffer = vec![0];
-            RectArea::new(0, 0, 1, 1)
+            Recti::new(0, 0, 1, 1)
         } else {
             let width = rect.width();
             let height = rect.height();
@@ -257,7 +297,7 @@ impl Screencast {
                 }
             }
             buffer = scaled_buffer;
-            RectArea::new(
+            Recti::new(
                 rect.left() * scale as i32,
                 rect.top() * scale as i32,
                 scaled_width,
@@ -279,4 +319,74 @@ impl Screencast {
         }
         (rect, palette, buffer)
     }
+
+    fn nearest_color_index(color_table: &IndexMap<Rgb8, u8>, rgb: Rgb8) -> u8 {
+        let distance = |other: Rgb8| {
+            (0..3)
+                .map(|i| {
+                    let diff =
+                        ((rgb >> (i * 8)) & 0xff) as i32 - ((other >> (i * 8)) & 0xff) as i32;
+                    diff * diff
+                })
+                .sum::<i32>()
+        };
+        color_table
+            .iter()
+            .filter(|(other, _)| **other != TRANSPARENT)
+            .min_by_key(|(other, _)| distance(**other))
+            .map_or(0, |(_, index)| *index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_fps() {
+        let mut screencast = Screencast::new(4, 2);
+        let colors = [0; MAX_COLORS as usize];
+        for frame_count in 0..10 {
+            screencast.capture(&[vec![frame_count as Color]], &colors, frame_count);
+        }
+        assert_eq!(screencast.screen(0).frame_count, 2);
+
+        screencast.set_fps(2);
+        assert_eq!(screencast.screens.len(), 4);
+        assert_eq!(screencast.num_captured_screens, 4);
+        let frame_counts: Vec<u32> = (0..4).map(|i| screencast.screen(i).frame_count).collect();
+        assert_eq!(frame_counts, [6, 7, 8, 9]);
+
+        screencast.set_fps(0);
+        assert_eq!(screencast.screens.len(), (GIF_MAX_FPS * 2) as usize);
+        assert_eq!(screencast.num_captured_screens, 4);
+        screencast.capture(&[vec![0]], &colors, 10);
+        assert_eq!(screencast.screen(4).frame_count, 10);
+        assert_eq!(screencast.screen(4).fps, 0);
+    }
+
+    #[test]
+    fn save_error() {
+        let mut screencast = Screencast::new(30, 1);
+        screencast.capture(&[vec![1, 2], vec![3, 4]], &[0; MAX_COLORS as usize], 0);
+        let filename = std::env::temp_dir().join("pyxel_missing_dir/capture");
+        assert!(matches!(
+            screencast.save(filename.to_str().unwrap(), 1),
+            Err(ImageError::Save(_))
+        ));
+    }
+
+    #[test]
+    fn make_gif_buffer_256_colors() {
+        let image: Vec<Vec<Rgb8>> = vec![(0..256).map(|i| i * 0x010101).collect()];
+        let (rect, palette, buffer) =
+            Screencast::make_gif_buffer(Recti::new(0, 0, 256, 1), &image, 1);
+        assert_eq!(rect, Recti::new(0, 0, 256, 1));
+        assert_eq!(palette.len(), GIF_MAX_COLORS * 3);
+        assert_eq!(buffer.len(), 256);
+        assert_eq!(&buffer[..255], (1..=255).collect::<Vec<u8>>().as_slice());
+        // The last color no longer fits and maps to its closest neighbor
+        assert_eq!(buffer[255], 255);
+        assert_eq!(&palette[255 * 3..], &[0xfe, 0xfe, 0xfe]);
+    }
 }
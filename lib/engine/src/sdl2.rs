@@ -1,13 +1,20 @@
-use std::cmp::min;
+use std::sync::Arc;
+use std::time::Instant;
 
+use log::{debug, error, info, warn};
+#[cfg(feature = "raw-window-handle")]
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use sdl2::audio::{
     AudioCallback as SdlAudioCallback, AudioDevice as SdlAudioDevice,
-    AudioSpecDesired as SdlAudioSpecDesired,
+    AudioSpecDesired as SdlAudioSpecDesired, AudioStatus as SdlAudioStatus,
 };
 use sdl2::controller::{Axis as SdlAxis, Button as SdlButton, GameController as SdlGameController};
-use sdl2::event::{Event as SdlEvent, WindowEvent as SdlWindowEvent};
+use sdl2::event::{
+    DisplayEvent as SdlDisplayEvent, Event as SdlEvent, WindowEvent as SdlWindowEvent,
+};
 use sdl2::hint;
-use sdl2::mouse::MouseButton as SdlMouseButton;
+use sdl2::joystick::{HatState as SdlHatState, Joystick as SdlJoystick};
+use sdl2::mouse::{MouseButton as SdlMouseButton, MouseWheelDirection as SdlMouseWheelDirection};
 use sdl2::pixels::{Color as SdlColor, PixelFormatEnum as SdlPixelFormat};
 use sdl2::rect::Rect as SdlRect;
 use sdl2::render::{Texture as SdlTexture, WindowCanvas as SdlCanvas};
@@ -15,22 +22,36 @@ use sdl2::surface::Surface as SdlSurface;
 use sdl2::video::FullscreenType as SdlFullscreenType;
 use sdl2::AudioSubsystem as SdlAudio;
 use sdl2::EventPump as SdlEventPump;
+use sdl2::GameControllerSubsystem as SdlGameControllerSubsystem;
+use sdl2::JoystickSubsystem as SdlJoystickSubsystem;
 use sdl2::Sdl as SdlContext;
 use sdl2::TimerSubsystem as SdlTimer;
 
-use crate::event::{ControllerAxis, ControllerButton, Event, MouseButton};
-use crate::platform::{AudioCallback, Platform};
+use crate::audio_monitor::AudioMonitor;
+use crate::event::{
+    self, ControllerAxis, ControllerButton, Event, HatState, MouseButton, TimedEvent,
+};
+use crate::geometry::Recti;
+use crate::platform::{
+    blend_overlay, write_rgb24, AudioCallback, AudioError, AuxWindowEvent, AuxWindowId,
+    DisplayEffect, FullscreenMode, Platform, PlatformBuilder, PlatformError, PostRenderHook,
+    RgbTables, ScreenFit, ScreenLayout, ScreenMode,
+};
+use crate::player_slots::PlayerSlots;
+use crate::scanline::Scanlines;
 use crate::types::{Color, Rgb8};
 
 struct AudioContextHolder {
     audio: shared_type!(dyn AudioCallback + Send),
+    monitor: Arc<AudioMonitor>,
+    history: Vec<i16>,
 }
 
 impl SdlAudioCallback for AudioContextHolder {
     type Channel = i16;
 
     fn callback(&mut self, out: &mut [i16]) {
-        self.audio.lock().update(out);
+        self.monitor.fill(&self.audio, &mut self.history, out);
     }
 }
 
@@ -40,80 +61,206 @@ pub struct Sdl2 {
     sdl_timer: SdlTimer,
     sdl_canvas: SdlCanvas,
     sdl_texture: SdlTexture,
-    #[allow(dead_code)]
+    sdl_game_controller: SdlGameControllerSubsystem,
     sdl_game_controllers: Vec<SdlGameController>,
+    player_slots: PlayerSlots,
+    sdl_joystick: SdlJoystickSubsystem,
+    sdl_joysticks: Vec<SdlJoystick>,
     sdl_audio: SdlAudio,
     sdl_audio_device: Option<SdlAudioDevice<AudioContextHolder>>,
+    sdl_audio_spec: Option<SdlAudioSpecDesired>,
+    audio_callback: Option<shared_type!(dyn AudioCallback + Send)>,
+    audio_monitor: Arc<AudioMonitor>,
     screen_width: u32,
     screen_height: u32,
+    screen_layout: ScreenLayout,
+    screen_fit: ScreenFit,
+    pixel_aspect: f64,
+    screen_mode: ScreenMode,
+    sdl_upscaled_texture: Option<((u32, u32), SdlTexture)>,
+    display_index: i32,
+    display_effect: DisplayEffect,
+    rendered_colors: Vec<Rgb8>,
+    needs_full_render: bool,
+    overlay: Option<Vec<u8>>,
+    is_raw_mouse: bool,
+    is_mouse_on_screen: bool,
+    has_precise_wheel: bool,
     mouse_x: i32,
     mouse_y: i32,
+    post_render_hook: Option<PostRenderHook<SdlCanvas>>,
+    aux_windows: Vec<AuxWindow>,
+    pending_events: Vec<TimedEvent>,
+    should_coalesce_mouse_motion: bool,
+    start_time: Instant,
+    last_event_us: u64,
+}
+
+struct AuxWindow {
+    id: AuxWindowId,
+    sdl_canvas: SdlCanvas,
+    sdl_texture: SdlTexture,
+    width: u32,
+    height: u32,
 }
 
 impl Platform for Sdl2 {
-    fn new(title: &str, width: u32, height: u32, display_ratio: f64) -> Self {
-        let sdl_context = sdl2::init().unwrap();
-        let sdl_event_pump = sdl_context.event_pump().unwrap();
-        let sdl_timer = sdl_context.timer().unwrap();
-        let sdl_video = sdl_context.video().unwrap();
-        let sdl_display_mode = sdl_video.desktop_display_mode(0).unwrap();
-        let scale = f64::max(
-            f64::min(
-                sdl_display_mode.w as f64 / width as f64,
-                sdl_display_mode.h as f64 / height as f64,
-            ) * display_ratio,
-            1.0,
-        ) as u32;
-        let sdl_window = sdl_video
-            .window(title, width * scale, height * scale)
-            .position_centered()
-            .resizable()
+    type Canvas = SdlCanvas;
+
+    fn from_builder(builder: &PlatformBuilder) -> Result<Self, PlatformError> {
+        let init_error = |err: String| PlatformError::Init(err);
+        let (width, height) = (builder.width, builder.height);
+        let sdl_context = sdl2::init().map_err(init_error)?;
+        let sdl_event_pump = sdl_context.event_pump().map_err(init_error)?;
+        let sdl_timer = sdl_context.timer().map_err(init_error)?;
+        let sdl_video = sdl_context.video().map_err(init_error)?;
+        let num_displays = sdl_video.num_video_displays().map_err(init_error)? as u32;
+        let display = builder.display.unwrap_or(0);
+        if display >= num_displays {
+            return Err(PlatformError::InvalidDisplay(display, num_displays));
+        }
+        let sdl_display_mode = sdl_video
+            .desktop_display_mode(display as i32)
+            .map_err(init_error)?;
+        let scale = builder.scale.unwrap_or_else(|| {
+            f64::max(
+                f64::min(
+                    sdl_display_mode.w as f64 / width as f64,
+                    sdl_display_mode.h as f64 / height as f64,
+                ) * builder.display_ratio,
+                1.0,
+            ) as u32
+        });
+        let (window_width, window_height) = (width * scale, height * scale);
+
+        // Positions are relative to the display, and the window is centered on it by default
+        let sdl_display_bounds = sdl_video
+            .display_bounds(display as i32)
+            .map_err(init_error)?;
+        let (x, y) = builder.position.unwrap_or_else(|| {
+            (
+                (sdl_display_bounds.width() as i32 - window_width as i32) / 2,
+                (sdl_display_bounds.height() as i32 - window_height as i32) / 2,
+            )
+        });
+        let mut sdl_window_builder = sdl_video.window(&builder.title, window_width, window_height);
+        sdl_window_builder.position(sdl_display_bounds.x() + x, sdl_display_bounds.y() + y);
+        if builder.is_resizable {
+            sdl_window_builder.resizable();
+        }
+        if builder.is_borderless {
+            sdl_window_builder.borderless();
+        }
+        match builder.fullscreen {
+            FullscreenMode::Off => {}
+            FullscreenMode::Desktop => {
+                sdl_window_builder.fullscreen_desktop();
+            }
+            FullscreenMode::Exclusive => {
+                sdl_window_builder.fullscreen();
+            }
+        }
+        let sdl_window = sdl_window_builder
+            .build()
+            .map_err(|err| init_error(err.to_string()))?;
+        let mut sdl_canvas_builder = sdl_window.into_canvas();
+        if builder.is_vsync {
+            sdl_canvas_builder = sdl_canvas_builder.present_vsync();
+        }
+        let mut sdl_canvas = sdl_canvas_builder
             .build()
-            .unwrap();
-        let mut sdl_canvas = sdl_window.into_canvas().present_vsync().build().unwrap();
+            .map_err(|err| init_error(err.to_string()))?;
         sdl_canvas
             .window_mut()
             .set_minimum_size(width, height)
-            .unwrap();
+            .map_err(|err| init_error(err.to_string()))?;
         let sdl_texture = sdl_canvas
             .texture_creator()
             .create_texture_streaming(SdlPixelFormat::RGB24, width, height)
-            .unwrap();
-        let sdl_game_controller = sdl_context.game_controller().unwrap();
-        let mut sdl_game_controllers = Vec::new();
-        for i in 0..sdl_game_controller.num_joysticks().unwrap_or(0) {
-            if let Ok(gc) = sdl_game_controller.open(i) {
-                sdl_game_controllers.push(gc);
-            }
-        }
-        let sdl_audio = sdl_context.audio().unwrap();
+            .map_err(|err| init_error(err.to_string()))?;
+        let sdl_game_controller = sdl_context.game_controller().map_err(init_error)?;
+        let sdl_joystick = sdl_context.joystick().map_err(init_error)?;
+        let sdl_audio = sdl_context.audio().map_err(init_error)?;
         hint::set("SDL_MOUSE_FOCUS_CLICKTHROUGH", "1");
+        let sdl_version = sdl2::version::version();
 
-        Self {
+        let mut sdl2 = Self {
             sdl_context,
             sdl_event_pump,
             sdl_timer,
             sdl_canvas,
             sdl_texture,
-            sdl_game_controllers,
+            sdl_game_controller,
+            sdl_game_controllers: Vec::new(),
+            player_slots: PlayerSlots::new(),
+            sdl_joystick,
+            sdl_joysticks: Vec::new(),
             sdl_audio,
             sdl_audio_device: None,
+            sdl_audio_spec: None,
+            audio_callback: None,
+            audio_monitor: Arc::new(AudioMonitor::new()),
             screen_width: width,
             screen_height: height,
+            screen_layout: ScreenLayout::new(
+                ScreenFit::Integer,
+                1.0,
+                width,
+                height,
+                width,
+                height,
+                1,
+            ),
+            screen_fit: ScreenFit::Integer,
+            pixel_aspect: 1.0,
+            screen_mode: ScreenMode::Nearest,
+            sdl_upscaled_texture: None,
+            display_index: display as i32,
+            display_effect: DisplayEffect::None,
+            rendered_colors: Vec::new(),
+            needs_full_render: true,
+            overlay: None,
+            is_raw_mouse: false,
+            is_mouse_on_screen: false,
+            has_precise_wheel: (sdl_version.major, sdl_version.minor, sdl_version.patch)
+                >= (2, 0, 18),
             mouse_x: i32::MIN,
             mouse_y: i32::MIN,
+            post_render_hook: None,
+            aux_windows: Vec::new(),
+            pending_events: Vec::new(),
+            should_coalesce_mouse_motion: true,
+            start_time: Instant::now(),
+            last_event_us: 0,
+        };
+        for i in 0..sdl2.sdl_game_controller.num_joysticks().unwrap_or(0) {
+            sdl2.open_game_controller(i);
+            sdl2.open_joystick(i);
+        }
+        sdl2.update_screen_pos_scale();
+        if let Some((image, colors, scale)) = &builder.icon {
+            sdl2.set_icon(image, colors, *scale);
         }
+        Ok(sdl2)
     }
 
     fn set_title(&mut self, title: &str) {
-        self.sdl_canvas.window_mut().set_title(title).unwrap();
+        if let Err(err) = self.sdl_canvas.window_mut().set_title(title) {
+            error!("Unable to set the window title: {}", err);
+        }
     }
 
     fn set_icon(&mut self, image: &[Vec<Color>], colors: &[Rgb8], scale: u32) {
         let width = image[0].len() as u32;
         let height = image.len() as u32;
         let mut sdl_surface =
-            SdlSurface::new(width * scale, height * scale, SdlPixelFormat::RGBA32).unwrap();
+            match SdlSurface::new(width * scale, height * scale, SdlPixelFormat::RGBA32) {
+                Ok(sdl_surface) => sdl_surface,
+                Err(err) => {
+                    warn!("Unable to convert the window icon: {}", err);
+                    return;
+                }
+            };
         let pitch = sdl_surface.pitch();
         sdl_surface.with_lock_mut(|buffer: &mut [u8]| {
             for y in 0..height * scale {
@@ -135,11 +282,11 @@ impl Platform for Sdl2 {
         self.sdl_context.mouse().show_cursor(show);
     }
 
-    fn move_cursor(&self, x: i32, y: i32) {
+    fn warp_mouse(&self, screen_x: i32, screen_y: i32) {
         let (window_x, window_y) = self.sdl_canvas.window().position();
-        let (screen_x, screen_y, screen_scale) = self.screen_pos_scale();
-        let mouse_x = x * screen_scale as i32 + window_x + screen_x as i32;
-        let mouse_y = y * screen_scale as i32 + window_y + screen_y as i32;
+        let (x, y) = self.screen_layout.screen_to_window(screen_x, screen_y);
+        let mouse_x = x + window_x;
+        let mouse_y = y + window_y;
         unsafe {
             sdl2::sys::SDL_WarpMouseGlobal(mouse_x, mouse_y);
         }
@@ -154,11 +301,58 @@ impl Platform for Sdl2 {
             return;
         }
         let window = self.sdl_canvas.window_mut();
-        if is_fullscreen {
-            let _ = window.set_fullscreen(SdlFullscreenType::Desktop);
+        let fullscreen_type = if is_fullscreen {
+            SdlFullscreenType::Desktop
         } else {
-            let _ = window.set_fullscreen(SdlFullscreenType::Off);
+            SdlFullscreenType::Off
+        };
+        match window.set_fullscreen(fullscreen_type) {
+            Ok(()) => info!("Switched to {:?} fullscreen mode", fullscreen_type),
+            Err(err) => warn!("Unable to switch fullscreen mode: {}", err),
         }
+        self.update_screen_pos_scale();
+    }
+
+    fn window_to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        self.screen_layout.window_to_screen(x, y)
+    }
+
+    fn set_raw_mouse(&mut self, is_raw_mouse: bool) {
+        self.is_raw_mouse = is_raw_mouse;
+    }
+
+    fn is_mouse_on_screen(&self) -> bool {
+        self.is_mouse_on_screen
+    }
+
+    fn start_text_input(&mut self) {
+        self.sdl_canvas.window().subsystem().text_input().start();
+    }
+
+    fn stop_text_input(&mut self) {
+        self.sdl_canvas.window().subsystem().text_input().stop();
+    }
+
+    fn controller_name(&self, slot: u32) -> Option<String> {
+        let instance_id = self.player_slots.instance_id(slot)?;
+        self.sdl_game_controllers
+            .iter()
+            .find(|gc| gc.instance_id() == instance_id)
+            .map(SdlGameController::name)
+    }
+
+    fn controller_connected(&self, slot: u32) -> bool {
+        self.player_slots.instance_id(slot).is_some()
+    }
+
+    fn set_text_input_rect(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        let (x, y) = self.screen_layout.screen_to_window(x, y);
+        let (width, height) = self.screen_layout.screen_to_window_size(width, height);
+        self.sdl_canvas
+            .window()
+            .subsystem()
+            .text_input()
+            .set_rect(SdlRect::new(x, y, width, height));
     }
 
     fn tick_count(&self) -> u32 {
@@ -169,37 +363,559 @@ impl Platform for Sdl2 {
         self.sdl_timer.delay(ms);
     }
 
+    fn elapsed_us(&self) -> u64 {
+        self.start_time.elapsed().as_micros() as u64
+    }
+
     fn poll_event(&mut self) -> Option<Event> {
+        self.poll_event_timed().map(|event| event.event)
+    }
+
+    fn poll_event_timed(&mut self) -> Option<TimedEvent> {
+        if self.pending_events.is_empty() {
+            self.poll_sdl_event()
+        } else {
+            Some(self.pending_events.remove(0))
+        }
+    }
+
+    fn poll_events(&mut self, out: &mut Vec<TimedEvent>) {
+        let should_coalesce_mouse_motion = self.should_coalesce_mouse_motion;
+        event::drain_events(out, should_coalesce_mouse_motion, || {
+            self.poll_event_timed()
+        });
+    }
+
+    fn flush_events(&mut self) {
+        let mut pending_events = std::mem::take(&mut self.pending_events);
+        event::flush_events(&mut pending_events, || self.poll_sdl_event());
+        self.pending_events = pending_events;
+    }
+
+    fn set_coalesce_mouse_motion(&mut self, is_enabled: bool) {
+        self.should_coalesce_mouse_motion = is_enabled;
+    }
+
+    fn set_display_effect(&mut self, effect: DisplayEffect) {
+        if effect.scale() != self.display_effect.scale() {
+            let sdl_texture = self.sdl_canvas.texture_creator().create_texture_streaming(
+                SdlPixelFormat::RGB24,
+                self.screen_width * effect.scale(),
+                self.screen_height * effect.scale(),
+            );
+            match sdl_texture {
+                Ok(sdl_texture) => self.sdl_texture = sdl_texture,
+                Err(err) => {
+                    error!("Unable to create the screen texture: {}", err);
+                    return;
+                }
+            }
+        }
+        self.display_effect = effect;
+        self.update_screen_pos_scale();
+        self.needs_full_render = true;
+    }
+
+    fn set_screen_fit(&mut self, fit: ScreenFit, pixel_aspect: f64) {
+        if pixel_aspect.is_finite() && pixel_aspect > 0.0 {
+            self.pixel_aspect = pixel_aspect;
+        } else {
+            warn!("Ignoring invalid pixel aspect ratio {}", pixel_aspect);
+        }
+        self.screen_fit = fit;
+        self.update_screen_pos_scale();
+    }
+
+    fn set_screen_mode(&mut self, mode: ScreenMode) {
+        self.screen_mode = mode;
+        if mode == ScreenMode::Nearest {
+            self.sdl_upscaled_texture = None;
+        }
+    }
+
+    fn set_overlay_enabled(&mut self, is_enabled: bool) {
+        self.overlay =
+            is_enabled.then(|| vec![0; (self.screen_width * self.screen_height * 4) as usize]);
+        self.needs_full_render = true;
+    }
+
+    fn overlay_mut(&mut self) -> Option<&mut [u8]> {
+        self.overlay.as_deref_mut()
+    }
+
+    fn render_screen(
+        &mut self,
+        image: &[Vec<Color>],
+        colors: &[Rgb8],
+        bg_color: Rgb8,
+        dirty_rects: Option<&[Recti]>,
+        scanlines: Option<&Scanlines>,
+    ) {
+        let width = image[0].len() as u32;
+        let height = image.len() as u32;
+        let effect = self.display_effect;
+        let effect_scale = effect.scale();
+        let full_rect = [Recti::new(0, 0, width, height)];
+        let rgb_tables = RgbTables::new(colors, scanlines);
+        if let Some(overlay) = &mut self.overlay {
+            let overlay_size = (width * height * 4) as usize;
+            if overlay.len() != overlay_size {
+                *overlay = vec![0; overlay_size];
+            }
+            let result = self
+                .sdl_texture
+                .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                    write_rgb24(
+                        buffer,
+                        pitch,
+                        image,
+                        &rgb_tables,
+                        effect,
+                        full_rect[0],
+                        scanlines,
+                    );
+                    blend_overlay(buffer, pitch, overlay, width, height, effect_scale);
+                });
+            if let Err(err) = result {
+                error!("Unable to update the screen texture: {}", err);
+            }
+            // The blended pixels must be replaced once the overlay is disabled
+            self.needs_full_render = true;
+        } else {
+            let dirty_rects = if self.needs_full_render
+                || self.rendered_colors != colors
+                || scanlines.is_some()
+            {
+                // Shifted rows move pixels outside any dirty rect, and the next frame
+                // without them has to put those pixels back
+                self.needs_full_render = scanlines.is_some();
+                self.rendered_colors = colors.to_vec();
+                &full_rect
+            } else {
+                dirty_rects.unwrap_or(&full_rect)
+            };
+            for rect in dirty_rects {
+                let sdl_rect = SdlRect::new(
+                    rect.left() * effect_scale as i32,
+                    rect.top() * effect_scale as i32,
+                    rect.width() * effect_scale,
+                    rect.height() * effect_scale,
+                );
+                let result = self.sdl_texture.with_lock(
+                    Some(sdl_rect),
+                    |buffer: &mut [u8], pitch: usize| {
+                        write_rgb24(buffer, pitch, image, &rgb_tables, effect, *rect, scanlines);
+                    },
+                );
+                if let Err(err) = result {
+                    error!("Unable to update the screen texture: {}", err);
+                    self.needs_full_render = true;
+                }
+            }
+        }
+        self.update_screen_pos_scale();
+        let is_upscaled = self.upscale_screen_texture();
+        let sdl_texture = match &self.sdl_upscaled_texture {
+            Some((_, sdl_upscaled_texture)) if is_upscaled => sdl_upscaled_texture,
+            _ => &self.sdl_texture,
+        };
+        draw_letterboxed(
+            &mut self.sdl_canvas,
+            sdl_texture,
+            self.screen_layout.rect,
+            bg_color,
+        );
+        if let Some(hook) = &mut self.post_render_hook {
+            hook(&mut self.sdl_canvas);
+        }
+        self.sdl_canvas.present();
+    }
+
+    fn set_post_render_hook(&mut self, hook: Option<PostRenderHook<SdlCanvas>>) {
+        self.post_render_hook = hook;
+    }
+
+    fn create_aux_window(
+        &mut self,
+        title: &str,
+        width: u32,
+        height: u32,
+        scale: u32,
+    ) -> Result<AuxWindowId, PlatformError> {
+        if width == 0 || height == 0 {
+            return Err(PlatformError::InvalidScreenSize(width, height));
+        }
+        let window_size = (width.checked_mul(scale), height.checked_mul(scale));
+        let (window_width, window_height) = match window_size {
+            (Some(window_width), Some(window_height)) if scale > 0 => (window_width, window_height),
+            _ => return Err(PlatformError::InvalidScale(scale)),
+        };
+        let init_error = |err: String| PlatformError::Init(err);
+        let sdl_window = self
+            .sdl_canvas
+            .window()
+            .subsystem()
+            .window(title, window_width, window_height)
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(|err| init_error(err.to_string()))?;
+        let mut sdl_canvas = sdl_window
+            .into_canvas()
+            .build()
+            .map_err(|err| init_error(err.to_string()))?;
+        sdl_canvas
+            .window_mut()
+            .set_minimum_size(width, height)
+            .map_err(|err| init_error(err.to_string()))?;
+        let sdl_texture = sdl_canvas
+            .texture_creator()
+            .create_texture_streaming(SdlPixelFormat::RGB24, width, height)
+            .map_err(|err| init_error(err.to_string()))?;
+        let id = AuxWindowId(sdl_canvas.window().id());
+        info!("Opened window '{}'", title);
+        self.aux_windows.push(AuxWindow {
+            id,
+            sdl_canvas,
+            sdl_texture,
+            width,
+            height,
+        });
+        Ok(id)
+    }
+
+    fn close_aux_window(&mut self, window: AuxWindowId) {
+        self.aux_windows
+            .retain(|aux_window| aux_window.id != window);
+    }
+
+    fn is_aux_window_open(&self, window: AuxWindowId) -> bool {
+        self.aux_windows
+            .iter()
+            .any(|aux_window| aux_window.id == window)
+    }
+
+    fn render_aux_window(
+        &mut self,
+        window: AuxWindowId,
+        image: &[Vec<Color>],
+        colors: &[Rgb8],
+        bg_color: Rgb8,
+    ) {
+        let aux_window = match self
+            .aux_windows
+            .iter_mut()
+            .find(|aux_window| aux_window.id == window)
+        {
+            Some(aux_window) => aux_window,
+            None => return,
+        };
+        let width = image[0].len() as u32;
+        let height = image.len() as u32;
+        if width != aux_window.width || height != aux_window.height {
+            let sdl_texture = aux_window
+                .sdl_canvas
+                .texture_creator()
+                .create_texture_streaming(SdlPixelFormat::RGB24, width, height);
+            match sdl_texture {
+                Ok(sdl_texture) => {
+                    aux_window.sdl_texture = sdl_texture;
+                    aux_window.width = width;
+                    aux_window.height = height;
+                }
+                Err(err) => {
+                    error!("Unable to create the window texture: {}", err);
+                    return;
+                }
+            }
+        }
+        let rect = Recti::new(0, 0, width, height);
+        let result = aux_window
+            .sdl_texture
+            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
+                write_rgb24(
+                    buffer,
+                    pitch,
+                    image,
+                    &RgbTables::new(colors, None),
+                    DisplayEffect::None,
+                    rect,
+                    None,
+                );
+            });
+        if let Err(err) = result {
+            error!("Unable to update the window texture: {}", err);
+        }
+        let (window_width, window_height) = aux_window.sdl_canvas.window().size();
+        let layout = ScreenLayout::new(
+            ScreenFit::Integer,
+            1.0,
+            width,
+            height,
+            window_width,
+            window_height,
+            1,
+        );
+        draw_letterboxed(
+            &mut aux_window.sdl_canvas,
+            &aux_window.sdl_texture,
+            layout.rect,
+            bg_color,
+        );
+        aux_window.sdl_canvas.present();
+    }
+
+    fn start_audio(
+        &mut self,
+        sample_rate: u32,
+        num_channels: u32,
+        num_samples: u32,
+        device_name: Option<&str>,
+        audio: shared_type!(dyn AudioCallback + Send),
+    ) -> Result<(), AudioError> {
+        if self.sdl_audio_spec.is_some() {
+            return Err(AudioError::AlreadyStarted);
+        }
+        let spec = SdlAudioSpecDesired {
+            freq: Some(sample_rate as i32),
+            channels: Some(num_channels as u8),
+            samples: Some(num_samples as u16),
+        };
+        let sdl_audio_device = self.open_audio_device(&spec, device_name, audio.clone())?;
+        info!(
+            "Opened audio device '{}' at {} Hz",
+            device_name.unwrap_or("default"),
+            sdl_audio_device.spec().freq
+        );
+        sdl_audio_device.resume();
+        self.sdl_audio_device = Some(sdl_audio_device);
+        self.sdl_audio_spec = Some(spec);
+        self.audio_callback = Some(audio);
+        Ok(())
+    }
+
+    fn actual_sample_rate(&self) -> u32 {
+        match (&self.sdl_audio_device, &self.sdl_audio_spec) {
+            (Some(audio_device), _) => audio_device.spec().freq as u32,
+            (None, Some(spec)) => spec.freq.unwrap_or(0) as u32,
+            (None, None) => 0,
+        }
+    }
+
+    fn audio_monitor(&self) -> Arc<AudioMonitor> {
+        self.audio_monitor.clone()
+    }
+
+    fn audio_devices(&self) -> Vec<String> {
+        let num_devices = self.sdl_audio.num_audio_playback_devices().unwrap_or(0);
+        (0..num_devices)
+            .filter_map(|index| self.sdl_audio.audio_playback_device_name(index).ok())
+            .collect()
+    }
+
+    fn reopen_audio(&mut self, device_name: Option<&str>) -> Result<(), AudioError> {
+        let (spec, audio) = match (&self.sdl_audio_spec, &self.audio_callback) {
+            (Some(spec), Some(audio)) => (spec.clone(), audio.clone()),
+            _ => return Err(AudioError::NotStarted),
+        };
+        // The callback is shared with the new device, so playback carries over
+        let is_paused = self
+            .sdl_audio_device
+            .as_ref()
+            .is_some_and(|audio_device| audio_device.status() == SdlAudioStatus::Paused);
+        self.sdl_audio_device = None;
+        info!("Closed audio device");
+        let sdl_audio_device = self.open_audio_device(&spec, device_name, audio)?;
+        info!(
+            "Opened audio device '{}' at {} Hz",
+            device_name.unwrap_or("default"),
+            sdl_audio_device.spec().freq
+        );
+        if !is_paused {
+            sdl_audio_device.resume();
+        }
+        self.sdl_audio_device = Some(sdl_audio_device);
+        Ok(())
+    }
+
+    fn pause_audio(&mut self) {
+        if let Some(audio_device) = &self.sdl_audio_device {
+            audio_device.pause();
+        }
+    }
+
+    fn resume_audio(&mut self) {
+        if let Some(audio_device) = &self.sdl_audio_device {
+            audio_device.resume();
+        }
+    }
+
+    fn is_audio_paused(&self) -> bool {
+        self.sdl_audio_device
+            .as_ref()
+            .is_none_or(|audio_device| audio_device.status() != SdlAudioStatus::Playing)
+    }
+}
+
+fn draw_letterboxed(
+    sdl_canvas: &mut SdlCanvas,
+    sdl_texture: &SdlTexture,
+    rect: Recti,
+    bg_color: Rgb8,
+) {
+    sdl_canvas.set_draw_color(SdlColor::RGB(
+        ((bg_color >> 16) & 0xff) as u8,
+        ((bg_color >> 8) & 0xff) as u8,
+        (bg_color & 0xff) as u8,
+    ));
+
+    // Instead of sdl_canvas.clear()
+    let result = sdl_canvas.output_size().and_then(|display_size| {
+        sdl_canvas.fill_rect(SdlRect::new(0, 0, display_size.0, display_size.1))
+    });
+    if let Err(err) = result {
+        error!("Unable to clear the window: {}", err);
+    }
+
+    let dst = SdlRect::new(rect.left(), rect.top(), rect.width(), rect.height());
+    if let Err(err) = sdl_canvas.copy(sdl_texture, None, Some(dst)) {
+        error!("Unable to draw the screen: {}", err);
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+unsafe impl HasRawWindowHandle for Sdl2 {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.sdl_canvas.window().raw_window_handle()
+    }
+}
+
+impl Sdl2 {
+    fn timed_event(&mut self, event_ms: u32, event: Event) -> TimedEvent {
+        let timestamp_us = event::event_timestamp_us(
+            self.elapsed_us(),
+            self.sdl_timer.ticks(),
+            event_ms,
+            self.last_event_us,
+        );
+        self.last_event_us = timestamp_us;
+        TimedEvent {
+            timestamp_us,
+            event,
+        }
+    }
+
+    fn poll_sdl_event(&mut self) -> Option<TimedEvent> {
         loop {
+            let precise_wheel = self.peek_precise_wheel();
             let sdl_event = self.sdl_event_pump.poll_event();
             if sdl_event.is_none() {
-                let (cur_mouse_x, cur_mouse_y) = self.mouse_pos();
-                if cur_mouse_x != self.mouse_x || cur_mouse_y != self.mouse_y {
-                    self.mouse_x = cur_mouse_x;
-                    self.mouse_y = cur_mouse_y;
-                    return Some(Event::MouseMotion {
-                        x: cur_mouse_x,
-                        y: cur_mouse_y,
-                    });
+                // The window position is compared so that moves over the letterbox, which
+                // clamp to the same screen position, still update it
+                let (window_x, window_y) = self.mouse_window_pos();
+                if window_x != self.mouse_x || window_y != self.mouse_y {
+                    self.mouse_x = window_x;
+                    self.mouse_y = window_y;
+                    let (x, y) = self.mouse_event_pos(window_x, window_y);
+                    let event = Event::MouseMotion {
+                        x,
+                        y,
+                        window_x,
+                        window_y,
+                        is_on_screen: self.is_mouse_on_screen,
+                    };
+                    let now_ms = self.sdl_timer.ticks();
+                    return Some(self.timed_event(now_ms, event));
                 }
                 return None;
             }
-            let event = match sdl_event.unwrap() {
+            let sdl_event = sdl_event.unwrap();
+            let event_ms = sdl_event.get_timestamp();
+            let event = match sdl_event {
                 // System events
                 SdlEvent::Quit { .. } => Event::Quit,
                 SdlEvent::DropFile { filename, .. } => {
                     self.sdl_canvas.window_mut().raise();
-                    Event::DropFile { filename }
+                    let (x, y) = self.drop_pos();
+                    Event::DropFile { filename, x, y }
+                }
+                SdlEvent::DropText { filename, .. } => {
+                    self.sdl_canvas.window_mut().raise();
+                    let (x, y) = self.drop_pos();
+                    Event::DropText {
+                        text: filename,
+                        x,
+                        y,
+                    }
                 }
 
                 // Window events
+                SdlEvent::Window {
+                    window_id,
+                    win_event,
+                    ..
+                } if window_id != self.sdl_canvas.window().id() => {
+                    let event = match win_event {
+                        SdlWindowEvent::Moved(x, y) => AuxWindowEvent::Moved { x, y },
+                        SdlWindowEvent::SizeChanged(width, height) => AuxWindowEvent::Resized {
+                            width: width as u32,
+                            height: height as u32,
+                        },
+                        SdlWindowEvent::FocusGained => AuxWindowEvent::Focused(true),
+                        SdlWindowEvent::FocusLost => AuxWindowEvent::Focused(false),
+                        SdlWindowEvent::Close => AuxWindowEvent::Closed,
+                        _ => continue,
+                    };
+                    Event::AuxWindow {
+                        window: AuxWindowId(window_id),
+                        event,
+                    }
+                }
                 SdlEvent::Window { win_event, .. } => match win_event {
+                    // SDL only quits by itself when the last window is closed
+                    SdlWindowEvent::Close if !self.aux_windows.is_empty() => Event::Quit,
+                    SdlWindowEvent::SizeChanged(..) => {
+                        self.update_screen_pos_scale();
+                        continue;
+                    }
+                    SdlWindowEvent::Moved(..) => {
+                        let display_index = self.sdl_canvas.window().display_index();
+                        if let Ok(display_index) = display_index {
+                            if display_index != self.display_index {
+                                info!("Window moved to display {}", display_index);
+                                self.display_index = display_index;
+                            }
+                        }
+                        continue;
+                    }
                     SdlWindowEvent::Shown
                     | SdlWindowEvent::Maximized
                     | SdlWindowEvent::Restored => Event::Shown,
                     SdlWindowEvent::Hidden | SdlWindowEvent::Minimized => Event::Hidden,
-                    _ => continue,
+                    win_event => {
+                        debug!("Ignoring window event {:?}", win_event);
+                        continue;
+                    }
                 },
+                SdlEvent::Display {
+                    display_index,
+                    display_event,
+                    ..
+                } => {
+                    match display_event {
+                        SdlDisplayEvent::Connected => info!("Display {} connected", display_index),
+                        SdlDisplayEvent::Disconnected => {
+                            info!("Display {} disconnected", display_index);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                // Audio events
+                SdlEvent::AudioDeviceRemoved {
+                    iscapture: false, ..
+                } => Event::AudioDeviceRemoved,
 
                 // Key events
                 SdlEvent::KeyDown {
@@ -216,36 +932,107 @@ impl Platform for Sdl2 {
                 } => Event::KeyUp {
                     keycode: keycode as u32,
                 },
+                SdlEvent::KeyDown {
+                    keycode: None,
+                    scancode,
+                    repeat: false,
+                    ..
+                }
+                | SdlEvent::KeyUp {
+                    keycode: None,
+                    scancode,
+                    repeat: false,
+                    ..
+                } => {
+                    debug!(
+                        "Ignoring key event without a keycode, scancode {:?}",
+                        scancode
+                    );
+                    continue;
+                }
                 SdlEvent::TextInput { text, .. } => Event::TextInput { text },
+                SdlEvent::TextEditing {
+                    text,
+                    start,
+                    length,
+                    ..
+                } => Event::TextEditing {
+                    text,
+                    start,
+                    length,
+                },
 
                 // Mouse events
-                SdlEvent::MouseButtonDown { mouse_btn, .. } => Event::MouseButtonDown {
-                    button: match mouse_btn {
-                        SdlMouseButton::Left => MouseButton::Left,
-                        SdlMouseButton::Middle => MouseButton::Middle,
-                        SdlMouseButton::Right => MouseButton::Right,
-                        SdlMouseButton::X1 => MouseButton::X1,
-                        SdlMouseButton::X2 => MouseButton::X2,
-                        SdlMouseButton::Unknown => MouseButton::Unknown,
-                    },
-                },
-                SdlEvent::MouseButtonUp { mouse_btn, .. } => Event::MouseButtonUp {
-                    button: match mouse_btn {
-                        SdlMouseButton::Left => MouseButton::Left,
-                        SdlMouseButton::Middle => MouseButton::Middle,
-                        SdlMouseButton::Right => MouseButton::Right,
-                        SdlMouseButton::X1 => MouseButton::X1,
-                        SdlMouseButton::X2 => MouseButton::X2,
-                        SdlMouseButton::Unknown => MouseButton::Unknown,
-                    },
-                },
-                SdlEvent::MouseWheel { x, y, .. } => Event::MouseWheel { x, y },
+                SdlEvent::MouseButtonDown {
+                    window_id,
+                    mouse_btn,
+                    x,
+                    y,
+                    ..
+                } if window_id == self.sdl_canvas.window().id() => {
+                    let (x, y) = self.mouse_event_pos(x, y);
+                    Event::MouseButtonDown {
+                        button: Self::to_mouse_button(mouse_btn),
+                        x,
+                        y,
+                        is_on_screen: self.is_mouse_on_screen,
+                    }
+                }
+                SdlEvent::MouseButtonUp {
+                    window_id,
+                    mouse_btn,
+                    x,
+                    y,
+                    ..
+                } if window_id == self.sdl_canvas.window().id() => {
+                    let (x, y) = self.mouse_event_pos(x, y);
+                    Event::MouseButtonUp {
+                        button: Self::to_mouse_button(mouse_btn),
+                        x,
+                        y,
+                        is_on_screen: self.is_mouse_on_screen,
+                    }
+                }
+                SdlEvent::MouseWheel {
+                    window_id,
+                    x,
+                    y,
+                    direction,
+                    ..
+                } if window_id == self.sdl_canvas.window().id() => {
+                    let sign = if direction == SdlMouseWheelDirection::Flipped {
+                        -1
+                    } else {
+                        1
+                    };
+                    let (precise_x, precise_y) = precise_wheel.unwrap_or((x as f32, y as f32));
+                    Event::MouseWheel {
+                        x: x * sign,
+                        y: y * sign,
+                        precise_x: precise_x * sign as f32,
+                        precise_y: precise_y * sign as f32,
+                    }
+                }
 
                 // Controller events
+                SdlEvent::ControllerDeviceAdded { which, .. } => {
+                    self.open_game_controller(which);
+                    continue;
+                }
+                SdlEvent::ControllerDeviceRemoved { which, .. } => {
+                    self.close_game_controller(which);
+                    continue;
+                }
                 SdlEvent::ControllerAxisMotion {
                     which, axis, value, ..
                 } => Event::ControllerAxisMotion {
-                    which,
+                    which: match self.player_slots.slot(which) {
+                        Some(slot) => slot,
+                        None => {
+                            debug!("Ignoring event of controller {} without a slot", which);
+                            continue;
+                        }
+                    },
                     axis: match axis {
                         SdlAxis::LeftX => ControllerAxis::LeftX,
                         SdlAxis::LeftY => ControllerAxis::LeftY,
@@ -258,7 +1045,13 @@ impl Platform for Sdl2 {
                 },
                 SdlEvent::ControllerButtonDown { which, button, .. } => {
                     Event::ControllerButtonDown {
-                        which,
+                        which: match self.player_slots.slot(which) {
+                            Some(slot) => slot,
+                            None => {
+                                debug!("Ignoring event of controller {} without a slot", which);
+                                continue;
+                            }
+                        },
                         button: match button {
                             SdlButton::A => ControllerButton::A,
                             SdlButton::B => ControllerButton::B,
@@ -285,7 +1078,13 @@ impl Platform for Sdl2 {
                     }
                 }
                 SdlEvent::ControllerButtonUp { which, button, .. } => Event::ControllerButtonUp {
-                    which,
+                    which: match self.player_slots.slot(which) {
+                        Some(slot) => slot,
+                        None => {
+                            debug!("Ignoring event of controller {} without a slot", which);
+                            continue;
+                        }
+                    },
                     button: match button {
                         SdlButton::A => ControllerButton::A,
                         SdlButton::B => ControllerButton::B,
@@ -311,113 +1110,278 @@ impl Platform for Sdl2 {
                     },
                 },
 
+                // Joystick events
+                SdlEvent::JoyDeviceAdded { which, .. } => {
+                    self.open_joystick(which);
+                    continue;
+                }
+                SdlEvent::JoyDeviceRemoved { which, .. } => {
+                    if self.is_raw_joystick(which) {
+                        info!("Closed joystick {}", which);
+                    }
+                    self.sdl_joysticks
+                        .retain(|joystick| joystick.instance_id() != which);
+                    continue;
+                }
+                SdlEvent::JoyAxisMotion {
+                    which,
+                    axis_idx,
+                    value,
+                    ..
+                } if self.is_raw_joystick(which) => Event::JoystickAxisMotion {
+                    which,
+                    axis: axis_idx as u32,
+                    value: value as i32,
+                },
+                SdlEvent::JoyButtonDown {
+                    which, button_idx, ..
+                } if self.is_raw_joystick(which) => Event::JoystickButtonDown {
+                    which,
+                    button: button_idx as u32,
+                },
+                SdlEvent::JoyButtonUp {
+                    which, button_idx, ..
+                } if self.is_raw_joystick(which) => Event::JoystickButtonUp {
+                    which,
+                    button: button_idx as u32,
+                },
+                SdlEvent::JoyHatMotion {
+                    which,
+                    hat_idx,
+                    state,
+                    ..
+                } if self.is_raw_joystick(which) => Event::JoystickHatMotion {
+                    which,
+                    hat: hat_idx as u32,
+                    state: match state {
+                        SdlHatState::Centered => HatState::Centered,
+                        SdlHatState::Up => HatState::Up,
+                        SdlHatState::Right => HatState::Right,
+                        SdlHatState::Down => HatState::Down,
+                        SdlHatState::Left => HatState::Left,
+                        SdlHatState::RightUp => HatState::RightUp,
+                        SdlHatState::RightDown => HatState::RightDown,
+                        SdlHatState::LeftUp => HatState::LeftUp,
+                        SdlHatState::LeftDown => HatState::LeftDown,
+                    },
+                },
+
                 // Others
-                _ => continue,
+                sdl_event => {
+                    // Mouse motion is polled, and controllers also send joystick events
+                    if !sdl_event.is_joy() && !matches!(sdl_event, SdlEvent::MouseMotion { .. }) {
+                        debug!("Ignoring SDL event {:?}", sdl_event);
+                    }
+                    continue;
+                }
             };
-            return Some(event);
+            return Some(self.timed_event(event_ms, event));
         }
     }
 
-    fn render_screen(&mut self, image: &[Vec<Color>], colors: &[Rgb8], bg_color: Rgb8) {
-        let width = image[0].len() as u32;
-        let height = image.len() as u32;
-        self.sdl_texture
-            .with_lock(None, |buffer: &mut [u8], pitch: usize| {
-                for i in 0..height as usize {
-                    for j in 0..width as usize {
-                        let offset = i * pitch + j * 3;
-                        let color = colors[image[i][j] as usize];
-                        buffer[offset] = ((color >> 16) & 0xff) as u8;
-                        buffer[offset + 1] = ((color >> 8) & 0xff) as u8;
-                        buffer[offset + 2] = (color & 0xff) as u8;
-                    }
+    fn open_audio_device(
+        &self,
+        spec: &SdlAudioSpecDesired,
+        device_name: Option<&str>,
+        audio: shared_type!(dyn AudioCallback + Send),
+    ) -> Result<SdlAudioDevice<AudioContextHolder>, AudioError> {
+        self.sdl_audio
+            .open_playback(device_name, spec, |spec| {
+                // The driver may not honor the request, so adapt to what it gives
+                let mut audio_callback = audio.lock();
+                audio_callback.set_sample_rate(spec.freq as u32);
+                audio_callback.set_num_channels(spec.channels as u32);
+                drop(audio_callback);
+                AudioContextHolder {
+                    audio,
+                    monitor: self.audio_monitor.clone(),
+                    history: Vec::with_capacity(spec.size as usize / 2),
                 }
             })
-            .unwrap();
-        self.sdl_canvas.set_draw_color(SdlColor::RGB(
-            ((bg_color >> 16) & 0xff) as u8,
-            ((bg_color >> 8) & 0xff) as u8,
-            (bg_color & 0xff) as u8,
-        ));
-
-        // Instead of self.sdl_canvas.clear()
-        {
-            let display_size = self.sdl_canvas.output_size().unwrap();
-            self.sdl_canvas
-                .fill_rect(SdlRect::new(0, 0, display_size.0, display_size.1))
-                .unwrap();
-        }
+            .map_err(|err| {
+                warn!("Unable to open audio device: {}", err);
+                AudioError::Open(err)
+            })
+    }
 
-        let (screen_x, screen_y, screen_scale) = self.screen_pos_scale();
-        let dst = SdlRect::new(
-            screen_x as i32,
-            screen_y as i32,
-            width * screen_scale,
-            height * screen_scale,
+    fn update_screen_pos_scale(&mut self) {
+        let (window_width, window_height) = self.sdl_canvas.window().size();
+        self.screen_layout = ScreenLayout::new(
+            self.screen_fit,
+            self.pixel_aspect,
+            self.screen_width,
+            self.screen_height,
+            window_width,
+            window_height,
+            self.display_effect.scale(),
         );
-        self.sdl_canvas
-            .copy(&self.sdl_texture, None, Some(dst))
-            .unwrap();
-        self.sdl_canvas.present();
     }
 
-    fn start_audio(
-        &mut self,
-        sample_rate: u32,
-        num_samples: u32,
-        audio: shared_type!(dyn AudioCallback + Send),
-    ) {
-        let spec = SdlAudioSpecDesired {
-            freq: Some(sample_rate as i32),
-            channels: Some(1),
-            samples: Some(num_samples as u16),
-        };
-        self.sdl_audio_device = if let Ok(sdl_audio_device) =
-            self.sdl_audio
-                .open_playback(None, &spec, |_| AudioContextHolder { audio })
-        {
-            sdl_audio_device.resume();
-            Some(sdl_audio_device)
-        } else {
-            None
+    fn upscale_screen_texture(&mut self) -> bool {
+        // Sharp bilinear scales up by an integer factor with nearest filtering first,
+        // so the linear filtering of the final pass only blends the edges of each pixel
+        if self.screen_mode != ScreenMode::SharpBilinear {
+            return false;
+        }
+        let effect_scale = self.display_effect.scale();
+        let texture_width = self.screen_width * effect_scale;
+        let texture_height = self.screen_height * effect_scale;
+        let prescale = match self.screen_layout.prescale(texture_width, texture_height) {
+            Some(prescale) => prescale,
+            None => return false,
         };
+        let size = (texture_width * prescale, texture_height * prescale);
+        if self.sdl_upscaled_texture.as_ref().map(|(size, _)| *size) != Some(size) {
+            // The filtering of a texture is chosen when it is created
+            hint::set("SDL_RENDER_SCALE_QUALITY", "linear");
+            let sdl_upscaled_texture = self
+                .sdl_canvas
+                .texture_creator()
+                .create_texture_target(None, size.0, size.1);
+            hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
+            match sdl_upscaled_texture {
+                Ok(sdl_upscaled_texture) => {
+                    self.sdl_upscaled_texture = Some((size, sdl_upscaled_texture));
+                }
+                Err(err) => {
+                    error!("Unable to create the upscaled screen texture: {}", err);
+                    self.sdl_upscaled_texture = None;
+                    return false;
+                }
+            }
+        }
+        let sdl_texture = &self.sdl_texture;
+        let (_, sdl_upscaled_texture) = self.sdl_upscaled_texture.as_mut().unwrap();
+        let mut result = Ok(());
+        let target_result =
+            self.sdl_canvas
+                .with_texture_canvas(sdl_upscaled_texture, |sdl_canvas| {
+                    result = sdl_canvas.copy(sdl_texture, None, None);
+                });
+        match target_result.map_err(|err| err.to_string()).and(result) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Unable to upscale the screen: {}", err);
+                false
+            }
+        }
     }
 
-    fn pause_audio(&mut self) {
-        if let Some(audio_device) = &self.sdl_audio_device {
-            audio_device.pause();
+    fn open_game_controller(&mut self, device_index: u32) {
+        match self.sdl_game_controller.open(device_index) {
+            Ok(gc) => {
+                let instance_id = gc.instance_id();
+                if self.player_slots.slot(instance_id).is_some() {
+                    return;
+                }
+                match self.player_slots.add(instance_id) {
+                    Some(slot) => {
+                        info!("Opened controller '{}' in slot {}", gc.name(), slot);
+                        self.sdl_game_controllers.push(gc);
+                    }
+                    None => warn!("No free slot for controller '{}'", gc.name()),
+                }
+            }
+            Err(err) => {
+                if self.sdl_game_controller.is_game_controller(device_index) {
+                    warn!("Unable to open controller {}: {}", device_index, err);
+                } else {
+                    debug!("Device {} has no controller mapping", device_index);
+                }
+            }
         }
     }
 
-    fn resume_audio(&mut self) {
-        if let Some(audio_device) = &self.sdl_audio_device {
-            audio_device.resume();
+    fn open_joystick(&mut self, device_index: u32) {
+        if self.sdl_game_controller.is_game_controller(device_index) {
+            return;
+        }
+        match self.sdl_joystick.open(device_index) {
+            Ok(joystick) => {
+                if !self.is_raw_joystick(joystick.instance_id()) {
+                    info!("Opened joystick '{}'", joystick.name());
+                    self.sdl_joysticks.push(joystick);
+                }
+            }
+            Err(err) => warn!("Unable to open joystick {}: {}", device_index, err),
         }
     }
-}
 
-impl Sdl2 {
-    fn screen_pos_scale(&self) -> (u32, u32, u32) {
-        let (window_width, window_height) = self.sdl_canvas.window().size();
-        let screen_scale = min(
-            window_width / self.screen_width,
-            window_height / self.screen_height,
-        );
-        let screen_x = (window_width - self.screen_width * screen_scale) / 2;
-        let screen_y = (window_height - self.screen_height * screen_scale) / 2;
-        (screen_x, screen_y, screen_scale)
+    fn is_raw_joystick(&self, instance_id: u32) -> bool {
+        self.sdl_joysticks
+            .iter()
+            .any(|joystick| joystick.instance_id() == instance_id)
+    }
+
+    fn close_game_controller(&mut self, instance_id: u32) {
+        if let Some(slot) = self.player_slots.slot(instance_id) {
+            info!("Closed controller in slot {}", slot);
+        }
+        self.player_slots.remove(instance_id);
+        self.sdl_game_controllers
+            .retain(|gc| gc.instance_id() != instance_id);
+    }
+
+    fn drop_pos(&self) -> (i32, i32) {
+        let mouse_state = self.sdl_event_pump.mouse_state();
+        self.window_to_screen(mouse_state.x(), mouse_state.y())
     }
 
-    fn mouse_pos(&self) -> (i32, i32) {
+    fn mouse_window_pos(&self) -> (i32, i32) {
         let (window_x, window_y) = self.sdl_canvas.window().position();
-        let (screen_x, screen_y, screen_scale) = self.screen_pos_scale();
         let mut mouse_x = 0;
         let mut mouse_y = 0;
         unsafe {
             sdl2::sys::SDL_GetGlobalMouseState(&mut mouse_x, &mut mouse_y);
         }
-        mouse_x = (mouse_x - window_x - screen_x as i32) / screen_scale as i32;
-        mouse_y = (mouse_y - window_y - screen_y as i32) / screen_scale as i32;
-        (mouse_x, mouse_y)
+        (mouse_x - window_x, mouse_y - window_y)
+    }
+
+    fn peek_precise_wheel(&self) -> Option<(f32, f32)> {
+        // sdl2 0.35 binds the SDL 2.0.14 headers, which lack preciseX and preciseY, so they
+        // are read from the raw event at the offsets SDL 2.0.18 added them at
+        if !self.has_precise_wheel {
+            return None;
+        }
+        unsafe {
+            let mut raw_event: sdl2::sys::SDL_Event = std::mem::zeroed();
+            sdl2::sys::SDL_PumpEvents();
+            let num_events = sdl2::sys::SDL_PeepEvents(
+                &mut raw_event,
+                1,
+                sdl2::sys::SDL_eventaction::SDL_PEEKEVENT,
+                sdl2::sys::SDL_EventType::SDL_FIRSTEVENT as u32,
+                sdl2::sys::SDL_EventType::SDL_LASTEVENT as u32,
+            );
+            if num_events != 1 || raw_event.type_ != sdl2::sys::SDL_EventType::SDL_MOUSEWHEEL as u32
+            {
+                return None;
+            }
+            let precise = (&raw_event as *const sdl2::sys::SDL_Event)
+                .cast::<u8>()
+                .add(std::mem::size_of::<sdl2::sys::SDL_MouseWheelEvent>())
+                .cast::<f32>();
+            Some((precise.read_unaligned(), precise.add(1).read_unaligned()))
+        }
+    }
+
+    fn mouse_event_pos(&mut self, x: i32, y: i32) -> (i32, i32) {
+        self.is_mouse_on_screen = self.screen_layout.rect.contains_point(x, y);
+        if self.is_raw_mouse {
+            (x, y)
+        } else {
+            self.window_to_screen(x, y)
+        }
+    }
+
+    fn to_mouse_button(mouse_btn: SdlMouseButton) -> MouseButton {
+        match mouse_btn {
+            SdlMouseButton::Left => MouseButton::Left,
+            SdlMouseButton::Middle => MouseButton::Middle,
+            SdlMouseButton::Right => MouseButton::Right,
+            SdlMouseButton::X1 => MouseButton::X1,
+            SdlMouseButton::X2 => MouseButton::X2,
+            SdlMouseButton::Unknown => MouseButton::Unknown,
+        }
     }
 }
@@ -0,0 +1,551 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write as _;
+
+use array_macro::array;
+use serde::de::Error as _;
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::action_map::{ActionMap, Binding};
+use crate::canvas::Canvas;
+use crate::image::Image;
+use crate::palette::Palette;
+use crate::settings::{
+    EFFECT_FADEOUT, EFFECT_NONE, EFFECT_SLIDE, EFFECT_VIBRATO, IMAGE_SIZE, MAX_COLORS,
+    TONE_NARROW_PULSE, TONE_NOISE, TONE_PULSE, TONE_SAWTOOTH, TONE_SHORT_NOISE, TONE_SQUARE,
+    TONE_TRIANGLE,
+};
+use crate::sound::Sound;
+use crate::tilemap::Tilemap;
+use crate::types::{Color, Rgb8, Speed, Tile};
+use crate::utils::parse_hex_string;
+
+pub mod rgb {
+    use super::*;
+
+    pub fn to_string(rgb: Rgb8) -> String {
+        format!("#{:06X}", rgb & 0xffffff)
+    }
+
+    pub fn from_str(string: &str) -> Result<Rgb8, String> {
+        match string.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 => {
+                parse_hex_string(hex).map_err(|_| format!("invalid color '{}'", string))
+            }
+            _ => Err(format!("invalid color '{}'", string)),
+        }
+    }
+
+    pub fn serialize<S: Serializer>(rgb: &Rgb8, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&to_string(*rgb))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgb8, D::Error> {
+        from_str(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImageData {
+    width: u32,
+    height: u32,
+    data: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TilemapData {
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    data: Vec<String>,
+    // Optional so that tilemaps saved before these fields still load
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    colkey: Option<Color>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    solid_tiles: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SoundData {
+    notes: String,
+    tones: String,
+    volumes: String,
+    effects: String,
+    speed: Speed,
+}
+
+fn check_size(name: &str, data: &[String], width: u32, height: u32) -> Result<(), String> {
+    if width == 0 || height == 0 {
+        return Err(format!("{} size {}x{} is empty", name, width, height));
+    }
+    if data.len() != height as usize {
+        return Err(format!(
+            "{} has {} rows but its height is {}",
+            name,
+            data.len(),
+            height
+        ));
+    }
+    Ok(())
+}
+
+fn parse_row<T>(
+    row: &str,
+    y: usize,
+    width: u32,
+    digits: &[usize],
+    parse: impl Fn(&str) -> Option<T>,
+) -> Result<Vec<T>, String> {
+    // Each row holds one value per column, and the row length tells which digit count is used
+    let digit = digits
+        .iter()
+        .find(|digit| row.len() == width as usize * **digit)
+        .ok_or_else(|| format!("row {} has {} digits for width {}", y, row.len(), width))?;
+    if !row.is_ascii() {
+        return Err(format!("row {} is not hex", y));
+    }
+    (0..width as usize)
+        .map(|x| {
+            let value = &row[x * digit..(x + 1) * digit];
+            parse(value).ok_or_else(|| format!("invalid value '{}' in row {}", value, y))
+        })
+        .collect()
+}
+
+impl Serialize for Image {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Images that only use the first 16 colors take one digit per pixel
+        let is_wide = self.canvas.data.iter().flatten().any(|color| *color >= 16);
+        let data = self
+            .canvas
+            .data
+            .iter()
+            .map(|row| {
+                row.iter().fold(String::new(), |mut output, color| {
+                    if is_wide {
+                        let _ = write!(output, "{:02x}", color);
+                    } else {
+                        let _ = write!(output, "{:x}", color);
+                    }
+                    output
+                })
+            })
+            .collect();
+        ImageData {
+            width: self.width(),
+            height: self.height(),
+            data,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Image {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ImageData {
+            width,
+            height,
+            data,
+        } = ImageData::deserialize(deserializer)?;
+        check_size("image", &data, width, height).map_err(D::Error::custom)?;
+        let mut canvas = Canvas::new(width, height);
+        for (y, row) in data.iter().enumerate() {
+            canvas.data[y] = parse_row(row, y, width, &[1, 2], |value| {
+                parse_hex_string(value)
+                    .ok()
+                    .filter(|color| *color < MAX_COLORS)
+                    .map(|color| color as Color)
+            })
+            .map_err(D::Error::custom)?;
+        }
+        Ok(Self {
+            canvas,
+            palette: array![i => i as Color; MAX_COLORS as usize],
+            num_remapped_colors: 0,
+        })
+    }
+}
+
+impl Serialize for Tilemap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self
+            .canvas
+            .data
+            .iter()
+            .map(|row| {
+                row.iter().fold(String::new(), |mut output, tile| {
+                    let _ = write!(output, "{:02x}{:02x}", tile.0, tile.1);
+                    output
+                })
+            })
+            .collect();
+        // The set has no order, so the tiles are sorted to keep the output stable
+        let mut solid_tiles: Vec<Tile> = self.solid_tiles.iter().copied().collect();
+        solid_tiles.sort_unstable();
+        TilemapData {
+            width: self.width(),
+            height: self.height(),
+            tile_size: self.tile_size,
+            data,
+            colkey: self.colkey,
+            solid_tiles: solid_tiles
+                .iter()
+                .map(|tile| format!("{:02x}{:02x}", tile.0, tile.1))
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tilemap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // The image is not part of the data, so the tilemap starts with a blank one
+        let TilemapData {
+            width,
+            height,
+            tile_size,
+            data,
+            colkey,
+            solid_tiles,
+        } = TilemapData::deserialize(deserializer)?;
+        check_size("tilemap", &data, width, height).map_err(D::Error::custom)?;
+        if tile_size == 0 {
+            return Err(D::Error::custom("tile size is 0"));
+        }
+        let parse_tile = |value: &str| {
+            let tile_x = parse_hex_string(&value[0..2]).ok()?;
+            let tile_y = parse_hex_string(&value[2..4]).ok()?;
+            Some((tile_x as u8, tile_y as u8))
+        };
+        let mut canvas = Canvas::new(width, height);
+        for (y, row) in data.iter().enumerate() {
+            canvas.data[y] =
+                parse_row(row, y, width, &[4], parse_tile).map_err(D::Error::custom)?;
+        }
+        let solid_tiles = solid_tiles
+            .iter()
+            .map(|tile| {
+                (tile.len() == 4 && tile.is_ascii())
+                    .then(|| parse_tile(tile))
+                    .flatten()
+                    .ok_or_else(|| D::Error::custom(format!("invalid solid tile '{}'", tile)))
+            })
+            .collect::<Result<HashSet<Tile>, _>>()?;
+        Ok(Self {
+            canvas,
+            image: Image::new(IMAGE_SIZE, IMAGE_SIZE),
+            colkey,
+            tile_size,
+            solid_tiles,
+        })
+    }
+}
+
+const NOTE_NAMES: [&str; 12] = [
+    "c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b",
+];
+
+impl Serialize for Sound {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut notes = String::new();
+        for note in &self.notes {
+            if *note < 0 {
+                notes += "r";
+            } else {
+                notes += NOTE_NAMES[*note as usize % 12];
+                notes += &(*note / 12).to_string();
+            }
+        }
+        let to_chars = |values: &[u8], chars: &[(u8, char)]| -> Result<String, S::Error> {
+            values
+                .iter()
+                .map(|value| {
+                    chars
+                        .iter()
+                        .find(|(target, _)| target == value)
+                        .map(|(_, c)| *c)
+                        .ok_or_else(|| S::Error::custom(format!("invalid value {}", value)))
+                })
+                .collect()
+        };
+        SoundData {
+            notes,
+            tones: to_chars(
+                &self.tones,
+                &[
+                    (TONE_TRIANGLE, 't'),
+                    (TONE_SQUARE, 's'),
+                    (TONE_PULSE, 'p'),
+                    (TONE_NOISE, 'n'),
+                    (TONE_SAWTOOTH, 'w'),
+                    (TONE_NARROW_PULSE, 'e'),
+                    (TONE_SHORT_NOISE, 'm'),
+                ],
+            )?,
+            volumes: self
+                .volumes
+                .iter()
+                .map(|volume| volume.to_string())
+                .collect(),
+            effects: to_chars(
+                &self.effects,
+                &[
+                    (EFFECT_NONE, 'n'),
+                    (EFFECT_SLIDE, 's'),
+                    (EFFECT_VIBRATO, 'v'),
+                    (EFFECT_FADEOUT, 'f'),
+                ],
+            )?,
+            speed: self.speed,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sound {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = SoundData::deserialize(deserializer)?;
+        let mut sound = Self::new().lock().clone();
+        sound
+            .try_set(
+                &data.notes,
+                &data.tones,
+                &data.volumes,
+                &data.effects,
+                data.speed,
+            )
+            .map_err(D::Error::custom)?;
+        Ok(sound)
+    }
+}
+
+impl Serialize for Palette {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let colors: Vec<String> = self
+            .colors()
+            .iter()
+            .map(|rgb| rgb::to_string(*rgb))
+            .collect();
+        colors.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Palette {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let colors = Vec::<String>::deserialize(deserializer)?;
+        if colors.is_empty() || colors.len() > MAX_COLORS as usize {
+            return Err(D::Error::custom(format!(
+                "palette has {} colors but must have 1 to {}",
+                colors.len(),
+                MAX_COLORS
+            )));
+        }
+        let colors = colors
+            .iter()
+            .map(|color| rgb::from_str(color))
+            .collect::<Result<Vec<Rgb8>, String>>()
+            .map_err(D::Error::custom)?;
+        Ok(Self::new(&colors))
+    }
+}
+
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut name = String::new();
+        write!(name, "{}", self)
+            .map_err(|_| S::Error::custom(format!("unnamed binding {:?}", self)))?;
+        serializer.serialize_str(&name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("invalid binding '{}'", name)))
+    }
+}
+
+impl Serialize for ActionMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.bindings.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ActionMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bindings = BTreeMap::<String, Vec<Binding>>::deserialize(deserializer)?;
+        let mut action_map = Self::new();
+        for (action, bindings) in bindings {
+            for binding in bindings {
+                action_map.bind(&action, binding);
+            }
+        }
+        Ok(action_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::ControllerButton;
+    use crate::key::{KEY_LEFT, KEY_RIGHT, KEY_SPACE};
+    use crate::settings::DEFAULT_COLORS;
+
+    fn round_trip<T: Serialize + for<'de> Deserialize<'de>>(value: &T) -> (String, String) {
+        let json = serde_json::to_string(value).unwrap();
+        let ron = ron::to_string(value).unwrap();
+        let from_json: T = serde_json::from_str(&json).unwrap();
+        let from_ron: T = ron::from_str(&ron).unwrap();
+        assert_eq!(serde_json::to_string(&from_json).unwrap(), json);
+        assert_eq!(ron::to_string(&from_ron).unwrap(), ron);
+        (json, ron)
+    }
+
+    #[test]
+    fn image() {
+        let image = Image::new(3, 2);
+        image.lock().set(0, 0, &["012", "f0a"]);
+        let (json, ron) = round_trip(&*image.lock());
+        assert_eq!(json, r#"{"width":3,"height":2,"data":["012","f0a"]}"#);
+        assert_eq!(ron, r#"(width:3,height:2,data:["012","f0a"])"#);
+
+        image.lock().canvas.data[1][2] = 200;
+        let (json, _) = round_trip(&*image.lock());
+        assert_eq!(json, r#"{"width":3,"height":2,"data":["000102","0f00c8"]}"#);
+
+        for (json, message) in [
+            (
+                r#"{"width":3,"height":1,"data":["01","23"]}"#,
+                "image has 2 rows but its height is 1",
+            ),
+            (
+                r#"{"width":3,"height":1,"data":["0123"]}"#,
+                "row 0 has 4 digits for width 3",
+            ),
+            (
+                r#"{"width":2,"height":1,"data":["0g"]}"#,
+                "invalid value 'g' in row 0",
+            ),
+            (
+                r#"{"width":0,"height":0,"data":[]}"#,
+                "image size 0x0 is empty",
+            ),
+        ] {
+            let err = serde_json::from_str::<Image>(json).err().unwrap();
+            assert!(err.to_string().starts_with(message), "{}", err);
+        }
+    }
+
+    #[test]
+    fn tilemap() {
+        let tilemap = Tilemap::new(2, 2, Image::new(8, 8));
+        tilemap.lock().set(0, 0, &["0102 0304", "ff00 0a0b"]);
+        let (json, ron) = round_trip(&*tilemap.lock());
+        assert_eq!(
+            json,
+            r#"{"width":2,"height":2,"tile_size":8,"data":["01020304","ff000a0b"]}"#
+        );
+        assert_eq!(
+            ron,
+            r#"(width:2,height:2,tile_size:8,data:["01020304","ff000a0b"])"#
+        );
+        let err = ron::from_str::<Tilemap>(r#"(width:2,height:1,tile_size:8,data:["010203"])"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("row 0 has 6 digits for width 2"));
+
+        tilemap.lock().colkey = Some(3);
+        tilemap.lock().set_collision(&[(10, 11), (1, 2)]);
+        let (json, _) = round_trip(&*tilemap.lock());
+        assert_eq!(
+            json,
+            r#"{"width":2,"height":2,"tile_size":8,"data":["01020304","ff000a0b"],"colkey":3,"solid_tiles":["0102","0a0b"]}"#
+        );
+        let tilemap: Tilemap = serde_json::from_str(&json).unwrap();
+        assert_eq!(tilemap.colkey, Some(3));
+        assert!(tilemap.is_solid_tile(0, 0));
+        assert!(!tilemap.is_solid_tile(1, 0));
+        assert!(tilemap.is_solid_tile(1, 1));
+
+        let err = serde_json::from_str::<Tilemap>(
+            r#"{"width":1,"height":1,"tile_size":8,"data":["0000"],"solid_tiles":["01"]}"#,
+        )
+        .err()
+        .unwrap();
+        assert!(
+            err.to_string().starts_with("invalid solid tile '01'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn sound() {
+        let sound = Sound::new();
+        sound
+            .lock()
+            .set("c0 d#1 r b4", "tspnwem", "0127", "nsvf", 12);
+        let (json, ron) = round_trip(&*sound.lock());
+        assert_eq!(
+            json,
+            r#"{"notes":"c0d#1rb4","tones":"tspnwem","volumes":"0127","effects":"nsvf","speed":12}"#
+        );
+        assert_eq!(
+            ron,
+            r#"(notes:"c0d#1rb4",tones:"tspnwem",volumes:"0127",effects:"nsvf",speed:12)"#
+        );
+        let err = serde_json::from_str::<Sound>(
+            r#"{"notes":"c5","tones":"","volumes":"","effects":"","speed":1}"#,
+        )
+        .err()
+        .unwrap();
+        assert!(
+            err.to_string().starts_with("Invalid sound note 'c5'"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn palette() {
+        let palette = Palette::new(&DEFAULT_COLORS[..2]);
+        let (json, ron) = round_trip(&palette);
+        assert_eq!(json, r##"["#000000","#2B335F"]"##);
+        assert_eq!(ron, r##"["#000000","#2B335F"]"##);
+        assert_eq!(
+            serde_json::from_str::<Palette>(r##"["#12345g"]"##)
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid color '#12345g'"
+        );
+        assert!(serde_json::from_str::<Palette>("[]").is_err());
+        assert_eq!(rgb::from_str("#ff8000"), Ok(0xff8000));
+        assert!(rgb::from_str("ff8000").is_err());
+    }
+
+    #[test]
+    fn action_map() {
+        let mut action_map = ActionMap::new();
+        action_map.bind("jump", Binding::Key(KEY_SPACE));
+        action_map.bind("jump", Binding::Button(ControllerButton::A));
+        action_map.bind("move_x", Binding::AxisPair(KEY_LEFT, KEY_RIGHT));
+        let (json, _) = round_trip(&action_map);
+        assert_eq!(
+            json,
+            r#"{"jump":["SPACE","BUTTON_A"],"move_x":["LEFT,RIGHT"]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ActionMap>(&json).unwrap(),
+            action_map
+        );
+        assert_eq!(
+            serde_json::from_str::<ActionMap>(r#"{"jump":["BUTTON_Q"]}"#)
+                .err()
+                .unwrap()
+                .to_string(),
+            "invalid binding 'BUTTON_Q' at line 1 column 20"
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use crate::key::KEY_ESCAPE;
+#[cfg(feature = "logger")]
+use log::LevelFilter;
+
+use crate::key::{KEY_ESCAPE, KEY_F10, KEY_F11, KEY_F6, KEY_F7, KEY_F8, KEY_F9};
 use crate::types::{Color, Effect, Key, Note, Rgb8, Speed, Tone, Volume};
 
 // System
@@ -7,12 +10,43 @@ pub const PYXEL_WORKING_DIR: &str = "~/.pyxel";
 pub const DEFAULT_TITLE: &str = "Pyxel";
 pub const DEFAULT_FPS: u32 = 30;
 pub const DEFAULT_QUIT_KEY: Key = KEY_ESCAPE;
+pub const DEFAULT_PAUSE_KEY: Key = KEY_F9;
+pub const DEFAULT_STEP_KEY: Key = KEY_F10;
+pub const DEFAULT_SCREENSHOT_KEY: Key = KEY_F8;
+pub const DEFAULT_RECORDING_KEY: Key = KEY_F7;
+pub const DEFAULT_PERF_MONITOR_KEY: Key = KEY_F6;
+pub const DEFAULT_FULLSCREEN_KEY: Key = KEY_F11;
+#[cfg(feature = "logger")]
+pub const LOG_LEVEL_ENV: &str = "PYXEL_LOG";
+#[cfg(feature = "logger")]
+pub const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Warn;
 pub const DEFAULT_CAPTURE_SCALE: u32 = 2;
 pub const DEFAULT_CAPTURE_SEC: u32 = 10;
+pub const MAX_SAVE_SCALE: u32 = 32;
 pub const DISPLAY_RATIO: f64 = 0.8;
 pub const BACKGROUND_COLOR: Rgb8 = 0x101018;
-pub const MAX_SKIP_FRAMES: u32 = 9;
-pub const NUM_MEASURE_FRAMES: u32 = 10;
+pub const DISPLAY_EFFECT_NONE: u32 = 0;
+pub const DISPLAY_EFFECT_SCANLINES: u32 = 1;
+pub const DISPLAY_EFFECT_GRID: u32 = 2;
+pub const SCREEN_FIT_INTEGER: u32 = 0;
+pub const SCREEN_FIT_ASPECT: u32 = 1;
+pub const SCREEN_FIT_FILL: u32 = 2;
+pub const SCREEN_MODE_NEAREST: u32 = 0;
+pub const SCREEN_MODE_SHARP_BILINEAR: u32 = 1;
+pub const ALIGN_LEFT: u32 = 0;
+pub const ALIGN_CENTER: u32 = 1;
+pub const ALIGN_RIGHT: u32 = 2;
+pub const CAPTURE_ACTION_SCREENSHOT: u32 = 0;
+pub const CAPTURE_ACTION_RECORDING: u32 = 1;
+pub const CAPTURE_ACTION_PERF_MONITOR: u32 = 2;
+pub const CAPTURE_ACTION_FULLSCREEN: u32 = 3;
+pub const DEFAULT_SCANLINE_DARKNESS: u8 = 96;
+pub const GRID_DARKNESS: u8 = 64;
+pub const DEFAULT_MAX_FRAME_SKIP: u32 = 5;
+pub const FRAME_STATS_WINDOW_MS: u64 = 1000;
+pub const PROFILER_NUM_FRAMES: usize = 120;
+pub const PROFILER_GRAPH_HEIGHT: u32 = 32;
+pub const PROFILER_COLORS: [Color; 6] = [8, 11, 12, 10, 14, 9];
 pub const ICON_SIZE: u32 = 16;
 pub const ICON_SCALE: u32 = 4;
 pub const ICON_DATA: [&str; ICON_SIZE as usize] = [
@@ -39,14 +73,39 @@ pub const APP_FILE_EXTENSION: &str = ".pyxapp";
 pub const APP_STARTUP_SCRIPT_FILE: &str = ".pyxapp_startup_script";
 pub const RESOURCE_FILE_EXTENSION: &str = ".pyxres";
 pub const RESOURCE_ARCHIVE_DIRNAME: &str = "pyxel_resource/";
+pub const STORAGE_FILE_EXTENSION: &str = ".sav";
+pub const STORAGE_SIGNATURE: &[u8] = b"PYXELSAV";
+
+// Input
+pub const NUM_PLAYER_SLOTS: u32 = 4;
+pub const VIRTUAL_GAMEPAD: u32 = u32::MAX;
+pub const VIRTUAL_GAMEPAD_AXIS_VALUE: i32 = 32767;
+pub const MAX_AXIS_VALUE: f32 = 32767.0;
+pub const DEFAULT_AXIS_DEADZONE_INNER: f32 = 0.15;
+pub const DEFAULT_AXIS_DEADZONE_OUTER: f32 = 0.95;
+pub const ACTION_AXIS_THRESHOLD: f32 = 0.5;
 
 // Graphics
 pub const NUM_COLORS: u32 = 16;
+pub const MAX_COLORS: u32 = 256;
 pub const NUM_IMAGES: u32 = 3;
 pub const IMAGE_SIZE: u32 = 256;
 pub const NUM_TILEMAPS: u32 = 8;
+pub const NUM_LAYERS: u32 = 2;
+pub const VIRTUAL_KEYBOARD_COLUMNS: u32 = 10;
+pub const VIRTUAL_KEYBOARD_KEY_SIZE: u32 = 10;
+pub const VIRTUAL_KEYBOARD_HOLD_FRAMES: u32 = 15;
+pub const VIRTUAL_KEYBOARD_REPEAT_FRAMES: u32 = 4;
+pub const VIRTUAL_KEYBOARD_CHAR_SETS: [&str; 4] = [
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZ",
+    "abcdefghijklmnopqrstuvwxyz",
+    "0123456789",
+    "!?.,:;'\"-+*/=()#&@_",
+];
 pub const TILEMAP_SIZE: u32 = 256;
 pub const TILE_SIZE: u32 = 8;
+pub const BEZIER_SEGMENT_LENGTH: f64 = 4.0;
+pub const DIRTY_BLOCK_SIZE: u32 = 16;
 pub const DEFAULT_COLORS: [Rgb8; NUM_COLORS as usize] = [
     0x000000, 0x2b335f, 0x7e2072, 0x19959c, 0x8b4852, 0x395c98, 0xa9c1ff, 0xeeeeee, 0xd4186c,
     0xd38441, 0xe9c35b, 0x70c6a9, 0x7696de, 0xa3a3a3, 0xFF9798, 0xedc7b0,
@@ -94,11 +153,27 @@ pub const FONT_DATA: [u32; MAX_FONT_CODE as usize - MIN_FONT_CODE as usize + 1]
 // Audio
 pub const CLOCK_RATE: u32 = 1789773; // 1.78 MHz clock rate
 pub const SAMPLE_RATE: u32 = 44100; // 44.1 kHz sample rate
+pub const MIN_SAMPLE_RATE: u32 = 8000;
+pub const MAX_SAMPLE_RATE: u32 = 192000;
 pub const NUM_SAMPLES: u32 = SAMPLE_RATE / 30;
+pub const NUM_OUTPUT_CHANNELS: u32 = 2;
 pub const NUM_CLOCKS_PER_TICK: u32 = CLOCK_RATE / 120;
 pub const OSCILLATOR_RESOLUTION: u32 = 32;
+pub const STOP_RAMP_CLOCKS: u32 = CLOCK_RATE / 1000; // 1 ms ramp to silence
+pub const NUM_STOP_RAMP_STEPS: u32 = 16;
+pub const VOLUME_RAMP_CLOCKS: u32 = CLOCK_RATE / 200; // 5 ms volume ramp
+pub const VOLUME_RAMP_SAMPLES: u32 = SAMPLE_RATE / 200;
+pub const SOFT_CLIP_THRESHOLD: f32 = 0.8;
+pub const AUDIO_CAPTURE_BUFFER_SEC: u32 = 1;
+pub const AUDIO_CAPTURE_CHUNK_SIZE: u32 = 4096;
+pub const AUDIO_LOCK_WAIT_THRESHOLD_US: u64 = 100;
+pub const AUDIO_LOCK_TIMEOUT_US: u64 = 5000;
+pub const AUDIO_COMMAND_QUEUE_SIZE: u32 = 256;
 pub const VIBRATO_DEPTH: f64 = 0.015;
 pub const VIBRATO_FREQUENCY: f64 = 6.0;
+pub const MIN_CHANNEL_SPEED: f64 = 0.25;
+pub const MAX_CHANNEL_SPEED: f64 = 4.0;
+pub const MAX_CHANNEL_PITCH: f64 = 24.0;
 pub const NUM_CHANNELS: u32 = 4;
 pub const NUM_SOUNDS: u32 = 64;
 pub const NUM_MUSICS: u32 = 8;
@@ -106,16 +181,28 @@ pub const TRIANGLE_VOLUME_FACTOR: f64 = 1.0;
 pub const SQUARE_VOLUME_FACTOR: f64 = 0.3;
 pub const PULSE_VOLUME_FACTOR: f64 = 0.3;
 pub const NOISE_VOLUME_FACTOR: f64 = 0.6;
+pub const SAWTOOTH_VOLUME_FACTOR: f64 = 0.4;
+pub const NARROW_PULSE_VOLUME_FACTOR: f64 = 0.3;
 pub const TONE_TRIANGLE: Tone = 0;
 pub const TONE_SQUARE: Tone = 1;
 pub const TONE_PULSE: Tone = 2;
 pub const TONE_NOISE: Tone = 3;
+pub const TONE_SAWTOOTH: Tone = 4;
+pub const TONE_NARROW_PULSE: Tone = 5;
+pub const TONE_SHORT_NOISE: Tone = 6;
 pub const EFFECT_NONE: Effect = 0;
 pub const EFFECT_SLIDE: Effect = 1;
 pub const EFFECT_VIBRATO: Effect = 2;
 pub const EFFECT_FADEOUT: Effect = 3;
-pub const MAX_TONE: Tone = 3;
+pub const MAX_TONE: Tone = 6;
 pub const MAX_NOTE: Note = 59; // 5 octaves
 pub const MAX_VOLUME: Volume = 7;
 pub const MAX_EFFECT: Effect = 3;
+pub const MML_DEFAULT_TEMPO: u32 = 120;
+pub const MML_MAX_TEMPO: u32 = 960;
+pub const MML_DEFAULT_OCTAVE: u32 = 4;
+pub const MML_MIN_OCTAVE: u32 = 2; // o2c is c0, so o4a is a2 (440 Hz)
+pub const MML_MAX_OCTAVE: u32 = 6;
+pub const MML_DEFAULT_LENGTH: u32 = 4;
+pub const MML_MAX_LENGTH: u32 = 64;
 pub const INITIAL_SPEED: Speed = 30;
@@ -1,12 +1,42 @@
+use std::cmp::max;
+use std::fmt;
+use std::path::Path;
+
+use crate::audio::render_sounds;
+use crate::envelope::Envelope;
+use crate::image::SharedImage;
+use crate::mml::{parse_mml, MmlError};
+use crate::platform::AudioError;
 use crate::resource::ResourceItem;
+use crate::sample::write_wav;
 use crate::settings::{
     EFFECT_FADEOUT, EFFECT_NONE, EFFECT_SLIDE, EFFECT_VIBRATO, INITIAL_SPEED,
-    RESOURCE_ARCHIVE_DIRNAME, TONE_NOISE, TONE_PULSE, TONE_SQUARE, TONE_TRIANGLE,
+    RESOURCE_ARCHIVE_DIRNAME, TONE_NARROW_PULSE, TONE_NOISE, TONE_PULSE, TONE_SAWTOOTH,
+    TONE_SHORT_NOISE, TONE_SQUARE, TONE_TRIANGLE, VIBRATO_DEPTH,
 };
 use crate::types::{Effect, Note, Speed, Tone, Volume};
 use crate::utils::{parse_hex_string, simplify_string};
 use crate::Pyxel;
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum SoundError {
+    InvalidNote(String),
+    InvalidTone(char),
+    InvalidVolume(char),
+    InvalidEffect(char),
+}
+
+impl fmt::Display for SoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidNote(note) => write!(f, "Invalid sound note '{}'", note),
+            Self::InvalidTone(tone) => write!(f, "Invalid sound tone '{}'", tone),
+            Self::InvalidVolume(volume) => write!(f, "Invalid sound volume '{}'", volume),
+            Self::InvalidEffect(effect) => write!(f, "Invalid sound effect '{}'", effect),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Sound {
     pub notes: Vec<Note>,
@@ -14,6 +44,8 @@ pub struct Sound {
     pub volumes: Vec<Volume>,
     pub effects: Vec<Effect>,
     pub speed: Speed,
+    pub envelope: Envelope,
+    pub vibrato_depth: f64,
 }
 
 pub type SharedSound = shared_type!(Sound);
@@ -26,9 +58,17 @@ impl Sound {
             volumes: Vec::new(),
             effects: Vec::new(),
             speed: INITIAL_SPEED,
+            envelope: Envelope::default(),
+            vibrato_depth: VIBRATO_DEPTH,
         })
     }
 
+    pub fn from_mml(mml: &str) -> Result<SharedSound, MmlError> {
+        let sound = Self::new();
+        sound.lock().try_set_mml(mml)?;
+        Ok(sound)
+    }
+
     pub fn set(
         &mut self,
         note_str: &str,
@@ -37,90 +77,179 @@ impl Sound {
         effect_str: &str,
         speed: Speed,
     ) {
-        self.set_notes(note_str);
-        self.set_tones(tone_str);
-        self.set_volumes(volume_str);
-        self.set_effects(effect_str);
+        self.try_set(note_str, tone_str, volume_str, effect_str, speed)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_set(
+        &mut self,
+        note_str: &str,
+        tone_str: &str,
+        volume_str: &str,
+        effect_str: &str,
+        speed: Speed,
+    ) -> Result<(), SoundError> {
+        let notes = parse_notes(note_str)?;
+        let tones = parse_tones(tone_str)?;
+        let volumes = parse_volumes(volume_str)?;
+        let effects = parse_effects(effect_str)?;
+        self.notes = notes;
+        self.tones = tones;
+        self.volumes = volumes;
+        self.effects = effects;
         self.speed = speed;
+        Ok(())
+    }
+
+    pub fn set_mml(&mut self, mml: &str) {
+        self.try_set_mml(mml)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_set_mml(&mut self, mml: &str) -> Result<(), MmlError> {
+        let mml_sound = parse_mml(mml)?;
+        self.notes = mml_sound.notes;
+        self.tones = mml_sound.tones;
+        self.volumes = mml_sound.volumes;
+        self.effects = mml_sound.effects;
+        self.speed = mml_sound.speed;
+        Ok(())
+    }
+
+    pub fn to_wav(&self, path: &Path, sample_rate: u32) -> Result<(), AudioError> {
+        let sound = new_shared_type!(self.clone());
+        let samples = render_sounds(vec![vec![sound]], sample_rate, false, self.num_ticks())?;
+        write_wav(path, sample_rate, &samples)
+    }
+
+    pub(crate) fn num_ticks(&self) -> u32 {
+        self.notes.len() as u32 * max(self.speed, 1)
     }
 
     pub fn set_notes(&mut self, note_str: &str) {
-        let note_str = simplify_string(note_str);
-        let mut chars = note_str.chars();
-        self.notes.clear();
-        while let Some(c) = chars.next() {
-            let mut note: Note;
-            if ('a'..='g').contains(&c) {
-                note = match c {
-                    'c' => 0,
-                    'd' => 2,
-                    'e' => 4,
-                    'f' => 5,
-                    'g' => 7,
-                    'a' => 9,
-                    'b' => 11,
-                    _ => panic!("Invalid sound note '{}'", c),
-                };
-                let mut c = chars.next().unwrap_or(0 as char);
-                if c == '#' {
-                    note += 1;
-                    c = chars.next().unwrap_or(0 as char);
-                } else if c == '-' {
-                    note -= 1;
-                    c = chars.next().unwrap_or(0 as char);
-                }
-                if ('0'..='4').contains(&c) {
-                    note += (c as Note - '0' as Note) * 12;
-                } else {
-                    panic!("Invalid sound note '{}'", c);
-                }
-            } else if c == 'r' {
-                note = -1;
-            } else {
-                panic!("Invalid sound note '{}'", c);
-            }
-            self.notes.push(note);
-        }
+        self.try_set_notes(note_str)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_set_notes(&mut self, note_str: &str) -> Result<(), SoundError> {
+        self.notes = parse_notes(note_str)?;
+        Ok(())
     }
 
     pub fn set_tones(&mut self, tone_str: &str) {
-        self.tones.clear();
-        for c in simplify_string(tone_str).chars() {
-            let tone = match c {
-                't' => TONE_TRIANGLE,
-                's' => TONE_SQUARE,
-                'p' => TONE_PULSE,
-                'n' => TONE_NOISE,
-                _ => panic!("Invalid sound tone '{}'", c),
-            };
-            self.tones.push(tone);
-        }
+        self.try_set_tones(tone_str)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_set_tones(&mut self, tone_str: &str) -> Result<(), SoundError> {
+        self.tones = parse_tones(tone_str)?;
+        Ok(())
     }
 
     pub fn set_volumes(&mut self, volume_str: &str) {
-        self.volumes.clear();
-        for c in simplify_string(volume_str).chars() {
-            if ('0'..='7').contains(&c) {
-                self.volumes.push((c as u32 - '0' as u32) as Volume);
-            } else {
-                panic!("Invalid sound volume '{}'", c);
-            }
-        }
+        self.try_set_volumes(volume_str)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_set_volumes(&mut self, volume_str: &str) -> Result<(), SoundError> {
+        self.volumes = parse_volumes(volume_str)?;
+        Ok(())
     }
 
     pub fn set_effects(&mut self, effect_str: &str) {
-        self.effects.clear();
-        for c in simplify_string(effect_str).chars() {
-            let effect = match c {
-                'n' => EFFECT_NONE,
-                's' => EFFECT_SLIDE,
-                'v' => EFFECT_VIBRATO,
-                'f' => EFFECT_FADEOUT,
-                _ => panic!("Invalid sound effect '{}'", c),
-            };
-            self.effects.push(effect);
+        self.try_set_effects(effect_str)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_set_effects(&mut self, effect_str: &str) -> Result<(), SoundError> {
+        self.effects = parse_effects(effect_str)?;
+        Ok(())
+    }
+}
+
+fn parse_notes(note_str: &str) -> Result<Vec<Note>, SoundError> {
+    let note_str = simplify_string(note_str);
+    let mut chars = note_str.chars().peekable();
+    let mut notes = Vec::new();
+    while let Some(c) = chars.next() {
+        if c == 'r' {
+            notes.push(-1);
+            continue;
         }
+        let mut note: Note = match c {
+            'c' => 0,
+            'd' => 2,
+            'e' => 4,
+            'f' => 5,
+            'g' => 7,
+            'a' => 9,
+            'b' => 11,
+            _ => return Err(SoundError::InvalidNote(c.to_string())),
+        };
+        let mut name = c.to_string();
+        if let Some(&accidental) = chars.peek() {
+            if accidental == '#' || accidental == '-' {
+                note += if accidental == '#' { 1 } else { -1 };
+                name.push(accidental);
+                chars.next();
+            }
+        }
+        match chars.next() {
+            Some(octave @ '0'..='4') => {
+                name.push(octave);
+                note += (octave as Note - '0' as Note) * 12;
+            }
+            Some(c) => {
+                name.push(c);
+                return Err(SoundError::InvalidNote(name));
+            }
+            None => return Err(SoundError::InvalidNote(name)),
+        }
+        if note < 0 {
+            return Err(SoundError::InvalidNote(name));
+        }
+        notes.push(note);
     }
+    Ok(notes)
+}
+
+fn parse_tones(tone_str: &str) -> Result<Vec<Tone>, SoundError> {
+    simplify_string(tone_str)
+        .chars()
+        .map(|c| match c {
+            't' => Ok(TONE_TRIANGLE),
+            's' => Ok(TONE_SQUARE),
+            'p' => Ok(TONE_PULSE),
+            'n' => Ok(TONE_NOISE),
+            'w' => Ok(TONE_SAWTOOTH),
+            'e' => Ok(TONE_NARROW_PULSE),
+            'm' => Ok(TONE_SHORT_NOISE),
+            _ => Err(SoundError::InvalidTone(c)),
+        })
+        .collect()
+}
+
+fn parse_volumes(volume_str: &str) -> Result<Vec<Volume>, SoundError> {
+    simplify_string(volume_str)
+        .chars()
+        .map(|c| match c {
+            '0'..='7' => Ok((c as u32 - '0' as u32) as Volume),
+            _ => Err(SoundError::InvalidVolume(c)),
+        })
+        .collect()
+}
+
+fn parse_effects(effect_str: &str) -> Result<Vec<Effect>, SoundError> {
+    simplify_string(effect_str)
+        .chars()
+        .map(|c| match c {
+            'n' => Ok(EFFECT_NONE),
+            's' => Ok(EFFECT_SLIDE),
+            'v' => Ok(EFFECT_VIBRATO),
+            'f' => Ok(EFFECT_FADEOUT),
+            _ => Err(SoundError::InvalidEffect(c)),
+        })
+        .collect()
 }
 
 impl ResourceItem for Sound {
@@ -141,6 +270,8 @@ impl ResourceItem for Sound {
         self.volumes.clear();
         self.effects.clear();
         self.speed = INITIAL_SPEED;
+        self.envelope = Envelope::default();
+        self.vibrato_depth = VIBRATO_DEPTH;
     }
 
     fn serialize(&self, _pyxel: &Pyxel) -> String {
@@ -178,37 +309,57 @@ impl ResourceItem for Sound {
         output
     }
 
-    fn deserialize(&mut self, _pyxel: &Pyxel, _version: u32, input: &str) {
-        self.clear();
+    fn deserialize(
+        &mut self,
+        _images: &[SharedImage],
+        _version: u32,
+        input: &str,
+    ) -> Result<(), String> {
+        let mut sound = Self {
+            notes: Vec::new(),
+            tones: Vec::new(),
+            volumes: Vec::new(),
+            effects: Vec::new(),
+            speed: INITIAL_SPEED,
+            envelope: Envelope::default(),
+            vibrato_depth: VIBRATO_DEPTH,
+        };
+        let invalid_value = |value: &str| format!("invalid value '{}'", value);
         for (i, line) in input.lines().enumerate() {
             if line == "none" {
                 continue;
             }
             if i == 0 {
                 string_loop!(j, value, line, 2, {
-                    self.notes.push(parse_hex_string(&value).unwrap() as i8);
+                    let note = parse_hex_string(&value).map_err(|_| invalid_value(&value))?;
+                    sound.notes.push(note as i8);
                 });
                 continue;
             } else if i == 4 {
-                self.speed = line.parse().unwrap();
+                sound.speed = line
+                    .parse()
+                    .map_err(|_| format!("invalid speed '{}'", line))?;
                 continue;
             }
             let data = match i {
-                1 => &mut self.tones,
-                2 => &mut self.volumes,
-                3 => &mut self.effects,
-                _ => panic!(),
+                1 => &mut sound.tones,
+                2 => &mut sound.volumes,
+                3 => &mut sound.effects,
+                _ => return Err(format!("unexpected line {}", i + 1)),
             };
             string_loop!(j, value, line, 1, {
-                data.push(parse_hex_string(&value).unwrap() as u8);
+                data.push(parse_hex_string(&value).map_err(|_| invalid_value(&value))? as u8);
             });
         }
+        *self = sound;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::settings::{CLOCK_RATE, NUM_CLOCKS_PER_TICK};
 
     #[test]
     fn new() {
@@ -249,10 +400,18 @@ mod tests {
     #[test]
     fn set_tone() {
         let sound = Sound::new();
-        sound.lock().set_tones(" t s p n ");
+        sound.lock().set_tones(" t s p n w e m ");
         assert_eq!(
             &sound.lock().tones,
-            &vec![TONE_TRIANGLE, TONE_SQUARE, TONE_PULSE, TONE_NOISE]
+            &vec![
+                TONE_TRIANGLE,
+                TONE_SQUARE,
+                TONE_PULSE,
+                TONE_NOISE,
+                TONE_SAWTOOTH,
+                TONE_NARROW_PULSE,
+                TONE_SHORT_NOISE
+            ]
         );
     }
 
@@ -272,4 +431,131 @@ mod tests {
             &vec![EFFECT_NONE, EFFECT_SLIDE, EFFECT_VIBRATO, EFFECT_FADEOUT]
         );
     }
+
+    #[test]
+    fn set_invalid() {
+        let sound = Sound::new();
+        sound.lock().set("c2e2", "s", "7", "n", 20);
+        let cases = [
+            (
+                "x1",
+                "s",
+                "7",
+                "n",
+                SoundError::InvalidNote("x".to_string()),
+            ),
+            ("c", "s", "7", "n", SoundError::InvalidNote("c".to_string())),
+            (
+                "c#",
+                "s",
+                "7",
+                "n",
+                SoundError::InvalidNote("c#".to_string()),
+            ),
+            (
+                "c5",
+                "s",
+                "7",
+                "n",
+                SoundError::InvalidNote("c5".to_string()),
+            ),
+            (
+                "c-0",
+                "s",
+                "7",
+                "n",
+                SoundError::InvalidNote("c-0".to_string()),
+            ),
+            (
+                "c2d",
+                "s",
+                "7",
+                "n",
+                SoundError::InvalidNote("d".to_string()),
+            ),
+            ("c2", "sx", "7", "n", SoundError::InvalidTone('x')),
+            ("c2", "s", "8", "n", SoundError::InvalidVolume('8')),
+            ("c2", "s", "7", "z", SoundError::InvalidEffect('z')),
+        ];
+        for (notes, tones, volumes, effects, err) in cases {
+            assert_eq!(
+                sound.lock().try_set(notes, tones, volumes, effects, 30),
+                Err(err)
+            );
+            assert_eq!(&sound.lock().notes, &vec![24, 28]);
+            assert_eq!(sound.lock().speed, 20);
+        }
+        assert_eq!(
+            sound.lock().try_set_volumes("7 6 q"),
+            Err(SoundError::InvalidVolume('q'))
+        );
+        assert_eq!(&sound.lock().volumes, &vec![7]);
+        assert_eq!(
+            SoundError::InvalidNote("c-0".to_string()).to_string(),
+            "Invalid sound note 'c-0'"
+        );
+        sound.lock().set_notes("c-1");
+        assert_eq!(&sound.lock().notes, &vec![11]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid sound tone 'x'")]
+    fn set_invalid_panics() {
+        Sound::new().lock().set_tones("x");
+    }
+
+    #[test]
+    fn to_wav() {
+        let sound = Sound::new();
+        sound.lock().set("c3", "s", "7", "n", 12);
+        let path = std::env::temp_dir().join("pyxel_sound_to_wav.wav");
+        sound.lock().to_wav(&path, 22050).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&data[0..4], b"RIFF");
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([data[22], data[23]]), 1);
+        assert_eq!(
+            u32::from_le_bytes([data[24], data[25], data[26], data[27]]),
+            22050
+        );
+        assert_eq!(u16::from_le_bytes([data[34], data[35]]), 16);
+        assert_eq!(&data[36..40], b"data");
+        let data_size = u32::from_le_bytes([data[40], data[41], data[42], data[43]]);
+        assert_eq!(data_size as usize, data.len() - 44);
+        assert_eq!(
+            data_size as u64 / 2,
+            13 * NUM_CLOCKS_PER_TICK as u64 * 22050 / CLOCK_RATE as u64
+        );
+
+        let samples: Vec<i16> = data[44..]
+            .chunks_exact(2)
+            .map(|value| i16::from_le_bytes([value[0], value[1]]))
+            .collect();
+        let expected = render_sounds(vec![vec![sound.clone()]], 22050, false, 12).unwrap();
+        assert_eq!(samples, expected);
+        assert!(samples[samples.len() - 1].abs() < 100);
+        assert!(samples.iter().any(|sample| *sample > 1000));
+        assert!(samples.iter().any(|sample| *sample < -1000));
+
+        assert!(matches!(
+            sound.lock().to_wav(&path, 0),
+            Err(AudioError::InvalidSampleRate(0))
+        ));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn from_mml() {
+        let mut sound = Sound::from_mml("t60 v5 @1 l8 ce").unwrap().lock().clone();
+        assert_eq!(sound.notes, vec![24, 28]);
+        assert_eq!(sound.tones, vec![TONE_SQUARE; 2]);
+        assert_eq!(sound.volumes, vec![5; 2]);
+        assert_eq!(sound.effects, vec![EFFECT_NONE; 2]);
+        assert_eq!(sound.speed, 60);
+
+        assert!(sound.try_set_mml("c x").is_err());
+        assert_eq!(sound.notes, vec![24, 28]);
+    }
 }
@@ -0,0 +1,265 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use platform_dirs::AppDirs;
+
+use crate::settings::{STORAGE_FILE_EXTENSION, STORAGE_SIGNATURE};
+
+#[derive(Debug)]
+pub enum StorageError {
+    NoDataDir,
+    InvalidSlot(String),
+    OpenFile(String),
+    WriteFile(String),
+    Corrupted(String),
+    #[cfg(feature = "serde")]
+    Json(String, String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NoDataDir => write!(f, "Unable to locate the user data directory"),
+            Self::InvalidSlot(slot) => write!(f, "Invalid save slot name '{}'", slot),
+            Self::OpenFile(filename) => write!(f, "Unable to open file '{}'", filename),
+            Self::WriteFile(filename) => write!(f, "Unable to write file '{}'", filename),
+            Self::Corrupted(filename) => write!(f, "Corrupted save file '{}'", filename),
+            #[cfg(feature = "serde")]
+            Self::Json(slot, message) => write!(f, "Invalid save data in '{}': {}", slot, message),
+        }
+    }
+}
+
+pub struct Storage {
+    dir: PathBuf,
+}
+
+fn app_dirname(app_id: &str) -> String {
+    let dirname: String = app_id
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if dirname.is_empty() {
+        "pyxel".to_string()
+    } else {
+        dirname
+    }
+}
+
+fn is_valid_slot(slot: &str) -> bool {
+    !slot.is_empty()
+        && !slot.starts_with('.')
+        && slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+fn encode(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(STORAGE_SIGNATURE.len() + 12 + data.len());
+    output.extend_from_slice(STORAGE_SIGNATURE);
+    output.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    output.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+    output.extend_from_slice(data);
+    output
+}
+
+fn decode(input: &[u8]) -> Option<Vec<u8>> {
+    let input = input.strip_prefix(STORAGE_SIGNATURE)?;
+    if input.len() < 12 {
+        return None;
+    }
+    let (length, input) = input.split_at(8);
+    let (crc, data) = input.split_at(4);
+    let length = u64::from_le_bytes(length.try_into().unwrap());
+    let crc = u32::from_le_bytes(crc.try_into().unwrap());
+    (data.len() as u64 == length && crc32fast::hash(data) == crc).then(|| data.to_vec())
+}
+
+impl Storage {
+    pub fn new(app_id: &str) -> Result<Self, StorageError> {
+        let app_dirs =
+            AppDirs::new(Some(&app_dirname(app_id)), false).ok_or(StorageError::NoDataDir)?;
+        Ok(Self::with_dir(app_dirs.data_dir))
+    }
+
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn slot_path(&self, slot: &str) -> Result<PathBuf, StorageError> {
+        if is_valid_slot(slot) {
+            Ok(self.dir.join(format!("{}{}", slot, STORAGE_FILE_EXTENSION)))
+        } else {
+            Err(StorageError::InvalidSlot(slot.to_string()))
+        }
+    }
+
+    pub fn save(&self, slot: &str, data: &[u8]) -> Result<(), StorageError> {
+        // The new data is fully written and synced to a temporary file before it replaces
+        // the previous save, so a crash at any point leaves one complete save on disk
+        let path = self.slot_path(slot)?;
+        let temp_path = path.with_extension("tmp");
+        let filename = path.to_string_lossy().to_string();
+        fs::create_dir_all(&self.dir).map_err(|_| StorageError::WriteFile(filename.clone()))?;
+        let write_temp = || -> std::io::Result<()> {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(&encode(data))?;
+            file.sync_all()
+        };
+        write_temp().map_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+            StorageError::WriteFile(filename.clone())
+        })?;
+        fs::rename(&temp_path, &path).map_err(|_| StorageError::WriteFile(filename))
+    }
+
+    pub fn load(&self, slot: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let path = self.slot_path(slot)?;
+        let filename = path.to_string_lossy().to_string();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let input = fs::read(&path).map_err(|_| StorageError::OpenFile(filename.clone()))?;
+        decode(&input)
+            .map(Some)
+            .ok_or(StorageError::Corrupted(filename))
+    }
+
+    pub fn delete(&self, slot: &str) -> Result<(), StorageError> {
+        let path = self.slot_path(slot)?;
+        if path.exists() {
+            fs::remove_file(&path)
+                .map_err(|_| StorageError::WriteFile(path.to_string_lossy().to_string()))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save_json<T: serde::Serialize>(
+        &self,
+        slot: &str,
+        value: &T,
+    ) -> Result<(), StorageError> {
+        let data = serde_json::to_vec(value)
+            .map_err(|err| StorageError::Json(slot.to_string(), err.to_string()))?;
+        self.save(slot, &data)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load_json<T: serde::de::DeserializeOwned>(
+        &self,
+        slot: &str,
+    ) -> Result<Option<T>, StorageError> {
+        self.load(slot)?
+            .map(|data| {
+                serde_json::from_slice(&data)
+                    .map_err(|err| StorageError::Json(slot.to_string(), err.to_string()))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_storage(name: &str) -> Storage {
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        Storage::with_dir(dir)
+    }
+
+    #[test]
+    fn save_load() {
+        let storage = test_storage("pyxel_storage_save_load");
+        assert!(storage.load("slot1").unwrap().is_none());
+        storage.save("slot1", b"progress").unwrap();
+        storage.save("slot.2", b"").unwrap();
+        assert_eq!(storage.load("slot1").unwrap().unwrap(), b"progress");
+        assert_eq!(storage.load("slot.2").unwrap().unwrap(), b"");
+        storage.save("slot1", b"more progress").unwrap();
+        assert_eq!(storage.load("slot1").unwrap().unwrap(), b"more progress");
+        storage.delete("slot1").unwrap();
+        assert!(storage.load("slot1").unwrap().is_none());
+        storage.delete("slot1").unwrap();
+
+        for slot in ["", ".hidden", "../escape", "a/b", "a\\b"] {
+            assert!(matches!(
+                storage.save(slot, b"data"),
+                Err(StorageError::InvalidSlot(_))
+            ));
+        }
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+
+    #[test]
+    fn corrupted() {
+        let storage = test_storage("pyxel_storage_corrupted");
+        storage.save("slot", b"settings").unwrap();
+        let path = storage.slot_path("slot").unwrap();
+
+        // An interrupted save leaves only the temporary file behind
+        fs::write(path.with_extension("tmp"), b"partial").unwrap();
+        assert_eq!(storage.load("slot").unwrap().unwrap(), b"settings");
+        storage.save("slot", b"settings2").unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let mut data = fs::read(&path).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            storage.load("slot"),
+            Err(StorageError::Corrupted(_))
+        ));
+        fs::write(&path, &data[..last]).unwrap();
+        assert!(matches!(
+            storage.load("slot"),
+            Err(StorageError::Corrupted(_))
+        ));
+        fs::write(&path, b"plain text").unwrap();
+        assert!(matches!(
+            storage.load("slot"),
+            Err(StorageError::Corrupted(_))
+        ));
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+
+    #[test]
+    fn app_dirname() {
+        assert_eq!(super::app_dirname("Super Mario"), "Super Mario");
+        assert_eq!(super::app_dirname(" ../My:Game* "), "___My_Game_");
+        assert_eq!(super::app_dirname(""), "pyxel");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn save_load_json() {
+        let storage = test_storage("pyxel_storage_json");
+        storage.save_json("scores", &vec![300, 200, 100]).unwrap();
+        assert_eq!(
+            storage.load_json::<Vec<u32>>("scores").unwrap(),
+            Some(vec![300, 200, 100])
+        );
+        assert!(storage.load_json::<Vec<u32>>("missing").unwrap().is_none());
+        storage.save("scores", b"{").unwrap();
+        assert!(matches!(
+            storage.load_json::<Vec<u32>>("scores"),
+            Err(StorageError::Json(_, _))
+        ));
+        fs::remove_dir_all(storage.dir()).unwrap();
+    }
+}
@@ -1,48 +1,168 @@
-use std::cmp::min;
 use std::process::exit;
+use std::time::Instant;
+
+use log::{error, warn};
+#[cfg(feature = "raw-window-handle")]
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use sdl2::render::WindowCanvas;
 
 use crate::event::Event;
-use crate::image::Image;
+use crate::frame_stats::{FramePhase, FrameStats, FrameStatsCollector};
+use crate::image::{Image, SharedImage};
+use crate::input_record::{InputRecordError, InputRecorder, InputReplay};
 use crate::key::{KEY_0, KEY_1, KEY_2, KEY_3, KEY_ALT, KEY_RETURN};
-use crate::platform::Platform;
-use crate::profiler::Profiler;
-use crate::settings::{BACKGROUND_COLOR, MAX_SKIP_FRAMES, NUM_MEASURE_FRAMES};
+use crate::layer::composite_layers;
+use crate::options::SystemOptions;
+use crate::platform::{
+    AuxWindowEvent, AuxWindowId, DisplayEffect, Platform, PlatformError, PostRenderHook, ScreenFit,
+    ScreenMode,
+};
+use crate::platform_handle::{PlatformCommand, PlatformCommandQueue, PlatformHandle};
+use crate::profiler::{ProfileScope, Profiler, ScopeStats};
+use crate::settings::{
+    BACKGROUND_COLOR, DEFAULT_FPS, DEFAULT_FULLSCREEN_KEY, DEFAULT_MAX_FRAME_SKIP,
+    DEFAULT_PAUSE_KEY, DEFAULT_PERF_MONITOR_KEY, DEFAULT_RECORDING_KEY, DEFAULT_SCREENSHOT_KEY,
+    DEFAULT_STEP_KEY, PROFILER_COLORS, PROFILER_GRAPH_HEIGHT,
+};
 use crate::types::Key;
 use crate::utils::simplify_string;
-use crate::{Pyxel, PyxelCallback};
+use crate::{Context, Game, Pyxel, PyxelCallback};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptureAction {
+    Screenshot,
+    Recording,
+    PerfMonitor,
+    Fullscreen,
+}
 
 pub struct System {
+    fps: u32,
     one_frame_ms: f64,
     next_update_ms: f64,
     disable_next_frame_skip: bool,
+    max_frame_skip: u32,
+    is_frame_skipped: bool,
+    is_quit_requested: bool,
+    input_recorder: Option<InputRecorder>,
+    input_replay: Option<InputReplay>,
+    profiler: shared_type!(Profiler),
+    enable_profiler: bool,
+    enable_profiler_overlay: bool,
     frame_count: u32,
     quit_key: Key,
-    is_paused: bool,
-    fps_profiler: Profiler,
-    update_profiler: Profiler,
-    draw_profiler: Profiler,
+    pause_key: Key,
+    step_key: Key,
+    capture_keys: [Option<Key>; 4],
+    is_recording: bool,
+    is_hidden: bool,
+    is_debug_paused: bool,
+    is_step_requested: bool,
+    num_paused_frames: u32,
+    frame_stats: FrameStatsCollector,
     enable_perf_monitor: bool,
+    platform_commands: PlatformCommandQueue,
+    aux_window_events: Vec<(AuxWindowId, AuxWindowEvent)>,
 }
 
 impl System {
     pub fn new(fps: u32, quit_key: Key) -> Self {
-        Self {
-            one_frame_ms: 1000.0 / fps as f64,
+        let mut system = Self {
+            fps,
+            one_frame_ms: 0.0,
             next_update_ms: -1.0,
             disable_next_frame_skip: true,
+            max_frame_skip: DEFAULT_MAX_FRAME_SKIP,
+            is_frame_skipped: false,
+            is_quit_requested: false,
+            input_recorder: None,
+            input_replay: None,
+            profiler: new_shared_type!(Profiler::new()),
+            enable_profiler: false,
+            enable_profiler_overlay: false,
             frame_count: 0,
             quit_key,
-            is_paused: false,
-            fps_profiler: Profiler::new(NUM_MEASURE_FRAMES),
-            update_profiler: Profiler::new(NUM_MEASURE_FRAMES),
-            draw_profiler: Profiler::new(NUM_MEASURE_FRAMES),
+            pause_key: DEFAULT_PAUSE_KEY,
+            step_key: DEFAULT_STEP_KEY,
+            capture_keys: [
+                Some(DEFAULT_SCREENSHOT_KEY),
+                Some(DEFAULT_RECORDING_KEY),
+                Some(DEFAULT_PERF_MONITOR_KEY),
+                Some(DEFAULT_FULLSCREEN_KEY),
+            ],
+            is_recording: false,
+            is_hidden: false,
+            is_debug_paused: false,
+            is_step_requested: false,
+            num_paused_frames: 0,
+            frame_stats: FrameStatsCollector::new(),
             enable_perf_monitor: false,
-        }
+            platform_commands: PlatformCommandQueue::new(),
+            aux_window_events: Vec::new(),
+        };
+        system.set_fps(fps);
+        system
+    }
+
+    fn set_fps(&mut self, fps: u32) {
+        self.fps = fps;
+        self.one_frame_ms = if fps == 0 { 0.0 } else { 1000.0 / fps as f64 };
+        self.frame_stats.set_target_fps(fps);
+        self.disable_next_frame_skip = true;
     }
 
     pub fn disable_next_frame_skip(&mut self) {
         self.disable_next_frame_skip = true;
     }
+
+    fn start_frame(&mut self) {
+        self.frame_stats.start_frame(Instant::now());
+        if self.enable_profiler {
+            self.profiler.lock().end_frame();
+        }
+    }
+
+    fn schedule_updates(&mut self, tick_count: f64) -> u32 {
+        if self.disable_next_frame_skip {
+            self.disable_next_frame_skip = false;
+            self.next_update_ms = tick_count + self.one_frame_ms;
+            return 1;
+        }
+        if self.fps == 0 {
+            // Uncapped games update once per frame and are only paced by vsync
+            self.next_update_ms = tick_count;
+            return 1;
+        }
+        let num_late_frames = ((tick_count - self.next_update_ms) / self.one_frame_ms) as u32;
+        if num_late_frames > self.max_frame_skip {
+            // Too far behind to catch up, so the clock moves on instead of piling up updates
+            self.next_update_ms = tick_count + self.one_frame_ms;
+            return self.max_frame_skip + 1;
+        }
+        self.next_update_ms += self.one_frame_ms * (num_late_frames + 1) as f64;
+        num_late_frames + 1
+    }
+
+    pub fn run<G: Game>(mut ctx: Context, game: G) {
+        // Closing the window requests a quit, which ends the loop once the game accepts it
+        ctx.run(&mut GameCallback(game));
+    }
+}
+
+struct GameCallback<G: Game>(G);
+
+impl<G: Game> PyxelCallback for GameCallback<G> {
+    fn update(&mut self, pyxel: &mut Pyxel) {
+        self.0.update(pyxel);
+    }
+
+    fn draw(&mut self, pyxel: &mut Pyxel) {
+        self.0.draw(pyxel);
+    }
+
+    fn on_quit(&mut self, pyxel: &mut Pyxel) -> bool {
+        self.0.on_quit(pyxel)
+    }
 }
 
 impl Pyxel {
@@ -62,13 +182,29 @@ impl Pyxel {
         self.platform.set_title(title);
     }
 
-    pub fn icon(&mut self, data_str: &[&str], scale: u32) {
+    pub(crate) fn icon_image(data_str: &[&str]) -> SharedImage {
         let width = simplify_string(data_str[0]).len() as u32;
         let height = data_str.len() as u32;
         let image = Image::new(width, height);
         image.lock().set(0, 0, data_str);
-        self.platform
-            .set_icon(&image.lock().canvas.data, &self.colors, scale);
+        image
+    }
+
+    pub fn icon(&mut self, data_str: &[&str], scale: u32) {
+        let image = Self::icon_image(data_str);
+        self.platform.set_icon(
+            &image.lock().canvas.data,
+            self.palette.display_colors(),
+            scale,
+        );
+    }
+
+    pub(crate) fn apply_options(&mut self, options: &SystemOptions) {
+        if let Some(filename) = &options.record_input {
+            if let Err(err) = self.try_record_input(filename) {
+                error!("{}", err);
+            }
+        }
     }
 
     pub fn is_fullscreen(&self) -> bool {
@@ -79,38 +215,254 @@ impl Pyxel {
         self.platform.set_fullscreen(is_fullscreen);
     }
 
+    pub fn platform_handle(&self) -> PlatformHandle {
+        self.system.platform_commands.handle()
+    }
+
+    fn apply_platform_commands(&mut self) {
+        for command in self.system.platform_commands.drain() {
+            match command {
+                PlatformCommand::SetWindowTitle(title) => self.title(&title),
+                PlatformCommand::SetFullscreen(is_fullscreen) => self.fullscreen(is_fullscreen),
+                PlatformCommand::RequestQuit => self.quit(),
+                PlatformCommand::PauseAudio => self.pause_audio(),
+                PlatformCommand::ResumeAudio => self.resume_audio(),
+            }
+        }
+    }
+
+    pub fn set_post_render_hook(&mut self, hook: Option<PostRenderHook<WindowCanvas>>) {
+        self.platform.set_post_render_hook(hook);
+    }
+
+    pub fn create_aux_window(
+        &mut self,
+        title: &str,
+        width: u32,
+        height: u32,
+        scale: u32,
+    ) -> Result<AuxWindowId, PlatformError> {
+        self.platform.create_aux_window(title, width, height, scale)
+    }
+
+    pub fn close_aux_window(&mut self, window: AuxWindowId) {
+        self.platform.close_aux_window(window);
+    }
+
+    pub fn is_aux_window_open(&self, window: AuxWindowId) -> bool {
+        self.platform.is_aux_window_open(window)
+    }
+
+    pub fn render_aux_window(&mut self, window: AuxWindowId, image: &SharedImage) {
+        self.platform.render_aux_window(
+            window,
+            &image.lock().canvas.data,
+            self.palette.display_colors(),
+            BACKGROUND_COLOR,
+        );
+    }
+
+    pub fn flush_events(&mut self) {
+        self.platform.flush_events();
+    }
+
+    pub fn set_coalesce_mouse_motion(&mut self, is_enabled: bool) {
+        self.platform.set_coalesce_mouse_motion(is_enabled);
+    }
+
+    pub fn aux_window_events(&self) -> &[(AuxWindowId, AuxWindowEvent)] {
+        &self.system.aux_window_events
+    }
+
+    pub fn display_effect(&mut self, effect: DisplayEffect) {
+        self.platform.set_display_effect(effect);
+    }
+
+    pub fn screen_fit(&mut self, fit: ScreenFit, pixel_aspect: f64) {
+        self.platform.set_screen_fit(fit, pixel_aspect);
+    }
+
+    pub fn screen_mode(&mut self, mode: ScreenMode) {
+        self.platform.set_screen_mode(mode);
+    }
+
+    pub fn set_dirty_tracking(&mut self, is_enabled: bool) {
+        self.screen.lock().set_dirty_tracking(is_enabled);
+    }
+
+    pub fn set_overlay_enabled(&mut self, is_enabled: bool) {
+        self.platform.set_overlay_enabled(is_enabled);
+    }
+
+    pub fn overlay_mut(&mut self) -> Option<&mut [u8]> {
+        self.platform.overlay_mut()
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        self.system.frame_stats.stats()
+    }
+
+    pub fn show_perf_overlay(&mut self, is_shown: bool) {
+        self.system.enable_perf_monitor = is_shown;
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.system.fps
+    }
+
+    pub fn set_target_fps(&mut self, fps: u32) {
+        self.system.set_fps(fps);
+        self.resource.set_capture_fps(fps);
+        // The clock restarts at the new rate instead of catching up with the old one
+        if self.system.next_update_ms >= 0.0 {
+            self.system.next_update_ms = self.platform.tick_count() as f64;
+        }
+    }
+
+    pub fn max_frame_skip(&self) -> u32 {
+        self.system.max_frame_skip
+    }
+
+    pub fn set_max_frame_skip(&mut self, max_frame_skip: u32) {
+        self.system.max_frame_skip = max_frame_skip;
+    }
+
+    pub fn is_frame_skipped(&self) -> bool {
+        self.system.is_frame_skipped
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.system.is_debug_paused {
+            return;
+        }
+        self.system.is_debug_paused = paused;
+        self.system.is_step_requested = false;
+        if paused {
+            self.platform.pause_audio();
+        } else if !self.system.is_hidden && !self.audio.is_paused {
+            self.platform.resume_audio();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.system.is_debug_paused
+    }
+
+    pub fn request_step(&mut self) {
+        if self.system.is_debug_paused {
+            self.system.is_step_requested = true;
+        }
+    }
+
+    pub fn set_pause_keys(&mut self, pause_key: Key, step_key: Key) {
+        self.system.pause_key = pause_key;
+        self.system.step_key = step_key;
+    }
+
+    pub fn set_capture_key(&mut self, action: CaptureAction, key: Option<Key>) {
+        self.system.capture_keys[action as usize] = key;
+    }
+
+    pub fn try_record_input(&mut self, filename: &str) -> Result<(), InputRecordError> {
+        // Recording reseeds the random generators so a replay can start from the same state
+        self.stop_input_record();
+        let seed = self.platform.tick_count();
+        let recorder =
+            InputRecorder::create(filename, self.system.fps, seed, self.system.frame_count)?;
+        self.rseed(seed);
+        self.nseed(seed);
+        self.system.input_recorder = Some(recorder);
+        Ok(())
+    }
+
+    pub fn record_input(&mut self, filename: &str) {
+        self.try_record_input(filename)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_replay_input(&mut self, filename: &str) -> Result<(), InputRecordError> {
+        let (replay, seed) = InputReplay::open(filename, self.system.fps, self.system.frame_count)?;
+        self.stop_input_record();
+        self.rseed(seed);
+        self.nseed(seed);
+        self.system.input_replay = Some(replay);
+        Ok(())
+    }
+
+    pub fn replay_input(&mut self, filename: &str) {
+        self.try_replay_input(filename)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn stop_input_record(&mut self) {
+        if let Some(recorder) = self.system.input_recorder.take() {
+            if let Err(err) = recorder.finish() {
+                error!("{}", err);
+            }
+        }
+        self.system.input_replay = None;
+    }
+
+    pub fn is_replaying_input(&self) -> bool {
+        self.system.input_replay.is_some()
+    }
+
+    pub fn profile_scope(&self, name: &str) -> ProfileScope {
+        ProfileScope::new(
+            self.system.enable_profiler.then_some(&self.system.profiler),
+            name,
+        )
+    }
+
+    pub fn profile<T>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> T) -> T {
+        let scope = self.profile_scope(name);
+        let value = f(self);
+        drop(scope);
+        value
+    }
+
+    pub fn enable_profiler(&mut self, enabled: bool) {
+        if enabled && !self.system.enable_profiler {
+            self.system.profiler.lock().clear();
+        }
+        self.system.enable_profiler = enabled;
+    }
+
+    pub fn show_profiler_overlay(&mut self, shown: bool) {
+        if shown {
+            self.enable_profiler(true);
+        }
+        self.system.enable_profiler_overlay = shown;
+    }
+
+    pub fn profiler_report(&self) -> Vec<ScopeStats> {
+        self.system.profiler.lock().report()
+    }
+
     pub fn run<T: PyxelCallback>(&mut self, callback: &mut T) {
         self.system.next_update_ms = self.platform.tick_count() as f64 + self.system.one_frame_ms;
+        self.system.start_frame();
         self.update_frame(Some(callback));
         self.draw_frame(Some(callback));
         self.system.frame_count += 1;
 
-        loop {
-            let sleep_time = self.wait_for_update_time();
-            let tick_count = self.platform.tick_count();
-            self.system.fps_profiler.end(tick_count);
-            self.system.fps_profiler.start(tick_count);
-            let update_count: u32;
-            if self.system.disable_next_frame_skip {
-                update_count = 1;
-                self.system.disable_next_frame_skip = false;
-                self.system.next_update_ms =
-                    self.platform.tick_count() as f64 + self.system.one_frame_ms;
-            } else {
-                update_count = min(
-                    (-sleep_time as f64 / self.system.one_frame_ms) as u32,
-                    MAX_SKIP_FRAMES,
-                ) + 1;
-                self.system.next_update_ms += self.system.one_frame_ms * update_count as f64;
-            }
+        while !self.confirm_quit(Some(callback)) {
+            self.wait_for_update_time();
+            self.system.start_frame();
+            let update_count = self
+                .system
+                .schedule_updates(self.platform.tick_count() as f64);
+            self.system.is_frame_skipped = true;
             for _ in 1..update_count {
                 self.update_frame(Some(callback));
                 self.system.frame_count += 1;
             }
+            self.system.is_frame_skipped = false;
             self.update_frame(Some(callback));
             self.draw_frame(Some(callback));
             self.system.frame_count += 1;
         }
+        self.shutdown();
     }
 
     pub fn show(&mut self) {
@@ -118,7 +470,11 @@ impl Pyxel {
             self.update_frame(None);
             self.draw_frame(None);
             self.system.frame_count += 1;
+            if self.confirm_quit(None) {
+                break;
+            }
         }
+        self.shutdown();
     }
 
     pub fn flip(&mut self) {
@@ -129,59 +485,189 @@ impl Pyxel {
             self.wait_for_update_time();
         }
         self.system.next_update_ms += self.system.one_frame_ms;
-        let tick_count = self.platform.tick_count();
-        self.system.fps_profiler.end(tick_count);
-        self.system.fps_profiler.start(tick_count);
+        self.system.start_frame();
         self.update_frame(None);
         self.draw_frame(None);
+        if self.confirm_quit(None) {
+            self.shutdown();
+            exit(0);
+        }
     }
 
     pub fn quit(&mut self) {
-        exit(0);
+        self.system.is_quit_requested = true;
+    }
+
+    fn confirm_quit(&mut self, callback: Option<&mut dyn PyxelCallback>) -> bool {
+        if !self.system.is_quit_requested {
+            return false;
+        }
+        self.system.is_quit_requested = false;
+        callback.is_none_or(|callback| callback.on_quit(self))
+    }
+
+    fn shutdown(&mut self) {
+        // The audio stops first so that a capture in progress is saved with its final samples
+        self.platform.pause_audio();
+        if self.audio.capture_path().is_some() {
+            if let Err(err) = self.stop_capture() {
+                error!("Unable to save the capture: {}", err);
+            }
+        }
+        self.stop_input_record();
+        self.system.platform_commands.close();
     }
 
     fn update_frame(&mut self, callback: Option<&mut dyn PyxelCallback>) {
-        self.system
-            .update_profiler
-            .start(self.platform.tick_count());
+        let start = Instant::now();
+        self.apply_platform_commands();
         self.process_events();
-        if self.system.is_paused {
+        let end = Instant::now();
+        self.system
+            .frame_stats
+            .add_phase_time(FramePhase::Events, start, end);
+        self.audio.flush_commands();
+        self.audio.update_capture();
+        self.reload_changed_assets();
+        if self.system.is_hidden {
             return;
         }
+        let start = Instant::now();
+        let update_start_us = self.platform.elapsed_us();
+        for latency_us in self.input.take_press_latencies(update_start_us) {
+            self.system.frame_stats.add_input_latency(latency_us);
+        }
         self.check_special_input();
+        if self.system.is_debug_paused {
+            if !self.system.is_step_requested {
+                self.system.num_paused_frames += 1;
+                return;
+            }
+            self.system.is_step_requested = false;
+        }
+        if self.system.num_paused_frames > 0 {
+            self.input
+                .skip_frames(self.system.num_paused_frames, self.system.frame_count);
+            self.system.num_paused_frames = 0;
+        }
         if let Some(callback) = callback {
             callback.update(self);
         }
-        self.system.update_profiler.end(self.platform.tick_count());
+        self.graphics.transition.update(&mut self.palette);
+        self.system
+            .frame_stats
+            .add_phase_time(FramePhase::Update, start, Instant::now());
     }
 
     fn process_events(&mut self) {
         self.input.reset_input_states();
-        while let Some(event) = self.platform.poll_event() {
-            match event {
+        self.system.aux_window_events.clear();
+        let mut events = Vec::new();
+        self.platform.poll_events(&mut events);
+        for timed_event in events {
+            let event = &timed_event.event;
+            match *event {
                 Event::Quit => {
+                    self.record_event(event);
                     self.quit();
                 }
                 Event::Shown => {
-                    self.system.is_paused = false;
+                    self.system.is_hidden = false;
                     self.system.disable_next_frame_skip = true;
-                    self.platform.resume_audio();
+                    if !self.audio.is_paused && !self.system.is_debug_paused {
+                        self.platform.resume_audio();
+                    }
                 }
                 Event::Hidden => {
-                    self.system.is_paused = true;
+                    self.system.is_hidden = true;
                     self.platform.pause_audio();
                 }
+                Event::AuxWindow { window, event } => {
+                    // Closing a secondary window never quits the game
+                    if event == AuxWindowEvent::Closed {
+                        self.platform.close_aux_window(window);
+                    }
+                    self.system.aux_window_events.push((window, event));
+                }
+                Event::AudioDeviceRemoved => {
+                    // Fall back to the default device so the game keeps its sound
+                    self.platform.reopen_audio(None).ok();
+                }
                 _ => {
-                    if !self.system.is_paused {
+                    if self.system.input_replay.is_some() {
+                        // Real input is ignored during a replay, except the quit key to abort it
+                        if matches!(*event, Event::KeyDown { keycode } if keycode == self.system.quit_key)
+                        {
+                            self.system.input_replay = None;
+                        }
+                    } else if !self.system.is_hidden {
+                        self.record_event(event);
                         self.input
-                            .process_input_event(event, self.system.frame_count);
+                            .process_timed_event(timed_event, self.system.frame_count);
                     }
                 }
             }
         }
+        if let Some(mut replay) = self.system.input_replay.take() {
+            while let Some(event) = replay.next_event(self.system.frame_count) {
+                if matches!(event, Event::Quit) {
+                    self.quit();
+                } else {
+                    self.input
+                        .process_input_event(event, self.system.frame_count);
+                }
+            }
+            if !replay.is_finished() {
+                self.system.input_replay = Some(replay);
+            }
+        }
+    }
+
+    fn record_event(&mut self, event: &Event) {
+        if let Some(recorder) = &mut self.system.input_recorder {
+            if let Err(err) = recorder.record(self.system.frame_count, event) {
+                error!("{}", err);
+                self.system.input_recorder = None;
+            }
+        }
+    }
+
+    // Hotkey captures must never take the game down, so failures are only logged
+    fn save_screenshot(&mut self) {
+        if let Err(err) = self.try_screenshot(None) {
+            warn!("Unable to save the screenshot: {}", err);
+        }
+    }
+
+    fn save_screencast(&mut self) {
+        if let Err(err) = self.try_screencast(None) {
+            warn!("Unable to save the screencast: {}", err);
+        }
     }
 
     fn check_special_input(&mut self) {
+        let capture_keys = self.system.capture_keys;
+        let is_pressed = |pyxel: &Self, action: CaptureAction| {
+            capture_keys[action as usize].is_some_and(|key| pyxel.btnp(key, None, None))
+        };
+        if is_pressed(self, CaptureAction::Screenshot) {
+            self.save_screenshot();
+        }
+        if is_pressed(self, CaptureAction::Recording) {
+            // The screencast keeps the last few seconds, so stopping saves up to that length
+            if self.system.is_recording {
+                self.save_screencast();
+            } else {
+                self.reset_capture();
+            }
+            self.system.is_recording = !self.system.is_recording;
+        }
+        if is_pressed(self, CaptureAction::PerfMonitor) {
+            self.system.enable_perf_monitor = !self.system.enable_perf_monitor;
+        }
+        if is_pressed(self, CaptureAction::Fullscreen) {
+            self.fullscreen(!self.is_fullscreen());
+        }
         if self.btn(KEY_ALT) {
             if self.btnp(KEY_RETURN, None, None) {
                 self.fullscreen(!self.is_fullscreen());
@@ -190,121 +676,276 @@ impl Pyxel {
                 self.system.enable_perf_monitor = !self.system.enable_perf_monitor;
             }
             if self.btnp(KEY_1, None, None) {
-                self.screenshot(None);
+                self.save_screenshot();
             }
             if self.btnp(KEY_2, None, None) {
                 self.reset_capture();
             }
             if self.btnp(KEY_3, None, None) {
-                self.screencast(None);
+                self.save_screencast();
             }
         }
+        if self.btnp(self.system.pause_key, None, None) {
+            self.set_paused(!self.system.is_debug_paused);
+        }
+        if self.btnp(self.system.step_key, None, None) {
+            self.request_step();
+        }
         if self.btnp(self.system.quit_key, None, None) {
             self.quit();
         }
     }
 
-    fn wait_for_update_time(&mut self) -> i32 {
+    fn wait_for_update_time(&mut self) {
         loop {
             let sleep_time = self.system.next_update_ms - self.platform.tick_count() as f64;
             if sleep_time <= 0.0 {
-                return sleep_time as i32;
+                return;
             }
             self.platform.sleep((sleep_time / 2.0) as u32);
         }
     }
 
     fn draw_frame(&mut self, callback: Option<&mut dyn PyxelCallback>) {
-        if self.system.is_paused {
+        if self.system.is_hidden {
             return;
         }
-        self.system.draw_profiler.start(self.platform.tick_count());
+        let start = Instant::now();
         if let Some(callback) = callback {
             callback.draw(self);
         }
         self.draw_perf_monitor();
+        self.draw_profiler_overlay();
         self.draw_cursor();
+        let end = Instant::now();
+        self.system
+            .frame_stats
+            .add_phase_time(FramePhase::Draw, start, end);
+        let mut screen = self.screen.lock();
+        let mut dirty_rects = screen.canvas.take_dirty_rects();
+        let scanlines = self.graphics.update_scanlines(screen.height()).is_some();
+        let screen_data = if self.graphics.layers.is_empty() {
+            &screen.canvas.data
+        } else {
+            composite_layers(
+                &mut self.graphics.composited_screen,
+                &self.graphics.layers,
+                &screen.canvas,
+                self.graphics.screen_colkey,
+            );
+            dirty_rects = None;
+            &self.graphics.composited_screen
+        };
         self.platform.render_screen(
-            &self.screen.lock().canvas.data,
-            &self.colors,
+            screen_data,
+            self.palette.display_colors(),
             BACKGROUND_COLOR,
+            dirty_rects.as_deref(),
+            scanlines.then_some(&self.graphics.scanlines),
         );
+        self.system
+            .frame_stats
+            .add_phase_time(FramePhase::Render, end, Instant::now());
         self.resource.capture_screen(
-            &self.screen.lock().canvas.data,
-            &self.colors,
+            screen_data,
+            self.palette.display_colors(),
             self.system.frame_count,
         );
-        self.system.draw_profiler.end(self.platform.tick_count());
+        drop(screen);
     }
 
     fn draw_perf_monitor(&mut self) {
         if !self.system.enable_perf_monitor {
             return;
         }
-        let mut screen = self.screen.lock();
-        let clip_rect = screen.canvas.clip_rect;
-        let camera_x = screen.canvas.camera_x;
-        let camera_y = screen.canvas.camera_y;
-        let palette1 = screen.palette[1];
-        let palette2 = screen.palette[2];
-        screen.clip0();
-        screen.camera0();
-        screen.pal(1, 1);
-        screen.pal(2, 9);
-
-        let fps = format!("{:.*}", 2, self.system.fps_profiler.average_fps());
-        screen.text(1.0, 0.0, &fps, 1, self.font.clone());
-        screen.text(0.0, 0.0, &fps, 2, self.font.clone());
-
-        let update_time = format!("{:.*}", 2, self.system.update_profiler.average_time());
-        screen.text(1.0, 6.0, &update_time, 1, self.font.clone());
-        screen.text(0.0, 6.0, &update_time, 2, self.font.clone());
-
-        let draw_time = format!("{:.*}", 2, self.system.draw_profiler.average_time());
-        screen.text(1.0, 12.0, &draw_time, 1, self.font.clone());
-        screen.text(0.0, 12.0, &draw_time, 2, self.font.clone());
-
-        screen.canvas.clip_rect = clip_rect;
-        screen.canvas.camera_x = camera_x;
-        screen.canvas.camera_y = camera_y;
-        screen.pal(1, palette1);
-        screen.pal(2, palette2);
+        let stats = self.system.frame_stats.stats();
+        let lines = [
+            if stats.target_fps == 0 {
+                format!("FPS {:.2}", stats.fps)
+            } else {
+                format!("FPS {:.2}/{}", stats.fps, stats.target_fps)
+            },
+            format!("FRM {:.2}/{:.2}", stats.mean_frame_ms, stats.max_frame_ms),
+            format!("EVT {:.2}", stats.events_ms),
+            format!("UPD {:.2}", stats.update_ms),
+            format!("DRW {:.2}", stats.draw_ms),
+            format!("RND {:.2}", stats.render_ms),
+            format!(
+                "LAT {:.2}/{:.2}",
+                stats.mean_input_latency_ms, stats.max_input_latency_ms
+            ),
+        ];
+        with_overlay_state(&mut self.screen.lock(), |screen| {
+            screen.pal(2, 9);
+            for (i, line) in lines.iter().enumerate() {
+                let y = i as f64 * 6.0;
+                screen.text(1.0, y, line, 1, self.font.clone());
+                screen.text(0.0, y, line, 2, self.font.clone());
+            }
+        });
+    }
+
+    fn draw_profiler_overlay(&self) {
+        if !self.system.enable_profiler_overlay {
+            return;
+        }
+        let frame_bars = self.system.profiler.lock().frame_bars();
+        if frame_bars.is_empty() {
+            return;
+        }
+        // The graph is two frame budgets tall, with a line marking one budget
+        let height = PROFILER_GRAPH_HEIGHT as f64;
+        let one_frame_ms = if self.system.fps == 0 {
+            // Uncapped games are measured against their mean frame time
+            let mean_frame_ms = self.system.frame_stats.stats().mean_frame_ms;
+            if mean_frame_ms > 0.0 {
+                mean_frame_ms
+            } else {
+                1000.0 / DEFAULT_FPS as f64
+            }
+        } else {
+            self.system.one_frame_ms
+        };
+        let pixels_per_ms = height / (one_frame_ms * 2.0);
+        with_overlay_state(&mut self.screen.lock(), |screen| {
+            let num_bars = frame_bars.len().min(screen.width() as usize);
+            let bottom = screen.height() as f64;
+            screen.rect(0.0, bottom - height, num_bars as f64, height, 0);
+            for (x, bars) in frame_bars[frame_bars.len() - num_bars..].iter().enumerate() {
+                let mut y = bottom;
+                for &(index, ms) in bars {
+                    let bar_height = ms * pixels_per_ms;
+                    let color = PROFILER_COLORS[index % PROFILER_COLORS.len()];
+                    screen.rect(x as f64, y - bar_height, 1.0, bar_height.ceil(), color);
+                    y -= bar_height;
+                }
+            }
+            let budget_y = bottom - (height / 2.0).floor();
+            screen.line(0.0, budget_y, num_bars as f64 - 1.0, budget_y, 7);
+        });
     }
 
     fn draw_cursor(&mut self) {
         let x = self.mouse_x();
         let y = self.mouse_y();
-        self.platform
-            .show_cursor(x < 0 || x >= self.width() as i32 || y < 0 || y >= self.height() as i32);
-        if !self.input.is_mouse_visible() {
+        let is_mouse_on_screen = self.platform.is_mouse_on_screen();
+        self.platform.show_cursor(!is_mouse_on_screen);
+        if !self.input.is_mouse_visible() || !is_mouse_on_screen {
             return;
         }
         let width = self.cursor.lock().width() as i32;
         let height = self.cursor.lock().height() as i32;
-        if x <= -width || x >= self.width() as i32 || y <= -height || y >= self.height() as i32 {
-            return;
+        with_overlay_state(&mut self.screen.lock(), |screen| {
+            screen.blt(
+                x as f64,
+                y as f64,
+                self.cursor.clone(),
+                0.0,
+                0.0,
+                width as f64,
+                height as f64,
+                Some(0),
+                None,
+                None,
+            );
+        });
+    }
+}
+
+// Engine overlays draw in screen space with the default palette and no fill
+// pattern, then hand the screen back to the game exactly as it was
+fn with_overlay_state(screen: &mut Image, draw: impl FnOnce(&mut Image)) {
+    let clip_rect = screen.canvas.clip_rect;
+    let camera_x = screen.canvas.camera_x;
+    let camera_y = screen.canvas.camera_y;
+    let fill_pattern = screen.canvas.fill_pattern;
+    let fill_pattern_values = screen.canvas.fill_pattern_values;
+    let palette = screen.palette;
+    screen.clip0();
+    screen.camera0();
+    screen.fillp0();
+    screen.pal0();
+    draw(screen);
+    screen.canvas.clip_rect = clip_rect;
+    screen.canvas.camera_x = camera_x;
+    screen.canvas.camera_y = camera_y;
+    screen.canvas.fill_pattern = fill_pattern;
+    screen.canvas.fill_pattern_values = fill_pattern_values;
+    screen.set_palette(palette);
+}
+
+#[cfg(feature = "raw-window-handle")]
+unsafe impl HasRawWindowHandle for Pyxel {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        self.platform.raw_window_handle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Recti;
+    use crate::key::KEY_ESCAPE;
+
+    #[test]
+    fn overlay_state() {
+        let screen = Image::new(8, 8);
+        let mut screen = screen.lock();
+        screen.clip(1.0, 1.0, 2.0, 2.0);
+        screen.camera(3.0, 3.0);
+        screen.fillp(0x5a5a);
+        screen.pal(7, 8);
+        with_overlay_state(&mut screen, |screen| {
+            screen.pset(0.0, 0.0, 7);
+            screen.rect(4.0, 4.0, 4.0, 4.0, 7);
+        });
+        assert_eq!(screen.pget(0.0, 0.0), 7);
+        assert_eq!(screen.pget(5.0, 5.0), 7);
+        assert_eq!(screen.canvas.clip_rect, Recti::new(1, 1, 2, 2));
+        assert_eq!((screen.canvas.camera_x, screen.canvas.camera_y), (3, 3));
+        assert_eq!(screen.canvas.fill_pattern, 0x5a5a);
+        assert_eq!(screen.palette[7], 8);
+    }
+
+    fn simulate(draw_ms: f64, max_frame_skip: u32) -> Vec<u32> {
+        let mut system = System::new(50, KEY_ESCAPE);
+        system.max_frame_skip = max_frame_skip;
+        let mut tick_count = 0.0;
+        let mut update_counts = Vec::new();
+        for _ in 0..6 {
+            tick_count = f64::max(tick_count, system.next_update_ms);
+            update_counts.push(system.schedule_updates(tick_count));
+            assert!(system.next_update_ms > tick_count - system.one_frame_ms);
+            tick_count += draw_ms;
         }
-        let mut screen = self.screen.lock();
-        let clip_rect = screen.canvas.clip_rect;
-        let camera_x = screen.canvas.camera_x;
-        let camera_y = screen.canvas.camera_y;
-        let palette = screen.palette;
-        screen.clip0();
-        screen.camera0();
-        screen.pal0();
-        screen.blt(
-            x as f64,
-            y as f64,
-            self.cursor.clone(),
-            0.0,
-            0.0,
-            width as f64,
-            height as f64,
-            Some(0),
-        );
-        screen.canvas.clip_rect = clip_rect;
-        screen.canvas.camera_x = camera_x;
-        screen.canvas.camera_y = camera_y;
-        screen.palette = palette;
+        update_counts
+    }
+
+    #[test]
+    fn schedule_updates() {
+        assert_eq!(simulate(10.0, 5), [1, 1, 1, 1, 1, 1]);
+        assert_eq!(simulate(20.0, 5), [1, 1, 1, 1, 1, 1]);
+        assert_eq!(simulate(60.0, 5), [1, 3, 3, 3, 3, 3]);
+        assert_eq!(simulate(50.0, 5), [1, 2, 3, 2, 3, 2]);
+        assert_eq!(simulate(200.0, 5), [1, 6, 6, 6, 6, 6]);
+        assert_eq!(simulate(60.0, 0), [1, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn set_fps() {
+        let mut system = System::new(60, KEY_ESCAPE);
+        assert_eq!(system.schedule_updates(0.0), 1);
+        system.set_fps(30);
+        assert_eq!(system.schedule_updates(500.0), 1);
+        assert!((system.next_update_ms - (500.0 + 1000.0 / 30.0)).abs() < 1e-9);
+        assert_eq!(system.schedule_updates(540.0), 1);
+        assert_eq!(system.schedule_updates(640.0), 3);
+
+        system.set_fps(0);
+        for tick_count in [641.0, 642.0, 5000.0] {
+            assert_eq!(system.schedule_updates(tick_count), 1);
+            assert_eq!(system.next_update_ms, tick_count);
+        }
+        assert_eq!(system.frame_stats.stats().target_fps, 0);
     }
 }
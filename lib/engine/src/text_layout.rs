@@ -0,0 +1,162 @@
+use crate::font::Font;
+use crate::settings::{FONT_HEIGHT, FONT_WIDTH};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub(crate) fn offset(self, box_width: u32, line_width: u32) -> u32 {
+        let space = box_width.saturating_sub(line_width);
+        match self {
+            Self::Left => 0,
+            Self::Center => space / 2,
+            Self::Right => space,
+        }
+    }
+}
+
+pub struct TextLine {
+    pub text: String,
+    pub width: u32,
+}
+
+pub fn line_height(font: Option<&Font>) -> u32 {
+    font.map_or(FONT_HEIGHT, |font| font.glyph_height)
+}
+
+pub fn char_width(font: Option<&Font>, c: char) -> u32 {
+    // The built-in font draws unknown characters as a fallback glyph, while fonts skip them
+    font.map_or(FONT_WIDTH, |font| {
+        font.glyph_index(c).map_or(0, |index| font.widths[index])
+    })
+}
+
+pub fn break_lines(width: u32, string: &str, font: Option<&Font>) -> Vec<TextLine> {
+    let text_width = |s: &str| s.chars().map(|c| char_width(font, c)).sum::<u32>();
+    let mut lines = Vec::new();
+    let mut line = TextLine {
+        text: String::new(),
+        width: 0,
+    };
+
+    // A trailing newline ends the last line instead of starting an empty one
+    let string = string.strip_suffix('\n').unwrap_or(string);
+    if string.is_empty() {
+        return lines;
+    }
+    for paragraph in string.split('\n') {
+        let mut has_word = false;
+        let mut separator = String::new();
+        for (i, word) in paragraph.split(' ').enumerate() {
+            if i > 0 {
+                separator.push(' ');
+            }
+            if word.is_empty() {
+                continue;
+            }
+
+            // Spaces between words are kept as typed and dropped where the line wraps
+            let separator_width = text_width(&separator);
+            let word_width = text_width(word);
+            if line.width + separator_width + word_width <= width {
+                line.text.push_str(&separator);
+                line.text.push_str(word);
+                line.width += separator_width + word_width;
+            } else {
+                if has_word {
+                    lines.push(std::mem::replace(
+                        &mut line,
+                        TextLine {
+                            text: String::new(),
+                            width: 0,
+                        },
+                    ));
+                }
+                if word_width <= width {
+                    line.text.push_str(word);
+                    line.width = word_width;
+                } else {
+                    for c in word.chars() {
+                        let c_width = char_width(font, c);
+                        if line.width + c_width > width && !line.text.is_empty() {
+                            lines.push(std::mem::replace(
+                                &mut line,
+                                TextLine {
+                                    text: String::new(),
+                                    width: 0,
+                                },
+                            ));
+                        }
+                        line.text.push(c);
+                        line.width += c_width;
+                    }
+                }
+            }
+            has_word = true;
+            separator.clear();
+        }
+        lines.push(std::mem::replace(
+            &mut line,
+            TextLine {
+                text: String::new(),
+                width: 0,
+            },
+        ));
+    }
+    lines
+}
+
+pub fn measure_text_box(width: u32, string: &str, font: Option<&Font>) -> (u32, u32) {
+    let lines = break_lines(width, string, font);
+    let text_width = lines.iter().map(|line| line.width).max().unwrap_or(0);
+    (text_width, lines.len() as u32 * line_height(font))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    fn lines(width: u32, string: &str) -> Vec<String> {
+        super::break_lines(width, string, None)
+            .into_iter()
+            .map(|line| line.text)
+            .collect()
+    }
+
+    #[test]
+    fn break_lines() {
+        assert!(lines(40, "").is_empty());
+        assert_eq!(lines(44, "Hello world"), ["Hello world"]);
+        assert_eq!(lines(32, "Hello world"), ["Hello", "world"]);
+        assert_eq!(lines(40, "a  b   c"), ["a  b   c"]);
+        assert_eq!(lines(16, "ab  cd"), ["ab", "cd"]);
+        assert_eq!(lines(40, "  indented"), ["  indented"]);
+        assert_eq!(lines(40, "trailing\n"), ["trailing"]);
+        assert_eq!(lines(40, "a\n\nb\n\n"), ["a", "", "b", ""]);
+        assert_eq!(lines(12, "abcdefgh ij"), ["abc", "def", "gh", "ij"]);
+        assert_eq!(lines(12, "x abcdefg"), ["x", "abc", "def", "g"]);
+        assert_eq!(lines(0, "ab"), ["a", "b"]);
+        assert_eq!(lines(16, "ab "), ["ab"]);
+    }
+
+    #[test]
+    fn measure_text_box() {
+        assert_eq!(super::measure_text_box(32, "Hello world", None), (20, 12));
+        assert_eq!(super::measure_text_box(100, "Hi\nthere", None), (20, 12));
+        assert_eq!(super::measure_text_box(100, "", None), (0, 0));
+
+        let font = Font::with_widths(Image::new(40, 24), 10, 12, ' ', &[2, 3, 5]);
+        let font = font.lock();
+        assert_eq!(char_width(Some(&font), '!'), 3);
+        assert_eq!(char_width(Some(&font), 'z'), 0);
+        assert_eq!(
+            super::measure_text_box(10, "\"\" !! !", Some(&font)),
+            (10, 36)
+        );
+    }
+}
@@ -1,8 +1,15 @@
+use std::cmp::max;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+
 use crate::canvas::{Canvas, ToIndex};
+use crate::geometry::{Rectf, Vec2};
 use crate::image::SharedImage;
 use crate::resource::ResourceItem;
-use crate::settings::{RESOURCE_ARCHIVE_DIRNAME, TILEMAP_SIZE};
-use crate::types::Tile;
+use crate::settings::{RESOURCE_ARCHIVE_DIRNAME, TILEMAP_SIZE, TILE_SIZE};
+use crate::tmx::parse_tmx;
+use crate::types::{Color, Tile};
 use crate::utils::{as_u32, parse_hex_string, simplify_string};
 use crate::Pyxel;
 
@@ -12,9 +19,44 @@ impl ToIndex for Tile {
     }
 }
 
+#[derive(Debug)]
+pub enum TilemapError {
+    Open(String),
+    Parse(String),
+    UnsupportedEncoding(String),
+    ExternalTileset(String),
+    InvalidTileSize(u32),
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Open(filename) => write!(f, "Unable to open file '{}'", filename),
+            Self::Parse(message) => write!(f, "Invalid TMX data: {}", message),
+            Self::UnsupportedEncoding(encoding) => {
+                write!(f, "Unsupported TMX layer encoding '{}'", encoding)
+            }
+            Self::ExternalTileset(source) => {
+                write!(f, "External TMX tileset '{}' is not supported", source)
+            }
+            Self::InvalidTileSize(tile_size) => write!(f, "Invalid tile size {}", tile_size),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SweepResult {
+    pub delta: Vec2,
+    pub hit_x: bool,
+    pub hit_y: bool,
+}
+
 pub struct Tilemap {
     pub(crate) canvas: Canvas<Tile>,
     pub image: SharedImage,
+    pub colkey: Option<Color>,
+    pub(crate) tile_size: u32,
+    pub(crate) solid_tiles: HashSet<Tile>,
 }
 
 pub type SharedTilemap = shared_type!(Tilemap);
@@ -24,9 +66,77 @@ impl Tilemap {
         new_shared_type!(Self {
             canvas: Canvas::new(width, height),
             image,
+            colkey: None,
+            tile_size: TILE_SIZE,
+            solid_tiles: HashSet::new(),
         })
     }
 
+    pub fn from_tmx(
+        filename: &str,
+        image: SharedImage,
+    ) -> Result<Vec<SharedTilemap>, TilemapError> {
+        let xml =
+            fs::read_to_string(filename).map_err(|_| TilemapError::Open(filename.to_string()))?;
+        let map = parse_tmx(&xml)?;
+        let tile_size = map.tile_size.unwrap_or(TILE_SIZE);
+        let image_columns = max(image.lock().width() / tile_size, 1);
+        let mut tilemaps = Vec::new();
+        for layer in &map.layers {
+            let tilemap = Self::new(layer.width, layer.height, image.clone());
+            {
+                let mut tilemap = tilemap.lock();
+                tilemap.tile_size = tile_size;
+                for (i, gid) in layer.gids.iter().enumerate() {
+                    let (index, columns) = match map.tile_index(*gid) {
+                        Some(tile_index) => tile_index,
+                        None => continue,
+                    };
+                    let columns = columns.unwrap_or(image_columns);
+                    let tile_x = index % columns;
+                    let tile_y = index / columns;
+                    if tile_x > u8::MAX as u32 || tile_y > u8::MAX as u32 {
+                        return Err(TilemapError::Parse(format!("gid {} is out of range", gid)));
+                    }
+                    let x = i % layer.width as usize;
+                    let y = i / layer.width as usize;
+                    tilemap.canvas.data[y][x] = (tile_x as u8, tile_y as u8);
+                }
+            }
+            tilemaps.push(tilemap);
+        }
+        Ok(tilemaps)
+    }
+
+    pub fn load_tmx(&mut self, filename: &str) -> Result<(), TilemapError> {
+        // Only the first layer is used, and tiles outside the tilemap are dropped
+        let tilemaps = Self::from_tmx(filename, self.image.clone())?;
+        let tilemap = tilemaps
+            .first()
+            .ok_or_else(|| TilemapError::Parse("missing <layer> in <map>".to_string()))?;
+        let tilemap = tilemap.lock();
+        let width = tilemap.width().min(self.width()) as usize;
+        let height = tilemap.height().min(self.height()) as usize;
+        for y in 0..height {
+            self.canvas.data[y][..width].copy_from_slice(&tilemap.canvas.data[y][..width]);
+        }
+        self.tile_size = tilemap.tile_size;
+        drop(tilemap);
+        Ok(())
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    pub fn set_tile_size(&mut self, tile_size: u32) -> Result<(), TilemapError> {
+        if tile_size == 0 {
+            return Err(TilemapError::InvalidTileSize(tile_size));
+        }
+        self.tile_size = tile_size;
+        Ok(())
+    }
+
     pub fn width(&self) -> u32 {
         self.canvas.width()
     }
@@ -36,31 +146,111 @@ impl Tilemap {
     }
 
     pub fn set(&mut self, x: i32, y: i32, data_str: &[&str]) {
-        let width = simplify_string(data_str[0]).len() as u32 / 4;
-        let height = data_str.len() as u32;
-        let tilemap = Self::new(width, height, self.image.clone());
-        {
-            let mut tilemap = tilemap.lock();
-            for y in 0..height {
-                let src_data = simplify_string(data_str[y as usize]);
-                for x in 0..width {
-                    let index = x as usize * 4;
-                    let tile = parse_hex_string(&src_data[index..index + 4]).unwrap();
-                    tilemap.canvas.data[y as usize][x as usize] =
-                        (((tile >> 8) & 0xff) as u8, (tile & 0xff) as u8);
+        // Tiles that are not 4 hex digits are skipped and leave the tile unchanged
+        for (yi, row) in data_str.iter().enumerate() {
+            let src_data: Vec<char> = simplify_string(row).chars().collect();
+            for (xi, tile_str) in src_data.chunks_exact(4).enumerate() {
+                let value_x = x + xi as i32;
+                let value_y = y + yi as i32;
+                let tile_str: String = tile_str.iter().collect();
+                if let Ok(tile) = parse_hex_string(&tile_str) {
+                    if self.canvas.self_rect.contains_point(value_x, value_y) {
+                        self.canvas.data[value_y as usize][value_x as usize] =
+                            (((tile >> 8) & 0xff) as u8, (tile & 0xff) as u8);
+                    }
                 }
             }
         }
-        self.blt(
-            x as f64,
-            y as f64,
-            tilemap,
-            0.0,
-            0.0,
-            width as f64,
-            height as f64,
-            None,
-        );
+    }
+
+    pub fn set_collision(&mut self, tiles: &[Tile]) {
+        self.solid_tiles = tiles.iter().copied().collect();
+    }
+
+    pub fn is_solid_tile(&self, tile_x: i32, tile_y: i32) -> bool {
+        // Cells outside the tilemap are empty, so bodies can leave through open edges
+        self.canvas.self_rect.contains_point(tile_x, tile_y)
+            && self
+                .solid_tiles
+                .contains(&self.canvas.data[tile_y as usize][tile_x as usize])
+    }
+
+    pub fn is_solid_at(&self, x: f64, y: f64) -> bool {
+        let tile_size = self.tile_size as f64;
+        self.is_solid_tile(
+            (x / tile_size).floor() as i32,
+            (y / tile_size).floor() as i32,
+        )
+    }
+
+    pub fn tiles_in_rect(&self, rect: Rectf) -> impl Iterator<Item = (i32, i32, Tile)> + '_ {
+        let tile_size = self.tile_size as f64;
+        let (tile_x1, tile_x2) = tile_range(rect.x, rect.right(), tile_size);
+        let (tile_y1, tile_y2) = tile_range(rect.y, rect.bottom(), tile_size);
+        let self_rect = self.canvas.self_rect;
+        let tile_x1 = tile_x1.max(self_rect.left());
+        let tile_x2 = tile_x2.min(self_rect.right() + 1);
+        let tile_y1 = tile_y1.max(self_rect.top());
+        let tile_y2 = tile_y2.min(self_rect.bottom() + 1);
+        (tile_y1..tile_y2).flat_map(move |tile_y| {
+            (tile_x1..tile_x2).map(move |tile_x| {
+                (
+                    tile_x,
+                    tile_y,
+                    self.canvas.data[tile_y as usize][tile_x as usize],
+                )
+            })
+        })
+    }
+
+    pub fn sweep_aabb(&self, rect: Rectf, delta: Vec2) -> SweepResult {
+        // Horizontal movement is resolved first and vertical movement from the moved rect,
+        // and every cell between the start and the end is checked so fast bodies cannot tunnel
+        let tile_size = self.tile_size as f64;
+        let rows = tile_range(rect.y, rect.bottom(), tile_size);
+        let hit_dx = self.sweep_axis(rect.x, rect.width, delta.x, rows, |column, row| {
+            self.is_solid_tile(column, row)
+        });
+        let dx = hit_dx.unwrap_or(delta.x);
+        let columns = tile_range(rect.x + dx, rect.right() + dx, tile_size);
+        let hit_dy = self.sweep_axis(rect.y, rect.height, delta.y, columns, |row, column| {
+            self.is_solid_tile(column, row)
+        });
+        SweepResult {
+            delta: Vec2::new(dx, hit_dy.unwrap_or(delta.y)),
+            hit_x: hit_dx.is_some(),
+            hit_y: hit_dy.is_some(),
+        }
+    }
+
+    fn sweep_axis(
+        &self,
+        start: f64,
+        size: f64,
+        delta: f64,
+        (cross1, cross2): (i32, i32),
+        is_solid: impl Fn(i32, i32) -> bool,
+    ) -> Option<f64> {
+        // Cells the rect already overlaps are skipped so a stuck body can still move out
+        let tile_size = self.tile_size as f64;
+        let is_blocked = |cell: i32| (cross1..cross2).any(|cross| is_solid(cell, cross));
+        if delta > 0.0 {
+            let end = start + size;
+            let cell1 = (end / tile_size).ceil() as i32;
+            let cell2 = ((end + delta) / tile_size).ceil() as i32;
+            (cell1..cell2)
+                .find(|&cell| is_blocked(cell))
+                .map(|cell| cell as f64 * tile_size - end)
+        } else if delta < 0.0 {
+            let cell1 = ((start + delta) / tile_size).floor() as i32;
+            let cell2 = (start / tile_size).floor() as i32;
+            (cell1..cell2)
+                .rev()
+                .find(|&cell| is_blocked(cell))
+                .map(|cell| (cell + 1) as f64 * tile_size - start)
+        } else {
+            None
+        }
     }
 
     pub fn clip(&mut self, x: f64, y: f64, width: f64, height: f64) {
@@ -95,6 +285,18 @@ impl Tilemap {
         self.canvas.line(x1, y1, x2, y2, tile);
     }
 
+    pub fn line_w(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, tile: Tile) {
+        self.canvas.line_w(x1, y1, x2, y2, thickness, tile);
+    }
+
+    pub fn polyline(&mut self, points: &[(f64, f64)], tile: Tile) {
+        self.canvas.polyline(points, tile);
+    }
+
+    pub fn bezier(&mut self, x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, tile: Tile) {
+        self.canvas.bezier(x1, y1, cx, cy, x2, y2, tile);
+    }
+
     pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, tile: Tile) {
         self.canvas.rect(x, y, width, height, tile);
     }
@@ -103,6 +305,10 @@ impl Tilemap {
         self.canvas.rectb(x, y, width, height, tile);
     }
 
+    pub fn rectb_w(&mut self, x: f64, y: f64, width: f64, height: f64, thickness: f64, tile: Tile) {
+        self.canvas.rectb_w(x, y, width, height, thickness, tile);
+    }
+
     pub fn circ(&mut self, x: f64, y: f64, radius: f64, tile: Tile) {
         self.canvas.circ(x, y, radius, tile);
     }
@@ -175,6 +381,13 @@ impl Tilemap {
     }
 }
 
+fn tile_range(start: f64, end: f64, tile_size: f64) -> (i32, i32) {
+    (
+        (start / tile_size).floor() as i32,
+        (end / tile_size).ceil() as i32,
+    )
+}
+
 impl ResourceItem for Tilemap {
     fn resource_name(item_no: u32) -> String {
         RESOURCE_ARCHIVE_DIRNAME.to_string() + "tilemap" + &item_no.to_string()
@@ -208,24 +421,286 @@ impl ResourceItem for Tilemap {
         output
     }
 
-    fn deserialize(&mut self, pyxel: &Pyxel, version: u32, input: &str) {
-        for (y, line) in input.lines().enumerate() {
-            if y < TILEMAP_SIZE as usize {
+    fn deserialize(
+        &mut self,
+        images: &[SharedImage],
+        version: u32,
+        input: &str,
+    ) -> Result<(), String> {
+        let tile_size = if version < 15000 { 3 } else { 4 };
+        let lines: Vec<&str> = input.lines().collect();
+        let num_rows = lines.len().min(TILEMAP_SIZE as usize);
+        let width = lines[..num_rows]
+            .iter()
+            .map(|line| line.len() / tile_size)
+            .max()
+            .unwrap_or(0) as u32;
+        let height = num_rows as u32;
+        if width > self.width() || height > self.height() {
+            return Err(format!(
+                "tilemap data is {}x{} but the tilemap is {}x{}",
+                width,
+                height,
+                self.width(),
+                self.height()
+            ));
+        }
+        let invalid_tile = |tile: &str| format!("invalid tile '{}'", tile);
+        let mut rows = Vec::with_capacity(num_rows);
+        for line in &lines[..num_rows] {
+            let mut row = Vec::with_capacity(line.len() / tile_size);
+            string_loop!(x, tile, line, tile_size, {
                 if version < 15000 {
-                    string_loop!(x, tile, line, 3, {
-                        let tile = parse_hex_string(&tile).unwrap();
-                        self.canvas.data[y][x] = ((tile % 32) as u8, (tile / 32) as u8);
-                    });
+                    let value = parse_hex_string(&tile).map_err(|_| invalid_tile(&tile))?;
+                    row.push(((value % 32) as u8, (value / 32) as u8));
                 } else {
-                    string_loop!(x, tile, line, 4, {
-                        let tile_x = parse_hex_string(&tile[0..2].to_string()).unwrap();
-                        let tile_y = parse_hex_string(&tile[2..4].to_string()).unwrap();
-                        self.canvas.data[y][x] = (tile_x as u8, tile_y as u8);
-                    });
+                    let tile_x = parse_hex_string(&tile[0..2]).map_err(|_| invalid_tile(&tile))?;
+                    let tile_y = parse_hex_string(&tile[2..4]).map_err(|_| invalid_tile(&tile))?;
+                    row.push((tile_x as u8, tile_y as u8));
                 }
-            } else {
-                self.image = pyxel.image(line.parse().unwrap());
+            });
+            rows.push(row);
+        }
+        let image = match lines.get(num_rows) {
+            Some(line) => match line.parse::<usize>().ok().and_then(|i| images.get(i)) {
+                Some(image) => Some(image.clone()),
+                None => return Err(format!("invalid image number '{}'", line)),
+            },
+            None => None,
+        };
+        for (y, row) in rows.into_iter().enumerate() {
+            self.canvas.data[y][..row.len()].copy_from_slice(&row);
+        }
+        if let Some(image) = image {
+            self.image = image;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::Image;
+
+    fn write_tmx(name: &str, xml: &str) -> String {
+        let filename = std::env::temp_dir().join(name);
+        std::fs::write(&filename, xml).unwrap();
+        filename.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn from_tmx() {
+        let filename = write_tmx(
+            "pyxel_test_from_tmx.tmx",
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.5" orientation="orthogonal" width="3" height="2" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="tiles" tilewidth="8" tileheight="8" columns="4">
+  <image source="tiles.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+5,2147483654,12
+</data>
+ </layer>
+ <layer id="2" name="objects" width="3" height="2">
+  <data encoding="csv">0,0,3,0,0,0</data>
+ </layer>
+</map>"#,
+        );
+        let tilemaps = Tilemap::from_tmx(&filename, Image::new(256, 256)).unwrap();
+        assert_eq!(tilemaps.len(), 2);
+        assert_eq!(
+            tilemaps[0].lock().canvas.data,
+            [[(0, 0), (1, 0), (0, 0)], [(0, 1), (1, 1), (3, 2)]]
+        );
+        assert_eq!(
+            tilemaps[1].lock().canvas.data,
+            [[(0, 0), (0, 0), (2, 0)], [(0, 0), (0, 0), (0, 0)]]
+        );
+        assert_eq!(tilemaps[0].lock().tile_size, 8);
+
+        let tilemap = Tilemap::new(2, 3, Image::new(256, 256));
+        tilemap.lock().load_tmx(&filename).unwrap();
+        assert_eq!(
+            tilemap.lock().canvas.data,
+            [[(0, 0), (1, 0)], [(0, 1), (1, 1)], [(0, 0), (0, 0)]]
+        );
+        std::fs::remove_file(&filename).unwrap();
+    }
+
+    #[test]
+    fn from_tmx_error() {
+        let filename = write_tmx(
+            "pyxel_test_from_tmx_base64.tmx",
+            r#"<map width="1" height="1" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" columns="2"/>
+ <layer width="1" height="1"><data encoding="base64" compression="zlib">eJxjYAAAAAIAAQ==</data></layer>
+</map>"#,
+        );
+        assert!(matches!(
+            Tilemap::from_tmx(&filename, Image::new(8, 8)),
+            Err(TilemapError::UnsupportedEncoding(_))
+        ));
+        std::fs::remove_file(&filename).unwrap();
+        let filename = write_tmx(
+            "pyxel_test_from_tmx_tsx.tmx",
+            r#"<map width="1" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" source="tiles.tsx"/>
+</map>"#,
+        );
+        assert!(matches!(
+            Tilemap::from_tmx(&filename, Image::new(8, 8)),
+            Err(TilemapError::ExternalTileset(_))
+        ));
+        std::fs::remove_file(&filename).unwrap();
+        assert!(matches!(
+            Tilemap::from_tmx("nonexistent.tmx", Image::new(8, 8)),
+            Err(TilemapError::Open(_))
+        ));
+    }
+
+    #[test]
+    fn set_tile_size() {
+        let tilemap = Tilemap::new(1, 1, Image::new(8, 8));
+        assert!(matches!(
+            tilemap.lock().set_tile_size(0),
+            Err(TilemapError::InvalidTileSize(0))
+        ));
+        assert_eq!(tilemap.lock().tile_size(), TILE_SIZE);
+        tilemap.lock().set_tile_size(16).unwrap();
+        assert_eq!(tilemap.lock().tile_size(), 16);
+    }
+
+    fn collision_map() -> SharedTilemap {
+        let rows = [
+            "................",
+            "................",
+            "........##......",
+            "...#............",
+            "...........#....",
+            ".....###...#....",
+            "...........#....",
+            "################",
+        ];
+        let tilemap = Tilemap::new(16, 8, Image::new(8, 8));
+        let data: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|c| if c == '#' { "0100" } else { "0000" })
+                    .collect()
+            })
+            .collect();
+        let data: Vec<&str> = data.iter().map(String::as_str).collect();
+        tilemap.lock().set(0, 0, &data);
+        tilemap.lock().set_collision(&[(1, 0)]);
+        tilemap
+    }
+
+    #[test]
+    fn collision_queries() {
+        let tilemap = collision_map();
+        let tilemap = tilemap.lock();
+        assert!(tilemap.is_solid_at(24.0, 24.0));
+        assert!(tilemap.is_solid_at(31.9, 31.9));
+        assert!(!tilemap.is_solid_at(32.0, 24.0));
+        assert!(!tilemap.is_solid_at(-1.0, 60.0));
+        assert!(!tilemap.is_solid_at(200.0, 60.0));
+
+        let tiles: Vec<(i32, i32, Tile)> = tilemap
+            .tiles_in_rect(Rectf::new(20.0, 50.0, 5.0, 10.0))
+            .collect();
+        assert_eq!(
+            tiles,
+            [
+                (2, 6, (0, 0)),
+                (3, 6, (0, 0)),
+                (2, 7, (1, 0)),
+                (3, 7, (1, 0))
+            ]
+        );
+        assert_eq!(
+            tilemap
+                .tiles_in_rect(Rectf::new(-20.0, -20.0, 28.0, 28.0))
+                .count(),
+            1
+        );
+
+        let rect = Rectf::new(60.0, 48.0, 6.0, 8.0);
+        let result = tilemap.sweep_aabb(rect, Vec2::new(50.0, 0.0));
+        assert_eq!(
+            result,
+            SweepResult {
+                delta: Vec2::new(22.0, 0.0),
+                hit_x: true,
+                hit_y: false
+            }
+        );
+        let result = tilemap.sweep_aabb(Rectf::new(100.0, 10.0, 6.0, 8.0), Vec2::new(-20.0, 40.0));
+        assert_eq!(result.delta, Vec2::new(-20.0, 38.0));
+        assert!(!result.hit_x && result.hit_y);
+        let result = tilemap.sweep_aabb(Rectf::new(66.0, 30.0, 6.0, 8.0), Vec2::new(0.0, -30.0));
+        assert_eq!(result.delta, Vec2::new(0.0, -6.0));
+        assert!(result.hit_y);
+        let result = tilemap.sweep_aabb(Rectf::new(24.0, 54.0, 6.0, 8.0), Vec2::new(0.0, -3.0));
+        assert_eq!(result.delta, Vec2::new(0.0, -3.0));
+        drop(tilemap);
+    }
+
+    #[test]
+    fn gravity_jump() {
+        let tilemap = collision_map();
+        let tilemap = tilemap.lock();
+        let mut player = Rectf::new(8.0, 8.0, 6.0, 8.0);
+        let mut velocity = Vec2::new(3.0, 0.0);
+        let (mut num_landings, mut num_turns) = (0, 0);
+        for frame in 0..600 {
+            velocity.y = (velocity.y + 0.75).min(20.0);
+            let result = tilemap.sweep_aabb(player, velocity);
+            player.x += result.delta.x;
+            player.y += result.delta.y;
+            assert!(
+                tilemap
+                    .tiles_in_rect(player)
+                    .all(|(_, _, tile)| tile != (1, 0)),
+                "clipped into a tile at frame {}: {:?}",
+                frame,
+                player
+            );
+            if result.hit_x {
+                velocity.x = -velocity.x;
+                num_turns += 1;
+            }
+            if result.hit_y {
+                if velocity.y > 0.0 {
+                    num_landings += 1;
+                    velocity.y = if frame % 3 == 0 { -9.0 } else { 0.0 };
+                } else {
+                    velocity.y = 0.0;
+                }
+            }
+            if player.x < 0.0 || player.right() > 128.0 {
+                velocity.x = -velocity.x;
             }
         }
+        assert!(num_landings > 10);
+        assert!(num_turns > 0);
+        assert!(player.bottom() <= 56.0);
+        drop(tilemap);
+    }
+
+    #[test]
+    fn set() {
+        let tilemap = Tilemap::new(3, 2, Image::new(8, 8));
+        tilemap.lock().camera(1.0, 1.0);
+        tilemap
+            .lock()
+            .set(-1, 0, &["0102 0304 0506 0708", "0a0b xx0c 0d0e 0f"]);
+        assert_eq!(
+            tilemap.lock().canvas.data,
+            [[(3, 4), (5, 6), (7, 8)], [(0, 0), (0x0d, 0x0e), (0, 0)]]
+        );
     }
 }
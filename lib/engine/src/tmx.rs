@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use crate::tilemap::TilemapError;
+
+const FLIPPED_FLAGS: u32 = 0xf000_0000;
+
+pub struct TmxTileset {
+    pub first_gid: u32,
+    pub columns: Option<u32>,
+}
+
+pub struct TmxLayer {
+    pub width: u32,
+    pub height: u32,
+    pub gids: Vec<u32>,
+}
+
+pub struct TmxMap {
+    pub tile_size: Option<u32>,
+    pub tilesets: Vec<TmxTileset>,
+    pub layers: Vec<TmxLayer>,
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    attrs: HashMap<&'a str, &'a str>,
+    body: &'a str,
+}
+
+fn parse_attrs(string: &str) -> HashMap<&str, &str> {
+    let mut attrs = HashMap::new();
+    let mut rest = string;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let value = rest[eq + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        attrs.insert(name, &value[1..end]);
+        rest = &value[end + 1..];
+    }
+    attrs
+}
+
+fn parse_tags(xml: &str) -> Vec<Tag<'_>> {
+    let mut tags = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with(['/', '?', '!']) {
+            continue;
+        }
+        let is_empty = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let body = if is_empty {
+            ""
+        } else {
+            let close = format!("</{}", name);
+            rest.find(&close).map_or("", |end| &rest[..end])
+        };
+        tags.push(Tag {
+            name,
+            attrs: parse_attrs(attrs),
+            body,
+        });
+    }
+    tags
+}
+
+fn parse_u32(tag: &Tag, name: &str) -> Result<u32, TilemapError> {
+    tag.attrs
+        .get(name)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| TilemapError::Parse(format!("missing {} in <{}>", name, tag.name)))
+}
+
+pub fn parse_tmx(xml: &str) -> Result<TmxMap, TilemapError> {
+    let tags = parse_tags(xml);
+    let mut tilesets = Vec::new();
+    let mut layers = Vec::new();
+    let mut tile_size = None;
+    for (i, tag) in tags.iter().enumerate() {
+        match tag.name {
+            "map" => {
+                let tile_width = parse_u32(tag, "tilewidth")?;
+                let tile_height = parse_u32(tag, "tileheight")?;
+                tile_size = (tile_width == tile_height && tile_width > 0).then_some(tile_width);
+            }
+            "tileset" => {
+                if let Some(source) = tag.attrs.get("source") {
+                    return Err(TilemapError::ExternalTileset(source.to_string()));
+                }
+                tilesets.push(TmxTileset {
+                    first_gid: parse_u32(tag, "firstgid")?,
+                    columns: tag
+                        .attrs
+                        .get("columns")
+                        .and_then(|value| value.parse().ok()),
+                });
+            }
+            "layer" => {
+                let width = parse_u32(tag, "width")?;
+                let height = parse_u32(tag, "height")?;
+                let num_tiles = width.checked_mul(height).ok_or_else(|| {
+                    TilemapError::Parse(format!("layer size {}x{} is too large", width, height))
+                })?;
+                let data = tags[i + 1..]
+                    .iter()
+                    .take_while(|tag| tag.name != "layer")
+                    .find(|tag| tag.name == "data")
+                    .ok_or_else(|| TilemapError::Parse("missing <data> in <layer>".to_string()))?;
+                let encoding = data.attrs.get("encoding").copied().unwrap_or("xml");
+                if encoding != "csv" || data.attrs.contains_key("compression") {
+                    return Err(TilemapError::UnsupportedEncoding(encoding.to_string()));
+                }
+                let gids = data
+                    .body
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(|value| {
+                        value
+                            .parse::<u32>()
+                            .map_err(|_| TilemapError::Parse(format!("invalid gid '{}'", value)))
+                    })
+                    .collect::<Result<Vec<u32>, TilemapError>>()?;
+                if gids.len() != num_tiles as usize {
+                    return Err(TilemapError::Parse(format!(
+                        "layer has {} tiles instead of {}",
+                        gids.len(),
+                        num_tiles
+                    )));
+                }
+                layers.push(TmxLayer {
+                    width,
+                    height,
+                    gids,
+                });
+            }
+            _ => {}
+        }
+    }
+    tilesets.sort_by_key(|tileset| tileset.first_gid);
+    Ok(TmxMap {
+        tile_size,
+        tilesets,
+        layers,
+    })
+}
+
+impl TmxMap {
+    pub fn tile_index(&self, gid: u32) -> Option<(u32, Option<u32>)> {
+        // TODO: Honor the flipped flags once tiles can be drawn flipped
+        let gid = gid & !FLIPPED_FLAGS;
+        if gid == 0 {
+            return None;
+        }
+        self.tilesets
+            .iter()
+            .rev()
+            .find(|tileset| tileset.first_gid <= gid)
+            .map(|tileset| (gid - tileset.first_gid, tileset.columns))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_attrs_() {
+        let attrs = parse_attrs(r#"a="1" b = '2 3'  c="x>y""#);
+        assert_eq!(attrs["a"], "1");
+        assert_eq!(attrs["b"], "2 3");
+        assert_eq!(attrs.len(), 3);
+    }
+
+    #[test]
+    fn tile_index() {
+        let map = TmxMap {
+            tile_size: None,
+            tilesets: vec![
+                TmxTileset {
+                    first_gid: 1,
+                    columns: Some(4),
+                },
+                TmxTileset {
+                    first_gid: 17,
+                    columns: None,
+                },
+            ],
+            layers: Vec::new(),
+        };
+        assert_eq!(map.tile_index(0), None);
+        assert_eq!(map.tile_index(1), Some((0, Some(4))));
+        assert_eq!(map.tile_index(0x8000_0006), Some((5, Some(4))));
+        assert_eq!(map.tile_index(0xe000_0011), Some((0, None)));
+    }
+
+    #[test]
+    fn parse_tmx_overflow() {
+        let result = parse_tmx(
+            r#"<map tilewidth="8" tileheight="8">
+ <layer width="65536" height="65536"><data encoding="csv">1</data></layer>
+</map>"#,
+        );
+        assert!(matches!(result, Err(TilemapError::Parse(_))));
+    }
+}
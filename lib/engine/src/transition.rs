@@ -0,0 +1,128 @@
+use crate::palette::Palette;
+use crate::types::Rgb8;
+
+#[derive(Default)]
+pub struct Transition {
+    color: Rgb8,
+    level: f64,
+    start_level: f64,
+    end_level: f64,
+    frame: u32,
+    num_frames: u32,
+    saved_tint: Option<(Rgb8, f64)>,
+}
+
+impl Transition {
+    pub fn fade_out(&mut self, frames: u32, to: Rgb8) {
+        self.color = to;
+        self.start(frames, 1.0);
+    }
+
+    pub fn fade_in(&mut self, frames: u32) {
+        self.start(frames, 0.0);
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.num_frames
+    }
+
+    pub fn update(&mut self, palette: &mut Palette) {
+        if self.is_done() {
+            return;
+        }
+        if self.saved_tint.is_none() {
+            self.saved_tint = Some(palette.display_tint());
+        }
+        self.frame += 1;
+        self.level = if self.is_done() {
+            self.end_level
+        } else {
+            self.start_level
+                + (self.end_level - self.start_level) * self.frame as f64 / self.num_frames as f64
+        };
+        if self.is_done() && self.level == 0.0 {
+            // Restore the tint exactly rather than relying on the interpolation
+            let (color, strength) = self.saved_tint.take().unwrap();
+            palette.set_display_tint(color, strength);
+        } else {
+            palette.set_display_tint(self.color, self.level);
+        }
+    }
+
+    fn start(&mut self, frames: u32, end_level: f64) {
+        self.start_level = self.level;
+        self.end_level = end_level;
+        self.frame = 0;
+        self.num_frames = frames.max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::red;
+    use crate::settings::DEFAULT_COLORS;
+
+    #[test]
+    fn fade() {
+        let mut palette = Palette::default();
+        let mut transition = Transition::default();
+        assert!(transition.is_done());
+        transition.update(&mut palette);
+        assert_eq!(palette.display_colors()[..16], DEFAULT_COLORS);
+
+        transition.fade_out(10, 0x000000);
+        assert!(!transition.is_done());
+        let mut last_red = red(palette.display_color(7));
+        for _ in 0..10 {
+            transition.update(&mut palette);
+            let value = red(palette.display_color(7));
+            assert!(value < last_red);
+            last_red = value;
+        }
+        assert!(transition.is_done());
+        assert_eq!(palette.display_color(7), 0x000000);
+        transition.update(&mut palette);
+        assert_eq!(palette.display_color(7), 0x000000);
+
+        transition.fade_in(8);
+        for _ in 0..8 {
+            transition.update(&mut palette);
+            let value = red(palette.display_color(7));
+            assert!(value > last_red);
+            last_red = value;
+        }
+        assert!(transition.is_done());
+        assert_eq!(palette.display_colors()[..16], DEFAULT_COLORS);
+    }
+
+    #[test]
+    fn fade_interrupted() {
+        let mut palette = Palette::default();
+        palette.set_display_tint(0x0000ff, 0.25);
+        let original = *palette.display_colors();
+        let mut transition = Transition::default();
+
+        transition.fade_out(10, 0xffffff);
+        for _ in 0..4 {
+            transition.update(&mut palette);
+        }
+        assert_ne!(*palette.display_colors(), original);
+        transition.fade_in(6);
+        let mut frames = 0;
+        while !transition.is_done() {
+            transition.update(&mut palette);
+            frames += 1;
+        }
+        assert_eq!(frames, 6);
+        assert_eq!(*palette.display_colors(), original);
+
+        transition.fade_out(0, 0x000000);
+        transition.update(&mut palette);
+        assert!(transition.is_done());
+        assert_eq!(palette.display_color(7), 0x000000);
+        transition.fade_in(0);
+        transition.update(&mut palette);
+        assert_eq!(*palette.display_colors(), original);
+    }
+}
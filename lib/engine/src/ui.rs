@@ -0,0 +1,360 @@
+use crate::image::{Image, SharedImage};
+use crate::key::{
+    GAMEPAD1_BUTTON_A, GAMEPAD1_BUTTON_B, GAMEPAD1_BUTTON_DPAD_DOWN, GAMEPAD1_BUTTON_DPAD_LEFT,
+    GAMEPAD1_BUTTON_DPAD_RIGHT, GAMEPAD1_BUTTON_DPAD_UP, KEY_BACKSPACE, KEY_DOWN, KEY_LEFT,
+    KEY_RETURN, KEY_RIGHT, KEY_UP,
+};
+use crate::settings::{
+    FONT_HEIGHT, FONT_WIDTH, VIRTUAL_KEYBOARD_CHAR_SETS, VIRTUAL_KEYBOARD_COLUMNS,
+    VIRTUAL_KEYBOARD_HOLD_FRAMES, VIRTUAL_KEYBOARD_KEY_SIZE, VIRTUAL_KEYBOARD_REPEAT_FRAMES,
+};
+use crate::types::{Color, Key};
+use crate::Pyxel;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CharSet {
+    Upper,
+    Lower,
+    Digits,
+    Symbols,
+}
+
+impl CharSet {
+    const fn chars(self) -> &'static str {
+        VIRTUAL_KEYBOARD_CHAR_SETS[self as usize]
+    }
+
+    const fn next(self) -> Self {
+        match self {
+            Self::Upper => Self::Lower,
+            Self::Lower => Self::Digits,
+            Self::Digits => Self::Symbols,
+            Self::Symbols => Self::Upper,
+        }
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Upper => "ABC",
+            Self::Lower => "abc",
+            Self::Digits => "123",
+            Self::Symbols => "#+=",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VirtualKey {
+    Char(char),
+    NextCharSet,
+    Backspace,
+    Done,
+}
+
+// Mirrors the text input events of a physical keyboard
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum VirtualKeyboardEvent {
+    TextInput { text: String },
+    Backspace,
+    Done,
+}
+
+// The bottom row holds the wide keys as (key, first column, number of columns)
+const SPECIAL_KEYS: [(VirtualKey, u32, u32); 4] = [
+    (VirtualKey::NextCharSet, 0, 3),
+    (VirtualKey::Char(' '), 3, 3),
+    (VirtualKey::Backspace, 6, 2),
+    (VirtualKey::Done, 8, 2),
+];
+
+pub struct VirtualKeyboard {
+    pub x: f64,
+    pub y: f64,
+    pub bg_color: Color,
+    pub key_color: Color,
+    pub text_color: Color,
+    pub cursor_color: Color,
+    char_set: CharSet,
+    cursor: (u32, u32),
+}
+
+impl VirtualKeyboard {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self {
+            x,
+            y,
+            bg_color: 1,
+            key_color: 5,
+            text_color: 7,
+            cursor_color: 10,
+            char_set: CharSet::Upper,
+            cursor: (0, 0),
+        }
+    }
+
+    pub const fn char_set(&self) -> CharSet {
+        self.char_set
+    }
+
+    pub fn set_char_set(&mut self, char_set: CharSet) {
+        // Keep the cursor on the bottom row if it was there, otherwise inside the grid
+        let is_bottom = self.cursor.1 == self.num_rows() - 1;
+        self.char_set = char_set;
+        let last_row = self.num_rows() - 1;
+        self.cursor.1 = if is_bottom {
+            last_row
+        } else {
+            self.cursor.1.min(last_row - 1)
+        };
+        self.cursor.0 = self.clamp_column(self.cursor.0, self.cursor.1);
+    }
+
+    pub const fn cursor(&self) -> (u32, u32) {
+        self.cursor
+    }
+
+    pub fn width(&self) -> u32 {
+        VIRTUAL_KEYBOARD_COLUMNS * VIRTUAL_KEYBOARD_KEY_SIZE + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        self.num_rows() * VIRTUAL_KEYBOARD_KEY_SIZE + 1
+    }
+
+    pub fn selected_key(&self) -> VirtualKey {
+        self.key_at(self.cursor.0, self.cursor.1)
+    }
+
+    pub fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let num_rows = self.num_rows() as i32;
+        let (mut col, mut row) = (self.cursor.0 as i32, self.cursor.1 as i32);
+        if dy != 0 {
+            row = (row + dy).rem_euclid(num_rows);
+            col = self.clamp_column(col as u32, row as u32) as i32;
+        }
+        if dx != 0 {
+            // Wide keys are stepped over as a whole
+            let (start, span) = self.key_span(col as u32, row as u32);
+            let next = if dx > 0 {
+                (start + span) as i32
+            } else {
+                start as i32 - 1
+            };
+            let row_width = self.row_width(row as u32) as i32;
+            col = next.rem_euclid(row_width);
+            col = self.key_span(col as u32, row as u32).0 as i32;
+        }
+        self.cursor = (col as u32, row as u32);
+    }
+
+    pub fn select(&mut self) -> Option<VirtualKeyboardEvent> {
+        match self.selected_key() {
+            VirtualKey::Char(c) => Some(VirtualKeyboardEvent::TextInput {
+                text: c.to_string(),
+            }),
+            VirtualKey::NextCharSet => {
+                self.set_char_set(self.char_set.next());
+                None
+            }
+            VirtualKey::Backspace => Some(VirtualKeyboardEvent::Backspace),
+            VirtualKey::Done => Some(VirtualKeyboardEvent::Done),
+        }
+    }
+
+    pub fn update(&mut self, pyxel: &Pyxel) -> Option<VirtualKeyboardEvent> {
+        let is_pressed = |keys: [Key; 2]| {
+            keys.iter().any(|&key| {
+                pyxel.btnp(
+                    key,
+                    Some(VIRTUAL_KEYBOARD_HOLD_FRAMES),
+                    Some(VIRTUAL_KEYBOARD_REPEAT_FRAMES),
+                )
+            })
+        };
+        let dx = is_pressed([KEY_RIGHT, GAMEPAD1_BUTTON_DPAD_RIGHT]) as i32
+            - is_pressed([KEY_LEFT, GAMEPAD1_BUTTON_DPAD_LEFT]) as i32;
+        let dy = is_pressed([KEY_DOWN, GAMEPAD1_BUTTON_DPAD_DOWN]) as i32
+            - is_pressed([KEY_UP, GAMEPAD1_BUTTON_DPAD_UP]) as i32;
+        self.move_cursor(dx, 0);
+        self.move_cursor(0, dy);
+        if is_pressed([KEY_RETURN, GAMEPAD1_BUTTON_A]) {
+            self.select()
+        } else if is_pressed([KEY_BACKSPACE, GAMEPAD1_BUTTON_B]) {
+            Some(VirtualKeyboardEvent::Backspace)
+        } else {
+            None
+        }
+    }
+
+    pub fn draw(&self, canvas: &mut Image, font: SharedImage) {
+        let key_size = VIRTUAL_KEYBOARD_KEY_SIZE as f64;
+        canvas.rect(
+            self.x,
+            self.y,
+            self.width() as f64,
+            self.height() as f64,
+            self.bg_color,
+        );
+        for row in 0..self.num_rows() {
+            let mut col = 0;
+            while col < self.row_width(row) {
+                let (_, span) = self.key_span(col, row);
+                let x = self.x + (col as f64) * key_size + 1.0;
+                let y = self.y + (row as f64) * key_size + 1.0;
+                let width = span as f64 * key_size - 1.0;
+                canvas.rect(x, y, width, key_size - 1.0, self.key_color);
+                if (col, row) == self.cursor {
+                    canvas.rectb(x, y, width, key_size - 1.0, self.cursor_color);
+                }
+                let label = self.key_label(self.key_at(col, row));
+                let label_width = (label.chars().count() as u32 * FONT_WIDTH) as f64;
+                canvas.text(
+                    x + ((width - label_width) / 2.0).round(),
+                    y + ((key_size - 1.0 - FONT_HEIGHT as f64) / 2.0).round(),
+                    &label,
+                    self.text_color,
+                    font.clone(),
+                );
+                col += span;
+            }
+        }
+    }
+
+    fn num_rows(&self) -> u32 {
+        let num_chars = self.char_set.chars().chars().count() as u32;
+        // Every character set is non-empty, plus the row of wide keys
+        (num_chars - 1) / VIRTUAL_KEYBOARD_COLUMNS + 2
+    }
+
+    fn row_width(&self, row: u32) -> u32 {
+        if row == self.num_rows() - 1 {
+            return VIRTUAL_KEYBOARD_COLUMNS;
+        }
+        let num_chars = self.char_set.chars().chars().count() as u32;
+        (num_chars - row * VIRTUAL_KEYBOARD_COLUMNS).min(VIRTUAL_KEYBOARD_COLUMNS)
+    }
+
+    fn clamp_column(&self, col: u32, row: u32) -> u32 {
+        self.key_span(col.min(self.row_width(row) - 1), row).0
+    }
+
+    fn key_span(&self, col: u32, row: u32) -> (u32, u32) {
+        if row == self.num_rows() - 1 {
+            SPECIAL_KEYS
+                .iter()
+                .find(|(_, start, span)| col < start + span)
+                .map_or((col, 1), |(_, start, span)| (*start, *span))
+        } else {
+            (col, 1)
+        }
+    }
+
+    fn key_at(&self, col: u32, row: u32) -> VirtualKey {
+        if row == self.num_rows() - 1 {
+            let (start, _) = self.key_span(col, row);
+            return SPECIAL_KEYS
+                .iter()
+                .find(|(_, key_start, _)| *key_start == start)
+                .map_or(VirtualKey::Done, |(key, _, _)| *key);
+        }
+        self.char_set
+            .chars()
+            .chars()
+            .nth((row * VIRTUAL_KEYBOARD_COLUMNS + col) as usize)
+            .map_or(VirtualKey::Done, VirtualKey::Char)
+    }
+
+    fn key_label(&self, key: VirtualKey) -> String {
+        match key {
+            VirtualKey::Char(' ') => "SPC".to_string(),
+            VirtualKey::Char(c) => c.to_string(),
+            VirtualKey::NextCharSet => self.char_set.next().label().to_string(),
+            VirtualKey::Backspace => "DEL".to_string(),
+            VirtualKey::Done => "END".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::Graphics;
+
+    #[test]
+    fn move_cursor() {
+        let mut keyboard = VirtualKeyboard::new(0.0, 0.0);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char('A'));
+        keyboard.move_cursor(-1, 0);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char('J'));
+        keyboard.move_cursor(0, 2);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char('Z'));
+        keyboard.move_cursor(1, 0);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char('U'));
+        keyboard.move_cursor(0, 1);
+        assert_eq!(keyboard.cursor(), (0, 3));
+        assert_eq!(keyboard.selected_key(), VirtualKey::NextCharSet);
+        keyboard.move_cursor(1, 0);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char(' '));
+        keyboard.move_cursor(1, 0);
+        keyboard.move_cursor(1, 0);
+        assert_eq!(keyboard.cursor(), (8, 3));
+        assert_eq!(keyboard.selected_key(), VirtualKey::Done);
+        keyboard.move_cursor(-1, 0);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Backspace);
+        keyboard.move_cursor(0, 1);
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char('G'));
+        keyboard.move_cursor(0, -2);
+        assert_eq!(keyboard.cursor(), (5, 2));
+        assert_eq!(keyboard.selected_key(), VirtualKey::Char('Z'));
+    }
+
+    #[test]
+    fn select() {
+        let mut keyboard = VirtualKeyboard::new(0.0, 0.0);
+        keyboard.move_cursor(1, 0);
+        assert_eq!(
+            keyboard.select(),
+            Some(VirtualKeyboardEvent::TextInput {
+                text: "B".to_string()
+            })
+        );
+        keyboard.move_cursor(0, -1);
+        for (char_set, text) in [(CharSet::Lower, "u"), (CharSet::Digits, "0")] {
+            assert_eq!(keyboard.selected_key(), VirtualKey::NextCharSet);
+            assert_eq!(keyboard.select(), None);
+            assert_eq!(keyboard.char_set(), char_set);
+            keyboard.move_cursor(0, -1);
+            assert_eq!(
+                keyboard.select(),
+                Some(VirtualKeyboardEvent::TextInput {
+                    text: text.to_string()
+                })
+            );
+            keyboard.move_cursor(0, 1);
+        }
+        assert_eq!(keyboard.cursor(), (0, 1));
+        keyboard.move_cursor(-1, 0);
+        assert_eq!(keyboard.select(), Some(VirtualKeyboardEvent::Done));
+        keyboard.move_cursor(-1, 0);
+        assert_eq!(keyboard.select(), Some(VirtualKeyboardEvent::Backspace));
+        keyboard.set_char_set(CharSet::Upper);
+        assert_eq!(keyboard.cursor(), (6, 3));
+    }
+
+    #[test]
+    fn draw() {
+        let mut keyboard = VirtualKeyboard::new(2.0, 3.0);
+        keyboard.key_color = 5;
+        keyboard.text_color = 7;
+        let canvas = Image::new(120, 60);
+        keyboard.draw(&mut canvas.lock(), Graphics::new_font_image());
+        let mut canvas = canvas.lock();
+        assert_eq!(canvas.pget(2.0, 3.0), keyboard.bg_color);
+        assert_eq!(canvas.pget(3.0, 4.0), keyboard.cursor_color);
+        assert_eq!(canvas.pget(13.0, 4.0), keyboard.key_color);
+        assert!((0..9).any(|i| canvas.pget(13.0 + i as f64, 7.0) == keyboard.text_color));
+        assert_eq!(canvas.pget(103.0, 3.0), 0);
+        assert_eq!(canvas.pget(2.0, 44.0), 0);
+        drop(canvas);
+    }
+}
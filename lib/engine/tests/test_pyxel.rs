@@ -64,9 +64,9 @@ impl App {
             25,
         );
 
-        pyxel.play(0, &[0, 1], None, true);
-        pyxel.play(1, &[2, 3], None, true);
-        pyxel.play(2, &[4], None, true);
+        pyxel.play(0, &[0, 1], None, true).unwrap();
+        pyxel.play(1, &[2, 3], None, true).unwrap();
+        pyxel.play(2, &[4], None, true).unwrap();
 
         let mut app = App { x: 0.0, y: 0.0 };
         pyxel.run(&mut app);
@@ -95,7 +95,7 @@ impl PyxelCallback for App {
         pyxel.rect(self.x + 10.0, 25.0, 15.0, 10.0, 8);
         pyxel.rectb(self.x + 15.0, 45.0, 15.0, 10.0, pyxel::COLOR_WHITE);
 
-        pyxel.blt(0.0, 0.0, 0, 0.0, 0.0, 8.0, 8.0, None);
+        pyxel.blt(0.0, 0.0, 0, 0.0, 0.0, 8.0, 8.0, None, None, None);
 
         pyxel.screen.lock().blt(
             50.0,
@@ -106,6 +106,8 @@ impl PyxelCallback for App {
             100.0,
             100.0,
             None,
+            None,
+            None,
         );
     }
 }
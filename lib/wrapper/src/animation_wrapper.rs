@@ -0,0 +1,102 @@
+use pyo3::prelude::*;
+use pyxel::Animation as PyxelAnimation;
+use pyxel::{Color, NUM_IMAGES};
+
+use crate::instance;
+
+#[pyclass]
+#[derive(Clone)]
+pub struct Animation {
+    pub pyxel_animation: PyxelAnimation,
+}
+
+#[pymethods]
+impl Animation {
+    #[new]
+    pub fn new(img: u32) -> PyResult<Self> {
+        if img >= NUM_IMAGES {
+            value_error!(format!("img must be less than {}", NUM_IMAGES));
+        }
+        Ok(Self {
+            pyxel_animation: PyxelAnimation::new(img),
+        })
+    }
+
+    #[getter]
+    pub fn img(&self) -> u32 {
+        self.pyxel_animation.image
+    }
+
+    #[getter]
+    pub fn colkey(&self) -> Option<Color> {
+        self.pyxel_animation.colkey
+    }
+
+    #[setter]
+    pub fn set_colkey(&mut self, colkey: Option<Color>) {
+        self.pyxel_animation.colkey = colkey;
+    }
+
+    #[getter]
+    pub fn clip(&self) -> Option<String> {
+        self.pyxel_animation.clip().map(str::to_string)
+    }
+
+    #[getter]
+    pub fn frame_index(&self) -> usize {
+        self.pyxel_animation.frame_index()
+    }
+
+    #[pyo3(text_signature = "($self, name, frames, *, loop)")]
+    pub fn add_clip(
+        &mut self,
+        name: &str,
+        frames: Vec<(f64, f64, f64, f64, u32)>,
+        r#loop: Option<bool>,
+    ) {
+        let loop_ = r#loop.unwrap_or(true);
+        self.pyxel_animation.add_clip(name, &frames, loop_);
+    }
+
+    #[pyo3(text_signature = "($self, name, u, v, w, h, count, duration, *, loop)")]
+    pub fn add_grid_clip(
+        &mut self,
+        name: &str,
+        u: f64,
+        v: f64,
+        w: f64,
+        h: f64,
+        count: u32,
+        duration: u32,
+        r#loop: Option<bool>,
+    ) {
+        let loop_ = r#loop.unwrap_or(true);
+        self.pyxel_animation
+            .add_grid_clip(name, u, v, w, h, count, duration, loop_);
+    }
+
+    pub fn play(&mut self, name: &str) {
+        self.pyxel_animation.play(name);
+    }
+
+    pub fn restart(&mut self) {
+        self.pyxel_animation.restart();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.pyxel_animation.is_finished()
+    }
+
+    pub fn update(&mut self) {
+        self.pyxel_animation.update();
+    }
+
+    pub fn draw(&self, x: f64, y: f64, flip_h: Option<bool>) {
+        instance().draw_animation(&self.pyxel_animation, x, y, flip_h.unwrap_or(false));
+    }
+}
+
+pub fn add_animation_class(m: &PyModule) -> PyResult<()> {
+    m.add_class::<Animation>()?;
+    Ok(())
+}
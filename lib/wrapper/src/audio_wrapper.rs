@@ -1,13 +1,34 @@
 use pyo3::prelude::*;
+use pyxel::AudioError as PyxelAudioError;
 
 use crate::channel_wrapper::{wrap_pyxel_channel, Channel};
 use crate::instance;
 use crate::music_wrapper::{wrap_pyxel_music, Music};
+use crate::sample_wrapper::{audio_error, Sample};
 use crate::sound_wrapper::{wrap_pyxel_sound, Sound};
 
+pub fn check_channel(ch: u32) -> PyResult<()> {
+    if ch < instance().num_channels() {
+        Ok(())
+    } else {
+        Err(audio_error(PyxelAudioError::InvalidChannel(ch)))
+    }
+}
+
+#[pyfunction]
+fn channel(ch: u32) -> PyResult<Channel> {
+    check_channel(ch)?;
+    Ok(wrap_pyxel_channel(ch))
+}
+
+#[pyfunction]
+fn num_channels() -> u32 {
+    instance().num_channels()
+}
+
 #[pyfunction]
-fn channel(ch: u32) -> Channel {
-    wrap_pyxel_channel(instance().channel(ch))
+fn set_num_channels(count: u32) {
+    instance().set_num_channels(count);
 }
 
 #[pyfunction]
@@ -21,8 +42,13 @@ fn music(msc: u32) -> Music {
 }
 
 #[pyfunction]
-fn play_pos(ch: u32) -> Option<(u32, u32)> {
-    instance().play_pos(ch)
+fn play_pos(ch: u32) -> PyResult<Option<(u32, u32)>> {
+    instance().play_pos(ch).map_err(audio_error)
+}
+
+#[pyfunction]
+fn elapsed_samples(ch: u32) -> PyResult<u64> {
+    instance().elapsed_samples(ch).map_err(audio_error)
 }
 
 #[pyfunction]
@@ -31,18 +57,26 @@ fn play(ch: u32, snd: &PyAny, tick: Option<u32>, r#loop: Option<bool>) -> PyResu
     type_switch! {
         snd,
         u32, {
-            instance().play1(ch, snd, tick, r#loop.unwrap_or(false));
+            instance()
+                .play1(ch, snd, tick, r#loop.unwrap_or(false))
+                .map_err(audio_error)?;
         },
         Vec<u32>, {
-            instance().play(ch, &snd, tick, r#loop.unwrap_or(false));
+            instance()
+                .play(ch, &snd, tick, r#loop.unwrap_or(false))
+                .map_err(audio_error)?;
         },
         Sound, {
-            instance().channel(ch).lock().play1(snd.pyxel_sound, tick, r#loop.unwrap_or(false));
+            instance()
+                .play_sounds(ch, vec![snd.pyxel_sound.lock().clone()], tick, r#loop.unwrap_or(false))
+                .map_err(audio_error)?;
         },
         Vec<Sound>, {
-            let sounds = snd.iter().map(|snd| snd.pyxel_sound.clone()).collect();
+            let sounds = snd.iter().map(|snd| snd.pyxel_sound.lock().clone()).collect();
 
-            instance().channel(ch).lock().play(sounds, tick, r#loop.unwrap_or(false));
+            instance()
+                .play_sounds(ch, sounds, tick, r#loop.unwrap_or(false))
+                .map_err(audio_error)?;
         }
     }
     Ok(())
@@ -50,26 +84,90 @@ fn play(ch: u32, snd: &PyAny, tick: Option<u32>, r#loop: Option<bool>) -> PyResu
 
 #[pyfunction]
 #[pyo3(text_signature = "(msc, *, tick, loop)")]
-fn playm(msc: u32, tick: Option<u32>, r#loop: Option<bool>) {
-    instance().playm(msc, tick, r#loop.unwrap_or(false));
+fn playm(msc: u32, tick: Option<u32>, r#loop: Option<bool>) -> PyResult<()> {
+    instance()
+        .playm(msc, tick, r#loop.unwrap_or(false))
+        .map_err(audio_error)
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "(ch, sample, *, loop)")]
+fn play_sample(ch: u32, sample: Sample, r#loop: Option<bool>) -> PyResult<()> {
+    instance()
+        .play_sample(ch, &sample.pyxel_sample, r#loop.unwrap_or(false))
+        .map_err(audio_error)
+}
+
+#[pyfunction]
+fn master_volume(vol: f64) {
+    instance().set_master_volume(vol);
 }
 
 #[pyfunction]
-fn stop(ch: Option<u32>) {
+fn audio_stats() -> (u64, u64, u64, u64, f64) {
+    let stats = instance().audio_stats();
+    (
+        stats.num_callbacks,
+        stats.num_lock_waits,
+        stats.num_lock_timeouts,
+        stats.max_fill_us,
+        stats.mean_fill_us,
+    )
+}
+
+#[pyfunction]
+fn audio_devices() -> Vec<String> {
+    instance().audio_devices()
+}
+
+#[pyfunction]
+fn set_audio_device(name: Option<&str>) -> PyResult<()> {
+    instance().set_audio_device(name).map_err(audio_error)
+}
+
+#[pyfunction]
+fn pause_audio() {
+    instance().pause_audio();
+}
+
+#[pyfunction]
+fn resume_audio() {
+    instance().resume_audio();
+}
+
+#[pyfunction]
+fn is_audio_paused() -> bool {
+    instance().is_audio_paused()
+}
+
+#[pyfunction]
+fn stop(ch: Option<u32>) -> PyResult<()> {
     if let Some(ch) = ch {
-        instance().stop(ch);
+        instance().stop(ch).map_err(audio_error)?;
     } else {
         instance().stop0();
     }
+    Ok(())
 }
 
 pub fn add_audio_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(channel, m)?)?;
+    m.add_function(wrap_pyfunction!(num_channels, m)?)?;
+    m.add_function(wrap_pyfunction!(set_num_channels, m)?)?;
     m.add_function(wrap_pyfunction!(sound, m)?)?;
     m.add_function(wrap_pyfunction!(music, m)?)?;
     m.add_function(wrap_pyfunction!(play_pos, m)?)?;
+    m.add_function(wrap_pyfunction!(elapsed_samples, m)?)?;
     m.add_function(wrap_pyfunction!(play, m)?)?;
     m.add_function(wrap_pyfunction!(playm, m)?)?;
+    m.add_function(wrap_pyfunction!(play_sample, m)?)?;
+    m.add_function(wrap_pyfunction!(master_volume, m)?)?;
+    m.add_function(wrap_pyfunction!(audio_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(audio_devices, m)?)?;
+    m.add_function(wrap_pyfunction!(set_audio_device, m)?)?;
+    m.add_function(wrap_pyfunction!(pause_audio, m)?)?;
+    m.add_function(wrap_pyfunction!(resume_audio, m)?)?;
+    m.add_function(wrap_pyfunction!(is_audio_paused, m)?)?;
     m.add_function(wrap_pyfunction!(stop, m)?)?;
     Ok(())
 }
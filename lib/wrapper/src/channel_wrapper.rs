@@ -1,34 +1,94 @@
 use pyo3::prelude::*;
-use pyxel::SharedChannel as PyxelSharedChannel;
 use pyxel::Volume;
 
 use crate::instance;
+use crate::sample_wrapper::audio_error;
 use crate::sound_wrapper::Sound;
 
+// Channels live on the audio thread, so this only keeps the channel number
+// and every change goes through the engine's command queue
 #[pyclass]
 #[derive(Clone)]
 pub struct Channel {
-    pyxel_channel: PyxelSharedChannel,
+    channel_no: u32,
 }
 
-pub fn wrap_pyxel_channel(pyxel_channel: PyxelSharedChannel) -> Channel {
-    Channel { pyxel_channel }
+pub fn wrap_pyxel_channel(channel_no: u32) -> Channel {
+    Channel { channel_no }
 }
 
 #[pymethods]
 impl Channel {
     #[getter]
-    pub fn get_gain(&self) -> Volume {
-        self.pyxel_channel.lock().gain
+    pub fn get_gain(&self) -> PyResult<Volume> {
+        instance()
+            .channel_gain(self.channel_no)
+            .map_err(audio_error)
     }
 
     #[setter]
-    pub fn set_gain(&self, gain: u8) {
-        self.pyxel_channel.lock().gain = gain;
+    pub fn set_gain(&self, gain: u8) -> PyResult<()> {
+        instance()
+            .set_channel_gain(self.channel_no, gain)
+            .map_err(audio_error)
     }
 
-    pub fn play_pos(&self) -> Option<(u32, u32)> {
-        self.pyxel_channel.lock().play_pos()
+    #[getter]
+    pub fn get_volume(&self) -> PyResult<f64> {
+        instance()
+            .channel_volume(self.channel_no)
+            .map_err(audio_error)
+    }
+
+    #[setter]
+    pub fn set_volume(&self, volume: f64) -> PyResult<()> {
+        instance()
+            .set_channel_volume(self.channel_no, volume)
+            .map_err(audio_error)
+    }
+
+    #[getter]
+    pub fn get_pan(&self) -> PyResult<f64> {
+        instance().channel_pan(self.channel_no).map_err(audio_error)
+    }
+
+    #[setter]
+    pub fn set_pan(&self, pan: f64) -> PyResult<()> {
+        instance()
+            .set_channel_pan(self.channel_no, pan)
+            .map_err(audio_error)
+    }
+
+    #[getter]
+    pub fn get_speed(&self) -> PyResult<f64> {
+        instance()
+            .channel_speed(self.channel_no)
+            .map_err(audio_error)
+    }
+
+    #[setter]
+    pub fn set_speed(&self, speed: f64) -> PyResult<()> {
+        instance()
+            .set_channel_speed(self.channel_no, speed)
+            .map_err(audio_error)
+    }
+
+    #[getter]
+    pub fn get_pitch(&self) -> PyResult<f64> {
+        instance()
+            .channel_pitch(self.channel_no)
+            .map_err(audio_error)
+    }
+
+    #[setter]
+    pub fn set_pitch(&self, pitch: f64) -> PyResult<()> {
+        instance()
+            .set_channel_pitch(self.channel_no, pitch)
+            .map_err(audio_error)
+    }
+
+    pub fn play_pos(&self) -> PyResult<Option<(u32, u32)>> {
+        instance().play_pos(self.channel_no).map_err(audio_error)
     }
 
     #[pyo3(text_signature = "($self, snd, *, tick, loop)")]
@@ -37,27 +97,27 @@ impl Channel {
         type_switch! {
             snd,
             u32, {
-                self.pyxel_channel.lock().play1(instance().sound(snd), tick, loop_);
+                instance().play1(self.channel_no, snd, tick, loop_).map_err(audio_error)?;
             },
             Vec<u32>, {
-                let snd = snd.iter().map(|sound_no| instance().sound(*sound_no)).collect();
-
-                self.pyxel_channel.lock().play(snd, tick, loop_);
+                instance().play(self.channel_no, &snd, tick, loop_).map_err(audio_error)?;
             },
             Sound, {
-                self.pyxel_channel.lock().play1(snd.pyxel_sound, tick, loop_);
+                let sounds = vec![snd.pyxel_sound.lock().clone()];
+
+                instance().play_sounds(self.channel_no, sounds, tick, loop_).map_err(audio_error)?;
             },
             Vec<Sound>, {
-                let snd = snd.iter().map(|sound| sound.pyxel_sound.clone()).collect();
+                let sounds = snd.iter().map(|sound| sound.pyxel_sound.lock().clone()).collect();
 
-                self.pyxel_channel.lock().play(snd, tick, loop_);
+                instance().play_sounds(self.channel_no, sounds, tick, loop_).map_err(audio_error)?;
             }
         }
         Ok(())
     }
 
-    pub fn stop(&mut self) {
-        self.pyxel_channel.lock().stop();
+    pub fn stop(&mut self) -> PyResult<()> {
+        instance().stop(self.channel_no).map_err(audio_error)
     }
 }
 
@@ -16,12 +16,31 @@ pub fn add_module_constants(m: &PyModule) -> PyResult<()> {
     add_constant!(RESOURCE_FILE_EXTENSION)?;
     add_constant!(RESOURCE_ARCHIVE_DIRNAME)?;
 
+    add_constant!(DISPLAY_EFFECT_NONE)?;
+    add_constant!(DISPLAY_EFFECT_SCANLINES)?;
+    add_constant!(DISPLAY_EFFECT_GRID)?;
+    add_constant!(SCREEN_FIT_INTEGER)?;
+    add_constant!(SCREEN_FIT_ASPECT)?;
+    add_constant!(SCREEN_FIT_FILL)?;
+    add_constant!(SCREEN_MODE_NEAREST)?;
+    add_constant!(SCREEN_MODE_SHARP_BILINEAR)?;
+    add_constant!(ALIGN_LEFT)?;
+    add_constant!(ALIGN_CENTER)?;
+    add_constant!(ALIGN_RIGHT)?;
+
+    add_constant!(CAPTURE_ACTION_SCREENSHOT)?;
+    add_constant!(CAPTURE_ACTION_RECORDING)?;
+    add_constant!(CAPTURE_ACTION_PERF_MONITOR)?;
+    add_constant!(CAPTURE_ACTION_FULLSCREEN)?;
+
     add_constant!(NUM_COLORS)?;
+    add_constant!(MAX_COLORS)?;
     add_constant!(NUM_IMAGES)?;
     add_constant!(IMAGE_SIZE)?;
     add_constant!(NUM_TILEMAPS)?;
     add_constant!(TILEMAP_SIZE)?;
     add_constant!(TILE_SIZE)?;
+    add_constant!(NUM_LAYERS)?;
     add_constant!(COLOR_BLACK)?;
     add_constant!(COLOR_NAVY)?;
     add_constant!(COLOR_PURPLE)?;
@@ -48,6 +67,9 @@ pub fn add_module_constants(m: &PyModule) -> PyResult<()> {
     add_constant!(TONE_SQUARE)?;
     add_constant!(TONE_PULSE)?;
     add_constant!(TONE_NOISE)?;
+    add_constant!(TONE_SAWTOOTH)?;
+    add_constant!(TONE_NARROW_PULSE)?;
+    add_constant!(TONE_SHORT_NOISE)?;
     add_constant!(EFFECT_NONE)?;
     add_constant!(EFFECT_SLIDE)?;
     add_constant!(EFFECT_VIBRATO)?;
@@ -350,5 +372,49 @@ pub fn add_module_constants(m: &PyModule) -> PyResult<()> {
     add_constant!(GAMEPAD2_BUTTON_DPAD_LEFT)?;
     add_constant!(GAMEPAD2_BUTTON_DPAD_RIGHT)?;
 
+    add_constant!(GAMEPAD3_AXIS_LEFTX)?;
+    add_constant!(GAMEPAD3_AXIS_LEFTY)?;
+    add_constant!(GAMEPAD3_AXIS_RIGHTX)?;
+    add_constant!(GAMEPAD3_AXIS_RIGHTY)?;
+    add_constant!(GAMEPAD3_AXIS_TRIGGERLEFT)?;
+    add_constant!(GAMEPAD3_AXIS_TRIGGERRIGHT)?;
+    add_constant!(GAMEPAD3_BUTTON_A)?;
+    add_constant!(GAMEPAD3_BUTTON_B)?;
+    add_constant!(GAMEPAD3_BUTTON_X)?;
+    add_constant!(GAMEPAD3_BUTTON_Y)?;
+    add_constant!(GAMEPAD3_BUTTON_BACK)?;
+    add_constant!(GAMEPAD3_BUTTON_GUIDE)?;
+    add_constant!(GAMEPAD3_BUTTON_START)?;
+    add_constant!(GAMEPAD3_BUTTON_LEFTSTICK)?;
+    add_constant!(GAMEPAD3_BUTTON_RIGHTSTICK)?;
+    add_constant!(GAMEPAD3_BUTTON_LEFTSHOULDER)?;
+    add_constant!(GAMEPAD3_BUTTON_RIGHTSHOULDER)?;
+    add_constant!(GAMEPAD3_BUTTON_DPAD_UP)?;
+    add_constant!(GAMEPAD3_BUTTON_DPAD_DOWN)?;
+    add_constant!(GAMEPAD3_BUTTON_DPAD_LEFT)?;
+    add_constant!(GAMEPAD3_BUTTON_DPAD_RIGHT)?;
+
+    add_constant!(GAMEPAD4_AXIS_LEFTX)?;
+    add_constant!(GAMEPAD4_AXIS_LEFTY)?;
+    add_constant!(GAMEPAD4_AXIS_RIGHTX)?;
+    add_constant!(GAMEPAD4_AXIS_RIGHTY)?;
+    add_constant!(GAMEPAD4_AXIS_TRIGGERLEFT)?;
+    add_constant!(GAMEPAD4_AXIS_TRIGGERRIGHT)?;
+    add_constant!(GAMEPAD4_BUTTON_A)?;
+    add_constant!(GAMEPAD4_BUTTON_B)?;
+    add_constant!(GAMEPAD4_BUTTON_X)?;
+    add_constant!(GAMEPAD4_BUTTON_Y)?;
+    add_constant!(GAMEPAD4_BUTTON_BACK)?;
+    add_constant!(GAMEPAD4_BUTTON_GUIDE)?;
+    add_constant!(GAMEPAD4_BUTTON_START)?;
+    add_constant!(GAMEPAD4_BUTTON_LEFTSTICK)?;
+    add_constant!(GAMEPAD4_BUTTON_RIGHTSTICK)?;
+    add_constant!(GAMEPAD4_BUTTON_LEFTSHOULDER)?;
+    add_constant!(GAMEPAD4_BUTTON_RIGHTSHOULDER)?;
+    add_constant!(GAMEPAD4_BUTTON_DPAD_UP)?;
+    add_constant!(GAMEPAD4_BUTTON_DPAD_DOWN)?;
+    add_constant!(GAMEPAD4_BUTTON_DPAD_LEFT)?;
+    add_constant!(GAMEPAD4_BUTTON_DPAD_RIGHT)?;
+
     Ok(())
 }
@@ -0,0 +1,72 @@
+use pyo3::prelude::*;
+use pyxel::Font as PyxelFont;
+use pyxel::SharedFont as PyxelSharedFont;
+use pyxel::{Color, SharedImage as PyxelSharedImage};
+
+use crate::image_wrapper::{wrap_pyxel_image, Image};
+use crate::instance;
+
+#[pyclass]
+#[derive(Clone)]
+pub struct Font {
+    pub pyxel_font: PyxelSharedFont,
+}
+
+pub fn wrap_pyxel_font(pyxel_font: PyxelSharedFont) -> Font {
+    Font { pyxel_font }
+}
+
+#[pymethods]
+impl Font {
+    #[new]
+    pub fn new(
+        img: &PyAny,
+        glyph_w: u32,
+        glyph_h: u32,
+        first_char: char,
+        widths: Option<Vec<u32>>,
+    ) -> PyResult<Self> {
+        if glyph_w == 0 || glyph_h == 0 {
+            value_error!("glyph_w and glyph_h must be positive");
+        }
+        let img: PyxelSharedImage = type_switch! {
+            img,
+            u32, {
+                instance().image(img)
+            },
+            Image, {
+                img.pyxel_image
+            }
+        };
+        let pyxel_font = if let Some(widths) = widths {
+            PyxelFont::with_widths(img, glyph_w, glyph_h, first_char, &widths)
+        } else {
+            PyxelFont::from_image(img, glyph_w, glyph_h, first_char)
+        };
+        Ok(wrap_pyxel_font(pyxel_font))
+    }
+
+    #[getter]
+    pub fn image(&self) -> Image {
+        wrap_pyxel_image(self.pyxel_font.lock().image.clone())
+    }
+
+    #[getter]
+    pub fn colkey(&self) -> Color {
+        self.pyxel_font.lock().colkey
+    }
+
+    #[setter]
+    pub fn set_colkey(&self, colkey: Color) {
+        self.pyxel_font.lock().colkey = colkey;
+    }
+
+    pub fn text_width(&self, s: &str) -> u32 {
+        self.pyxel_font.lock().text_width(s)
+    }
+}
+
+pub fn add_font_class(m: &PyModule) -> PyResult<()> {
+    m.add_class::<Font>()?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use pyo3::prelude::*;
-use pyxel::Color;
+use pyxel::{Align, Color, Rgb8, ALIGN_CENTER, ALIGN_LEFT, ALIGN_RIGHT, NUM_IMAGES, NUM_LAYERS};
 
+use crate::font_wrapper::Font;
 use crate::image_wrapper::{wrap_pyxel_image, Image};
 use crate::instance;
 use crate::tilemap_wrapper::{wrap_pyxel_tilemap, Tilemap};
@@ -15,6 +16,68 @@ fn tilemap(tm: u32) -> Tilemap {
     wrap_pyxel_tilemap(instance().tilemap(tm))
 }
 
+#[pyfunction]
+fn set_draw_target(img: Option<u32>) -> PyResult<()> {
+    if img.is_some_and(|img| img >= NUM_IMAGES) {
+        value_error!(format!("img must be less than {}", NUM_IMAGES));
+    }
+    instance().set_draw_target(img);
+    Ok(())
+}
+
+#[pyfunction]
+fn screen_to_image(img: u32) -> PyResult<()> {
+    if img >= NUM_IMAGES {
+        value_error!(format!("img must be less than {}", NUM_IMAGES));
+    }
+    instance().screen_to_image(img);
+    Ok(())
+}
+
+#[pyfunction]
+fn layer(layer: u32) -> PyResult<Image> {
+    if layer >= NUM_LAYERS {
+        value_error!(format!("layer must be less than {}", NUM_LAYERS));
+    }
+    Ok(wrap_pyxel_image(instance().layer(layer)))
+}
+
+#[pyfunction]
+fn layer_colkey(layer: u32, col: Color) -> PyResult<()> {
+    if layer >= NUM_LAYERS {
+        value_error!(format!("layer must be less than {}", NUM_LAYERS));
+    }
+    instance().layer_colkey(layer, col);
+    Ok(())
+}
+
+#[pyfunction]
+fn layer_scroll(layer: u32, x: i32, y: i32) -> PyResult<()> {
+    if layer >= NUM_LAYERS {
+        value_error!(format!("layer must be less than {}", NUM_LAYERS));
+    }
+    instance().layer_scroll(layer, x, y);
+    Ok(())
+}
+
+#[pyfunction]
+fn pick(x: f64, y: f64) -> Option<Color> {
+    instance().pick(x, y)
+}
+
+#[pyfunction]
+fn pick_layer(layer: u32, x: f64, y: f64) -> PyResult<Option<Color>> {
+    if layer >= NUM_LAYERS {
+        value_error!(format!("layer must be less than {}", NUM_LAYERS));
+    }
+    Ok(instance().pick_layer(layer, x, y))
+}
+
+#[pyfunction]
+fn screen_colkey(col: Color) {
+    instance().screen_colkey(col);
+}
+
 #[pyfunction]
 fn clip(x: Option<f64>, y: Option<f64>, w: Option<f64>, h: Option<f64>) -> PyResult<()> {
     if let (Some(x), Some(y), Some(w), Some(h)) = (x, y, w, h) {
@@ -51,6 +114,60 @@ fn pal(col1: Option<Color>, col2: Option<Color>) -> PyResult<()> {
     Ok(())
 }
 
+#[pyfunction]
+fn pal_cycle(start: Option<Color>, end: Option<Color>, shift: Option<i32>) -> PyResult<()> {
+    if let (Some(start), Some(end), Some(shift)) = (start, end, shift) {
+        instance().pal_cycle(start..end, shift);
+    } else if (start, end, shift) == (None, None, None) {
+        instance().pal_cycle0();
+    } else {
+        type_error!("pal_cycle() takes 0 or 3 arguments");
+    }
+    Ok(())
+}
+
+#[pyfunction]
+fn display_brightness(factor: f64) {
+    instance().display_brightness(factor);
+}
+
+#[pyfunction]
+fn display_tint(rgb: Rgb8, strength: f64) {
+    instance().display_tint(rgb, strength);
+}
+
+#[pyfunction]
+fn fade_out(frames: u32, rgb: Option<Rgb8>) {
+    instance().fade_out(frames, rgb.unwrap_or(0));
+}
+
+#[pyfunction]
+fn fade_in(frames: u32) {
+    instance().fade_in(frames);
+}
+
+#[pyfunction]
+fn is_transition_done() -> bool {
+    instance().is_transition_done()
+}
+
+#[pyfunction]
+fn fillp(pattern: Option<u16>, col1: Option<Color>, col2: Option<Color>) -> PyResult<()> {
+    match (pattern, col1, col2) {
+        (Some(pattern), None, None) => {
+            instance().fillp0();
+            instance().fillp(pattern);
+        }
+        (Some(pattern), Some(col1), Some(col2)) => {
+            instance().fillp(pattern);
+            instance().fillp_colors(col1, col2);
+        }
+        (None, None, None) => instance().fillp0(),
+        _ => type_error!("fillp() takes 0, 1, or 3 arguments"),
+    }
+    Ok(())
+}
+
 #[pyfunction]
 fn cls(col: Color) {
     instance().cls(col);
@@ -66,11 +183,36 @@ fn pset(x: f64, y: f64, col: Color) {
     instance().pset(x, y, col);
 }
 
+#[pyfunction]
+fn pset_slice(points: Vec<(i32, i32)>, col: Color) {
+    instance().pset_slice(&points, col);
+}
+
+#[pyfunction]
+fn pset_colored(points: Vec<(i32, i32, Color)>) {
+    instance().pset_colored(&points);
+}
+
 #[pyfunction]
 fn line(x1: f64, y1: f64, x2: f64, y2: f64, col: Color) {
     instance().line(x1, y1, x2, y2, col);
 }
 
+#[pyfunction]
+fn line_w(x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, col: Color) {
+    instance().line_w(x1, y1, x2, y2, thickness, col);
+}
+
+#[pyfunction]
+fn polyline(points: Vec<(f64, f64)>, col: Color) {
+    instance().polyline(&points, col);
+}
+
+#[pyfunction]
+fn bezier(x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, col: Color) {
+    instance().bezier(x1, y1, cx, cy, x2, y2, col);
+}
+
 #[pyfunction]
 fn rect(x: f64, y: f64, w: f64, h: f64, col: Color) {
     instance().rect(x, y, w, h, col);
@@ -81,6 +223,11 @@ fn rectb(x: f64, y: f64, w: f64, h: f64, col: Color) {
     instance().rectb(x, y, w, h, col);
 }
 
+#[pyfunction]
+fn rectb_w(x: f64, y: f64, w: f64, h: f64, thickness: f64, col: Color) {
+    instance().rectb_w(x, y, w, h, thickness, col);
+}
+
 #[pyfunction]
 fn circ(x: f64, y: f64, r: f64, col: Color) {
     instance().circ(x, y, r, col);
@@ -117,6 +264,7 @@ fn fill(x: f64, y: f64, col: Color) {
 }
 
 #[pyfunction]
+#[pyo3(text_signature = "(x, y, img, u, v, w, h, colkey, *, rotate, scale)")]
 fn blt(
     x: f64,
     y: f64,
@@ -126,14 +274,48 @@ fn blt(
     w: f64,
     h: f64,
     colkey: Option<Color>,
+    rotate: Option<f64>,
+    scale: Option<f64>,
 ) -> PyResult<()> {
     type_switch! {
         img,
         u32, {
-            instance().blt(x, y, img, u, v, w, h, colkey);
+            instance().blt(x, y, img, u, v, w, h, colkey, rotate, scale);
         },
         Image, {
-            instance().screen.lock().blt(x, y, img.pyxel_image, u, v, w, h, colkey);
+            instance()
+                .draw_target()
+                .lock()
+                .blt(x, y, img.pyxel_image, u, v, w, h, colkey, rotate, scale);
+        }
+    }
+    Ok(())
+}
+
+#[pyfunction]
+fn blt9(
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    img: &PyAny,
+    u: f64,
+    v: f64,
+    src_w: f64,
+    src_h: f64,
+    border: u32,
+    colkey: Option<Color>,
+) -> PyResult<()> {
+    type_switch! {
+        img,
+        u32, {
+            instance().blt9(x, y, w, h, img, u, v, src_w, src_h, border, colkey);
+        },
+        Image, {
+            instance()
+                .draw_target()
+                .lock()
+                .blt9(x, y, w, h, img.pyxel_image, u, v, src_w, src_h, border, colkey);
         }
     }
     Ok(())
@@ -156,29 +338,91 @@ fn bltm(
             instance().bltm(x, y, tm, u, v, w, h, colkey);
         },
         Tilemap, {
-            instance().screen.lock().bltm(x, y, tm.pyxel_tilemap, u, v, w, h, colkey);
+            instance()
+                .draw_target()
+                .lock()
+                .bltm(x, y, tm.pyxel_tilemap, u, v, w, h, colkey);
         }
     }
     Ok(())
 }
 
 #[pyfunction]
-fn text(x: f64, y: f64, s: &str, col: Color) {
-    instance().text(x, y, s, col);
+fn text(x: f64, y: f64, s: &str, col: Color) -> u32 {
+    instance().text(x, y, s, col)
+}
+
+#[pyfunction]
+fn text_with(x: f64, y: f64, s: &str, col: Color, font: Font) -> u32 {
+    instance().text_with(x, y, s, col, font.pyxel_font)
+}
+
+fn to_align(align: Option<u32>) -> PyResult<Align> {
+    Ok(match align.unwrap_or(ALIGN_LEFT) {
+        ALIGN_LEFT => Align::Left,
+        ALIGN_CENTER => Align::Center,
+        ALIGN_RIGHT => Align::Right,
+        align => value_error!(format!("invalid text alignment {}", align)),
+    })
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "(x, y, w, s, col, *, align, font)")]
+fn text_box(
+    x: f64,
+    y: f64,
+    w: u32,
+    s: &str,
+    col: Color,
+    align: Option<u32>,
+    font: Option<Font>,
+) -> PyResult<u32> {
+    let align = to_align(align)?;
+    Ok(instance().text_box(x, y, w, s, col, align, font.map(|font| font.pyxel_font)))
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "(w, s, *, font)")]
+fn measure_text_box(w: u32, s: &str, font: Option<Font>) -> (u32, u32) {
+    match font {
+        Some(font) => pyxel::measure_text_box(w, s, Some(&font.pyxel_font.lock())),
+        None => pyxel::measure_text_box(w, s, None),
+    }
 }
 
 pub fn add_graphics_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(image, m)?)?;
     m.add_function(wrap_pyfunction!(tilemap, m)?)?;
+    m.add_function(wrap_pyfunction!(set_draw_target, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_to_image, m)?)?;
+    m.add_function(wrap_pyfunction!(layer, m)?)?;
+    m.add_function(wrap_pyfunction!(layer_colkey, m)?)?;
+    m.add_function(wrap_pyfunction!(layer_scroll, m)?)?;
+    m.add_function(wrap_pyfunction!(pick, m)?)?;
+    m.add_function(wrap_pyfunction!(pick_layer, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_colkey, m)?)?;
     m.add_function(wrap_pyfunction!(clip, m)?)?;
     m.add_function(wrap_pyfunction!(camera, m)?)?;
     m.add_function(wrap_pyfunction!(pal, m)?)?;
+    m.add_function(wrap_pyfunction!(pal_cycle, m)?)?;
+    m.add_function(wrap_pyfunction!(display_brightness, m)?)?;
+    m.add_function(wrap_pyfunction!(display_tint, m)?)?;
+    m.add_function(wrap_pyfunction!(fade_out, m)?)?;
+    m.add_function(wrap_pyfunction!(fade_in, m)?)?;
+    m.add_function(wrap_pyfunction!(is_transition_done, m)?)?;
+    m.add_function(wrap_pyfunction!(fillp, m)?)?;
     m.add_function(wrap_pyfunction!(cls, m)?)?;
     m.add_function(wrap_pyfunction!(pget, m)?)?;
     m.add_function(wrap_pyfunction!(pset, m)?)?;
+    m.add_function(wrap_pyfunction!(pset_slice, m)?)?;
+    m.add_function(wrap_pyfunction!(pset_colored, m)?)?;
     m.add_function(wrap_pyfunction!(line, m)?)?;
+    m.add_function(wrap_pyfunction!(line_w, m)?)?;
+    m.add_function(wrap_pyfunction!(polyline, m)?)?;
+    m.add_function(wrap_pyfunction!(bezier, m)?)?;
     m.add_function(wrap_pyfunction!(rect, m)?)?;
     m.add_function(wrap_pyfunction!(rectb, m)?)?;
+    m.add_function(wrap_pyfunction!(rectb_w, m)?)?;
     m.add_function(wrap_pyfunction!(circ, m)?)?;
     m.add_function(wrap_pyfunction!(circb, m)?)?;
     m.add_function(wrap_pyfunction!(elli, m)?)?;
@@ -187,7 +431,11 @@ pub fn add_graphics_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(trib, m)?)?;
     m.add_function(wrap_pyfunction!(fill, m)?)?;
     m.add_function(wrap_pyfunction!(blt, m)?)?;
+    m.add_function(wrap_pyfunction!(blt9, m)?)?;
     m.add_function(wrap_pyfunction!(bltm, m)?)?;
     m.add_function(wrap_pyfunction!(text, m)?)?;
+    m.add_function(wrap_pyfunction!(text_with, m)?)?;
+    m.add_function(wrap_pyfunction!(text_box, m)?)?;
+    m.add_function(wrap_pyfunction!(measure_text_box, m)?)?;
     Ok(())
 }
@@ -1,8 +1,11 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
 use pyo3::prelude::*;
 use pyxel::Image as PyxelImage;
+use pyxel::ImageError as PyxelImageError;
 use pyxel::SharedImage as PyxelSharedImage;
 use pyxel::{Color, DEFAULT_COLORS};
 
+use crate::font_wrapper::Font;
 use crate::tilemap_wrapper::Tilemap;
 use crate::{instance, instance_exists};
 
@@ -16,6 +19,15 @@ pub fn wrap_pyxel_image(pyxel_image: PyxelSharedImage) -> Image {
     Image { pyxel_image }
 }
 
+pub fn image_error(err: PyxelImageError) -> PyErr {
+    match err {
+        PyxelImageError::TooLarge(..)
+        | PyxelImageError::InvalidScale(_)
+        | PyxelImageError::ScreenRotation(..) => PyValueError::new_err(err.to_string()),
+        _ => PyIOError::new_err(err.to_string()),
+    }
+}
+
 #[pymethods]
 impl Image {
     #[new]
@@ -24,13 +36,15 @@ impl Image {
     }
 
     #[staticmethod]
-    pub fn from_image(filename: &str) -> Self {
+    pub fn from_image(filename: &str) -> PyResult<Self> {
         let colors = if instance_exists() {
-            &instance().colors
+            instance().palette.colors()
         } else {
             &DEFAULT_COLORS
         };
-        wrap_pyxel_image(PyxelImage::from_image(filename, colors))
+        PyxelImage::try_from_image(filename, colors)
+            .map(wrap_pyxel_image)
+            .map_err(image_error)
     }
 
     #[getter]
@@ -47,16 +61,18 @@ impl Image {
         self.pyxel_image.lock().set(x, y, &data);
     }
 
-    pub fn load(&self, x: i32, y: i32, filename: &str) {
+    pub fn load(&self, x: i32, y: i32, filename: &str) -> PyResult<()> {
         self.pyxel_image
             .lock()
-            .load(x, y, filename, &instance().colors);
+            .load(x, y, filename, instance().palette.colors())
+            .map_err(image_error)
     }
 
-    pub fn save(&self, filename: &str, scale: u32) {
+    pub fn save(&self, filename: &str, scale: u32) -> PyResult<()> {
         self.pyxel_image
             .lock()
-            .save(filename, &instance().colors, scale);
+            .save(filename, instance().palette.colors(), scale)
+            .map_err(image_error)
     }
 
     pub fn clip(
@@ -87,6 +103,72 @@ impl Image {
         Ok(())
     }
 
+    pub fn pal(&self, col1: Option<Color>, col2: Option<Color>) -> PyResult<()> {
+        if let (Some(col1), Some(col2)) = (col1, col2) {
+            self.pyxel_image.lock().pal(col1, col2);
+        } else if (col1, col2) == (None, None) {
+            self.pyxel_image.lock().pal0();
+        } else {
+            type_error!("pal() takes 0 or 2 arguments");
+        }
+        Ok(())
+    }
+
+    pub fn fillp(
+        &self,
+        pattern: Option<u16>,
+        col1: Option<Color>,
+        col2: Option<Color>,
+    ) -> PyResult<()> {
+        match (pattern, col1, col2) {
+            (Some(pattern), None, None) => {
+                self.pyxel_image.lock().fillp0();
+                self.pyxel_image.lock().fillp(pattern);
+            }
+            (Some(pattern), Some(col1), Some(col2)) => {
+                self.pyxel_image.lock().fillp(pattern);
+                self.pyxel_image.lock().fillp_colors(col1, col2);
+            }
+            (None, None, None) => self.pyxel_image.lock().fillp0(),
+            _ => type_error!("fillp() takes 0, 1, or 3 arguments"),
+        }
+        Ok(())
+    }
+
+    pub fn flip_horizontal(&self) {
+        self.pyxel_image.lock().flip_horizontal();
+    }
+
+    pub fn flip_vertical(&self) {
+        self.pyxel_image.lock().flip_vertical();
+    }
+
+    pub fn rotate90(&self, times: u32) -> PyResult<()> {
+        if instance_exists() {
+            instance()
+                .rotate90(&self.pyxel_image, times)
+                .map_err(image_error)
+        } else {
+            self.pyxel_image.lock().rotate90(times);
+            Ok(())
+        }
+    }
+
+    pub fn remap_colors(&self, map: Vec<Color>) {
+        self.pyxel_image.lock().remap_colors(&map);
+    }
+
+    pub fn find_color_bounds(&self, col: Color) -> Option<(i32, i32, u32, u32)> {
+        self.pyxel_image
+            .lock()
+            .find_color_bounds(col)
+            .map(|rect| (rect.left(), rect.top(), rect.width(), rect.height()))
+    }
+
+    pub fn histogram(&self) -> Vec<u32> {
+        self.pyxel_image.lock().histogram().to_vec()
+    }
+
     pub fn cls(&self, col: Color) {
         self.pyxel_image.lock().cls(col);
     }
@@ -99,10 +181,32 @@ impl Image {
         self.pyxel_image.lock().pset(x, y, col);
     }
 
+    pub fn pset_slice(&self, points: Vec<(i32, i32)>, col: Color) {
+        self.pyxel_image.lock().pset_slice(&points, col);
+    }
+
+    pub fn pset_colored(&self, points: Vec<(i32, i32, Color)>) {
+        self.pyxel_image.lock().pset_colored(&points);
+    }
+
     pub fn line(&self, x1: f64, y1: f64, x2: f64, y2: f64, col: Color) {
         self.pyxel_image.lock().line(x1, y1, x2, y2, col);
     }
 
+    pub fn line_w(&self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, col: Color) {
+        self.pyxel_image
+            .lock()
+            .line_w(x1, y1, x2, y2, thickness, col);
+    }
+
+    pub fn polyline(&self, points: Vec<(f64, f64)>, col: Color) {
+        self.pyxel_image.lock().polyline(&points, col);
+    }
+
+    pub fn bezier(&self, x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, col: Color) {
+        self.pyxel_image.lock().bezier(x1, y1, cx, cy, x2, y2, col);
+    }
+
     pub fn rect(&self, x: f64, y: f64, w: f64, h: f64, col: Color) {
         self.pyxel_image.lock().rect(x, y, w, h, col);
     }
@@ -111,6 +215,10 @@ impl Image {
         self.pyxel_image.lock().rectb(x, y, w, h, col);
     }
 
+    pub fn rectb_w(&self, x: f64, y: f64, w: f64, h: f64, thickness: f64, col: Color) {
+        self.pyxel_image.lock().rectb_w(x, y, w, h, thickness, col);
+    }
+
     pub fn circ(&self, x: f64, y: f64, r: f64, col: Color) {
         self.pyxel_image.lock().circ(x, y, r, col);
     }
@@ -139,6 +247,7 @@ impl Image {
         self.pyxel_image.lock().fill(x, y, col);
     }
 
+    #[pyo3(text_signature = "($self, x, y, img, u, v, w, h, colkey, *, rotate, scale)")]
     pub fn blt(
         &self,
         x: f64,
@@ -149,14 +258,92 @@ impl Image {
         w: f64,
         h: f64,
         colkey: Option<Color>,
+        rotate: Option<f64>,
+        scale: Option<f64>,
     ) -> PyResult<()> {
         type_switch! {
             img,
             u32, {
-                self.pyxel_image.lock().blt(x, y, instance().image(img), u, v, w, h, colkey);
+                self.pyxel_image.lock().blt(
+                    x,
+                    y,
+                    instance().image(img),
+                    u,
+                    v,
+                    w,
+                    h,
+                    colkey,
+                    rotate,
+                    scale,
+                );
             },
             Image, {
-                self.pyxel_image.lock().blt(x, y, img.pyxel_image, u, v, w, h, colkey);
+                self.pyxel_image
+                    .lock()
+                    .blt(x, y, img.pyxel_image, u, v, w, h, colkey, rotate, scale);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn blt9(
+        &self,
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+        img: &PyAny,
+        u: f64,
+        v: f64,
+        src_w: f64,
+        src_h: f64,
+        border: u32,
+        colkey: Option<Color>,
+    ) -> PyResult<()> {
+        type_switch! {
+            img,
+            u32, {
+                self.pyxel_image.lock().blt9(
+                    x,
+                    y,
+                    w,
+                    h,
+                    instance().image(img),
+                    u,
+                    v,
+                    src_w,
+                    src_h,
+                    border,
+                    colkey,
+                );
+            },
+            Image, {
+                self.pyxel_image
+                    .lock()
+                    .blt9(x, y, w, h, img.pyxel_image, u, v, src_w, src_h, border, colkey);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn copy(
+        &self,
+        x: f64,
+        y: f64,
+        img: &PyAny,
+        u: f64,
+        v: f64,
+        w: f64,
+        h: f64,
+        colkey: Option<Color>,
+    ) -> PyResult<()> {
+        type_switch! {
+            img,
+            u32, {
+                self.pyxel_image.lock().copy(x, y, instance().image(img), u, v, w, h, colkey);
+            },
+            Image, {
+                self.pyxel_image.lock().copy(x, y, img.pyxel_image, u, v, w, h, colkey);
             }
         }
         Ok(())
@@ -185,13 +372,19 @@ impl Image {
         Ok(())
     }
 
-    pub fn text(&self, x: f64, y: f64, s: &str, col: Color, font: Option<Self>) {
+    pub fn text_with(&self, x: f64, y: f64, s: &str, col: Color, font: Font) -> u32 {
+        self.pyxel_image
+            .lock()
+            .text_with(x, y, s, col, font.pyxel_font)
+    }
+
+    pub fn text(&self, x: f64, y: f64, s: &str, col: Color, font: Option<Self>) -> u32 {
         if let Some(font) = font {
-            self.pyxel_image.lock().text(x, y, s, col, font.pyxel_image);
+            self.pyxel_image.lock().text(x, y, s, col, font.pyxel_image)
         } else {
             self.pyxel_image
                 .lock()
-                .text(x, y, s, col, instance().font.clone());
+                .text(x, y, s, col, instance().font.clone())
         }
     }
 }
@@ -1,5 +1,6 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
 use pyo3::prelude::*;
-use pyxel::{Key, KeyValue};
+use pyxel::{Binding, InputRecordError, Key, KeyValue};
 
 use crate::instance;
 
@@ -24,6 +25,16 @@ fn btnv(key: Key) -> KeyValue {
     instance().btnv(key)
 }
 
+#[pyfunction]
+fn key_name(key: Key) -> Option<&'static str> {
+    pyxel::key_name(key)
+}
+
+#[pyfunction]
+fn key_from_name(name: &str) -> Option<Key> {
+    pyxel::key_from_name(name)
+}
+
 #[pyfunction]
 fn mouse(visible: bool) {
     instance().mouse(visible);
@@ -39,19 +50,155 @@ pub fn set_btnv(key: Key, val: f64) {
     instance().set_btnv(key, val);
 }
 
+#[pyfunction]
+fn start_text_input() {
+    instance().start_text_input();
+}
+
+#[pyfunction]
+fn stop_text_input() {
+    instance().stop_text_input();
+}
+
+#[pyfunction]
+fn set_text_input_rect(x: f64, y: f64, w: f64, h: f64) {
+    instance().set_text_input_rect(x, y, w, h);
+}
+
 #[pyfunction]
 pub fn set_mouse_pos(x: f64, y: f64) {
     instance().set_mouse_pos(x, y);
 }
 
+#[pyfunction]
+fn mouse_on_screen() -> bool {
+    instance().mouse_on_screen()
+}
+
+#[pyfunction]
+fn mouse_window_pos() -> (i32, i32) {
+    instance().mouse_window_pos()
+}
+
+#[pyfunction]
+fn flush_events() {
+    instance().flush_events();
+}
+
+#[pyfunction]
+fn set_coalesce_mouse_motion(enabled: bool) {
+    instance().set_coalesce_mouse_motion(enabled);
+}
+
+#[pyfunction]
+fn last_press_latency_us() -> Option<u64> {
+    instance().last_press_latency_us()
+}
+
+#[pyfunction]
+fn bind(action: &str, binding: &str) -> PyResult<()> {
+    match Binding::from_name(binding) {
+        Some(binding) => instance().bind(action, binding),
+        None => value_error!(format!("Invalid binding '{}'", binding)),
+    }
+    Ok(())
+}
+
+#[pyfunction]
+fn unbind(action: &str) {
+    instance().action_map_mut().unbind(action);
+}
+
+#[pyfunction]
+fn action_down(action: &str) -> bool {
+    instance().action_down(action)
+}
+
+#[pyfunction]
+fn action_pressed(action: &str) -> bool {
+    instance().action_pressed(action)
+}
+
+#[pyfunction]
+fn action_released(action: &str) -> bool {
+    instance().action_released(action)
+}
+
+#[pyfunction]
+fn action_value(action: &str) -> f32 {
+    instance().action_value(action)
+}
+
+#[pyfunction]
+fn capture_next_binding() -> Option<String> {
+    instance()
+        .action_map_mut()
+        .capture_next_binding()
+        .map(|binding| binding.to_string())
+}
+
+fn input_record_error(err: InputRecordError) -> PyErr {
+    match err {
+        InputRecordError::OpenFile(_) | InputRecordError::WriteFile(_) => {
+            PyIOError::new_err(err.to_string())
+        }
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+#[pyfunction]
+fn record_input(filename: &str) -> PyResult<()> {
+    instance()
+        .try_record_input(filename)
+        .map_err(input_record_error)
+}
+
+#[pyfunction]
+fn replay_input(filename: &str) -> PyResult<()> {
+    instance()
+        .try_replay_input(filename)
+        .map_err(input_record_error)
+}
+
+#[pyfunction]
+fn stop_input_record() {
+    instance().stop_input_record();
+}
+
+#[pyfunction]
+fn is_replaying_input() -> bool {
+    instance().is_replaying_input()
+}
+
 pub fn add_input_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(btn, m)?)?;
     m.add_function(wrap_pyfunction!(btnp, m)?)?;
     m.add_function(wrap_pyfunction!(btnr, m)?)?;
     m.add_function(wrap_pyfunction!(btnv, m)?)?;
+    m.add_function(wrap_pyfunction!(key_name, m)?)?;
+    m.add_function(wrap_pyfunction!(key_from_name, m)?)?;
     m.add_function(wrap_pyfunction!(mouse, m)?)?;
     m.add_function(wrap_pyfunction!(set_btn, m)?)?;
     m.add_function(wrap_pyfunction!(set_btnv, m)?)?;
     m.add_function(wrap_pyfunction!(set_mouse_pos, m)?)?;
+    m.add_function(wrap_pyfunction!(mouse_on_screen, m)?)?;
+    m.add_function(wrap_pyfunction!(mouse_window_pos, m)?)?;
+    m.add_function(wrap_pyfunction!(flush_events, m)?)?;
+    m.add_function(wrap_pyfunction!(set_coalesce_mouse_motion, m)?)?;
+    m.add_function(wrap_pyfunction!(last_press_latency_us, m)?)?;
+    m.add_function(wrap_pyfunction!(bind, m)?)?;
+    m.add_function(wrap_pyfunction!(unbind, m)?)?;
+    m.add_function(wrap_pyfunction!(action_down, m)?)?;
+    m.add_function(wrap_pyfunction!(action_pressed, m)?)?;
+    m.add_function(wrap_pyfunction!(action_released, m)?)?;
+    m.add_function(wrap_pyfunction!(action_value, m)?)?;
+    m.add_function(wrap_pyfunction!(capture_next_binding, m)?)?;
+    m.add_function(wrap_pyfunction!(start_text_input, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_text_input, m)?)?;
+    m.add_function(wrap_pyfunction!(set_text_input_rect, m)?)?;
+    m.add_function(wrap_pyfunction!(record_input, m)?)?;
+    m.add_function(wrap_pyfunction!(replay_input, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_input_record, m)?)?;
+    m.add_function(wrap_pyfunction!(is_replaying_input, m)?)?;
     Ok(())
 }
@@ -22,15 +22,18 @@
 
 #[macro_use]
 mod utils;
+mod animation_wrapper;
 mod audio_wrapper;
 mod channel_wrapper;
 mod constant_wrapper;
+mod font_wrapper;
 mod graphics_wrapper;
 mod image_wrapper;
 mod input_wrapper;
 mod math_wrapper;
 mod music_wrapper;
 mod resource_wrapper;
+mod sample_wrapper;
 mod sound_wrapper;
 mod system_wrapper;
 mod tilemap_wrapper;
@@ -42,15 +45,18 @@ use std::mem::transmute;
 use pyo3::prelude::*;
 use pyxel::Pyxel;
 
+use crate::animation_wrapper::add_animation_class;
 use crate::audio_wrapper::add_audio_functions;
 use crate::channel_wrapper::add_channel_class;
 use crate::constant_wrapper::add_module_constants;
+use crate::font_wrapper::add_font_class;
 use crate::graphics_wrapper::add_graphics_functions;
 use crate::image_wrapper::add_image_class;
 use crate::input_wrapper::add_input_functions;
 use crate::math_wrapper::add_math_functions;
 use crate::music_wrapper::add_music_class;
 use crate::resource_wrapper::add_resource_functions;
+use crate::sample_wrapper::add_sample_class;
 use crate::sound_wrapper::add_sound_class;
 use crate::system_wrapper::add_system_functions;
 use crate::tilemap_wrapper::add_tilemap_class;
@@ -80,9 +86,12 @@ pub fn set_instance(pyxel: Pyxel) {
 fn pyxel_wrapper(_py: Python, m: &PyModule) -> PyResult<()> {
     add_image_class(m)?;
     add_tilemap_class(m)?;
+    add_font_class(m)?;
+    add_animation_class(m)?;
     add_channel_class(m)?;
     add_sound_class(m)?;
     add_music_class(m)?;
+    add_sample_class(m)?;
 
     add_module_constants(m)?;
     add_module_variables(m)?;
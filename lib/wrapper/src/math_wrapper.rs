@@ -1,4 +1,5 @@
 use pyo3::prelude::*;
+use pyo3::types::PyList;
 
 use crate::instance;
 
@@ -52,6 +53,16 @@ fn rndf(a: f64, b: f64) -> f64 {
     instance().rndf(a, b)
 }
 
+#[pyfunction]
+fn shuffle(list: &PyList) -> PyResult<()> {
+    let mut values: Vec<&PyAny> = list.iter().collect();
+    instance().shuffle(&mut values);
+    for (i, value) in values.into_iter().enumerate() {
+        list.set_item(i, value)?;
+    }
+    Ok(())
+}
+
 #[pyfunction]
 fn nseed(seed: u32) {
     instance().nseed(seed);
@@ -75,6 +86,7 @@ pub fn add_math_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(rseed, m)?)?;
     m.add_function(wrap_pyfunction!(rndi, m)?)?;
     m.add_function(wrap_pyfunction!(rndf, m)?)?;
+    m.add_function(wrap_pyfunction!(shuffle, m)?)?;
     m.add_function(wrap_pyfunction!(nseed, m)?)?;
     m.add_function(wrap_pyfunction!(noise, m)?)?;
     Ok(())
@@ -1,8 +1,14 @@
+use std::path::Path;
+
 use pyo3::class::PySequenceProtocol;
 use pyo3::exceptions::PyIndexError;
 use pyo3::prelude::*;
 use pyxel::Music as PyxelMusic;
 use pyxel::SharedMusic as PyxelSharedMusic;
+use pyxel::SAMPLE_RATE;
+
+use crate::instance;
+use crate::sample_wrapper::audio_error;
 
 #[pyclass]
 #[derive(Clone)]
@@ -111,6 +117,34 @@ impl Music {
     pub fn sequences(&self) -> Sequences {
         Sequences::new(self.pyxel_music.clone())
     }
+
+    #[pyo3(text_signature = "($self, start, end, *, ch)")]
+    pub fn set_loop_range(&self, start: u32, end: u32, ch: Option<u32>) {
+        if let Some(ch) = ch {
+            self.pyxel_music
+                .lock()
+                .set_channel_loop_range(ch, start, end);
+        } else {
+            self.pyxel_music.lock().set_loop_range(start, end);
+        }
+    }
+
+    pub fn clear_loop_range(&self) {
+        self.pyxel_music.lock().clear_loop_range();
+    }
+
+    #[pyo3(text_signature = "($self, filename, *, rate, loops)")]
+    pub fn to_wav(&self, filename: &str, rate: Option<u32>, loops: Option<u32>) -> PyResult<()> {
+        self.pyxel_music
+            .lock()
+            .to_wav(
+                instance(),
+                Path::new(filename),
+                rate.unwrap_or(SAMPLE_RATE),
+                loops.unwrap_or(1),
+            )
+            .map_err(audio_error)
+    }
 }
 
 pub fn add_music_class(m: &PyModule) -> PyResult<()> {
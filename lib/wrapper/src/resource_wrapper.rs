@@ -1,6 +1,20 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use pyxel::{AssetKind, ResourceError, StorageError, NUM_IMAGES, NUM_TILEMAPS};
 
+use crate::image_wrapper::image_error;
 use crate::instance;
+use crate::sample_wrapper::audio_error;
+
+fn resource_error(err: ResourceError) -> PyErr {
+    match err {
+        ResourceError::OpenFile(_) | ResourceError::WriteFile(_) => {
+            PyIOError::new_err(err.to_string())
+        }
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
 
 #[pyfunction]
 #[pyo3(text_signature = "(filename, *, image, tilemap, sound, music)")]
@@ -10,12 +24,14 @@ fn load(
     tilemap: Option<bool>,
     sound: Option<bool>,
     music: Option<bool>,
-) {
+) -> PyResult<()> {
     let image = image.unwrap_or(true);
     let tilemap = tilemap.unwrap_or(true);
     let sound = sound.unwrap_or(true);
     let music = music.unwrap_or(true);
-    instance().load(filename, image, tilemap, sound, music);
+    instance()
+        .try_load(filename, image, tilemap, sound, music)
+        .map_err(resource_error)
 }
 
 #[pyfunction]
@@ -26,17 +42,63 @@ fn save(
     tilemap: Option<bool>,
     sound: Option<bool>,
     music: Option<bool>,
-) {
+) -> PyResult<()> {
     let image = image.unwrap_or(true);
     let tilemap = tilemap.unwrap_or(true);
     let sound = sound.unwrap_or(true);
     let music = music.unwrap_or(true);
-    instance().save(filename, image, tilemap, sound, music);
+    instance()
+        .try_save(filename, image, tilemap, sound, music)
+        .map_err(resource_error)
+}
+
+fn storage_error(err: StorageError) -> PyErr {
+    match err {
+        StorageError::OpenFile(_) | StorageError::WriteFile(_) | StorageError::NoDataDir => {
+            PyIOError::new_err(err.to_string())
+        }
+        _ => PyValueError::new_err(err.to_string()),
+    }
+}
+
+#[pyfunction]
+fn set_app_id(app_id: &str) {
+    instance().set_app_id(app_id);
+}
+
+#[pyfunction]
+fn save_data(slot: &str, data: &[u8]) -> PyResult<()> {
+    instance()
+        .storage()
+        .and_then(|storage| storage.save(slot, data))
+        .map_err(storage_error)
+}
+
+#[pyfunction]
+fn load_data(py: Python, slot: &str) -> PyResult<Option<PyObject>> {
+    instance()
+        .storage()
+        .and_then(|storage| storage.load(slot))
+        .map(|data| data.map(|data| PyBytes::new(py, &data).into()))
+        .map_err(storage_error)
+}
+
+#[pyfunction]
+fn delete_data(slot: &str) -> PyResult<()> {
+    instance()
+        .storage()
+        .and_then(|storage| storage.delete(slot))
+        .map_err(storage_error)
 }
 
 #[pyfunction]
-fn screenshot(scale: Option<u32>) {
-    instance().screenshot(scale);
+fn screenshot(scale: Option<u32>) -> PyResult<()> {
+    instance().try_screenshot(scale).map_err(image_error)
+}
+
+#[pyfunction]
+fn set_capture_dir(dir: Option<&str>) {
+    instance().set_capture_dir(dir);
 }
 
 #[pyfunction]
@@ -45,15 +107,69 @@ fn reset_capture() {
 }
 
 #[pyfunction]
-fn screencast(scale: Option<u32>) {
-    instance().screencast(scale);
+fn screencast(scale: Option<u32>) -> PyResult<()> {
+    instance().try_screencast(scale).map_err(image_error)
+}
+
+#[pyfunction]
+fn start_capture(filename: &str) -> PyResult<()> {
+    instance().start_capture(filename).map_err(audio_error)
+}
+
+#[pyfunction]
+fn stop_capture() -> PyResult<(u64, u64)> {
+    let stats = instance().stop_capture().map_err(audio_error)?;
+    Ok((stats.num_frames, stats.num_dropped_frames))
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "(filename, *, image, tilemap)")]
+fn watch(filename: &str, image: Option<u32>, tilemap: Option<u32>) -> PyResult<()> {
+    let kind = match (image, tilemap) {
+        (Some(image_no), None) if image_no < NUM_IMAGES => AssetKind::Image(image_no),
+        (None, Some(tilemap_no)) if tilemap_no < NUM_TILEMAPS => AssetKind::Tilemap(tilemap_no),
+        (None, None) => AssetKind::Resource,
+        (Some(_), Some(_)) => {
+            return Err(PyValueError::new_err(
+                "Only one of image and tilemap can be specified",
+            ))
+        }
+        (Some(image_no), _) => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid image number {}",
+                image_no
+            )))
+        }
+        (_, Some(tilemap_no)) => {
+            return Err(PyValueError::new_err(format!(
+                "Invalid tilemap number {}",
+                tilemap_no
+            )))
+        }
+    };
+    instance().watch(filename, kind);
+    Ok(())
+}
+
+#[pyfunction]
+fn unwatch(filename: &str) {
+    instance().unwatch(filename);
 }
 
 pub fn add_resource_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(load, m)?)?;
     m.add_function(wrap_pyfunction!(save, m)?)?;
     m.add_function(wrap_pyfunction!(screenshot, m)?)?;
+    m.add_function(wrap_pyfunction!(set_capture_dir, m)?)?;
     m.add_function(wrap_pyfunction!(reset_capture, m)?)?;
     m.add_function(wrap_pyfunction!(screencast, m)?)?;
+    m.add_function(wrap_pyfunction!(start_capture, m)?)?;
+    m.add_function(wrap_pyfunction!(stop_capture, m)?)?;
+    m.add_function(wrap_pyfunction!(watch, m)?)?;
+    m.add_function(wrap_pyfunction!(unwatch, m)?)?;
+    m.add_function(wrap_pyfunction!(set_app_id, m)?)?;
+    m.add_function(wrap_pyfunction!(save_data, m)?)?;
+    m.add_function(wrap_pyfunction!(load_data, m)?)?;
+    m.add_function(wrap_pyfunction!(delete_data, m)?)?;
     Ok(())
 }
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyxel::AudioError as PyxelAudioError;
+use pyxel::Sample as PyxelSample;
+
+#[pyclass]
+#[derive(Clone)]
+pub struct Sample {
+    pub pyxel_sample: PyxelSample,
+}
+
+pub fn audio_error(err: PyxelAudioError) -> PyErr {
+    match err {
+        PyxelAudioError::InvalidWav(_)
+        | PyxelAudioError::InvalidChannel(_)
+        | PyxelAudioError::InvalidSampleRate(_) => PyValueError::new_err(err.to_string()),
+        _ => PyIOError::new_err(err.to_string()),
+    }
+}
+
+#[pymethods]
+impl Sample {
+    #[staticmethod]
+    pub fn from_wav(filename: &str) -> PyResult<Self> {
+        PyxelSample::from_wav(Path::new(filename))
+            .map(|pyxel_sample| Self { pyxel_sample })
+            .map_err(audio_error)
+    }
+
+    #[getter]
+    pub fn length(&self) -> usize {
+        self.pyxel_sample.len()
+    }
+}
+
+pub fn add_sample_class(m: &PyModule) -> PyResult<()> {
+    m.add_class::<Sample>()?;
+    Ok(())
+}
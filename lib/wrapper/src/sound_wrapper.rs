@@ -1,8 +1,15 @@
+use std::path::Path;
+
 use pyo3::class::PySequenceProtocol;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use pyxel::Envelope as PyxelEnvelope;
 use pyxel::SharedSound as PyxelSharedSound;
 use pyxel::Sound as PyxelSound;
-use pyxel::{Effect, Note, Speed, Tone, Volume};
+use pyxel::SoundError as PyxelSoundError;
+use pyxel::{Effect, Note, Speed, Tone, Volume, SAMPLE_RATE};
+
+use crate::sample_wrapper::audio_error;
 
 macro_rules! define_private_methods_for_list {
     ($type: ident, $elems: ident) => {
@@ -164,6 +171,10 @@ impl Effects {
     }
 }
 
+fn sound_error(err: PyxelSoundError) -> PyErr {
+    PyValueError::new_err(err.to_string())
+}
+
 #[pyclass]
 #[derive(Clone)]
 pub struct Sound {
@@ -211,26 +222,91 @@ impl Sound {
         self.pyxel_sound.lock().speed = speed;
     }
 
-    pub fn set(&self, notes: &str, tones: &str, volumes: &str, effects: &str, speed: Speed) {
+    #[getter]
+    pub fn get_vibrato_depth(&self) -> f64 {
+        self.pyxel_sound.lock().vibrato_depth
+    }
+
+    #[setter]
+    pub fn set_vibrato_depth(&self, vibrato_depth: f64) {
+        self.pyxel_sound.lock().vibrato_depth = vibrato_depth;
+    }
+
+    #[getter]
+    pub fn envelope(&self) -> (u32, u32, f64, u32) {
+        let envelope = self.pyxel_sound.lock().envelope;
+        (
+            envelope.attack,
+            envelope.decay,
+            envelope.sustain,
+            envelope.release,
+        )
+    }
+
+    pub fn set_envelope(&self, attack: u32, decay: u32, sustain: f64, release: u32) {
+        self.pyxel_sound.lock().envelope = PyxelEnvelope {
+            attack,
+            decay,
+            sustain,
+            release,
+        };
+    }
+
+    pub fn set(
+        &self,
+        notes: &str,
+        tones: &str,
+        volumes: &str,
+        effects: &str,
+        speed: Speed,
+    ) -> PyResult<()> {
         self.pyxel_sound
             .lock()
-            .set(notes, tones, volumes, effects, speed);
+            .try_set(notes, tones, volumes, effects, speed)
+            .map_err(sound_error)
     }
 
-    pub fn set_notes(&self, notes: &str) {
-        self.pyxel_sound.lock().set_notes(notes);
+    pub fn mml(&self, mml: &str) -> PyResult<()> {
+        self.pyxel_sound
+            .lock()
+            .try_set_mml(mml)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
     }
 
-    pub fn set_tones(&self, tones: &str) {
-        self.pyxel_sound.lock().set_tones(tones);
+    pub fn set_notes(&self, notes: &str) -> PyResult<()> {
+        self.pyxel_sound
+            .lock()
+            .try_set_notes(notes)
+            .map_err(sound_error)
     }
 
-    pub fn set_volumes(&self, volumes: &str) {
-        self.pyxel_sound.lock().set_volumes(volumes);
+    pub fn set_tones(&self, tones: &str) -> PyResult<()> {
+        self.pyxel_sound
+            .lock()
+            .try_set_tones(tones)
+            .map_err(sound_error)
+    }
+
+    pub fn set_volumes(&self, volumes: &str) -> PyResult<()> {
+        self.pyxel_sound
+            .lock()
+            .try_set_volumes(volumes)
+            .map_err(sound_error)
     }
 
-    pub fn set_effects(&self, effects: &str) {
-        self.pyxel_sound.lock().set_effects(effects);
+    pub fn set_effects(&self, effects: &str) -> PyResult<()> {
+        self.pyxel_sound
+            .lock()
+            .try_set_effects(effects)
+            .map_err(sound_error)
+    }
+
+    #[pyo3(text_signature = "($self, filename, *, rate)")]
+    pub fn to_wav(&self, filename: &str, rate: Option<u32>) -> PyResult<()> {
+        self.pyxel_sound
+            .lock()
+            .to_wav(Path::new(filename), rate.unwrap_or(SAMPLE_RATE))
+            .map_err(audio_error)
     }
 }
 
@@ -1,8 +1,16 @@
 use std::process::exit;
 
+use pyo3::exceptions::PyRuntimeError;
 use pyo3::prelude::*;
 use pyo3::types::{PyAny, PyDict};
-use pyxel::{Pyxel, PyxelCallback};
+use pyxel::{
+    CaptureAction, DisplayEffect, Key, PlatformBuilder, ProfileScope as PyxelProfileScope, Pyxel,
+    PyxelCallback, ScreenFit, ScreenMode, SystemOptions, CAPTURE_ACTION_FULLSCREEN,
+    CAPTURE_ACTION_PERF_MONITOR, CAPTURE_ACTION_RECORDING, CAPTURE_ACTION_SCREENSHOT,
+    DEFAULT_SCANLINE_DARKNESS, DEFAULT_TITLE, DISPLAY_EFFECT_GRID, DISPLAY_EFFECT_NONE,
+    DISPLAY_EFFECT_SCANLINES, DISPLAY_RATIO, SCREEN_FIT_ASPECT, SCREEN_FIT_FILL,
+    SCREEN_FIT_INTEGER, SCREEN_MODE_NEAREST, SCREEN_MODE_SHARP_BILINEAR,
+};
 use sysinfo::{Pid, PidExt, System, SystemExt};
 
 use crate::{instance, set_instance};
@@ -27,15 +35,15 @@ fn init(
         None,
         Some(locals),
     )?;
-    set_instance(Pyxel::new(
-        width,
-        height,
-        title,
-        fps,
-        quit_key,
-        capture_scale,
-        capture_sec,
-    ));
+    // Engine warnings keep reaching stderr unless the application installed its own logger
+    let _ = pyxel::init_logger();
+    let builder = PlatformBuilder::new()
+        .title(title.unwrap_or(DEFAULT_TITLE))
+        .screen_size(width, height)
+        .display_ratio(DISPLAY_RATIO);
+    let pyxel = Pyxel::with_builder(builder, fps, quit_key, capture_scale, capture_sec)
+        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+    set_instance(pyxel);
     Ok(())
 }
 
@@ -55,11 +63,64 @@ fn fullscreen(full: bool) {
 }
 
 #[pyfunction]
-fn run(py: Python, update: &PyAny, draw: &PyAny) {
+fn options_help() -> String {
+    SystemOptions::help()
+}
+
+#[pyfunction]
+fn display_effect(effect: u32, darkness: Option<u8>) -> PyResult<()> {
+    let effect = match effect {
+        DISPLAY_EFFECT_NONE => DisplayEffect::None,
+        DISPLAY_EFFECT_SCANLINES => DisplayEffect::Scanlines {
+            darkness: darkness.unwrap_or(DEFAULT_SCANLINE_DARKNESS),
+        },
+        DISPLAY_EFFECT_GRID => DisplayEffect::Grid,
+        _ => value_error!(format!("invalid display effect {}", effect)),
+    };
+    instance().display_effect(effect);
+    Ok(())
+}
+
+#[pyfunction]
+fn screen_fit(fit: u32, pixel_aspect: Option<f64>) -> PyResult<()> {
+    let fit = match fit {
+        SCREEN_FIT_INTEGER => ScreenFit::Integer,
+        SCREEN_FIT_ASPECT => ScreenFit::Aspect,
+        SCREEN_FIT_FILL => ScreenFit::FillIgnoreAspect,
+        _ => value_error!(format!("invalid screen fit {}", fit)),
+    };
+    let pixel_aspect = pixel_aspect.unwrap_or(1.0);
+    if !(pixel_aspect.is_finite() && pixel_aspect > 0.0) {
+        value_error!(format!("invalid pixel aspect ratio {}", pixel_aspect));
+    }
+    instance().screen_fit(fit, pixel_aspect);
+    Ok(())
+}
+
+#[pyfunction]
+fn screen_mode(mode: u32) -> PyResult<()> {
+    let mode = match mode {
+        SCREEN_MODE_NEAREST => ScreenMode::Nearest,
+        SCREEN_MODE_SHARP_BILINEAR => ScreenMode::SharpBilinear,
+        _ => value_error!(format!("invalid screen mode {}", mode)),
+    };
+    instance().screen_mode(mode);
+    Ok(())
+}
+
+#[pyfunction]
+fn set_dirty_tracking(enabled: bool) {
+    instance().set_dirty_tracking(enabled);
+}
+
+#[pyfunction]
+#[pyo3(text_signature = "(update, draw, *, on_quit)")]
+fn run(py: Python, update: &PyAny, draw: &PyAny, on_quit: Option<&PyAny>) {
     struct PythonCallback<'a> {
         py: Python<'a>,
         update: &'a PyAny,
         draw: &'a PyAny,
+        on_quit: Option<&'a PyAny>,
     }
 
     impl<'a> PyxelCallback for PythonCallback<'a> {
@@ -76,14 +137,41 @@ fn run(py: Python, update: &PyAny, draw: &PyAny) {
                 exit(1);
             }
         }
+
+        fn on_quit(&mut self, _pyxel: &mut Pyxel) -> bool {
+            let on_quit = match self.on_quit {
+                Some(on_quit) => on_quit,
+                None => return true,
+            };
+            match on_quit.call0().and_then(|result| {
+                if result.is_none() {
+                    Ok(true)
+                } else {
+                    result.is_true()
+                }
+            }) {
+                Ok(result) => result,
+                Err(err) => {
+                    err.print(self.py);
+                    exit(1);
+                }
+            }
+        }
     }
 
-    instance().run(&mut PythonCallback { py, update, draw });
+    instance().run(&mut PythonCallback {
+        py,
+        update,
+        draw,
+        on_quit,
+    });
+    exit(0);
 }
 
 #[pyfunction]
 fn show() {
     instance().show();
+    exit(0);
 }
 
 #[pyfunction]
@@ -91,6 +179,133 @@ fn flip() {
     instance().flip();
 }
 
+#[pyfunction]
+fn frame_stats() -> (f64, f64, f64, f64, f64, f64, f64, f64) {
+    let stats = instance().frame_stats();
+    (
+        stats.fps,
+        stats.last_frame_ms,
+        stats.mean_frame_ms,
+        stats.max_frame_ms,
+        stats.events_ms,
+        stats.update_ms,
+        stats.draw_ms,
+        stats.render_ms,
+    )
+}
+
+#[pyfunction]
+fn show_perf_overlay(shown: bool) {
+    instance().show_perf_overlay(shown);
+}
+
+#[pyclass]
+struct ProfileScope {
+    name: String,
+    pyxel_scope: Option<PyxelProfileScope>,
+}
+
+#[pymethods]
+impl ProfileScope {
+    fn __enter__(&mut self) {
+        self.pyxel_scope = Some(instance().profile_scope(&self.name));
+    }
+
+    fn __exit__(&mut self, _exc_type: &PyAny, _exc_value: &PyAny, _traceback: &PyAny) -> bool {
+        self.pyxel_scope = None;
+        false
+    }
+}
+
+#[pyfunction]
+fn profile(name: &str) -> ProfileScope {
+    ProfileScope {
+        name: name.to_string(),
+        pyxel_scope: None,
+    }
+}
+
+#[pyfunction]
+fn enable_profiler(enabled: bool) {
+    instance().enable_profiler(enabled);
+}
+
+#[pyfunction]
+fn show_profiler_overlay(shown: bool) {
+    instance().show_profiler_overlay(shown);
+}
+
+#[pyfunction]
+fn profiler_report() -> Vec<(String, u32, u32, f64, f64, f64)> {
+    instance()
+        .profiler_report()
+        .into_iter()
+        .map(|stats| {
+            (
+                stats.name,
+                stats.depth,
+                stats.num_calls,
+                stats.total_ms,
+                stats.self_ms,
+                stats.max_ms,
+            )
+        })
+        .collect()
+}
+
+#[pyfunction]
+fn set_capture_key(action: u32, key: Option<Key>) -> PyResult<()> {
+    let action = match action {
+        CAPTURE_ACTION_SCREENSHOT => CaptureAction::Screenshot,
+        CAPTURE_ACTION_RECORDING => CaptureAction::Recording,
+        CAPTURE_ACTION_PERF_MONITOR => CaptureAction::PerfMonitor,
+        CAPTURE_ACTION_FULLSCREEN => CaptureAction::Fullscreen,
+        _ => value_error!(format!("invalid capture action {}", action)),
+    };
+    instance().set_capture_key(action, key);
+    Ok(())
+}
+
+#[pyfunction]
+fn set_paused(paused: bool) {
+    instance().set_paused(paused);
+}
+
+#[pyfunction]
+fn is_paused() -> bool {
+    instance().is_paused()
+}
+
+#[pyfunction]
+fn request_step() {
+    instance().request_step();
+}
+
+#[pyfunction]
+fn set_pause_keys(pause_key: Key, step_key: Key) {
+    instance().set_pause_keys(pause_key, step_key);
+}
+
+#[pyfunction]
+fn target_fps() -> u32 {
+    instance().target_fps()
+}
+
+#[pyfunction]
+fn set_target_fps(fps: u32) {
+    instance().set_target_fps(fps);
+}
+
+#[pyfunction]
+fn set_max_frame_skip(max_frame_skip: u32) {
+    instance().set_max_frame_skip(max_frame_skip);
+}
+
+#[pyfunction]
+fn is_frame_skipped() -> bool {
+    instance().is_frame_skipped()
+}
+
 #[pyfunction]
 fn quit() {
     instance().quit();
@@ -107,9 +322,30 @@ pub fn add_system_functions(m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(title, m)?)?;
     m.add_function(wrap_pyfunction!(icon, m)?)?;
     m.add_function(wrap_pyfunction!(fullscreen, m)?)?;
+    m.add_function(wrap_pyfunction!(options_help, m)?)?;
+    m.add_function(wrap_pyfunction!(display_effect, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_fit, m)?)?;
+    m.add_function(wrap_pyfunction!(screen_mode, m)?)?;
+    m.add_function(wrap_pyfunction!(set_dirty_tracking, m)?)?;
     m.add_function(wrap_pyfunction!(run, m)?)?;
     m.add_function(wrap_pyfunction!(show, m)?)?;
     m.add_function(wrap_pyfunction!(flip, m)?)?;
+    m.add_function(wrap_pyfunction!(frame_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(show_perf_overlay, m)?)?;
+    m.add_class::<ProfileScope>()?;
+    m.add_function(wrap_pyfunction!(profile, m)?)?;
+    m.add_function(wrap_pyfunction!(enable_profiler, m)?)?;
+    m.add_function(wrap_pyfunction!(show_profiler_overlay, m)?)?;
+    m.add_function(wrap_pyfunction!(profiler_report, m)?)?;
+    m.add_function(wrap_pyfunction!(set_capture_key, m)?)?;
+    m.add_function(wrap_pyfunction!(set_paused, m)?)?;
+    m.add_function(wrap_pyfunction!(is_paused, m)?)?;
+    m.add_function(wrap_pyfunction!(request_step, m)?)?;
+    m.add_function(wrap_pyfunction!(set_pause_keys, m)?)?;
+    m.add_function(wrap_pyfunction!(target_fps, m)?)?;
+    m.add_function(wrap_pyfunction!(set_target_fps, m)?)?;
+    m.add_function(wrap_pyfunction!(set_max_frame_skip, m)?)?;
+    m.add_function(wrap_pyfunction!(is_frame_skipped, m)?)?;
     m.add_function(wrap_pyfunction!(quit, m)?)?;
     m.add_function(wrap_pyfunction!(process_exists, m)?)?;
     Ok(())
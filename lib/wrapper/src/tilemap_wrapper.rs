@@ -1,7 +1,11 @@
+use pyo3::exceptions::{PyIOError, PyValueError};
 use pyo3::prelude::*;
+use pyxel::Color;
 use pyxel::SharedTilemap as PyxelSharedTilemap;
 use pyxel::Tile;
 use pyxel::Tilemap as PyxelTilemap;
+use pyxel::TilemapError as PyxelTilemapError;
+use pyxel::{Rectf, Vec2};
 
 use crate::image_wrapper::{wrap_pyxel_image, Image};
 use crate::instance;
@@ -32,6 +36,25 @@ impl Tilemap {
         Ok(wrap_pyxel_tilemap(PyxelTilemap::new(width, height, img)))
     }
 
+    #[staticmethod]
+    pub fn from_tmx(filename: &str, img: &PyAny) -> PyResult<Vec<Self>> {
+        let img = type_switch! {
+            img,
+            u32, {
+                instance().image(img)
+            },
+            Image, {
+                img.pyxel_image
+            }
+        };
+        PyxelTilemap::from_tmx(filename, img)
+            .map(|tilemaps| tilemaps.into_iter().map(wrap_pyxel_tilemap).collect())
+            .map_err(|err| match err {
+                PyxelTilemapError::Open(_) => PyIOError::new_err(err.to_string()),
+                _ => PyValueError::new_err(err.to_string()),
+            })
+    }
+
     #[getter]
     pub fn width(&self) -> u32 {
         self.pyxel_tilemap.lock().width()
@@ -52,6 +75,29 @@ impl Tilemap {
         self.pyxel_tilemap.lock().image = image.pyxel_image;
     }
 
+    #[getter]
+    pub fn colkey(&self) -> Option<Color> {
+        self.pyxel_tilemap.lock().colkey
+    }
+
+    #[setter]
+    pub fn set_colkey(&self, colkey: Option<Color>) {
+        self.pyxel_tilemap.lock().colkey = colkey;
+    }
+
+    #[getter]
+    pub fn tile_size(&self) -> u32 {
+        self.pyxel_tilemap.lock().tile_size()
+    }
+
+    #[setter]
+    pub fn set_tile_size(&self, tile_size: u32) -> PyResult<()> {
+        self.pyxel_tilemap
+            .lock()
+            .set_tile_size(tile_size)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
     #[getter]
     pub fn refimg(&self) -> Option<u32> {
         instance().image_no(self.pyxel_tilemap.lock().image.clone())
@@ -62,6 +108,37 @@ impl Tilemap {
         self.pyxel_tilemap.lock().image = instance().image(img);
     }
 
+    pub fn set_collision(&self, tiles: Vec<Tile>) {
+        self.pyxel_tilemap.lock().set_collision(&tiles);
+    }
+
+    pub fn is_solid_at(&self, x: f64, y: f64) -> bool {
+        self.pyxel_tilemap.lock().is_solid_at(x, y)
+    }
+
+    pub fn tiles_in_rect(&self, x: f64, y: f64, w: f64, h: f64) -> Vec<(i32, i32, Tile)> {
+        self.pyxel_tilemap
+            .lock()
+            .tiles_in_rect(Rectf::new(x, y, w, h))
+            .collect()
+    }
+
+    pub fn sweep_aabb(
+        &self,
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+        dx: f64,
+        dy: f64,
+    ) -> (f64, f64, bool, bool) {
+        let result = self
+            .pyxel_tilemap
+            .lock()
+            .sweep_aabb(Rectf::new(x, y, w, h), Vec2::new(dx, dy));
+        (result.delta.x, result.delta.y, result.hit_x, result.hit_y)
+    }
+
     pub fn set(&mut self, x: i32, y: i32, data: Vec<&str>) {
         self.pyxel_tilemap.lock().set(x, y, &data);
     }
@@ -110,6 +187,22 @@ impl Tilemap {
         self.pyxel_tilemap.lock().line(x1, y1, x2, y2, tile);
     }
 
+    pub fn line_w(&self, x1: f64, y1: f64, x2: f64, y2: f64, thickness: f64, tile: Tile) {
+        self.pyxel_tilemap
+            .lock()
+            .line_w(x1, y1, x2, y2, thickness, tile);
+    }
+
+    pub fn polyline(&self, points: Vec<(f64, f64)>, tile: Tile) {
+        self.pyxel_tilemap.lock().polyline(&points, tile);
+    }
+
+    pub fn bezier(&self, x1: f64, y1: f64, cx: f64, cy: f64, x2: f64, y2: f64, tile: Tile) {
+        self.pyxel_tilemap
+            .lock()
+            .bezier(x1, y1, cx, cy, x2, y2, tile);
+    }
+
     pub fn rect(&self, x: f64, y: f64, w: f64, h: f64, tile: Tile) {
         self.pyxel_tilemap.lock().rect(x, y, w, h, tile);
     }
@@ -118,6 +211,12 @@ impl Tilemap {
         self.pyxel_tilemap.lock().rectb(x, y, w, h, tile);
     }
 
+    pub fn rectb_w(&self, x: f64, y: f64, w: f64, h: f64, thickness: f64, tile: Tile) {
+        self.pyxel_tilemap
+            .lock()
+            .rectb_w(x, y, w, h, thickness, tile);
+    }
+
     pub fn circ(&self, x: f64, y: f64, r: f64, tile: Tile) {
         self.pyxel_tilemap.lock().circ(x, y, r, tile);
     }
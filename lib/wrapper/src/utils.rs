@@ -4,6 +4,12 @@ macro_rules! type_error {
     };
 }
 
+macro_rules! value_error {
+    ($msg: expr) => {
+        return Err(pyo3::exceptions::PyValueError::new_err($msg))
+    };
+}
+
 macro_rules! type_switch {
     ($var: ident, $type1: ty, $block1: block, $type2: ty, $block2: block) => {
         if let Ok($var) = <$type1>::extract($var) {
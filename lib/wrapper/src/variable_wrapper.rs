@@ -1,7 +1,7 @@
 use pyo3::class::PySequenceProtocol;
-use pyo3::exceptions::{PyAttributeError, PyValueError};
+use pyo3::exceptions::{PyAttributeError, PyIndexError, PyValueError};
 use pyo3::prelude::*;
-use pyxel::Rgb8;
+use pyxel::{Color, Rgb8, MAX_COLORS};
 
 use crate::image_wrapper::wrap_pyxel_image;
 use crate::instance;
@@ -11,11 +11,7 @@ struct Colors;
 
 impl Colors {
     fn list(&self) -> &[Rgb8] {
-        &instance().colors
-    }
-
-    fn list_mut(&mut self) -> &mut [Rgb8] {
-        &mut instance().colors
+        instance().palette.colors()
     }
 }
 
@@ -30,18 +26,26 @@ impl PySequenceProtocol for Colors {
     }
 
     fn __setitem__(&mut self, index: isize, value: Rgb8) -> PyResult<()> {
-        impl_setitem_method_for_list!(self, index, value)
+        if index >= 0 && index < self.list().len() as isize {
+            instance().palette.set_color(index as Color, value);
+            Ok(())
+        } else {
+            Err(PyIndexError::new_err("list assignment index out of range"))
+        }
     }
 }
 
 #[pymethods]
 impl Colors {
     pub fn from_list(&mut self, lst: Vec<Rgb8>) -> PyResult<()> {
-        if self.list().len() == lst.len() {
-            self.list_mut()[..].clone_from_slice(&lst[..]);
+        if !lst.is_empty() && lst.len() <= MAX_COLORS as usize {
+            instance().palette.set_colors(&lst);
             Ok(())
         } else {
-            Err(PyValueError::new_err("list must be same length as array"))
+            Err(PyValueError::new_err(format!(
+                "list must have 1 to {} colors",
+                MAX_COLORS
+            )))
         }
     }
 
@@ -59,12 +63,17 @@ fn __getattr__(py: Python, name: &str) -> PyResult<PyObject> {
         "frame_count" => instance().frame_count().to_object(py),
         "is_fullscreen" => instance().is_fullscreen().to_object(py),
 
+        // Resource
+        "reloaded_assets" => instance().reloaded_assets().to_object(py),
+
         // Input
         "mouse_x" => instance().mouse_x().to_object(py),
         "mouse_y" => instance().mouse_y().to_object(py),
         "mouse_wheel" => instance().mouse_wheel().to_object(py),
         "input_keys" => instance().input_keys().to_object(py),
         "input_text" => instance().input_text().to_object(py),
+        "editing_text" => instance().editing_text().to_object(py),
+        "editing_range" => instance().editing_range().to_object(py),
         "drop_files" => instance().drop_files().to_object(py),
 
         // Graphics
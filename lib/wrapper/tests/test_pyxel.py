@@ -21,3 +21,13 @@ class TestPyxel(unittest.TestCase):
 
     def test_input_text(self):
         pyxel.input_text
+
+    def test_blt_image_to_draw_target(self):
+        img = pyxel.Image(4, 4)
+        img.cls(5)
+        pyxel.image(0).cls(0)
+        pyxel.set_draw_target(0)
+        pyxel.blt(2, 3, img, 0, 0, 4, 4)
+        pyxel.set_draw_target(None)
+        self.assertEqual(pyxel.image(0).pget(2, 3), 5)
+        self.assertEqual(pyxel.image(0).pget(5, 6), 5)
@@ -1,5 +1,5 @@
 # flake8: noqa
-from typing import Callable, List, Optional, Tuple, Union
+from typing import Any, Callable, List, Optional, Tuple, Union
 
 # Constants
 PYXEL_VERSION: str
@@ -10,12 +10,31 @@ APP_STARTUP_SCRIPT_FILE: str
 RESOURCE_FILE_EXTENSION: str
 RESOURCE_ARCHIVE_DIRNAME: str
 
+DISPLAY_EFFECT_NONE: int
+DISPLAY_EFFECT_SCANLINES: int
+DISPLAY_EFFECT_GRID: int
+SCREEN_FIT_INTEGER: int
+SCREEN_FIT_ASPECT: int
+SCREEN_FIT_FILL: int
+SCREEN_MODE_NEAREST: int
+SCREEN_MODE_SHARP_BILINEAR: int
+ALIGN_LEFT: int
+ALIGN_CENTER: int
+ALIGN_RIGHT: int
+
+CAPTURE_ACTION_SCREENSHOT: int
+CAPTURE_ACTION_RECORDING: int
+CAPTURE_ACTION_PERF_MONITOR: int
+CAPTURE_ACTION_FULLSCREEN: int
+
 NUM_COLORS: int
+MAX_COLORS: int
 NUM_IMAGES: int
 IMAGE_SIZE: int
 NUM_TILEMAPS: int
 TILEMAP_SIZE: int
 TILE_SIZE: int
+NUM_LAYERS: int
 
 COLOR_BLACK: int
 COLOR_NAVY: int
@@ -45,6 +64,9 @@ TONE_TRIANGLE: int
 TONE_SQUARE: int
 TONE_PULSE: int
 TONE_NOISE: int
+TONE_SAWTOOTH: int
+TONE_NARROW_PULSE: int
+TONE_SHORT_NOISE: int
 
 EFFECT_NONE: int
 EFFECT_SLIDE: int
@@ -348,12 +370,59 @@ GAMEPAD2_BUTTON_DPAD_DOWN: int
 GAMEPAD2_BUTTON_DPAD_LEFT: int
 GAMEPAD2_BUTTON_DPAD_RIGHT: int
 
+GAMEPAD3_AXIS_LEFTX: int
+GAMEPAD3_AXIS_LEFTY: int
+GAMEPAD3_AXIS_RIGHTX: int
+GAMEPAD3_AXIS_RIGHTY: int
+GAMEPAD3_AXIS_TRIGGERLEFT: int
+GAMEPAD3_AXIS_TRIGGERRIGHT: int
+GAMEPAD3_BUTTON_A: int
+GAMEPAD3_BUTTON_B: int
+GAMEPAD3_BUTTON_X: int
+GAMEPAD3_BUTTON_Y: int
+GAMEPAD3_BUTTON_BACK: int
+GAMEPAD3_BUTTON_GUIDE: int
+GAMEPAD3_BUTTON_START: int
+GAMEPAD3_BUTTON_LEFTSTICK: int
+GAMEPAD3_BUTTON_RIGHTSTICK: int
+GAMEPAD3_BUTTON_LEFTSHOULDER: int
+GAMEPAD3_BUTTON_RIGHTSHOULDER: int
+GAMEPAD3_BUTTON_DPAD_UP: int
+GAMEPAD3_BUTTON_DPAD_DOWN: int
+GAMEPAD3_BUTTON_DPAD_LEFT: int
+GAMEPAD3_BUTTON_DPAD_RIGHT: int
+
+GAMEPAD4_AXIS_LEFTX: int
+GAMEPAD4_AXIS_LEFTY: int
+GAMEPAD4_AXIS_RIGHTX: int
+GAMEPAD4_AXIS_RIGHTY: int
+GAMEPAD4_AXIS_TRIGGERLEFT: int
+GAMEPAD4_AXIS_TRIGGERRIGHT: int
+GAMEPAD4_BUTTON_A: int
+GAMEPAD4_BUTTON_B: int
+GAMEPAD4_BUTTON_X: int
+GAMEPAD4_BUTTON_Y: int
+GAMEPAD4_BUTTON_BACK: int
+GAMEPAD4_BUTTON_GUIDE: int
+GAMEPAD4_BUTTON_START: int
+GAMEPAD4_BUTTON_LEFTSTICK: int
+GAMEPAD4_BUTTON_RIGHTSTICK: int
+GAMEPAD4_BUTTON_LEFTSHOULDER: int
+GAMEPAD4_BUTTON_RIGHTSHOULDER: int
+GAMEPAD4_BUTTON_DPAD_UP: int
+GAMEPAD4_BUTTON_DPAD_DOWN: int
+GAMEPAD4_BUTTON_DPAD_LEFT: int
+GAMEPAD4_BUTTON_DPAD_RIGHT: int
+
 # System
 width: int
 height: int
 frame_count: int
 is_fullscreen: bool
 
+# Resource
+reloaded_assets: List[str]
+
 def init(
     width: int,
     height: int,
@@ -367,9 +436,39 @@ def init(
 def title(title: str) -> None: ...
 def icon(data: List[str], scale: int) -> None: ...
 def fullscreen(full: bool) -> None: ...
-def run(update: Callable[[], None], draw: Callable[[], None]) -> None: ...
+def options_help() -> str: ...
+def display_effect(effect: int, darkness: Optional[int] = None) -> None: ...
+def screen_fit(fit: int, pixel_aspect: Optional[float] = None) -> None: ...
+def screen_mode(mode: int) -> None: ...
+def set_dirty_tracking(enabled: bool) -> None: ...
+def run(
+    update: Callable[[], None],
+    draw: Callable[[], None],
+    *,
+    on_quit: Optional[Callable[[], Optional[bool]]] = None,
+) -> None: ...
 def show() -> None: ...
 def flip() -> None: ...
+def frame_stats() -> Tuple[float, float, float, float, float, float, float, float]: ...
+def show_perf_overlay(shown: bool) -> None: ...
+
+class ProfileScope:
+    def __enter__(self) -> None: ...
+    def __exit__(self, exc_type: Any, exc_value: Any, traceback: Any) -> bool: ...
+
+def profile(name: str) -> ProfileScope: ...
+def enable_profiler(enabled: bool) -> None: ...
+def show_profiler_overlay(shown: bool) -> None: ...
+def profiler_report() -> List[Tuple[str, int, int, float, float, float]]: ...
+def set_capture_key(action: int, key: Optional[int]) -> None: ...
+def set_paused(paused: bool) -> None: ...
+def is_paused() -> bool: ...
+def request_step() -> None: ...
+def set_pause_keys(pause_key: int, step_key: int) -> None: ...
+def target_fps() -> int: ...
+def set_target_fps(fps: int) -> None: ...
+def set_max_frame_skip(max_frame_skip: int) -> None: ...
+def is_frame_skipped() -> bool: ...
 def quit() -> None: ...
 def process_exists(pid: int) -> bool: ...
 
@@ -391,8 +490,19 @@ def save(
     music: Optional[bool] = None,
 ) -> None: ...
 def screenshot(scale: Optional[int] = None) -> None: ...
+def set_capture_dir(dir: Optional[str]) -> None: ...
 def reset_capture() -> None: ...
 def screencast(scale: Optional[int] = None) -> None: ...
+def start_capture(filename: str) -> None: ...
+def stop_capture() -> Tuple[int, int]: ...
+def watch(
+    filename: str, *, image: Optional[int] = None, tilemap: Optional[int] = None
+) -> None: ...
+def unwatch(filename: str) -> None: ...
+def set_app_id(app_id: str) -> None: ...
+def save_data(slot: str, data: bytes) -> None: ...
+def load_data(slot: str) -> Optional[bytes]: ...
+def delete_data(slot: str) -> None: ...
 
 # Input
 mouse_x: int
@@ -400,6 +510,8 @@ mouse_y: int
 mouse_wheel: int
 input_keys: List[int]
 input_text: str
+editing_text: str
+editing_range: Tuple[int, int]
 drop_files: List[str]
 
 def btn(key: int) -> bool: ...
@@ -408,14 +520,37 @@ def btnp(
 ) -> bool: ...
 def btnr(key: int) -> bool: ...
 def btnv(key: int) -> int: ...
+def key_name(key: int) -> Optional[str]: ...
+def key_from_name(name: str) -> Optional[int]: ...
 def mouse(visible: bool) -> None: ...
 def set_btn(key: int, state: bool) -> None: ...
 def set_btnv(key: int, val: float) -> None: ...
 def set_mouse_pos(x: float, y: float) -> None: ...
+def mouse_on_screen() -> bool: ...
+def mouse_window_pos() -> Tuple[int, int]: ...
+def flush_events() -> None: ...
+def set_coalesce_mouse_motion(enabled: bool) -> None: ...
+def last_press_latency_us() -> Optional[int]: ...
+def bind(action: str, binding: str) -> None: ...
+def unbind(action: str) -> None: ...
+def action_down(action: str) -> bool: ...
+def action_pressed(action: str) -> bool: ...
+def action_released(action: str) -> bool: ...
+def action_value(action: str) -> float: ...
+def capture_next_binding() -> Optional[str]: ...
+def start_text_input() -> None: ...
+def stop_text_input() -> None: ...
+def set_text_input_rect(x: float, y: float, w: float, h: float) -> None: ...
+def record_input(filename: str) -> None: ...
+def replay_input(filename: str) -> None: ...
+def stop_input_record() -> None: ...
+def is_replaying_input() -> bool: ...
 
 # Graphics
 class Image: ...
 class Tilemap: ...
+class Font: ...
+class Animation: ...
 
 colors: List[int]
 screen: Image
@@ -424,6 +559,14 @@ font: Image
 
 def image(img: int) -> Image: ...
 def tilemap(tm: int) -> Tilemap: ...
+def set_draw_target(img: Optional[int] = None) -> None: ...
+def screen_to_image(img: int) -> None: ...
+def layer(layer: int) -> Image: ...
+def layer_colkey(layer: int, col: int) -> None: ...
+def layer_scroll(layer: int, x: int, y: int) -> None: ...
+def pick(x: float, y: float) -> Optional[int]: ...
+def pick_layer(layer: int, x: float, y: float) -> Optional[int]: ...
+def screen_colkey(col: int) -> None: ...
 def clip(
     x: Optional[float] = None,
     y: Optional[float] = None,
@@ -435,12 +578,39 @@ def camera(
     y: Optional[float] = None,
 ) -> None: ...
 def pal(col1: Optional[int] = None, col2: Optional[int] = None) -> None: ...
+def pal_cycle(
+    start: Optional[int] = None,
+    end: Optional[int] = None,
+    shift: Optional[int] = None,
+) -> None: ...
+def display_brightness(factor: float) -> None: ...
+def display_tint(rgb: int, strength: float) -> None: ...
+def fade_out(frames: int, rgb: Optional[int] = None) -> None: ...
+def fade_in(frames: int) -> None: ...
+def is_transition_done() -> bool: ...
+def fillp(
+    pattern: Optional[int] = None,
+    col1: Optional[int] = None,
+    col2: Optional[int] = None,
+) -> None: ...
 def cls(col: int) -> None: ...
 def pget(x: float, y: float) -> int: ...
 def pset(x: float, y: float, col: int) -> None: ...
+def pset_slice(points: List[Tuple[int, int]], col: int) -> None: ...
+def pset_colored(points: List[Tuple[int, int, int]]) -> None: ...
 def line(x1: float, y1: float, x2: float, y2: float, col: int) -> None: ...
+def line_w(
+    x1: float, y1: float, x2: float, y2: float, thickness: float, col: int
+) -> None: ...
+def polyline(points: List[Tuple[float, float]], col: int) -> None: ...
+def bezier(
+    x1: float, y1: float, cx: float, cy: float, x2: float, y2: float, col: int
+) -> None: ...
 def rect(x: float, y: float, w: float, h: float, col: int) -> None: ...
 def rectb(x: float, y: float, w: float, h: float, col: int) -> None: ...
+def rectb_w(
+    x: float, y: float, w: float, h: float, thickness: float, col: int
+) -> None: ...
 def circ(x: float, y: float, r: float, col: int) -> None: ...
 def circb(x: float, y: float, r: float, col: int) -> None: ...
 def elli(self, x: float, y: float, w: float, h: float, col: int) -> None: ...
@@ -473,6 +643,22 @@ def blt(
     w: float,
     h: float,
     colkey: Optional[int] = None,
+    *,
+    rotate: Optional[float] = None,
+    scale: Optional[float] = None,
+) -> None: ...
+def blt9(
+    x: float,
+    y: float,
+    w: float,
+    h: float,
+    img: Union[int, Image],
+    u: float,
+    v: float,
+    src_w: float,
+    src_h: float,
+    border: int,
+    colkey: Optional[int] = None,
 ) -> None: ...
 def bltm(
     x: float,
@@ -484,17 +670,33 @@ def bltm(
     h: float,
     colkey: Optional[int] = None,
 ) -> None: ...
-def text(x: float, y: float, s: str, col: int) -> None: ...
+def text(x: float, y: float, s: str, col: int) -> int: ...
+def text_with(x: float, y: float, s: str, col: int, font: Font) -> int: ...
+def text_box(
+    x: float,
+    y: float,
+    w: int,
+    s: str,
+    col: int,
+    *,
+    align: int = ALIGN_LEFT,
+    font: Optional[Font] = None,
+) -> int: ...
+def measure_text_box(w: int, s: str, *, font: Optional[Font] = None) -> Tuple[int, int]: ...
 
 # Audio
 class Channel: ...
 class Sound: ...
 class Music: ...
+class Sample: ...
 
 def channel(ch: int) -> Channel: ...
+def num_channels() -> int: ...
+def set_num_channels(count: int) -> None: ...
 def sound(snd: int) -> Sound: ...
 def music(msc: int) -> Music: ...
 def play_pos(ch: int) -> Optional[Tuple[int, int]]: ...
+def elapsed_samples(ch: int) -> int: ...
 def play(
     ch: int,
     snd: Union[int, List[int], Sound, List[Sound]],
@@ -505,6 +707,14 @@ def play(
 def playm(
     msc: int, *, tick: Optional[int] = None, loop: Optional[bool] = None
 ) -> None: ...
+def play_sample(ch: int, sample: Sample, *, loop: Optional[bool] = None) -> None: ...
+def master_volume(vol: float) -> None: ...
+def audio_stats() -> Tuple[int, int, int, int, float]: ...
+def audio_devices() -> List[str]: ...
+def set_audio_device(name: Optional[str] = None) -> None: ...
+def pause_audio() -> None: ...
+def resume_audio() -> None: ...
+def is_audio_paused() -> bool: ...
 def stop(ch: Optional[int] = None) -> None: ...
 
 # Math
@@ -518,6 +728,7 @@ def atan2(y: float, x: float) -> float: ...
 def rseed(seed: int): ...
 def rndi(a: int, b: int) -> int: ...
 def rndf(a: float, b: float) -> int: ...
+def shuffle(list: List[Any]) -> None: ...
 def nseed(seed: int): ...
 def noise(x: float, y: Optional[float], z: Optional[float]) -> float: ...
 
@@ -543,12 +754,36 @@ class Image:
         y: Optional[float] = None,
     ) -> None: ...
     def pal(self, col1: Optional[int] = None, col2: Optional[int] = None) -> None: ...
+    def fillp(
+        self,
+        pattern: Optional[int] = None,
+        col1: Optional[int] = None,
+        col2: Optional[int] = None,
+    ) -> None: ...
+    def flip_horizontal(self) -> None: ...
+    def flip_vertical(self) -> None: ...
+    def rotate90(self, times: int) -> None: ...
+    def remap_colors(self, map: List[int]) -> None: ...
+    def find_color_bounds(self, col: int) -> Optional[Tuple[int, int, int, int]]: ...
+    def histogram(self) -> List[int]: ...
     def cls(self, col: int) -> None: ...
     def pget(self, x: float, y: float) -> int: ...
     def pset(self, x: float, y: float, col: int) -> None: ...
+    def pset_slice(self, points: List[Tuple[int, int]], col: int) -> None: ...
+    def pset_colored(self, points: List[Tuple[int, int, int]]) -> None: ...
     def line(self, x1: float, y1: float, x2: float, y2: float, col: int) -> None: ...
+    def line_w(
+        self, x1: float, y1: float, x2: float, y2: float, thickness: float, col: int
+    ) -> None: ...
+    def polyline(self, points: List[Tuple[float, float]], col: int) -> None: ...
+    def bezier(
+        self, x1: float, y1: float, cx: float, cy: float, x2: float, y2: float, col: int
+    ) -> None: ...
     def rect(self, x: float, y: float, w: float, h: float, col: int) -> None: ...
     def rectb(self, x: float, y: float, w: float, h: float, col: int) -> None: ...
+    def rectb_w(
+        self, x: float, y: float, w: float, h: float, thickness: float, col: int
+    ) -> None: ...
     def circ(self, x: float, y: float, r: float, col: int) -> None: ...
     def circb(self, x: float, y: float, r: float, col: int) -> None: ...
     def elli(self, x: float, y: float, w: float, h: float, col: int) -> None: ...
@@ -570,6 +805,34 @@ class Image:
         w: float,
         h: float,
         colkey: Optional[int] = None,
+        *,
+        rotate: Optional[float] = None,
+        scale: Optional[float] = None,
+    ) -> None: ...
+    def blt9(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        img: Union[int, Image],
+        u: float,
+        v: float,
+        src_w: float,
+        src_h: float,
+        border: int,
+        colkey: Optional[int] = None,
+    ) -> None: ...
+    def copy(
+        self,
+        x: float,
+        y: float,
+        img: Union[int, Image],
+        u: float,
+        v: float,
+        w: float,
+        h: float,
+        colkey: Optional[int] = None,
     ) -> None: ...
     def bltm(
         self,
@@ -584,7 +847,8 @@ class Image:
     ) -> None: ...
     def text(
         self, x: float, y: float, s: str, col: int, font: Optional[Image] = None
-    ) -> None: ...
+    ) -> int: ...
+    def text_with(self, x: float, y: float, s: str, col: int, font: Font) -> int: ...
 
 # Tilemap class
 class Tilemap:
@@ -592,8 +856,20 @@ class Tilemap:
     height: int
     image: Image
     refimg: Optional[int]
+    colkey: Optional[int]
+    tile_size: int
     def __init__(self, width: int, height: int, img: Union[int, Image]) -> None: ...
+    @staticmethod
+    def from_tmx(filename: str, img: Union[int, Image]) -> List[Tilemap]: ...
     def set(self, x: int, y: int, data: List[str]) -> None: ...
+    def set_collision(self, tiles: List[Tuple[int, int]]) -> None: ...
+    def is_solid_at(self, x: float, y: float) -> bool: ...
+    def tiles_in_rect(
+        self, x: float, y: float, w: float, h: float
+    ) -> List[Tuple[int, int, Tuple[int, int]]]: ...
+    def sweep_aabb(
+        self, x: float, y: float, w: float, h: float, dx: float, dy: float
+    ) -> Tuple[float, float, bool, bool]: ...
     def clip(
         self,
         x: Optional[float] = None,
@@ -612,12 +888,43 @@ class Tilemap:
     def line(
         self, x1: float, y1: float, x2: float, y2: float, tile: Tuple[int, int]
     ) -> None: ...
+    def line_w(
+        self,
+        x1: float,
+        y1: float,
+        x2: float,
+        y2: float,
+        thickness: float,
+        tile: Tuple[int, int],
+    ) -> None: ...
+    def polyline(
+        self, points: List[Tuple[float, float]], tile: Tuple[int, int]
+    ) -> None: ...
+    def bezier(
+        self,
+        x1: float,
+        y1: float,
+        cx: float,
+        cy: float,
+        x2: float,
+        y2: float,
+        tile: Tuple[int, int],
+    ) -> None: ...
     def rect(
         self, x: float, y: float, w: float, h: float, tile: Tuple[int, int]
     ) -> None: ...
     def rectb(
         self, x: float, y: float, w: float, h: float, tile: Tuple[int, int]
     ) -> None: ...
+    def rectb_w(
+        self,
+        x: float,
+        y: float,
+        w: float,
+        h: float,
+        thickness: float,
+        tile: Tuple[int, int],
+    ) -> None: ...
     def circ(self, x: float, y: float, r: float, tile: Tuple[int, int]) -> None: ...
     def circb(self, x: float, y: float, r: float, tile: Tuple[int, int]) -> None: ...
     def elli(
@@ -659,9 +966,59 @@ class Tilemap:
         tilekey: Optional[Tuple[int, int]] = None,
     ) -> None: ...
 
+# Font class
+class Font:
+    image: Image
+    colkey: int
+    def __init__(
+        self,
+        img: Union[int, Image],
+        glyph_w: int,
+        glyph_h: int,
+        first_char: str,
+        widths: Optional[List[int]] = None,
+    ) -> None: ...
+    def text_width(self, s: str) -> int: ...
+
+# Animation class
+class Animation:
+    img: int
+    colkey: Optional[int]
+    clip: Optional[str]
+    frame_index: int
+    def __init__(self, img: int) -> None: ...
+    def add_clip(
+        self,
+        name: str,
+        frames: List[Tuple[float, float, float, float, int]],
+        *,
+        loop: bool = True,
+    ) -> None: ...
+    def add_grid_clip(
+        self,
+        name: str,
+        u: float,
+        v: float,
+        w: float,
+        h: float,
+        count: int,
+        duration: int,
+        *,
+        loop: bool = True,
+    ) -> None: ...
+    def play(self, name: str) -> None: ...
+    def restart(self) -> None: ...
+    def is_finished(self) -> bool: ...
+    def update(self) -> None: ...
+    def draw(self, x: float, y: float, flip_h: bool = False) -> None: ...
+
 # Channel class
 class Channel:
     gain: int
+    volume: float
+    pan: float
+    speed: float
+    pitch: float
     def play_pos(self) -> Optional[Tuple[int, int]]: ...
     def play(
         self,
@@ -679,6 +1036,8 @@ class Sound:
     volumes: List[int]
     effects: List[int]
     speed: int
+    vibrato_depth: float
+    envelope: Tuple[int, int, float, int]
     def __init__(self) -> None: ...
     def set(
         self,
@@ -692,6 +1051,11 @@ class Sound:
     def set_tones(self, tones: str) -> None: ...
     def set_volumes(self, volumes: str) -> None: ...
     def set_effects(self, effects: str) -> None: ...
+    def mml(self, mml: str) -> None: ...
+    def set_envelope(
+        self, attack: int, decay: int, sustain: float, release: int
+    ) -> None: ...
+    def to_wav(self, filename: str, *, rate: Optional[int] = None) -> None: ...
 
 # Music class
 class Music:
@@ -704,3 +1068,18 @@ class Music:
         seq2: List[int],
         seq3: List[int],
     ) -> None: ...
+    def set_loop_range(self, start: int, end: int, *, ch: Optional[int] = None) -> None: ...
+    def clear_loop_range(self) -> None: ...
+    def to_wav(
+        self,
+        filename: str,
+        *,
+        rate: Optional[int] = None,
+        loops: Optional[int] = None,
+    ) -> None: ...
+
+# Sample class
+class Sample:
+    length: int
+    @staticmethod
+    def from_wav(filename: str) -> Sample: ...
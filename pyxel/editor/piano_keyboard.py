@@ -124,7 +124,7 @@ class PianoKeyboard(Widget):
         ):
             return
         if pyxel.btnp(pyxel.KEY_1):
-            self._preview_tone = (self._preview_tone + 1) % 4
+            self._preview_tone = (self._preview_tone + 1) % 7
         self.note_var = self._mouse_note
         for i, key in enumerate(key_table):
             if pyxel.btn(key):
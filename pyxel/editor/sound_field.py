@@ -11,7 +11,15 @@ from .settings import (
 from .widgets import Widget
 from .widgets.settings import WIDGET_HOLD_TIME, WIDGET_REPEAT_TIME
 
-TONE_KEY_TABLE = [pyxel.KEY_T, pyxel.KEY_S, pyxel.KEY_P, pyxel.KEY_N]
+TONE_KEY_TABLE = [
+    pyxel.KEY_T,
+    pyxel.KEY_S,
+    pyxel.KEY_P,
+    pyxel.KEY_N,
+    pyxel.KEY_W,
+    pyxel.KEY_E,
+    pyxel.KEY_M,
+]
 EFFECT_KEY_TABLE = [pyxel.KEY_N, pyxel.KEY_S, pyxel.KEY_V, pyxel.KEY_F]
 
 
@@ -49,7 +57,7 @@ class SoundField(Widget):
     def __on_mouse_hover(self, x, y):
         x, y = self._screen_to_view(x, y)
         if y == 0:
-            self.help_message_var = "TONE:T/S/P/N/BS/DEL"
+            self.help_message_var = "TONE:T/S/P/N/W/E/M/BS/DEL"
         elif y == 1:
             self.help_message_var = "VOLUME:0-7/BS/DEL"
         elif y == 2:
@@ -68,7 +76,7 @@ class SoundField(Widget):
             return
         value = None
         if cursor_y == 1:
-            for i in range(4):
+            for i in range(len(TONE_KEY_TABLE)):
                 if pyxel.btnp(TONE_KEY_TABLE[i], WIDGET_HOLD_TIME, WIDGET_REPEAT_TIME):
                     value = i
                     break
@@ -108,7 +116,7 @@ class SoundField(Widget):
 
         # Draw field data
         data_str = []
-        data_str.append("".join(["TSPN"[v] for v in self.get_field(1)]))
+        data_str.append("".join(["TSPNWEM"[v] for v in self.get_field(1)]))
         data_str.append("".join([str(v) for v in self.get_field(2)]))
         data_str.append("".join(["NSVF"[v] for v in self.get_field(3)]))
         for i in range(3):
@@ -0,0 +1,62 @@
+import pyxel
+
+WARP_FRAMES = 40
+WARP_IMAGE = 2
+
+
+class App:
+    def __init__(self):
+        pyxel.init(160, 120, title="Screen Warp")
+        pyxel.image(0).load(0, 0, "assets/pyxel_logo_38x16.png")
+        self.scene = 0
+        self.warp_frame = None
+        pyxel.run(self.update, self.draw)
+
+    def update(self):
+        if pyxel.btnp(pyxel.KEY_Q):
+            pyxel.quit()
+        if self.warp_frame is None and pyxel.btnp(pyxel.KEY_SPACE):
+            self.warp_frame = 0
+        elif self.warp_frame is not None:
+            self.warp_frame += 1
+            if self.warp_frame == WARP_FRAMES // 2:
+                self.scene = 1 - self.scene
+            elif self.warp_frame >= WARP_FRAMES:
+                self.warp_frame = None
+
+    def draw_scene(self):
+        if self.scene == 0:
+            pyxel.cls(1)
+            for i in range(8):
+                pyxel.circ(20 + i * 17, 60 + pyxel.sin(i * 45) * 20, 6, 8 + i)
+            pyxel.text(42, 100, "Press SPACE to warp", 7)
+        else:
+            pyxel.cls(5)
+            for i in range(0, 160, 16):
+                pyxel.rect(i, 0, 8, 120, 6)
+            pyxel.blt(61, 52, 0, 0, 0, 38, 16)
+            pyxel.text(42, 100, "Press SPACE to warp", 0)
+
+    def draw(self):
+        self.draw_scene()
+        if self.warp_frame is None:
+            return
+
+        # Capture the finished scene, then redraw it rotated and scaled
+        pyxel.screen_to_image(WARP_IMAGE)
+        t = abs(self.warp_frame - WARP_FRAMES / 2) / (WARP_FRAMES / 2)
+        pyxel.cls(0)
+        pyxel.blt(
+            0,
+            0,
+            WARP_IMAGE,
+            0,
+            0,
+            pyxel.width,
+            pyxel.height,
+            rotate=(1 - t) * 360,
+            scale=max(t, 0.05),
+        )
+
+
+App()